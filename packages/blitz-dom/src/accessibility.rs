@@ -1,4 +1,6 @@
-use crate::{BaseDocument, Node as BlitzDomNode, local_name};
+use crate::{
+    BaseDocument, MeterValues, Node as BlitzDomNode, ProgressValues, RangeBounds, local_name,
+};
 use accesskit::{Node as AccessKitNode, NodeId, Role, Tree, TreeUpdate};
 
 impl BaseDocument {
@@ -57,12 +59,37 @@ impl BaseDocument {
                     match ty {
                         "number" => Role::NumberInput,
                         "checkbox" => Role::CheckBox,
+                        "range" => Role::Slider,
                         _ => Role::TextInput,
                     }
                 }
+                "progress" => Role::ProgressIndicator,
+                "meter" => Role::Meter,
                 _ => Role::Unknown,
             };
 
+            if let Some(range_data) = element_data.range_input_data() {
+                let bounds = RangeBounds::from_element(element_data);
+                builder.set_numeric_value(range_data.value);
+                builder.set_min_numeric_value(bounds.min);
+                builder.set_max_numeric_value(bounds.max);
+                if let Some(step) = bounds.step {
+                    builder.set_numeric_value_step(step);
+                }
+            } else if role == Role::ProgressIndicator {
+                let progress = ProgressValues::from_element(element_data);
+                if let Some(value) = progress.value {
+                    builder.set_numeric_value(value);
+                    builder.set_min_numeric_value(0.0);
+                    builder.set_max_numeric_value(progress.max);
+                }
+            } else if role == Role::Meter {
+                let meter = MeterValues::from_element(element_data);
+                builder.set_numeric_value(meter.value);
+                builder.set_min_numeric_value(meter.min);
+                builder.set_max_numeric_value(meter.max);
+            }
+
             builder.set_role(role);
             builder.set_html_tag(name);
         } else if node.is_text_node() {
@@ -5,6 +5,7 @@ use crate::layout::construct::{
     build_inline_layout_into, collect_layout_children,
 };
 use crate::layout::damage::{ALL_DAMAGE, CONSTRUCT_BOX, CONSTRUCT_DESCENDENT, CONSTRUCT_FC};
+use crate::layout::paged_media::{PAGED_MEDIA_UA_CSS, Pagination, expose_paged_media_properties};
 use crate::mutator::ViewportMut;
 use crate::net::{Resource, StylesheetLoader};
 use crate::node::{ImageData, NodeFlags, RasterImageData, SpecialElementData, Status, TextBrush};
@@ -45,7 +46,7 @@ use style::media_queries::MediaType;
 use style::properties::ComputedValues;
 use style::properties::style_structs::Font;
 use style::queries::values::PrefersColorScheme;
-use style::selector_parser::ServoElementSnapshot;
+use style::selector_parser::{RestyleDamage, ServoElementSnapshot};
 use style::servo_arc::Arc as ServoArc;
 use style::values::GenericAtomIdent;
 use style::values::computed::Overflow;
@@ -68,9 +69,6 @@ thread_local! {
     static FONT_CTX: RefCell<Option<Box<FontContext>>> = const { RefCell::new(None) };
 }
 
-#[cfg(feature = "incremental")]
-use style::selector_parser::RestyleDamage;
-
 /// Abstraction over wrappers around [`BaseDocument`] to allow for them all to
 /// be driven by [`blitz-shell`](https://docs.rs/blitz-shell)
 pub trait Document: Deref<Target = BaseDocument> + DerefMut + 'static {
@@ -108,6 +106,9 @@ pub struct BaseDocument {
     pub(crate) viewport: Viewport,
     // Scroll within our viewport
     pub(crate) viewport_scroll: crate::Point<f64>,
+    /// Nodes with `position: sticky`, and nodes with `position: fixed` within inline formatting
+    /// contexts (whose sticky offset must be updated when scrolling)
+    pub(crate) sticky_nodes: Vec<usize>,
 
     /// A slab-backed tree of nodes
     ///
@@ -138,6 +139,13 @@ pub struct BaseDocument {
     pub(crate) active_node_id: Option<usize>,
     /// The node which recieved a mousedown event (if any)
     pub(crate) mousedown_node_id: Option<usize>,
+    /// The node which is the target of the URL fragment (if any)
+    pub(crate) target_node_id: Option<usize>,
+    /// Whether the most recent user interaction was with the keyboard (rather than the mouse).
+    /// Used to determine whether focus should be visible (`:focus-visible`)
+    pub(crate) keyboard_modality: bool,
+    /// The \<select\> element whose drop-down popup is open (if any)
+    pub(crate) open_select_id: Option<usize>,
 
     /// Whether there are active CSS animations/transitions (so we should re-render every frame)
     pub(crate) has_active_animations: bool,
@@ -148,13 +156,22 @@ pub struct BaseDocument {
     pub(crate) nodes_to_id: HashMap<String, usize>,
     /// Map of `<style>` and `<link>` node IDs to their associated stylesheet
     pub(crate) nodes_to_stylesheet: BTreeMap<usize, DocumentStyleSheet>,
+    /// Map of `<style>` and `<link>` node IDs within shadow trees to the ID of the shadow root
+    /// their stylesheet is scoped to
+    pub(crate) scoped_stylesheets: HashMap<usize, usize>,
     /// Stylesheets added by the useragent
     /// where the key is the hashed CSS
     pub(crate) ua_stylesheets: HashMap<String, DocumentStyleSheet>,
+    /// The state of the document while it is paginated (see `BaseDocument::paginate`)
+    pub(crate) pagination: Option<Pagination>,
+    /// Whether styles are resolved for `print` media (rather than `screen` media)
+    pub(crate) print_media: bool,
     /// Map from form control node ID's to their associated forms node ID's
     pub(crate) controls_to_form: HashMap<usize, usize>,
     /// Set of changed nodes for updating the accessibility tree
     pub(crate) changed_nodes: HashSet<usize>,
+    /// Set of nodes whose painted output may have changed since paint damage was last taken
+    pub(crate) paint_damage: HashSet<usize>,
     /// Set of changed nodes for updating the accessibility tree
     pub(crate) deferred_construction_nodes: Vec<ConstructionTask>,
 
@@ -170,14 +187,21 @@ pub struct BaseDocument {
     pub html_parser_provider: Arc<dyn HtmlParserProvider>,
 }
 
-pub(crate) fn make_device(viewport: &Viewport, font_ctx: Arc<Mutex<FontContext>>) -> Device {
+pub(crate) fn make_device(
+    viewport: &Viewport,
+    print_media: bool,
+    font_ctx: Arc<Mutex<FontContext>>,
+) -> Device {
     let width = viewport.window_size.0 as f32 / viewport.scale();
     let height = viewport.window_size.1 as f32 / viewport.scale();
     let viewport_size = euclid::Size2D::new(width, height);
     let device_pixel_ratio = euclid::Scale::new(viewport.scale());
 
     Device::new(
-        MediaType::screen(),
+        match print_media {
+            true => MediaType::print(),
+            false => MediaType::screen(),
+        },
         selectors::matching::QuirksMode::NoQuirks,
         viewport_size,
         device_pixel_ratio,
@@ -221,7 +245,7 @@ impl BaseDocument {
         let font_ctx = Arc::new(Mutex::new(font_ctx));
 
         let viewport = config.viewport.unwrap_or_default();
-        let device = make_device(&viewport, font_ctx.clone());
+        let device = make_device(&viewport, false, font_ctx.clone());
         let stylist = Stylist::new(device, QuirksMode::NoQuirks);
         let snapshots = SnapshotMap::new();
         let nodes = Box::new(Slab::new());
@@ -234,6 +258,7 @@ impl BaseDocument {
         style_config::set_bool("layout.legacy_layout", true);
         style_config::set_bool("layout.unimplemented", true);
         style_config::set_bool("layout.columns.enabled", true);
+        style_config::set_bool("layout.writing-mode.enabled", true);
 
         let base_url = config
             .base_url
@@ -264,9 +289,13 @@ impl BaseDocument {
             viewport,
             devtool_settings: DevtoolSettings::default(),
             viewport_scroll: crate::Point::ZERO,
+            sticky_nodes: Vec::new(),
             url: base_url,
             ua_stylesheets: HashMap::new(),
+            pagination: None,
+            print_media: false,
             nodes_to_stylesheet: BTreeMap::new(),
+            scoped_stylesheets: HashMap::new(),
             font_ctx,
             layout_ctx: parley::LayoutContext::new(),
 
@@ -274,9 +303,13 @@ impl BaseDocument {
             focus_node_id: None,
             active_node_id: None,
             mousedown_node_id: None,
+            target_node_id: None,
+            keyboard_modality: false,
+            open_select_id: None,
             has_active_animations: false,
             has_canvas: false,
             changed_nodes: HashSet::new(),
+            paint_damage: HashSet::new(),
             deferred_construction_nodes: Vec::new(),
             controls_to_form: HashMap::new(),
             net_provider,
@@ -297,6 +330,7 @@ impl BaseDocument {
             }
             None => doc.add_user_agent_stylesheet(DEFAULT_CSS),
         }
+        doc.add_user_agent_stylesheet(PAGED_MEDIA_UA_CSS);
 
         // Stylo data on the root node container is needed to render the node
         let stylo_element_data = StyloElementData {
@@ -337,6 +371,7 @@ impl BaseDocument {
     /// Set base url for resolving linked resources (stylesheets, images, fonts, etc)
     pub fn set_base_url(&mut self, url: &str) {
         self.url = DocumentUrl::from(Url::parse(url).unwrap());
+        self.update_target_node();
     }
 
     pub fn guard(&self) -> &SharedRwLock {
@@ -428,18 +463,21 @@ impl BaseDocument {
         *is_checked
     }
 
-    pub fn toggle_radio(&mut self, radio_set_name: String, target_radio_id: usize) {
-        for i in 0..self.nodes.len() {
-            let node = &mut self.nodes[i];
-            if let Some(node_data) = node.data.downcast_element_mut() {
-                if node_data.attr(local_name!("name")) == Some(&radio_set_name) {
-                    let was_clicked = i == target_radio_id;
-                    let Some(is_checked) = node_data.checkbox_input_checked_mut() else {
-                        continue;
-                    };
-                    *is_checked = was_clicked;
-                }
-            }
+    /// Check `target_radio_id` and uncheck the other radio buttons in its group
+    pub fn toggle_radio(&mut self, target_radio_id: usize) {
+        let mut radio_ids = self.radio_group_members(target_radio_id);
+        if radio_ids.is_empty() {
+            // A radio button without a name is in a group of its own
+            radio_ids.push(target_radio_id);
+        }
+        for id in radio_ids {
+            let Some(is_checked) = self.nodes[id]
+                .element_data_mut()
+                .and_then(|el| el.checkbox_input_checked_mut())
+            else {
+                continue;
+            };
+            *is_checked = id == target_radio_id;
         }
     }
 
@@ -504,9 +542,15 @@ impl BaseDocument {
     pub fn deep_clone_node(&mut self, node_id: usize) -> usize {
         // Load existing node
         let node = &self.nodes[node_id];
-        let data = node.data.clone();
+        let mut data = node.data.clone();
         let children = node.children.clone();
 
+        // Shadow trees and slot assignments are not cloned
+        if let Some(element) = data.downcast_element_mut() {
+            element.shadow_root = None;
+            element.assigned_nodes.clear();
+        }
+
         // Create new node
         let new_node_id = self.create_node(data);
 
@@ -582,8 +626,9 @@ impl BaseDocument {
     }
 
     pub fn make_stylesheet(&self, css: impl AsRef<str>, origin: Origin) -> DocumentStyleSheet {
+        let css = expose_paged_media_properties(css.as_ref());
         let data = Stylesheet::from_str(
-            css.as_ref(),
+            &css,
             self.url.url_extra_data(),
             origin,
             ServoArc::new(self.guard.wrap(MediaList::empty())),
@@ -606,14 +651,20 @@ impl BaseDocument {
     pub fn add_stylesheet_for_node(&mut self, stylesheet: DocumentStyleSheet, node_id: usize) {
         let old = self.nodes_to_stylesheet.insert(node_id, stylesheet.clone());
 
-        if let Some(old) = old {
-            self.stylist.remove_stylesheet(old, &self.guard.read())
-        }
-
         // Store data on element
         let element = &mut self.nodes[node_id].element_data_mut().unwrap();
         element.special_data = SpecialElementData::Stylesheet(stylesheet.clone());
 
+        // Stylesheets within a shadow tree only apply to that shadow tree
+        if let Some(shadow_root_id) = self.nodes[node_id].containing_shadow_root_id() {
+            self.add_scoped_stylesheet_for_node(shadow_root_id, stylesheet, old, node_id);
+            return;
+        }
+
+        if let Some(old) = old {
+            self.stylist.remove_stylesheet(old, &self.guard.read())
+        }
+
         // TODO: Nodes could potentially get reused so ordering by node_id might be wrong.
         let insertion_point = self
             .nodes_to_stylesheet
@@ -662,7 +713,21 @@ impl BaseDocument {
                                 ImageData::Raster(RasterImageData::new(width, height, image_data))
                         }
                     }
+                    ImageType::ListStyleImage => {
+                        if let Some(image) = node
+                            .element_data_mut()
+                            .and_then(|el| el.list_style_image.as_mut())
+                        {
+                            image.status = Status::Ok;
+                            image.image =
+                                ImageData::Raster(RasterImageData::new(width, height, image_data))
+                        }
+                    }
                 }
+
+                // Loading a background or list marker image isn't reported as damage (it doesn't
+                // affect layout), so the node is recorded as needing to be repainted here
+                self.invalidate_paint(node_id);
             }
             #[cfg(feature = "svg")]
             Resource::Svg(node_id, kind, tree) => {
@@ -686,7 +751,20 @@ impl BaseDocument {
                             bg_image.image = ImageData::Svg(tree);
                         }
                     }
+                    ImageType::ListStyleImage => {
+                        if let Some(image) = node
+                            .element_data_mut()
+                            .and_then(|el| el.list_style_image.as_mut())
+                        {
+                            image.status = Status::Ok;
+                            image.image = ImageData::Svg(tree);
+                        }
+                    }
                 }
+
+                // Loading a background or list marker image isn't reported as damage (it doesn't
+                // affect layout), so the node is recorded as needing to be repainted here
+                self.invalidate_paint(node_id);
             }
             Resource::Font(bytes) => {
                 let font = Blob::new(Arc::new(bytes));
@@ -810,10 +888,12 @@ impl BaseDocument {
         self.resolve_stylist(current_time_for_animations);
         timer.record_time("style");
 
+        // Resolve counters and quotes in ::before and ::after content
+        self.resolve_generated_content();
+        timer.record_time("content");
+
         // Propagate damage flags (from mutation and restyles) up and down the tree
-        #[cfg(feature = "incremental")]
         self.propagate_damage_flags(root_node_id, RestyleDamage::empty());
-        #[cfg(feature = "incremental")]
         timer.record_time("damage");
 
         // Fix up tree for layout (insert anonymous blocks as necessary, etc)
@@ -832,13 +912,10 @@ impl BaseDocument {
         timer.record_time("layout");
 
         // Clear all damage
-        #[cfg(feature = "incremental")]
-        {
-            for (_, node) in self.nodes.iter_mut() {
-                node.clear_damage_mut();
-            }
-            timer.record_time("c_damage");
+        for (_, node) in self.nodes.iter_mut() {
+            node.clear_damage_mut();
         }
+        timer.record_time("c_damage");
 
         timer.print_times("Resolve: ");
     }
@@ -853,6 +930,15 @@ impl BaseDocument {
             return None;
         }
 
+        // An open drop-down popup is painted above all other content
+        if let Some(popup) = self.select_popup().filter(|popup| popup.contains(x, y)) {
+            return Some(HitResult {
+                node_id: popup.select_id,
+                x: x - popup.x,
+                y: y - popup.y,
+            });
+        }
+
         self.root_element().hit(x, y)
     }
 
@@ -867,6 +953,9 @@ impl BaseDocument {
     pub fn clear_focus(&mut self) {
         if let Some(id) = self.focus_node_id {
             self.snapshot_node_and(id, |node| node.blur());
+            for ancestor_id in self.node_chain(id) {
+                self.snapshot_node_and(ancestor_id, |node| node.unfocus_within());
+            }
             self.focus_node_id = None;
         }
     }
@@ -886,14 +975,63 @@ impl BaseDocument {
             self.snapshot_node_and(id, |node| node.blur());
         }
 
-        // Focus the new node
-        self.snapshot_node_and(focus_node_id, |node| node.focus());
+        // Focus the new node. Focus is always visible for text inputs, and otherwise only when
+        // focus was moved using the keyboard.
+        let focus_visible = self.keyboard_modality
+            || self.nodes[focus_node_id]
+                .element_data()
+                .is_some_and(|el| el.text_input_data().is_some());
+        self.snapshot_node_and(focus_node_id, |node| node.focus(focus_visible));
+
+        // Update :focus-within for ancestors of the old and new nodes
+        let old_chain = self
+            .focus_node_id
+            .map(|id| self.node_chain(id))
+            .unwrap_or_default();
+        let new_chain = self.node_chain(focus_node_id);
+        for &id in old_chain.iter().filter(|id| !new_chain.contains(id)) {
+            self.snapshot_node_and(id, |node| node.unfocus_within());
+        }
+        for &id in new_chain.iter().filter(|id| !old_chain.contains(id)) {
+            self.snapshot_node_and(id, |node| node.focus_within());
+        }
 
         self.focus_node_id = Some(focus_node_id);
 
         true
     }
 
+    /// Update the element matching `:target` to match the fragment of the document's URL
+    ///
+    /// <https://html.spec.whatwg.org/multipage/browsing-the-web.html#find-a-potential-indicated-element>
+    pub fn update_target_node(&mut self) {
+        let target_node_id = self
+            .url
+            .fragment()
+            .filter(|fragment| !fragment.is_empty())
+            .and_then(|fragment| {
+                let decoded = percent_encoding::percent_decode_str(fragment).decode_utf8_lossy();
+                self.nodes_to_id.get(&*decoded).copied().or_else(|| {
+                    TreeTraverser::new(self).find(|id| {
+                        let node = &self.nodes[*id];
+                        node.data.is_element_with_tag_name(&local_name!("a"))
+                            && node.attr(local_name!("name")) == Some(&*decoded)
+                    })
+                })
+            });
+
+        if target_node_id == self.target_node_id {
+            return;
+        }
+        if let Some(id) = self.target_node_id.filter(|id| self.nodes.contains(*id)) {
+            self.snapshot_node_and(id, |node| node.untarget());
+        }
+        if let Some(id) = target_node_id {
+            self.snapshot_node_and(id, |node| node.target());
+        }
+        self.target_node_id = target_node_id;
+    }
+
     pub fn active_node(&mut self) -> bool {
         let Some(hover_node_id) = self.get_hover_node_id() else {
             return false;
@@ -973,7 +1111,11 @@ impl BaseDocument {
     pub fn set_viewport(&mut self, viewport: Viewport) {
         let scale_has_changed = viewport.scale_f64() != self.viewport.scale_f64();
         self.viewport = viewport;
-        self.set_stylist_device(make_device(&self.viewport, self.font_ctx.clone()));
+        self.set_stylist_device(make_device(
+            &self.viewport,
+            self.print_media,
+            self.font_ctx.clone(),
+        ));
         self.scroll_viewport_by(0.0, 0.0); // Clamp scroll offset
 
         if scale_has_changed {
@@ -1046,6 +1188,12 @@ impl BaseDocument {
                 let mut anonymous_block: Option<usize> = None;
                 collect_layout_children(doc, node_id, &mut layout_children, &mut anonymous_block);
 
+                // The node paints its layout children, so it must be repainted if they changed
+                // (which includes anonymous blocks being recreated)
+                if doc.nodes[node_id].layout_children.borrow().as_ref() != Some(&layout_children) {
+                    doc.paint_damage.insert(node_id);
+                }
+
                 // Recurse into newly collected layout children
                 for child_id in layout_children.iter().copied() {
                     resolve_layout_children_recursive(doc, child_id);
@@ -1178,8 +1326,12 @@ impl BaseDocument {
         // println!("\n\nRESOLVE LAYOUT\n===========\n");
 
         taffy::compute_root_layout(self, root_element_id, available_space);
+        self.resolve_inline_absolute_positions(root_element_id.into());
         taffy::round_layout(self, root_element_id);
 
+        self.collect_sticky_nodes();
+        self.update_sticky_offsets();
+
         // println!("\n\n");
         // taffy::print_tree(self, root_node_id)
     }
@@ -1227,6 +1379,11 @@ impl BaseDocument {
     /// Will bubble scrolling up to parent node once it can no longer scroll further
     /// If we're already at the root node, bubbles scrolling up to the viewport
     pub fn scroll_node_by_has_changed(&mut self, node_id: usize, x: f64, y: f64) -> bool {
+        // List boxes and drop-down popups scroll by whole options
+        if self.scroll_select_by(node_id, y) == Some(true) {
+            return true;
+        }
+
         let Some(node) = self.nodes.get_mut(node_id) else {
             return false;
         };
@@ -1283,9 +1440,14 @@ impl BaseDocument {
         }
 
         let has_changed = node.scroll_offset != initial;
+        let parent = node.parent;
+        if has_changed {
+            self.paint_damage.insert(node_id);
+            self.update_sticky_offsets();
+        }
 
         if bubble_x != 0.0 || bubble_y != 0.0 {
-            if let Some(parent) = node.parent {
+            if let Some(parent) = parent {
                 return self.scroll_node_by_has_changed(parent, bubble_x, bubble_y) | has_changed;
             } else {
                 return self.scroll_viewport_by_has_changed(bubble_x, bubble_y) | has_changed;
@@ -1316,7 +1478,11 @@ impl BaseDocument {
             f64::min(new_scroll.1, content_size.height as f64 - window_height),
         );
 
-        self.viewport_scroll != initial
+        let has_changed = self.viewport_scroll != initial;
+        if has_changed {
+            self.update_sticky_offsets();
+        }
+        has_changed
     }
 
     pub fn viewport_scroll(&self) -> crate::Point<f64> {
@@ -1325,6 +1491,7 @@ impl BaseDocument {
 
     pub fn set_viewport_scroll(&mut self, scroll: crate::Point<f64>) {
         self.viewport_scroll = scroll;
+        self.update_sticky_offsets();
     }
 
     pub fn find_title_node(&self) -> Option<&Node> {
@@ -59,12 +59,16 @@ impl<'doc, Handler: EventHandler> EventDriver<'doc, Handler> {
                 hover_node_id = self.doc().hover_node_id;
             }
             UiEvent::MouseDown(_) => {
+                self.doc_mut().keyboard_modality = false;
                 self.doc_mut().active_node();
                 self.doc_mut().set_mousedown_node_id(hover_node_id);
             }
             UiEvent::MouseUp(_) => {
                 self.doc_mut().unactive_node();
             }
+            UiEvent::KeyDown(_) => {
+                self.doc_mut().keyboard_modality = true;
+            }
             _ => {}
         };
 
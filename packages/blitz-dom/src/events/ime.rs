@@ -26,6 +26,11 @@ pub(crate) fn handle_ime_event<F: FnMut(DomEvent)>(
                 BlitzImeEvent::Commit(text) => {
                     driver.insert_or_replace_selection(&text);
                     let value = input_data.editor.raw_text().to_string();
+                    drop(font_ctx);
+                    if let Some(element) = doc.nodes[node_id].element_data_mut() {
+                        element.user_validity = true;
+                    }
+                    doc.update_form_control_state(node_id);
                     dispatch_event(DomEvent::new(
                         node_id,
                         DomEventData::Input(BlitzInputEvent { value }),
@@ -37,6 +37,16 @@ pub(crate) fn handle_keypress<F: FnMut(DomEvent)>(
             return;
         };
 
+        if element_data.select_data().is_some() {
+            doc.handle_select_keypress(node_id, &event, dispatch_event);
+            return;
+        }
+
+        if element_data.range_input_data().is_some() {
+            doc.handle_range_keypress(node_id, &event, dispatch_event);
+            return;
+        }
+
         if let Some(input_data) = element_data.text_input_data_mut() {
             let generated_event = apply_keypress_event(
                 input_data,
@@ -50,6 +60,8 @@ pub(crate) fn handle_keypress<F: FnMut(DomEvent)>(
                 match generated_event {
                     GeneratedEvent::Input => {
                         let value = input_data.editor.raw_text().to_string();
+                        element_data.user_validity = true;
+                        doc.update_form_control_state(node_id);
                         dispatch_event(DomEvent::new(
                             node_id,
                             DomEventData::Input(BlitzInputEvent { value }),
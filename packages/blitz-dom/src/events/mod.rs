@@ -27,13 +27,14 @@ pub(crate) fn handle_dom_event<F: FnMut(DomEvent)>(
                 mouse_event.x,
                 mouse_event.y,
                 mouse_event.buttons,
+                dispatch_event,
             );
             if changed {
                 doc.shell_provider.request_redraw();
             }
         }
         DomEventData::MouseDown(event) => {
-            handle_mousedown(doc, target_node_id, event.x, event.y);
+            handle_mousedown(doc, target_node_id, event.x, event.y, dispatch_event);
         }
         DomEventData::MouseUp(event) => {
             handle_mouseup(doc, target_node_id, event, dispatch_event);
@@ -56,5 +57,8 @@ pub(crate) fn handle_dom_event<F: FnMut(DomEvent)>(
         DomEventData::Input(_) => {
             // Do nothing (no default action)
         }
+        DomEventData::Change(_) => {
+            // Do nothing (no default action)
+        }
     }
 }
@@ -9,14 +9,23 @@ use markup5ever::local_name;
 
 use crate::{BaseDocument, node::SpecialElementData};
 
-pub(crate) fn handle_mousemove(
+pub(crate) fn handle_mousemove<F: FnMut(DomEvent)>(
     doc: &mut BaseDocument,
     target: usize,
     x: f32,
     y: f32,
     buttons: MouseEventButtons,
+    dispatch_event: F,
 ) -> bool {
     let mut changed = doc.set_hover_to(x, y);
+    changed |= doc.handle_select_mousemove(x, y);
+
+    // Range inputs continue to be dragged while the pointer is outside of them
+    if let Some(node_id) = doc.mousedown_node_id {
+        if buttons.contains(MouseEventButtons::Primary) {
+            changed |= doc.update_range_drag(node_id, x, dispatch_event);
+        }
+    }
 
     let Some(hit) = doc.hit(x, y) else {
         return changed;
@@ -60,7 +69,13 @@ pub(crate) fn handle_mousemove(
     changed
 }
 
-pub(crate) fn handle_mousedown(doc: &mut BaseDocument, target: usize, x: f32, y: f32) {
+pub(crate) fn handle_mousedown<F: FnMut(DomEvent)>(
+    doc: &mut BaseDocument,
+    target: usize,
+    x: f32,
+    y: f32,
+    dispatch_event: F,
+) {
     let Some(hit) = doc.hit(x, y) else {
         return;
     };
@@ -92,6 +107,8 @@ pub(crate) fn handle_mousedown(doc: &mut BaseDocument, target: usize, x: f32, y:
             .move_to_point(x as f32, y as f32);
 
         doc.set_focus_to(hit.node_id);
+    } else if el.range_input_data().is_some() {
+        doc.start_range_drag(target, x, dispatch_event);
     }
 }
 
@@ -113,6 +130,10 @@ pub(crate) fn handle_mouseup<F: FnMut(DomEvent)>(
         return;
     }
 
+    if let Some(node_id) = doc.mousedown_node_id {
+        doc.end_range_drag(node_id, &mut dispatch_event);
+    }
+
     // Determine whether to dispatch a click event
     let do_click = true;
     // let do_click = doc.mouse_down_node.is_some_and(|mouse_down_id| {
@@ -134,6 +155,13 @@ pub(crate) fn handle_click<F: FnMut(DomEvent)>(
     event: &BlitzMouseButtonEvent,
     mut dispatch_event: F,
 ) {
+    // Clicking anywhere outside of an open drop-down popup closes it
+    if let Some(open_select_id) = doc.open_select_id() {
+        if open_select_id != target {
+            doc.close_select_popup(open_select_id);
+        }
+    }
+
     let mut maybe_node_id = Some(target);
     while let Some(node_id) = maybe_node_id {
         let maybe_element = {
@@ -158,6 +186,8 @@ pub(crate) fn handle_click<F: FnMut(DomEvent)>(
         match el.name.local {
             local_name!("input") if el.attr(local_name!("type")) == Some("checkbox") => {
                 let is_checked = BaseDocument::toggle_checkbox(el);
+                el.user_validity = true;
+                doc.update_form_control_state(node_id);
                 let value = is_checked.to_string();
                 dispatch_event(DomEvent::new(
                     node_id,
@@ -167,8 +197,12 @@ pub(crate) fn handle_click<F: FnMut(DomEvent)>(
                 return;
             }
             local_name!("input") if el.attr(local_name!("type")) == Some("radio") => {
-                let radio_set = el.attr(local_name!("name")).unwrap().to_string();
-                BaseDocument::toggle_radio(doc, radio_set, node_id);
+                BaseDocument::toggle_radio(doc, node_id);
+                if let Some(el) = doc.nodes[node_id].element_data_mut() {
+                    el.user_validity = true;
+                }
+                // Also updates the other radio buttons in the same group
+                doc.update_form_control_state(node_id);
 
                 // TODO: make input event conditional on value actually changing
                 let value = String::from("true");
@@ -181,6 +215,10 @@ pub(crate) fn handle_click<F: FnMut(DomEvent)>(
 
                 return;
             }
+            local_name!("select") => {
+                doc.handle_select_click(node_id, event.x, event.y, event.mods, dispatch_event);
+                return;
+            }
             // Clicking labels triggers click, and possibly input event, of associated input
             local_name!("label") => {
                 if let Some(target_node_id) = doc.label_bound_input_element(node_id).map(|n| n.id) {
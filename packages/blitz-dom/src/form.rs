@@ -267,11 +267,27 @@ fn construct_entry_list(doc: &BaseDocument, form_id: usize, submitter_id: usize)
             continue;
         };
 
-        // TODO: If the field element is a select element,
+        // If the field element is a select element,
         //  then for each option element in the select element's
         //  list of options whose selectedness is true and that is not disabled,
         //  create an entry with name and the value of the option element,
         //  and append it to entry list.
+        if element.name.local == local_name!("select") {
+            match element.select_data() {
+                Some(data) => {
+                    for option in data.options.iter().filter(|o| o.selected && !o.disabled) {
+                        create_entry(name, option.value.as_str().into());
+                    }
+                }
+                // The select's options haven't been collected (e.g. it isn't rendered)
+                None => {
+                    if !doc.select_list_of_options(control_id).is_empty() {
+                        create_entry(name, doc.select_value(control_id).as_str().into());
+                    }
+                }
+            }
+            continue;
+        }
 
         // Otherwise, if the field element is an input element whose type attribute is in the Checkbox state or the Radio Button state, then:
         if element.name.local == local_name!("input")
@@ -315,6 +331,8 @@ fn construct_entry_list(doc: &BaseDocument, form_id: usize, submitter_id: usize)
         // Otherwise, create an entry with name and the value of the field element, and append it to entry list.
         else if let Some(text) = element.text_input_data() {
             create_entry(name, text.editor.text().to_string().as_str().into());
+        } else if let Some(range) = element.range_input_data() {
+            create_entry(name, range.value.to_string().as_str().into());
         } else if let Some(value) = element.attr(local_name!("value")) {
             create_entry(name, value.into());
         }
@@ -1,4 +1,5 @@
 use core::str;
+use std::borrow::Cow;
 use std::sync::Arc;
 
 use markup5ever::{QualName, local_name, ns};
@@ -11,7 +12,7 @@ use style::{
     selector_parser::RestyleDamage,
     shared_lock::StylesheetGuards,
     values::{
-        computed::{Content, ContentItem, Display},
+        computed::Display,
         specified::box_::{DisplayInside, DisplayOutside},
     },
 };
@@ -20,13 +21,17 @@ use crate::{
     BaseDocument, ElementData, Node, NodeData,
     layout::damage::{CONSTRUCT_BOX, CONSTRUCT_DESCENDENT, CONSTRUCT_FC},
     node::{
-        ListItemLayout, ListItemLayoutPosition, Marker, NodeFlags, NodeKind, SpecialElementData,
-        TextBrush, TextInputData, TextLayout,
+        ListItemLayout, ListItemLayoutPosition, Marker, NodeFlags, NodeKind, RangeInputData,
+        SpecialElementData, TextBrush, TextInputData, TextLayout,
     },
-    qual_name, stylo_to_parley,
+    qual_name,
+    stylo_to_parley::{self, stylo},
 };
 
-use super::{damage::ALL_DAMAGE, list::collect_list_item_children, table::build_table_context};
+use super::{
+    damage::ALL_DAMAGE, generated_content::build_generated_content, select::create_select,
+    table::build_table_context,
+};
 
 const DUMMY_NAME: QualName = qual_name!("div", html);
 
@@ -54,14 +59,14 @@ fn push_children_and_pseudos(layout_children: &mut Vec<usize>, node: &Node) {
     if let Some(before) = node.before {
         layout_children.push(before);
     }
-    layout_children.extend_from_slice(&node.children);
+    layout_children.extend_from_slice(node.flat_tree_children());
     if let Some(after) = node.after {
         layout_children.push(after);
     }
 }
 
 /// Convert a relative line height to an absolute one
-fn resolve_line_height(line_height: parley::LineHeight, font_size: f32) -> f32 {
+pub(super) fn resolve_line_height(line_height: parley::LineHeight, font_size: f32) -> f32 {
     match line_height {
         parley::LineHeight::FontSizeRelative(relative) => relative * font_size,
         parley::LineHeight::Absolute(absolute) => absolute,
@@ -106,9 +111,24 @@ pub(crate) fn collect_layout_children(
             } else if matches!(type_attr, Some("checkbox" | "radio")) {
                 create_checkbox_input(doc, container_node_id);
                 return;
+            } else if type_attr == Some("range") {
+                create_range_input(doc, container_node_id);
+                return;
             }
         }
 
+        // Progress bars and meters are painted from their attributes. Their fallback content
+        // is not rendered.
+        if matches!(tag_name, "progress" | "meter") {
+            return;
+        }
+
+        // Selects are laid out as a single box. Their options are painted from the select's data.
+        if tag_name == "select" {
+            create_select(doc, container_node_id);
+            return;
+        }
+
         #[cfg(feature = "svg")]
         if matches!(tag_name, "svg") {
             let mut outer_html = doc.get_node(container_node_id).unwrap().outer_html();
@@ -141,25 +161,12 @@ pub(crate) fn collect_layout_children(
             };
             return;
         }
-
-        //Only ol tags have start and reversed attributes
-        let (mut index, reversed) = if tag_name == "ol" {
-            (
-                el.attr_parsed(local_name!("start"))
-                    .map(|start: usize| start - 1)
-                    .unwrap_or(0),
-                el.attr_parsed(local_name!("reversed")).unwrap_or(false),
-            )
-        } else {
-            (1, false)
-        };
-        collect_list_item_children(doc, &mut index, reversed, container_node_id);
     }
 
     // Skip further construction if the node has no children or psuedo-children
     {
         let node = &doc.nodes[container_node_id];
-        if node.children.is_empty() && node.before.is_none() && node.after.is_none() {
+        if node.flat_tree_children().is_empty() && node.before.is_none() && node.after.is_none() {
             return;
         }
     }
@@ -176,15 +183,12 @@ pub(crate) fn collect_layout_children(
         DisplayInside::Contents => {
             doc.nodes[container_node_id]
                 .remove_damage(CONSTRUCT_BOX | CONSTRUCT_DESCENDENT | CONSTRUCT_FC);
-            // Take children array from node to avoid borrow checker issues.
-            let children = std::mem::take(&mut doc.nodes[container_node_id].children);
+            // Copy children array from node to avoid borrow checker issues.
+            let children = doc.nodes[container_node_id].flat_tree_children().to_vec();
 
             for child_id in children.iter().copied() {
                 collect_layout_children(doc, child_id, layout_children, anonymous_block_id)
             }
-
-            // Put children array back
-            doc.nodes[container_node_id].children = children;
         }
         DisplayInside::Flow | DisplayInside::FlowRoot | DisplayInside::TableCell => {
             // TODO: make "all_inline" detection work in the presence of display:contents nodes
@@ -192,7 +196,7 @@ pub(crate) fn collect_layout_children(
             let mut all_inline = true;
             let mut has_contents = false;
             for child in doc.nodes[container_node_id]
-                .children
+                .flat_tree_children()
                 .iter()
                 .copied()
                 .map(|child_id| &doc.nodes[child_id])
@@ -262,7 +266,7 @@ pub(crate) fn collect_layout_children(
         }
         DisplayInside::Flex | DisplayInside::Grid => {
             let has_text_node_or_contents = doc.nodes[container_node_id]
-                .children
+                .flat_tree_children()
                 .iter()
                 .copied()
                 .map(|child_id| &doc.nodes[child_id])
@@ -361,35 +365,27 @@ fn flush_pseudo_elements(doc: &mut BaseDocument, node_id: usize) {
             doc.nodes[new_node_id].parent = Some(node_id);
             doc.nodes[new_node_id].layout_parent.set(Some(node_id));
 
-            let content = &pe_style.as_ref().get_counters().content;
-            if let Content::Items(item_data) = content {
-                let items = &item_data.items[0..item_data.alt_start];
-                match &items[0] {
-                    ContentItem::String(owned_str) => {
-                        let text_node_id = doc.create_text_node(owned_str);
-                        doc.nodes[new_node_id].children.push(text_node_id);
-                    }
-                    _ => {
-                        // TODO: other types of content
-                    }
-                }
-            }
-
             let mut element_data = StyloElementData::default();
             element_data.styles.primary = Some(pe_style.clone());
             element_data.set_restyled();
             element_data.damage = RestyleDamage::all();
             *doc.nodes[new_node_id].stylo_element_data.borrow_mut() = Some(element_data);
 
+            // Generated content is resolved before construction (see `resolve_generated_content`)
+            let content = doc.nodes[node_id]
+                .element_data()
+                .and_then(|el| el.generated_content[idx].clone())
+                .unwrap_or_default();
+            build_generated_content(doc, new_node_id, &content);
+
             let node = &mut doc.nodes[node_id];
             node.set_pe_by_index(idx, Some(new_node_id));
             node.insert_damage(ALL_DAMAGE);
         }
 
         // Else: Update psuedo element
+        // (content is updated by `resolve_generated_content`)
         if let (Some(pe_node_id), Some(pe_style)) = (pe_node_id, pe_style) {
-            // TODO: Update content
-
             let mut node_styles = doc.nodes[pe_node_id].stylo_element_data.borrow_mut();
             let node_styles = &mut node_styles.as_mut().unwrap();
             node_styles.damage.insert(RestyleDamage::all());
@@ -565,6 +561,13 @@ fn create_checkbox_input(doc: &mut BaseDocument, input_element_id: usize) {
     }
 }
 
+fn create_range_input(doc: &mut BaseDocument, input_element_id: usize) {
+    let element = doc.nodes[input_element_id].element_data_mut().unwrap();
+    if element.range_input_data().is_none() {
+        element.special_data = SpecialElementData::RangeInput(RangeInputData::new(element));
+    }
+}
+
 /// Find and return the "layout_children" (inline boxes) for an inline layout
 /// without actually constructing the layout. This allows us to defer the expensive
 /// construction of the Parley layout (which invokes text shaping) to a paralell phase.
@@ -584,7 +587,7 @@ pub(crate) fn find_inline_layout_embedded_boxes(
             layout_children,
         );
     }
-    for child_id in root_node.children.iter().copied() {
+    for child_id in root_node.flat_tree_children().iter().copied() {
         find_inline_layout_embedded_boxes_recursive(
             &doc.nodes,
             inline_context_root_node_id,
@@ -602,7 +605,8 @@ pub(crate) fn find_inline_layout_embedded_boxes(
     }
 
     fn flush_inline_pseudos_recursive(doc: &mut BaseDocument, node_id: usize) {
-        doc.iter_children_mut(node_id, |child_id, doc| {
+        let children = doc.nodes[node_id].flat_tree_children().to_vec();
+        for child_id in children {
             flush_pseudo_elements(doc, child_id);
             let display = doc.nodes[node_id]
                 .display_style()
@@ -615,7 +619,7 @@ pub(crate) fn find_inline_layout_embedded_boxes(
             if do_recurse {
                 flush_inline_pseudos_recursive(doc, child_id);
             }
-        });
+        }
     }
 
     fn find_inline_layout_embedded_boxes_recursive(
@@ -645,7 +649,7 @@ pub(crate) fn find_inline_layout_embedded_boxes(
                         node.remove_damage(CONSTRUCT_DESCENDENT | CONSTRUCT_FC | CONSTRUCT_BOX);
                     }
                     (DisplayOutside::None, DisplayInside::Contents) => {
-                        for child_id in node.children.iter().copied() {
+                        for child_id in node.flat_tree_children().iter().copied() {
                             node.remove_damage(CONSTRUCT_DESCENDENT | CONSTRUCT_FC | CONSTRUCT_BOX);
                             find_inline_layout_embedded_boxes_recursive(
                                 nodes,
@@ -678,7 +682,7 @@ pub(crate) fn find_inline_layout_embedded_boxes(
                                     layout_children,
                                 );
                             }
-                            for child_id in node.children.iter().copied() {
+                            for child_id in node.flat_tree_children().iter().copied() {
                                 find_inline_layout_embedded_boxes_recursive(
                                     nodes,
                                     node_id,
@@ -705,7 +709,7 @@ pub(crate) fn find_inline_layout_embedded_boxes(
             NodeData::Comment | NodeData::Text(_) => {
                 node.remove_damage(CONSTRUCT_DESCENDENT | CONSTRUCT_FC | CONSTRUCT_BOX);
             }
-            NodeData::Document => unreachable!(),
+            NodeData::Document | NodeData::ShadowRoot(_) => unreachable!(),
         }
     }
 }
@@ -717,6 +721,62 @@ pub(crate) fn build_inline_layout_into(
     text_layout: &mut TextLayout,
     scale: f32,
     inline_context_root_node_id: usize,
+) {
+    let build = |layout_ctx: &mut LayoutContext<TextBrush>,
+                 font_ctx: &mut FontContext,
+                 text_layout: &mut TextLayout,
+                 paragraph_controls: &str| {
+        build_inline_layout_with_controls(
+            nodes,
+            layout_ctx,
+            font_ctx,
+            text_layout,
+            scale,
+            inline_context_root_node_id,
+            paragraph_controls,
+        )
+    };
+    build(layout_ctx, font_ctx, text_layout, "");
+    if text_layout.text.is_empty() && text_layout.layout.inline_boxes().is_empty() {
+        return;
+    }
+
+    // Parley takes the base direction of the paragraph from its first strong character, so if that
+    // doesn't match `direction` (or the paragraph is overridden) then the text is rebuilt starting
+    // with a mark of the right direction (and override). Empty paragraphs are left empty, as the
+    // controls would give them a line box.
+    let root_node = &nodes[inline_context_root_node_id];
+    let Some(style) = root_node.primary_styles() else {
+        return;
+    };
+    let is_rtl = style.clone_direction() == stylo::Direction::Rtl;
+    let unicode_bidi = style.clone_unicode_bidi();
+    let mut controls = String::new();
+    if unicode_bidi != stylo::UnicodeBidi::Plaintext && text_layout.layout.is_rtl() != is_rtl {
+        controls.push(if is_rtl { '\u{200F}' } else { '\u{200E}' });
+    }
+    if matches!(
+        unicode_bidi,
+        stylo::UnicodeBidi::BidiOverride | stylo::UnicodeBidi::IsolateOverride
+    ) {
+        controls.push(if is_rtl { '\u{202E}' } else { '\u{202D}' });
+    }
+    drop(style);
+    if !controls.is_empty() {
+        build(layout_ctx, font_ctx, text_layout, &controls);
+    }
+}
+
+/// Build the inline layout of an inline formatting context, with `paragraph_controls` (bidi control
+/// characters) at the start of its text
+fn build_inline_layout_with_controls(
+    nodes: &Slab<Node>,
+    layout_ctx: &mut LayoutContext<TextBrush>,
+    font_ctx: &mut FontContext,
+    text_layout: &mut TextLayout,
+    scale: f32,
+    inline_context_root_node_id: usize,
+    paragraph_controls: &str,
 ) {
     // Get the inline context's root node's text styles
     let root_node = &nodes[inline_context_root_node_id];
@@ -737,11 +797,18 @@ pub(crate) fn build_inline_layout_into(
     let mut builder = layout_ctx.tree_builder(font_ctx, scale, true, &parley_style);
 
     // Set whitespace collapsing mode
-    let collapse_mode = root_node_style
-        .map(|s| s.get_inherited_text().white_space_collapse)
-        .map(stylo_to_parley::white_space_collapse)
-        .unwrap_or(WhiteSpaceCollapse::Collapse);
-    builder.set_white_space_mode(collapse_mode);
+    let text_mode = root_node_style
+        .as_ref()
+        .map(|s| TextMode::from_style(s))
+        .unwrap_or_default();
+    builder.set_white_space_mode(text_mode.collapse_mode());
+
+    // The controls are committed in their own span, so that leading whitespace in the content
+    // which follows them is still collapsed
+    if !paragraph_controls.is_empty() {
+        builder.push_text(paragraph_controls);
+        builder.push_style_modification_span(&[]);
+    }
 
     // Render position-inside list items
     if let Some(ListItemLayout {
@@ -751,10 +818,22 @@ pub(crate) fn build_inline_layout_into(
         .element_data()
         .and_then(|el| el.list_item_data.as_deref())
     {
+        // The marker is styled by the list item's `::marker` pseudo element
+        let marker_style = root_node.marker.and_then(|marker_id| {
+            nodes[marker_id]
+                .primary_styles()
+                .map(|style| stylo_to_parley::style(marker_id, &style))
+        });
+        if let Some(marker_style) = &marker_style {
+            builder.push_style_span(marker_style.clone());
+        }
         match marker {
             Marker::Char(char) => builder.push_text(&format!("{char} ")),
             Marker::String(str) => builder.push_text(str),
         }
+        if marker_style.is_some() {
+            builder.pop_style_span();
+        }
     };
 
     if let Some(before_id) = root_node.before {
@@ -763,17 +842,17 @@ pub(crate) fn build_inline_layout_into(
             nodes,
             inline_context_root_node_id,
             before_id,
-            collapse_mode,
+            text_mode,
             root_line_height,
         );
     }
-    for child_id in root_node.children.iter().copied() {
+    for child_id in root_node.flat_tree_children().iter().copied() {
         build_inline_layout_recursive(
             &mut builder,
             nodes,
             inline_context_root_node_id,
             child_id,
-            collapse_mode,
+            text_mode,
             root_line_height,
         );
     }
@@ -783,11 +862,15 @@ pub(crate) fn build_inline_layout_into(
             nodes,
             inline_context_root_node_id,
             after_id,
-            collapse_mode,
+            text_mode,
             root_line_height,
         );
     }
 
+    if !paragraph_controls.is_empty() {
+        builder.pop_style_span();
+    }
+
     text_layout.text = builder.build_into(&mut text_layout.layout);
     return;
 
@@ -796,7 +879,7 @@ pub(crate) fn build_inline_layout_into(
         nodes: &Slab<Node>,
         parent_id: usize,
         node_id: usize,
-        collapse_mode: WhiteSpaceCollapse,
+        text_mode: TextMode,
         root_line_height: f32,
     ) {
         let node = &nodes[node_id];
@@ -805,11 +888,11 @@ pub(crate) fn build_inline_layout_into(
         node.layout_parent.set(Some(parent_id));
 
         // Set whitespace collapsing mode
-        let collapse_mode = node
+        let text_mode = node
             .primary_styles()
-            .map(|s| s.get_inherited_text().white_space_collapse)
-            .map(stylo_to_parley::white_space_collapse)
-            .unwrap_or(collapse_mode);
+            .map(|s| TextMode::from_style(&s))
+            .unwrap_or(text_mode);
+        let collapse_mode = text_mode.collapse_mode();
         builder.set_white_space_mode(collapse_mode);
 
         match &node.data {
@@ -828,14 +911,14 @@ pub(crate) fn build_inline_layout_into(
                         // node.remove_damage(CONSTRUCT_DESCENDENT | CONSTRUCT_FC | CONSTRUCT_BOX);
                     }
                     (DisplayOutside::None, DisplayInside::Contents) => {
-                        for child_id in node.children.iter().copied() {
+                        for child_id in node.flat_tree_children().iter().copied() {
                             // node.remove_damage(CONSTRUCT_DESCENDENT | CONSTRUCT_FC | CONSTRUCT_BOX);
                             build_inline_layout_recursive(
                                 builder,
                                 nodes,
                                 parent_id,
                                 child_id,
-                                collapse_mode,
+                                text_mode,
                                 root_line_height,
                             );
                         }
@@ -861,10 +944,8 @@ pub(crate) fn build_inline_layout_into(
                             // node.remove_damage(CONSTRUCT_DESCENDENT | CONSTRUCT_FC | CONSTRUCT_BOX);
                             // TODO: update span id for br spans
                             builder.push_style_modification_span(&[]);
-                            builder.set_white_space_mode(WhiteSpaceCollapse::Preserve);
-                            builder.push_text("\n");
+                            push_line_break(builder, collapse_mode);
                             builder.pop_style_span();
-                            builder.set_white_space_mode(collapse_mode);
                         } else {
                             // node.remove_damage(CONSTRUCT_DESCENDENT | CONSTRUCT_FC | CONSTRUCT_BOX);
                             let mut style = node
@@ -888,24 +969,35 @@ pub(crate) fn build_inline_layout_into(
 
                             builder.push_style_span(style);
 
+                            // Embeddings, isolates and overrides are delimited by bidi controls,
+                            // with the content in its own span so that its whitespace is collapsed
+                            let (open_controls, close_controls) = node
+                                .primary_styles()
+                                .map(|s| bidi_controls(&s))
+                                .unwrap_or_default();
+                            if !open_controls.is_empty() {
+                                builder.push_text(open_controls);
+                                builder.push_style_modification_span(&[]);
+                            }
+
                             if let Some(before_id) = node.before {
                                 build_inline_layout_recursive(
                                     builder,
                                     nodes,
                                     node_id,
                                     before_id,
-                                    collapse_mode,
+                                    text_mode,
                                     root_line_height,
                                 );
                             }
 
-                            for child_id in node.children.iter().copied() {
+                            for child_id in node.flat_tree_children().iter().copied() {
                                 build_inline_layout_recursive(
                                     builder,
                                     nodes,
                                     node_id,
                                     child_id,
-                                    collapse_mode,
+                                    text_mode,
                                     root_line_height,
                                 );
                             }
@@ -915,11 +1007,15 @@ pub(crate) fn build_inline_layout_into(
                                     nodes,
                                     node_id,
                                     after_id,
-                                    collapse_mode,
+                                    text_mode,
                                     root_line_height,
                                 );
                             }
 
+                            if !open_controls.is_empty() {
+                                builder.pop_style_span();
+                                builder.push_text(close_controls);
+                            }
                             builder.pop_style_span();
                         }
                     }
@@ -939,12 +1035,117 @@ pub(crate) fn build_inline_layout_into(
             NodeData::Text(data) => {
                 // node.remove_damage(CONSTRUCT_DESCENDENT | CONSTRUCT_FC | CONSTRUCT_BOX);
                 // dbg!(&data.content);
-                builder.push_text(&data.content);
+                push_text(builder, &data.content, text_mode);
             }
             NodeData::Comment => {
                 // node.remove_damage(CONSTRUCT_DESCENDENT | CONSTRUCT_FC | CONSTRUCT_BOX);
             }
-            NodeData::Document => unreachable!(),
+            NodeData::Document | NodeData::ShadowRoot(_) => unreachable!(),
+        }
+    }
+}
+
+/// The bidi control characters which open and close an inline box with the specified
+/// `unicode-bidi` and `direction`
+///
+/// See <https://drafts.csswg.org/css-writing-modes/#unicode-bidi>
+fn bidi_controls(style: &stylo::ComputedValues) -> (&'static str, &'static str) {
+    use stylo::UnicodeBidi;
+    let is_rtl = style.clone_direction() == stylo::Direction::Rtl;
+    match (style.clone_unicode_bidi(), is_rtl) {
+        (UnicodeBidi::Normal, _) => ("", ""),
+        (UnicodeBidi::Embed, false) => ("\u{202A}", "\u{202C}"),
+        (UnicodeBidi::Embed, true) => ("\u{202B}", "\u{202C}"),
+        (UnicodeBidi::Isolate, false) => ("\u{2066}", "\u{2069}"),
+        (UnicodeBidi::Isolate, true) => ("\u{2067}", "\u{2069}"),
+        (UnicodeBidi::BidiOverride, false) => ("\u{202D}", "\u{202C}"),
+        (UnicodeBidi::BidiOverride, true) => ("\u{202E}", "\u{202C}"),
+        (UnicodeBidi::IsolateOverride, false) => ("\u{2066}\u{202D}", "\u{202C}\u{2069}"),
+        (UnicodeBidi::IsolateOverride, true) => ("\u{2067}\u{202E}", "\u{202C}\u{2069}"),
+        (UnicodeBidi::Plaintext, _) => ("\u{2068}", "\u{2069}"),
+    }
+}
+
+/// The inherited text styles which control how text content is pushed into an inline layout
+#[derive(Clone, Copy)]
+struct TextMode {
+    white_space_collapse: stylo::WhiteSpaceCollapse,
+    text_wrap_mode: stylo::TextWrapMode,
+    hyphens: stylo::Hyphens,
+}
+
+impl Default for TextMode {
+    fn default() -> Self {
+        Self {
+            white_space_collapse: stylo::WhiteSpaceCollapse::Collapse,
+            text_wrap_mode: stylo::TextWrapMode::Wrap,
+            hyphens: stylo::Hyphens::Manual,
+        }
+    }
+}
+
+impl TextMode {
+    fn from_style(style: &stylo::ComputedValues) -> Self {
+        let itext_styles = style.get_inherited_text();
+        Self {
+            white_space_collapse: itext_styles.white_space_collapse,
+            text_wrap_mode: itext_styles.text_wrap_mode,
+            hyphens: itext_styles.hyphens,
+        }
+    }
+
+    fn collapse_mode(&self) -> WhiteSpaceCollapse {
+        stylo_to_parley::white_space_collapse(self.white_space_collapse)
+    }
+}
+
+/// Push a forced line break, which is preserved regardless of the whitespace collapsing mode
+fn push_line_break(builder: &mut TreeBuilder<TextBrush>, collapse_mode: WhiteSpaceCollapse) {
+    builder.set_white_space_mode(WhiteSpaceCollapse::Preserve);
+    builder.push_text("\n");
+    builder.set_white_space_mode(collapse_mode);
+}
+
+const SOFT_HYPHEN: char = '\u{00AD}';
+
+/// Push text content into an inline layout, implementing the parts of `white-space-collapse` and
+/// `hyphens` which Parley doesn't support by rewriting the text (`text-wrap-mode` is a Parley style):
+///
+///   - `white-space-collapse: preserve-breaks` splits the text into collapsed segments separated by
+///     forced line breaks.
+///   - `white-space-collapse: break-spaces` replaces spaces with non-collapsible, non-hanging spaces
+///     followed by a break opportunity.
+///   - `hyphens: none` removes soft hyphens (which are otherwise break opportunities).
+///
+/// Automatic hyphenation is not supported (there are no hyphenation dictionaries), so
+/// `hyphens: auto` only breaks at soft hyphens, like `hyphens: manual`.
+fn push_text(builder: &mut TreeBuilder<TextBrush>, text: &str, mode: TextMode) {
+    let text = match mode.hyphens {
+        stylo::Hyphens::None => Cow::Owned(text.replace(SOFT_HYPHEN, "")),
+        stylo::Hyphens::Manual | stylo::Hyphens::Auto => Cow::Borrowed(text),
+    };
+
+    match mode.white_space_collapse {
+        stylo::WhiteSpaceCollapse::PreserveBreaks => {
+            let segment_count = text.split('\n').count();
+            for (i, segment) in text.split('\n').enumerate() {
+                // Spaces and tabs around preserved segment breaks are removed
+                let mut segment = segment;
+                if i > 0 {
+                    push_line_break(builder, WhiteSpaceCollapse::Collapse);
+                    segment = segment.trim_start_matches([' ', '\t']);
+                }
+                if i + 1 < segment_count {
+                    segment = segment.trim_end_matches([' ', '\t']);
+                }
+                builder.push_text(segment);
+            }
+        }
+        stylo::WhiteSpaceCollapse::BreakSpaces
+            if mode.text_wrap_mode == stylo::TextWrapMode::Wrap =>
+        {
+            builder.push_text(&text.replace(' ', "\u{00A0}\u{200B}"));
         }
+        _ => builder.push_text(&text),
     }
 }
@@ -0,0 +1,742 @@
+//! Counter styles: the predefined counter styles and user-defined `@counter-style` rules
+//!
+//! Counter styles are used to format list item markers (`list-style-type`) and the `counter()` and
+//! `counters()` functions in generated content.
+//!
+//! See: <https://www.w3.org/TR/css-counter-styles-3/>
+
+use std::collections::HashMap;
+
+use style::counter_style::{CounterBound, Symbol, System};
+use style::shared_lock::SharedRwLockReadGuard;
+use style::stylesheets::{CssRule, StylesheetInDocument};
+use style_traits::values::ToCss;
+
+use crate::BaseDocument;
+
+/// The maximum length of a `extends` or `fallback` chain (which may be cyclic)
+const MAX_CHAIN_LENGTH: usize = 16;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum CounterSystem {
+    Cyclic,
+    Fixed(i32),
+    Symbolic,
+    Alphabetic,
+    Numeric,
+    Additive,
+}
+
+impl CounterSystem {
+    fn uses_negative_sign(self) -> bool {
+        matches!(
+            self,
+            Self::Symbolic | Self::Alphabetic | Self::Numeric | Self::Additive
+        )
+    }
+
+    /// The range of the system when the `range` descriptor is `auto`
+    fn auto_range(self) -> (i64, i64) {
+        match self {
+            Self::Cyclic | Self::Fixed(_) | Self::Numeric => (i64::MIN, i64::MAX),
+            Self::Symbolic | Self::Alphabetic => (1, i64::MAX),
+            Self::Additive => (0, i64::MAX),
+        }
+    }
+}
+
+/// A fully resolved counter style
+#[derive(Clone, Debug)]
+struct CounterStyle {
+    system: CounterSystem,
+    symbols: Vec<String>,
+    additive_symbols: Vec<(i32, String)>,
+    negative: (String, String),
+    prefix: String,
+    suffix: String,
+    /// `None` for `range: auto`
+    range: Option<Vec<(i64, i64)>>,
+    pad: Option<(usize, String)>,
+    fallback: String,
+}
+
+impl CounterStyle {
+    fn new(system: CounterSystem, symbols: &[&str]) -> Self {
+        Self {
+            system,
+            symbols: symbols.iter().map(|s| s.to_string()).collect(),
+            additive_symbols: Vec::new(),
+            negative: (String::from("-"), String::new()),
+            prefix: String::new(),
+            suffix: String::from(". "),
+            range: None,
+            pad: None,
+            fallback: String::from("decimal"),
+        }
+    }
+
+    fn additive(additive_symbols: &[(i32, &str)], range: (i64, i64)) -> Self {
+        Self {
+            additive_symbols: additive_symbols
+                .iter()
+                .map(|(weight, symbol)| (*weight, symbol.to_string()))
+                .collect(),
+            range: Some(vec![range]),
+            ..Self::new(CounterSystem::Additive, &[])
+        }
+    }
+
+    fn numeric(digits: &str) -> Self {
+        let digits: Vec<String> = digits.chars().map(String::from).collect();
+        Self {
+            symbols: digits,
+            ..Self::new(CounterSystem::Numeric, &[])
+        }
+    }
+
+    fn alphabetic(letters: &str) -> Self {
+        let letters: Vec<String> = letters.chars().map(String::from).collect();
+        Self {
+            symbols: letters,
+            ..Self::new(CounterSystem::Alphabetic, &[])
+        }
+    }
+
+    fn with_suffix(self, suffix: &str) -> Self {
+        Self {
+            suffix: suffix.to_string(),
+            ..self
+        }
+    }
+
+    fn in_range(&self, value: i32) -> bool {
+        let value = value as i64;
+        match &self.range {
+            Some(ranges) => ranges
+                .iter()
+                .any(|(start, end)| *start <= value && value <= *end),
+            None => {
+                let (start, end) = self.system.auto_range();
+                start <= value && value <= end
+            }
+        }
+    }
+
+    /// Generate the representation of a (non-negative, unless the system doesn't use a negative sign)
+    /// value using the style's counter system. Returns `None` if the system can't represent the value.
+    fn generate(&self, value: i32) -> Option<String> {
+        let symbols = &self.symbols;
+        let len = symbols.len() as i64;
+        let value = value as i64;
+        match self.system {
+            CounterSystem::Cyclic => {
+                let idx = (value - 1).rem_euclid(len.max(1));
+                symbols.get(idx as usize).cloned()
+            }
+            CounterSystem::Fixed(first) => {
+                let idx = value - first as i64;
+                (idx >= 0).then(|| symbols.get(idx as usize).cloned())?
+            }
+            CounterSystem::Symbolic => {
+                if value < 1 || len == 0 {
+                    return None;
+                }
+                let symbol = &symbols[((value - 1) % len) as usize];
+                let count = ((value + len - 1) / len) as usize;
+                Some(symbol.repeat(count))
+            }
+            CounterSystem::Alphabetic => {
+                if value < 1 || len < 2 {
+                    return None;
+                }
+                let mut value = value;
+                let mut parts = Vec::new();
+                while value != 0 {
+                    value -= 1;
+                    parts.push(symbols[(value % len) as usize].as_str());
+                    value /= len;
+                }
+                parts.reverse();
+                Some(parts.concat())
+            }
+            CounterSystem::Numeric => {
+                if len < 2 {
+                    return None;
+                }
+                if value == 0 {
+                    return Some(symbols[0].clone());
+                }
+                let mut value = value;
+                let mut parts = Vec::new();
+                while value != 0 {
+                    parts.push(symbols[(value % len) as usize].as_str());
+                    value /= len;
+                }
+                parts.reverse();
+                Some(parts.concat())
+            }
+            CounterSystem::Additive => {
+                if value == 0 {
+                    return self
+                        .additive_symbols
+                        .iter()
+                        .find(|(weight, _)| *weight == 0)
+                        .map(|(_, symbol)| symbol.clone());
+                }
+                let mut value = value;
+                let mut result = String::new();
+                for (weight, symbol) in &self.additive_symbols {
+                    let weight = *weight as i64;
+                    if weight <= 0 {
+                        continue;
+                    }
+                    let reps = value / weight;
+                    if reps > 0 {
+                        result.push_str(&symbol.repeat(reps as usize));
+                        value -= reps * weight;
+                    }
+                    if value == 0 {
+                        break;
+                    }
+                }
+                (value == 0).then_some(result)
+            }
+        }
+    }
+
+    /// Generate the representation of a value, including its negative sign and padding. Returns
+    /// `None` if the value must be formatted with the fallback style instead.
+    fn representation(&self, value: i32) -> Option<String> {
+        if !self.in_range(value) {
+            return None;
+        }
+        let is_negative = value < 0 && self.system.uses_negative_sign();
+        let mut repr = self.generate(if is_negative {
+            value.checked_neg()?
+        } else {
+            value
+        })?;
+
+        let (neg_prefix, neg_suffix) = match is_negative {
+            true => (self.negative.0.as_str(), self.negative.1.as_str()),
+            false => ("", ""),
+        };
+        if let Some((pad_length, pad_symbol)) = &self.pad {
+            let length =
+                repr.chars().count() + neg_prefix.chars().count() + neg_suffix.chars().count();
+            if length < *pad_length {
+                repr.insert_str(0, &pad_symbol.repeat(pad_length - length));
+            }
+        }
+
+        Some(format!("{neg_prefix}{repr}{neg_suffix}"))
+    }
+}
+
+/// The predefined counter style with the given name
+fn predefined_counter_style(name: &str) -> Option<CounterStyle> {
+    use CounterSystem::*;
+
+    let name = name.to_ascii_lowercase();
+    Some(match name.as_str() {
+        "decimal" => CounterStyle::numeric("0123456789"),
+        "decimal-leading-zero" => CounterStyle {
+            pad: Some((2, String::from("0"))),
+            ..CounterStyle::numeric("0123456789")
+        },
+        "arabic-indic" => CounterStyle::numeric("٠١٢٣٤٥٦٧٨٩"),
+        "bengali" => CounterStyle::numeric("০১২৩৪৫৬৭৮৯"),
+        "cambodian" | "khmer" => CounterStyle::numeric("០១២៣៤៥៦៧៨៩"),
+        "cjk-decimal" => CounterStyle::numeric("〇一二三四五六七八九").with_suffix("、"),
+        "devanagari" => CounterStyle::numeric("०१२३४५६७८९"),
+        "gujarati" => CounterStyle::numeric("૦૧૨૩૪૫૬૭૮૯"),
+        "gurmukhi" => CounterStyle::numeric("੦੧੨੩੪੫੬੭੮੯"),
+        "kannada" => CounterStyle::numeric("೦೧೨೩೪೫೬೭೮೯"),
+        "lao" => CounterStyle::numeric("໐໑໒໓໔໕໖໗໘໙"),
+        "malayalam" => CounterStyle::numeric("൦൧൨൩൪൫൬൭൮൯"),
+        "mongolian" => CounterStyle::numeric("᠐᠑᠒᠓᠔᠕᠖᠗᠘᠙"),
+        "myanmar" => CounterStyle::numeric("၀၁၂၃၄၅၆၇၈၉"),
+        "oriya" => CounterStyle::numeric("୦୧୨୩୪୫୬୭୮୯"),
+        "persian" => CounterStyle::numeric("۰۱۲۳۴۵۶۷۸۹"),
+        "tamil" => CounterStyle::numeric("௦௧௨௩௪௫௬௭௮௯"),
+        "telugu" => CounterStyle::numeric("౦౧౨౩౪౫౬౭౮౯"),
+        "thai" => CounterStyle::numeric("๐๑๒๓๔๕๖๗๘๙"),
+        "tibetan" => CounterStyle::numeric("༠༡༢༣༤༥༦༧༨༩"),
+
+        "lower-alpha" | "lower-latin" => CounterStyle::alphabetic("abcdefghijklmnopqrstuvwxyz"),
+        "upper-alpha" | "upper-latin" => CounterStyle::alphabetic("ABCDEFGHIJKLMNOPQRSTUVWXYZ"),
+        "lower-greek" => CounterStyle::alphabetic("αβγδεζηθικλμνξοπρστυφχψω"),
+        "hiragana" => CounterStyle::alphabetic(
+            "あいうえおかきくけこさしすせそたちつてとなにぬねのはひふへほまみむめもやゆよらりるれろわゐゑをん",
+        )
+        .with_suffix("、"),
+        "hiragana-iroha" => CounterStyle::alphabetic(
+            "いろはにほへとちりぬるをわかよたれそつねならむうゐのおくやまけふこえてあさきゆめみしゑひもせす",
+        )
+        .with_suffix("、"),
+        "katakana" => CounterStyle::alphabetic(
+            "アイウエオカキクケコサシスセソタチツテトナニヌネノハヒフヘホマミムメモヤユヨラリルレロワヰヱヲン",
+        )
+        .with_suffix("、"),
+        "katakana-iroha" => CounterStyle::alphabetic(
+            "イロハニホヘトチリヌルヲワカヨタレソツネナラムウヰノオクヤマケフコエテアサキユメミシヱヒモセス",
+        )
+        .with_suffix("、"),
+        "cjk-earthly-branch" => CounterStyle::alphabetic("子丑寅卯辰巳午未申酉戌亥").with_suffix("、"),
+        "cjk-heavenly-stem" => CounterStyle::alphabetic("甲乙丙丁戊己庚辛壬癸").with_suffix("、"),
+
+        "lower-roman" | "upper-roman" => {
+            let style = CounterStyle::additive(
+                &[
+                    (1000, "M"),
+                    (900, "CM"),
+                    (500, "D"),
+                    (400, "CD"),
+                    (100, "C"),
+                    (90, "XC"),
+                    (50, "L"),
+                    (40, "XL"),
+                    (10, "X"),
+                    (9, "IX"),
+                    (5, "V"),
+                    (4, "IV"),
+                    (1, "I"),
+                ],
+                (1, 3999),
+            );
+            match name.as_str() {
+                "lower-roman" => CounterStyle {
+                    additive_symbols: style
+                        .additive_symbols
+                        .into_iter()
+                        .map(|(weight, symbol)| (weight, symbol.to_ascii_lowercase()))
+                        .collect(),
+                    ..style
+                },
+                _ => style,
+            }
+        }
+        "armenian" | "upper-armenian" | "lower-armenian" => {
+            let style = CounterStyle::additive(
+                &[
+                    (9000, "Ք"),
+                    (8000, "Փ"),
+                    (7000, "Ւ"),
+                    (6000, "Ց"),
+                    (5000, "Ր"),
+                    (4000, "Տ"),
+                    (3000, "Վ"),
+                    (2000, "Ս"),
+                    (1000, "Ռ"),
+                    (900, "Ջ"),
+                    (800, "Պ"),
+                    (700, "Չ"),
+                    (600, "Ո"),
+                    (500, "Շ"),
+                    (400, "Ն"),
+                    (300, "Յ"),
+                    (200, "Մ"),
+                    (100, "Ճ"),
+                    (90, "Ղ"),
+                    (80, "Ձ"),
+                    (70, "Հ"),
+                    (60, "Կ"),
+                    (50, "Ծ"),
+                    (40, "Խ"),
+                    (30, "Լ"),
+                    (20, "Ի"),
+                    (10, "Ժ"),
+                    (9, "Թ"),
+                    (8, "Ը"),
+                    (7, "Է"),
+                    (6, "Զ"),
+                    (5, "Ե"),
+                    (4, "Դ"),
+                    (3, "Գ"),
+                    (2, "Բ"),
+                    (1, "Ա"),
+                ],
+                (1, 9999),
+            );
+            match name.as_str() {
+                "lower-armenian" => CounterStyle {
+                    additive_symbols: style
+                        .additive_symbols
+                        .into_iter()
+                        .map(|(weight, symbol)| (weight, symbol.to_lowercase()))
+                        .collect(),
+                    ..style
+                },
+                _ => style,
+            }
+        }
+        "georgian" => CounterStyle::additive(
+            &[
+                (10000, "ჵ"),
+                (9000, "ჰ"),
+                (8000, "ჯ"),
+                (7000, "ჴ"),
+                (6000, "ხ"),
+                (5000, "ჭ"),
+                (4000, "წ"),
+                (3000, "ძ"),
+                (2000, "ც"),
+                (1000, "ჩ"),
+                (900, "შ"),
+                (800, "ყ"),
+                (700, "ღ"),
+                (600, "ქ"),
+                (500, "ფ"),
+                (400, "ჳ"),
+                (300, "ტ"),
+                (200, "ს"),
+                (100, "რ"),
+                (90, "ჟ"),
+                (80, "პ"),
+                (70, "ო"),
+                (60, "ჲ"),
+                (50, "ნ"),
+                (40, "მ"),
+                (30, "ლ"),
+                (20, "კ"),
+                (10, "ი"),
+                (9, "თ"),
+                (8, "ჱ"),
+                (7, "ზ"),
+                (6, "ვ"),
+                (5, "ე"),
+                (4, "დ"),
+                (3, "გ"),
+                (2, "ბ"),
+                (1, "ა"),
+            ],
+            (1, 19999),
+        ),
+        "hebrew" => CounterStyle::additive(
+            &[
+                (10000, "י׳"),
+                (9000, "ט׳"),
+                (8000, "ח׳"),
+                (7000, "ז׳"),
+                (6000, "ו׳"),
+                (5000, "ה׳"),
+                (4000, "ד׳"),
+                (3000, "ג׳"),
+                (2000, "ב׳"),
+                (1000, "א׳"),
+                (400, "ת"),
+                (300, "ש"),
+                (200, "ר"),
+                (100, "ק"),
+                (90, "צ"),
+                (80, "פ"),
+                (70, "ע"),
+                (60, "ס"),
+                (50, "נ"),
+                (40, "מ"),
+                (30, "ל"),
+                (20, "כ"),
+                (19, "יט"),
+                (18, "יח"),
+                (17, "יז"),
+                (16, "טז"),
+                (15, "טו"),
+                (10, "י"),
+                (9, "ט"),
+                (8, "ח"),
+                (7, "ז"),
+                (6, "ו"),
+                (5, "ה"),
+                (4, "ד"),
+                (3, "ג"),
+                (2, "ב"),
+                (1, "א"),
+            ],
+            (1, 10999),
+        ),
+
+        "disc" => CounterStyle::new(Cyclic, &["•"]).with_suffix(" "),
+        "circle" => CounterStyle::new(Cyclic, &["◦"]).with_suffix(" "),
+        "square" => CounterStyle::new(Cyclic, &["▪"]).with_suffix(" "),
+        "disclosure-open" => CounterStyle::new(Cyclic, &["▾"]).with_suffix(" "),
+        "disclosure-closed" => CounterStyle::new(Cyclic, &["▸"]).with_suffix(" "),
+        _ => return None,
+    })
+}
+
+/// Whether a counter style name can't be redefined by an `@counter-style` rule
+fn is_reserved_counter_style_name(name: &str) -> bool {
+    matches!(
+        name.to_ascii_lowercase().as_str(),
+        "decimal" | "disc" | "square" | "circle" | "disclosure-open" | "disclosure-closed" | "none"
+    )
+}
+
+/// The descriptors of an `@counter-style` rule (which are unset if not specified)
+#[derive(Clone, Debug, Default)]
+struct CounterStyleRuleData {
+    system: Option<CounterSystem>,
+    extends: Option<String>,
+    symbols: Option<Vec<String>>,
+    additive_symbols: Option<Vec<(i32, String)>>,
+    negative: Option<(String, String)>,
+    prefix: Option<String>,
+    suffix: Option<String>,
+    range: Option<Option<Vec<(i64, i64)>>>,
+    pad: Option<(usize, String)>,
+    fallback: Option<String>,
+}
+
+fn symbol_text(symbol: &Symbol) -> String {
+    match symbol {
+        Symbol::String(text) => text.to_string(),
+        Symbol::Ident(ident) => ident.0.to_string(),
+    }
+}
+
+impl CounterStyleRuleData {
+    fn from_rule(rule: &style::counter_style::CounterStyleRule) -> Self {
+        let (system, extends) = match rule.system() {
+            None | Some(System::Symbolic) => (Some(CounterSystem::Symbolic), None),
+            Some(System::Cyclic) => (Some(CounterSystem::Cyclic), None),
+            Some(System::Numeric) => (Some(CounterSystem::Numeric), None),
+            Some(System::Alphabetic) => (Some(CounterSystem::Alphabetic), None),
+            Some(System::Additive) => (Some(CounterSystem::Additive), None),
+            Some(System::Fixed { first_symbol_value }) => {
+                let first = first_symbol_value.map(|v| v.value()).unwrap_or(1);
+                (Some(CounterSystem::Fixed(first)), None)
+            }
+            Some(System::Extends(name)) => (None, Some(name.0.to_string())),
+        };
+        let bound = |bound: &CounterBound, infinite: i64| match bound {
+            CounterBound::Integer(value) => value.value() as i64,
+            CounterBound::Infinite => infinite,
+        };
+
+        Self {
+            system,
+            extends,
+            symbols: rule
+                .symbols()
+                .map(|symbols| symbols.0.iter().map(symbol_text).collect()),
+            additive_symbols: rule.additive_symbols().map(|symbols| {
+                symbols
+                    .0
+                    .iter()
+                    .map(|tuple| (tuple.weight.value(), symbol_text(&tuple.symbol)))
+                    .collect()
+            }),
+            negative: rule.negative().map(|negative| {
+                (
+                    symbol_text(&negative.0),
+                    negative.1.as_ref().map(symbol_text).unwrap_or_default(),
+                )
+            }),
+            prefix: rule.prefix().map(symbol_text),
+            suffix: rule.suffix().map(symbol_text),
+            range: rule.range().map(|ranges| {
+                // An empty list of ranges is `range: auto`
+                (!ranges.0.is_empty()).then(|| {
+                    ranges
+                        .0
+                        .iter()
+                        .map(|range| (bound(&range.start, i64::MIN), bound(&range.end, i64::MAX)))
+                        .collect()
+                })
+            }),
+            pad: rule
+                .pad()
+                .map(|pad| (pad.0.value().max(0) as usize, symbol_text(&pad.1))),
+            fallback: rule.fallback().map(|fallback| fallback.0.0.to_string()),
+        }
+    }
+
+    /// Resolve the rule into a counter style, filling in unset descriptors from the initial values
+    /// (or the extended counter style)
+    fn resolve(&self, base: Option<CounterStyle>) -> CounterStyle {
+        let mut style = base.unwrap_or_else(|| CounterStyle {
+            system: self.system.unwrap_or(CounterSystem::Symbolic),
+            symbols: Vec::new(),
+            ..CounterStyle::new(CounterSystem::Symbolic, &[])
+        });
+        if self.extends.is_none() {
+            style.range = None;
+            style.pad = None;
+            style.fallback = String::from("decimal");
+        }
+
+        if let Some(symbols) = &self.symbols {
+            style.symbols = symbols.clone();
+        }
+        if let Some(additive_symbols) = &self.additive_symbols {
+            style.additive_symbols = additive_symbols.clone();
+        }
+        if let Some(negative) = &self.negative {
+            style.negative = negative.clone();
+        }
+        if let Some(prefix) = &self.prefix {
+            style.prefix = prefix.clone();
+        }
+        if let Some(suffix) = &self.suffix {
+            style.suffix = suffix.clone();
+        }
+        if let Some(range) = &self.range {
+            style.range = range.clone();
+        }
+        if let Some(pad) = &self.pad {
+            style.pad = Some(pad.clone());
+        }
+        if let Some(fallback) = &self.fallback {
+            style.fallback = fallback.clone();
+        }
+
+        style
+    }
+}
+
+/// The counter styles available to a document
+#[derive(Default)]
+pub(crate) struct CounterStyles {
+    rules: HashMap<String, CounterStyleRuleData>,
+}
+
+impl CounterStyles {
+    /// Collect the `@counter-style` rules from the document's stylesheets
+    pub(crate) fn from_document(doc: &BaseDocument) -> Self {
+        let guard = doc.guard.read();
+        let mut rules = HashMap::new();
+
+        let sheets = doc
+            .ua_stylesheets
+            .values()
+            .chain(doc.nodes_to_stylesheet.values());
+        for sheet in sheets {
+            collect_counter_style_rules(sheet.0.rules(&guard), &guard, &mut rules);
+        }
+
+        Self { rules }
+    }
+
+    fn get(&self, name: &str, depth: usize) -> Option<CounterStyle> {
+        if depth > MAX_CHAIN_LENGTH {
+            return None;
+        }
+        match self.rules.get(name) {
+            Some(rule) => {
+                let base = match &rule.extends {
+                    Some(extends) => Some(
+                        self.get(extends, depth + 1)
+                            .or_else(|| predefined_counter_style("decimal"))?,
+                    ),
+                    None => None,
+                };
+                Some(rule.resolve(base))
+            }
+            None => predefined_counter_style(name),
+        }
+    }
+
+    fn representation_with_style(&self, name: &str, value: i32) -> (String, Option<CounterStyle>) {
+        let mut name = name.to_string();
+        for depth in 0..MAX_CHAIN_LENGTH {
+            let Some(style) = self.get(&name, depth) else {
+                break;
+            };
+            if let Some(repr) = style.representation(value) {
+                return (repr, Some(style));
+            }
+            name = style.fallback.clone();
+        }
+
+        // Fall back to decimal (which can represent every value)
+        let decimal = predefined_counter_style("decimal").unwrap();
+        (decimal.representation(value).unwrap(), Some(decimal))
+    }
+
+    /// Format a counter value with the named counter style (as used by `counter()` and `counters()`)
+    pub(crate) fn format(&self, name: &str, value: i32) -> String {
+        if name.eq_ignore_ascii_case("none") {
+            return String::new();
+        }
+        self.representation_with_style(name, value).0
+    }
+
+    /// Format a list item marker with the named counter style (including its prefix and suffix)
+    pub(crate) fn marker(&self, name: &str, value: i32) -> String {
+        let (repr, style) = self.representation_with_style(name, value);
+        match style {
+            Some(style) => format!("{}{repr}{}", style.prefix, style.suffix),
+            None => repr,
+        }
+    }
+}
+
+fn collect_counter_style_rules(
+    css_rules: &[CssRule],
+    guard: &SharedRwLockReadGuard,
+    rules: &mut HashMap<String, CounterStyleRuleData>,
+) {
+    for rule in css_rules {
+        match rule {
+            CssRule::CounterStyle(rule) => {
+                let rule = rule.read_with(guard);
+                let name = rule.name().0.to_string();
+                if !is_reserved_counter_style_name(&name) {
+                    rules.insert(name, CounterStyleRuleData::from_rule(rule));
+                }
+            }
+            CssRule::Media(media_rule) => {
+                collect_counter_style_rules(&media_rule.rules.read_with(guard).0, guard, rules);
+            }
+            _ => {}
+        }
+    }
+}
+
+/// The name of the counter style referred to by a `list-style-type` (or the counter style of a
+/// `counter()` function), or the marker string if it is a string
+pub(crate) enum CounterStyleRef {
+    Name(String),
+    String(String),
+}
+
+impl CounterStyleRef {
+    pub(crate) fn from_css(value: &impl ToCss) -> Self {
+        let css = value.to_css_string();
+        match css.strip_prefix('"').and_then(|css| css.strip_suffix('"')) {
+            Some(string) => Self::String(string.replace("\\\"", "\"")),
+            None => Self::Name(css),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_counter_style_systems() {
+        let styles = CounterStyles::default();
+        assert_eq!(styles.format("decimal", -12), "-12");
+        assert_eq!(styles.format("decimal-leading-zero", 7), "07");
+        assert_eq!(styles.format("lower-alpha", 28), "ab");
+        assert_eq!(styles.format("upper-roman", 1994), "MCMXCIV");
+        assert_eq!(styles.format("lower-roman", 4), "iv");
+        assert_eq!(styles.format("lower-greek", 2), "β");
+        assert_eq!(styles.format("armenian", 1), "Ա");
+        assert_eq!(styles.format("hebrew", 15), "טו");
+        assert_eq!(styles.format("cjk-decimal", 2025), "二〇二五");
+        assert_eq!(styles.format("unknown-style", 3), "3");
+    }
+
+    #[test]
+    fn test_counter_style_fallback() {
+        let styles = CounterStyles::default();
+        // Out of range values are formatted with the fallback style
+        assert_eq!(styles.format("upper-roman", 4000), "4000");
+        assert_eq!(styles.format("lower-alpha", 0), "0");
+        assert_eq!(styles.marker("decimal", 3), "3. ");
+        assert_eq!(styles.marker("disc", 3), "• ");
+        assert_eq!(styles.marker("cjk-earthly-branch", 1), "子、");
+    }
+}
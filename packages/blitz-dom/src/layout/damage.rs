@@ -1,9 +1,8 @@
 use crate::NON_INCREMENTAL;
 use crate::node::NodeFlags;
-use crate::{BaseDocument, net::ImageHandler, node::BackgroundImageData, util::ImageType};
+use crate::{BaseDocument, Node, net::ImageHandler, node::BackgroundImageData, util::ImageType};
 use blitz_traits::net::Request;
 use style::properties::ComputedValues;
-use style::properties::generated::longhands::position::computed_value::T as Position;
 use style::selector_parser::RestyleDamage;
 use style::servo::url::ComputedUrl;
 use style::values::generics::image::Image as StyloImage;
@@ -11,6 +10,8 @@ use style::values::specified::align::AlignFlags;
 use style::values::specified::box_::DisplayInside;
 use style::values::specified::box_::DisplayOutside;
 
+use std::collections::HashSet;
+
 pub(crate) const CONSTRUCT_BOX: RestyleDamage =
     RestyleDamage::from_bits_retain(0b_0000_0000_0001_0000);
 pub(crate) const CONSTRUCT_FC: RestyleDamage =
@@ -25,7 +26,6 @@ pub(crate) const ALL_DAMAGE: RestyleDamage =
     RestyleDamage::from_bits_retain(0b_0000_0000_0111_1111);
 
 impl BaseDocument {
-    #[cfg(feature = "incremental")]
     pub(crate) fn propagate_damage_flags(
         &mut self,
         node_id: usize,
@@ -37,7 +37,7 @@ impl BaseDocument {
         damage |= damage_from_parent;
 
         let damage_for_children = RestyleDamage::empty();
-        let children = std::mem::take(&mut self.nodes[node_id].children);
+        let children = self.nodes[node_id].flat_tree_children().to_vec();
         let layout_children = std::mem::take(self.nodes[node_id].layout_children.get_mut());
         let use_layout_children = self.nodes[node_id].should_traverse_layout_children();
         if use_layout_children {
@@ -59,8 +59,7 @@ impl BaseDocument {
 
         let node = &mut self.nodes[node_id];
 
-        // Put children back
-        node.children = children;
+        // Put layout children back
         *node.layout_children.get_mut() = layout_children;
 
         if damage.contains(CONSTRUCT_BOX) {
@@ -87,6 +86,12 @@ impl BaseDocument {
         // Store damage for current node
         node.set_damage(damage);
 
+        // Damage propagates up to the root, so ancestors (whose layout may depend on the node) are
+        // repainted too
+        if !damage.is_empty() {
+            self.paint_damage.insert(node_id);
+        }
+
         // let _is_fc_root = node
         //     .primary_styles()
         //     .map(|s| is_fc_root(&s))
@@ -109,6 +114,55 @@ impl BaseDocument {
     }
 }
 
+impl BaseDocument {
+    /// Mark a node as needing to be repainted. Restyles and DOM mutations are tracked
+    /// automatically, so this is only needed for other changes to what a node paints.
+    pub fn invalidate_paint(&mut self, node_id: usize) {
+        self.paint_damage.insert(node_id);
+    }
+
+    /// Take the set of nodes which need to be repainted because they have been damaged since
+    /// this was last called.
+    ///
+    /// Text and inline-level boxes are painted as part of their inline formatting context, so the
+    /// ancestors up to (and including) the inline root which paints them are included in the set.
+    /// Damage is tracked with or without the `incremental` feature (which only affects whether
+    /// layout is recomputed incrementally).
+    pub fn take_paint_damage(&mut self) -> HashSet<usize> {
+        let mut damage = std::mem::take(&mut self.paint_damage);
+        let inline_ancestors: Vec<usize> = damage
+            .iter()
+            .flat_map(|&node_id| self.inline_ancestors(node_id))
+            .collect();
+        damage.extend(inline_ancestors);
+        damage
+    }
+
+    fn inline_ancestors(&self, node_id: usize) -> Vec<usize> {
+        let mut ancestors = Vec::new();
+        let mut node = self.nodes.get(node_id);
+        while let Some(current) = node.filter(|node| is_inline_level(node)) {
+            let Some(parent_id) = current.layout_parent.get().or(current.parent) else {
+                break;
+            };
+            ancestors.push(parent_id);
+            node = self
+                .nodes
+                .get(parent_id)
+                .filter(|parent| !parent.flags.is_inline_root());
+        }
+        ancestors
+    }
+}
+
+fn is_inline_level(node: &Node) -> bool {
+    node.is_text_node()
+        || node.primary_styles().is_some_and(|style| {
+            let display = style.clone_display();
+            display.outside() == DisplayOutside::Inline && display.inside() == DisplayInside::Flow
+        })
+}
+
 // #[cfg(feature = "incremental")]
 // fn is_fc_root(style: &ComputedValues) -> bool {
 //     let display = style.clone_display();
@@ -278,8 +332,8 @@ impl BaseDocument {
             node.display_constructed_as = style.clone_display();
             // }
 
-            // Flush background image from style to dedicated storage on the node
-            // TODO: handle multiple background images
+            // Flush background images from style to dedicated storage on the node (one entry per
+            // background layer)
             if let Some(elem) = node.data.downcast_element_mut() {
                 let style_bgs = &style.get_background().background_image.0;
                 let elem_bgs = &mut elem.background_images;
@@ -288,13 +342,20 @@ impl BaseDocument {
                 elem_bgs.resize_with(len, || None);
 
                 for idx in 0..len {
-                    let background_image = &style_bgs[idx];
+                    // For `image-set()`, the image selected by the style system is loaded
+                    let background_image = match &style_bgs[idx] {
+                        StyloImage::ImageSet(image_set) => image_set
+                            .items
+                            .get(image_set.selected_index)
+                            .map(|item| &item.image),
+                        image => Some(image),
+                    };
                     let new_bg_image = match background_image {
-                        StyloImage::Url(ComputedUrl::Valid(new_url)) => {
+                        Some(StyloImage::Url(ComputedUrl::Valid(new_url))) => {
                             let old_bg_image = elem_bgs[idx].as_ref();
                             let old_bg_image_url = old_bg_image.map(|data| &data.url);
                             if old_bg_image_url.is_some_and(|old_url| **new_url == **old_url) {
-                                break;
+                                continue;
                             }
 
                             self.net_provider.fetch(
@@ -314,6 +375,26 @@ impl BaseDocument {
                 }
             }
 
+            // Flush list-style-image from style to dedicated storage on the node
+            if let Some(elem) = node.data.downcast_element_mut() {
+                let is_list_item = style.get_box().display.is_list_item();
+                match style.clone_list_style_image() {
+                    StyloImage::Url(ComputedUrl::Valid(new_url)) if is_list_item => {
+                        let old_url = elem.list_style_image.as_ref().map(|data| &data.url);
+                        if old_url.is_none_or(|old_url| **new_url != **old_url) {
+                            self.net_provider.fetch(
+                                doc_id,
+                                Request::get((*new_url).clone()),
+                                Box::new(ImageHandler::new(node_id, ImageType::ListStyleImage)),
+                            );
+                            elem.list_style_image =
+                                Some(Box::new(BackgroundImageData::new(new_url.clone())));
+                        }
+                    }
+                    _ => elem.list_style_image = None,
+                }
+            }
+
             // In non-incremental mode we unconditionally clear the Taffy cache.
             // In incremental mode this is handled as part of damage propagation.
             if NON_INCREMENTAL {
@@ -330,6 +411,8 @@ impl BaseDocument {
             node.style.display
         };
 
+        self.apply_writing_mode(node_id);
+
         // If the node has children, then take those children and...
         let children = self.nodes[node_id].layout_children.borrow_mut().take();
         if let Some(mut children) = children {
@@ -349,38 +432,8 @@ impl BaseDocument {
 
             // Put children back
             *self.nodes[node_id].layout_children.borrow_mut() = Some(children);
-
-            // Sort paint_children in place
-            self.nodes[node_id]
-                .paint_children
-                .borrow_mut()
-                .as_mut()
-                .unwrap()
-                .sort_by(|left, right| {
-                    let left_node = self.nodes.get(*left).unwrap();
-                    let right_node = self.nodes.get(*right).unwrap();
-                    left_node
-                        .z_index()
-                        .cmp(&right_node.z_index())
-                        .then_with(|| {
-                            fn position_to_order(pos: Position) -> u8 {
-                                match pos {
-                                    Position::Static | Position::Relative | Position::Sticky => 0,
-                                    Position::Absolute | Position::Fixed => 1,
-                                }
-                            }
-                            let left_position = left_node
-                                .primary_styles()
-                                .map(|s| position_to_order(s.clone_position()))
-                                .unwrap_or(0);
-                            let right_position = right_node
-                                .primary_styles()
-                                .map(|s| position_to_order(s.clone_position()))
-                                .unwrap_or(0);
-
-                            left_position.cmp(&right_position)
-                        })
-                })
         }
+
+        self.resolve_paint_order(node_id);
     }
 }
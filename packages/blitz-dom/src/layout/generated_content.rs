@@ -0,0 +1,592 @@
+//! Generated content (the `content` of `::before` and `::after` pseudo elements)
+//!
+//! Counters and quotes depend on every element which precedes a pseudo element in the document, so
+//! generated content is resolved in a single pass over the flat tree (after styling and before box
+//! construction). The resolved content is stored on the originating element, and the content of
+//! pseudo elements which have already been constructed is rebuilt when it changes.
+//!
+//! The same pass maintains the `list-item` counter (including the counter of `<ol start reversed>` and
+//! `<li value>`) and resolves the markers of list items.
+//!
+//! See: <https://www.w3.org/TR/css-content-3/> and <https://www.w3.org/TR/css-lists-3/#auto-numbering>
+
+use blitz_traits::net::Request;
+use markup5ever::{QualName, local_name, ns};
+use style::properties::ComputedValues;
+use style::selector_parser::PseudoElement;
+use style::servo::url::ComputedUrl;
+use style::servo_arc::Arc;
+use style::shared_lock::StylesheetGuards;
+use style::stylist::RuleInclusion;
+use style::values::computed::{Content, ContentItem, Quotes};
+use style::values::generics::image::Image as StyloImage;
+use style::{Atom, data::ElementData as StyloElementData};
+use style_traits::values::ToCss;
+
+use super::counter_style::{CounterStyleRef, CounterStyles};
+use super::damage::ALL_DAMAGE;
+use super::list::{build_list_item_layout, marker_for_style};
+use crate::net::ImageHandler;
+use crate::node::{ElementData, GeneratedContent, Marker, NodeData};
+use crate::util::ImageType;
+use crate::{BaseDocument, Node};
+
+const IMG_NAME: QualName = QualName {
+    prefix: None,
+    ns: ns!(html),
+    local: local_name!("img"),
+};
+
+const MARKER_NAME: QualName = QualName {
+    prefix: None,
+    ns: ns!(html),
+    local: local_name!("span"),
+};
+
+/// A counter in scope at the current point of the traversal
+struct Counter {
+    name: Atom,
+    value: i32,
+    /// Whether the counter counts down (the `list-item` counter of `<ol reversed>`)
+    reversed: bool,
+}
+
+/// The values of the `list-item` counter which are set by HTML attributes
+#[derive(Default)]
+struct ListAttributes {
+    /// The value which the `list-item` counter is reset to, and whether it counts down
+    /// (`<ol start reversed>`)
+    reset: Option<(i32, bool)>,
+    /// The value which the `list-item` counter is set to (`<li value>`)
+    set: Option<i32>,
+}
+
+struct GeneratedContentState {
+    /// The counters in scope, innermost last. Multiple counters with the same name may be in scope
+    /// (nested counters, as used by `counters()`)
+    counters: Vec<Counter>,
+    /// The current nesting level of quotes
+    quote_depth: usize,
+    counter_styles: CounterStyles,
+    list_item: Atom,
+}
+
+impl GeneratedContentState {
+    fn counter(&self, name: &Atom) -> Option<&Counter> {
+        self.counters
+            .iter()
+            .rev()
+            .find(|counter| counter.name == *name)
+    }
+
+    /// Get the innermost counter with the given name, instantiating a new counter if none is in scope
+    fn counter_mut(&mut self, name: &Atom) -> &mut Counter {
+        let idx = match self
+            .counters
+            .iter()
+            .rposition(|counter| counter.name == *name)
+        {
+            Some(idx) => idx,
+            None => {
+                self.counters.push(Counter {
+                    name: name.clone(),
+                    value: 0,
+                    reversed: false,
+                });
+                self.counters.len() - 1
+            }
+        };
+        &mut self.counters[idx]
+    }
+
+    /// Apply an element's `counter-reset`, `counter-increment` and `counter-set` (in that order)
+    fn apply_counter_properties(&mut self, style: &ComputedValues, list_attrs: ListAttributes) {
+        let counters = style.get_counters();
+        for pair in counters.counter_reset.iter() {
+            let (value, reversed) = match list_attrs.reset {
+                Some(reset) if pair.name.0 == self.list_item => reset,
+                _ => (pair.value, false),
+            };
+            self.counters.push(Counter {
+                name: pair.name.0.clone(),
+                value,
+                reversed,
+            });
+        }
+
+        let mut increments_list_item = false;
+        for pair in counters.counter_increment.iter() {
+            increments_list_item |= pair.name.0 == self.list_item;
+            self.counter_mut(&pair.name.0).value += pair.value;
+        }
+        // List items implicitly increment the `list-item` counter
+        if !increments_list_item && style.get_box().display.is_list_item() {
+            let list_item = self.list_item.clone();
+            let counter = self.counter_mut(&list_item);
+            counter.value += if counter.reversed { -1 } else { 1 };
+        }
+
+        for pair in counters.counter_set.iter() {
+            self.counter_mut(&pair.name.0).value = pair.value;
+        }
+        if let Some(value) = list_attrs.set {
+            let list_item = self.list_item.clone();
+            self.counter_mut(&list_item).value = value;
+        }
+    }
+
+    fn format_counter(&self, value: i32, counter_style: &impl ToCss) -> String {
+        match CounterStyleRef::from_css(counter_style) {
+            CounterStyleRef::Name(name) => self.counter_styles.format(&name, value),
+            CounterStyleRef::String(string) => string,
+        }
+    }
+
+    /// Resolve the `content` of a pseudo element. Returns `None` if the pseudo element has no content
+    /// (and therefore does not generate a box).
+    fn resolve_content(
+        &mut self,
+        element: &ElementData,
+        style: &ComputedValues,
+    ) -> Option<Vec<GeneratedContent>> {
+        let Content::Items(item_data) = &style.get_counters().content else {
+            return None;
+        };
+
+        let mut content: Vec<GeneratedContent> = Vec::new();
+        for item in &item_data.items[0..item_data.alt_start] {
+            let text = match item {
+                ContentItem::String(text) => text.to_string(),
+                ContentItem::Counter(name, counter_style) => {
+                    let value = self.counter(&name.0).map(|c| c.value).unwrap_or(0);
+                    self.format_counter(value, counter_style)
+                }
+                ContentItem::Counters(name, separator, counter_style) => {
+                    let values: Vec<String> = self
+                        .counters
+                        .iter()
+                        .filter(|counter| counter.name == name.0)
+                        .map(|counter| self.format_counter(counter.value, counter_style))
+                        .collect();
+                    match values.is_empty() {
+                        true => self.format_counter(0, counter_style),
+                        false => values.join(&**separator),
+                    }
+                }
+                ContentItem::OpenQuote => {
+                    let quote = quote(style, self.quote_depth, true);
+                    self.quote_depth += 1;
+                    quote
+                }
+                ContentItem::CloseQuote => match self.quote_depth.checked_sub(1) {
+                    Some(depth) => {
+                        self.quote_depth = depth;
+                        quote(style, depth, false)
+                    }
+                    None => continue,
+                },
+                ContentItem::NoOpenQuote => {
+                    self.quote_depth += 1;
+                    continue;
+                }
+                ContentItem::NoCloseQuote => {
+                    self.quote_depth = self.quote_depth.saturating_sub(1);
+                    continue;
+                }
+                ContentItem::Attr(attr) => element
+                    .attrs()
+                    .iter()
+                    .find(|a| *a.name.local == *attr.attribute)
+                    .map(|a| a.value.clone())
+                    .unwrap_or_default(),
+                ContentItem::Image(image) => {
+                    if let StyloImage::Url(ComputedUrl::Valid(url)) = image {
+                        content.push(GeneratedContent::Image((**url).clone()));
+                    }
+                    continue;
+                }
+                #[allow(unreachable_patterns)]
+                _ => continue,
+            };
+
+            // Merge adjacent text items
+            match content.last_mut() {
+                Some(GeneratedContent::Text(prev)) => prev.push_str(&text),
+                _ => content.push(GeneratedContent::Text(text)),
+            }
+        }
+
+        Some(content)
+    }
+}
+
+/// The opening or closing quote for the given nesting depth
+fn quote(style: &ComputedValues, depth: usize, open: bool) -> String {
+    let (open_quote, close_quote) = match &style.get_list().quotes {
+        Quotes::QuoteList(list) => {
+            let Some(pair) = list.0.get(depth).or(list.0.last()) else {
+                return String::new();
+            };
+            (pair.opening.to_string(), pair.closing.to_string())
+        }
+        Quotes::Auto => match depth {
+            0 => ("\u{201C}".to_string(), "\u{201D}".to_string()),
+            _ => ("\u{2018}".to_string(), "\u{2019}".to_string()),
+        },
+    };
+    match open {
+        true => open_quote,
+        false => close_quote,
+    }
+}
+
+impl BaseDocument {
+    /// Resolve the generated content of every `::before` and `::after` pseudo element (and the
+    /// marker of every list item) in the document
+    pub(crate) fn resolve_generated_content(&mut self) {
+        let root_node_id = self.root_element().id;
+        let mut state = GeneratedContentState {
+            counters: Vec::new(),
+            quote_depth: 0,
+            counter_styles: CounterStyles::from_document(self),
+            list_item: Atom::from("list-item"),
+        };
+        self.resolve_generated_content_recursive(root_node_id, &mut state);
+    }
+
+    fn resolve_generated_content_recursive(
+        &mut self,
+        node_id: usize,
+        state: &mut GeneratedContentState,
+    ) {
+        let node = &self.nodes[node_id];
+        let Some(style) = node.primary_styles() else {
+            return;
+        };
+
+        // Elements which don't generate boxes don't affect counters
+        if style.clone_display().is_none() {
+            return;
+        }
+        let is_list_item = style.get_box().display.is_list_item();
+        let list_attrs = self.list_attributes(node_id);
+        state.apply_counter_properties(&style, list_attrs);
+        drop(style);
+
+        // Note: stylo stores the `::after` style first
+        let (after_style, before_style) = {
+            let style_data = node.stylo_element_data.borrow();
+            let pseudos = style_data.as_ref().map(|d| d.styles.pseudos.as_array());
+            (
+                pseudos.and_then(|p| p[0].clone()),
+                pseudos.and_then(|p| p[1].clone()),
+            )
+        };
+        let children = node.flat_tree_children().to_vec();
+
+        // Counters instantiated by this element's children (and pseudo elements) are scoped to
+        // this element
+        let scope_start = state.counters.len();
+
+        self.resolve_list_marker(node_id, is_list_item, state);
+
+        let before_content = self.resolve_pseudo_content(node_id, before_style, state);
+        self.set_generated_content(node_id, 1, before_content);
+
+        for child_id in children {
+            self.resolve_generated_content_recursive(child_id, state);
+        }
+
+        let after_content = self.resolve_pseudo_content(node_id, after_style, state);
+        self.set_generated_content(node_id, 0, after_content);
+
+        state.counters.truncate(scope_start);
+    }
+
+    /// The values of the `list-item` counter set by the `start` and `reversed` attributes of `<ol>`
+    /// and the `value` attribute of `<li>`
+    fn list_attributes(&self, node_id: usize) -> ListAttributes {
+        let Some(element) = self.nodes[node_id].element_data() else {
+            return ListAttributes::default();
+        };
+        match element.name.local {
+            local_name!("ol") => {
+                let start: Option<i32> = element.attr_parsed(local_name!("start"));
+                let reversed = element.attr(local_name!("reversed")).is_some();
+                let value = match reversed {
+                    true => start.unwrap_or_else(|| self.count_list_items(node_id)) + 1,
+                    false => start.unwrap_or(1) - 1,
+                };
+                ListAttributes {
+                    reset: Some((value, reversed)),
+                    set: None,
+                }
+            }
+            local_name!("li") => ListAttributes {
+                reset: None,
+                set: element.attr_parsed(local_name!("value")),
+            },
+            _ => ListAttributes::default(),
+        }
+    }
+
+    /// Count the list items whose `list-item` counter is instantiated by a list element
+    fn count_list_items(&self, list_node_id: usize) -> i32 {
+        let mut count = 0;
+        for child_id in self.nodes[list_node_id].flat_tree_children() {
+            let Some(style) = self.nodes[*child_id].primary_styles() else {
+                continue;
+            };
+            if style.clone_display().is_none() {
+                continue;
+            }
+            if style.get_box().display.is_list_item() {
+                count += 1;
+            }
+            let resets_list_item = style
+                .get_counters()
+                .counter_reset
+                .iter()
+                .any(|pair| &*pair.name.0 == "list-item");
+            if !resets_list_item {
+                count += self.count_list_items(*child_id);
+            }
+        }
+        count
+    }
+
+    /// Resolve the marker of a list item from its `::marker` pseudo element, or its
+    /// `list-style-type`, and the current value of the `list-item` counter
+    fn resolve_list_marker(
+        &mut self,
+        node_id: usize,
+        is_list_item: bool,
+        state: &mut GeneratedContentState,
+    ) {
+        let restyled = self.nodes[node_id]
+            .damage()
+            .is_none_or(|damage| !damage.is_empty());
+
+        // Update the `::marker` pseudo element (which is computed lazily, as stylo doesn't compute
+        // it eagerly)
+        if !is_list_item {
+            self.sync_marker_node(node_id, None);
+        } else if restyled || self.nodes[node_id].marker.is_none() {
+            let node = &self.nodes[node_id];
+            let style = node.primary_styles().unwrap();
+            let read_guard = self.guard.read();
+            let guards = StylesheetGuards::same(&read_guard);
+            let marker_style = self.stylist.lazily_compute_pseudo_element_style(
+                &guards,
+                node,
+                &PseudoElement::Marker,
+                RuleInclusion::All,
+                &style,
+                false,
+                None,
+            );
+            drop(style);
+            drop(read_guard);
+            self.sync_marker_node(node_id, marker_style);
+        }
+
+        let marker = match is_list_item {
+            true => self.marker_for_list_item(node_id, state),
+            false => None,
+        };
+
+        // Lay out the marker if it (or the list item's styles) changed
+        let Some(element) = self.nodes[node_id].element_data_mut() else {
+            return;
+        };
+        let prev_marker = element.list_item_data.as_ref().map(|data| &data.marker);
+        let changed = prev_marker != marker.as_ref();
+        if !changed && !restyled {
+            return;
+        }
+        element.list_item_data = None;
+        if let Some(marker) = marker {
+            let layout = build_list_item_layout(self, node_id, marker);
+            let element = self.nodes[node_id].element_data_mut().unwrap();
+            element.list_item_data = Some(Box::new(layout));
+        }
+        if changed {
+            self.nodes[node_id].insert_damage(ALL_DAMAGE);
+        }
+    }
+
+    fn marker_for_list_item(
+        &self,
+        node_id: usize,
+        state: &mut GeneratedContentState,
+    ) -> Option<Marker> {
+        let node = &self.nodes[node_id];
+        let style = node.primary_styles()?;
+        let value = state
+            .counter(&state.list_item)
+            .map(|c| c.value)
+            .unwrap_or(0);
+
+        // The `content` of the `::marker` pseudo element replaces the list-style-type marker
+        if let Some(marker_style) = node.marker.and_then(|id| self.nodes[id].primary_styles()) {
+            match &marker_style.get_counters().content {
+                Content::Normal => {}
+                Content::None => return None,
+                Content::Items(_) => {
+                    let content = state.resolve_content(node.element_data()?, &marker_style)?;
+                    let text: String = content
+                        .iter()
+                        .filter_map(|item| match item {
+                            GeneratedContent::Text(text) => Some(text.as_str()),
+                            GeneratedContent::Image(_) => None,
+                        })
+                        .collect();
+                    return Some(Marker::String(text));
+                }
+            }
+        }
+
+        let marker = marker_for_style(&state.counter_styles, &style.clone_list_style_type(), value);
+
+        // The list-style-type marker is used as a fallback if the list-style-image fails to load,
+        // so a list item with an image always has a marker
+        let has_image = node
+            .element_data()
+            .is_some_and(|el| el.list_style_image.is_some());
+        match (marker, has_image) {
+            (None, true) => Some(Marker::String(String::new())),
+            (marker, _) => marker,
+        }
+    }
+
+    /// Create, update or remove the node holding the styles of a list item's `::marker`
+    fn sync_marker_node(&mut self, node_id: usize, marker_style: Option<Arc<ComputedValues>>) {
+        let marker_node_id = self.nodes[node_id].marker;
+        match (marker_node_id, marker_style) {
+            (None, None) => {}
+            (Some(marker_node_id), None) => {
+                self.remove_and_drop_pe(marker_node_id);
+                self.nodes[node_id].marker = None;
+            }
+            (None, Some(marker_style)) => {
+                let new_node_id = self.create_node(NodeData::AnonymousBlock(ElementData::new(
+                    MARKER_NAME,
+                    Vec::new(),
+                )));
+                self.nodes[new_node_id].parent = Some(node_id);
+
+                let mut element_data = StyloElementData {
+                    damage: ALL_DAMAGE,
+                    ..Default::default()
+                };
+                element_data.styles.primary = Some(marker_style);
+                element_data.set_restyled();
+                *self.nodes[new_node_id].stylo_element_data.borrow_mut() = Some(element_data);
+
+                self.nodes[node_id].marker = Some(new_node_id);
+            }
+            (Some(marker_node_id), Some(marker_style)) => {
+                let mut element_data = self.nodes[marker_node_id].stylo_element_data.borrow_mut();
+                let element_data = element_data.as_mut().unwrap();
+                let primary_styles = &mut element_data.styles.primary;
+                if primary_styles
+                    .as_ref()
+                    .is_none_or(|style| !Arc::ptr_eq(style, &marker_style))
+                {
+                    *primary_styles = Some(marker_style);
+                    element_data.damage.insert(ALL_DAMAGE);
+                    element_data.set_restyled();
+                }
+            }
+        }
+    }
+
+    fn resolve_pseudo_content(
+        &self,
+        node_id: usize,
+        pe_style: Option<Arc<ComputedValues>>,
+        state: &mut GeneratedContentState,
+    ) -> Option<Vec<GeneratedContent>> {
+        let pe_style = pe_style?;
+        let element = self.nodes[node_id].element_data()?;
+        state.apply_counter_properties(&pe_style, ListAttributes::default());
+        state.resolve_content(element, &pe_style)
+    }
+
+    /// Store the resolved generated content of a pseudo element, rebuilding the pseudo element's
+    /// children if it has already been constructed and its content has changed
+    fn set_generated_content(
+        &mut self,
+        node_id: usize,
+        pe_idx: usize,
+        content: Option<Vec<GeneratedContent>>,
+    ) {
+        let Some(element) = self.nodes[node_id].element_data_mut() else {
+            return;
+        };
+        if element.generated_content[pe_idx] == content {
+            return;
+        }
+        element.generated_content[pe_idx] = content.clone();
+
+        if let Some(pe_node_id) = self.nodes[node_id].pe_by_index(pe_idx) {
+            let children = std::mem::take(&mut self.nodes[pe_node_id].children);
+            for child_id in children {
+                self.remove_and_drop_pe(child_id);
+            }
+            build_generated_content(self, pe_node_id, content.as_deref().unwrap_or_default());
+            self.nodes[pe_node_id].insert_damage(ALL_DAMAGE);
+            self.nodes[node_id].insert_damage(ALL_DAMAGE);
+        }
+    }
+}
+
+/// Create the children of a pseudo element from its generated content. The pseudo element's styles
+/// must already be set.
+pub(crate) fn build_generated_content(
+    doc: &mut BaseDocument,
+    pe_node_id: usize,
+    content: &[GeneratedContent],
+) {
+    for item in content {
+        let child_id = match item {
+            GeneratedContent::Text(text) => doc.create_text_node(text),
+            GeneratedContent::Image(url) => {
+                let child_id = doc.create_node(NodeData::AnonymousBlock(ElementData::new(
+                    IMG_NAME,
+                    Vec::new(),
+                )));
+
+                // Images inherit their styles from the pseudo element
+                let parent_style = doc.nodes[pe_node_id].primary_styles().unwrap();
+                let read_guard = doc.guard.read();
+                let guards = StylesheetGuards::same(&read_guard);
+                let style = doc.stylist.style_for_anonymous::<&Node>(
+                    &guards,
+                    &PseudoElement::ServoAnonymousBox,
+                    &parent_style,
+                );
+                drop(parent_style);
+                drop(read_guard);
+
+                let mut stylo_element_data = StyloElementData {
+                    damage: ALL_DAMAGE,
+                    ..Default::default()
+                };
+                stylo_element_data.styles.primary = Some(style);
+                stylo_element_data.set_restyled();
+                *doc.nodes[child_id].stylo_element_data.borrow_mut() = Some(stylo_element_data);
+
+                doc.net_provider.fetch(
+                    doc.id(),
+                    Request::get(url.clone()),
+                    Box::new(ImageHandler::new(child_id, ImageType::Image)),
+                );
+
+                child_id
+            }
+        };
+        doc.nodes[child_id].parent = Some(pe_node_id);
+        doc.nodes[pe_node_id].children.push(child_id);
+    }
+}
@@ -1,17 +1,22 @@
+use crate::node::TextBrush;
 use parley::AlignmentOptions;
+use parley::layout::{Alignment, LineMetrics};
 use taffy::{
-    AvailableSpace, LayoutPartialTree as _, MaybeMath as _, MaybeResolve as _, NodeId, Position,
-    ResolveOrZero as _, Size, compute_leaf_layout,
+    AvailableSpace, BlockContext, Clear, LayoutPartialTree as _, MaybeMath as _, MaybeResolve as _,
+    NodeId, Point, Position, Rect, ResolveOrZero as _, Size, compute_leaf_layout,
 };
 
 use super::resolve_calc_value;
 use crate::BaseDocument;
+use style::computed_values::position::T as StyloPosition;
+use style::values::specified::box_::{DisplayInside, DisplayOutside};
 
 impl BaseDocument {
     pub(crate) fn compute_inline_layout(
         &mut self,
         node_id: usize,
         inputs: taffy::tree::LayoutInput,
+        block_ctx: Option<&mut BlockContext<'_>>,
     ) -> taffy::LayoutOutput {
         let scale = self.viewport.scale();
 
@@ -111,6 +116,16 @@ impl BaseDocument {
                             - pbw
                     });
 
+                // Lines beside floats are shortened to fit into the space beside them
+                let floats = block_ctx
+                    .as_deref()
+                    .filter(|block_ctx| block_ctx.has_active_floats(container_pb.top));
+                // Space inserted above lines to push them onto the next page (when paginated)
+                let line_struts = self
+                    .pagination
+                    .as_ref()
+                    .and_then(|pagination| pagination.line_struts(node_id));
+
                 if inputs.run_mode == taffy::RunMode::ComputeSize {
                     // Height SHOULD be ignored if RequestedAxis is Horizontal, but currently that doesn't
                     // always seem to be the case. So we perform layout to obtain a height every time. We
@@ -122,23 +137,56 @@ impl BaseDocument {
                     //
                     // FIXME: avoid the need to clone the layout each time
                     let mut layout = inline_layout.clone();
-                    layout.layout.break_all_lines(Some(width));
+                    let line_slots = match floats {
+                        Some(block_ctx) => break_lines_around_floats(
+                            &mut layout.layout,
+                            block_ctx,
+                            container_pb,
+                            width / scale,
+                        ),
+                        None => {
+                            layout.layout.break_all_lines(Some(width));
+                            Vec::new()
+                        }
+                    };
+                    let line_slots = match line_struts {
+                        Some(struts) => push_lines_down(line_slots, &layout.layout, struts, width),
+                        None => line_slots,
+                    };
 
                     return taffy::Size {
                         width: width.ceil() / scale,
-                        height: layout.layout.height() / scale,
+                        height: lines_height(&layout.layout, &line_slots),
                     };
                 }
 
                 // Perform inline layout
-                inline_layout.layout.break_all_lines(Some(width));
+                let line_slots = match floats {
+                    Some(block_ctx) => break_lines_around_floats(
+                        &mut inline_layout.layout,
+                        block_ctx,
+                        container_pb,
+                        width / scale,
+                    ),
+                    None => {
+                        inline_layout.layout.break_all_lines(Some(width));
+                        Vec::new()
+                    }
+                };
+                let line_slots = match line_struts {
+                    Some(struts) => {
+                        push_lines_down(line_slots, &inline_layout.layout, struts, width)
+                    }
+                    None => line_slots,
+                };
 
                 let alignment = self.nodes[node_id]
                     .primary_styles()
                     .map(|s| {
-                        use parley::layout::Alignment;
                         use style::values::specified::TextAlignKeyword;
 
+                        // The base direction of the paragraph is set from `direction` when the
+                        // layout is built, so Parley resolves start and end in the same way as CSS
                         match s.clone_text_align() {
                             TextAlignKeyword::Start => Alignment::Start,
                             TextAlignKeyword::Left => Alignment::Left,
@@ -151,7 +199,15 @@ impl BaseDocument {
                             TextAlignKeyword::MozRight => Alignment::Right,
                         }
                     })
-                    .unwrap_or(parley::layout::Alignment::Start);
+                    .unwrap_or(Alignment::Start);
+
+                // Parley can only justify lines to a single width, so paragraphs with lines
+                // shortened by floats are start-aligned instead
+                let is_shortened = line_slots.iter().any(|slot| slot.width < width / scale);
+                let alignment = match alignment {
+                    Alignment::Justify if is_shortened => Alignment::Start,
+                    alignment => alignment,
+                };
 
                 inline_layout.layout.align(
                     Some(width),
@@ -161,8 +217,28 @@ impl BaseDocument {
                     },
                 );
 
+                // Lines are aligned within the full width by Parley, so lines beside floats are
+                // moved into (and re-aligned within) their own line box
+                let is_rtl = inline_layout.layout.is_rtl();
+                inline_layout.line_offsets = inline_layout
+                    .layout
+                    .lines()
+                    .zip(&line_slots)
+                    .map(|(line, slot)| {
+                        let metrics = line.metrics();
+                        let realignment =
+                            alignment_offset(metrics, alignment, is_rtl, slot.width * scale)
+                                - alignment_offset(metrics, alignment, is_rtl, width);
+                        Point {
+                            x: slot.x + realignment / scale,
+                            y: slot.y,
+                        }
+                    })
+                    .collect();
+
                 // Store sizes and positions of inline boxes
-                for line in inline_layout.layout.lines() {
+                for (line_index, line) in inline_layout.layout.lines().enumerate() {
+                    let line_offset = inline_layout.line_offset(line_index);
                     for item in line.items() {
                         if let parley::layout::PositionedLayoutItem::InlineBox(ibox) = item {
                             let node = &mut self.nodes[ibox.id as usize];
@@ -179,55 +255,23 @@ impl BaseDocument {
                                 .margin
                                 .resolve_or_zero(child_inputs.parent_size, resolve_calc_value);
 
-                            // Resolve inset
-                            let left = node
-                                .style
-                                .inset
-                                .left
-                                .maybe_resolve(child_inputs.parent_size.width, resolve_calc_value);
-                            let right = node
-                                .style
-                                .inset
-                                .right
-                                .maybe_resolve(child_inputs.parent_size.width, resolve_calc_value);
-                            let top = node
-                                .style
-                                .inset
-                                .top
-                                .maybe_resolve(child_inputs.parent_size.height, resolve_calc_value);
-                            let bottom = node
-                                .style
-                                .inset
-                                .bottom
-                                .maybe_resolve(child_inputs.parent_size.height, resolve_calc_value);
-
                             if node.style.position == Position::Absolute {
                                 let output =
                                     self.compute_child_layout(NodeId::from(ibox.id), child_inputs);
 
+                                // Position the box at its static position. The box is then resolved
+                                // against its containing block once the rest of the tree has been laid
+                                // out (see `resolve_inline_absolute_positions`)
                                 let layout = &mut self.nodes[ibox.id as usize].unrounded_layout;
                                 layout.size = output.size;
-
-                                // TODO: Implement absolute positioning
-                                layout.location.x = left
-                                    .or_else(|| {
-                                        child_inputs
-                                            .parent_size
-                                            .width
-                                            .zip(right)
-                                            .map(|(w, r)| w - r)
-                                    })
-                                    .unwrap_or((ibox.x / scale) + margin.left + container_pb.left);
-                                layout.location.y = top
-                                    .or_else(|| {
-                                        child_inputs
-                                            .parent_size
-                                            .height
-                                            .zip(bottom)
-                                            .map(|(w, r)| w - r)
-                                    })
-                                    .unwrap_or((ibox.y / scale) + margin.top + container_pb.top);
-
+                                layout.location.x = (ibox.x / scale)
+                                    + margin.left
+                                    + container_pb.left
+                                    + line_offset.x;
+                                layout.location.y = (ibox.y / scale)
+                                    + margin.top
+                                    + container_pb.top
+                                    + line_offset.y;
                                 layout.padding = padding; //.map(|p| p / scale);
                                 layout.border = border; //.map(|p| p / scale);
                             } else {
@@ -236,10 +280,14 @@ impl BaseDocument {
                                     (ibox.width / scale) - margin.left - margin.right;
                                 layout.size.height =
                                     (ibox.height / scale) - margin.top - margin.bottom;
-                                layout.location.x =
-                                    (ibox.x / scale) + margin.left + container_pb.left;
-                                layout.location.y =
-                                    (ibox.y / scale) + margin.top + container_pb.top;
+                                layout.location.x = (ibox.x / scale)
+                                    + margin.left
+                                    + container_pb.left
+                                    + line_offset.x;
+                                layout.location.y = (ibox.y / scale)
+                                    + margin.top
+                                    + container_pb.top
+                                    + line_offset.y;
                                 layout.padding = padding; //.map(|p| p / scale);
                                 layout.border = border; //.map(|p| p / scale);
                             }
@@ -255,7 +303,7 @@ impl BaseDocument {
 
                 inputs.known_dimensions.unwrap_or(taffy::Size {
                     width: inline_layout.layout.width().ceil() / scale,
-                    height: inline_layout.layout.height() / scale,
+                    height: lines_height(&inline_layout.layout, &line_slots),
                 })
             },
         );
@@ -269,4 +317,542 @@ impl BaseDocument {
 
         output
     }
+
+    /// Resolve the position (and where insets determine it, the size) of absolutely positioned
+    /// boxes within inline formatting contexts against their containing block.
+    ///
+    /// Inline layout can only place such boxes at their static position, because the position of
+    /// the inline root relative to its ancestors isn't known until the rest of the tree has been laid
+    /// out. This must therefore be run after the root layout has been computed but before rounding.
+    ///
+    /// The containing block of an absolutely positioned box is its nearest positioned ancestor,
+    /// which may be an inline box within the same inline formatting context (in which case it is
+    /// the box which spans from the start of the ancestor's first fragment to the end of its last
+    /// fragment). Fixed boxes are positioned against the viewport at its initial scroll position,
+    /// and are kept in place when scrolling by their sticky offset (see the `sticky` module).
+    pub(crate) fn resolve_inline_absolute_positions(&mut self, root_node_id: usize) {
+        let size = self.stylist.device().au_viewport_size();
+        let initial_containing_block = ContainingBlock {
+            origin: Point::ZERO,
+            size: Size {
+                width: size.width.to_f32_px(),
+                height: size.height.to_f32_px(),
+            },
+        }
+        .translate(self.nodes[root_node_id].unrounded_layout.location);
+
+        self.resolve_inline_absolute_positions_recursive(
+            root_node_id,
+            initial_containing_block,
+            initial_containing_block,
+        );
+    }
+
+    /// `absolute_cb` and `fixed_cb` are relative to the border box of `node_id`
+    fn resolve_inline_absolute_positions_recursive(
+        &mut self,
+        node_id: usize,
+        absolute_cb: ContainingBlock,
+        fixed_cb: ContainingBlock,
+    ) {
+        let node = &self.nodes[node_id];
+
+        // Positioned boxes are the containing block for their absolutely positioned descendants
+        let is_positioned = node
+            .primary_styles()
+            .is_some_and(|s| s.clone_position() != StyloPosition::Static);
+        let absolute_cb = match is_positioned {
+            true => {
+                let layout = &node.unrounded_layout;
+                ContainingBlock {
+                    origin: Point {
+                        x: layout.border.left,
+                        y: layout.border.top,
+                    },
+                    size: Size {
+                        width: layout.size.width - layout.border.left - layout.border.right,
+                        height: layout.size.height - layout.border.top - layout.border.bottom,
+                    },
+                }
+            }
+            false => absolute_cb,
+        };
+
+        if node.flags.is_inline_root() {
+            self.resolve_absolute_inline_boxes(node_id, absolute_cb, fixed_cb);
+        }
+
+        let Some(children) = self.nodes[node_id].layout_children.borrow().clone() else {
+            return;
+        };
+        for child_id in children {
+            let location = self.nodes[child_id].unrounded_layout.location;
+            self.resolve_inline_absolute_positions_recursive(
+                child_id,
+                absolute_cb.translate(location),
+                fixed_cb.translate(location),
+            );
+        }
+    }
+
+    fn resolve_absolute_inline_boxes(
+        &mut self,
+        node_id: usize,
+        absolute_cb: ContainingBlock,
+        fixed_cb: ContainingBlock,
+    ) {
+        let scale = self.viewport.scale();
+        let container = &self.nodes[node_id].unrounded_layout;
+        let container_pb = container.padding + container.border;
+
+        let Some(inline_layout) = self.nodes[node_id]
+            .element_data()
+            .and_then(|el| el.inline_layout_data.as_ref())
+        else {
+            return;
+        };
+        let content_offset = Point {
+            x: container_pb.left,
+            y: container_pb.top,
+        };
+        let static_positions: Vec<(usize, Point<f32>)> = inline_layout
+            .layout
+            .lines()
+            .enumerate()
+            .flat_map(|(line_index, line)| {
+                let line_offset = inline_layout.line_offset(line_index);
+                line.items().filter_map(move |item| match item {
+                    parley::layout::PositionedLayoutItem::InlineBox(ibox) => {
+                        Some((ibox, line_offset))
+                    }
+                    _ => None,
+                })
+            })
+            .filter(|(ibox, _)| self.nodes[ibox.id as usize].style.position == Position::Absolute)
+            .map(|(ibox, line_offset)| {
+                let position = Point {
+                    x: (ibox.x / scale) + content_offset.x + line_offset.x,
+                    y: (ibox.y / scale) + content_offset.y + line_offset.y,
+                };
+                (ibox.id as usize, position)
+            })
+            .collect();
+
+        for (child_id, static_position) in static_positions {
+            let child = &self.nodes[child_id];
+            let is_fixed = child
+                .primary_styles()
+                .is_some_and(|s| s.clone_position() == StyloPosition::Fixed);
+            let cb = match is_fixed {
+                true => fixed_cb,
+                false => self
+                    .positioned_inline_ancestor(node_id, child_id)
+                    .and_then(|ancestor_id| {
+                        self.inline_fragments_bounds(node_id, ancestor_id, content_offset)
+                    })
+                    .unwrap_or(absolute_cb),
+            };
+
+            let style = &child.style;
+            let cb_size = cb.size.map(Some);
+            let margin = style
+                .margin
+                .resolve_or_zero(cb_size.width, resolve_calc_value);
+            let left = style
+                .inset
+                .left
+                .maybe_resolve(cb_size.width, resolve_calc_value);
+            let right = style
+                .inset
+                .right
+                .maybe_resolve(cb_size.width, resolve_calc_value);
+            let top = style
+                .inset
+                .top
+                .maybe_resolve(cb_size.height, resolve_calc_value);
+            let bottom = style
+                .inset
+                .bottom
+                .maybe_resolve(cb_size.height, resolve_calc_value);
+
+            // If both insets in an axis are set and the size is auto then the box is stretched
+            // to fill the containing block
+            let known_dimensions = Size {
+                width: left
+                    .zip(right)
+                    .filter(|_| style.size.width.is_auto())
+                    .map(|(l, r)| (cb.size.width - l - r - margin.left - margin.right).max(0.0)),
+                height: top
+                    .zip(bottom)
+                    .filter(|_| style.size.height.is_auto())
+                    .map(|(t, b)| (cb.size.height - t - b - margin.top - margin.bottom).max(0.0)),
+            };
+
+            let output = self.compute_child_layout(
+                NodeId::from(child_id),
+                taffy::tree::LayoutInput {
+                    known_dimensions,
+                    parent_size: cb_size,
+                    available_space: cb.size.map(AvailableSpace::Definite),
+                    run_mode: taffy::RunMode::PerformLayout,
+                    sizing_mode: taffy::SizingMode::InherentSize,
+                    axis: taffy::RequestedAxis::Both,
+                    vertical_margins_are_collapsible: taffy::Line::FALSE,
+                },
+            );
+            let size = output.size;
+
+            let x = match (left, right) {
+                (Some(left), _) => cb.origin.x + left + margin.left,
+                (None, Some(right)) => {
+                    cb.origin.x + cb.size.width - right - margin.right - size.width
+                }
+                (None, None) => static_position.x + margin.left,
+            };
+            let y = match (top, bottom) {
+                (Some(top), _) => cb.origin.y + top + margin.top,
+                (None, Some(bottom)) => {
+                    cb.origin.y + cb.size.height - bottom - margin.bottom - size.height
+                }
+                (None, None) => static_position.y + margin.top,
+            };
+
+            let layout = &mut self.nodes[child_id].unrounded_layout;
+            layout.size = size;
+            layout.location = Point { x, y };
+        }
+    }
+
+    /// The nearest positioned ancestor of a box within the inline formatting context of
+    /// `inline_root_id`, if it is an inline box in the same inline formatting context
+    fn positioned_inline_ancestor(&self, inline_root_id: usize, node_id: usize) -> Option<usize> {
+        let mut ancestor_id = self.nodes[node_id].parent?;
+        while ancestor_id != inline_root_id {
+            let ancestor = &self.nodes[ancestor_id];
+            let styles = ancestor.primary_styles()?;
+            let display = styles.clone_display();
+            match (display.outside(), display.inside()) {
+                (DisplayOutside::Inline, DisplayInside::Flow) => {}
+                (DisplayOutside::None, DisplayInside::Contents) => {}
+                _ => return None,
+            }
+            if styles.clone_position() != StyloPosition::Static {
+                return Some(ancestor_id);
+            }
+            ancestor_id = ancestor.parent?;
+        }
+        None
+    }
+
+    /// The box which spans from the start of the first fragment to the end of the last fragment of
+    /// an inline box (relative to the border box of its inline root). Returns `None` if the inline
+    /// box has no fragments.
+    fn inline_fragments_bounds(
+        &self,
+        inline_root_id: usize,
+        element_id: usize,
+        content_offset: Point<f32>,
+    ) -> Option<ContainingBlock> {
+        let inline_layout = self.nodes[inline_root_id]
+            .element_data()?
+            .inline_layout_data
+            .as_ref()?;
+        let is_inside_element = |mut node_id: usize| loop {
+            if node_id == element_id {
+                return true;
+            }
+            match self.nodes[node_id].parent {
+                Some(parent_id) if node_id != inline_root_id => node_id = parent_id,
+                _ => return false,
+            }
+        };
+
+        // The bounds (x0, y0, x1, y1) of the fragments on the first and last lines
+        let mut first: Option<[f32; 4]> = None;
+        let mut last: Option<[f32; 4]> = None;
+        let scale = self.viewport.scale();
+        for (line_index, line) in inline_layout.layout.lines().enumerate() {
+            let line_offset = inline_layout.line_offset(line_index);
+            let (dx, dy) = (line_offset.x * scale, line_offset.y * scale);
+            let mut line_bounds: Option<[f32; 4]> = None;
+            for item in line.items() {
+                let bounds = match item {
+                    parley::layout::PositionedLayoutItem::GlyphRun(glyph_run) => {
+                        if !is_inside_element(glyph_run.style().brush.id) {
+                            continue;
+                        }
+                        let metrics = glyph_run.run().metrics();
+                        let baseline = glyph_run.baseline();
+                        [
+                            glyph_run.offset(),
+                            baseline - metrics.ascent,
+                            glyph_run.offset() + glyph_run.advance(),
+                            baseline + metrics.descent,
+                        ]
+                    }
+                    parley::layout::PositionedLayoutItem::InlineBox(ibox) => {
+                        let id = ibox.id as usize;
+                        if self.nodes[id].style.position == Position::Absolute
+                            || !is_inside_element(id)
+                        {
+                            continue;
+                        }
+                        [ibox.x, ibox.y, ibox.x + ibox.width, ibox.y + ibox.height]
+                    }
+                };
+                line_bounds = Some(match line_bounds {
+                    Some([x0, y0, x1, y1]) => [
+                        x0.min(bounds[0]),
+                        y0.min(bounds[1]),
+                        x1.max(bounds[2]),
+                        y1.max(bounds[3]),
+                    ],
+                    None => bounds,
+                });
+            }
+            if let Some([x0, y0, x1, y1]) = line_bounds {
+                let bounds = [x0 + dx, y0 + dy, x1 + dx, y1 + dy];
+                first.get_or_insert(bounds);
+                last = Some(bounds);
+            }
+        }
+
+        let ([x0, y0, _, _], [_, _, x1, y1]) = (first?, last?);
+        Some(ContainingBlock {
+            origin: Point {
+                x: x0 / scale + content_offset.x,
+                y: y0 / scale + content_offset.y,
+            },
+            size: Size {
+                width: (x1 - x0).max(0.0) / scale,
+                height: (y1 - y0).max(0.0) / scale,
+            },
+        })
+    }
+}
+
+/// The space given to a line box beside floats (in CSS pixels)
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct LineSlot {
+    /// The offset of the line box from the start of the content box
+    x: f32,
+    /// How far the line box is moved down (in addition to the lines before it) to clear floats
+    /// which leave too little room beside them, or onto the next page when paginated
+    y: f32,
+    width: f32,
+}
+
+/// The maximum number of times the lines of a paragraph are broken to place them beside floats
+const MAX_LINE_BREAKING_PASSES: usize = 4;
+
+/// Break lines such that each line box is shortened to fit into the space left beside the floats
+/// which intrude into the inline formatting context, returning the space given to each line box.
+///
+/// Parley places each line directly below the previous one, and only knows the heights of the
+/// lines once all of them have been broken. So the lines are broken repeatedly, with each pass
+/// placing the lines using the line heights of the previous pass, and moving lines which
+/// overflowed the space beside the floats in the previous pass below those floats. This stops once
+/// the lines are placed in the same spaces as in the previous pass.
+///
+/// `container_pb` is the offset of the content box from the border box of the inline root (the
+/// coordinate space of `block_ctx`) and `width` the width of the content box, in CSS pixels.
+fn break_lines_around_floats(
+    layout: &mut parley::Layout<TextBrush>,
+    block_ctx: &BlockContext<'_>,
+    container_pb: Rect<f32>,
+    width: f32,
+) -> Vec<LineSlot> {
+    let scale = layout.scale();
+    let mut slots: Vec<LineSlot> = Vec::new();
+    // The heights of the lines and the widths they need, as found by the previous pass
+    let mut line_heights: Vec<f32> = Vec::new();
+    let mut min_widths: Vec<f32> = Vec::new();
+
+    for _ in 0..MAX_LINE_BREAKING_PASSES {
+        let mut next_slots = Vec::new();
+        let mut line_top = 0.0;
+        let mut shift = 0.0;
+        let mut breaker = layout.break_lines();
+        loop {
+            let index = next_slots.len();
+            let line_height = line_heights
+                .get(index)
+                .or(line_heights.last())
+                .copied()
+                .unwrap_or(0.0);
+            let min_width = min_widths.get(index).copied().unwrap_or(0.0);
+            let (slot, top) = line_slot(
+                block_ctx,
+                container_pb,
+                width,
+                line_top,
+                line_height,
+                min_width,
+            );
+            if breaker.break_next(slot.width * scale).is_none() {
+                break;
+            }
+            shift += top - line_top;
+            next_slots.push(LineSlot { y: shift, ..slot });
+            line_top = top + line_height;
+        }
+        breaker.finish();
+
+        // Lines whose content doesn't fit into their line box need a wider space
+        line_heights.clear();
+        for (index, (line, slot)) in layout.lines().zip(&next_slots).enumerate() {
+            let metrics = line.metrics();
+            line_heights.push(metrics.line_height / scale);
+            let content_width = (metrics.advance - metrics.trailing_whitespace) / scale;
+            if content_width > slot.width {
+                if index >= min_widths.len() {
+                    min_widths.resize(index + 1, 0.0);
+                }
+                min_widths[index] = min_widths[index].max(content_width);
+            }
+        }
+
+        let is_settled = next_slots == slots;
+        slots = next_slots;
+        if is_settled {
+            break;
+        }
+    }
+
+    slots
+}
+
+/// Find the space for a line box of `line_height` which is at least `min_width` wide, starting at
+/// `top` (relative to the content box). Returns the space beside the floats and the top of the
+/// line box, which is moved below floats that leave too little room beside them. All values are in
+/// CSS pixels.
+fn line_slot(
+    block_ctx: &BlockContext<'_>,
+    container_pb: Rect<f32>,
+    width: f32,
+    top: f32,
+    line_height: f32,
+    min_width: f32,
+) -> (LineSlot, f32) {
+    let content_left = container_pb.left;
+    let content_right = container_pb.left + width;
+
+    let mut top = top;
+    loop {
+        // The line box must fit beside every float that it is level with
+        let min_y = container_pb.top + top;
+        let first = block_ctx.find_content_slot(min_y, Clear::None, None);
+        let mut left = first.x.max(content_left);
+        let mut right = (first.x + first.width).min(content_right);
+        let mut slot = first;
+        while let Some(segment_id) = slot.segment_id {
+            slot = block_ctx.find_content_slot(min_y, Clear::None, Some(segment_id));
+            if slot.y >= min_y + line_height {
+                break;
+            }
+            left = left.max(slot.x);
+            right = right.min(slot.x + slot.width);
+        }
+
+        let line_width = (right - left).max(0.0);
+        if line_width >= min_width || first.segment_id.is_none() {
+            let slot = LineSlot {
+                x: left - content_left,
+                y: 0.0,
+                width: line_width,
+            };
+            return (slot, top);
+        }
+
+        // If there is no room beside any of the floats then the line goes below all of them
+        let next = block_ctx.find_content_slot(min_y, Clear::None, first.segment_id);
+        if next.segment_id.is_none() {
+            let cleared = block_ctx
+                .cleared_threshold(Clear::Both)
+                .map_or(top, |threshold| (threshold - container_pb.top).max(top));
+            let slot = LineSlot {
+                x: 0.0,
+                y: 0.0,
+                width,
+            };
+            return (slot, cleared);
+        }
+        top = next.y - container_pb.top;
+    }
+}
+
+/// Move lines down by the space inserted above them to push them onto the next page (see
+/// `paged_media`). `struts` is the space inserted above each line and `width` the width of the
+/// content box (in layout units).
+fn push_lines_down(
+    mut line_slots: Vec<LineSlot>,
+    layout: &parley::Layout<TextBrush>,
+    struts: &[f32],
+    width: f32,
+) -> Vec<LineSlot> {
+    let line_count = layout.lines().count();
+    if line_slots.is_empty() {
+        let slot = LineSlot {
+            x: 0.0,
+            y: 0.0,
+            width: width / layout.scale(),
+        };
+        line_slots = vec![slot; line_count];
+    }
+
+    let mut shift = 0.0;
+    for (slot, strut) in line_slots
+        .iter_mut()
+        .zip(struts.iter().chain(std::iter::repeat(&0.0)))
+    {
+        shift += strut;
+        slot.y += shift;
+    }
+    line_slots
+}
+
+/// The height of the lines of a paragraph (in CSS pixels), including the space they are moved
+/// down by to clear floats (or onto the next page)
+fn lines_height(layout: &parley::Layout<TextBrush>, line_slots: &[LineSlot]) -> f32 {
+    let shift = line_slots.last().map_or(0.0, |slot| slot.y);
+    layout.height() / layout.scale() + shift
+}
+
+/// The offset Parley gives a line when it is aligned within `width` (see `parley::Layout::align`,
+/// excluding the hanging trailing whitespace of right-to-left lines which doesn't depend on the
+/// width). Values are in layout units.
+fn alignment_offset(metrics: &LineMetrics, alignment: Alignment, is_rtl: bool, width: f32) -> f32 {
+    let free_space = width - metrics.advance + metrics.trailing_whitespace;
+    if free_space <= 0.0 {
+        // Overflowing lines are aligned to the start edge
+        return if is_rtl { free_space } else { 0.0 };
+    }
+    let share = match (alignment, is_rtl) {
+        (Alignment::Left, _) | (Alignment::Start | Alignment::Justify, false) => 0.0,
+        (Alignment::End, true) => 0.0,
+        (Alignment::Right, _) | (Alignment::Start | Alignment::Justify, true) => 1.0,
+        (Alignment::End, false) => 1.0,
+        (Alignment::Center, _) => 0.5,
+    };
+    free_space * share
+}
+
+/// The padding box of a containing block, relative to the border box of some descendant box
+#[derive(Clone, Copy)]
+struct ContainingBlock {
+    origin: Point<f32>,
+    size: Size<f32>,
+}
+
+impl ContainingBlock {
+    /// Re-express the containing block relative to a child box located at `location`
+    fn translate(self, location: Point<f32>) -> Self {
+        Self {
+            origin: Point {
+                x: self.origin.x - location.x,
+                y: self.origin.y - location.y,
+            },
+            size: self.size,
+        }
+    }
 }
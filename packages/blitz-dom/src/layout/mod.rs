@@ -4,7 +4,7 @@
 //! However, in Blitz, we do a style pass then a layout pass.
 //! This is slower, yes, but happens fast enough that it's not a huge issue.
 
-use crate::node::{ImageData, NodeData, SpecialElementData};
+use crate::node::{ImageData, NodeData, SelectData, SpecialElementData};
 use crate::{document::BaseDocument, node::Node};
 use markup5ever::local_name;
 use std::cell::Ref;
@@ -13,20 +13,28 @@ use style::Atom;
 use style::values::computed::CSSPixelLength;
 use style::values::computed::length_percentage::CalcLengthPercentage;
 use taffy::{
-    CollapsibleMarginSet, FlexDirection, LayoutPartialTree, NodeId, ResolveOrZero, RoundTree,
-    Style, TraversePartialTree, TraverseTree, compute_block_layout, compute_cached_layout,
-    compute_flexbox_layout, compute_grid_layout, compute_leaf_layout, prelude::*,
+    BlockContext, CollapsibleMarginSet, FlexDirection, LayoutPartialTree, NodeId, ResolveOrZero,
+    RoundTree, Style, TraversePartialTree, TraverseTree, compute_block_layout,
+    compute_cached_layout, compute_flexbox_layout, compute_grid_layout, compute_leaf_layout,
+    prelude::*,
 };
 
 pub(crate) mod construct;
+pub(crate) mod counter_style;
 pub(crate) mod damage;
+pub(crate) mod generated_content;
 pub(crate) mod inline;
 pub(crate) mod list;
+pub(crate) mod paged_media;
 pub(crate) mod replaced;
+pub(crate) mod select;
+pub(crate) mod sticky;
 pub(crate) mod table;
+pub(crate) mod writing_mode;
 
+use self::paged_media::PagedBlockItemStyle;
 use self::replaced::{ReplacedContext, replaced_measure_function};
-use self::table::TableTreeWrapper;
+use self::table::compute_table_layout;
 
 pub(crate) fn resolve_calc_value(calc_ptr: *const (), parent_size: f32) -> f32 {
     let calc = unsafe { &*(calc_ptr as *const CalcLengthPercentage) };
@@ -98,221 +106,291 @@ impl LayoutPartialTree for BaseDocument {
         &mut self,
         node_id: NodeId,
         inputs: taffy::tree::LayoutInput,
+    ) -> taffy::tree::LayoutOutput {
+        self.compute_child_layout_with_block_context(node_id, inputs, None)
+    }
+}
+
+impl BaseDocument {
+    /// Lay out a child node. `block_ctx` is the block formatting context of the parent, which is
+    /// only set for in-flow children of block containers so that they can flow around floats.
+    fn compute_child_layout_with_block_context(
+        &mut self,
+        node_id: NodeId,
+        inputs: taffy::tree::LayoutInput,
+        block_ctx: Option<&mut BlockContext<'_>>,
     ) -> taffy::tree::LayoutOutput {
         compute_cached_layout(self, node_id, inputs, |tree, node_id, inputs| {
-            let node = &mut tree.nodes[node_id.into()];
+            match tree.nodes[node_id.into()].vertical_flow_root() {
+                Some(flow) => tree.compute_vertical_flow_root_layout(node_id, inputs, flow),
+                None => compute_node_layout(tree, node_id, inputs, block_ctx),
+            }
+        })
+    }
+}
 
-            let font_styles = node.primary_styles().map(|style| {
-                use style::values::computed::font::LineHeight;
+/// Lay out a node using the layout algorithm for its display type
+pub(crate) fn compute_node_layout(
+    tree: &mut BaseDocument,
+    node_id: NodeId,
+    inputs: taffy::tree::LayoutInput,
+    block_ctx: Option<&mut BlockContext<'_>>,
+) -> taffy::tree::LayoutOutput {
+    let node = &mut tree.nodes[node_id.into()];
+
+    let font_styles = node.primary_styles().map(|style| {
+        use style::values::computed::font::LineHeight;
+
+        let font_size = style.clone_font_size().used_size().px();
+        let line_height = match style.clone_line_height() {
+            LineHeight::Normal => font_size * 1.2,
+            LineHeight::Number(num) => font_size * num.0,
+            LineHeight::Length(value) => value.0.px(),
+        };
+
+        (font_size, line_height)
+    });
+    let font_size = font_styles.map(|s| s.0);
+    let resolved_line_height = font_styles.map(|s| s.1);
+
+    match &mut node.data {
+        NodeData::Text(data) => {
+            // With the new "inline context" architecture all text nodes should be wrapped in an "inline layout context"
+            // and should therefore never be measured individually.
+            println!(
+                "ERROR: Tried to lay out text node individually ({})",
+                usize::from(node_id)
+            );
+            dbg!(data);
+            taffy::LayoutOutput::HIDDEN
+            // unreachable!();
+
+            // compute_leaf_layout(inputs, &node.style, |known_dimensions, available_space| {
+            //     let context = TextContext {
+            //         text_content: &data.content.trim(),
+            //         writing_mode: WritingMode::Horizontal,
+            //     };
+            //     let font_metrics = FontMetrics {
+            //         char_width: 8.0,
+            //         char_height: 16.0,
+            //     };
+            //     text_measure_function(
+            //         known_dimensions,
+            //         available_space,
+            //         &context,
+            //         &font_metrics,
+            //     )
+            // })
+        }
+        NodeData::Element(element_data) | NodeData::AnonymousBlock(element_data) => {
+            // TODO: deduplicate with single-line text input
+            if *element_data.name.local == *"textarea" {
+                let rows = element_data
+                    .attr(local_name!("rows"))
+                    .and_then(|val| val.parse::<f32>().ok())
+                    .unwrap_or(2.0);
+
+                let cols = element_data
+                    .attr(local_name!("cols"))
+                    .and_then(|val| val.parse::<f32>().ok());
+
+                return compute_leaf_layout(
+                    inputs,
+                    &node.style,
+                    resolve_calc_value,
+                    |_known_size, _available_space| taffy::Size {
+                        width: cols
+                            .map(|cols| cols * font_size.unwrap_or(16.0) * 0.6)
+                            .unwrap_or(300.0),
+                        height: resolved_line_height.unwrap_or(16.0) * rows,
+                    },
+                );
+            }
 
-                let font_size = style.clone_font_size().used_size().px();
-                let line_height = match style.clone_line_height() {
-                    LineHeight::Normal => font_size * 1.2,
-                    LineHeight::Number(num) => font_size * num.0,
-                    LineHeight::Length(value) => value.0.px(),
+            if let Some(select_data) = element_data.select_data() {
+                let arrow_width = match select_data.is_drop_down() {
+                    true => SelectData::ARROW_WIDTH,
+                    false => 0.0,
                 };
+                let size = taffy::Size {
+                    width: select_data.content_width + arrow_width,
+                    height: select_data.row_height * select_data.display_size as f32,
+                };
+                return compute_leaf_layout(
+                    inputs,
+                    &node.style,
+                    resolve_calc_value,
+                    |_known_size, _available_space| size,
+                );
+            }
 
-                (font_size, line_height)
-            });
-            let font_size = font_styles.map(|s| s.0);
-            let resolved_line_height = font_styles.map(|s| s.1);
-
-            match &mut node.data {
-                NodeData::Text(data) => {
-                    // With the new "inline context" architecture all text nodes should be wrapped in an "inline layout context"
-                    // and should therefore never be measured individually.
-                    println!(
-                        "ERROR: Tried to lay out text node individually ({})",
-                        usize::from(node_id)
-                    );
-                    dbg!(data);
-                    taffy::LayoutOutput::HIDDEN
-                    // unreachable!();
-
-                    // compute_leaf_layout(inputs, &node.style, |known_dimensions, available_space| {
-                    //     let context = TextContext {
-                    //         text_content: &data.content.trim(),
-                    //         writing_mode: WritingMode::Horizontal,
-                    //     };
-                    //     let font_metrics = FontMetrics {
-                    //         char_width: 8.0,
-                    //         char_height: 16.0,
-                    //     };
-                    //     text_measure_function(
-                    //         known_dimensions,
-                    //         available_space,
-                    //         &context,
-                    //         &font_metrics,
-                    //     )
-                    // })
-                }
-                NodeData::Element(element_data) | NodeData::AnonymousBlock(element_data) => {
-                    // TODO: deduplicate with single-line text input
-                    if *element_data.name.local == *"textarea" {
-                        let rows = element_data
-                            .attr(local_name!("rows"))
-                            .and_then(|val| val.parse::<f32>().ok())
-                            .unwrap_or(2.0);
-
-                        let cols = element_data
-                            .attr(local_name!("cols"))
-                            .and_then(|val| val.parse::<f32>().ok());
+            // Progress bars are 10em wide and meters are 5em wide (both are 1em high)
+            if matches!(element_data.name.local.as_ref(), "progress" | "meter") {
+                let em = font_size.unwrap_or(16.0);
+                let width = match element_data.name.local.as_ref() {
+                    "progress" => 10.0 * em,
+                    _ => 5.0 * em,
+                };
+                return compute_leaf_layout(
+                    inputs,
+                    &node.style,
+                    resolve_calc_value,
+                    |_known_size, _available_space| taffy::Size { width, height: em },
+                );
+            }
 
+            if *element_data.name.local == *"input" {
+                match element_data.attr(local_name!("type")) {
+                    // if the input type is hidden, hide it
+                    Some("hidden") => {
+                        node.style.display = Display::None;
+                        return taffy::LayoutOutput::HIDDEN;
+                    }
+                    Some("checkbox") => {
+                        return compute_leaf_layout(
+                            inputs,
+                            &node.style,
+                            resolve_calc_value,
+                            |_known_size, _available_space| {
+                                let width = node
+                                    .style
+                                    .size
+                                    .width
+                                    .resolve_or_zero(inputs.parent_size.width, resolve_calc_value);
+                                let height = node
+                                    .style
+                                    .size
+                                    .height
+                                    .resolve_or_zero(inputs.parent_size.height, resolve_calc_value);
+                                let min_size = width.min(height);
+                                taffy::Size {
+                                    width: min_size,
+                                    height: min_size,
+                                }
+                            },
+                        );
+                    }
+                    Some("range") => {
                         return compute_leaf_layout(
                             inputs,
                             &node.style,
                             resolve_calc_value,
                             |_known_size, _available_space| taffy::Size {
-                                width: cols
-                                    .map(|cols| cols * font_size.unwrap_or(16.0) * 0.6)
-                                    .unwrap_or(300.0),
-                                height: resolved_line_height.unwrap_or(16.0) * rows,
+                                width: 129.0,
+                                height: 16.0,
                             },
                         );
                     }
+                    None | Some("text" | "password" | "email") => {
+                        return compute_leaf_layout(
+                            inputs,
+                            &node.style,
+                            resolve_calc_value,
+                            |_known_size, _available_space| taffy::Size {
+                                width: 300.0,
+                                height: resolved_line_height.unwrap_or(16.0),
+                            },
+                        );
+                    }
+                    _ => {}
+                }
+            }
 
-                    if *element_data.name.local == *"input" {
-                        match element_data.attr(local_name!("type")) {
-                            // if the input type is hidden, hide it
-                            Some("hidden") => {
-                                node.style.display = Display::None;
-                                return taffy::LayoutOutput::HIDDEN;
-                            }
-                            Some("checkbox") => {
-                                return compute_leaf_layout(
-                                    inputs,
-                                    &node.style,
-                                    resolve_calc_value,
-                                    |_known_size, _available_space| {
-                                        let width = node.style.size.width.resolve_or_zero(
-                                            inputs.parent_size.width,
-                                            resolve_calc_value,
-                                        );
-                                        let height = node.style.size.height.resolve_or_zero(
-                                            inputs.parent_size.height,
-                                            resolve_calc_value,
-                                        );
-                                        let min_size = width.min(height);
-                                        taffy::Size {
-                                            width: min_size,
-                                            height: min_size,
-                                        }
-                                    },
-                                );
-                            }
-                            None | Some("text" | "password" | "email") => {
-                                return compute_leaf_layout(
-                                    inputs,
-                                    &node.style,
-                                    resolve_calc_value,
-                                    |_known_size, _available_space| taffy::Size {
-                                        width: 300.0,
-                                        height: resolved_line_height.unwrap_or(16.0),
-                                    },
-                                );
+            if *element_data.name.local == *"img"
+                || *element_data.name.local == *"canvas"
+                || (cfg!(feature = "svg") && *element_data.name.local == *"svg")
+            {
+                // Get width and height attributes on image element. These act as the preferred
+                // size of the element (`object-fit` then determines how the content is painted
+                // into the resulting box, see blitz-paint).
+                let attr_size = taffy::Size {
+                    width: element_data
+                        .attr(local_name!("width"))
+                        .and_then(|val| val.parse::<f32>().ok()),
+                    height: element_data
+                        .attr(local_name!("height"))
+                        .and_then(|val| val.parse::<f32>().ok()),
+                };
+
+                // Get image's native sizespecial_data
+                let inherent_size = match &element_data.special_data {
+                    SpecialElementData::Image(image_data) => match &**image_data {
+                        ImageData::Raster(image) => taffy::Size {
+                            width: image.width as f32,
+                            height: image.height as f32,
+                        },
+                        #[cfg(feature = "svg")]
+                        ImageData::Svg(svg) => {
+                            let size = svg.size();
+                            taffy::Size {
+                                width: size.width(),
+                                height: size.height(),
                             }
-                            _ => {}
                         }
-                    }
+                        ImageData::None => taffy::Size::ZERO,
+                    },
+                    // The default size of a canvas's bitmap
+                    SpecialElementData::Canvas(_) => taffy::Size {
+                        width: 300.0,
+                        height: 150.0,
+                    },
+                    SpecialElementData::None => taffy::Size::ZERO,
+                    _ => unreachable!(),
+                };
 
-                    if *element_data.name.local == *"img"
-                        || *element_data.name.local == *"canvas"
-                        || (cfg!(feature = "svg") && *element_data.name.local == *"svg")
-                    {
-                        // Get width and height attributes on image element
-                        //
-                        // TODO: smarter sizing using these (depending on object-fit, they shouldn't
-                        // necessarily just override the native size)
-                        let attr_size = taffy::Size {
-                            width: element_data
-                                .attr(local_name!("width"))
-                                .and_then(|val| val.parse::<f32>().ok()),
-                            height: element_data
-                                .attr(local_name!("height"))
-                                .and_then(|val| val.parse::<f32>().ok()),
-                        };
-
-                        // Get image's native sizespecial_data
-                        let inherent_size = match &element_data.special_data {
-                            SpecialElementData::Image(image_data) => match &**image_data {
-                                ImageData::Raster(image) => taffy::Size {
-                                    width: image.width as f32,
-                                    height: image.height as f32,
-                                },
-                                #[cfg(feature = "svg")]
-                                ImageData::Svg(svg) => {
-                                    let size = svg.size();
-                                    taffy::Size {
-                                        width: size.width(),
-                                        height: size.height(),
-                                    }
-                                }
-                                ImageData::None => taffy::Size::ZERO,
-                            },
-                            SpecialElementData::Canvas(_) => taffy::Size::ZERO,
-                            SpecialElementData::None => taffy::Size::ZERO,
-                            _ => unreachable!(),
-                        };
-
-                        let replaced_context = ReplacedContext {
-                            inherent_size,
-                            attr_size,
-                        };
-
-                        let computed = replaced_measure_function(
-                            inputs.known_dimensions,
-                            inputs.parent_size,
-                            inputs.available_space,
-                            &replaced_context,
-                            &node.style,
-                            false,
-                        );
+                let replaced_context = ReplacedContext {
+                    inherent_size,
+                    attr_size,
+                };
 
-                        return taffy::LayoutOutput {
-                            size: computed,
-                            content_size: computed,
-                            first_baselines: taffy::Point::NONE,
-                            top_margin: CollapsibleMarginSet::ZERO,
-                            bottom_margin: CollapsibleMarginSet::ZERO,
-                            margins_can_collapse_through: false,
-                        };
-                    }
+                let computed = replaced_measure_function(
+                    inputs.known_dimensions,
+                    inputs.parent_size,
+                    inputs.available_space,
+                    &replaced_context,
+                    &node.style,
+                    false,
+                );
+
+                return taffy::LayoutOutput {
+                    size: computed,
+                    content_size: computed,
+                    first_baselines: taffy::Point::NONE,
+                    top_margin: CollapsibleMarginSet::ZERO,
+                    bottom_margin: CollapsibleMarginSet::ZERO,
+                    margins_can_collapse_through: false,
+                };
+            }
 
-                    if node.flags.is_table_root() {
-                        let SpecialElementData::TableRoot(context) = &tree.nodes[node_id.into()]
-                            .data
-                            .downcast_element()
-                            .unwrap()
-                            .special_data
-                        else {
-                            panic!("Node marked as table root but doesn't have TableContext");
-                        };
-                        let context = Arc::clone(context);
-
-                        let mut table_wrapper = TableTreeWrapper {
-                            doc: tree,
-                            ctx: context,
-                        };
-                        return compute_grid_layout(&mut table_wrapper, node_id, inputs);
-                    }
+            if node.flags.is_table_root() {
+                let SpecialElementData::TableRoot(context) = &tree.nodes[node_id.into()]
+                    .data
+                    .downcast_element()
+                    .unwrap()
+                    .special_data
+                else {
+                    panic!("Node marked as table root but doesn't have TableContext");
+                };
+                let context = Arc::clone(context);
 
-                    if node.flags.is_inline_root() {
-                        return tree.compute_inline_layout(usize::from(node_id), inputs);
-                    }
+                return compute_table_layout(tree, node_id, inputs, context);
+            }
 
-                    // The default CSS file will set
-                    match node.style.display {
-                        Display::Block => compute_block_layout(tree, node_id, inputs),
-                        Display::Flex => compute_flexbox_layout(tree, node_id, inputs),
-                        Display::Grid => compute_grid_layout(tree, node_id, inputs),
-                        Display::None => taffy::LayoutOutput::HIDDEN,
-                    }
-                }
-                NodeData::Document => compute_block_layout(tree, node_id, inputs),
+            if node.flags.is_inline_root() {
+                return tree.compute_inline_layout(usize::from(node_id), inputs, block_ctx);
+            }
 
-                _ => taffy::LayoutOutput::HIDDEN,
+            // The default CSS file will set
+            match node.style.display {
+                Display::Block => compute_block_layout(tree, node_id, inputs, block_ctx),
+                Display::Flex => compute_flexbox_layout(tree, node_id, inputs),
+                Display::Grid => compute_grid_layout(tree, node_id, inputs),
+                Display::None => taffy::LayoutOutput::HIDDEN,
             }
-        })
+        }
+        NodeData::Document => compute_block_layout(tree, node_id, inputs, None),
+
+        _ => taffy::LayoutOutput::HIDDEN,
     }
 }
 
@@ -321,30 +399,21 @@ impl taffy::CacheTree for BaseDocument {
     fn cache_get(
         &self,
         node_id: NodeId,
-        known_dimensions: Size<Option<f32>>,
-        available_space: Size<AvailableSpace>,
-        run_mode: taffy::RunMode,
+        input: &taffy::tree::LayoutInput,
     ) -> Option<taffy::LayoutOutput> {
-        self.node_from_id(node_id)
-            .cache
-            .get(known_dimensions, available_space, run_mode)
+        self.node_from_id(node_id).cache.get(input)
     }
 
     #[inline]
     fn cache_store(
         &mut self,
         node_id: NodeId,
-        known_dimensions: Size<Option<f32>>,
-        available_space: Size<AvailableSpace>,
-        run_mode: taffy::RunMode,
+        input: &taffy::tree::LayoutInput,
         layout_output: taffy::LayoutOutput,
     ) {
-        self.node_from_id_mut(node_id).cache.store(
-            known_dimensions,
-            available_space,
-            run_mode,
-            layout_output,
-        );
+        self.node_from_id_mut(node_id)
+            .cache
+            .store(input, layout_output);
     }
 
     #[inline]
@@ -360,7 +429,7 @@ impl taffy::LayoutBlockContainer for BaseDocument {
         Self: 'a;
 
     type BlockItemStyle<'a>
-        = &'a Style<Atom>
+        = PagedBlockItemStyle<'a>
     where
         Self: 'a;
 
@@ -369,7 +438,20 @@ impl taffy::LayoutBlockContainer for BaseDocument {
     }
 
     fn get_block_child_style(&self, child_node_id: NodeId) -> Self::BlockItemStyle<'_> {
-        self.get_core_container_style(child_node_id)
+        let margin_top = self
+            .pagination
+            .as_ref()
+            .and_then(|pagination| pagination.margin_top(child_node_id.into()));
+        PagedBlockItemStyle::new(self.get_core_container_style(child_node_id), margin_top)
+    }
+
+    fn compute_block_child_layout(
+        &mut self,
+        node_id: NodeId,
+        inputs: taffy::tree::LayoutInput,
+        block_ctx: Option<&mut BlockContext<'_>>,
+    ) -> taffy::tree::LayoutOutput {
+        self.compute_child_layout_with_block_context(node_id, inputs, block_ctx)
     }
 }
 
@@ -433,6 +515,7 @@ impl PrintTree for BaseDocument {
             // NodeData::Doctype { .. } => return "DOCTYPE",
             NodeData::Text { .. } => node.node_debug_str().leak(),
             NodeData::Comment => "COMMENT",
+            NodeData::ShadowRoot(_) => "SHADOW ROOT",
             NodeData::AnonymousBlock(_) => "ANONYMOUS BLOCK",
             NodeData::Element(_) => {
                 let display = match style.display {
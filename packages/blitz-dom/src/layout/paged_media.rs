@@ -0,0 +1,1376 @@
+//! Paged media: `@page` rules, the `break-before`, `break-after` and `break-inside` properties and
+//! the fragmentation of a document into pages.
+//!
+//! Stylo (in servo mode) doesn't parse the break properties, `orphans` and `widows` or `@page`
+//! rules, so stylesheets and `style` attributes are rewritten before they are parsed (see
+//! [`expose_paged_media_properties`]): the properties set custom properties of the same name
+//! (prefixed with `--blitz-`), and `@page` rules set custom properties on the root element (named
+//! after the page selector and descriptor). Stylo then cascades them like any other property, so
+//! rules in `<link>`ed and `@import`ed stylesheets and in `@media` blocks apply as usual.
+//!
+//! Boxes are fragmented during layout. The document is laid out (at the width of the content area
+//! of the first page) and then, page by page, content which would otherwise be split by the end of
+//! the page is pushed onto the next page by inserting space above it: block-level boxes are given a
+//! larger top margin and lines of text are moved down within their inline formatting context. The
+//! document is laid out again after each push, so that the content which follows moves down with
+//! it, and each page displays the next page-height stripe of the layout. As a consequence:
+//!
+//!   - Only the children of block containers and the lines of inline formatting contexts are pushed
+//!     onto the next page. Other boxes (such as images, tables, and flex and grid containers) are
+//!     monolithic, and are sliced at the end of the page if they don't fit on a page.
+//!   - Boxes which span a page break are cut at the break (their borders aren't closed off and
+//!     their backgrounds aren't repeated), and margins adjoining a break are not truncated.
+//!   - Pages whose content area is narrower or wider than that of the first page display the
+//!     document as it is laid out for the first page.
+//!
+//! See: <https://www.w3.org/TR/css-page-3/> and <https://www.w3.org/TR/css-break-3/>
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::ops::Range;
+
+use blitz_traits::shell::Viewport;
+use cssparser::{ParseError, Parser, ParserInput, Token};
+use style::Atom;
+use style::properties::ComputedValues;
+use style::properties_and_values::registry::PropertyRegistrationData;
+use taffy::{
+    BoxGenerationMode, BoxSizing, Clear, CoreStyle, Dimension, Direction, Display, Float,
+    LengthPercentage, LengthPercentageAuto, Overflow, Point, Position, Rect, Size, Style,
+};
+
+use crate::BaseDocument;
+use crate::node::Node;
+
+/// The number of CSS pixels per millimetre
+const PX_PER_MM: f32 = 96.0 / 25.4;
+
+/// Breaks closer than this (in CSS pixels) to the start of a page are ignored so that every page
+/// makes progress through the document
+const MIN_FRAGMENT_HEIGHT: f32 = 1.0;
+
+const EPSILON: f32 = 0.01;
+
+/// How many times content is pushed towards the start of a page before it is given up on (and
+/// sliced at the end of the previous page instead). Content may have to be pushed more than once
+/// when the space inserted above it collapses with adjoining margins.
+const MAX_PUSHES_PER_PAGE: usize = 4;
+
+/// The prefix of the names (excluding the leading `--`) of the custom properties which are set in
+/// place of the properties and `@page` descriptors Stylo doesn't parse
+const PROPERTY_PREFIX: &str = "blitz-";
+
+/// The properties affecting pagination which Stylo doesn't parse, and the names (without the
+/// prefix) of the custom properties they set. The legacy `page-break-*` properties are aliases of
+/// the `break-*` properties.
+const PAGINATION_PROPERTIES: [(&str, &str); 8] = [
+    ("break-before", "break-before"),
+    ("break-after", "break-after"),
+    ("break-inside", "break-inside"),
+    ("page-break-before", "break-before"),
+    ("page-break-after", "break-after"),
+    ("page-break-inside", "break-inside"),
+    ("orphans", "orphans"),
+    ("widows", "widows"),
+];
+
+/// A user agent stylesheet which stops the custom properties set in place of the `break-*`
+/// properties from being inherited (as those properties aren't inherited). `orphans` and `widows`
+/// are inherited.
+pub(crate) const PAGED_MEDIA_UA_CSS: &str = "* {
+    --blitz-break-before: initial;
+    --blitz-break-after: initial;
+    --blitz-break-inside: initial;
+}";
+
+/// The side of a spread on which a page is displayed. The first page of a (left-to-right) document
+/// is a right page.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PageSide {
+    Left,
+    Right,
+}
+
+impl PageSide {
+    fn of_page(index: usize) -> Self {
+        if index % 2 == 0 {
+            Self::Right
+        } else {
+            Self::Left
+        }
+    }
+}
+
+/// Options for [`BaseDocument::paginate`]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PageOptions {
+    /// The size of pages (in CSS pixels) if it is not specified by an `@page` rule
+    pub size: Size<f32>,
+    /// The margins of pages (in CSS pixels) if they are not specified by an `@page` rule
+    pub margin: Rect<f32>,
+}
+
+impl PageOptions {
+    /// A4 pages with 1cm margins
+    pub const A4: Self = Self {
+        size: Size {
+            width: 210.0 * PX_PER_MM,
+            height: 297.0 * PX_PER_MM,
+        },
+        margin: Rect {
+            left: 10.0 * PX_PER_MM,
+            right: 10.0 * PX_PER_MM,
+            top: 10.0 * PX_PER_MM,
+            bottom: 10.0 * PX_PER_MM,
+        },
+    };
+
+    /// US Letter pages with 0.4in margins
+    pub const LETTER: Self = Self {
+        size: Size {
+            width: 816.0,
+            height: 1056.0,
+        },
+        margin: Rect {
+            left: 38.4,
+            right: 38.4,
+            top: 38.4,
+            bottom: 38.4,
+        },
+    };
+}
+
+impl Default for PageOptions {
+    fn default() -> Self {
+        Self::A4
+    }
+}
+
+/// A page of a paginated document. All lengths are in CSS pixels.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Page {
+    /// The (zero-based) index of the page
+    pub index: usize,
+    pub side: PageSide,
+    /// Whether the page is a blank page inserted to satisfy a `left` or `right` forced break
+    pub blank: bool,
+    /// The size of the page box
+    pub size: Size<f32>,
+    /// The margins between the edges of the page box and its content area
+    pub margin: Rect<f32>,
+    /// The position (relative to the top of the paginated document) of the fragment of the
+    /// document displayed in the content area of the page
+    pub offset: f32,
+    /// The height of the fragment of the document displayed in the content area of the page. This
+    /// is at most the height of the content area (and is zero for blank pages).
+    pub fragment_height: f32,
+}
+
+impl Page {
+    /// The width of the content area of the page
+    pub fn content_width(&self) -> f32 {
+        (self.size.width - self.margin.left - self.margin.right).max(0.0)
+    }
+
+    /// The height of the content area of the page
+    pub fn content_height(&self) -> f32 {
+        (self.size.height - self.margin.top - self.margin.bottom).max(0.0)
+    }
+}
+
+/// The height of the stripe of the document displayed on a page. This is the height of the content
+/// area rounded down to whole pixels, so that pages start where boxes are placed after rounding.
+fn fragmentainer_height(size: Size<f32>, margin: Rect<f32>) -> f32 {
+    (size.height - margin.top - margin.bottom)
+        .floor()
+        .max(MIN_FRAGMENT_HEIGHT)
+}
+
+/// The value of the `break-before` and `break-after` properties
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+enum BreakBetween {
+    #[default]
+    Auto,
+    Avoid,
+    Page,
+    Left,
+    Right,
+}
+
+impl BreakBetween {
+    /// Parse the value of the `break-before` and `break-after` properties (or of the legacy
+    /// `page-break-before` and `page-break-after` properties)
+    fn parse(value: &str) -> Option<Self> {
+        Some(match value {
+            "auto" | "column" | "avoid-column" | "region" | "avoid-region" => Self::Auto,
+            "avoid" | "avoid-page" => Self::Avoid,
+            "page" | "all" | "always" => Self::Page,
+            // Recto and verso pages are right and left pages in left-to-right documents
+            "left" | "verso" => Self::Left,
+            "right" | "recto" => Self::Right,
+            _ => return None,
+        })
+    }
+
+    fn is_forced(self) -> bool {
+        matches!(self, Self::Page | Self::Left | Self::Right)
+    }
+
+    fn side(self) -> Option<PageSide> {
+        match self {
+            Self::Left => Some(PageSide::Left),
+            Self::Right => Some(PageSide::Right),
+            _ => None,
+        }
+    }
+
+    /// Combine the values of breaks which adjoin the same break point. Forced breaks take
+    /// precedence over avoiding a break, and `self` precedes `later` in the flow.
+    fn combine(self, later: Self) -> Self {
+        if later.is_forced() {
+            later
+        } else if self.is_forced() {
+            self
+        } else if self == Self::Avoid || later == Self::Avoid {
+            Self::Avoid
+        } else {
+            Self::Auto
+        }
+    }
+}
+
+/// The computed values of the properties of an element which affect pagination
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct BreakStyle {
+    before: BreakBetween,
+    after: BreakBetween,
+    avoid_inside: bool,
+    orphans: usize,
+    widows: usize,
+}
+
+impl Default for BreakStyle {
+    fn default() -> Self {
+        Self {
+            before: BreakBetween::Auto,
+            after: BreakBetween::Auto,
+            avoid_inside: false,
+            orphans: 2,
+            widows: 2,
+        }
+    }
+}
+
+impl BreakStyle {
+    fn of(node: &Node) -> Self {
+        let Some(style) = node.primary_styles() else {
+            return Self::default();
+        };
+        let default = Self::default();
+        let count = |name: &str| {
+            custom_property(&style, name)?
+                .parse::<usize>()
+                .ok()
+                .filter(|count| *count > 0)
+        };
+        Self {
+            before: custom_property(&style, "break-before")
+                .and_then(|value| BreakBetween::parse(&value))
+                .unwrap_or_default(),
+            after: custom_property(&style, "break-after")
+                .and_then(|value| BreakBetween::parse(&value))
+                .unwrap_or_default(),
+            avoid_inside: custom_property(&style, "break-inside")
+                .is_some_and(|value| matches!(value.as_str(), "avoid" | "avoid-page")),
+            orphans: count("orphans").unwrap_or(default.orphans),
+            widows: count("widows").unwrap_or(default.widows),
+        }
+    }
+}
+
+/// The computed value (trimmed and in lowercase) of one of the custom properties which are set in
+/// place of the properties and descriptors Stylo doesn't parse
+fn custom_property(style: &ComputedValues, name: &str) -> Option<String> {
+    let name = Atom::from(format!("{PROPERTY_PREFIX}{name}"));
+    let value = style
+        .custom_properties()
+        .get(PropertyRegistrationData::unregistered(), &name)?;
+    Some(value.as_universal()?.css_text().trim().to_ascii_lowercase())
+}
+
+/// A length in an `@page` rule
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum PageLength {
+    Auto,
+    Px(f32),
+    /// A percentage (as a fraction) of the width or height of the page box
+    Percent(f32),
+}
+
+impl PageLength {
+    fn parse(value: &str) -> Option<Self> {
+        match parse_components(value).as_deref() {
+            Some([component]) => component.page_length(),
+            _ => None,
+        }
+    }
+}
+
+/// The value of the `size` descriptor of an `@page` rule
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+struct PageSize {
+    /// `None` for `auto` (or an orientation without a size)
+    size: Option<Size<f32>>,
+    landscape: Option<bool>,
+}
+
+impl PageSize {
+    fn parse(value: &str) -> Option<Self> {
+        let components = parse_components(value)?;
+        let mut page_size = Self::default();
+        match components.as_slice() {
+            [Component::Ident(ident)] if ident == "auto" => {}
+            [Component::Length(size)] => page_size.size = Some(page_size(*size, *size)),
+            [Component::Length(width), Component::Length(height)] => {
+                page_size.size = Some(page_size(*width, *height))
+            }
+            components => {
+                for component in components {
+                    let Component::Ident(ident) = component else {
+                        return None;
+                    };
+                    match ident.as_str() {
+                        "portrait" if page_size.landscape.is_none() => {
+                            page_size.landscape = Some(false)
+                        }
+                        "landscape" if page_size.landscape.is_none() => {
+                            page_size.landscape = Some(true)
+                        }
+                        name if page_size.size.is_none() => {
+                            page_size.size = Some(named_page_size(name)?)
+                        }
+                        _ => return None,
+                    }
+                }
+            }
+        }
+        Some(page_size)
+    }
+
+    fn resolve(&self, default: Size<f32>) -> Size<f32> {
+        let size = self.size.unwrap_or(default);
+        let is_landscape = size.width > size.height;
+        match self.landscape {
+            Some(landscape) if landscape != is_landscape => Size {
+                width: size.height,
+                height: size.width,
+            },
+            _ => size,
+        }
+    }
+}
+
+fn page_size(width: f32, height: f32) -> Size<f32> {
+    Size { width, height }
+}
+
+/// The size of a page size keyword (in portrait orientation)
+fn named_page_size(name: &str) -> Option<Size<f32>> {
+    let (width, height) = match name {
+        "a5" => (148.0, 210.0),
+        "a4" => (210.0, 297.0),
+        "a3" => (297.0, 420.0),
+        "b5" => (176.0, 250.0),
+        "b4" => (250.0, 353.0),
+        "jis-b5" => (182.0, 257.0),
+        "jis-b4" => (257.0, 364.0),
+        "letter" => return Some(page_size(816.0, 1056.0)),
+        "legal" => return Some(page_size(816.0, 1344.0)),
+        "ledger" => return Some(page_size(1056.0, 1632.0)),
+        _ => return None,
+    };
+    Some(page_size(width * PX_PER_MM, height * PX_PER_MM))
+}
+
+/// A (non-named) page selector
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+struct PageSelector {
+    first: bool,
+    blank: bool,
+    side: Option<PageSide>,
+}
+
+impl PageSelector {
+    /// Parse a page selector. Returns `None` for named pages (which are not supported) and
+    /// invalid selectors.
+    fn parse(selector: &str) -> Option<Self> {
+        let mut result = Self::default();
+        let mut pseudo_classes = selector.trim().split(':');
+        if !pseudo_classes.next()?.trim().is_empty() {
+            return None;
+        }
+        for pseudo_class in pseudo_classes {
+            match pseudo_class.trim().to_ascii_lowercase().as_str() {
+                "first" => result.first = true,
+                "blank" => result.blank = true,
+                "left" => result.side = Some(PageSide::Left),
+                "right" => result.side = Some(PageSide::Right),
+                _ => return None,
+            }
+        }
+        Some(result)
+    }
+
+    /// Every page selector, in order of increasing specificity
+    fn all() -> Vec<Self> {
+        let mut selectors = Vec::new();
+        for first in [false, true] {
+            for blank in [false, true] {
+                for side in [None, Some(PageSide::Left), Some(PageSide::Right)] {
+                    selectors.push(Self { first, blank, side });
+                }
+            }
+        }
+        selectors.sort_by_key(Self::specificity);
+        selectors
+    }
+
+    fn specificity(&self) -> u32 {
+        (self.first as u32 + self.blank as u32) * 2 + self.side.is_some() as u32
+    }
+
+    fn matches(&self, index: usize, blank: bool) -> bool {
+        (!self.first || index == 0)
+            && (!self.blank || blank)
+            && self
+                .side
+                .is_none_or(|side| side == PageSide::of_page(index))
+    }
+
+    /// The name (without the prefix) of the custom property which holds the value of a descriptor
+    /// in `@page` rules with this selector
+    fn property_name(&self, descriptor: &str) -> String {
+        let mut name = String::from("page");
+        if self.first {
+            name.push_str("-first");
+        }
+        if self.blank {
+            name.push_str("-blank");
+        }
+        match self.side {
+            Some(PageSide::Left) => name.push_str("-left"),
+            Some(PageSide::Right) => name.push_str("-right"),
+            None => {}
+        }
+        name.push('-');
+        name.push_str(descriptor);
+        name
+    }
+}
+
+/// The descriptors of the `@page` rules with a selector (as cascaded by Stylo)
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct PageRule {
+    selector: PageSelector,
+    size: Option<PageSize>,
+    margin: Rect<Option<PageLength>>,
+}
+
+/// The `@page` rules which apply to a document, in order of increasing specificity
+struct PageStyles<'a> {
+    rules: Vec<PageRule>,
+    options: &'a PageOptions,
+}
+
+impl<'a> PageStyles<'a> {
+    fn new(doc: &BaseDocument, options: &'a PageOptions) -> Self {
+        let root_style = doc.root_element().primary_styles();
+        let rules = root_style
+            .map(|style| {
+                let descriptor = |selector: &PageSelector, name: &str| {
+                    custom_property(&style, &selector.property_name(name))
+                };
+                let margin = |selector: &PageSelector, name: &str| {
+                    descriptor(selector, name).and_then(|value| PageLength::parse(&value))
+                };
+                PageSelector::all()
+                    .into_iter()
+                    .map(|selector| PageRule {
+                        selector,
+                        size: descriptor(&selector, "size")
+                            .and_then(|value| PageSize::parse(&value)),
+                        margin: Rect {
+                            left: margin(&selector, "margin-left"),
+                            right: margin(&selector, "margin-right"),
+                            top: margin(&selector, "margin-top"),
+                            bottom: margin(&selector, "margin-bottom"),
+                        },
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        Self { rules, options }
+    }
+
+    /// The size and margins of a page
+    fn resolve(&self, index: usize, blank: bool) -> (Size<f32>, Rect<f32>) {
+        let mut page_size = PageSize::default();
+        let mut margin = Rect {
+            left: None,
+            right: None,
+            top: None,
+            bottom: None,
+        };
+        for rule in &self.rules {
+            if !rule.selector.matches(index, blank) {
+                continue;
+            }
+            page_size = rule.size.unwrap_or(page_size);
+            margin.left = rule.margin.left.or(margin.left);
+            margin.right = rule.margin.right.or(margin.right);
+            margin.top = rule.margin.top.or(margin.top);
+            margin.bottom = rule.margin.bottom.or(margin.bottom);
+        }
+
+        let size = page_size.resolve(self.options.size);
+        let resolve = |length: Option<PageLength>, default: f32, basis: f32| match length {
+            Some(PageLength::Px(px)) => px,
+            Some(PageLength::Percent(fraction)) => fraction * basis,
+            Some(PageLength::Auto) | None => default,
+        };
+        let default = self.options.margin;
+        let margin = Rect {
+            left: resolve(margin.left, default.left, size.width),
+            right: resolve(margin.right, default.right, size.width),
+            top: resolve(margin.top, default.top, size.height),
+            bottom: resolve(margin.bottom, default.bottom, size.height),
+        };
+        (size, margin)
+    }
+}
+
+/// The name of the custom property a property affecting pagination sets in its place (without the
+/// prefix), or `None` for other properties
+fn exposed_name(name: &str) -> Option<&'static str> {
+    PAGINATION_PROPERTIES
+        .iter()
+        .find(|(property, _)| name.eq_ignore_ascii_case(property))
+        .map(|(_, exposed)| *exposed)
+}
+
+/// The name of the custom property which is set in place of a property affecting pagination (e.g.
+/// by `style.setProperty()`), or the name itself for other properties
+pub(crate) fn exposed_property_name(name: &str) -> Cow<'_, str> {
+    match exposed_name(name) {
+        Some(exposed) => Cow::Owned(format!("--{PROPERTY_PREFIX}{exposed}")),
+        None => Cow::Borrowed(name),
+    }
+}
+
+/// Rewrite a stylesheet (or a declaration list, such as a `style` attribute) so that declarations
+/// of the properties affecting pagination set custom properties, and `@page` rules become style
+/// rules which set custom properties on the root element. Stylo's servo build doesn't parse either
+/// of them. See the [module docs](self).
+pub(crate) fn expose_paged_media_properties(css: &str) -> Cow<'_, str> {
+    let lowercase = css.to_ascii_lowercase();
+    if !["break-", "orphans", "widows", "@page"]
+        .iter()
+        .any(|pattern| lowercase.contains(pattern))
+    {
+        return Cow::Borrowed(css);
+    }
+
+    let mut input = ParserInput::new(css);
+    let mut input = Parser::new(&mut input);
+    let mut edits = Vec::new();
+    rewrite_rule_or_declaration_list(&mut input, &mut edits);
+    if edits.is_empty() {
+        return Cow::Borrowed(css);
+    }
+
+    let mut result = String::with_capacity(css.len() + edits.len() * 16);
+    let mut position = 0;
+    for (range, replacement) in edits {
+        result.push_str(&css[position..range.start]);
+        result.push_str(&replacement);
+        position = range.end;
+    }
+    result.push_str(&css[position..]);
+    Cow::Owned(result)
+}
+
+/// Find the edits which expose pagination properties in a list of rules and/or declarations (and
+/// in any nested blocks). Edits are byte ranges of the source and their replacements, in order.
+fn rewrite_rule_or_declaration_list(input: &mut Parser, edits: &mut Vec<(Range<usize>, String)>) {
+    let mut at_declaration_start = true;
+    loop {
+        let start = input.position();
+        let token = match input.next_including_whitespace() {
+            Ok(token) => token.clone(),
+            Err(_) => return,
+        };
+        match token {
+            Token::WhiteSpace(_) => continue,
+            Token::Semicolon => {
+                at_declaration_start = true;
+                continue;
+            }
+            Token::Ident(name) if at_declaration_start => {
+                let end = input.position();
+                let is_declaration = input.try_parse(|input| input.expect_colon()).is_ok();
+                if let Some(exposed) = exposed_name(&name).filter(|_| is_declaration) {
+                    let replacement = format!("--{PROPERTY_PREFIX}{exposed}");
+                    edits.push((start.byte_index()..end.byte_index(), replacement));
+                }
+            }
+            Token::AtKeyword(name) if name.eq_ignore_ascii_case("page") => {
+                let prelude_start = input.position();
+                if let Some(prelude_end) = skip_to_block(input) {
+                    let selectors: Vec<PageSelector> = input
+                        .slice(prelude_start..prelude_end)
+                        .split(',')
+                        .filter_map(PageSelector::parse)
+                        .collect();
+                    let mut declarations = String::new();
+                    let _ = input.parse_nested_block(|input| {
+                        parse_declarations(input, |name, value, important| {
+                            for selector in &selectors {
+                                write_page_declarations(
+                                    &mut declarations,
+                                    selector,
+                                    name,
+                                    value,
+                                    important,
+                                );
+                            }
+                        });
+                        Ok::<_, ParseError<()>>(())
+                    });
+                    let end = input.position();
+                    let replacement = format!(":root {{ {declarations}}}");
+                    edits.push((start.byte_index()..end.byte_index(), replacement));
+                }
+                at_declaration_start = true;
+                continue;
+            }
+            Token::CurlyBracketBlock => {
+                let _ = input.parse_nested_block(|input| {
+                    rewrite_rule_or_declaration_list(input, edits);
+                    Ok::<_, ParseError<()>>(())
+                });
+                at_declaration_start = true;
+                continue;
+            }
+            _ => {}
+        }
+        at_declaration_start = false;
+    }
+}
+
+/// Write the custom property declarations which hold a descriptor of an `@page` rule. The `margin`
+/// shorthand is expanded into its longhands.
+fn write_page_declarations(
+    declarations: &mut String,
+    selector: &PageSelector,
+    name: &str,
+    value: &str,
+    important: bool,
+) {
+    let mut write = |descriptor: &str, value: &str| {
+        let name = selector.property_name(descriptor);
+        let priority = if important { " !important" } else { "" };
+        declarations.push_str(&format!("--{PROPERTY_PREFIX}{name}: {value}{priority}; "));
+    };
+    match name {
+        "size" | "margin-top" | "margin-right" | "margin-bottom" | "margin-left" => {
+            write(name, value)
+        }
+        "margin" => {
+            let (top, right, bottom, left) = match split_components(value).as_slice() {
+                &[all] => (all, all, all, all),
+                &[vertical, horizontal] => (vertical, horizontal, vertical, horizontal),
+                &[top, horizontal, bottom] => (top, horizontal, bottom, horizontal),
+                &[top, right, bottom, left] => (top, right, bottom, left),
+                _ => return,
+            };
+            write("margin-top", top);
+            write("margin-right", right);
+            write("margin-bottom", bottom);
+            write("margin-left", left);
+        }
+        _ => {}
+    }
+}
+
+/// Skip to the next `{}` block (returning the position at which it starts), or to the end of an
+/// at-rule without a block (returning `None`)
+fn skip_to_block(input: &mut Parser) -> Option<cssparser::SourcePosition> {
+    loop {
+        let position = input.position();
+        match input.next() {
+            Ok(Token::CurlyBracketBlock) => return Some(position),
+            Ok(Token::Semicolon) | Err(_) => return None,
+            Ok(_) => {}
+        }
+    }
+}
+
+/// Parse a list of declarations, calling `declaration` with the (lowercase) name, the value and the
+/// importance of each declaration. Nested rules are skipped.
+fn parse_declarations(input: &mut Parser, mut declaration: impl FnMut(&str, &str, bool)) {
+    loop {
+        let name = match input.next() {
+            Ok(Token::Ident(name)) => name.to_ascii_lowercase(),
+            Ok(Token::Semicolon) => continue,
+            Ok(_) => {
+                skip_declaration(input);
+                continue;
+            }
+            Err(_) => return,
+        };
+        if input.expect_colon().is_err() {
+            skip_declaration(input);
+            continue;
+        }
+
+        let start = input.position();
+        let end = loop {
+            let position = input.position();
+            match input.next() {
+                Ok(Token::Semicolon) | Err(_) => break position,
+                Ok(_) => {}
+            }
+        };
+        let value = input.slice(start..end);
+        let (value, priority) = value.split_once('!').unwrap_or((value, ""));
+        let is_important = priority.trim().eq_ignore_ascii_case("important");
+        declaration(&name, value.trim(), is_important);
+    }
+}
+
+fn skip_declaration(input: &mut Parser) {
+    while let Ok(token) = input.next() {
+        if matches!(token, Token::Semicolon | Token::CurlyBracketBlock) {
+            return;
+        }
+    }
+}
+
+/// Split the value of a declaration into the source of its (whitespace separated) component values
+fn split_components(value: &str) -> Vec<&str> {
+    let mut input = ParserInput::new(value);
+    let mut input = Parser::new(&mut input);
+    let mut components = Vec::new();
+    loop {
+        input.skip_whitespace();
+        let start = input.position();
+        if input.next().is_err() {
+            return components;
+        }
+        components.push(input.slice_from(start));
+    }
+}
+
+/// A component value of a declaration
+#[derive(Clone, Debug, PartialEq)]
+enum Component {
+    Ident(String),
+    /// A length (in CSS pixels)
+    Length(f32),
+    /// A percentage (as a fraction)
+    Percent(f32),
+    Number(f32),
+}
+
+impl Component {
+    fn page_length(&self) -> Option<PageLength> {
+        match self {
+            Self::Ident(ident) if ident == "auto" => Some(PageLength::Auto),
+            Self::Length(px) => Some(PageLength::Px(*px)),
+            Self::Number(number) if *number == 0.0 => Some(PageLength::Px(0.0)),
+            Self::Percent(fraction) => Some(PageLength::Percent(*fraction)),
+            _ => None,
+        }
+    }
+}
+
+/// Parse the (whitespace separated) component values of a declaration. Returns `None` if the
+/// value contains anything other than identifiers, numbers, lengths and percentages.
+fn parse_components(value: &str) -> Option<Vec<Component>> {
+    let mut input = ParserInput::new(value);
+    let mut input = Parser::new(&mut input);
+    let mut components = Vec::new();
+    while let Ok(token) = input.next() {
+        components.push(match token {
+            Token::Ident(ident) => Component::Ident(ident.to_ascii_lowercase()),
+            Token::Number { value, .. } => Component::Number(*value),
+            Token::Percentage { unit_value, .. } => Component::Percent(*unit_value),
+            Token::Dimension { value, unit, .. } => {
+                // Font-relative lengths are relative to the initial font size of the page context
+                let px_per_unit = match unit.to_ascii_lowercase().as_str() {
+                    "px" => 1.0,
+                    "in" => 96.0,
+                    "cm" => PX_PER_MM * 10.0,
+                    "mm" => PX_PER_MM,
+                    "q" => PX_PER_MM / 4.0,
+                    "pt" => 96.0 / 72.0,
+                    "pc" => 16.0,
+                    "em" | "rem" => 16.0,
+                    _ => return None,
+                };
+                Component::Length(value * px_per_unit)
+            }
+            _ => return None,
+        });
+    }
+    Some(components)
+}
+
+/// The state of a paginated document
+pub(crate) struct Pagination {
+    /// The viewport, scroll position and media type to restore when pagination ends
+    viewport: Viewport,
+    scroll: crate::Point<f64>,
+    print_media: bool,
+    /// The used top margin of block-level boxes which are pushed onto the next page
+    margins: HashMap<usize, f32>,
+    /// The space inserted above each line (by index) of inline formatting contexts to push lines
+    /// onto the next page
+    lines: HashMap<usize, Vec<f32>>,
+}
+
+impl Pagination {
+    /// The top margin a block-level box is given to push it onto the next page
+    pub(crate) fn margin_top(&self, node_id: usize) -> Option<f32> {
+        self.margins.get(&node_id).copied()
+    }
+
+    /// The space inserted above each line of an inline formatting context
+    pub(crate) fn line_struts(&self, node_id: usize) -> Option<&[f32]> {
+        self.lines.get(&node_id).map(Vec::as_slice)
+    }
+}
+
+/// The style of a block-level box as seen by its parent's block layout: the box's own style, but
+/// with the top margin which pushes it onto the next page when the document is paginated
+pub(crate) struct PagedBlockItemStyle<'a> {
+    style: &'a Style<Atom>,
+    margin_top: Option<f32>,
+}
+
+impl<'a> PagedBlockItemStyle<'a> {
+    pub(crate) fn new(style: &'a Style<Atom>, margin_top: Option<f32>) -> Self {
+        Self { style, margin_top }
+    }
+}
+
+impl CoreStyle for PagedBlockItemStyle<'_> {
+    type CustomIdent = Atom;
+
+    fn box_generation_mode(&self) -> BoxGenerationMode {
+        self.style.box_generation_mode()
+    }
+    fn is_block(&self) -> bool {
+        self.style.is_block()
+    }
+    fn is_compressible_replaced(&self) -> bool {
+        self.style.is_compressible_replaced()
+    }
+    fn box_sizing(&self) -> BoxSizing {
+        self.style.box_sizing()
+    }
+    fn direction(&self) -> Direction {
+        self.style.direction()
+    }
+    fn overflow(&self) -> Point<Overflow> {
+        self.style.overflow()
+    }
+    fn scrollbar_width(&self) -> f32 {
+        self.style.scrollbar_width()
+    }
+    fn position(&self) -> Position {
+        self.style.position()
+    }
+    fn inset(&self) -> Rect<LengthPercentageAuto> {
+        self.style.inset()
+    }
+    fn size(&self) -> Size<Dimension> {
+        self.style.size()
+    }
+    fn min_size(&self) -> Size<Dimension> {
+        self.style.min_size()
+    }
+    fn max_size(&self) -> Size<Dimension> {
+        self.style.max_size()
+    }
+    fn aspect_ratio(&self) -> Option<f32> {
+        self.style.aspect_ratio()
+    }
+    fn margin(&self) -> Rect<LengthPercentageAuto> {
+        let mut margin = self.style.margin();
+        if let Some(margin_top) = self.margin_top {
+            margin.top = LengthPercentageAuto::length(margin_top);
+        }
+        margin
+    }
+    fn padding(&self) -> Rect<LengthPercentage> {
+        self.style.padding()
+    }
+    fn border(&self) -> Rect<LengthPercentage> {
+        self.style.border()
+    }
+}
+
+impl taffy::BlockItemStyle for PagedBlockItemStyle<'_> {
+    fn is_table(&self) -> bool {
+        self.style.is_table()
+    }
+    fn float(&self) -> Float {
+        self.style.float()
+    }
+    fn clear(&self) -> Clear {
+        self.style.clear()
+    }
+}
+
+/// Content which can be pushed onto the next page
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Anchor {
+    /// A block-level box (which is pushed down by increasing its top margin)
+    Block(usize),
+    /// A line (by index) of an inline formatting context (which is pushed down by inserting space
+    /// above it)
+    Line(usize, usize),
+}
+
+/// The break opportunities in the current layout of a document
+struct Fragmentation {
+    /// Positions at which the document may be broken, and the content which starts there
+    candidates: Vec<(f32, Anchor)>,
+    /// Forced breaks, and the content which starts there
+    forced: Vec<(f32, BreakBetween, Anchor)>,
+    /// Positions at which breaks should be avoided
+    avoid: Vec<f32>,
+    /// Ranges which can't be broken (line boxes and monolithic boxes)
+    unbreakable: Vec<Range<f32>>,
+    /// Ranges which should not be broken (`break-inside: avoid`)
+    avoid_inside: Vec<Range<f32>>,
+    /// The height of the document
+    height: f32,
+}
+
+impl Fragmentation {
+    fn new(doc: &BaseDocument) -> Self {
+        let root = doc.root_element();
+        let root_layout = &root.final_layout;
+        let mut fragmentation = Self {
+            candidates: Vec::new(),
+            forced: Vec::new(),
+            avoid: Vec::new(),
+            unbreakable: Vec::new(),
+            avoid_inside: Vec::new(),
+            height: root_layout.location.y
+                + root_layout.size.height.max(root_layout.content_size.height),
+        };
+        fragmentation.visit(doc, root.id, 0.0, false);
+
+        // Where several boxes start at the same position, the outermost (which was visited first)
+        // is pushed
+        fragmentation.candidates.sort_by(|a, b| a.0.total_cmp(&b.0));
+        fragmentation.candidates.dedup_by(|a, b| a.0 == b.0);
+        fragmentation.forced.sort_by(|a, b| a.0.total_cmp(&b.0));
+        fragmentation
+    }
+
+    /// Collect the break opportunities within a box. `is_block_child` is whether the box is laid
+    /// out by its parent's block layout (and so can be pushed down). Returns the values of the
+    /// breaks before and after the box (including those propagated from its first and last
+    /// children).
+    fn visit(
+        &mut self,
+        doc: &BaseDocument,
+        node_id: usize,
+        offset: f32,
+        is_block_child: bool,
+    ) -> (BreakBetween, BreakBetween) {
+        let node = &doc.nodes[node_id];
+        let style = BreakStyle::of(node);
+        let layout = &node.final_layout;
+        let top = offset + layout.location.y;
+        let bottom = top + layout.size.height;
+
+        if is_block_child {
+            self.candidates.push((top, Anchor::Block(node_id)));
+        }
+        if style.avoid_inside {
+            self.avoid_inside.push(top..bottom);
+        }
+
+        // Break properties only apply to block-level boxes (not to inline-level boxes)
+        if node.flags.is_inline_root() {
+            self.visit_lines(node, top, &style);
+            return (style.before, style.after);
+        }
+
+        let children = node.layout_children.borrow();
+        let has_children = children
+            .as_ref()
+            .is_some_and(|children| !children.is_empty());
+        let is_block_container = has_children
+            && node.style.display == Display::Block
+            && node.style.overflow.y == Overflow::Visible
+            && !node.flags.is_table_root()
+            && node.vertical_flow_root().is_none();
+        if !is_block_container {
+            self.unbreakable.push(top..bottom);
+            return (style.before, style.after);
+        }
+
+        let mut first = None;
+        let mut previous = None;
+        for &child_id in children.iter().flatten() {
+            let child = &doc.nodes[child_id];
+            if child.style.display == Display::None || child.style.position == Position::Absolute {
+                continue;
+            }
+            let child_top = top + child.final_layout.location.y;
+            if child.style.float != Float::None {
+                let child_bottom = child_top + child.final_layout.size.height;
+                self.unbreakable.push(child_top..child_bottom);
+                continue;
+            }
+
+            let (child_before, child_after) = self.visit(doc, child_id, top, true);
+            match previous {
+                None => first = Some(child_before),
+                Some(previous_after) => match BreakBetween::combine(previous_after, child_before) {
+                    BreakBetween::Auto => {}
+                    BreakBetween::Avoid => self.avoid.push(child_top),
+                    forced => self
+                        .forced
+                        .push((child_top, forced, Anchor::Block(child_id))),
+                },
+            }
+            previous = Some(child_after);
+        }
+
+        // Breaks before the first child and after the last child propagate to their parent
+        (
+            style.before.combine(first.unwrap_or_default()),
+            previous.unwrap_or_default().combine(style.after),
+        )
+    }
+
+    fn visit_lines(&mut self, node: &Node, top: f32, style: &BreakStyle) {
+        let Some(text_layout) = node
+            .element_data()
+            .and_then(|element| element.inline_layout_data.as_ref())
+        else {
+            return;
+        };
+        let layout = &text_layout.layout;
+        let scale = layout.scale();
+        let content_top = top + node.final_layout.padding.top + node.final_layout.border.top;
+
+        let line_count = layout.lines().count();
+        for (index, line) in layout.lines().enumerate() {
+            let metrics = line.metrics();
+            let line_shift = text_layout.line_offset(index).y;
+            let line_top = content_top + line_shift + metrics.min_coord / scale;
+            let line_bottom = content_top + line_shift + metrics.max_coord / scale;
+            self.unbreakable.push(line_top..line_bottom);
+            if index > 0 {
+                self.candidates
+                    .push((line_top, Anchor::Line(node.id, index)));
+                if index < style.orphans || line_count - index < style.widows {
+                    self.avoid.push(line_top);
+                }
+            }
+        }
+    }
+
+    /// Whether the document must be broken before `limit` (the end of a page which starts at
+    /// `start`), because `limit` is within content which can't (or shouldn't) be broken
+    fn must_break_before(&self, start: f32, limit: f32) -> bool {
+        let page_height = limit - start;
+        is_inside_any(&self.unbreakable, limit, page_height)
+            || is_inside_any(&self.avoid_inside, limit, page_height)
+            || self
+                .avoid
+                .iter()
+                .any(|avoid| (avoid - limit).abs() < EPSILON)
+    }
+
+    /// The best position at which to end a page which starts at `start` and whose content area
+    /// ends at `limit`, and the content which starts there. Returns `None` if there is no break
+    /// opportunity on the page (in which case content must be sliced at the end of the page).
+    fn best_break(&self, start: f32, limit: f32) -> Option<(f32, Anchor)> {
+        let page_height = limit - start;
+        let lower = self
+            .candidates
+            .partition_point(|(y, _)| *y <= start + MIN_FRAGMENT_HEIGHT);
+        let upper = self.candidates.partition_point(|(y, _)| *y < limit);
+
+        let mut fallback = None;
+        for &(y, anchor) in self.candidates[lower..upper.max(lower)].iter().rev() {
+            if is_inside_any(&self.unbreakable, y, page_height) {
+                continue;
+            }
+            let avoided = is_inside_any(&self.avoid_inside, y, page_height)
+                || self.avoid.iter().any(|avoid| (avoid - y).abs() < EPSILON);
+            if !avoided {
+                return Some((y, anchor));
+            }
+            fallback.get_or_insert((y, anchor));
+        }
+        fallback
+    }
+}
+
+/// Whether a position is strictly inside any of the ranges. Ranges taller than a page are ignored
+/// (as they have to be broken).
+fn is_inside_any(ranges: &[Range<f32>], y: f32, page_height: f32) -> bool {
+    ranges.iter().any(|range| {
+        range.start + EPSILON < y
+            && y < range.end - EPSILON
+            && range.end - range.start <= page_height
+    })
+}
+
+impl BaseDocument {
+    /// Resolve styles for `print` media rather than `screen` media
+    pub fn set_print_media(&mut self, print_media: bool) {
+        self.print_media = print_media;
+        self.set_stylist_device(crate::document::make_device(
+            &self.viewport,
+            self.print_media,
+            self.font_ctx.clone(),
+        ));
+    }
+
+    /// Whether the document is paginated (see [`BaseDocument::paginate`])
+    pub fn is_paginated(&self) -> bool {
+        self.pagination.is_some()
+    }
+
+    /// Lay out the document for paged media (e.g. for printing or PDF export) and divide it into
+    /// pages.
+    ///
+    /// Styles are resolved for `print` media, and the document is laid out with a viewport the
+    /// size of the content area of the first page. Content which would be split by the end of a
+    /// page is pushed onto the next page (see the [module docs](self)).
+    ///
+    /// The document stays laid out for the pages until [`BaseDocument::end_pagination`] is called,
+    /// so the pages can be painted (e.g. using `blitz_paint::paint_page`) after this returns. The
+    /// viewport shouldn't be changed in the meantime.
+    pub fn paginate(&mut self, options: &PageOptions) -> Vec<Page> {
+        self.end_pagination();
+        self.pagination = Some(Pagination {
+            viewport: self.viewport.clone(),
+            scroll: self.viewport_scroll,
+            print_media: self.print_media,
+            margins: HashMap::new(),
+            lines: HashMap::new(),
+        });
+        self.set_print_media(true);
+        self.resolve(0.0);
+
+        let page_styles = PageStyles::new(self, options);
+        let (size, margin) = page_styles.resolve(0, false);
+        self.lay_out_for_page(
+            (size.width - margin.left - margin.right).max(0.0),
+            (size.height - margin.top - margin.bottom).max(0.0),
+        );
+
+        let mut pages: Vec<Page> = Vec::new();
+        let mut offset = 0.0;
+        let mut pushes = 0;
+        let mut blank_page_follows = false;
+        loop {
+            let index = pages.len();
+            let (size, margin) = page_styles.resolve(index, false);
+            let limit = offset + fragmentainer_height(size, margin);
+            let fragmentation = Fragmentation::new(self);
+            if offset >= fragmentation.height && !pages.is_empty() {
+                break;
+            }
+
+            // Find the content (if any) which has to be pushed onto the next page. A forced break
+            // at the start of a page has no effect.
+            let forced = fragmentation
+                .forced
+                .iter()
+                .find(|(y, _, _)| *y > offset + MIN_FRAGMENT_HEIGHT)
+                .filter(|(y, _, _)| *y <= limit + EPSILON);
+            let push = match forced {
+                // A blank page is inserted if the content after a `left` or `right` break would
+                // otherwise start on the wrong side
+                Some(&(y, value, anchor)) => Some((
+                    y,
+                    anchor,
+                    value
+                        .side()
+                        .is_some_and(|side| side != PageSide::of_page(index + 1)),
+                )),
+                None if limit >= fragmentation.height => None,
+                None if fragmentation.must_break_before(offset, limit) => fragmentation
+                    .best_break(offset, limit)
+                    .map(|(y, anchor)| (y, anchor, false)),
+                None => None,
+            };
+            if let Some((y, anchor, needs_blank_page)) = push {
+                let next_offset = if needs_blank_page {
+                    let (size, margin) = page_styles.resolve(index + 1, true);
+                    limit + fragmentainer_height(size, margin)
+                } else {
+                    limit
+                };
+                if next_offset - y > EPSILON && pushes < MAX_PUSHES_PER_PAGE {
+                    self.push_onto_next_page(anchor, next_offset - y);
+                    blank_page_follows |= needs_blank_page;
+                    pushes += 1;
+                    continue;
+                }
+            }
+
+            pages.push(Page {
+                index,
+                side: PageSide::of_page(index),
+                blank: false,
+                size,
+                margin,
+                offset,
+                fragment_height: limit.min(fragmentation.height) - offset,
+            });
+            offset = limit;
+            pushes = 0;
+
+            if blank_page_follows && offset < fragmentation.height {
+                let index = pages.len();
+                let (size, margin) = page_styles.resolve(index, true);
+                pages.push(Page {
+                    index,
+                    side: PageSide::of_page(index),
+                    blank: true,
+                    size,
+                    margin,
+                    offset,
+                    fragment_height: 0.0,
+                });
+                offset += fragmentainer_height(size, margin);
+            }
+            blank_page_follows = false;
+        }
+
+        pages
+    }
+
+    /// Lay out a paginated document for the screen again, restoring the media type, viewport and
+    /// scroll position it had before [`BaseDocument::paginate`] was called. Does nothing if the
+    /// document isn't paginated.
+    pub fn end_pagination(&mut self) {
+        let Some(pagination) = self.pagination.take() else {
+            return;
+        };
+        for &node_id in pagination.margins.keys().chain(pagination.lines.keys()) {
+            self.clear_layout_cache_and_ancestors(node_id);
+        }
+        self.print_media = pagination.print_media;
+        self.set_viewport(pagination.viewport);
+        self.resolve(0.0);
+        self.set_viewport_scroll(pagination.scroll);
+    }
+
+    /// Lay out the document with a viewport the size of the content area of a page
+    fn lay_out_for_page(&mut self, content_width: f32, content_height: f32) {
+        let scale = self.viewport.scale_f64();
+        let mut viewport = self.viewport.clone();
+        viewport.window_size = (
+            (content_width as f64 * scale).round() as u32,
+            (content_height as f64 * scale).round() as u32,
+        );
+        self.set_viewport(viewport);
+        self.resolve(0.0);
+    }
+
+    /// Push content down by `distance` (onto the next page) and lay out the document again
+    fn push_onto_next_page(&mut self, anchor: Anchor, distance: f32) {
+        let Some(pagination) = self.pagination.as_mut() else {
+            return;
+        };
+        let node_id = match anchor {
+            Anchor::Block(node_id) => {
+                let margin_top = pagination
+                    .margins
+                    .get(&node_id)
+                    .copied()
+                    .unwrap_or(self.nodes[node_id].unrounded_layout.margin.top);
+                pagination.margins.insert(node_id, margin_top + distance);
+                node_id
+            }
+            Anchor::Line(node_id, line_index) => {
+                let struts = pagination.lines.entry(node_id).or_default();
+                if struts.len() <= line_index {
+                    struts.resize(line_index + 1, 0.0);
+                }
+                struts[line_index] += distance;
+                node_id
+            }
+        };
+        self.clear_layout_cache_and_ancestors(node_id);
+        self.resolve_layout();
+    }
+
+    fn clear_layout_cache_and_ancestors(&mut self, node_id: usize) {
+        let mut next = Some(node_id);
+        while let Some(node) = next.and_then(|node_id| self.nodes.get_mut(node_id)) {
+            node.cache.clear();
+            next = node.layout_parent.get();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_expose_paged_media_properties() {
+        let css = expose_paged_media_properties(
+            "
+        @page { size: A4 landscape; margin: 1in 2cm; }
+        @page :first, :left { margin-top: 10% !important }
+        @page chapter { size: letter }
+        h1 { BREAK-BEFORE: page !important; color: red }
+        @media print { figure { page-break-inside: avoid; orphans: 3 } }
+        a:hover { widows: 1 }
+        ",
+        );
+
+        assert!(css.contains(
+            ":root { --blitz-page-size: A4 landscape; --blitz-page-margin-top: 1in; \
+            --blitz-page-margin-right: 2cm; --blitz-page-margin-bottom: 1in; \
+            --blitz-page-margin-left: 2cm; }"
+        ));
+        assert!(css.contains(
+            ":root { --blitz-page-first-margin-top: 10% !important; \
+            --blitz-page-left-margin-top: 10% !important; }"
+        ));
+        assert!(!css.contains("@page"));
+        assert!(css.contains("h1 { --blitz-break-before: page !important; color: red }"));
+        assert!(css.contains("figure { --blitz-break-inside: avoid; --blitz-orphans: 3 } }"));
+        assert!(css.contains("a:hover { --blitz-widows: 1 }"));
+
+        // Other stylesheets are left alone
+        let css = "p { color: red }";
+        assert!(matches!(
+            expose_paged_media_properties(css),
+            Cow::Borrowed(_)
+        ));
+
+        let size = PageSize::parse("a4 landscape")
+            .unwrap()
+            .resolve(PageOptions::A4.size);
+        assert_eq!((size.width.round(), size.height.round()), (1123.0, 794.0));
+    }
+}
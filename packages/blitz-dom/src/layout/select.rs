@@ -0,0 +1,111 @@
+use markup5ever::local_name;
+
+use crate::{
+    BaseDocument,
+    node::{SelectData, SelectOption, SpecialElementData},
+    select::{apply_selectedness_setting_algorithm, select_display_size},
+    stylo_to_parley,
+};
+
+use super::construct::resolve_line_height;
+
+/// Collect the list of options for a \<select\> element and lay out their labels.
+///
+/// Selectedness is preserved for options that were already in the list. New options take
+/// their selectedness from their `selected` attribute.
+pub(super) fn create_select(doc: &mut BaseDocument, select_id: usize) {
+    let option_ids = doc.select_list_of_options(select_id);
+
+    let node = &doc.nodes[select_id];
+    let Some(styles) = node.primary_styles() else {
+        return;
+    };
+    let parley_style = stylo_to_parley::style(select_id, &styles);
+    drop(styles);
+
+    let element = node.element_data().unwrap();
+    let multiple = element.has_attr(local_name!("multiple"));
+    let display_size = select_display_size(element);
+    let previous = element.select_data();
+
+    let scale = doc.viewport.scale();
+    let mut font_ctx = doc.font_ctx.lock().unwrap();
+    let mut options = Vec::with_capacity(option_ids.len());
+    let mut content_width: f32 = 0.0;
+    for option_id in option_ids {
+        let option = &doc.nodes[option_id];
+        let text = collapse_whitespace(&option.text_content());
+        let label = match option.attr(local_name!("label")) {
+            Some(label) if !label.is_empty() => label.to_string(),
+            _ => text.clone(),
+        };
+        let value = option
+            .attr(local_name!("value"))
+            .map(str::to_string)
+            .unwrap_or(text);
+        let selected = previous
+            .and_then(|data| data.options.iter().find(|o| o.node_id == option_id))
+            .map(|o| o.selected)
+            .unwrap_or_else(|| option.data.has_attr(local_name!("selected")));
+        let in_optgroup = option.parent.is_some_and(|parent_id| {
+            doc.nodes[parent_id]
+                .data
+                .is_element_with_tag_name(&local_name!("optgroup"))
+        });
+
+        let mut builder = doc
+            .layout_ctx
+            .tree_builder(&mut font_ctx, scale, true, &parley_style);
+        builder.push_text(&label);
+        let mut layout = builder.build().0;
+        layout.break_all_lines(None);
+
+        let indent = if in_optgroup {
+            SelectOption::OPTGROUP_INDENT
+        } else {
+            0.0
+        };
+        content_width = content_width.max(layout.full_width() / scale + indent);
+
+        options.push(SelectOption {
+            node_id: option_id,
+            value,
+            selected,
+            disabled: doc.is_disabled(option_id),
+            in_optgroup,
+            layout: Box::new(layout),
+        });
+    }
+    drop(font_ctx);
+
+    let row_height = resolve_line_height(parley_style.line_height, parley_style.font_size);
+    let mut data = SelectData {
+        options,
+        multiple,
+        display_size,
+        content_width,
+        row_height,
+        highlighted: previous.and_then(|data| data.highlighted),
+        scroll_index: previous.map(|data| data.scroll_index).unwrap_or(0),
+    };
+    apply_selectedness_setting_algorithm(&mut data);
+    if data
+        .highlighted
+        .is_some_and(|idx| idx >= data.options.len())
+    {
+        data.highlighted = None;
+    }
+    data.scroll_index = data
+        .scroll_index
+        .min(data.options.len().saturating_sub(data.display_size));
+
+    doc.nodes[select_id]
+        .element_data_mut()
+        .unwrap()
+        .special_data = SpecialElementData::Select(Box::new(data));
+}
+
+/// <https://infra.spec.whatwg.org/#strip-and-collapse-ascii-whitespace>
+fn collapse_whitespace(text: &str) -> String {
+    text.split_ascii_whitespace().collect::<Vec<_>>().join(" ")
+}
@@ -0,0 +1,271 @@
+//! Sticky positioning
+//!
+//! Sticky boxes are laid out as if they were relatively positioned with no offset. They are then
+//! shifted at paint time by an offset which depends on the scroll position of their nearest scroll
+//! container, so the offset must be recomputed whenever layout or a scroll position changes.
+//!
+//! Fixed boxes within inline formatting contexts are laid out against the viewport at its initial
+//! scroll position (see `resolve_inline_absolute_positions`), and are kept in place by the same
+//! mechanism: their offset cancels out the scroll positions of the viewport and their ancestors.
+
+use style::computed_values::position::T as Position;
+use style::values::computed::Overflow;
+use taffy::{MaybeResolve as _, Rect};
+
+use super::resolve_calc_value;
+use crate::{BaseDocument, node::Node};
+
+impl BaseDocument {
+    /// Find all of the laid out nodes which have `position: sticky`, and those which have
+    /// `position: fixed` within an inline formatting context
+    pub(crate) fn collect_sticky_nodes(&mut self) {
+        fn collect_recursive(doc: &BaseDocument, node_id: usize, sticky_nodes: &mut Vec<usize>) {
+            let node = &doc.nodes[node_id];
+            let in_inline_context = node
+                .layout_parent
+                .get()
+                .is_some_and(|parent_id| doc.nodes[parent_id].flags.is_inline_root());
+            if node
+                .primary_styles()
+                .is_some_and(|s| match s.clone_position() {
+                    Position::Sticky => true,
+                    Position::Fixed => in_inline_context,
+                    _ => false,
+                })
+            {
+                sticky_nodes.push(node_id);
+            }
+            for &child_id in node.layout_children.borrow().iter().flatten() {
+                collect_recursive(doc, child_id, sticky_nodes);
+            }
+        }
+
+        let mut sticky_nodes = std::mem::take(&mut self.sticky_nodes);
+        sticky_nodes.clear();
+        collect_recursive(self, self.root_element().id, &mut sticky_nodes);
+        self.sticky_nodes = sticky_nodes;
+    }
+
+    /// Recompute the sticky offset of every sticky node from the current scroll positions
+    pub(crate) fn update_sticky_offsets(&mut self) {
+        for idx in 0..self.sticky_nodes.len() {
+            let node_id = self.sticky_nodes[idx];
+            let offset = self.compute_sticky_offset(node_id);
+            if self.nodes[node_id].sticky_offset != offset {
+                self.nodes[node_id].sticky_offset = offset;
+                self.paint_damage.insert(node_id);
+            }
+        }
+    }
+
+    fn compute_sticky_offset(&self, node_id: usize) -> crate::Point<f64> {
+        let node = &self.nodes[node_id];
+        let Some(parent_id) = node.layout_parent.get() else {
+            return crate::Point::ZERO;
+        };
+        if node
+            .primary_styles()
+            .is_some_and(|s| s.clone_position() == Position::Fixed)
+        {
+            return self.compute_fixed_offset(parent_id);
+        }
+        let Some(inset) = node.primary_styles().map(|s| {
+            let position = s.get_position();
+            Rect {
+                left: stylo_taffy::convert::inset(&position.left),
+                right: stylo_taffy::convert::inset(&position.right),
+                top: stylo_taffy::convert::inset(&position.top),
+                bottom: stylo_taffy::convert::inset(&position.bottom),
+            }
+        }) else {
+            return crate::Point::ZERO;
+        };
+
+        // The border box of the sticky box and the content box of its containing block, both
+        // of which are moved into the coordinate space of the scroll container as we walk up the tree
+        let layout = &node.final_layout;
+        let mut sticky_box = Rect {
+            left: layout.location.x,
+            right: layout.location.x + layout.size.width,
+            top: layout.location.y,
+            bottom: layout.location.y + layout.size.height,
+        };
+        let parent_layout = &self.nodes[parent_id].final_layout;
+        let mut containing_block = Rect {
+            left: parent_layout.border.left + parent_layout.padding.left,
+            right: parent_layout.size.width
+                - parent_layout.border.right
+                - parent_layout.padding.right,
+            top: parent_layout.border.top + parent_layout.padding.top,
+            bottom: parent_layout.size.height
+                - parent_layout.border.bottom
+                - parent_layout.padding.bottom,
+        };
+
+        // Find the scrollport (and scroll position) of the nearest scroll container. If there is no
+        // scroll container then the box sticks to the viewport.
+        let mut ancestor_id = parent_id;
+        let (scrollport, scroll) = loop {
+            let ancestor = &self.nodes[ancestor_id];
+            if is_scroll_container(ancestor) {
+                let layout = &ancestor.final_layout;
+                let scrollport = Rect {
+                    left: layout.border.left,
+                    right: layout.size.width - layout.border.right - layout.scrollbar_size.width,
+                    top: layout.border.top,
+                    bottom: layout.size.height
+                        - layout.border.bottom
+                        - layout.scrollbar_size.height,
+                };
+                break (scrollport, ancestor.scroll_offset);
+            }
+
+            let location = ancestor.final_layout.location;
+            sticky_box = translate(sticky_box, location.x, location.y);
+            containing_block = translate(containing_block, location.x, location.y);
+
+            match ancestor.layout_parent.get() {
+                Some(id) => ancestor_id = id,
+                None => {
+                    let scale = self.viewport.scale();
+                    let scrollport = Rect {
+                        left: 0.0,
+                        right: self.viewport.window_size.0 as f32 / scale,
+                        top: 0.0,
+                        bottom: self.viewport.window_size.1 as f32 / scale,
+                    };
+                    break (scrollport, self.viewport_scroll);
+                }
+            }
+        };
+
+        // The visible part of the scroll container's content
+        let visible = translate(scrollport, scroll.x as f32, scroll.y as f32);
+        let scrollport_width = Some(scrollport.right - scrollport.left);
+        let scrollport_height = Some(scrollport.bottom - scrollport.top);
+
+        let x = sticky_axis_offset(
+            (sticky_box.left, sticky_box.right),
+            (
+                containing_block.left + layout.margin.left,
+                containing_block.right - layout.margin.right,
+            ),
+            (visible.left, visible.right),
+            (
+                inset
+                    .left
+                    .maybe_resolve(scrollport_width, resolve_calc_value),
+                inset
+                    .right
+                    .maybe_resolve(scrollport_width, resolve_calc_value),
+            ),
+        );
+        let y = sticky_axis_offset(
+            (sticky_box.top, sticky_box.bottom),
+            (
+                containing_block.top + layout.margin.top,
+                containing_block.bottom - layout.margin.bottom,
+            ),
+            (visible.top, visible.bottom),
+            (
+                inset
+                    .top
+                    .maybe_resolve(scrollport_height, resolve_calc_value),
+                inset
+                    .bottom
+                    .maybe_resolve(scrollport_height, resolve_calc_value),
+            ),
+        );
+
+        crate::Point {
+            x: x as f64,
+            y: y as f64,
+        }
+    }
+
+    /// The offset which keeps a fixed box with the parent `parent_id` in place in the viewport: the
+    /// sum of the scroll positions of the viewport and the box's ancestors, less the sticky offsets
+    /// of its ancestors. Sticky nodes are collected in tree order, so the sticky offsets of ancestors
+    /// are already up to date.
+    fn compute_fixed_offset(&self, parent_id: usize) -> crate::Point<f64> {
+        let mut offset = self.viewport_scroll;
+        let mut ancestor_id = Some(parent_id);
+        while let Some(id) = ancestor_id {
+            let ancestor = &self.nodes[id];
+            offset.x += ancestor.scroll_offset.x - ancestor.sticky_offset.x;
+            offset.y += ancestor.scroll_offset.y - ancestor.sticky_offset.y;
+            ancestor_id = ancestor.layout_parent.get();
+        }
+        offset
+    }
+}
+
+fn is_scroll_container(node: &Node) -> bool {
+    node.primary_styles().is_some_and(|s| {
+        let is_scrollable = |overflow| {
+            matches!(
+                overflow,
+                Overflow::Hidden | Overflow::Scroll | Overflow::Auto
+            )
+        };
+        is_scrollable(s.clone_overflow_x()) || is_scrollable(s.clone_overflow_y())
+    })
+}
+
+fn translate(rect: Rect<f32>, x: f32, y: f32) -> Rect<f32> {
+    Rect {
+        left: rect.left + x,
+        right: rect.right + x,
+        top: rect.top + y,
+        bottom: rect.bottom + y,
+    }
+}
+
+/// Compute the sticky offset in one axis. Each argument is a (start, end) pair of coordinates
+/// in the same space: the sticky box, the area it must stay within (its containing block less its
+/// margins), the visible part of the scroll container, and the resolved insets.
+///
+/// The box is shifted to keep it at least `inset` away from the edges of the visible area, but is
+/// never shifted out of its containing block. If both insets apply then the start inset wins.
+fn sticky_axis_offset(
+    (start, end): (f32, f32),
+    (limit_start, limit_end): (f32, f32),
+    (visible_start, visible_end): (f32, f32),
+    (inset_start, inset_end): (Option<f32>, Option<f32>),
+) -> f32 {
+    let mut offset = 0.0;
+    if let Some(inset_end) = inset_end {
+        let desired = (visible_end - inset_end) - end;
+        offset = desired.max(limit_start - start).min(0.0);
+    }
+    if let Some(inset_start) = inset_start {
+        let desired = (visible_start + inset_start) - start;
+        offset = f32::max(offset, desired.min(limit_end - end));
+    }
+    offset
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sticky_axis_offset() {
+        // A header at y=100..120 in a 0..1000 container with `top: 0`
+        let offset = |scroll: f32| {
+            sticky_axis_offset(
+                (100.0, 120.0),
+                (0.0, 1000.0),
+                (scroll, scroll + 500.0),
+                (Some(0.0), None),
+            )
+        };
+
+        // Not yet scrolled past: no offset
+        assert_eq!(offset(0.0), 0.0);
+        // Scrolled past: sticks to the top of the scrollport
+        assert_eq!(offset(300.0), 200.0);
+        // Scrolled past the end of the containing block: stays within it
+        assert_eq!(offset(990.0), 880.0);
+    }
+}
@@ -1,33 +1,71 @@
+//! Table layout
+//!
+//! Tables are laid out as a CSS grid: cells are placed into the grid slots that they occupy
+//! (taking `rowspan` and `colspan` into account), and the column widths are computed by the auto
+//! or fixed table layout algorithm before the grid is laid out with a fixed-size track for each
+//! column. Captions are placed in rows above or below the table's rows which span every column.
+//!
+//! In the collapsing border model, the borders of adjacent cells (and of the rows, row groups,
+//! columns, column groups and table around them) are resolved into a single border for each cell
+//! edge. Each cell is laid out with half of the resolved border on each of its edges, and the
+//! resolved borders are painted by the cells (see `ElementData::collapsed_borders`).
+
 use std::{ops::Range, sync::Arc};
 
 use markup5ever::local_name;
+use style::Atom;
+use style::computed_values::{
+    border_collapse::T as BorderCollapse, caption_side::T as CaptionSide,
+    table_layout::T as TableLayout,
+};
+use style::properties::ComputedValues;
+use style::values::specified::BorderStyle;
 use style::values::specified::box_::{DisplayInside, DisplayOutside};
-use style::{Atom, computed_values::table_layout::T as TableLayout};
 use taffy::{
-    Dimension, LayoutPartialTree as _, ResolveOrZero, TrackSizingFunction, compute_leaf_layout,
-    style_helpers,
+    AvailableSpace, CompactLength, Dimension, LayoutInput, LayoutOutput, LayoutPartialTree as _,
+    Line, NodeId, RequestedAxis, ResolveOrZero, RunMode, Size, SizingMode, TrackSizingFunction,
+    compute_grid_layout, compute_leaf_layout, style_helpers,
 };
 
 use crate::BaseDocument;
+use crate::node::CollapsedBorder;
 
 use super::damage::{CONSTRUCT_BOX, CONSTRUCT_DESCENDENT, CONSTRUCT_FC};
 use super::resolve_calc_value;
 
+/// The maximum `colspan` of a cell (as specified by HTML)
+const MAX_COLSPAN: u16 = 1000;
+/// The maximum `rowspan` of a cell (as specified by HTML)
+const MAX_ROWSPAN: u16 = 65534;
+/// The maximum number of rows, columns or captions of a table. Rows, columns and captions
+/// beyond this are dropped so that every grid line fits in an `i16`.
+const MAX_TRACKS: u16 = i16::MAX as u16 / 2;
+
 pub struct TableTreeWrapper<'doc> {
     pub(crate) doc: &'doc mut BaseDocument,
     pub(crate) ctx: Arc<TableContext>,
+    /// The table's grid container style, with the column widths resolved
+    pub(crate) style: taffy::Style<Atom>,
 }
 
 #[derive(Debug, Clone)]
 pub struct TableContext {
+    table_id: usize,
     style: taffy::Style<Atom>,
     items: Vec<TableItem>,
+    rows: Vec<TableRow>,
+    columns: Vec<TableColumn>,
+    /// The item index of the cell occupying each slot of the table (row-major)
+    slots: Vec<Option<usize>>,
+    is_fixed: bool,
+    border_collapse: bool,
 }
 
 #[derive(Debug, Clone, Eq, PartialEq)]
 pub enum TableItemKind {
     Row,
     Cell,
+    Caption,
 }
 
 #[derive(Debug, Clone)]
@@ -35,16 +73,126 @@ pub struct TableItem {
     kind: TableItemKind,
     node_id: usize,
     style: taffy::Style<Atom>,
+    /// The first row and column of the item (rows and cells only)
+    row: u16,
+    col: u16,
+    row_span: u16,
+    col_span: u16,
+}
+
+#[derive(Debug, Clone)]
+struct TableRow {
+    node_id: Option<usize>,
+    group_id: Option<usize>,
+}
+
+#[derive(Debug, Clone, Default)]
+struct TableColumn {
+    /// The width specified by the `<col>` or `<colgroup>` of the column
+    width: Option<Dimension>,
+    node_id: Option<usize>,
+    group_id: Option<usize>,
+}
+
+/// The state of the table's slots during row and cell collection
+#[derive(Default)]
+struct TableBuilder {
+    items: Vec<TableItem>,
+    rows: Vec<TableRow>,
+    /// The number of rows which each column is still occupied for by a cell with a `rowspan`
+    col_rowspans: Vec<u16>,
+    /// The current column within the current row
+    col: u16,
+    /// The current row group, and the cells with `rowspan="0"` within it
+    group_id: Option<usize>,
+    group_start: usize,
+    zero_rowspan_cells: Vec<usize>,
+}
+
+impl TableBuilder {
+    fn start_row(&mut self, node_id: Option<usize>) {
+        for remaining in self.col_rowspans.iter_mut() {
+            *remaining = remaining.saturating_sub(1);
+        }
+        self.col = 0;
+        self.rows.push(TableRow {
+            node_id,
+            group_id: self.group_id,
+        });
+    }
+
+    fn start_group(&mut self, group_id: Option<usize>) {
+        self.end_group();
+        self.group_id = group_id;
+        self.group_start = self.rows.len();
+    }
+
+    /// Cells with `rowspan="0"` span the remaining rows of their row group. No cell can span past
+    /// the end of its row group.
+    fn end_group(&mut self) {
+        let group_end = self.rows.len() as u16;
+        for idx in std::mem::take(&mut self.zero_rowspan_cells) {
+            let item = &mut self.items[idx];
+            item.row_span = (group_end - item.row).max(1);
+        }
+        for item in &mut self.items[..] {
+            if item.kind == TableItemKind::Cell && item.row as usize >= self.group_start {
+                item.row_span = item.row_span.min(group_end.saturating_sub(item.row)).max(1);
+            }
+        }
+        self.col_rowspans
+            .iter_mut()
+            .for_each(|remaining| *remaining = 0);
+    }
+
+    /// Place a cell in the first free slot of the current row
+    fn push_cell(&mut self, node_id: usize, style: taffy::Style<Atom>, colspan: u16, rowspan: u16) {
+        if self.rows.is_empty() {
+            self.start_row(None);
+        }
+        while self
+            .col_rowspans
+            .get(self.col as usize)
+            .is_some_and(|remaining| *remaining > 0)
+        {
+            self.col += 1;
+        }
+
+        // Cells which would start past the last column are dropped
+        let col = self.col;
+        if col >= MAX_TRACKS {
+            return;
+        }
+        let colspan = colspan.min(MAX_TRACKS - col);
+        let row = (self.rows.len() - 1) as u16;
+        let end = (col + colspan) as usize;
+        if self.col_rowspans.len() < end {
+            self.col_rowspans.resize(end, 0);
+        }
+        for remaining in &mut self.col_rowspans[col as usize..end] {
+            *remaining = rowspan.max(1);
+        }
+
+        if rowspan == 0 {
+            self.zero_rowspan_cells.push(self.items.len());
+        }
+        self.items.push(TableItem {
+            kind: TableItemKind::Cell,
+            node_id,
+            style,
+            row,
+            col,
+            row_span: rowspan.max(1),
+            col_span: colspan,
+        });
+        self.col += colspan;
+    }
 }
 
 pub(crate) fn build_table_context(
     doc: &mut BaseDocument,
     table_root_node_id: usize,
 ) -> (TableContext, Vec<usize>) {
-    let mut items: Vec<TableItem> = Vec::new();
-    let mut row = 0u16;
-    let mut col = 0u16;
-
     let root_node = &mut doc.nodes[table_root_node_id];
 
     let children = std::mem::take(&mut root_node.children);
@@ -61,48 +209,174 @@ pub(crate) fn build_table_context(
         TableLayout::Fixed => true,
         TableLayout::Auto => false,
     };
+    let border_collapse = stylo_styles.clone_border_collapse() == BorderCollapse::Collapse;
+
+    // In the separated borders model, the border spacing separates the cells from each other and
+    // from the table's border. In the collapsing border model tables have no padding.
+    if border_collapse {
+        style.padding = taffy::Rect::zero();
+        style.gap = Size::zero();
+    } else {
+        let spacing = stylo_styles.get_inherited_table().border_spacing.clone();
+        let horizontal = spacing.horizontal().to_f32_px();
+        let vertical = spacing.vertical().to_f32_px();
+        let add_spacing = |padding: taffy::LengthPercentage, spacing: f32| match padding.tag() {
+            CompactLength::LENGTH_TAG => style_helpers::length(padding.value() + spacing),
+            _ => padding,
+        };
+        style.padding = taffy::Rect {
+            left: add_spacing(style.padding.left, horizontal),
+            right: add_spacing(style.padding.right, horizontal),
+            top: add_spacing(style.padding.top, vertical),
+            bottom: add_spacing(style.padding.bottom, vertical),
+        };
+        style.gap = Size {
+            width: style_helpers::length(horizontal),
+            height: style_helpers::length(vertical),
+        };
+    }
 
     drop(stylo_styles);
 
-    let mut column_sizes: Vec<taffy::Dimension> = Vec::new();
-    for child_id in children.iter().copied() {
-        collect_table_cells(
-            doc,
-            child_id,
-            is_fixed,
-            &mut row,
-            &mut col,
-            &mut items,
-            &mut column_sizes,
-        );
+    // The first header group is rendered before all other rows, and the first footer group after
+    let display_inside = |doc: &BaseDocument, node_id: usize| {
+        doc.nodes[node_id]
+            .primary_styles()
+            .map(|style| style.clone_display().inside())
+    };
+    let header = children
+        .iter()
+        .copied()
+        .find(|id| display_inside(doc, *id) == Some(DisplayInside::TableHeaderGroup));
+    let footer = children
+        .iter()
+        .copied()
+        .find(|id| display_inside(doc, *id) == Some(DisplayInside::TableFooterGroup));
+    let ordered_children = header
+        .into_iter()
+        .chain(
+            children
+                .iter()
+                .copied()
+                .filter(|id| Some(*id) != header && Some(*id) != footer),
+        )
+        .chain(footer);
+
+    let mut builder = TableBuilder::default();
+    let mut columns: Vec<TableColumn> = Vec::new();
+    let mut captions: Vec<(usize, CaptionSide)> = Vec::new();
+    for child_id in ordered_children {
+        collect_table_cells(doc, child_id, &mut builder, &mut columns, &mut captions);
     }
-    column_sizes.resize(col as usize, style_helpers::auto());
+    builder.end_group();
 
-    style.grid_template_columns = column_sizes
-        .into_iter()
-        .map(|dim| TrackSizingFunction::from(dim).into())
-        .collect();
-    style.grid_template_rows = vec![style_helpers::auto(); row as usize];
+    let TableBuilder {
+        mut items, rows, ..
+    } = builder;
+    let row_count = rows.len();
+    let col_count = items
+        .iter()
+        .filter(|item| item.kind == TableItemKind::Cell)
+        .map(|cell| (cell.col + cell.col_span) as usize)
+        .max()
+        .unwrap_or(0)
+        .max(columns.len())
+        .min(MAX_TRACKS as usize);
+    columns.resize_with(col_count, TableColumn::default);
+
+    let mut slots = vec![None; row_count * col_count];
+    for (idx, item) in items.iter().enumerate() {
+        if item.kind != TableItemKind::Cell {
+            continue;
+        }
+        for row in item.row..item.row + item.row_span {
+            for col in item.col..item.col + item.col_span {
+                slots[row as usize * col_count + col as usize] = Some(idx);
+            }
+        }
+    }
+
+    // Place the items in the grid, with the captions in rows above and below the table's rows
+    let top_caption_count = captions
+        .iter()
+        .filter(|(_, side)| *side == CaptionSide::Top)
+        .count();
+    for item in &mut items {
+        item.style.grid_row = taffy::Line {
+            start: style_helpers::line(grid_line(top_caption_count + item.row as usize)),
+            end: style_helpers::span(item.row_span),
+        };
+        item.style.grid_column = match item.kind {
+            TableItemKind::Row => taffy::Line {
+                start: style_helpers::line(1),
+                end: style_helpers::line(-1),
+            },
+            _ => taffy::Line {
+                start: style_helpers::line(grid_line(item.col as usize)),
+                end: style_helpers::span(item.col_span),
+            },
+        };
+    }
+    let mut top_row = 0;
+    let mut bottom_row = top_caption_count + row_count;
+    for (node_id, side) in captions {
+        let row = match side {
+            CaptionSide::Top => &mut top_row,
+            CaptionSide::Bottom => &mut bottom_row,
+        };
+        let row_line = grid_line(*row);
+        *row += 1;
+        let mut style = stylo_taffy::to_taffy_style(&doc.nodes[node_id].primary_styles().unwrap());
+        style.grid_row = taffy::Line {
+            start: style_helpers::line(row_line),
+            end: style_helpers::span(1),
+        };
+        style.grid_column = taffy::Line {
+            start: style_helpers::line(1),
+            end: style_helpers::line(-1),
+        };
+        items.push(TableItem {
+            kind: TableItemKind::Caption,
+            node_id,
+            style,
+            row: 0,
+            col: 0,
+            row_span: 1,
+            col_span: 1,
+        });
+    }
+
+    // Column widths are computed during layout (see `compute_table_layout`)
+    style.grid_template_columns = vec![style_helpers::auto(); col_count];
+    style.grid_template_rows = vec![style_helpers::auto(); bottom_row];
 
     let layout_children = items
         .iter()
-        .filter(|item| item.kind == TableItemKind::Cell)
+        .filter(|item| item.kind != TableItemKind::Row)
         .map(|cell| cell.node_id)
         .collect();
     let root_node = &mut doc.nodes[table_root_node_id];
     root_node.children = children;
 
-    (TableContext { style, items }, layout_children)
+    let context = TableContext {
+        table_id: table_root_node_id,
+        style,
+        items,
+        rows,
+        columns,
+        slots,
+        is_fixed,
+        border_collapse,
+    };
+    (context, layout_children)
 }
 
-pub(crate) fn collect_table_cells(
+fn collect_table_cells(
     doc: &mut BaseDocument,
     node_id: usize,
-    is_fixed: bool,
-    row: &mut u16,
-    col: &mut u16,
-    cells: &mut Vec<TableItem>,
-    columns: &mut Vec<Dimension>,
+    builder: &mut TableBuilder,
+    columns: &mut Vec<TableColumn>,
+    captions: &mut Vec<(usize, CaptionSide)>,
 ) {
     let node = &doc.nodes[node_id];
 
@@ -120,118 +394,129 @@ pub(crate) fn collect_table_cells(
         return;
     }
 
+    if display.outside() == DisplayOutside::TableCaption {
+        let side = node.primary_styles().unwrap().clone_caption_side();
+        if captions.len() < MAX_TRACKS as usize {
+            captions.push((node_id, side));
+        }
+        return;
+    }
+
     match display.inside() {
         DisplayInside::TableRowGroup
         | DisplayInside::TableHeaderGroup
-        | DisplayInside::TableFooterGroup
-        | DisplayInside::Contents => {
+        | DisplayInside::TableFooterGroup => {
+            node.remove_damage(CONSTRUCT_DESCENDENT | CONSTRUCT_FC | CONSTRUCT_BOX);
+            builder.start_group(Some(node_id));
             let children = std::mem::take(&mut doc.nodes[node_id].children);
             for child_id in children.iter().copied() {
                 doc.nodes[child_id]
                     .remove_damage(CONSTRUCT_DESCENDENT | CONSTRUCT_FC | CONSTRUCT_BOX);
-                collect_table_cells(doc, child_id, is_fixed, row, col, cells, columns);
+                collect_table_cells(doc, child_id, builder, columns, captions);
+            }
+            doc.nodes[node_id].children = children;
+            builder.start_group(None);
+        }
+        DisplayInside::Contents => {
+            let children = std::mem::take(&mut doc.nodes[node_id].children);
+            for child_id in children.iter().copied() {
+                doc.nodes[child_id]
+                    .remove_damage(CONSTRUCT_DESCENDENT | CONSTRUCT_FC | CONSTRUCT_BOX);
+                collect_table_cells(doc, child_id, builder, columns, captions);
             }
             doc.nodes[node_id].children = children;
         }
         DisplayInside::TableRow => {
             node.remove_damage(CONSTRUCT_DESCENDENT | CONSTRUCT_FC | CONSTRUCT_BOX);
-            *row += 1;
-            *col = 0;
-
-            {
-                let stylo_style = &node.primary_styles().unwrap();
-                let mut style = stylo_taffy::to_taffy_style(stylo_style);
-                style.grid_column = taffy::Line {
-                    start: style_helpers::line(0),
-                    end: style_helpers::line(-1),
-                };
-                style.grid_row = taffy::Line {
-                    start: style_helpers::line(*row as i16),
-                    end: style_helpers::span(1),
-                };
-                cells.push(TableItem {
-                    kind: TableItemKind::Row,
-                    node_id,
-                    style,
-                });
+            // Rows past the last row are dropped
+            if builder.rows.len() >= MAX_TRACKS as usize {
+                return;
             }
+            builder.start_row(Some(node_id));
+
+            let style = stylo_taffy::to_taffy_style(&node.primary_styles().unwrap());
+            builder.items.push(TableItem {
+                kind: TableItemKind::Row,
+                node_id,
+                style,
+                row: (builder.rows.len() - 1) as u16,
+                col: 0,
+                row_span: 1,
+                col_span: 1,
+            });
 
             let children = std::mem::take(&mut doc.nodes[node_id].children);
             for child_id in children.iter().copied() {
-                collect_table_cells(doc, child_id, is_fixed, row, col, cells, columns);
+                collect_table_cells(doc, child_id, builder, columns, captions);
             }
             doc.nodes[node_id].children = children;
         }
         DisplayInside::TableCell => {
             // node.remove_damage(CONSTRUCT_DESCENDENT | CONSTRUCT_FC | CONSTRUCT_BOX);
-            let stylo_style = &node.primary_styles().unwrap();
-            let colspan: u16 = node
-                .attr(local_name!("colspan"))
-                .and_then(|val| val.parse().ok())
-                .unwrap_or(1);
-            let mut style = stylo_taffy::to_taffy_style(stylo_style);
-
-            // TODO: account for padding/border/margin
-            if *row == 1 {
-                let column = match style.size.width.tag() {
-                    taffy::CompactLength::LENGTH_TAG => {
-                        let len = style.size.width.value();
-                        let padding = style.padding.resolve_or_zero(None, resolve_calc_value);
-                        style_helpers::length(len + padding.left + padding.right)
-                    }
-                    taffy::CompactLength::PERCENT_TAG => {
-                        style_helpers::percent(style.size.width.value())
-                    }
-                    taffy::CompactLength::AUTO_TAG => style_helpers::auto(),
-                    _ => unreachable!(),
-                };
-                columns.push(column);
-            } else if !is_fixed
-                && (*col as usize) < columns.len()
-                && taffy::CompactLength::LENGTH_TAG == style.size.width.tag()
-            {
-                let new_len = style.size.width.value();
-                let tag = columns[*col as usize].tag();
-                let value = columns[*col as usize].value();
-                columns[*col as usize] = match tag {
-                    taffy::CompactLength::LENGTH_TAG => style_helpers::length(value.max(new_len)),
-                    taffy::CompactLength::AUTO_TAG => style_helpers::length(new_len),
-                    taffy::CompactLength::PERCENT_TAG => style_helpers::percent(value),
-                    _ => unreachable!(),
-                }
-            }
-
-            style.grid_column = taffy::Line {
-                start: style_helpers::line((*col + 1) as i16),
-                end: style_helpers::span(colspan),
-            };
-            style.grid_row = taffy::Line {
-                start: style_helpers::line(*row as i16),
-                end: style_helpers::span(1),
+            let span_attr = |name| {
+                node.attr(name)
+                    .and_then(|val| val.trim().parse::<u16>().ok())
             };
+            let colspan = span_attr(local_name!("colspan"))
+                .unwrap_or(1)
+                .clamp(1, MAX_COLSPAN);
+            let rowspan = span_attr(local_name!("rowspan"))
+                .unwrap_or(1)
+                .min(MAX_ROWSPAN);
+
+            let mut style = stylo_taffy::to_taffy_style(&node.primary_styles().unwrap());
             style.size.width = style_helpers::auto();
-            cells.push(TableItem {
-                kind: TableItemKind::Cell,
-                node_id,
-                style,
-            });
+            builder.push_cell(node_id, style, colspan, rowspan);
+        }
+        DisplayInside::TableColumnGroup => {
+            node.remove_damage(CONSTRUCT_DESCENDENT | CONSTRUCT_FC | CONSTRUCT_BOX);
+            let width = column_width(node.primary_styles().as_deref());
+            let start = columns.len();
+
+            let children = std::mem::take(&mut doc.nodes[node_id].children);
+            for child_id in children.iter().copied() {
+                let child = &doc.nodes[child_id];
+                let is_column = child
+                    .primary_styles()
+                    .is_some_and(|s| s.clone_display().inside() == DisplayInside::TableColumn);
+                if is_column {
+                    child.remove_damage(CONSTRUCT_DESCENDENT | CONSTRUCT_FC | CONSTRUCT_BOX);
+                    push_columns(columns, &doc.nodes[child_id], Some(node_id));
+                }
+            }
+            doc.nodes[node_id].children = children;
 
-            *col += colspan;
+            // A column group without columns spans `span` columns
+            let node = &doc.nodes[node_id];
+            if columns.len() == start {
+                let span = node
+                    .attr(local_name!("span"))
+                    .and_then(|val| val.trim().parse::<u16>().ok())
+                    .unwrap_or(1)
+                    .clamp(1, MAX_COLSPAN);
+                for _ in 0..span {
+                    columns.push(TableColumn {
+                        width,
+                        node_id: None,
+                        group_id: Some(node_id),
+                    });
+                }
+            }
+            for column in &mut columns[start..] {
+                column.width = column.width.or(width);
+            }
+        }
+        DisplayInside::TableColumn => {
+            node.remove_damage(CONSTRUCT_DESCENDENT | CONSTRUCT_FC | CONSTRUCT_BOX);
+            push_columns(columns, node, None);
         }
         DisplayInside::Flow
         | DisplayInside::FlowRoot
         | DisplayInside::Flex
-        | DisplayInside::Grid => {
-            node.remove_damage(CONSTRUCT_DESCENDENT | CONSTRUCT_FC | CONSTRUCT_BOX);
-            // Probably a table caption: ignore
-            // println!(
-            //     "Warning: ignoring non-table typed descendent of table ({:?})",
-            //     display.inside()
-            // );
-        }
-        DisplayInside::TableColumnGroup | DisplayInside::TableColumn | DisplayInside::Table => {
+        | DisplayInside::Grid
+        | DisplayInside::Table => {
             node.remove_damage(CONSTRUCT_DESCENDENT | CONSTRUCT_FC | CONSTRUCT_BOX);
-            //Ignore
+            // Non-table descendents of tables are ignored
         }
         DisplayInside::None => {
             node.remove_damage(CONSTRUCT_DESCENDENT | CONSTRUCT_FC | CONSTRUCT_BOX);
@@ -240,6 +525,709 @@ pub(crate) fn collect_table_cells(
     }
 }
 
+/// The grid line at the start of the row or column with index `track`
+fn grid_line(track: usize) -> i16 {
+    i16::try_from(track.saturating_add(1)).unwrap_or(i16::MAX)
+}
+
+/// Add the columns of a `<col>` element (which spans `span` columns)
+fn push_columns(columns: &mut Vec<TableColumn>, node: &crate::Node, group_id: Option<usize>) {
+    let width = column_width(node.primary_styles().as_deref());
+    let span = node
+        .attr(local_name!("span"))
+        .and_then(|val| val.trim().parse::<u16>().ok())
+        .unwrap_or(1)
+        .clamp(1, MAX_COLSPAN);
+    for _ in 0..span {
+        columns.push(TableColumn {
+            width,
+            node_id: Some(node.id),
+            group_id,
+        });
+    }
+}
+
+fn column_width(style: Option<&ComputedValues>) -> Option<Dimension> {
+    let width = stylo_taffy::convert::dimension(&style?.clone_width());
+    (!width.is_auto()).then_some(width)
+}
+
+/// The intrinsic width contributions of the cells in a column
+#[derive(Debug, Clone, Copy, Default)]
+struct ColumnConstraints {
+    min: f32,
+    max: f32,
+    /// The width of the column specified by a `<col>` or a cell
+    fixed: Option<f32>,
+    percent: Option<f32>,
+}
+
+/// Resolve a specified width to a length (percentages are resolved against `basis`)
+fn resolve_width(width: Dimension, basis: Option<f32>) -> Option<f32> {
+    match width.tag() {
+        CompactLength::LENGTH_TAG => Some(width.value()),
+        CompactLength::PERCENT_TAG => basis.map(|basis| width.value() * basis),
+        _ => None,
+    }
+}
+
+/// Distribute `extra` width between some columns in proportion to `weights` (or equally if every
+/// weight is zero)
+fn distribute(widths: &mut [f32], weights: &[f32], extra: f32) {
+    if widths.is_empty() || extra <= 0.0 {
+        return;
+    }
+    let total: f32 = weights.iter().sum();
+    for (width, weight) in widths.iter_mut().zip(weights) {
+        *width += match total > 0.0 {
+            true => extra * weight / total,
+            false => extra / weights.len() as f32,
+        };
+    }
+}
+
+impl BaseDocument {
+    /// The min-content and max-content width of a table cell
+    fn table_cell_intrinsic_widths(&mut self, node_id: usize) -> (f32, f32) {
+        let mut measure = |available_space| {
+            self.compute_child_layout(
+                NodeId::from(node_id),
+                LayoutInput {
+                    run_mode: RunMode::ComputeSize,
+                    sizing_mode: SizingMode::InherentSize,
+                    axis: RequestedAxis::Horizontal,
+                    known_dimensions: Size::NONE,
+                    parent_size: Size::NONE,
+                    available_space: Size {
+                        width: available_space,
+                        height: AvailableSpace::MaxContent,
+                    },
+                    vertical_margins_are_collapsible: Line::FALSE,
+                },
+            )
+            .size
+            .width
+        };
+        let min = measure(AvailableSpace::MinContent);
+        let max = measure(AvailableSpace::MaxContent);
+        (min, max.max(min))
+    }
+
+    /// The border-box width specified by the styles of a table cell
+    fn table_cell_specified_width(&self, node_id: usize, basis: Option<f32>) -> Option<Dimension> {
+        let node = &self.nodes[node_id];
+        let styles = node.primary_styles()?;
+        let width = stylo_taffy::convert::dimension(&styles.clone_width());
+        if width.tag() != CompactLength::LENGTH_TAG {
+            return (width.tag() == CompactLength::PERCENT_TAG).then_some(width);
+        }
+        let mut width = width.value();
+        if node.style.box_sizing == taffy::BoxSizing::ContentBox {
+            let padding = node
+                .style
+                .padding
+                .resolve_or_zero(basis, resolve_calc_value);
+            let border = node.style.border.resolve_or_zero(basis, resolve_calc_value);
+            width += padding.left + padding.right + border.left + border.right;
+        }
+        Some(style_helpers::length(width))
+    }
+
+    /// Compute the width of each column of a table. `table_width` is the specified width of the
+    /// table's grid (excluding its padding and border) and `gaps` is the total spacing between
+    /// its columns.
+    fn compute_table_column_widths(
+        &mut self,
+        ctx: &TableContext,
+        table_width: Option<f32>,
+        available_width: AvailableSpace,
+        gaps: f32,
+    ) -> Vec<f32> {
+        let col_count = ctx.columns.len();
+
+        // Fixed table layout: the column widths are determined by the `<col>` elements and the
+        // cells in the first row
+        if ctx.is_fixed {
+            if let Some(table_width) = table_width {
+                let mut widths: Vec<Option<f32>> = ctx
+                    .columns
+                    .iter()
+                    .map(|column| {
+                        column
+                            .width
+                            .and_then(|w| resolve_width(w, Some(table_width)))
+                    })
+                    .collect();
+                for cell in ctx
+                    .items
+                    .iter()
+                    .filter(|item| item.kind == TableItemKind::Cell && item.row == 0)
+                {
+                    let cols = cell.col as usize..(cell.col + cell.col_span) as usize;
+                    if widths[cols.clone()].iter().any(Option::is_some) {
+                        continue;
+                    }
+                    let width = self
+                        .table_cell_specified_width(cell.node_id, Some(table_width))
+                        .and_then(|w| resolve_width(w, Some(table_width)));
+                    if let Some(width) = width {
+                        let span_width = (width
+                            - gaps * (cell.col_span - 1) as f32
+                                / col_count.saturating_sub(1).max(1) as f32)
+                            / cell.col_span as f32;
+                        widths[cols].fill(Some(span_width.max(0.0)));
+                    }
+                }
+
+                let fixed_sum: f32 = widths.iter().flatten().sum();
+                let auto_count = widths.iter().filter(|w| w.is_none()).count();
+                let remaining = (table_width - gaps - fixed_sum).max(0.0);
+                let mut widths: Vec<f32> = widths
+                    .into_iter()
+                    .map(|w| w.unwrap_or(remaining / auto_count.max(1) as f32))
+                    .collect();
+                // Any remaining width is distributed between all of the columns
+                if auto_count == 0 {
+                    let weights = widths.clone();
+                    distribute(&mut widths, &weights, remaining);
+                }
+                return widths;
+            }
+        }
+
+        // Auto table layout: compute the min-content and max-content width of each column from
+        // the cells in it, then distribute the table's width between the columns
+        let basis = table_width;
+        let mut constraints = vec![ColumnConstraints::default(); col_count];
+        for (column, constraint) in ctx.columns.iter().zip(constraints.iter_mut()) {
+            match column.width.map(|width| width.tag()) {
+                Some(CompactLength::LENGTH_TAG) => {
+                    let width = column.width.unwrap().value();
+                    constraint.fixed = Some(width);
+                    constraint.min = width;
+                }
+                Some(CompactLength::PERCENT_TAG) => {
+                    constraint.percent = Some(column.width.unwrap().value())
+                }
+                _ => {}
+            }
+        }
+
+        let mut spanning_cells = Vec::new();
+        for cell in ctx
+            .items
+            .iter()
+            .filter(|item| item.kind == TableItemKind::Cell)
+        {
+            let (mut min, mut max) = self.table_cell_intrinsic_widths(cell.node_id);
+            let specified = self.table_cell_specified_width(cell.node_id, basis);
+            let mut percent = None;
+            match specified.map(|width| width.tag()) {
+                Some(CompactLength::LENGTH_TAG) => {
+                    let width = specified.unwrap().value();
+                    min = min.max(width);
+                    max = min.max(width);
+                }
+                Some(CompactLength::PERCENT_TAG) => percent = Some(specified.unwrap().value()),
+                _ => {}
+            }
+
+            if cell.col_span > 1 {
+                spanning_cells.push((cell, min, max));
+                continue;
+            }
+            let constraint = &mut constraints[cell.col as usize];
+            constraint.min = constraint.min.max(min);
+            constraint.max = constraint.max.max(max);
+            if specified.is_some_and(|width| width.tag() == CompactLength::LENGTH_TAG) {
+                constraint.fixed = Some(constraint.fixed.unwrap_or(0.0).max(max));
+            }
+            if let Some(percent) = percent {
+                constraint.percent = Some(constraint.percent.unwrap_or(0.0).max(percent));
+            }
+        }
+        for constraint in &mut constraints {
+            constraint.max = match constraint.fixed {
+                Some(fixed) => fixed.max(constraint.min),
+                None => constraint.max.max(constraint.min),
+            };
+        }
+
+        // Distribute the widths of cells which span multiple columns between those columns (in
+        // proportion to the max-content widths of the columns)
+        spanning_cells.sort_by_key(|(cell, _, _)| cell.col_span);
+        let gap = gaps / col_count.saturating_sub(1).max(1) as f32;
+        for (cell, min, max) in spanning_cells {
+            let cols = cell.col as usize..(cell.col + cell.col_span) as usize;
+            let span_gaps = gap * (cell.col_span - 1) as f32;
+            let weights: Vec<f32> = constraints[cols.clone()].iter().map(|c| c.max).collect();
+
+            let mut mins: Vec<f32> = constraints[cols.clone()].iter().map(|c| c.min).collect();
+            let extra_min = min - span_gaps - mins.iter().sum::<f32>();
+            distribute(&mut mins, &weights, extra_min);
+            let mut maxes = weights.clone();
+            let extra_max = max - span_gaps - maxes.iter().sum::<f32>();
+            distribute(&mut maxes, &weights, extra_max);
+
+            for ((constraint, min), max) in constraints[cols].iter_mut().zip(mins).zip(maxes) {
+                constraint.min = min;
+                constraint.max = max.max(min);
+            }
+        }
+
+        let min_sum: f32 = constraints.iter().map(|c| c.min).sum();
+        let max_sum: f32 = constraints.iter().map(|c| c.max).sum();
+
+        // The width of the table's columns is its specified width (if any), or else fills the
+        // available space, clamped between the table's min-content and max-content widths
+        let target = match (table_width, available_width) {
+            (Some(width), _) => (width - gaps).max(min_sum),
+            (None, AvailableSpace::Definite(available)) => {
+                (available - gaps).min(max_sum).max(min_sum)
+            }
+            (None, AvailableSpace::MinContent) => min_sum,
+            (None, AvailableSpace::MaxContent) => max_sum,
+        };
+
+        // Percentage columns take their share of the table's width first
+        let mut widths: Vec<f32> = constraints.iter().map(|c| c.min).collect();
+        let mut remaining = target;
+        if table_width.is_some() || matches!(available_width, AvailableSpace::Definite(_)) {
+            for (width, constraint) in widths.iter_mut().zip(&constraints) {
+                if let Some(percent) = constraint.percent {
+                    *width = (percent * target).max(constraint.min);
+                    remaining -= *width;
+                }
+            }
+        } else {
+            for (width, constraint) in widths.iter_mut().zip(&constraints) {
+                if constraint.percent.is_some() {
+                    *width = constraint.max;
+                    remaining -= *width;
+                }
+            }
+        }
+
+        // The remaining width is distributed between the other columns: first growing each column
+        // from its min-content width towards its max-content width, then growing auto columns
+        // (or every column, if there are none) beyond their max-content widths
+        let others: Vec<usize> = (0..col_count)
+            .filter(|idx| constraints[*idx].percent.is_none())
+            .collect();
+        let others_min: f32 = others.iter().map(|idx| constraints[*idx].min).sum();
+        let others_max: f32 = others.iter().map(|idx| constraints[*idx].max).sum();
+        if remaining <= others_min {
+            // The table overflows: columns keep their min-content widths
+        } else if remaining <= others_max {
+            let ratio = (remaining - others_min) / (others_max - others_min);
+            for idx in &others {
+                let c = &constraints[*idx];
+                widths[*idx] = c.min + (c.max - c.min) * ratio;
+            }
+        } else {
+            for idx in &others {
+                widths[*idx] = constraints[*idx].max;
+            }
+            let mut growable: Vec<usize> = others
+                .iter()
+                .copied()
+                .filter(|idx| constraints[*idx].fixed.is_none())
+                .collect();
+            if growable.is_empty() {
+                growable = others.clone();
+            }
+            let weights: Vec<f32> = growable.iter().map(|idx| constraints[*idx].max).collect();
+            let mut grown: Vec<f32> = growable.iter().map(|idx| widths[*idx]).collect();
+            distribute(&mut grown, &weights, remaining - others_max);
+            for (idx, width) in growable.into_iter().zip(grown) {
+                widths[idx] = width;
+            }
+        }
+
+        widths
+    }
+}
+
+/// Lay out a table
+pub(crate) fn compute_table_layout(
+    doc: &mut BaseDocument,
+    node_id: NodeId,
+    inputs: LayoutInput,
+    ctx: Arc<TableContext>,
+) -> LayoutOutput {
+    let mut style = ctx.style.clone();
+
+    // Cells are laid out with the widths of their columns (so their specified widths only
+    // contribute to the column widths), and with collapsed borders in the collapsing border model
+    let collapsed_borders = ctx
+        .border_collapse
+        .then(|| resolve_collapsed_borders(doc, &ctx));
+    for (idx, item) in ctx.items.iter().enumerate() {
+        if item.kind != TableItemKind::Cell {
+            continue;
+        }
+        let node = &mut doc.nodes[item.node_id];
+        node.style.size.width = style_helpers::auto();
+        if let Some(borders) = &collapsed_borders {
+            let edges = &borders[idx];
+            let half = |edge: &Option<CollapsedBorder>| {
+                style_helpers::length(edge.as_ref().map_or(0.0, |b| b.width) / 2.0)
+            };
+            node.style.border = taffy::Rect {
+                top: half(&edges.top),
+                right: half(&edges.right),
+                bottom: half(&edges.bottom),
+                left: half(&edges.left),
+            };
+        }
+        if let Some(element) = node.element_data_mut() {
+            element.collapsed_borders = None;
+        }
+    }
+    if let Some(borders) = collapsed_borders {
+        style.border = collapsed_table_border(&ctx, &borders);
+        paint_collapsed_borders(doc, &ctx, borders);
+    }
+
+    let parent_width = inputs.parent_size.width;
+    let padding = style
+        .padding
+        .resolve_or_zero(parent_width, resolve_calc_value);
+    let border = style
+        .border
+        .resolve_or_zero(parent_width, resolve_calc_value);
+    let pb_width = padding.left + padding.right + border.left + border.right;
+    let col_count = ctx.columns.len();
+    let gaps = style
+        .gap
+        .width
+        .resolve_or_zero(parent_width, resolve_calc_value)
+        * col_count.saturating_sub(1) as f32;
+
+    // The width of the table's grid (which excludes its padding and border)
+    let table_width = inputs
+        .known_dimensions
+        .width
+        .or_else(|| {
+            let width = resolve_width(style.size.width, parent_width)?;
+            Some(match style.box_sizing {
+                taffy::BoxSizing::BorderBox => width,
+                taffy::BoxSizing::ContentBox => width + pb_width,
+            })
+        })
+        .map(|width| (width - pb_width).max(0.0));
+    let available_width = inputs
+        .available_space
+        .width
+        .map_definite_value(|width| width - pb_width);
+
+    let widths = doc.compute_table_column_widths(&ctx, table_width, available_width, gaps);
+    let grid_width: f32 = widths.iter().sum::<f32>() + gaps;
+    style.grid_template_columns = widths
+        .into_iter()
+        .map(|width| TrackSizingFunction::from(style_helpers::length::<Dimension>(width)).into())
+        .collect();
+
+    // The table is at least as wide as its columns
+    let inputs = LayoutInput {
+        known_dimensions: Size {
+            width: Some(table_width.unwrap_or(0.0).max(grid_width) + pb_width),
+            height: inputs.known_dimensions.height,
+        },
+        ..inputs
+    };
+
+    let mut table_wrapper = TableTreeWrapper { doc, ctx, style };
+    compute_grid_layout(&mut table_wrapper, node_id, inputs)
+}
+
+/// The priority of a border style in border conflict resolution
+fn border_style_priority(style: BorderStyle) -> u8 {
+    match style {
+        BorderStyle::Hidden => 9,
+        BorderStyle::Double => 8,
+        BorderStyle::Solid => 7,
+        BorderStyle::Dashed => 6,
+        BorderStyle::Dotted => 5,
+        BorderStyle::Ridge => 4,
+        BorderStyle::Outset => 3,
+        BorderStyle::Groove => 2,
+        BorderStyle::Inset => 1,
+        BorderStyle::None => 0,
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Side {
+    Top,
+    Right,
+    Bottom,
+    Left,
+}
+
+/// The border of one side of an element
+fn border_side(doc: &BaseDocument, node_id: Option<usize>, side: Side) -> Option<CollapsedBorder> {
+    let style = doc.nodes[node_id?].primary_styles()?;
+    let border = style.get_border();
+    let (width, border_style, color) = match side {
+        Side::Top => (
+            border.border_top_width,
+            border.border_top_style,
+            &border.border_top_color,
+        ),
+        Side::Right => (
+            border.border_right_width,
+            border.border_right_style,
+            &border.border_right_color,
+        ),
+        Side::Bottom => (
+            border.border_bottom_width,
+            border.border_bottom_style,
+            &border.border_bottom_color,
+        ),
+        Side::Left => (
+            border.border_left_width,
+            border.border_left_style,
+            &border.border_left_color,
+        ),
+    };
+    Some(CollapsedBorder {
+        width: width.to_f32_px(),
+        style: border_style,
+        color: color.resolve_to_absolute(&style.clone_color()),
+    })
+}
+
+/// Resolve conflicting borders. The candidates must be in order of precedence (cells, rows, row
+/// groups, columns, column groups, the table; with the top-most or left-most element first when
+/// two elements of the same type conflict).
+///
+/// See: <https://www.w3.org/TR/CSS22/tables.html#border-conflict-resolution>
+fn resolve_border_conflict(
+    candidates: impl IntoIterator<Item = Option<CollapsedBorder>>,
+) -> Option<CollapsedBorder> {
+    let mut winner: Option<CollapsedBorder> = None;
+    for candidate in candidates.into_iter().flatten() {
+        match candidate.style {
+            // Hidden borders suppress all other borders
+            BorderStyle::Hidden => return None,
+            BorderStyle::None => continue,
+            _ => {}
+        }
+        let wins = winner.as_ref().is_none_or(|winner| {
+            candidate.width > winner.width
+                || (candidate.width == winner.width
+                    && border_style_priority(candidate.style) > border_style_priority(winner.style))
+        });
+        if wins {
+            winner = Some(candidate);
+        }
+    }
+    winner.filter(|border| border.width > 0.0)
+}
+
+/// Resolve the borders of each edge of each cell in the collapsing border model (indexed by item)
+fn resolve_collapsed_borders(
+    doc: &BaseDocument,
+    ctx: &TableContext,
+) -> Vec<taffy::Rect<Option<CollapsedBorder>>> {
+    let row_count = ctx.rows.len();
+    let col_count = ctx.columns.len();
+    let table_id = Some(ctx.table_id);
+
+    let slot = |row: usize, col: usize| -> Option<usize> {
+        (row < row_count && col < col_count)
+            .then(|| ctx.slots[row * col_count + col])
+            .flatten()
+            .map(|idx| ctx.items[idx].node_id)
+    };
+    let row_node = |row: usize| ctx.rows.get(row).and_then(|row| row.node_id);
+    let row_group = |row: usize| ctx.rows.get(row).and_then(|row| row.group_id);
+    let col_node = |col: usize| ctx.columns.get(col).and_then(|col| col.node_id);
+    let col_group = |col: usize| ctx.columns.get(col).and_then(|col| col.group_id);
+    let side = |node_id: Option<usize>, side: Side| border_side(doc, node_id, side);
+
+    ctx.items
+        .iter()
+        .map(|item| {
+            if item.kind != TableItemKind::Cell {
+                return taffy::Rect {
+                    top: None,
+                    right: None,
+                    bottom: None,
+                    left: None,
+                };
+            }
+            let cell = Some(item.node_id);
+            let (row, col) = (item.row as usize, item.col as usize);
+            let last_row = row + item.row_span as usize - 1;
+            let last_col = col + item.col_span as usize - 1;
+            let is_first_row = row == 0;
+            let is_last_row = last_row + 1 >= row_count;
+            let is_first_col = col == 0;
+            let is_last_col = last_col + 1 >= col_count;
+
+            // Row group borders only apply at the boundaries of the group
+            let group_above =
+                (is_first_row || row_group(row - 1) != row_group(row)).then(|| row_group(row));
+            let group_below = (is_last_row || row_group(last_row + 1) != row_group(last_row))
+                .then(|| row_group(last_row));
+            let col_group_left =
+                (is_first_col || col_group(col - 1) != col_group(col)).then(|| col_group(col));
+            let col_group_right = (is_last_col || col_group(last_col + 1) != col_group(last_col))
+                .then(|| col_group(last_col));
+
+            let top = resolve_border_conflict([
+                (!is_first_row)
+                    .then(|| side(slot(row - 1, col), Side::Bottom))
+                    .flatten(),
+                side(cell, Side::Top),
+                (!is_first_row)
+                    .then(|| side(row_node(row - 1), Side::Bottom))
+                    .flatten(),
+                side(row_node(row), Side::Top),
+                (!is_first_row && group_above.is_some())
+                    .then(|| side(row_group(row - 1), Side::Bottom))
+                    .flatten(),
+                group_above.and_then(|group| side(group, Side::Top)),
+                is_first_row
+                    .then(|| side(col_node(col), Side::Top))
+                    .flatten(),
+                is_first_row
+                    .then(|| side(col_group(col), Side::Top))
+                    .flatten(),
+                is_first_row.then(|| side(table_id, Side::Top)).flatten(),
+            ]);
+            let bottom = resolve_border_conflict([
+                side(cell, Side::Bottom),
+                (!is_last_row)
+                    .then(|| side(slot(last_row + 1, col), Side::Top))
+                    .flatten(),
+                side(row_node(last_row), Side::Bottom),
+                (!is_last_row)
+                    .then(|| side(row_node(last_row + 1), Side::Top))
+                    .flatten(),
+                group_below.and_then(|group| side(group, Side::Bottom)),
+                (!is_last_row && group_below.is_some())
+                    .then(|| side(row_group(last_row + 1), Side::Top))
+                    .flatten(),
+                is_last_row
+                    .then(|| side(col_node(col), Side::Bottom))
+                    .flatten(),
+                is_last_row
+                    .then(|| side(col_group(col), Side::Bottom))
+                    .flatten(),
+                is_last_row.then(|| side(table_id, Side::Bottom)).flatten(),
+            ]);
+            let left = resolve_border_conflict([
+                (!is_first_col)
+                    .then(|| side(slot(row, col - 1), Side::Right))
+                    .flatten(),
+                side(cell, Side::Left),
+                is_first_col
+                    .then(|| side(row_node(row), Side::Left))
+                    .flatten(),
+                is_first_col
+                    .then(|| side(row_group(row), Side::Left))
+                    .flatten(),
+                (!is_first_col)
+                    .then(|| side(col_node(col - 1), Side::Right))
+                    .flatten(),
+                side(col_node(col), Side::Left),
+                (!is_first_col && col_group_left.is_some())
+                    .then(|| side(col_group(col - 1), Side::Right))
+                    .flatten(),
+                col_group_left.and_then(|group| side(group, Side::Left)),
+                is_first_col.then(|| side(table_id, Side::Left)).flatten(),
+            ]);
+            let right = resolve_border_conflict([
+                side(cell, Side::Right),
+                (!is_last_col)
+                    .then(|| side(slot(row, last_col + 1), Side::Left))
+                    .flatten(),
+                is_last_col
+                    .then(|| side(row_node(row), Side::Right))
+                    .flatten(),
+                is_last_col
+                    .then(|| side(row_group(row), Side::Right))
+                    .flatten(),
+                side(col_node(last_col), Side::Right),
+                (!is_last_col)
+                    .then(|| side(col_node(last_col + 1), Side::Left))
+                    .flatten(),
+                col_group_right.and_then(|group| side(group, Side::Right)),
+                (!is_last_col && col_group_right.is_some())
+                    .then(|| side(col_group(last_col + 1), Side::Left))
+                    .flatten(),
+                is_last_col.then(|| side(table_id, Side::Right)).flatten(),
+            ]);
+
+            taffy::Rect {
+                top,
+                right,
+                bottom,
+                left,
+            }
+        })
+        .collect()
+}
+
+/// The border of a table in the collapsing border model: half of the widest collapsed border on
+/// each of its outer edges
+fn collapsed_table_border(
+    ctx: &TableContext,
+    borders: &[taffy::Rect<Option<CollapsedBorder>>],
+) -> taffy::Rect<taffy::LengthPercentage> {
+    let row_count = ctx.rows.len() as u16;
+    let col_count = ctx.columns.len() as u16;
+    let mut widths = taffy::Rect::<f32>::zero();
+    for (item, edges) in ctx.items.iter().zip(borders) {
+        if item.kind != TableItemKind::Cell {
+            continue;
+        }
+        let width = |edge: &Option<CollapsedBorder>| edge.as_ref().map_or(0.0, |b| b.width) / 2.0;
+        if item.row == 0 {
+            widths.top = widths.top.max(width(&edges.top));
+        }
+        if item.row + item.row_span >= row_count {
+            widths.bottom = widths.bottom.max(width(&edges.bottom));
+        }
+        if item.col == 0 {
+            widths.left = widths.left.max(width(&edges.left));
+        }
+        if item.col + item.col_span >= col_count {
+            widths.right = widths.right.max(width(&edges.right));
+        }
+    }
+    widths.map(style_helpers::length)
+}
+
+/// Store the collapsed borders which each cell paints. Shared edges are painted by the cell above
+/// or to the left of them, so each cell paints its top and left edges (and its bottom and right
+/// edges at the edges of the table).
+fn paint_collapsed_borders(
+    doc: &mut BaseDocument,
+    ctx: &TableContext,
+    borders: Vec<taffy::Rect<Option<CollapsedBorder>>>,
+) {
+    let row_count = ctx.rows.len() as u16;
+    let col_count = ctx.columns.len() as u16;
+    for (item, mut edges) in ctx.items.iter().zip(borders) {
+        if item.kind != TableItemKind::Cell {
+            continue;
+        }
+        if item.row + item.row_span < row_count {
+            edges.bottom = None;
+        }
+        if item.col + item.col_span < col_count {
+            edges.right = None;
+        }
+        if let Some(element) = doc.nodes[item.node_id].element_data_mut() {
+            element.collapsed_borders = Some(Box::new(edges));
+        }
+    }
+}
+
 pub struct RangeIter(Range<usize>);
 
 impl Iterator for RangeIter {
@@ -262,8 +1250,8 @@ impl taffy::TraversePartialTree for TableTreeWrapper<'_> {
     }
 
     #[inline(always)]
-    fn child_count(&self, node_id: taffy::NodeId) -> usize {
-        self.doc.child_count(node_id)
+    fn child_count(&self, _node_id: taffy::NodeId) -> usize {
+        self.ctx.items.len()
     }
 
     #[inline(always)]
@@ -282,7 +1270,7 @@ impl taffy::LayoutPartialTree for TableTreeWrapper<'_> {
     type CustomIdent = Atom;
 
     fn get_core_container_style(&self, _node_id: taffy::NodeId) -> &taffy::Style<Atom> {
-        &self.ctx.style
+        &self.style
     }
 
     fn resolve_calc_value(&self, calc_ptr: *const (), parent_size: f32) -> f32 {
@@ -299,15 +1287,15 @@ impl taffy::LayoutPartialTree for TableTreeWrapper<'_> {
         node_id: taffy::NodeId,
         inputs: taffy::tree::LayoutInput,
     ) -> taffy::LayoutOutput {
-        let cell = &self.ctx.items[usize::from(node_id)];
-        match cell.kind {
+        let item = &self.ctx.items[usize::from(node_id)];
+        match item.kind {
             TableItemKind::Row => {
-                compute_leaf_layout(inputs, &cell.style, resolve_calc_value, |_, _| {
+                compute_leaf_layout(inputs, &item.style, resolve_calc_value, |_, _| {
                     taffy::Size::ZERO
                 })
             }
-            TableItemKind::Cell => {
-                let node_id = taffy::NodeId::from(cell.node_id);
+            TableItemKind::Cell | TableItemKind::Caption => {
+                let node_id = taffy::NodeId::from(item.node_id);
                 self.doc.compute_child_layout(node_id, inputs)
             }
         }
@@ -333,3 +1321,83 @@ impl taffy::LayoutGridContainer for TableTreeWrapper<'_> {
         &self.ctx.items[usize::from(child_node_id)].style
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_border_conflict_resolution() {
+        use style::color::AbsoluteColor;
+
+        let border = |width: f32, style: BorderStyle| {
+            Some(CollapsedBorder {
+                width,
+                style,
+                color: AbsoluteColor::BLACK,
+            })
+        };
+
+        // Wider borders win
+        let winner = resolve_border_conflict([
+            border(1.0, BorderStyle::Solid),
+            border(3.0, BorderStyle::Dotted),
+        ]);
+        assert_eq!(winner.map(|b| b.style), Some(BorderStyle::Dotted));
+
+        // Then the style with the highest priority
+        let winner = resolve_border_conflict([
+            border(2.0, BorderStyle::Dashed),
+            border(2.0, BorderStyle::Double),
+        ]);
+        assert_eq!(winner.map(|b| b.style), Some(BorderStyle::Double));
+
+        // Then the first candidate
+        let winner = resolve_border_conflict([
+            border(2.0, BorderStyle::Solid),
+            border(2.0, BorderStyle::Solid),
+            None,
+        ]);
+        assert_eq!(winner.map(|b| b.width), Some(2.0));
+
+        // Hidden borders suppress all others, and none loses to everything
+        assert!(
+            resolve_border_conflict([
+                border(5.0, BorderStyle::Solid),
+                border(0.0, BorderStyle::Hidden)
+            ])
+            .is_none()
+        );
+        assert!(resolve_border_conflict([border(0.0, BorderStyle::None)]).is_none());
+    }
+
+    #[test]
+    fn test_distribute() {
+        let mut widths = [10.0, 20.0];
+        distribute(&mut widths, &[1.0, 3.0], 40.0);
+        assert_eq!(widths, [20.0, 50.0]);
+
+        let mut widths = [0.0, 0.0];
+        distribute(&mut widths, &[0.0, 0.0], 10.0);
+        assert_eq!(widths, [5.0, 5.0]);
+    }
+
+    #[test]
+    fn test_table_size_limits() {
+        let mut builder = TableBuilder::default();
+        for node_id in 0..40 {
+            builder.push_cell(node_id, taffy::Style::default(), MAX_COLSPAN, 1);
+        }
+
+        // Spans are truncated at the last column, and cells which would start past it are dropped
+        let last = builder.items.last().unwrap();
+        assert_eq!(last.col + last.col_span, MAX_TRACKS);
+        assert_eq!(builder.items.len(), 17);
+        assert_eq!(builder.col, MAX_TRACKS);
+
+        // Every row, column and caption starts at a valid grid line
+        assert_eq!(grid_line(0), 1);
+        assert_eq!(grid_line(2 * MAX_TRACKS as usize), i16::MAX);
+        assert_eq!(grid_line(usize::MAX), i16::MAX);
+    }
+}
@@ -0,0 +1,161 @@
+//! Support for `direction` and `writing-mode`
+//!
+//! - Taffy lays out the children of right-to-left block, flex and grid containers itself (from the
+//!   `direction` of their Taffy style). Within inline formatting contexts, Parley resolves the bidi
+//!   levels of the text from its content, and `text-align: start | end` is resolved against the
+//!   direction of the inline root (see [`BaseDocument::compute_inline_layout`]).
+//! - Taffy only implements horizontal layout, so boxes in a vertical flow are laid out with their
+//!   styles transposed (see [`stylo_taffy::convert::transpose_for_vertical_writing_mode`]) so that
+//!   the flow is laid out as if it were horizontal. The root of the flow maps this "flow-relative"
+//!   coordinate space back to physical coordinates (see [`Node::content_transform`]). Text within
+//!   vertical flows is laid out and painted sideways.
+
+use euclid::default::Transform2D;
+use style::properties::ComputedValues;
+use taffy::{
+    AvailableSpace, CollapsibleMarginSet, LayoutInput, LayoutOutput, Line, NodeId, Point, Rect,
+    RequestedAxis, Size,
+};
+
+use super::compute_node_layout;
+use crate::BaseDocument;
+use crate::node::Node;
+
+/// The direction in which a vertical flow's blocks are stacked
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum VerticalFlow {
+    /// `writing-mode: vertical-rl` (blocks are stacked right-to-left)
+    Rl,
+    /// `writing-mode: vertical-lr` (blocks are stacked left-to-right)
+    Lr,
+}
+
+impl VerticalFlow {
+    pub fn from_style(style: &ComputedValues) -> Option<Self> {
+        let writing_mode = style.writing_mode;
+        if !writing_mode.is_vertical() {
+            return None;
+        }
+        Some(match writing_mode.is_vertical_lr() {
+            true => Self::Lr,
+            false => Self::Rl,
+        })
+    }
+
+    /// Convert physical box edges into the flow-relative coordinate space of the flow
+    pub fn transpose_rect<T>(self, rect: Rect<T>) -> Rect<T> {
+        let (block_start, block_end) = match self {
+            Self::Lr => (rect.left, rect.right),
+            Self::Rl => (rect.right, rect.left),
+        };
+        Rect {
+            left: rect.top,
+            right: rect.bottom,
+            top: block_start,
+            bottom: block_end,
+        }
+    }
+}
+
+fn transpose_size<T>(size: Size<T>) -> Size<T> {
+    Size {
+        width: size.height,
+        height: size.width,
+    }
+}
+
+fn transpose_inputs(inputs: LayoutInput) -> LayoutInput {
+    LayoutInput {
+        known_dimensions: transpose_size(inputs.known_dimensions),
+        parent_size: transpose_size(inputs.parent_size),
+        available_space: transpose_size::<AvailableSpace>(inputs.available_space),
+        axis: match inputs.axis {
+            RequestedAxis::Horizontal => RequestedAxis::Vertical,
+            RequestedAxis::Vertical => RequestedAxis::Horizontal,
+            RequestedAxis::Both => RequestedAxis::Both,
+        },
+        vertical_margins_are_collapsible: Line::FALSE,
+        ..inputs
+    }
+}
+
+impl Node {
+    /// The vertical flow that this node establishes, if it is the root of a vertical flow (a box
+    /// with a vertical writing mode whose layout parent is in a horizontal flow)
+    pub fn vertical_flow_root(&self) -> Option<VerticalFlow> {
+        self.vertical_flow.filter(|_| {
+            self.layout_parent
+                .get()
+                .is_none_or(|parent_id| self.with(parent_id).vertical_flow.is_none())
+        })
+    }
+
+    /// The transform from the flow-relative coordinate space in which this node's children are
+    /// laid out to this node's physical coordinate space. `None` unless the node is the root of a
+    /// vertical flow.
+    pub fn content_transform(&self) -> Option<Transform2D<f32>> {
+        Some(match self.vertical_flow_root()? {
+            VerticalFlow::Rl => {
+                Transform2D::new(0.0, 1.0, -1.0, 0.0, self.final_layout.size.width, 0.0)
+            }
+            VerticalFlow::Lr => Transform2D::new(0.0, 1.0, 1.0, 0.0, 0.0, 0.0),
+        })
+    }
+}
+
+impl BaseDocument {
+    /// Resolve the writing mode of a node from its computed styles, adjusting its Taffy style
+    /// accordingly. Must be called after the node's parent has been resolved.
+    pub(crate) fn apply_writing_mode(&mut self, node_id: usize) {
+        let parent_flow = self.nodes[node_id]
+            .layout_parent
+            .get()
+            .and_then(|parent_id| self.nodes[parent_id].vertical_flow);
+
+        let node = &mut self.nodes[node_id];
+        let Some(own_flow) = node
+            .primary_styles()
+            .map(|style| VerticalFlow::from_style(&style))
+        else {
+            return;
+        };
+
+        // Writing mode only takes effect at the root of a flow (orthogonal flows are not supported)
+        node.vertical_flow = parent_flow.or(own_flow);
+        if let Some(flow) = parent_flow {
+            stylo_taffy::convert::transpose_for_vertical_writing_mode(
+                &mut node.style,
+                flow == VerticalFlow::Lr,
+            );
+        }
+    }
+
+    /// Lay out the root of a vertical flow in the flow-relative coordinate space of the flow
+    pub(crate) fn compute_vertical_flow_root_layout(
+        &mut self,
+        node_id: NodeId,
+        inputs: LayoutInput,
+        flow: VerticalFlow,
+    ) -> LayoutOutput {
+        let node = &mut self.nodes[node_id.into()];
+        let mut style = node.style.clone();
+        stylo_taffy::convert::transpose_for_vertical_writing_mode(
+            &mut style,
+            flow == VerticalFlow::Lr,
+        );
+        let physical_style = std::mem::replace(&mut node.style, style);
+
+        let output = compute_node_layout(self, node_id, transpose_inputs(inputs), None);
+
+        self.nodes[usize::from(node_id)].style = physical_style;
+
+        LayoutOutput {
+            size: transpose_size(output.size),
+            content_size: transpose_size(output.content_size),
+            first_baselines: Point::NONE,
+            top_margin: CollapsibleMarginSet::ZERO,
+            bottom_margin: CollapsibleMarginSet::ZERO,
+            margins_can_collapse_through: false,
+        }
+    }
+}
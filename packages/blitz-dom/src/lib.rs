@@ -50,12 +50,17 @@ mod html;
 mod layout;
 mod mutator;
 mod query_selector;
+mod range;
+mod select;
+mod shadow;
+mod stacking_context;
 /// Implementations that interact with servo's style engine
 mod stylo;
 mod stylo_to_cursor_icon;
 mod stylo_to_parley;
 mod traversal;
 mod url;
+mod validation;
 
 pub mod net;
 pub mod util;
@@ -69,12 +74,20 @@ pub use markup5ever::{
     LocalName, Namespace, NamespaceStaticSet, Prefix, PrefixStaticSet, QualName, local_name,
     namespace_prefix, namespace_url, ns,
 };
-pub use mutator::DocumentMutator;
-pub use node::{Attribute, ElementData, Node, NodeData, TextNodeData};
+pub use mutator::{AttachShadowErr, DocumentMutator};
+pub use node::{
+    Attribute, ElementData, Node, NodeData, ShadowRootData, ShadowRootMode, TextNodeData,
+};
 pub use parley::FontContext;
 pub use style::Atom;
 pub use style::invalidation::element::restyle_hints::RestyleHint;
 pub type SelectorList = selectors::SelectorList<style::selector_parser::SelectorImpl>;
 pub use events::{EventDriver, EventHandler, NoopEventHandler};
 pub use html::{DummyHtmlParserProvider, HtmlParserProvider};
+pub use layout::paged_media::{Page, PageOptions, PageSide};
+pub use layout::writing_mode::VerticalFlow;
+pub use range::{MeterRegion, MeterValues, ProgressValues, RangeBounds};
+pub use select::SelectPopup;
+pub use stacking_context::StackingContext;
 pub use util::Point;
+pub use validation::ValidityState;
@@ -5,10 +5,12 @@ use std::ops::{Deref, DerefMut};
 use crate::document::make_device;
 use crate::layout::damage::ALL_DAMAGE;
 use crate::net::{CssHandler, ImageHandler};
-use crate::node::{CanvasData, NodeFlags, SpecialElementData};
+use crate::node::{CanvasData, NodeFlags, ShadowRootData, ShadowRootMode, SpecialElementData};
 use crate::util::ImageType;
+use crate::validation::is_form_related;
 use crate::{
-    Attribute, BaseDocument, ElementData, Node, NodeData, QualName, local_name, qual_name,
+    Attribute, BaseDocument, ElementData, LocalName, Node, NodeData, QualName, local_name,
+    qual_name,
 };
 use blitz_traits::net::Request;
 use blitz_traits::shell::Viewport;
@@ -28,6 +30,17 @@ pub enum AppendTextErr {
     NotTextNode,
 }
 
+#[derive(Debug, Clone)]
+pub enum AttachShadowErr {
+    /// The node is not an element
+    NotElement,
+    /// The element cannot host a shadow root
+    /// (see <https://dom.spec.whatwg.org/#valid-shadow-host-name>)
+    InvalidHost,
+    /// The element already has a shadow root attached
+    AlreadyAttached,
+}
+
 /// Operations that happen almost immediately, but are deferred within a
 /// function for borrow-checker reasons.
 enum SpecialOp {
@@ -49,10 +62,22 @@ pub struct DocumentMutator<'doc> {
     title_node: Option<usize>,
     style_nodes: HashSet<usize>,
     form_nodes: HashSet<usize>,
+    select_nodes: HashSet<usize>,
 
     /// Whether an element/attribute that affect animation status has been seen
     recompute_is_animating: bool,
 
+    /// Whether a mutation that may affect the assignment of nodes to `<slot>`s has been seen
+    recompute_slots: bool,
+
+    /// Elements whose form control state (`:disabled`, `:invalid`, etc) may have been affected
+    /// by a mutation
+    form_state_nodes: HashSet<usize>,
+
+    /// Whether an element which may be the target of the URL fragment (an element with an `id`,
+    /// or an `<a>` with a `name`) has been seen
+    recompute_target: bool,
+
     /// The (latest) node which has been mounted in and had autofocus=true, if any
     #[cfg(feature = "autofocus")]
     node_to_autofocus: Option<usize>,
@@ -72,7 +97,11 @@ impl DocumentMutator<'_> {
             title_node: None,
             style_nodes: HashSet::new(),
             form_nodes: HashSet::new(),
+            select_nodes: HashSet::new(),
             recompute_is_animating: false,
+            recompute_slots: false,
+            form_state_nodes: HashSet::new(),
+            recompute_target: false,
             #[cfg(feature = "autofocus")]
             node_to_autofocus: None,
         }
@@ -147,6 +176,47 @@ impl DocumentMutator<'_> {
         self.doc.deep_clone_node(node_id)
     }
 
+    /// Attach a shadow root to the element `host_id`, returning the shadow root's node ID.
+    ///
+    /// Nodes appended to the shadow root are rendered in place of the host's children, which are
+    /// in turn rendered in the `<slot>` elements of the shadow tree. `<style>` and `<link>` elements
+    /// within the shadow tree only apply to the shadow tree.
+    pub fn attach_shadow(
+        &mut self,
+        host_id: usize,
+        mode: ShadowRootMode,
+    ) -> Result<usize, AttachShadowErr> {
+        let host = &self.doc.nodes[host_id];
+        let Some(element) = host.element_data() else {
+            return Err(AttachShadowErr::NotElement);
+        };
+        if element.shadow_root.is_some() {
+            return Err(AttachShadowErr::AlreadyAttached);
+        }
+        if !is_valid_shadow_host_name(&element.name.local) {
+            return Err(AttachShadowErr::InvalidHost);
+        }
+        let host_is_in_doc = host.flags.is_in_document();
+
+        let shadow_root_id = self
+            .doc
+            .create_node(NodeData::ShadowRoot(ShadowRootData::new(host_id, mode)));
+        let shadow_root = &mut self.doc.nodes[shadow_root_id];
+        shadow_root.parent = Some(host_id);
+        shadow_root
+            .flags
+            .set(NodeFlags::IS_IN_DOCUMENT, host_is_in_doc);
+
+        let host = &mut self.doc.nodes[host_id];
+        host.element_data_mut().unwrap().shadow_root = Some(shadow_root_id);
+        host.insert_damage(ALL_DAMAGE);
+        host.set_restyle_hint(RestyleHint::restyle_subtree());
+
+        self.recompute_slots = true;
+
+        Ok(shadow_root_id)
+    }
+
     // Node mutation methods
 
     pub fn set_node_text(&mut self, node_id: usize, value: &str) {
@@ -164,6 +234,8 @@ impl DocumentMutator<'_> {
             text.content.push_str(value);
             node.insert_damage(ALL_DAMAGE);
             let parent = node.parent;
+            // Text nodes don't have style data to store damage in
+            self.doc.invalidate_paint(node_id);
             self.maybe_record_node(parent);
         }
     }
@@ -199,6 +271,7 @@ impl DocumentMutator<'_> {
 
     pub fn set_attribute(&mut self, node_id: usize, name: QualName, value: &str) {
         self.doc.snapshot_node(node_id);
+        self.maybe_record_select(node_id);
 
         let node = &mut self.doc.nodes[node_id];
         if let Some(data) = &mut *node.stylo_element_data.borrow_mut() {
@@ -221,6 +294,14 @@ impl DocumentMutator<'_> {
             return;
         };
 
+        let is_in_document = node.flags.is_in_document();
+        if name.local == local_name!("id") && is_in_document {
+            if let Some(old_id) = element.attr(local_name!("id")) {
+                self.doc.nodes_to_id.remove(old_id);
+            }
+            self.doc.nodes_to_id.insert(value.to_string(), node_id);
+        }
+
         element.attrs.set(name.clone(), value);
 
         let tag = &element.name.local;
@@ -230,6 +311,22 @@ impl DocumentMutator<'_> {
             element.id = Some(Atom::from(value))
         }
 
+        if *attr == local_name!("id") || (tag, attr) == tag_and_attr!("a", "name") {
+            self.recompute_target |= is_in_document;
+        }
+
+        if *attr == local_name!("slot") || (tag, attr) == tag_and_attr!("slot", "name") {
+            self.recompute_slots = true;
+        }
+
+        if is_form_related(tag) || *attr == local_name!("contenteditable") {
+            self.form_state_nodes.insert(node_id);
+        }
+
+        if *attr == local_name!("pattern") {
+            element.pattern_regex.take();
+        }
+
         if *attr == local_name!("value") {
             if let Some(input_data) = element.text_input_data_mut() {
                 // Update text input value
@@ -238,10 +335,25 @@ impl DocumentMutator<'_> {
                     &mut self.doc.layout_ctx,
                     value,
                 );
+            } else if element.range_input_data().is_some() {
+                let value = value.trim().parse().unwrap_or(f64::NAN);
+                self.doc.set_range_input_value(node_id, value);
             }
             return;
         }
 
+        // Changing the bounds of a range input may change its value
+        if element.range_input_data().is_some()
+            && matches!(
+                *attr,
+                local_name!("min") | local_name!("max") | local_name!("step")
+            )
+        {
+            let value = element.range_input_data().unwrap().value;
+            self.doc.set_range_input_value(node_id, value);
+            return;
+        }
+
         if *attr == local_name!("style") {
             element.flush_style_attribute(&self.doc.guard, &self.doc.url.url_extra_data());
             return;
@@ -259,11 +371,14 @@ impl DocumentMutator<'_> {
             self.load_image(node_id);
         } else if (tag, attr) == tag_and_attr!("canvas", "src") {
             self.load_custom_paint_src(node_id);
+        } else if (tag, attr) == tag_and_attr!("option", "selected") {
+            self.doc.option_selected_attr_changed(node_id);
         }
     }
 
     pub fn clear_attribute(&mut self, node_id: usize, name: QualName) {
         self.doc.snapshot_node(node_id);
+        self.maybe_record_select(node_id);
 
         let node = &mut self.doc.nodes[node_id];
 
@@ -274,6 +389,7 @@ impl DocumentMutator<'_> {
         }
         drop(stylo_element_data);
 
+        let is_in_document = node.flags.is_in_document();
         let Some(element) = node.element_data_mut() else {
             return;
         };
@@ -286,6 +402,28 @@ impl DocumentMutator<'_> {
 
         if name.local == local_name!("id") {
             element.id = None;
+            if is_in_document {
+                self.doc.nodes_to_id.remove(&removed_attr.unwrap().value);
+                self.recompute_target = true;
+            }
+        }
+
+        if (&element.name.local, &name.local) == tag_and_attr!("a", "name") {
+            self.recompute_target |= is_in_document;
+        }
+
+        if name.local == local_name!("slot")
+            || (&element.name.local, &name.local) == tag_and_attr!("slot", "name")
+        {
+            self.recompute_slots = true;
+        }
+
+        if is_form_related(&element.name.local) || name.local == local_name!("contenteditable") {
+            self.form_state_nodes.insert(node_id);
+        }
+
+        if name.local == local_name!("pattern") {
+            element.pattern_regex.take();
         }
 
         // Update text input value
@@ -299,6 +437,21 @@ impl DocumentMutator<'_> {
             }
         }
 
+        // Update range input value (an absent value is reset to the default value)
+        if let Some(range_data) = element.range_input_data() {
+            let value = match name.local {
+                local_name!("value") => Some(f64::NAN),
+                local_name!("min") | local_name!("max") | local_name!("step") => {
+                    Some(range_data.value)
+                }
+                _ => None,
+            };
+            if let Some(value) = value {
+                self.doc.set_range_input_value(node_id, value);
+                return;
+            }
+        }
+
         let tag = &element.name.local;
         let attr = &name.local;
         if *attr == local_name!("style") {
@@ -307,6 +460,8 @@ impl DocumentMutator<'_> {
             self.recompute_is_animating = true;
         } else if (tag, attr) == tag_and_attr!("link", "href") {
             self.unload_stylesheet(node_id);
+        } else if (tag, attr) == tag_and_attr!("option", "selected") {
+            self.doc.option_selected_attr_changed(node_id);
         }
     }
 
@@ -322,29 +477,38 @@ impl DocumentMutator<'_> {
     pub fn remove_node(&mut self, node_id: usize) {
         let node = &mut self.doc.nodes[node_id];
 
+        node.assigned_slot = None;
+
         // Update child_idx values
-        if let Some(parent_id) = node.parent.take() {
+        let parent_id = node.parent.take();
+        if let Some(parent_id) = parent_id {
             let parent = &mut self.doc.nodes[parent_id];
             parent.insert_damage(ALL_DAMAGE);
             parent.children.retain(|id| *id != node_id);
+            self.maybe_invalidate_shadow_tree(parent_id);
             self.maybe_record_node(parent_id);
         }
 
-        self.process_removed_subtree(node_id);
+        self.process_removed_subtree(node_id, parent_id);
     }
 
     fn remove_node_ignoring_parent(&mut self, node_id: usize) -> Option<Node> {
         let mut node = self.doc.nodes.try_remove(node_id);
+        self.doc.controls_to_form.remove(&node_id);
         if let Some(node) = &mut node {
             for &child in &node.children {
                 self.remove_node_ignoring_parent(child);
             }
+            if let Some(shadow_root_id) = node.shadow_root_id() {
+                self.remove_node_ignoring_parent(shadow_root_id);
+            }
         }
         node
     }
 
     pub fn remove_and_drop_node(&mut self, node_id: usize) -> Option<Node> {
-        self.process_removed_subtree(node_id);
+        let parent_id = self.doc.nodes[node_id].parent;
+        self.process_removed_subtree(node_id, parent_id);
 
         let node = self.remove_node_ignoring_parent(node_id);
 
@@ -362,6 +526,7 @@ impl DocumentMutator<'_> {
             }
 
             parent.children.retain(|id| *id != node_id);
+            self.maybe_invalidate_shadow_tree(parent_id);
             self.maybe_record_node(parent_id);
         }
 
@@ -381,9 +546,10 @@ impl DocumentMutator<'_> {
 
         let children = mem::take(&mut parent.children);
         for child_id in children {
-            self.process_removed_subtree(child_id);
+            self.process_removed_subtree(child_id, Some(node_id));
             let _ = self.remove_node_ignoring_parent(child_id);
         }
+        self.maybe_invalidate_shadow_tree(node_id);
         self.maybe_record_node(node_id);
     }
 
@@ -435,6 +601,7 @@ impl DocumentMutator<'_> {
         for child_id in child_ids.iter().copied() {
             let child = &mut self.doc.nodes[child_id];
             let old_parent_id = child.parent.replace(parent_id);
+            child.assigned_slot = None;
 
             let child_was_in_doc = child.flags.is_in_document();
             if new_parent_is_in_doc != child_was_in_doc {
@@ -453,10 +620,12 @@ impl DocumentMutator<'_> {
                 }
 
                 old_parent.children.retain(|id| *id != child_id);
+                self.maybe_invalidate_shadow_tree(old_parent_id);
                 self.maybe_record_node(old_parent_id);
             }
         }
 
+        self.maybe_invalidate_shadow_tree(parent_id);
         self.maybe_record_node(parent_id);
     }
 
@@ -489,6 +658,11 @@ impl<'doc> DocumentMutator<'doc> {
             self.doc.has_canvas = self.doc.compute_has_canvas();
         }
 
+        if self.recompute_slots {
+            self.doc.assign_slottables();
+            self.recompute_slots = false;
+        }
+
         if let Some(id) = self.title_node {
             let title = self.doc.nodes[id].text_content();
             self.doc.shell_provider.set_window_title(title);
@@ -503,6 +677,22 @@ impl<'doc> DocumentMutator<'doc> {
             self.doc.reset_form_owner(id);
         }
 
+        for id in self.select_nodes.drain() {
+            if self.doc.nodes.contains(id) {
+                self.doc.invalidate_select(id);
+            }
+        }
+
+        if !self.form_state_nodes.is_empty() {
+            let ids = mem::take(&mut self.form_state_nodes);
+            self.doc.update_form_control_states(ids);
+        }
+
+        // Element IDs may have changed
+        if mem::take(&mut self.recompute_target) {
+            self.doc.update_target_node();
+        }
+
         #[cfg(feature = "autofocus")]
         if let Some(node_id) = self.node_to_autofocus.take() {
             if self.doc.get_node(node_id).is_some() {
@@ -544,12 +734,21 @@ impl<'doc> DocumentMutator<'doc> {
             // If the node has an "id" attribute, store it in the ID map.
             if let Some(id_attr) = node.attr(local_name!("id")) {
                 doc.nodes_to_id.insert(id_attr.to_string(), node_id);
+                self.recompute_target = true;
             }
 
             let NodeData::Element(ref mut element) = node.data else {
                 return;
             };
 
+            if element.name.local == local_name!("a") && element.has_attr(local_name!("name")) {
+                self.recompute_target = true;
+            }
+
+            if is_form_related(&element.name.local) {
+                self.form_state_nodes.insert(node_id);
+            }
+
             // Custom post-processing by element tag name
             let tag = element.name.local.as_ref();
             match tag {
@@ -585,7 +784,8 @@ impl<'doc> DocumentMutator<'doc> {
         self.flush_eager_ops();
     }
 
-    fn process_removed_subtree(&mut self, node_id: usize) {
+    fn process_removed_subtree(&mut self, node_id: usize, former_parent_id: Option<usize>) {
+        let mut has_form_related = false;
         self.doc.iter_subtree_mut(node_id, |node_id, doc| {
             let node = &mut doc.nodes[node_id];
             node.flags.set(NodeFlags::IS_IN_DOCUMENT, false);
@@ -593,12 +793,28 @@ impl<'doc> DocumentMutator<'doc> {
             // If the node has an "id" attribute remove it from the ID map.
             if let Some(id_attr) = node.attr(local_name!("id")) {
                 doc.nodes_to_id.remove(id_attr);
+                self.recompute_target = true;
             }
 
             let NodeData::Element(ref mut element) = node.data else {
                 return;
             };
 
+            if element.name.local == local_name!("a") && element.has_attr(local_name!("name")) {
+                self.recompute_target = true;
+            }
+
+            if element.name.local == local_name!("slot") {
+                self.recompute_slots = true;
+            }
+
+            if is_form_related(&element.name.local) {
+                // The validity of the element's form owner may depend on it
+                has_form_related = true;
+                self.form_state_nodes
+                    .extend(doc.controls_to_form.get(&node_id).copied());
+            }
+
             match &element.special_data {
                 SpecialElementData::Stylesheet(_) => self
                     .eager_op_queue
@@ -610,12 +826,23 @@ impl<'doc> DocumentMutator<'doc> {
                 SpecialElementData::TableRoot(_) => {}
                 SpecialElementData::TextInput(_) => {}
                 SpecialElementData::CheckboxInput(_) => {}
+                SpecialElementData::RangeInput(_) => {}
+                SpecialElementData::Select(_) => {
+                    if doc.open_select_id == Some(node_id) {
+                        doc.open_select_id = None;
+                    }
+                }
                 #[cfg(feature = "file_input")]
                 SpecialElementData::FileInput(_) => {}
                 SpecialElementData::None => {}
             }
         });
 
+        // So may that of the fieldsets and selects that the subtree was removed from
+        if has_form_related {
+            self.form_state_nodes.extend(former_parent_id);
+        }
+
         self.flush_eager_ops();
     }
 
@@ -637,7 +864,38 @@ impl<'doc> DocumentMutator<'doc> {
             "style" => {
                 self.style_nodes.insert(node_id);
             }
-            _ => {}
+            _ => self.maybe_record_select(node_id),
+        }
+    }
+
+    /// Schedule the list of options of a `<select>` to be recollected if `node_id` is
+    /// (or is part of) one.
+    fn maybe_record_select(&mut self, node_id: usize) {
+        let Some(element) = self.doc.nodes[node_id].element_data() else {
+            return;
+        };
+        let select_id = match element.name.local {
+            local_name!("select") => Some(node_id),
+            local_name!("option") | local_name!("optgroup") => self.doc.owning_select(node_id),
+            _ => None,
+        };
+        if let Some(select_id) = select_id {
+            self.select_nodes.insert(select_id);
+        }
+    }
+
+    /// Schedule slot assignment to be recomputed if the children of `node_id` may affect it.
+    /// That is, if it is a shadow host or within a shadow tree.
+    fn maybe_invalidate_shadow_tree(&mut self, node_id: usize) {
+        let node = &self.doc.nodes[node_id];
+        if let Some(host_id) = node.shadow_root_data().map(|data| data.host) {
+            // Children of the shadow root itself are laid out as children of the host
+            let host = &mut self.doc.nodes[host_id];
+            host.insert_damage(ALL_DAMAGE);
+            host.set_restyle_hint(RestyleHint::restyle_subtree());
+            self.recompute_slots = true;
+        } else if node.shadow_root_id().is_some() || node.containing_shadow_root_id().is_some() {
+            self.recompute_slots = true;
         }
     }
 
@@ -676,13 +934,10 @@ impl<'doc> DocumentMutator<'doc> {
             unreachable!();
         };
 
-        let guard = self.doc.guard.read();
-        self.doc.stylist.remove_stylesheet(stylesheet, &guard);
+        self.doc.remove_stylesheet_for_node(stylesheet, node_id);
         self.doc
             .stylist
             .force_stylesheet_origins_dirty(OriginSet::all());
-
-        self.doc.nodes_to_stylesheet.remove(&node_id);
     }
 
     fn load_image(&mut self, target_id: usize) {
@@ -776,6 +1031,31 @@ fn set_input_checked_state(element: &mut ElementData, value: String) {
     }
 }
 
+/// <https://dom.spec.whatwg.org/#valid-shadow-host-name>
+fn is_valid_shadow_host_name(name: &LocalName) -> bool {
+    matches!(
+        name.as_ref(),
+        "article"
+            | "aside"
+            | "blockquote"
+            | "body"
+            | "div"
+            | "footer"
+            | "h1"
+            | "h2"
+            | "h3"
+            | "h4"
+            | "h5"
+            | "h6"
+            | "header"
+            | "main"
+            | "nav"
+            | "p"
+            | "section"
+            | "span"
+    ) || name.contains('-')
+}
+
 /// Type that allows mutable access to the viewport
 /// And syncs it back to stylist on drop.
 pub struct ViewportMut<'doc> {
@@ -802,8 +1082,11 @@ impl DerefMut for ViewportMut<'_> {
 }
 impl Drop for ViewportMut<'_> {
     fn drop(&mut self) {
-        self.doc
-            .set_stylist_device(make_device(&self.doc.viewport, self.doc.font_ctx.clone()));
+        self.doc.set_stylist_device(make_device(
+            &self.doc.viewport,
+            self.doc.print_media,
+            self.doc.font_ctx.clone(),
+        ));
         self.doc.scroll_viewport_by(0.0, 0.0); // Clamp scroll offset
 
         let scale_has_changed = self.doc.viewport().scale_f64() != self.initial_scale;
@@ -20,6 +20,7 @@ use blitz_traits::net::{Bytes, NetHandler, Request, SharedCallback, SharedProvid
 
 use url::Url;
 
+use crate::layout::paged_media::expose_paged_media_properties;
 use crate::util::ImageType;
 
 #[derive(Clone, Debug)]
@@ -107,9 +108,10 @@ impl ServoStylesheetLoader for StylesheetLoader {
 
                 // NOTE(Nico): I don't *think* external stylesheets should have HTML entities escaped
                 // let escaped_css = html_escape::decode_html_entities(css);
+                let css = expose_paged_media_properties(css);
                 Stylesheet::update_from_str(
                     &self.sheet,
-                    css,
+                    &css,
                     UrlExtraData(self.url),
                     Some(&self.loader),
                     None,
@@ -145,8 +147,9 @@ impl NetHandler<Resource> for CssHandler {
         // NOTE(Nico): I don't *think* external stylesheets should have HTML entities escaped
         // let escaped_css = html_escape::decode_html_entities(css);
 
+        let css = expose_paged_media_properties(css);
         let sheet = Stylesheet::from_str(
-            css,
+            &css,
             self.source_url.into(),
             Origin::Author,
             ServoArc::new(self.guard.wrap(MediaList::empty())),
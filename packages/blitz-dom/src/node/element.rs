@@ -2,12 +2,15 @@ use cssparser::ParserInput;
 use markup5ever::{LocalName, QualName, local_name};
 use parley::{ContentWidths, FontContext, LayoutContext};
 use selectors::matching::QuirksMode;
+use std::cell::OnceCell;
 use std::str::FromStr;
 use std::sync::Arc;
 use style::Atom;
+use style::color::AbsoluteColor;
 use style::parser::ParserContext;
 use style::properties::{Importance, PropertyDeclaration, PropertyId, SourcePropertyDeclaration};
 use style::stylesheets::{DocumentStyleSheet, Origin, UrlExtraData};
+use style::values::specified::BorderStyle;
 use style::{
     properties::{PropertyDeclarationBlock, parse_style_attribute},
     servo_arc::Arc as ServoArc,
@@ -18,6 +21,7 @@ use style_traits::ParsingMode;
 use url::Url;
 
 use super::{Attribute, Attributes};
+use crate::layout::paged_media::{expose_paged_media_properties, exposed_property_name};
 use crate::layout::table::TableContext;
 
 #[derive(Debug, Clone)]
@@ -53,8 +57,34 @@ pub struct ElementData {
     /// does not exclude inline_layout_data
     pub list_item_data: Option<Box<ListItemLayout>>,
 
+    /// The `list-style-image` of a list item
+    pub list_style_image: Option<Box<BackgroundImageData>>,
+
+    /// The borders painted by a table cell in the collapsing border model (edges which are
+    /// painted by a neighbouring cell, or which have no border, are `None`)
+    pub collapsed_borders: Option<Box<taffy::Rect<Option<CollapsedBorder>>>>,
+
+    /// The resolved `content` of the element's `::after` and `::before` pseudo elements (in that
+    /// order, matching stylo's pseudo element styles)
+    pub generated_content: [Option<Vec<GeneratedContent>>; 2],
+
     /// The element's template contents (\<template\> elements only)
     pub template_contents: Option<usize>,
+
+    /// The shadow root attached to the element (shadow hosts only)
+    pub shadow_root: Option<usize>,
+
+    /// The nodes assigned to the element (\<slot\> elements in a shadow tree only)
+    pub assigned_nodes: Vec<usize>,
+
+    /// Whether the user has interacted with the element (form controls only).
+    /// Enables `:user-valid` / `:user-invalid` and length constraints.
+    /// See <https://html.spec.whatwg.org/multipage/form-control-infrastructure.html#user-validity>
+    pub user_validity: bool,
+
+    /// The compiled `pattern` attribute (text inputs only, `None` if the pattern is invalid).
+    /// Compiled on first use and reset when the attribute changes.
+    pub(crate) pattern_regex: OnceCell<Option<regex::Regex>>,
     // /// Whether the node is a [HTML integration point] (https://html.spec.whatwg.org/multipage/#html-integration-point)
     // pub mathml_annotation_xml_integration_point: bool,
 }
@@ -68,6 +98,8 @@ pub enum SpecialElementType {
     TableRoot,
     TextInput,
     CheckboxInput,
+    RangeInput,
+    Select,
     #[cfg(feature = "file_input")]
     FileInput,
     #[default]
@@ -88,6 +120,10 @@ pub enum SpecialElementData {
     TextInput(TextInputData),
     /// Checkbox checked state
     CheckboxInput(bool),
+    /// Range input value
+    RangeInput(RangeInputData),
+    /// \<select\> element options and selection state
+    Select(Box<SelectData>),
     /// Selected files
     #[cfg(feature = "file_input")]
     FileInput(FileData),
@@ -118,8 +154,15 @@ impl ElementData {
             style_attribute: Default::default(),
             inline_layout_data: None,
             list_item_data: None,
+            list_style_image: None,
+            collapsed_borders: None,
+            generated_content: [None, None],
             special_data: SpecialElementData::None,
             template_contents: None,
+            shadow_root: None,
+            assigned_nodes: Vec::new(),
+            user_validity: false,
+            pattern_regex: OnceCell::new(),
             background_images: Vec::new(),
         };
         data.flush_is_focussable();
@@ -224,6 +267,34 @@ impl ElementData {
         }
     }
 
+    pub fn range_input_data(&self) -> Option<&RangeInputData> {
+        match &self.special_data {
+            SpecialElementData::RangeInput(data) => Some(data),
+            _ => None,
+        }
+    }
+
+    pub fn range_input_data_mut(&mut self) -> Option<&mut RangeInputData> {
+        match &mut self.special_data {
+            SpecialElementData::RangeInput(data) => Some(data),
+            _ => None,
+        }
+    }
+
+    pub fn select_data(&self) -> Option<&SelectData> {
+        match &self.special_data {
+            SpecialElementData::Select(data) => Some(data),
+            _ => None,
+        }
+    }
+
+    pub fn select_data_mut(&mut self) -> Option<&mut SelectData> {
+        match &mut self.special_data {
+            SpecialElementData::Select(data) => Some(data),
+            _ => None,
+        }
+    }
+
     #[cfg(feature = "file_input")]
     pub fn file_data(&self) -> Option<&FileData> {
         match &self.special_data {
@@ -274,7 +345,7 @@ impl ElementData {
     pub fn flush_style_attribute(&mut self, guard: &SharedRwLock, url_extra_data: &UrlExtraData) {
         self.style_attribute = self.attr(local_name!("style")).map(|style_str| {
             ServoArc::new(guard.wrap(parse_style_attribute(
-                style_str,
+                &expose_paged_media_properties(style_str),
                 url_extra_data,
                 None,
                 QuirksMode::NoQuirks,
@@ -301,7 +372,7 @@ impl ElementData {
             None,
         );
 
-        let Ok(property_id) = PropertyId::parse(name, &context) else {
+        let Ok(property_id) = PropertyId::parse(&exposed_property_name(name), &context) else {
             eprintln!("Warning: unsupported property {name}");
             return;
         };
@@ -344,7 +415,7 @@ impl ElementData {
             None,
             None,
         );
-        let Ok(property_id) = PropertyId::parse(name, &context) else {
+        let Ok(property_id) = PropertyId::parse(&exposed_property_name(name), &context) else {
             eprintln!("Warning: unsupported property {name}");
             return;
         };
@@ -471,6 +542,93 @@ impl TextInputData {
     }
 }
 
+/// The current value of an \<input type=range\>
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RangeInputData {
+    /// The (sanitized) value
+    pub value: f64,
+    /// The value when the user started dragging the thumb (if they are dragging it)
+    pub drag_start_value: Option<f64>,
+}
+
+/// The options of a \<select\> element and their selectedness
+#[derive(Clone, Default)]
+pub struct SelectData {
+    /// The select's list of options (in tree order)
+    pub options: Vec<SelectOption>,
+    /// Whether multiple options can be selected (the `multiple` attribute)
+    pub multiple: bool,
+    /// The number of rows displayed. A value of 1 (without `multiple`) means the select
+    /// is rendered as a drop-down box rather than a list box.
+    pub display_size: usize,
+    /// The width of the widest option label (in CSS pixels)
+    pub content_width: f32,
+    /// The option currently highlighted by the mouse or keyboard (an index into `options`)
+    pub highlighted: Option<usize>,
+    /// The first option visible in a list box or drop-down popup (an index into `options`)
+    pub scroll_index: usize,
+    /// The height of each option's row (in CSS pixels)
+    pub row_height: f32,
+}
+
+impl SelectData {
+    /// The width reserved for the drop-down arrow (in CSS pixels)
+    pub const ARROW_WIDTH: f32 = 16.0;
+    /// The maximum number of options shown at once by the popup of a drop-down box
+    pub const MAX_POPUP_ROWS: usize = 20;
+
+    /// Whether the select is rendered as a drop-down box (rather than a list box)
+    pub fn is_drop_down(&self) -> bool {
+        !self.multiple && self.display_size == 1
+    }
+
+    /// Indexes of the options that are selected
+    pub fn selected_indexes(&self) -> impl Iterator<Item = usize> + '_ {
+        self.options
+            .iter()
+            .enumerate()
+            .filter(|(_, option)| option.selected)
+            .map(|(idx, _)| idx)
+    }
+
+    /// Index of the first selected option (if any)
+    pub fn selected_index(&self) -> Option<usize> {
+        self.selected_indexes().next()
+    }
+}
+
+/// An \<option\> in a \<select\> element's list of options
+#[derive(Clone)]
+pub struct SelectOption {
+    /// The node ID of the \<option\> element
+    pub node_id: usize,
+    /// The option's value (for form submission)
+    pub value: String,
+    /// The option's selectedness
+    pub selected: bool,
+    /// Whether the option (or its \<optgroup\>) is disabled
+    pub disabled: bool,
+    /// Whether the option is within an \<optgroup\> (and should be indented)
+    pub in_optgroup: bool,
+    /// The option's label, laid out with the select's font styles
+    pub layout: Box<parley::Layout<TextBrush>>,
+}
+
+impl SelectOption {
+    /// The indentation of options within an \<optgroup\> (in CSS pixels)
+    pub const OPTGROUP_INDENT: f32 = 8.0;
+}
+
+impl std::fmt::Debug for SelectOption {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SelectOption")
+            .field("node_id", &self.node_id)
+            .field("value", &self.value)
+            .field("selected", &self.selected)
+            .finish()
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct CanvasData {
     pub custom_paint_source_id: u64,
@@ -490,6 +648,8 @@ impl std::fmt::Debug for SpecialElementData {
             SpecialElementData::TableRoot(_) => f.write_str("NodeSpecificData::TableRoot"),
             SpecialElementData::TextInput(_) => f.write_str("NodeSpecificData::TextInput"),
             SpecialElementData::CheckboxInput(_) => f.write_str("NodeSpecificData::CheckboxInput"),
+            SpecialElementData::RangeInput(_) => f.write_str("NodeSpecificData::RangeInput"),
+            SpecialElementData::Select(_) => f.write_str("NodeSpecificData::Select"),
             #[cfg(feature = "file_input")]
             SpecialElementData::FileInput(_) => f.write_str("NodeSpecificData::FileInput"),
             SpecialElementData::None => f.write_str("NodeSpecificData::None"),
@@ -503,6 +663,22 @@ pub struct ListItemLayout {
     pub position: ListItemLayoutPosition,
 }
 
+/// A border of a table cell resolved by the collapsing border model
+#[derive(Debug, Clone, PartialEq)]
+pub struct CollapsedBorder {
+    pub width: f32,
+    pub style: BorderStyle,
+    pub color: AbsoluteColor,
+}
+
+/// An item of generated content (the `content` of a `::before` or `::after` pseudo element) with
+/// counters, quotes and attributes resolved to text
+#[derive(Debug, PartialEq, Clone)]
+pub enum GeneratedContent {
+    Text(String),
+    Image(Url),
+}
+
 //We seperate chars from strings in order to optimise rendering - ie not needing to
 //construct a whole parley layout for simple char markers
 #[derive(Debug, PartialEq, Clone)]
@@ -542,6 +718,10 @@ pub struct TextLayout {
     pub text: String,
     pub content_widths: Option<ContentWidths>,
     pub layout: parley::layout::Layout<TextBrush>,
+    /// The offset of each line box from the position Parley gives it (in CSS pixels). Lines
+    /// beside floats are moved aside, and lines which don't fit beside floats are moved below them.
+    /// Empty if no floats intrude into the inline formatting context.
+    pub line_offsets: Vec<taffy::Point<f32>>,
 }
 
 impl TextLayout {
@@ -554,6 +734,14 @@ impl TextLayout {
             .content_widths
             .get_or_insert_with(|| self.layout.calculate_content_widths())
     }
+
+    /// The offset of the line box at `line_index` from the position Parley gives it (in CSS pixels)
+    pub fn line_offset(&self, line_index: usize) -> taffy::Point<f32> {
+        self.line_offsets
+            .get(line_index)
+            .copied()
+            .unwrap_or(taffy::Point::ZERO)
+    }
 }
 
 impl std::fmt::Debug for TextLayout {
@@ -6,8 +6,9 @@ mod node;
 
 pub use attributes::{Attribute, Attributes};
 pub use element::{
-    BackgroundImageData, CanvasData, ElementData, ImageData, ListItemLayout,
-    ListItemLayoutPosition, Marker, RasterImageData, SpecialElementData, SpecialElementType,
-    Status, TextBrush, TextInputData, TextLayout,
+    BackgroundImageData, CanvasData, CollapsedBorder, ElementData, GeneratedContent, ImageData,
+    ListItemLayout, ListItemLayoutPosition, Marker, RangeInputData, RasterImageData, SelectData,
+    SelectOption, SpecialElementData, SpecialElementType, Status, TextBrush, TextInputData,
+    TextLayout,
 };
 pub use node::*;
@@ -1,6 +1,7 @@
 use atomic_refcell::{AtomicRef, AtomicRefCell, AtomicRefMut};
 use bitflags::bitflags;
 use blitz_traits::events::{BlitzMouseButtonEvent, DomEventData, HitResult};
+use euclid::default::{Transform2D, Transform3D};
 use keyboard_types::Modifiers;
 use markup5ever::{LocalName, local_name};
 use parley::Cluster;
@@ -10,12 +11,15 @@ use std::cell::{Cell, RefCell};
 use std::fmt::Write;
 use std::sync::atomic::AtomicBool;
 use style::Atom;
+use style::author_styles::AuthorStyles;
 use style::invalidation::element::restyle_hints::RestyleHint;
 use style::properties::ComputedValues;
 use style::properties::generated::longhands::position::computed_value::T as Position;
 use style::selector_parser::{PseudoElement, RestyleDamage};
-use style::stylesheets::UrlExtraData;
+use style::stylesheets::{DocumentStyleSheet, UrlExtraData};
+use style::values::computed::CSSPixelLength;
 use style::values::computed::Display as StyloDisplay;
+use style::values::generics::box_::GenericPerspective;
 use style::values::specified::box_::{DisplayInside, DisplayOutside};
 use style::{data::ElementData as StyloElementData, shared_lock::SharedRwLock};
 use style_dom::ElementState;
@@ -26,6 +30,7 @@ use taffy::{
 };
 
 use super::{Attribute, ElementData};
+use crate::{StackingContext, VerticalFlow};
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum DisplayOuter {
@@ -102,6 +107,12 @@ pub struct Node {
     // Pseudo element nodes
     pub before: Option<usize>,
     pub after: Option<usize>,
+    /// The `::marker` pseudo element of a list item. It is not part of the layout tree (the marker is
+    /// laid out by the list item), it only holds the styles of the marker.
+    pub marker: Option<usize>,
+
+    /// The \<slot\> element this node is assigned to (children of shadow hosts only)
+    pub assigned_slot: Option<usize>,
 
     // Taffy layout data:
     pub style: Style<Atom>,
@@ -112,6 +123,13 @@ pub struct Node {
     pub unrounded_layout: Layout,
     pub final_layout: Layout,
     pub scroll_offset: crate::Point<f64>,
+    /// Offset applied to `position: sticky` boxes (and `position: fixed` boxes within inline
+    /// formatting contexts) at paint time (zero for all other boxes)
+    pub sticky_offset: crate::Point<f64>,
+    /// The descendants painted by this node, if it is the root of a stacking context
+    pub stacking_context: Option<Box<StackingContext>>,
+    /// The vertical writing mode flow that this node is laid out in (`None` for horizontal flows)
+    pub vertical_flow: Option<VerticalFlow>,
 }
 
 unsafe impl Send for Node {}
@@ -144,6 +162,9 @@ impl Node {
 
             before: None,
             after: None,
+            marker: None,
+
+            assigned_slot: None,
 
             style: Default::default(),
             has_snapshot: false,
@@ -153,6 +174,9 @@ impl Node {
             unrounded_layout: Layout::new(),
             final_layout: Layout::new(),
             scroll_offset: crate::Point::ZERO,
+            sticky_offset: crate::Point::ZERO,
+            stacking_context: None,
+            vertical_flow: None,
         }
     }
 
@@ -199,7 +223,7 @@ impl Node {
             DisplayOutside::Block => true,
             _ => {
                 if display.inside() == DisplayInside::Flow {
-                    self.children
+                    self.flat_tree_children()
                         .iter()
                         .copied()
                         .any(|child_id| self.tree()[child_id].is_or_contains_block())
@@ -280,9 +304,11 @@ impl Node {
         self.element_state.contains(ElementState::HOVER)
     }
 
-    pub fn focus(&mut self) {
+    /// Focus the node. `focus_visible` controls whether it matches `:focus-visible`
+    pub fn focus(&mut self, focus_visible: bool) {
+        self.element_state.insert(ElementState::FOCUS);
         self.element_state
-            .insert(ElementState::FOCUS | ElementState::FOCUSRING);
+            .set(ElementState::FOCUSRING, focus_visible);
         self.set_restyle_hint(RestyleHint::restyle_subtree());
     }
 
@@ -296,6 +322,36 @@ impl Node {
         self.element_state.contains(ElementState::FOCUS)
     }
 
+    pub fn focus_within(&mut self) {
+        self.element_state.insert(ElementState::FOCUS_WITHIN);
+    }
+
+    pub fn unfocus_within(&mut self) {
+        self.element_state.remove(ElementState::FOCUS_WITHIN);
+    }
+
+    pub fn target(&mut self) {
+        self.element_state.insert(ElementState::URLTARGET);
+    }
+
+    pub fn untarget(&mut self) {
+        self.element_state.remove(ElementState::URLTARGET);
+    }
+
+    /// The node's language, as given by the `lang` attribute of the node or its nearest
+    /// ancestor with one (following shadow roots to their host).
+    ///
+    /// <https://html.spec.whatwg.org/multipage/dom.html#language>
+    pub fn language(&self) -> Option<&str> {
+        let mut node = self;
+        loop {
+            if let Some(lang) = node.attr(local_name!("lang")) {
+                return Some(lang);
+            }
+            node = self.with(node.parent?);
+        }
+    }
+
     pub fn active(&mut self) {
         self.element_state.insert(ElementState::ACTIVE);
         self.set_restyle_hint(RestyleHint::restyle_subtree());
@@ -318,6 +374,7 @@ pub enum NodeKind {
     AnonymousBlock,
     Text,
     Comment,
+    ShadowRoot,
 }
 
 /// The different kinds of nodes in the DOM.
@@ -337,6 +394,9 @@ pub enum NodeData {
 
     /// A comment.
     Comment,
+
+    /// The root of a shadow tree attached to a host element
+    ShadowRoot(ShadowRootData),
     // Comment { contents: String },
 
     // /// A `DOCTYPE` with name, public id, and system id. See
@@ -391,6 +451,7 @@ impl NodeData {
             NodeData::AnonymousBlock(_) => NodeKind::AnonymousBlock,
             NodeData::Text(_) => NodeKind::Text,
             NodeData::Comment => NodeKind::Comment,
+            NodeData::ShadowRoot(_) => NodeKind::ShadowRoot,
         }
     }
 }
@@ -407,6 +468,49 @@ impl TextNodeData {
     }
 }
 
+/// <https://dom.spec.whatwg.org/#shadowroot-mode>
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ShadowRootMode {
+    #[default]
+    Open,
+    Closed,
+}
+
+pub struct ShadowRootData {
+    /// The ID of the element this shadow root is attached to
+    pub host: usize,
+    pub mode: ShadowRootMode,
+    /// Stylesheets scoped to the shadow tree, and the cascade data computed from them
+    pub author_styles: AuthorStyles<DocumentStyleSheet>,
+}
+
+impl ShadowRootData {
+    pub fn new(host: usize, mode: ShadowRootMode) -> Self {
+        Self {
+            host,
+            mode,
+            author_styles: AuthorStyles::new(),
+        }
+    }
+}
+
+impl Clone for ShadowRootData {
+    /// Scoped stylesheets are not copied. They are re-added when the cloned shadow tree's
+    /// \<style\> elements are processed.
+    fn clone(&self) -> Self {
+        Self::new(self.host, self.mode)
+    }
+}
+
+impl std::fmt::Debug for ShadowRootData {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ShadowRootData")
+            .field("host", &self.host)
+            .field("mode", &self.mode)
+            .finish_non_exhaustive()
+    }
+}
+
 /*
 -> Computed styles
 -> Layout
@@ -503,6 +607,62 @@ impl Node {
         matches!(self.data, NodeData::Text { .. })
     }
 
+    pub fn is_shadow_root(&self) -> bool {
+        matches!(self.data, NodeData::ShadowRoot(_))
+    }
+
+    pub fn shadow_root_data(&self) -> Option<&ShadowRootData> {
+        match self.data {
+            NodeData::ShadowRoot(ref data) => Some(data),
+            _ => None,
+        }
+    }
+
+    pub fn shadow_root_data_mut(&mut self) -> Option<&mut ShadowRootData> {
+        match self.data {
+            NodeData::ShadowRoot(ref mut data) => Some(data),
+            _ => None,
+        }
+    }
+
+    /// The node's parent in the DOM, including shadow roots (which Stylo's `parent_node` excludes)
+    pub(crate) fn parent_node_or_shadow_root(&self) -> Option<&Node> {
+        self.parent.map(|id| self.with(id))
+    }
+
+    /// The ID of the shadow root attached to this node (if it is a shadow host)
+    pub fn shadow_root_id(&self) -> Option<usize> {
+        self.element_data()?.shadow_root
+    }
+
+    /// The ID of the shadow root whose shadow tree contains this node (if any)
+    pub fn containing_shadow_root_id(&self) -> Option<usize> {
+        let mut node = self;
+        while let Some(parent_id) = node.parent {
+            node = self.with(parent_id);
+            if node.is_shadow_root() {
+                return Some(node.id);
+            }
+        }
+        None
+    }
+
+    /// The node's children in the [flat tree](https://drafts.csswg.org/css-scoping/#flat-tree):
+    ///   - For shadow hosts, the children of the attached shadow root
+    ///   - For \<slot\> elements, the assigned nodes (or the slot's own children as fallback content)
+    ///   - Otherwise, the regular DOM children
+    pub fn flat_tree_children(&self) -> &[usize] {
+        if let Some(shadow_root_id) = self.shadow_root_id() {
+            return &self.with(shadow_root_id).children;
+        }
+        if let Some(el) = self.element_data() {
+            if !el.assigned_nodes.is_empty() {
+                return &el.assigned_nodes;
+            }
+        }
+        &self.children
+    }
+
     pub fn element_data(&self) -> Option<&ElementData> {
         match self.data {
             NodeData::Element(ref data) => Some(data),
@@ -554,6 +714,7 @@ impl Node {
                 // &std::str::from_utf8(data.contents.as_bytes().split_at(10).0).unwrap_or("INVALID UTF8")
             ),
             NodeData::AnonymousBlock(_) => write!(s, "AnonymousBlock"),
+            NodeData::ShadowRoot(data) => write!(s, "SHADOW ROOT (host: {})", data.host),
             NodeData::Element(data) => {
                 let name = &data.name;
                 let class = self.attr(local_name!("class")).unwrap_or("");
@@ -594,6 +755,7 @@ impl Node {
             NodeData::Document => {}
             NodeData::Comment => {}
             NodeData::AnonymousBlock(_) => {}
+            NodeData::ShadowRoot(_) => {}
             // NodeData::Doctype { name, .. } => write!(s, "DOCTYPE {name}"),
             NodeData::Text(data) => {
                 writer.push_str(data.content.as_str());
@@ -705,17 +867,137 @@ impl Node {
             .unwrap_or(0)
     }
 
+    /// The 2D transform which maps points in the node's local coordinate space (relative to the
+    /// top-left of its border box) to where they are painted (relative to the same point in its
+    /// untransformed position). Returns `None` if the node is not transformed.
+    ///
+    /// This combines the node's `transform` and `transform-origin` with the `perspective` of its
+    /// parent. 3D transforms are flattened into 2D by projecting the node's border box.
+    pub fn css_transform(&self) -> Option<Transform2D<f32>> {
+        let style = self.primary_styles()?;
+        let box_styles = style.get_box();
+
+        let parent = self.layout_parent.get().map(|id| self.with(id));
+        let parent_styles = parent.and_then(|parent| parent.primary_styles());
+        let parent_perspective =
+            parent_styles
+                .as_ref()
+                .and_then(|styles| match styles.get_box().perspective {
+                    GenericPerspective::Length(length) => Some(length.0.px()),
+                    GenericPerspective::None => None,
+                });
+        if box_styles.transform.0.is_empty() && parent_perspective.is_none() {
+            return None;
+        }
+
+        let size = self.final_layout.size;
+        let reference_box = euclid::Rect::new(
+            euclid::Point2D::new(CSSPixelLength::new(0.0), CSSPixelLength::new(0.0)),
+            euclid::Size2D::new(
+                CSSPixelLength::new(size.width),
+                CSSPixelLength::new(size.height),
+            ),
+        );
+        let (transform, _) = box_styles
+            .transform
+            .to_transform_3d_matrix(Some(&reference_box))
+            .ok()?;
+
+        // Apply the transform around the transform origin
+        let origin = &box_styles.transform_origin;
+        let origin = (
+            origin
+                .horizontal
+                .resolve(CSSPixelLength::new(size.width))
+                .px(),
+            origin
+                .vertical
+                .resolve(CSSPixelLength::new(size.height))
+                .px(),
+            origin.depth.px(),
+        );
+        let mut transform = Transform3D::translation(-origin.0, -origin.1, -origin.2)
+            .then(&transform)
+            .then(&Transform3D::translation(origin.0, origin.1, origin.2));
+
+        // Apply the parent's perspective around the parent's perspective origin
+        if let (Some(distance), Some(parent), Some(parent_styles)) =
+            (parent_perspective, parent, parent_styles.as_ref())
+        {
+            let parent_size = parent.final_layout.size;
+            let location = self.final_layout.location;
+            let origin = &parent_styles.get_box().perspective_origin;
+            let origin = (
+                origin
+                    .horizontal
+                    .resolve(CSSPixelLength::new(parent_size.width))
+                    .px()
+                    - location.x,
+                origin
+                    .vertical
+                    .resolve(CSSPixelLength::new(parent_size.height))
+                    .px()
+                    - location.y,
+            );
+            #[rustfmt::skip]
+            let perspective = Transform3D::new(
+                1.0, 0.0, 0.0, 0.0,
+                0.0, 1.0, 0.0, 0.0,
+                0.0, 0.0, 1.0, -1.0 / distance.max(1.0),
+                0.0, 0.0, 0.0, 1.0,
+            );
+            transform = transform
+                .then(&Transform3D::translation(-origin.0, -origin.1, 0.0))
+                .then(&perspective)
+                .then(&Transform3D::translation(origin.0, origin.1, 0.0));
+        }
+
+        // Flatten to 2D by finding the affine transform which maps the top-left, top-right and
+        // bottom-left corners of the border box to the same points as the 3D transform
+        let width = if size.width > 0.0 { size.width } else { 1.0 };
+        let height = if size.height > 0.0 { size.height } else { 1.0 };
+        let project = |x, y| transform.transform_point2d(euclid::Point2D::new(x, y));
+        let top_left = project(0.0, 0.0)?;
+        let top_right = project(width, 0.0)?;
+        let bottom_left = project(0.0, height)?;
+        Some(Transform2D::new(
+            (top_right.x - top_left.x) / width,
+            (top_right.y - top_left.y) / width,
+            (bottom_left.x - top_left.x) / height,
+            (bottom_left.y - top_left.y) / height,
+            top_left.x,
+            top_left.y,
+        ))
+    }
+
     /// Takes an (x, y) position (relative to the *parent's* top-left corner) and returns:
     ///    - None if the position is outside of this node's bounds
     ///    - Some(HitResult) if the position is within the node but doesn't match any children
     ///    - The result of recursively calling child.hit() on the the child element that is
     ///      positioned at that position if there is one.
     ///
-    /// TODO: z-index
-    /// (If multiple children are positioned at the position then a random one will be recursed into)
+    /// Transformed nodes are hit tested by mapping the position through the inverse of the node's
+    /// transform, so the x and y of the result are in the hit node's untransformed coordinate space.
+    ///
+    /// Where boxes overlap, the one painted on top is hit: descendants in the node's stacking
+    /// context are tested in reverse paint order (positive z-index, then in-flow children, then
+    /// negative z-index).
     pub fn hit(&self, x: f32, y: f32) -> Option<HitResult> {
-        let mut x = x - self.final_layout.location.x + self.scroll_offset.x as f32;
-        let mut y = y - self.final_layout.location.y + self.scroll_offset.y as f32;
+        let location = self.final_layout.location;
+        let mut x = x - location.x - self.sticky_offset.x as f32;
+        let mut y = y - location.y - self.sticky_offset.y as f32;
+
+        // Map the point into the node's untransformed coordinate space
+        if let Some(transform) = self.css_transform() {
+            let point = transform
+                .inverse()?
+                .transform_point(euclid::Point2D::new(x, y));
+            x = point.x;
+            y = point.y;
+        }
+
+        x += self.scroll_offset.x as f32;
+        y += self.scroll_offset.y as f32;
 
         let size = self.final_layout.size;
         let matches_self = !(x < 0.0
@@ -729,14 +1011,55 @@ impl Node {
             || y < 0.0
             || y > content_size.height + self.scroll_offset.y as f32);
 
-        if !matches_self && !matches_content {
+        // Descendants painted by this node's stacking context may overflow it, so they must still be
+        // hit tested when the point is outside of this node.
+        let stacking_context = self.stacking_context.as_deref();
+        if !matches_self && !matches_content && stacking_context.is_none() {
             return None;
         }
 
+        // Map the point into the coordinate space of the node's content if it is the root of a
+        // vertical flow
+        if let Some(transform) = self.content_transform() {
+            let point = transform
+                .inverse()?
+                .transform_point(euclid::Point2D::new(x, y));
+            x = point.x;
+            y = point.y;
+        }
+
+        // Hit test the stacking context's descendants in reverse paint order. They are positioned
+        // relative to their layout parent, so the point is offset by the position of the layout parent
+        // relative to this node.
+        let hit_stacking_context = move |descendants: &[usize]| {
+            descendants.iter().rev().find_map(|&id| {
+                let descendant = self.with(id);
+                let offset = descendant
+                    .layout_parent
+                    .get()
+                    .map(|parent_id| self.with(parent_id).offset_from_ancestor(self.id))
+                    .unwrap_or(taffy::Point::ZERO);
+                descendant.hit(x - offset.x, y - offset.y)
+            })
+        };
+        if let Some(hit) =
+            stacking_context.and_then(|sc| hit_stacking_context(&sc.positive_z_descendants))
+        {
+            return Some(hit);
+        }
+        if !matches_self && !matches_content {
+            return stacking_context
+                .and_then(|sc| hit_stacking_context(&sc.negative_z_descendants));
+        }
+
         if self.flags.is_inline_root() {
+            let mut padding_border = self.final_layout.padding + self.final_layout.border;
+            if let Some(flow) = self.vertical_flow_root() {
+                padding_border = flow.transpose_rect(padding_border);
+            }
             let content_box_offset = taffy::Point {
-                x: self.final_layout.padding.left + self.final_layout.border.left,
-                y: self.final_layout.padding.top + self.final_layout.border.top,
+                x: padding_border.left,
+                y: padding_border.top,
             };
             x -= content_box_offset.x;
             y -= content_box_offset.y;
@@ -752,9 +1075,22 @@ impl Node {
             .or_else(|| {
                 if self.flags.is_inline_root() {
                     let element_data = &self.element_data().unwrap();
-                    let layout = &element_data.inline_layout_data.as_ref().unwrap().layout;
+                    let text_layout = element_data.inline_layout_data.as_ref().unwrap();
+                    let layout = &text_layout.layout;
                     let scale = layout.scale();
 
+                    // Lines may be moved aside by floats, so find the line at `y` (or the last
+                    // line) and hit test the text where Parley placed that line
+                    let mut line_offset = taffy::Point::ZERO;
+                    for (line_index, line) in layout.lines().enumerate() {
+                        line_offset = text_layout.line_offset(line_index);
+                        if y - line_offset.y < line.metrics().max_coord / scale {
+                            break;
+                        }
+                    }
+                    let x = x - line_offset.x;
+                    let y = y - line_offset.y;
+
                     Cluster::from_point(layout, x * scale, y * scale).and_then(|(cluster, _)| {
                         let style_index = cluster.glyphs().next()?.style_index();
                         let node_id = layout.styles()[style_index].brush.id;
@@ -764,6 +1100,9 @@ impl Node {
                     None
                 }
             })
+            .or_else(|| {
+                stacking_context.and_then(|sc| hit_stacking_context(&sc.negative_z_descendants))
+            })
             .or(Some(HitResult {
                 node_id: self.id,
                 x,
@@ -772,15 +1111,53 @@ impl Node {
             .filter(|_| matches_self))
     }
 
+    /// The position of this node's children's coordinate space relative to the children's coordinate
+    /// space of the ancestor `ancestor_id` (taking into account scroll and sticky offsets).
+    ///
+    /// Used to position descendants painted by a stacking context.
+    pub fn offset_from_ancestor(&self, ancestor_id: usize) -> taffy::Point<f32> {
+        let mut offset = taffy::Point::ZERO;
+        let mut node = self;
+        while node.id != ancestor_id {
+            let location = node.final_layout.location;
+            offset.x += location.x + (node.sticky_offset.x - node.scroll_offset.x) as f32;
+            offset.y += location.y + (node.sticky_offset.y - node.scroll_offset.y) as f32;
+            match node.layout_parent.get() {
+                Some(parent_id) => node = self.with(parent_id),
+                None => break,
+            }
+        }
+        offset
+    }
+
     /// Computes the Document-relative coordinates of the Node
     pub fn absolute_position(&self, x: f32, y: f32) -> taffy::Point<f32> {
-        let x = x + self.final_layout.location.x - self.scroll_offset.x as f32;
-        let y = y + self.final_layout.location.y - self.scroll_offset.y as f32;
+        let mut x = x - self.scroll_offset.x as f32;
+        let mut y = y - self.scroll_offset.y as f32;
+
+        // Map the point from the node's untransformed coordinate space to where it is painted
+        if let Some(transform) = self.css_transform() {
+            let point = transform.transform_point(euclid::Point2D::new(x, y));
+            x = point.x;
+            y = point.y;
+        }
+
+        let location = self.final_layout.location;
+        let x = x + location.x + self.sticky_offset.x as f32;
+        let y = y + location.y + self.sticky_offset.y as f32;
 
-        // Recurse up the layout hierarchy
+        // Recurse up the layout hierarchy (mapping the point from the coordinate space of a vertical
+        // flow to where it is painted if the parent is the root of one)
         self.layout_parent
             .get()
-            .map(|i| self.with(i).absolute_position(x, y))
+            .map(|i| {
+                let parent = self.with(i);
+                let point = match parent.content_transform() {
+                    Some(transform) => transform.transform_point(euclid::Point2D::new(x, y)),
+                    None => euclid::Point2D::new(x, y),
+                };
+                parent.absolute_position(point.x, point.y)
+            })
             .unwrap_or(taffy::Point { x, y })
     }
 
@@ -0,0 +1,431 @@
+//! Numeric form controls: `<input type=range>`, `<progress>` and `<meter>`
+//!
+//! Range inputs store their current value in [`RangeInputData`]. Progress and meter elements
+//! are purely attribute-driven. The state of a meter (`:-moz-meter-optimum`, etc) and of an
+//! indeterminate progress bar (`:indeterminate`) is stored in its `ElementState` alongside the
+//! other form control states (see `validation.rs`).
+//!
+//! <https://html.spec.whatwg.org/multipage/input.html#range-state-(type=range)>
+
+use blitz_traits::events::{BlitzInputEvent, BlitzKeyEvent, DomEvent, DomEventData};
+use keyboard_types::Key;
+use markup5ever::local_name;
+use style_dom::ElementState;
+
+use crate::node::RangeInputData;
+use crate::{BaseDocument, ElementData};
+
+/// The minimum, maximum and step of a range input (with defaults applied)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RangeBounds {
+    pub min: f64,
+    pub max: f64,
+    /// The allowed value step (`None` if any value is allowed)
+    pub step: Option<f64>,
+}
+
+impl RangeBounds {
+    pub fn from_element(element: &ElementData) -> Self {
+        let min = parse_number(element, local_name!("min")).unwrap_or(0.0);
+        let max = parse_number(element, local_name!("max")).unwrap_or(100.0);
+        let step = match element.attr(local_name!("step")) {
+            Some(step) if step.eq_ignore_ascii_case("any") => None,
+            Some(step) => Some(
+                step.trim()
+                    .parse::<f64>()
+                    .ok()
+                    .filter(|s| *s > 0.0)
+                    .unwrap_or(1.0),
+            ),
+            None => Some(1.0),
+        };
+
+        Self {
+            min,
+            // If the maximum is less than the minimum, then the maximum is the minimum
+            max: max.max(min),
+            step,
+        }
+    }
+
+    /// <https://html.spec.whatwg.org/multipage/input.html#range-state-(type=range):concept-input-value-default-range>
+    pub fn default_value(&self) -> f64 {
+        self.sanitize(self.min + (self.max - self.min) / 2.0)
+    }
+
+    /// Clamp a value to the bounds and round it to the nearest allowed step
+    ///
+    /// <https://html.spec.whatwg.org/multipage/input.html#range-state-(type=range):value-sanitization-algorithm>
+    pub fn sanitize(&self, value: f64) -> f64 {
+        if !value.is_finite() {
+            return self.default_value();
+        }
+        let mut value = value.clamp(self.min, self.max);
+        if let Some(step) = self.step {
+            // Ties round up (towards positive infinity)
+            let steps = ((value - self.min) / step + 0.5).floor();
+            value = self.min + steps * step;
+            if value > self.max {
+                value -= step;
+            }
+        }
+        value
+    }
+
+    /// The position of `value` along the slider (from 0.0 at the minimum to 1.0 at the maximum)
+    pub fn position(&self, value: f64) -> f64 {
+        match self.max > self.min {
+            true => ((value - self.min) / (self.max - self.min)).clamp(0.0, 1.0),
+            false => 0.0,
+        }
+    }
+
+    /// The value at a position (from 0.0 to 1.0) along the slider
+    pub fn value_at(&self, position: f64) -> f64 {
+        self.sanitize(self.min + position.clamp(0.0, 1.0) * (self.max - self.min))
+    }
+
+    /// The amount that the arrow keys change the value by
+    fn key_step(&self) -> f64 {
+        self.step.unwrap_or((self.max - self.min) / 100.0)
+    }
+}
+
+/// The values of a `<progress>` element (with defaults applied)
+///
+/// <https://html.spec.whatwg.org/multipage/form-elements.html#the-progress-element>
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ProgressValues {
+    /// The current value (`None` if the progress bar is indeterminate)
+    pub value: Option<f64>,
+    pub max: f64,
+}
+
+impl ProgressValues {
+    pub fn from_element(element: &ElementData) -> Self {
+        let max = parse_number(element, local_name!("max"))
+            .filter(|max| *max > 0.0)
+            .unwrap_or(1.0);
+        let value = element
+            .attr(local_name!("value"))
+            .map(|value| value.trim().parse::<f64>().ok().filter(|v| v.is_finite()))
+            .map(|value| value.unwrap_or(0.0).clamp(0.0, max));
+
+        Self { value, max }
+    }
+
+    /// The fraction of the task that has completed (`None` if the progress bar is indeterminate)
+    pub fn position(&self) -> Option<f64> {
+        self.value.map(|value| value / self.max)
+    }
+}
+
+/// The values of a `<meter>` element (with defaults applied and constrained to the
+/// minimum and maximum)
+///
+/// <https://html.spec.whatwg.org/multipage/form-elements.html#the-meter-element>
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MeterValues {
+    pub min: f64,
+    pub max: f64,
+    pub value: f64,
+    pub low: f64,
+    pub high: f64,
+    pub optimum: f64,
+}
+
+impl MeterValues {
+    pub fn from_element(element: &ElementData) -> Self {
+        let min = parse_number(element, local_name!("min")).unwrap_or(0.0);
+        let max = parse_number(element, local_name!("max"))
+            .unwrap_or(1.0)
+            .max(min);
+        let value = parse_number(element, local_name!("value"))
+            .unwrap_or(0.0)
+            .clamp(min, max);
+        let low = parse_number(element, local_name!("low"))
+            .unwrap_or(min)
+            .clamp(min, max);
+        let high = parse_number(element, local_name!("high"))
+            .unwrap_or(max)
+            .clamp(low, max);
+        let optimum = parse_number(element, local_name!("optimum"))
+            .unwrap_or(min + (max - min) / 2.0)
+            .clamp(min, max);
+
+        Self {
+            min,
+            max,
+            value,
+            low,
+            high,
+            optimum,
+        }
+    }
+
+    /// The position of the value along the gauge (from 0.0 to 1.0)
+    pub fn position(&self) -> f64 {
+        match self.max > self.min {
+            true => (self.value - self.min) / (self.max - self.min),
+            false => 0.0,
+        }
+    }
+
+    /// Which of the optimum, suboptimum or even-less-good regions the value is in
+    ///
+    /// <https://html.spec.whatwg.org/multipage/rendering.html#the-meter-element-2>
+    pub fn region(&self) -> MeterRegion {
+        let Self {
+            value,
+            low,
+            high,
+            optimum,
+            ..
+        } = *self;
+
+        if optimum < low {
+            if value <= low {
+                MeterRegion::Optimum
+            } else if value <= high {
+                MeterRegion::SubOptimum
+            } else {
+                MeterRegion::SubSubOptimum
+            }
+        } else if optimum > high {
+            if value >= high {
+                MeterRegion::Optimum
+            } else if value >= low {
+                MeterRegion::SubOptimum
+            } else {
+                MeterRegion::SubSubOptimum
+            }
+        } else if value >= low && value <= high {
+            MeterRegion::Optimum
+        } else {
+            MeterRegion::SubOptimum
+        }
+    }
+
+    /// The `:-moz-meter-optimum`-family state of the meter
+    pub(crate) fn state(&self) -> ElementState {
+        match self.region() {
+            MeterRegion::Optimum => ElementState::OPTIMUM,
+            MeterRegion::SubOptimum => ElementState::SUB_OPTIMUM,
+            MeterRegion::SubSubOptimum => ElementState::SUB_SUB_OPTIMUM,
+        }
+    }
+}
+
+/// The region of a `<meter>`'s gauge that its value is in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MeterRegion {
+    Optimum,
+    SubOptimum,
+    SubSubOptimum,
+}
+
+/// <https://html.spec.whatwg.org/multipage/common-microsyntaxes.html#rules-for-parsing-floating-point-number-values>
+fn parse_number(element: &ElementData, name: markup5ever::LocalName) -> Option<f64> {
+    element
+        .attr(name)
+        .and_then(|value| value.trim().parse::<f64>().ok())
+        .filter(|value| value.is_finite())
+}
+
+impl BaseDocument {
+    /// The current value of an `<input type=range>`
+    pub fn range_input_value(&self, node_id: usize) -> Option<f64> {
+        self.nodes[node_id]
+            .element_data()?
+            .range_input_data()
+            .map(|data| data.value)
+    }
+
+    /// Set the value of an `<input type=range>`. The value is sanitized to the input's bounds.
+    pub fn set_range_input_value(&mut self, node_id: usize, value: f64) {
+        let Some(element) = self.nodes[node_id].element_data_mut() else {
+            return;
+        };
+        let bounds = RangeBounds::from_element(element);
+        if let Some(data) = element.range_input_data_mut() {
+            data.value = bounds.sanitize(value);
+            self.shell_provider.request_redraw();
+        }
+    }
+
+    /// Start dragging a range input's thumb. `x` is in document coordinates.
+    pub(crate) fn start_range_drag<F: FnMut(DomEvent)>(
+        &mut self,
+        node_id: usize,
+        x: f32,
+        dispatch_event: F,
+    ) {
+        let Some(data) = self.nodes[node_id]
+            .element_data_mut()
+            .and_then(|el| el.range_input_data_mut())
+        else {
+            return;
+        };
+        data.drag_start_value = Some(data.value);
+        self.set_focus_to(node_id);
+        self.update_range_drag(node_id, x, dispatch_event);
+    }
+
+    /// Move the thumb of a range input that is being dragged to `x` (in document coordinates)
+    pub(crate) fn update_range_drag<F: FnMut(DomEvent)>(
+        &mut self,
+        node_id: usize,
+        x: f32,
+        mut dispatch_event: F,
+    ) -> bool {
+        let node = &self.nodes[node_id];
+        let Some(element) = node.element_data() else {
+            return false;
+        };
+        let Some(data) = element.range_input_data() else {
+            return false;
+        };
+        if data.drag_start_value.is_none() {
+            return false;
+        }
+
+        let layout = &node.final_layout;
+        let content_x =
+            node.absolute_position(0.0, 0.0).x + layout.border.left + layout.padding.left;
+        let content_width = layout.size.width
+            - layout.border.left
+            - layout.border.right
+            - layout.padding.left
+            - layout.padding.right;
+        let position = match content_width > 0.0 {
+            true => ((x - content_x) / content_width) as f64,
+            false => 0.0,
+        };
+        let value = RangeBounds::from_element(element).value_at(position);
+        self.user_set_range_value(node_id, value, &mut dispatch_event)
+    }
+
+    /// Stop dragging a range input's thumb, firing a `change` event if the value changed
+    pub(crate) fn end_range_drag<F: FnMut(DomEvent)>(
+        &mut self,
+        node_id: usize,
+        mut dispatch_event: F,
+    ) {
+        let Some(data) = self.nodes[node_id]
+            .element_data_mut()
+            .and_then(|el| el.range_input_data_mut())
+        else {
+            return;
+        };
+        let Some(start_value) = data.drag_start_value.take() else {
+            return;
+        };
+        if data.value != start_value {
+            let value = data.value.to_string();
+            dispatch_event(DomEvent::new(
+                node_id,
+                DomEventData::Change(BlitzInputEvent { value }),
+            ));
+        }
+    }
+
+    /// Handle keyboard stepping of a focussed range input
+    pub(crate) fn handle_range_keypress<F: FnMut(DomEvent)>(
+        &mut self,
+        node_id: usize,
+        event: &BlitzKeyEvent,
+        mut dispatch_event: F,
+    ) {
+        if self.is_disabled(node_id) {
+            return;
+        }
+        let Some(element) = self.nodes[node_id].element_data() else {
+            return;
+        };
+        let Some(data) = element.range_input_data() else {
+            return;
+        };
+        let bounds = RangeBounds::from_element(element);
+        let step = bounds.key_step();
+        let big_step = ((bounds.max - bounds.min) / 10.0).max(step);
+
+        let value = match event.key {
+            Key::ArrowUp | Key::ArrowRight => data.value + step,
+            Key::ArrowDown | Key::ArrowLeft => data.value - step,
+            Key::PageUp => data.value + big_step,
+            Key::PageDown => data.value - big_step,
+            Key::Home => bounds.min,
+            Key::End => bounds.max,
+            _ => return,
+        };
+
+        if self.user_set_range_value(node_id, value, &mut dispatch_event) {
+            let value = self.range_input_value(node_id).unwrap().to_string();
+            dispatch_event(DomEvent::new(
+                node_id,
+                DomEventData::Change(BlitzInputEvent { value }),
+            ));
+        }
+    }
+
+    /// Set a range input's value in response to user interaction, firing an `input` event
+    /// if it changed. Returns whether the value changed.
+    fn user_set_range_value<F: FnMut(DomEvent)>(
+        &mut self,
+        node_id: usize,
+        value: f64,
+        dispatch_event: &mut F,
+    ) -> bool {
+        let old_value = self.range_input_value(node_id);
+        self.set_range_input_value(node_id, value);
+        let new_value = self.range_input_value(node_id);
+        if new_value == old_value {
+            return false;
+        }
+
+        if let Some(element) = self.nodes[node_id].element_data_mut() {
+            element.user_validity = true;
+        }
+        self.update_form_control_state(node_id);
+
+        let value = new_value.unwrap_or_default().to_string();
+        dispatch_event(DomEvent::new(
+            node_id,
+            DomEventData::Input(BlitzInputEvent { value }),
+        ));
+        true
+    }
+}
+
+impl RangeInputData {
+    pub fn new(element: &ElementData) -> Self {
+        let bounds = RangeBounds::from_element(element);
+        let value = parse_number(element, local_name!("value"))
+            .map(|value| bounds.sanitize(value))
+            .unwrap_or_else(|| bounds.default_value());
+        Self {
+            value,
+            drag_start_value: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_range_sanitize() {
+        let bounds = RangeBounds {
+            min: 0.0,
+            max: 10.0,
+            step: Some(3.0),
+        };
+        assert_eq!(bounds.sanitize(-5.0), 0.0);
+        assert_eq!(bounds.sanitize(4.4), 3.0);
+        assert_eq!(bounds.sanitize(4.5), 6.0);
+        // 12 would be above the maximum, so step down
+        assert_eq!(bounds.sanitize(10.0), 9.0);
+        assert_eq!(bounds.default_value(), 6.0);
+    }
+}
@@ -0,0 +1,604 @@
+//! The \<select\> element: selectedness, the drop-down popup, and user interaction
+//!
+//! The options of a select are collected (and their labels laid out) during box construction
+//! (see `layout::select`). The select itself is laid out as a single box, and its options are
+//! painted from its [`SelectData`] rather than as regular children.
+//!
+//! <https://html.spec.whatwg.org/multipage/form-elements.html#the-select-element>
+
+use blitz_traits::events::{BlitzInputEvent, BlitzKeyEvent, DomEvent, DomEventData};
+use keyboard_types::{Key, Modifiers};
+use markup5ever::local_name;
+
+use crate::layout::damage::ALL_DAMAGE;
+use crate::node::SelectData;
+use crate::{BaseDocument, ElementData};
+
+/// The geometry of an open drop-down popup (in document coordinates)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SelectPopup {
+    /// The node ID of the \<select\> element
+    pub select_id: usize,
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub row_height: f32,
+    /// The number of rows shown by the popup
+    pub row_count: usize,
+    /// The option shown in the first row (an index into the select's options)
+    pub first_option: usize,
+}
+
+impl SelectPopup {
+    pub fn height(&self) -> f32 {
+        self.row_height * self.row_count as f32
+    }
+
+    pub fn contains(&self, x: f32, y: f32) -> bool {
+        x >= self.x && x <= self.x + self.width && y >= self.y && y <= self.y + self.height()
+    }
+
+    /// The index of the option at the point (if any)
+    pub fn option_at(&self, x: f32, y: f32) -> Option<usize> {
+        if !self.contains(x, y) || self.row_height <= 0.0 {
+            return None;
+        }
+        let row = ((y - self.y) / self.row_height) as usize;
+        (row < self.row_count).then_some(self.first_option + row)
+    }
+}
+
+/// The number of rows that a select displays
+///
+/// <https://html.spec.whatwg.org/multipage/form-elements.html#concept-select-size>
+pub(crate) fn select_display_size(element: &ElementData) -> usize {
+    match element.attr_parsed::<usize>(local_name!("size")) {
+        Some(size) if size > 0 => size,
+        _ if element.has_attr(local_name!("multiple")) => 4,
+        _ => 1,
+    }
+}
+
+/// <https://html.spec.whatwg.org/multipage/form-elements.html#selectedness-setting-algorithm>
+pub(crate) fn apply_selectedness_setting_algorithm(data: &mut SelectData) {
+    if data.multiple {
+        return;
+    }
+
+    let selected: Vec<usize> = data.selected_indexes().collect();
+    match selected.len() {
+        // Drop-down boxes must always have a selected option (if there are any enabled options)
+        0 if data.is_drop_down() => {
+            if let Some(option) = data.options.iter_mut().find(|option| !option.disabled) {
+                option.selected = true;
+            }
+        }
+        0 | 1 => {}
+        // Only the last selected option remains selected
+        _ => {
+            for idx in &selected[..selected.len() - 1] {
+                data.options[*idx].selected = false;
+            }
+        }
+    }
+}
+
+impl BaseDocument {
+    /// The \<option\> elements of a \<select\> (including those within an \<optgroup\>)
+    ///
+    /// <https://html.spec.whatwg.org/multipage/form-elements.html#concept-select-option-list>
+    pub fn select_list_of_options(&self, select_id: usize) -> Vec<usize> {
+        let mut options = Vec::new();
+        for &child_id in &self.nodes[select_id].children {
+            let child = &self.nodes[child_id];
+            if child.data.is_element_with_tag_name(&local_name!("option")) {
+                options.push(child_id);
+            } else if child
+                .data
+                .is_element_with_tag_name(&local_name!("optgroup"))
+            {
+                options.extend(child.children.iter().copied().filter(|id| {
+                    self.nodes[*id]
+                        .data
+                        .is_element_with_tag_name(&local_name!("option"))
+                }));
+            }
+        }
+        options
+    }
+
+    /// The node IDs of the selected \<option\>s of a \<select\>
+    pub fn selected_options(&self, select_id: usize) -> Vec<usize> {
+        self.nodes[select_id]
+            .element_data()
+            .and_then(|el| el.select_data())
+            .map(|data| {
+                data.selected_indexes()
+                    .map(|idx| data.options[idx].node_id)
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Set the selectedness of the option at `option_idx` (an index into the select's list of options).
+    /// For selects without the `multiple` attribute this deselects all other options.
+    pub fn set_select_option_selected(
+        &mut self,
+        select_id: usize,
+        option_idx: usize,
+        selected: bool,
+    ) {
+        let Some(data) = self.select_data_mut(select_id) else {
+            return;
+        };
+        if option_idx >= data.options.len() {
+            return;
+        }
+        if selected && !data.multiple {
+            for option in data.options.iter_mut() {
+                option.selected = false;
+            }
+        }
+        data.options[option_idx].selected = selected;
+        apply_selectedness_setting_algorithm(data);
+
+        self.update_form_control_state(select_id);
+        self.shell_provider.request_redraw();
+    }
+
+    /// The node ID of the \<select\> whose drop-down popup is open (if any)
+    pub fn open_select_id(&self) -> Option<usize> {
+        self.open_select_id
+    }
+
+    /// The geometry of the open drop-down popup (if any)
+    ///
+    /// The popup opens below the select, unless more of its rows fit above it within the
+    /// viewport. It shows at most [`SelectData::MAX_POPUP_ROWS`] rows (fewer if they do not fit),
+    /// and the rest of the options are scrolled into view.
+    pub fn select_popup(&self) -> Option<SelectPopup> {
+        let select_id = self.open_select_id?;
+        let node = self.nodes.get(select_id)?;
+        let data = node.element_data()?.select_data()?;
+
+        let position = node.absolute_position(0.0, 0.0);
+        let size = node.final_layout.size;
+        let width = size.width.max(data.content_width + SelectData::ARROW_WIDTH);
+
+        // The visible area of the document
+        let scale = self.viewport.scale();
+        let viewport_x = self.viewport_scroll.x as f32;
+        let viewport_y = self.viewport_scroll.y as f32;
+        let viewport_width = self.viewport.window_size.0 as f32 / scale;
+        let viewport_height = self.viewport.window_size.1 as f32 / scale;
+
+        let rows_within = |space: f32| match data.row_height > 0.0 {
+            true => (space / data.row_height).floor().max(0.0) as usize,
+            false => usize::MAX,
+        };
+        let space_below = viewport_y + viewport_height - (position.y + size.height);
+        let space_above = position.y - viewport_y;
+        let row_count = data.options.len().min(SelectData::MAX_POPUP_ROWS);
+        let open_above = rows_within(space_below) < row_count
+            && rows_within(space_above) > rows_within(space_below);
+        let space = if open_above { space_above } else { space_below };
+        let row_count = row_count.min(rows_within(space).max(1));
+
+        let y = match open_above {
+            true => position.y - row_count as f32 * data.row_height,
+            false => position.y + size.height,
+        };
+        let x = position
+            .x
+            .min(viewport_x + viewport_width - width)
+            .max(viewport_x);
+
+        Some(SelectPopup {
+            select_id,
+            x,
+            y,
+            width,
+            row_height: data.row_height,
+            row_count,
+            first_option: data
+                .scroll_index
+                .min(data.options.len().saturating_sub(row_count)),
+        })
+    }
+
+    /// The number of options shown at once by a list box, or by the popup of a drop-down box
+    /// (`None` if it is not open)
+    fn visible_option_rows(&self, select_id: usize) -> Option<usize> {
+        let data = self.nodes.get(select_id)?.element_data()?.select_data()?;
+        match data.is_drop_down() {
+            true => self
+                .select_popup()
+                .filter(|popup| popup.select_id == select_id)
+                .map(|popup| popup.row_count),
+            false => Some(data.display_size),
+        }
+    }
+
+    /// Scroll a list box (or the open popup of a drop-down box) by `y` CSS pixels. Returns
+    /// `None` if the node is not a select which can be scrolled.
+    pub(crate) fn scroll_select_by(&mut self, select_id: usize, y: f64) -> Option<bool> {
+        let visible_rows = self.visible_option_rows(select_id)?;
+        let data = self.select_data_mut(select_id)?;
+        let max_scroll_index = data.options.len().saturating_sub(visible_rows);
+        if max_scroll_index == 0 || data.row_height <= 0.0 || y == 0.0 {
+            return None;
+        }
+
+        // Scroll by whole rows (and by at least one row)
+        let rows = (y.abs() / data.row_height as f64)
+            .round()
+            .max(1.0)
+            .copysign(y) as isize;
+        let scroll_index = data
+            .scroll_index
+            .min(max_scroll_index)
+            .saturating_add_signed(-rows)
+            .min(max_scroll_index);
+        let has_changed = scroll_index != data.scroll_index;
+        data.scroll_index = scroll_index;
+        if has_changed {
+            self.paint_damage.insert(select_id);
+        }
+        Some(has_changed)
+    }
+
+    pub fn open_select_popup(&mut self, select_id: usize) {
+        if self.open_select_id == Some(select_id) {
+            return;
+        }
+        if let Some(open_id) = self.open_select_id {
+            self.close_select_popup(open_id);
+        }
+        let Some(data) = self.select_data_mut(select_id) else {
+            return;
+        };
+        if !data.is_drop_down() {
+            return;
+        }
+        data.highlighted = data.selected_index();
+        self.open_select_id = Some(select_id);
+
+        // Scroll the selected option into view
+        let visible_rows = self.visible_option_rows(select_id);
+        let data = self.select_data_mut(select_id).unwrap();
+        if let Some((highlighted, visible_rows)) = data.highlighted.zip(visible_rows) {
+            scroll_to_option(data, highlighted, visible_rows);
+        }
+        self.shell_provider.request_redraw();
+    }
+
+    pub fn close_select_popup(&mut self, select_id: usize) {
+        if self.open_select_id != Some(select_id) {
+            return;
+        }
+        self.open_select_id = None;
+        if let Some(data) = self.select_data_mut(select_id) {
+            data.highlighted = None;
+        }
+        self.shell_provider.request_redraw();
+    }
+
+    /// Mark a select whose list of options may have changed as needing its options to be
+    /// recollected (during box construction).
+    pub(crate) fn invalidate_select(&mut self, select_id: usize) {
+        self.nodes[select_id].insert_damage(ALL_DAMAGE);
+    }
+
+    /// The \<select\> that an \<option\> (or \<optgroup\>) belongs to (if any)
+    pub(crate) fn owning_select(&self, node_id: usize) -> Option<usize> {
+        let node = &self.nodes[node_id];
+        let mut parent_id = node.parent?;
+        if self.nodes[parent_id]
+            .data
+            .is_element_with_tag_name(&local_name!("optgroup"))
+        {
+            parent_id = self.nodes[parent_id].parent?;
+        }
+        self.nodes[parent_id]
+            .data
+            .is_element_with_tag_name(&local_name!("select"))
+            .then_some(parent_id)
+    }
+
+    /// Update the selectedness of an \<option\> whose `selected` attribute has changed
+    pub(crate) fn option_selected_attr_changed(&mut self, option_id: usize) {
+        let Some(select_id) = self.owning_select(option_id) else {
+            return;
+        };
+        let selected = self.nodes[option_id].data.has_attr(local_name!("selected"));
+        let option_idx = self
+            .select_data_mut(select_id)
+            .and_then(|data| data.options.iter().position(|o| o.node_id == option_id));
+        if let Some(option_idx) = option_idx {
+            self.set_select_option_selected(select_id, option_idx, selected);
+        }
+    }
+
+    fn select_data_mut(&mut self, select_id: usize) -> Option<&mut SelectData> {
+        self.nodes
+            .get_mut(select_id)?
+            .element_data_mut()?
+            .select_data_mut()
+    }
+
+    /// The value of the first selected \<option\> of a \<select\> (or the empty string).
+    ///
+    /// Falls back to the `selected` attributes if the select's options haven't been collected yet
+    /// (e.g. because it is not rendered).
+    pub(crate) fn select_value(&self, select_id: usize) -> String {
+        if let Some(data) = self.nodes[select_id]
+            .element_data()
+            .and_then(|el| el.select_data())
+        {
+            return data
+                .selected_index()
+                .map(|idx| data.options[idx].value.clone())
+                .unwrap_or_default();
+        }
+
+        let options = self.select_list_of_options(select_id);
+        let selected = options
+            .iter()
+            .copied()
+            .rfind(|id| self.nodes[*id].data.has_attr(local_name!("selected")))
+            .or_else(|| options.first().copied());
+        let Some(option) = selected.map(|id| &self.nodes[id]) else {
+            return String::new();
+        };
+        match option.attr(local_name!("value")) {
+            Some(value) => value.to_string(),
+            None => option.text_content().trim().to_string(),
+        }
+    }
+
+    /// Select an option in response to user interaction, firing `input` and `change` events
+    /// if the selection changed.
+    fn user_select_option<F: FnMut(DomEvent)>(
+        &mut self,
+        select_id: usize,
+        option_idx: usize,
+        toggle: bool,
+        dispatch_event: &mut F,
+    ) {
+        let Some(data) = self.select_data_mut(select_id) else {
+            return;
+        };
+        let Some(option) = data.options.get(option_idx) else {
+            return;
+        };
+        if option.disabled {
+            return;
+        }
+        let before: Vec<usize> = data.selected_indexes().collect();
+        let selected = if toggle { !option.selected } else { true };
+        if toggle && data.multiple {
+            data.options[option_idx].selected = selected;
+        } else {
+            // Without Ctrl/Meta, even in a `multiple` select only the clicked option is selected
+            for option in data.options.iter_mut() {
+                option.selected = false;
+            }
+            self.set_select_option_selected(select_id, option_idx, selected);
+        }
+
+        let visible_rows = self.visible_option_rows(select_id);
+        let data = self.select_data_mut(select_id).unwrap();
+        data.highlighted = Some(option_idx);
+        if let Some(visible_rows) = visible_rows {
+            scroll_to_option(data, option_idx, visible_rows);
+        }
+        let after: Vec<usize> = data.selected_indexes().collect();
+
+        if let Some(element) = self.nodes[select_id].element_data_mut() {
+            element.user_validity = true;
+        }
+        self.update_form_control_state(select_id);
+        self.shell_provider.request_redraw();
+
+        if before != after {
+            let value = self.select_value(select_id);
+            dispatch_event(DomEvent::new(
+                select_id,
+                DomEventData::Input(BlitzInputEvent {
+                    value: value.clone(),
+                }),
+            ));
+            dispatch_event(DomEvent::new(
+                select_id,
+                DomEventData::Change(BlitzInputEvent { value }),
+            ));
+        }
+    }
+
+    /// Handle a click on a \<select\> element (or its open popup). `x` and `y` are in
+    /// document coordinates.
+    pub(crate) fn handle_select_click<F: FnMut(DomEvent)>(
+        &mut self,
+        select_id: usize,
+        x: f32,
+        y: f32,
+        mods: Modifiers,
+        mut dispatch_event: F,
+    ) {
+        if self.is_disabled(select_id) {
+            return;
+        }
+        self.set_focus_to(select_id);
+
+        // Click within the open popup
+        if let Some(popup) = self.select_popup().filter(|p| p.select_id == select_id) {
+            if let Some(option_idx) = popup.option_at(x, y) {
+                self.user_select_option(select_id, option_idx, false, &mut dispatch_event);
+                self.close_select_popup(select_id);
+            } else if !popup.contains(x, y) {
+                self.close_select_popup(select_id);
+            }
+            return;
+        }
+
+        let node = &self.nodes[select_id];
+        let Some(data) = node.element_data().and_then(|el| el.select_data()) else {
+            return;
+        };
+
+        if data.is_drop_down() {
+            self.open_select_popup(select_id);
+            return;
+        }
+
+        // Click on a list box row
+        let position = node.absolute_position(0.0, 0.0);
+        let layout = &node.final_layout;
+        let content_y = y - position.y - layout.border.top - layout.padding.top;
+        if content_y < 0.0 || data.row_height <= 0.0 {
+            return;
+        }
+        let option_idx = data.scroll_index + (content_y / data.row_height) as usize;
+        let toggle = data.multiple && mods.intersects(Modifiers::CONTROL | Modifiers::META);
+        self.user_select_option(select_id, option_idx, toggle, &mut dispatch_event);
+    }
+
+    /// Highlight the option in the open popup that is under the mouse
+    pub(crate) fn handle_select_mousemove(&mut self, x: f32, y: f32) -> bool {
+        let Some(popup) = self.select_popup() else {
+            return false;
+        };
+        let Some(option_idx) = popup.option_at(x, y) else {
+            return false;
+        };
+        let data = self.select_data_mut(popup.select_id).unwrap();
+        if data.highlighted == Some(option_idx) {
+            return false;
+        }
+        data.highlighted = Some(option_idx);
+        true
+    }
+
+    /// Handle keyboard navigation of a focussed \<select\> element
+    pub(crate) fn handle_select_keypress<F: FnMut(DomEvent)>(
+        &mut self,
+        select_id: usize,
+        event: &BlitzKeyEvent,
+        mut dispatch_event: F,
+    ) {
+        if self.is_disabled(select_id) {
+            return;
+        }
+        let is_open = self.open_select_id == Some(select_id);
+        let visible_rows = self.visible_option_rows(select_id);
+        let Some(data) = self.select_data_mut(select_id) else {
+            return;
+        };
+
+        // The option that navigation is relative to
+        let current = match is_open {
+            true => data.highlighted,
+            false => data.highlighted.or_else(|| data.selected_index()),
+        };
+        let enabled = |idx: &usize| !data.options[*idx].disabled;
+        let len = data.options.len();
+
+        let target = match &event.key {
+            Key::ArrowDown if event.modifiers.contains(Modifiers::ALT) && !is_open => {
+                self.open_select_popup(select_id);
+                return;
+            }
+            Key::ArrowDown => match current {
+                Some(current) => (current + 1..len).find(enabled),
+                None => (0..len).find(enabled),
+            },
+            Key::ArrowUp => match current {
+                Some(current) => (0..current).rev().find(enabled),
+                None => (0..len).rev().find(enabled),
+            },
+            Key::Home => (0..len).find(enabled),
+            Key::End => (0..len).rev().find(enabled),
+            Key::Enter if is_open => {
+                if let Some(highlighted) = data.highlighted {
+                    self.user_select_option(select_id, highlighted, false, &mut dispatch_event);
+                }
+                self.close_select_popup(select_id);
+                return;
+            }
+            Key::Character(c) if c == " " && data.is_drop_down() => {
+                match is_open {
+                    true => {
+                        if let Some(highlighted) = data.highlighted {
+                            self.user_select_option(
+                                select_id,
+                                highlighted,
+                                false,
+                                &mut dispatch_event,
+                            );
+                        }
+                        self.close_select_popup(select_id);
+                    }
+                    false => self.open_select_popup(select_id),
+                }
+                return;
+            }
+            Key::Escape if is_open => {
+                self.close_select_popup(select_id);
+                return;
+            }
+            _ => return,
+        };
+
+        let Some(target) = target else {
+            return;
+        };
+        if is_open {
+            data.highlighted = Some(target);
+            if let Some(visible_rows) = visible_rows {
+                scroll_to_option(data, target, visible_rows);
+            }
+            self.shell_provider.request_redraw();
+        } else {
+            self.user_select_option(select_id, target, false, &mut dispatch_event);
+        }
+    }
+}
+
+/// Scroll a list box (or drop-down popup) which shows `visible_rows` options at once so that
+/// the option at `option_idx` is visible
+fn scroll_to_option(data: &mut SelectData, option_idx: usize, visible_rows: usize) {
+    if option_idx < data.scroll_index {
+        data.scroll_index = option_idx;
+    } else if option_idx >= data.scroll_index + visible_rows {
+        data.scroll_index = option_idx + 1 - visible_rows;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_popup_option_at() {
+        let popup = SelectPopup {
+            select_id: 0,
+            x: 10.0,
+            y: 20.0,
+            width: 100.0,
+            row_height: 20.0,
+            row_count: 3,
+            first_option: 0,
+        };
+        assert_eq!(popup.option_at(15.0, 25.0), Some(0));
+        assert_eq!(popup.option_at(15.0, 65.0), Some(2));
+        assert_eq!(popup.option_at(15.0, 85.0), None);
+        assert_eq!(popup.option_at(5.0, 25.0), None);
+
+        // Scrolled popups map rows to later options
+        let popup = SelectPopup {
+            first_option: 5,
+            ..popup
+        };
+        assert_eq!(popup.option_at(15.0, 45.0), Some(6));
+    }
+}
@@ -0,0 +1,187 @@
+//! Shadow DOM: scoped stylesheets and slot assignment for shadow trees
+//!
+//! Shadow roots are stored in the node slab as [`NodeData::ShadowRoot`] nodes. A shadow root's
+//! `parent` is its host (so that events bubble out of the shadow tree), but it is *not* included
+//! in the host's `children`. Instead the host's [`ElementData::shadow_root`] points to it.
+//!
+//! Styling and layout both operate on the flat tree (see [`Node::flat_tree_children`]).
+
+use std::ops::Bound;
+
+use markup5ever::local_name;
+use style::invalidation::element::restyle_hints::RestyleHint;
+use style::stylesheets::DocumentStyleSheet;
+
+use crate::layout::damage::ALL_DAMAGE;
+use crate::traversal::TreeTraverser;
+use crate::{BaseDocument, Node, NodeData};
+
+#[cfg(doc)]
+use crate::ElementData;
+
+impl BaseDocument {
+    /// The ID of the shadow root attached to `host_id` (if any)
+    pub fn shadow_root(&self, host_id: usize) -> Option<usize> {
+        self.nodes.get(host_id)?.shadow_root_id()
+    }
+
+    /// Add a stylesheet belonging to a `<style>` or `<link>` node within a shadow tree to that
+    /// shadow root's scoped styles. The stylesheet will only apply to the shadow tree.
+    pub(crate) fn add_scoped_stylesheet_for_node(
+        &mut self,
+        shadow_root_id: usize,
+        stylesheet: DocumentStyleSheet,
+        old: Option<DocumentStyleSheet>,
+        node_id: usize,
+    ) {
+        // Keep sheets within the shadow tree ordered by node id (as we do for document-level sheets)
+        let insertion_point = self
+            .nodes_to_stylesheet
+            .range((Bound::Excluded(node_id), Bound::Unbounded))
+            .find(|(id, _)| self.scoped_stylesheets.get(id) == Some(&shadow_root_id))
+            .map(|(_, sheet)| sheet.clone());
+
+        self.scoped_stylesheets.insert(node_id, shadow_root_id);
+
+        let guard = self.guard.read();
+        let Some(data) = self.nodes[shadow_root_id].shadow_root_data_mut() else {
+            return;
+        };
+        let stylesheets = &mut data.author_styles.stylesheets;
+        if let Some(old) = old {
+            stylesheets.remove_stylesheet(None, old, &guard);
+        }
+        match insertion_point {
+            Some(insertion_point) => {
+                stylesheets.insert_stylesheet_before(None, stylesheet, insertion_point, &guard)
+            }
+            None => stylesheets.append_stylesheet(None, stylesheet, &guard),
+        }
+    }
+
+    /// Remove the stylesheet associated with a `<style>` or `<link>` node from whichever
+    /// set of styles (document or shadow root) it was added to.
+    pub(crate) fn remove_stylesheet_for_node(
+        &mut self,
+        stylesheet: DocumentStyleSheet,
+        node_id: usize,
+    ) {
+        self.nodes_to_stylesheet.remove(&node_id);
+
+        let guard = self.guard.read();
+        match self.scoped_stylesheets.remove(&node_id) {
+            Some(shadow_root_id) => {
+                if let Some(data) = self
+                    .nodes
+                    .get_mut(shadow_root_id)
+                    .and_then(|node| node.shadow_root_data_mut())
+                {
+                    data.author_styles
+                        .stylesheets
+                        .remove_stylesheet(None, stylesheet, &guard);
+                }
+            }
+            None => self.stylist.remove_stylesheet(stylesheet, &guard),
+        }
+    }
+
+    /// Rebuild the cascade data for any shadow roots whose stylesheets have changed,
+    /// and restyle their hosts.
+    pub(crate) fn flush_shadow_root_styles(&mut self) {
+        let guard = self.guard.read();
+        let mut dirty_hosts = Vec::new();
+        for (_, node) in self.nodes.iter_mut() {
+            let Some(data) = node.shadow_root_data_mut() else {
+                continue;
+            };
+            if !data.author_styles.stylesheets.dirty() {
+                continue;
+            }
+            data.author_styles.flush::<&Node>(&mut self.stylist, &guard);
+            dirty_hosts.push(data.host);
+        }
+        drop(guard);
+
+        for host_id in dirty_hosts {
+            if let Some(host) = self.nodes.get(host_id) {
+                host.set_restyle_hint(RestyleHint::restyle_subtree());
+            }
+        }
+    }
+
+    /// Recompute the assigned nodes of every `<slot>` element in every shadow tree
+    pub fn assign_slottables(&mut self) {
+        let shadow_root_ids: Vec<usize> = self
+            .nodes
+            .iter()
+            .filter(|(_, node)| node.is_shadow_root())
+            .map(|(id, _)| id)
+            .collect();
+        for shadow_root_id in shadow_root_ids {
+            self.assign_slottables_for_tree(shadow_root_id);
+        }
+    }
+
+    /// <https://dom.spec.whatwg.org/#assign-slotables-for-a-tree>
+    fn assign_slottables_for_tree(&mut self, shadow_root_id: usize) {
+        let Some(host_id) = self.nodes[shadow_root_id]
+            .shadow_root_data()
+            .map(|data| data.host)
+        else {
+            return;
+        };
+
+        // Slots in tree order. Nested shadow trees are not traversed as shadow roots
+        // are not included in their host's children.
+        let slots: Vec<usize> = TreeTraverser::new_with_root(self, shadow_root_id)
+            .filter(|id| {
+                self.nodes[*id]
+                    .data
+                    .is_element_with_tag_name(&local_name!("slot"))
+            })
+            .collect();
+
+        // Find the first slot (in tree order) whose name matches each slottable's slot attribute.
+        let host_children = self.nodes[host_id].children.clone();
+        let mut assignments: Vec<(usize, Vec<usize>)> =
+            slots.iter().map(|slot_id| (*slot_id, Vec::new())).collect();
+        for child_id in host_children.iter().copied() {
+            let child = &self.nodes[child_id];
+            if !matches!(child.data, NodeData::Element(_) | NodeData::Text(_)) {
+                continue;
+            }
+            let name = child.attr(local_name!("slot")).unwrap_or("");
+            let slot = assignments.iter_mut().find(|(slot_id, _)| {
+                self.nodes[*slot_id].attr(local_name!("name")).unwrap_or("") == name
+            });
+            if let Some((_, assigned)) = slot {
+                assigned.push(child_id);
+            }
+        }
+
+        let mut changed = false;
+        for child_id in host_children.iter().copied() {
+            self.nodes[child_id].assigned_slot = None;
+        }
+        for (slot_id, assigned) in assignments {
+            for child_id in assigned.iter().copied() {
+                self.nodes[child_id].assigned_slot = Some(slot_id);
+            }
+
+            let slot = &mut self.nodes[slot_id];
+            let element = slot.element_data_mut().unwrap();
+            if element.assigned_nodes != assigned {
+                element.assigned_nodes = assigned;
+                slot.insert_damage(ALL_DAMAGE);
+                slot.set_restyle_hint(RestyleHint::restyle_subtree());
+                changed = true;
+            }
+        }
+
+        if changed {
+            let host = &mut self.nodes[host_id];
+            host.insert_damage(ALL_DAMAGE);
+            host.set_restyle_hint(RestyleHint::restyle_subtree());
+        }
+    }
+}
@@ -0,0 +1,139 @@
+//! Stacking contexts and paint order
+//!
+//! Positioned boxes and boxes which establish a stacking context are not painted in tree order with
+//! their siblings. Instead they are "hoisted" to the nearest ancestor stacking context, which paints
+//! them (and their descendants) atomically, ordered by z-index, either before (negative z-index) or
+//! after (zero, auto or positive z-index) its in-flow content.
+//!
+//! As a simplification, positioned boxes with `z-index: auto` are treated as if they established a
+//! stacking context, and boxes which clip their overflow also act as a boundary for hoisting so that
+//! their descendants remain clipped. The roots of vertical writing mode flows are also boundaries, as
+//! their descendants are positioned in the flow's coordinate space.
+//!
+//! See: <https://www.w3.org/TR/CSS22/zindex.html>
+
+use style::computed_values::position::T as Position;
+use style::values::computed::Overflow;
+use style::values::specified::box_::DisplayInside;
+
+use crate::{BaseDocument, Node};
+
+/// The descendants which are painted by a stacking context, in paint order
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct StackingContext {
+    /// Descendants with a negative z-index. Painted after the root's background and borders but
+    /// before its in-flow content.
+    pub negative_z_descendants: Vec<usize>,
+    /// Descendants with a zero, auto or positive z-index. Painted after the root's in-flow content.
+    pub positive_z_descendants: Vec<usize>,
+}
+
+impl StackingContext {
+    /// All descendants of the stacking context, in paint order
+    pub fn descendants(&self) -> impl DoubleEndedIterator<Item = usize> + '_ {
+        self.negative_z_descendants
+            .iter()
+            .chain(self.positive_z_descendants.iter())
+            .copied()
+    }
+}
+
+impl Node {
+    /// Whether the node is painted by its nearest ancestor stacking context (rather than in tree
+    /// order with its siblings)
+    pub fn is_hoisted_for_paint(&self) -> bool {
+        let Some(style) = self.primary_styles() else {
+            return false;
+        };
+        if style.clone_position() != Position::Static {
+            return true;
+        }
+
+        // Non-positioned boxes only establish stacking contexts if they are flex or grid items with
+        // a z-index, or have an effect which requires compositing
+        let is_flex_or_grid_item = self
+            .layout_parent
+            .get()
+            .and_then(|id| self.with(id).primary_styles())
+            .is_some_and(|parent_style| {
+                matches!(
+                    parent_style.clone_display().inside(),
+                    DisplayInside::Flex | DisplayInside::Grid
+                )
+            });
+        (is_flex_or_grid_item && !style.clone_z_index().is_auto())
+            || style.get_effects().opacity < 1.0
+            || !style.get_box().transform.0.is_empty()
+    }
+
+    /// Whether hoisted descendants of the node are painted by the node rather than by an ancestor
+    fn is_hoisting_boundary(&self) -> bool {
+        let clips_overflow = self.primary_styles().is_some_and(|style| {
+            style.clone_overflow_x() != Overflow::Visible
+                || style.clone_overflow_y() != Overflow::Visible
+        });
+        clips_overflow || self.vertical_flow_root().is_some()
+    }
+}
+
+impl BaseDocument {
+    /// Compute the paint children and (where the node is the root of a stacking context) the
+    /// stacking context of a node. Must be called after the node's layout children are final.
+    pub(crate) fn resolve_paint_order(&mut self, node_id: usize) {
+        let node = &self.nodes[node_id];
+        let paint_children: Vec<usize> = node
+            .layout_children
+            .borrow()
+            .iter()
+            .flatten()
+            .copied()
+            .filter(|&child_id| !self.nodes[child_id].is_hoisted_for_paint())
+            .collect();
+
+        let is_stacking_root = node_id == self.root_element().id
+            || node.is_hoisted_for_paint()
+            || node.is_hoisting_boundary();
+        let stacking_context = is_stacking_root.then(|| {
+            let mut descendants = Vec::new();
+            self.collect_hoisted_descendants(node_id, &mut descendants);
+
+            // Sort by z-index. The sort is stable, so boxes with the same z-index remain in tree order.
+            descendants.sort_by_key(|(_, z_index)| *z_index);
+            let (negative, positive): (Vec<_>, Vec<_>) = descendants
+                .into_iter()
+                .partition(|(_, z_index)| *z_index < 0);
+            Box::new(StackingContext {
+                negative_z_descendants: negative.into_iter().map(|(id, _)| id).collect(),
+                positive_z_descendants: positive.into_iter().map(|(id, _)| id).collect(),
+            })
+        });
+
+        // The paint order isn't part of a node's damage, so changes to it are recorded separately
+        let node = &mut self.nodes[node_id];
+        let paint_order_changed = node.paint_children.get_mut().as_ref() != Some(&paint_children)
+            || node.stacking_context != stacking_context;
+        *node.paint_children.get_mut() = Some(paint_children);
+        node.stacking_context = stacking_context;
+        if paint_order_changed {
+            self.paint_damage.insert(node_id);
+        }
+    }
+
+    /// Collect the hoisted descendants of a node (and their z-index), stopping at the boundaries of
+    /// nested stacking contexts
+    fn collect_hoisted_descendants(&self, node_id: usize, descendants: &mut Vec<(usize, i32)>) {
+        for &child_id in self.nodes[node_id]
+            .layout_children
+            .borrow()
+            .iter()
+            .flatten()
+        {
+            let child = &self.nodes[child_id];
+            if child.is_hoisted_for_paint() {
+                descendants.push((child_id, child.z_index()));
+            } else if !child.is_hoisting_boundary() {
+                self.collect_hoisted_descendants(child_id, descendants);
+            }
+        }
+    }
+}
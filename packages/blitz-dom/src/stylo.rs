@@ -9,7 +9,7 @@ use crate::layout::damage::compute_layout_damage;
 use crate::node::Node;
 use crate::node::NodeData;
 use atomic_refcell::{AtomicRef, AtomicRefMut};
-use markup5ever::{LocalName, LocalNameStaticSet, Namespace, NamespaceStaticSet, local_name};
+use markup5ever::{LocalName, LocalNameStaticSet, Namespace, NamespaceStaticSet, local_name, ns};
 use selectors::{
     Element, OpaqueElement,
     attr::{AttrSelectorOperation, AttrSelectorOperator, NamespaceConstraint},
@@ -56,6 +56,9 @@ impl crate::document::BaseDocument {
     pub fn resolve_stylist(&mut self, now: f64) {
         style::thread_state::enter(ThreadState::LAYOUT);
 
+        // Rebuild scoped styles for shadow roots before matching against them
+        self.flush_shadow_root_styles();
+
         let guard = &self.guard;
         let guards = StylesheetGuards {
             author: &guard.read(),
@@ -192,14 +195,17 @@ impl<'a> TShadowRoot for BlitzNode<'a> {
     }
 
     fn host(&self) -> <Self::ConcreteNode as TNode>::ConcreteElement {
-        todo!("Shadow roots not implemented")
+        let host_id = self.shadow_root_data().expect("Not a shadow root").host;
+        self.with(host_id)
     }
 
     fn style_data<'b>(&self) -> Option<&'b style::stylist::CascadeData>
     where
         Self: 'b,
     {
-        todo!("Shadow roots not implemented")
+        let node: &'b Node = self;
+        let data: &'b style::stylist::CascadeData = &node.shadow_root_data()?.author_styles.data;
+        Some(data)
     }
 }
 
@@ -210,6 +216,11 @@ impl<'a> TNode for BlitzNode<'a> {
     type ConcreteShadowRoot = BlitzNode<'a>;
 
     fn parent_node(&self) -> Option<Self> {
+        // Shadow roots are parented to their host for event bubbling, but are not
+        // children of their host in the DOM
+        if self.is_shadow_root() {
+            return None;
+        }
         self.parent.map(|id| self.with(id))
     }
 
@@ -237,12 +248,17 @@ impl<'a> TNode for BlitzNode<'a> {
         true
     }
 
-    // I think this is the same as parent_node only in the cases when the direct parent is not a real element, forcing us
-    // to travel upwards
-    //
-    // For the sake of this demo, we're just going to return the parent node ann
+    // The parent in the flat tree: slotted nodes are children of their assigned slot, and
+    // the children of a shadow root are children of the shadow host.
     fn traversal_parent(&self) -> Option<Self::ConcreteElement> {
-        self.parent_node().and_then(|node| node.as_element())
+        if let Some(slot_id) = self.assigned_slot {
+            return Some(self.with(slot_id));
+        }
+        let parent = self.parent_node_or_shadow_root()?;
+        match parent.shadow_root_data() {
+            Some(data) => Some(self.with(data.host)),
+            None => parent.as_element(),
+        }
     }
 
     fn opaque(&self) -> OpaqueNode {
@@ -268,8 +284,10 @@ impl<'a> TNode for BlitzNode<'a> {
     }
 
     fn as_shadow_root(&self) -> Option<Self::ConcreteShadowRoot> {
-        // TODO: implement shadow DOM
-        None
+        match self.data {
+            NodeData::ShadowRoot(_) => Some(self),
+            _ => None,
+        }
     }
 }
 
@@ -284,15 +302,21 @@ impl selectors::Element for BlitzNode<'_> {
     }
 
     fn parent_element(&self) -> Option<Self> {
-        TElement::traversal_parent(self)
+        self.parent_node().and_then(|node| node.as_element())
     }
 
     fn parent_node_is_shadow_root(&self) -> bool {
-        false
+        self.parent_node_or_shadow_root()
+            .is_some_and(|parent| parent.is_shadow_root())
     }
 
     fn containing_shadow_host(&self) -> Option<Self> {
-        None
+        let shadow_root_id = self.containing_shadow_root_id()?;
+        Some(TShadowRoot::host(&self.with(shadow_root_id)))
+    }
+
+    fn assigned_slot(&self) -> Option<Self> {
+        self.assigned_slot.map(|slot_id| self.with(slot_id))
     }
 
     fn is_pseudo_element(&self) -> bool {
@@ -406,18 +430,22 @@ impl selectors::Element for BlitzNode<'_> {
                 .downcast_element()
                 .and_then(|elem| elem.checkbox_input_checked())
                 .unwrap_or(false),
-            NonTSPseudoClass::Valid => false,
-            NonTSPseudoClass::Invalid => false,
+            NonTSPseudoClass::Valid => self.element_state.contains(ElementState::VALID),
+            NonTSPseudoClass::Invalid => self.element_state.contains(ElementState::INVALID),
             NonTSPseudoClass::Defined => false,
-            NonTSPseudoClass::Disabled => false,
-            NonTSPseudoClass::Enabled => false,
+            NonTSPseudoClass::Disabled => self.element_state.contains(ElementState::DISABLED),
+            NonTSPseudoClass::Enabled => self.element_state.contains(ElementState::ENABLED),
             NonTSPseudoClass::Focus => self.element_state.contains(ElementState::FOCUS),
-            NonTSPseudoClass::FocusWithin => false,
-            NonTSPseudoClass::FocusVisible => false,
+            NonTSPseudoClass::FocusWithin => {
+                self.element_state.contains(ElementState::FOCUS_WITHIN)
+            }
+            NonTSPseudoClass::FocusVisible => self.element_state.contains(ElementState::FOCUSRING),
             NonTSPseudoClass::Fullscreen => false,
             NonTSPseudoClass::Hover => self.element_state.contains(ElementState::HOVER),
-            NonTSPseudoClass::Indeterminate => false,
-            NonTSPseudoClass::Lang(_) => false,
+            NonTSPseudoClass::Indeterminate => {
+                self.element_state.contains(ElementState::INDETERMINATE)
+            }
+            NonTSPseudoClass::Lang(ref lang) => self.match_element_lang(None, lang),
             NonTSPseudoClass::CustomState(_) => false,
             NonTSPseudoClass::Link => self
                 .data
@@ -427,26 +455,34 @@ impl selectors::Element for BlitzNode<'_> {
                         && elem.attr(local_name!("href")).is_some()
                 })
                 .unwrap_or(false),
-            NonTSPseudoClass::PlaceholderShown => false,
-            NonTSPseudoClass::ReadWrite => false,
-            NonTSPseudoClass::ReadOnly => false,
+            NonTSPseudoClass::PlaceholderShown => {
+                self.element_state.contains(ElementState::PLACEHOLDER_SHOWN)
+            }
+            NonTSPseudoClass::ReadWrite => self.element_state.contains(ElementState::READWRITE),
+            NonTSPseudoClass::ReadOnly => !self.element_state.contains(ElementState::READWRITE),
             NonTSPseudoClass::ServoNonZeroBorder => false,
-            NonTSPseudoClass::Target => false,
+            NonTSPseudoClass::Target => self.element_state.contains(ElementState::URLTARGET),
             NonTSPseudoClass::Visited => false,
             NonTSPseudoClass::Autofill => false,
             NonTSPseudoClass::Default => false,
 
-            NonTSPseudoClass::InRange => false,
+            NonTSPseudoClass::InRange => self.element_state.contains(ElementState::INRANGE),
             NonTSPseudoClass::Modal => false,
-            NonTSPseudoClass::Optional => false,
-            NonTSPseudoClass::OutOfRange => false,
+            NonTSPseudoClass::Optional => self.element_state.contains(ElementState::OPTIONAL),
+            NonTSPseudoClass::OutOfRange => self.element_state.contains(ElementState::OUTOFRANGE),
             NonTSPseudoClass::PopoverOpen => false,
-            NonTSPseudoClass::Required => false,
-            NonTSPseudoClass::UserInvalid => false,
-            NonTSPseudoClass::UserValid => false,
-            NonTSPseudoClass::MozMeterOptimum => false,
-            NonTSPseudoClass::MozMeterSubOptimum => false,
-            NonTSPseudoClass::MozMeterSubSubOptimum => false,
+            NonTSPseudoClass::Required => self.element_state.contains(ElementState::REQUIRED),
+            NonTSPseudoClass::UserInvalid => {
+                self.element_state.contains(ElementState::USER_INVALID)
+            }
+            NonTSPseudoClass::UserValid => self.element_state.contains(ElementState::USER_VALID),
+            NonTSPseudoClass::MozMeterOptimum => self.element_state.contains(ElementState::OPTIMUM),
+            NonTSPseudoClass::MozMeterSubOptimum => {
+                self.element_state.contains(ElementState::SUB_OPTIMUM)
+            }
+            NonTSPseudoClass::MozMeterSubSubOptimum => {
+                self.element_state.contains(ElementState::SUB_SUB_OPTIMUM)
+            }
         }
     }
 
@@ -482,7 +518,8 @@ impl selectors::Element for BlitzNode<'_> {
     }
 
     fn is_html_slot_element(&self) -> bool {
-        false
+        self.element_data()
+            .is_some_and(|el| el.name.local == local_name!("slot") && el.name.ns == ns!(html))
     }
 
     fn has_id(
@@ -531,8 +568,8 @@ impl selectors::Element for BlitzNode<'_> {
     }
 
     fn is_root(&self) -> bool {
-        self.parent_node()
-            .and_then(|parent| parent.parent_node())
+        self.parent_node_or_shadow_root()
+            .and_then(|parent| parent.parent_node_or_shadow_root())
             .is_none()
     }
 
@@ -564,8 +601,8 @@ impl<'a> TElement for BlitzNode<'a> {
         // We cannot currently implement this as we are using the NodeId as the OpaqueElement,
         // and need a reference to the Slab to convert it back into an Element
         //
-        // Luckily it is only needed for shadow dom.
-        todo!();
+        // This means that prelude-less @scope rules within shadow trees are not scoped.
+        None
     }
 
     fn traversal_children(&self) -> style::dom::LayoutIterator<Self::TraversalChildrenIterator> {
@@ -759,23 +796,30 @@ impl<'a> TElement for BlitzNode<'a> {
     }
 
     fn shadow_root(&self) -> Option<<Self::ConcreteNode as TNode>::ConcreteShadowRoot> {
-        None
+        self.shadow_root_id().map(|id| self.with(id))
     }
 
     fn containing_shadow(&self) -> Option<<Self::ConcreteNode as TNode>::ConcreteShadowRoot> {
-        None
+        self.containing_shadow_root_id().map(|id| self.with(id))
     }
 
     fn lang_attr(&self) -> Option<style::selector_parser::AttrValue> {
-        None
+        self.attr(local_name!("lang")).map(AtomString::from)
     }
 
     fn match_element_lang(
         &self,
-        _override_lang: Option<Option<style::selector_parser::AttrValue>>,
-        _value: &style::selector_parser::Lang,
+        override_lang: Option<Option<style::selector_parser::AttrValue>>,
+        value: &style::selector_parser::Lang,
     ) -> bool {
-        false
+        // `override_lang` is the element's own `lang` attribute from a snapshot. If it had
+        // none then the language is inherited from its ancestors as usual.
+        let lang = match override_lang {
+            Some(Some(ref lang)) => Some(lang.as_ref()),
+            Some(None) => self.parent.and_then(|id| self.with(id).language()),
+            None => self.language(),
+        };
+        lang.is_some_and(|lang| lang_matches(lang, value))
     }
 
     fn is_html_document_body_element(&self) -> bool {
@@ -1010,7 +1054,7 @@ impl<'a> Iterator for Traverser<'a> {
     type Item = BlitzNode<'a>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        let node_id = self.parent.children.get(self.child_index)?;
+        let node_id = self.parent.flat_tree_children().get(self.child_index)?;
         let node = self.parent.with(*node_id);
 
         self.child_index += 1;
@@ -1088,30 +1132,83 @@ where
     }
 }
 
-#[test]
-fn assert_size_of_equals() {
-    // use std::mem;
-
-    // fn assert_layout<E>() {
-    //     assert_eq!(
-    //         mem::size_of::<SharingCache<E>>(),
-    //         mem::size_of::<TypelessSharingCache>()
-    //     );
-    //     assert_eq!(
-    //         mem::align_of::<SharingCache<E>>(),
-    //         mem::align_of::<TypelessSharingCache>()
-    //     );
-    // }
+/// Match a language tag against a `:lang()` language range using "extended filtering"
+///
+/// <https://www.rfc-editor.org/rfc/rfc4647#section-3.3.2>
+fn lang_matches(tag: &str, range: &str) -> bool {
+    let mut tag_subtags = tag.split('-');
+    let mut range_subtags = range.split('-');
+
+    let (Some(tag_first), Some(range_first)) = (tag_subtags.next(), range_subtags.next()) else {
+        return false;
+    };
+    if range_first != "*" && !range_first.eq_ignore_ascii_case(tag_first) {
+        return false;
+    }
+
+    let mut tag_subtag = tag_subtags.next();
+    for range_subtag in range_subtags {
+        if range_subtag == "*" {
+            continue;
+        }
+        loop {
+            match tag_subtag {
+                None => return false,
+                Some(subtag) if subtag.eq_ignore_ascii_case(range_subtag) => {
+                    tag_subtag = tag_subtags.next();
+                    break;
+                }
+                // Singletons (e.g. "x" for private use) cannot be skipped over
+                Some(subtag) if subtag.len() == 1 => return false,
+                Some(_) => tag_subtag = tag_subtags.next(),
+            }
+        }
+    }
 
-    // let size = mem::size_of::<StyleSharingCandidate<BlitzNode>>();
-    // dbg!(size);
+    true
 }
 
-#[test]
-fn parse_inline() {
-    // let attrs = style::attr::AttrValue::from_serialized_tokenlist(
-    //     r#"visibility: hidden; left: 1306.5px; top: 50px; display: none;"#.to_string(),
-    // );
-
-    // let val = CSSInlineStyleDeclaration();
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lang_extended_filtering() {
+        assert!(lang_matches("en", "en"));
+        assert!(lang_matches("en-US", "en"));
+        assert!(lang_matches("EN-us", "en-US"));
+        assert!(lang_matches("de-Latn-DE", "de-DE"));
+        assert!(lang_matches("de-DE", "*-DE"));
+        assert!(!lang_matches("en", "en-US"));
+        assert!(!lang_matches("fr", "en"));
+        assert!(!lang_matches("de-x-DE", "de-DE"));
+    }
+
+    #[test]
+    fn assert_size_of_equals() {
+        // use std::mem;
+
+        // fn assert_layout<E>() {
+        //     assert_eq!(
+        //         mem::size_of::<SharingCache<E>>(),
+        //         mem::size_of::<TypelessSharingCache>()
+        //     );
+        //     assert_eq!(
+        //         mem::align_of::<SharingCache<E>>(),
+        //         mem::align_of::<TypelessSharingCache>()
+        //     );
+        // }
+
+        // let size = mem::size_of::<StyleSharingCandidate<BlitzNode>>();
+        // dbg!(size);
+    }
+
+    #[test]
+    fn parse_inline() {
+        // let attrs = style::attr::AttrValue::from_serialized_tokenlist(
+        //     r#"visibility: hidden; left: 1306.5px; top: 50px; display: none;"#.to_string(),
+        // );
+
+        // let val = CSSInlineStyleDeclaration();
+    }
 }
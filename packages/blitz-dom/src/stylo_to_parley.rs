@@ -7,6 +7,10 @@ use crate::node::TextBrush;
 
 // Module of type aliases so we can refer to stylo types with nicer names
 pub(crate) mod stylo {
+    pub(crate) use style::computed_values::direction::T as Direction;
+    pub(crate) use style::computed_values::hyphens::T as Hyphens;
+    pub(crate) use style::computed_values::text_wrap_mode::T as TextWrapMode;
+    pub(crate) use style::computed_values::unicode_bidi::T as UnicodeBidi;
     pub(crate) use style::computed_values::white_space_collapse::T as WhiteSpaceCollapse;
     pub(crate) use style::properties::ComputedValues;
     pub(crate) use style::values::computed::OverflowWrap;
@@ -96,8 +100,10 @@ pub(crate) fn white_space_collapse(input: stylo::WhiteSpaceCollapse) -> parley::
         stylo::WhiteSpaceCollapse::Collapse => parley::WhiteSpaceCollapse::Collapse,
         stylo::WhiteSpaceCollapse::Preserve => parley::WhiteSpaceCollapse::Preserve,
 
-        // TODO: Implement PreserveBreaks and BreakSpaces modes
-        stylo::WhiteSpaceCollapse::PreserveBreaks => parley::WhiteSpaceCollapse::Preserve,
+        // Parley doesn't support these modes directly. Segment breaks (for PreserveBreaks) and break
+        // opportunities after spaces (for BreakSpaces) are handled when text is pushed into the layout
+        // (see `layout::construct::push_text`)
+        stylo::WhiteSpaceCollapse::PreserveBreaks => parley::WhiteSpaceCollapse::Collapse,
         stylo::WhiteSpaceCollapse::BreakSpaces => parley::WhiteSpaceCollapse::Preserve,
     }
 }
@@ -170,6 +176,10 @@ pub(crate) fn style(
         stylo::OverflowWrap::BreakWord => parley::OverflowWrap::BreakWord,
         stylo::OverflowWrap::Anywhere => parley::OverflowWrap::Anywhere,
     };
+    let text_wrap_mode = match itext_styles.text_wrap_mode {
+        stylo::TextWrapMode::Wrap => parley::TextWrapMode::Wrap,
+        stylo::TextWrapMode::Nowrap => parley::TextWrapMode::NoWrap,
+    };
 
     parley::TextStyle {
         // font_stack: parley::FontStack::Single(FontFamily::Generic(GenericFamily::SystemUi)),
@@ -186,6 +196,7 @@ pub(crate) fn style(
         letter_spacing,
         overflow_wrap,
         word_break,
+        text_wrap_mode,
 
         // Contains NodeId
         brush: TextBrush::from_id(span_id),
@@ -133,6 +133,7 @@ impl BaseDocument {
         self.nodes[node_id].children = children;
     }
 
+    /// Visit the node and all of its descendants (including those in attached shadow trees)
     pub fn iter_subtree_mut(
         &mut self,
         node_id: usize,
@@ -151,9 +152,16 @@ impl BaseDocument {
                 iter_subtree_mut_inner(doc, child_id, cb);
             }
             doc.nodes[node_id].children = children;
+
+            if let Some(shadow_root_id) = doc.nodes[node_id].shadow_root_id() {
+                cb(shadow_root_id, doc);
+                iter_subtree_mut_inner(doc, shadow_root_id, cb);
+            }
         }
     }
 
+    /// Visit the node's ::before pseudo-element, its children in the flat tree
+    /// (see [`Node::flat_tree_children`]), and its ::after pseudo-element
     pub fn iter_children_and_pseudos_mut(
         &mut self,
         node_id: usize,
@@ -165,7 +173,10 @@ impl BaseDocument {
         }
         self.nodes[node_id].before = before;
 
-        self.iter_children_mut(node_id, &mut cb);
+        let children = self.nodes[node_id].flat_tree_children().to_vec();
+        for child_id in children {
+            cb(child_id, self);
+        }
 
         let after = self.nodes[node_id].after.take();
         if let Some(after_node_id) = after {
@@ -19,6 +19,7 @@ pub(crate) static FONT_DB: LazyLock<Arc<fontdb::Database>> = LazyLock::new(|| {
 pub enum ImageType {
     Image,
     Background(usize),
+    ListStyleImage,
 }
 
 /// A point
@@ -69,6 +70,8 @@ pub fn walk_tree(indent: usize, node: &Node) {
 
         NodeData::Comment => println!("<!-- COMMENT {id} -->"),
 
+        NodeData::ShadowRoot(data) => println!("#shadow-root ({:?}) {id}", data.mode),
+
         NodeData::AnonymousBlock(_) => println!("{id} AnonymousBlock"),
 
         NodeData::Element(data) => {
@@ -89,6 +92,10 @@ pub fn walk_tree(indent: usize, node: &Node) {
           // NodeData::ProcessingInstruction { .. } => unreachable!(),
     }
 
+    if let Some(shadow_root_id) = node.shadow_root_id() {
+        walk_tree(indent + 2, node.with(shadow_root_id));
+    }
+
     if !node.children.is_empty() {
         for child_id in node.children.iter() {
             walk_tree(indent + 2, node.with(*child_id));
@@ -0,0 +1,616 @@
+//! Form control state (`:disabled`, `:required`, `:read-write`, `:placeholder-shown`, etc)
+//! and constraint validation.
+//!
+//! The state is stored in each element's [`ElementState`] so that Stylo can invalidate
+//! selectors that depend on it when it changes.
+//!
+//! <https://html.spec.whatwg.org/multipage/form-control-infrastructure.html#constraints>
+
+use std::cell::OnceCell;
+use std::collections::{HashMap, HashSet};
+
+use markup5ever::{LocalName, local_name};
+use style_dom::ElementState;
+
+use crate::range::MeterValues;
+use crate::traversal::{AncestorTraverser, TreeTraverser};
+use crate::{BaseDocument, ElementData, Node};
+
+/// The subset of [`ElementState`] that is derived from form control attributes and values
+const FORM_CONTROL_STATES: ElementState = ElementState::ENABLED
+    .union(ElementState::DISABLED)
+    .union(ElementState::REQUIRED)
+    .union(ElementState::OPTIONAL)
+    .union(ElementState::READONLY)
+    .union(ElementState::READWRITE)
+    .union(ElementState::PLACEHOLDER_SHOWN)
+    .union(ElementState::VALID)
+    .union(ElementState::INVALID)
+    .union(ElementState::USER_VALID)
+    .union(ElementState::USER_INVALID)
+    .union(ElementState::INRANGE)
+    .union(ElementState::OUTOFRANGE)
+    .union(ElementState::INDETERMINATE)
+    .union(ElementState::OPTIMUM)
+    .union(ElementState::SUB_OPTIMUM)
+    .union(ElementState::SUB_SUB_OPTIMUM);
+
+/// The ways in which an element can fail to satisfy its constraints
+///
+/// <https://html.spec.whatwg.org/multipage/form-control-infrastructure.html#validitystate>
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ValidityState {
+    pub value_missing: bool,
+    pub type_mismatch: bool,
+    pub pattern_mismatch: bool,
+    pub too_long: bool,
+    pub too_short: bool,
+    pub range_underflow: bool,
+    pub range_overflow: bool,
+    pub bad_input: bool,
+}
+
+impl ValidityState {
+    /// Whether the element satisfies all of its constraints
+    pub fn valid(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// A group of radio buttons with the same form owner and name
+#[derive(Default)]
+struct RadioGroup {
+    members: Vec<usize>,
+    checked: bool,
+    required: bool,
+}
+
+/// The document's radio button groups, indexed by form owner and name.
+/// Built on first use so that it is shared by all controls validated at once.
+#[derive(Default)]
+struct RadioGroups(OnceCell<HashMap<(Option<usize>, String), RadioGroup>>);
+
+impl RadioGroups {
+    /// The group of the radio button `node_id`
+    fn get(&self, doc: &BaseDocument, node_id: usize) -> Option<&RadioGroup> {
+        let key = radio_group_key(doc, node_id)?;
+        let groups = self.0.get_or_init(|| {
+            let mut groups: HashMap<_, RadioGroup> = HashMap::new();
+            for (id, node) in doc.nodes.iter() {
+                let Some(key) = radio_group_key(doc, id) else {
+                    continue;
+                };
+                let element = node.element_data().unwrap();
+                let group = groups.entry(key).or_default();
+                group.members.push(id);
+                group.checked |= element.checkbox_input_checked().unwrap_or(false);
+                group.required |= element.has_attr(local_name!("required"));
+            }
+            groups
+        });
+        groups.get(&key)
+    }
+}
+
+impl BaseDocument {
+    /// The connected radio buttons in the same group as `node_id` (same form
+    /// owner and name), including `node_id` itself. Empty if `node_id` is not
+    /// a connected radio button with a non-empty name.
+    pub(crate) fn radio_group_members(&self, node_id: usize) -> Vec<usize> {
+        RadioGroups::default()
+            .get(self, node_id)
+            .map(|group| group.members.clone())
+            .unwrap_or_default()
+    }
+
+    /// Whether the element satisfies its constraints. Elements that are not
+    /// candidates for constraint validation are always valid.
+    ///
+    /// For `<form>` and `<fieldset>` elements this checks every associated control.
+    ///
+    /// <https://html.spec.whatwg.org/multipage/form-control-infrastructure.html#dom-cva-checkvalidity>
+    pub fn check_validity(&self, node_id: usize) -> bool {
+        self.check_validity_with(node_id, &RadioGroups::default())
+    }
+
+    fn check_validity_with(&self, node_id: usize, radio_groups: &RadioGroups) -> bool {
+        let Some(element) = self.nodes[node_id].element_data() else {
+            return true;
+        };
+        match element.name.local {
+            local_name!("form") => self
+                .controls_to_form
+                .iter()
+                .filter(|(_, form_id)| **form_id == node_id)
+                .all(|(control_id, _)| self.control_is_valid(*control_id, radio_groups)),
+            local_name!("fieldset") => TreeTraverser::new_with_root(self, node_id)
+                .skip(1)
+                .all(|control_id| self.control_is_valid(control_id, radio_groups)),
+            _ => self.control_is_valid(node_id, radio_groups),
+        }
+    }
+
+    /// The validity state of a form control
+    ///
+    /// <https://html.spec.whatwg.org/multipage/form-control-infrastructure.html#dom-cva-validity>
+    pub fn validity(&self, node_id: usize) -> ValidityState {
+        self.validity_with(node_id, &RadioGroups::default())
+    }
+
+    fn validity_with(&self, node_id: usize, radio_groups: &RadioGroups) -> ValidityState {
+        let node = &self.nodes[node_id];
+        let Some(element) = node.element_data() else {
+            return ValidityState::default();
+        };
+
+        match element.name.local {
+            local_name!("input") => self.input_validity(node, element, radio_groups),
+            local_name!("textarea") => {
+                let value = control_value(element);
+                let mut validity = ValidityState {
+                    value_missing: element.has_attr(local_name!("required")) && value.is_empty(),
+                    ..Default::default()
+                };
+                check_length(element, &value, &mut validity);
+                validity
+            }
+            local_name!("select") => ValidityState {
+                value_missing: element.has_attr(local_name!("required"))
+                    && self.select_value(node_id).is_empty(),
+                ..Default::default()
+            },
+            _ => ValidityState::default(),
+        }
+    }
+
+    /// Whether the element is "actually disabled"
+    ///
+    /// <https://html.spec.whatwg.org/multipage/semantics-other.html#concept-element-disabled>
+    pub fn is_disabled(&self, node_id: usize) -> bool {
+        let node = &self.nodes[node_id];
+        let Some(element) = node.element_data() else {
+            return false;
+        };
+        let has_disabled_attr = element.has_attr(local_name!("disabled"));
+
+        match element.name.local {
+            local_name!("button")
+            | local_name!("input")
+            | local_name!("select")
+            | local_name!("textarea")
+            | local_name!("fieldset") => has_disabled_attr || self.is_in_disabled_fieldset(node_id),
+            local_name!("optgroup") => has_disabled_attr,
+            local_name!("option") => {
+                has_disabled_attr
+                    || node.parent.is_some_and(|parent_id| {
+                        let parent = &self.nodes[parent_id];
+                        parent
+                            .data
+                            .is_element_with_tag_name(&local_name!("optgroup"))
+                            && parent.data.has_attr(local_name!("disabled"))
+                    })
+            }
+            _ => false,
+        }
+    }
+
+    /// Recompute the form control state of `node_id`, along with that of any elements
+    /// (radio buttons in the same group, `<form>`s, `<fieldset>`s, etc) whose state depends on it.
+    pub fn update_form_control_state(&mut self, node_id: usize) {
+        self.update_form_control_states([node_id]);
+    }
+
+    /// Recompute the form control state of the given elements, along with that of any
+    /// elements whose state depends on them.
+    pub(crate) fn update_form_control_states(&mut self, node_ids: impl IntoIterator<Item = usize>) {
+        let radio_groups = RadioGroups::default();
+
+        let mut changed = HashSet::new();
+        for node_id in node_ids {
+            let Some(element) = self.nodes.get(node_id).and_then(|node| node.element_data()) else {
+                continue;
+            };
+            changed.insert(node_id);
+
+            // These may disable their descendants or bar them from constraint validation
+            if matches!(
+                element.name.local,
+                local_name!("fieldset")
+                    | local_name!("legend")
+                    | local_name!("optgroup")
+                    | local_name!("datalist")
+            ) {
+                changed.extend(TreeTraverser::new_with_root(self, node_id).skip(1));
+            }
+
+            // Whether a radio button is checked or required affects its whole group
+            if let Some(group) = radio_groups.get(self, node_id) {
+                changed.extend(group.members.iter().copied());
+            }
+        }
+
+        // The validity of fieldsets, selects (and so forms) reflects that of their descendants
+        let mut dependents = HashSet::new();
+        for &node_id in &changed {
+            let ancestors = AncestorTraverser::new(self, node_id).filter(|id| {
+                let data = &self.nodes[*id].data;
+                data.is_element_with_tag_name(&local_name!("fieldset"))
+                    || data.is_element_with_tag_name(&local_name!("select"))
+            });
+            for id in std::iter::once(node_id).chain(ancestors) {
+                dependents.insert(id);
+                dependents.extend(self.controls_to_form.get(&id).copied());
+            }
+        }
+
+        let states: Vec<(usize, ElementState)> = dependents
+            .into_iter()
+            .filter(|id| has_form_control_state(&self.nodes[*id]))
+            .map(|id| (id, self.compute_form_control_state(id, &radio_groups)))
+            .collect();
+        for (node_id, state) in states {
+            self.set_form_control_state(node_id, state);
+        }
+    }
+
+    fn set_form_control_state(&mut self, node_id: usize, state: ElementState) {
+        let node = &self.nodes[node_id];
+        if node.element_state & FORM_CONTROL_STATES == state {
+            return;
+        }
+        self.snapshot_node_and(node_id, |node| {
+            node.element_state.remove(FORM_CONTROL_STATES);
+            node.element_state.insert(state);
+        });
+    }
+
+    fn compute_form_control_state(
+        &self,
+        node_id: usize,
+        radio_groups: &RadioGroups,
+    ) -> ElementState {
+        let node = &self.nodes[node_id];
+        let Some(element) = node.element_data() else {
+            return ElementState::empty();
+        };
+        let tag = &element.name.local;
+        let mut state = ElementState::empty();
+
+        // :valid / :invalid on forms and fieldsets reflect their descendant controls
+        if matches!(*tag, local_name!("form") | local_name!("fieldset")) {
+            state |= match self.check_validity_with(node_id, radio_groups) {
+                true => ElementState::VALID,
+                false => ElementState::INVALID,
+            };
+        }
+
+        if matches!(
+            *tag,
+            local_name!("button")
+                | local_name!("input")
+                | local_name!("select")
+                | local_name!("textarea")
+                | local_name!("fieldset")
+                | local_name!("optgroup")
+                | local_name!("option")
+        ) {
+            state |= match self.is_disabled(node_id) {
+                true => ElementState::DISABLED,
+                false => ElementState::ENABLED,
+            };
+        }
+
+        // :indeterminate (progress bars without a value) and :-moz-meter-optimum, etc
+        match *tag {
+            local_name!("progress") if !element.has_attr(local_name!("value")) => {
+                state |= ElementState::INDETERMINATE;
+            }
+            local_name!("meter") => state |= MeterValues::from_element(element).state(),
+            _ => {}
+        }
+
+        let input_type = input_type(element);
+        let is_text_control = *tag == local_name!("textarea")
+            || (*tag == local_name!("input") && element.text_input_data().is_some());
+
+        // :required / :optional
+        if *tag == local_name!("select")
+            || *tag == local_name!("textarea")
+            || (*tag == local_name!("input")
+                && !matches!(
+                    input_type,
+                    "hidden" | "range" | "color" | "submit" | "image" | "reset" | "button"
+                ))
+        {
+            state |= match element.has_attr(local_name!("required")) {
+                true => ElementState::REQUIRED,
+                false => ElementState::OPTIONAL,
+            };
+        }
+
+        // :read-write / :read-only
+        let is_mutable = is_text_control
+            && !element.has_attr(local_name!("readonly"))
+            && !state.contains(ElementState::DISABLED);
+        state |= match is_mutable || is_content_editable(node) {
+            true => ElementState::READWRITE,
+            false => ElementState::READONLY,
+        };
+
+        // :placeholder-shown
+        if is_text_control
+            && element.has_attr(local_name!("placeholder"))
+            && control_value(element).is_empty()
+        {
+            state |= ElementState::PLACEHOLDER_SHOWN;
+        }
+
+        // :valid / :invalid / :user-valid / :user-invalid
+        if self.is_candidate_for_constraint_validation(node_id) {
+            let validity = self.validity_with(node_id, radio_groups);
+            state |= match (validity.valid(), element.user_validity) {
+                (true, true) => ElementState::VALID | ElementState::USER_VALID,
+                (true, false) => ElementState::VALID,
+                (false, true) => ElementState::INVALID | ElementState::USER_INVALID,
+                (false, false) => ElementState::INVALID,
+            };
+
+            // :in-range / :out-of-range
+            if *tag == local_name!("input")
+                && matches!(input_type, "number" | "range")
+                && (element.has_attr(local_name!("min")) || element.has_attr(local_name!("max")))
+            {
+                state |= match validity.range_underflow || validity.range_overflow {
+                    true => ElementState::OUTOFRANGE,
+                    false => ElementState::INRANGE,
+                };
+            }
+        }
+
+        state
+    }
+
+    /// <https://html.spec.whatwg.org/multipage/form-control-infrastructure.html#candidate-for-constraint-validation>
+    fn is_candidate_for_constraint_validation(&self, node_id: usize) -> bool {
+        let Some(element) = self.nodes[node_id].element_data() else {
+            return false;
+        };
+        let is_barred = match element.name.local {
+            local_name!("input") => {
+                matches!(input_type(element), "hidden" | "reset" | "button")
+                    || (element.has_attr(local_name!("readonly"))
+                        && element.text_input_data().is_some())
+            }
+            local_name!("textarea") => element.has_attr(local_name!("readonly")),
+            local_name!("select") => false,
+            _ => return false,
+        };
+
+        !is_barred
+            && !self.is_disabled(node_id)
+            && !AncestorTraverser::new(self, node_id).any(|id| {
+                self.nodes[id]
+                    .data
+                    .is_element_with_tag_name(&local_name!("datalist"))
+            })
+    }
+
+    fn control_is_valid(&self, node_id: usize, radio_groups: &RadioGroups) -> bool {
+        !self.is_candidate_for_constraint_validation(node_id)
+            || self.validity_with(node_id, radio_groups).valid()
+    }
+
+    fn is_in_disabled_fieldset(&self, node_id: usize) -> bool {
+        // The child of the fieldset that this node is a descendant of
+        let mut child_id = node_id;
+        for ancestor_id in AncestorTraverser::new(self, node_id) {
+            let ancestor = &self.nodes[ancestor_id];
+            if ancestor
+                .data
+                .is_element_with_tag_name(&local_name!("fieldset"))
+                && ancestor.data.has_attr(local_name!("disabled"))
+            {
+                // Descendants of the fieldset's first <legend> child are not disabled by it
+                let first_legend = ancestor.children.iter().copied().find(|id| {
+                    self.nodes[*id]
+                        .data
+                        .is_element_with_tag_name(&local_name!("legend"))
+                });
+                if first_legend != Some(child_id) {
+                    return true;
+                }
+            }
+            child_id = ancestor_id;
+        }
+        false
+    }
+
+    fn input_validity(
+        &self,
+        node: &Node,
+        element: &ElementData,
+        radio_groups: &RadioGroups,
+    ) -> ValidityState {
+        let mut validity = ValidityState::default();
+        let required = element.has_attr(local_name!("required"));
+        let value = control_value(element);
+
+        match input_type(element) {
+            "checkbox" => {
+                validity.value_missing =
+                    required && !element.checkbox_input_checked().unwrap_or(false);
+                return validity;
+            }
+            "radio" => {
+                validity.value_missing = match radio_groups.get(self, node.id) {
+                    Some(group) => group.required && !group.checked,
+                    // Radio buttons without a name are in a group of their own
+                    None => required && !element.checkbox_input_checked().unwrap_or(false),
+                };
+                return validity;
+            }
+            #[cfg(feature = "file_input")]
+            "file" => {
+                validity.value_missing =
+                    required && element.file_data().is_none_or(|files| files.is_empty());
+                return validity;
+            }
+            "email" if !value.is_empty() => {
+                validity.type_mismatch = match element.has_attr(local_name!("multiple")) {
+                    true => !value.split(',').map(str::trim).all(is_valid_email),
+                    false => !is_valid_email(&value),
+                };
+            }
+            "url" if !value.is_empty() => {
+                validity.type_mismatch = url::Url::parse(&value).is_err();
+            }
+            // The value of a range input is always sanitized to be within its bounds
+            "range" => return validity,
+            "number" if !value.is_empty() => match value.parse::<f64>() {
+                Ok(number) if number.is_finite() => {
+                    let min: Option<f64> = element.attr_parsed(local_name!("min"));
+                    let max: Option<f64> = element.attr_parsed(local_name!("max"));
+                    validity.range_underflow = min.is_some_and(|min| number < min);
+                    validity.range_overflow = max.is_some_and(|max| number > max);
+                }
+                _ => validity.bad_input = true,
+            },
+            _ => {}
+        }
+
+        validity.value_missing = required && value.is_empty();
+        if element.text_input_data().is_some() {
+            check_length(element, &value, &mut validity);
+            if let Some(pattern) = element.attr(local_name!("pattern")) {
+                let regex = element
+                    .pattern_regex
+                    .get_or_init(|| compile_pattern(pattern));
+                // Invalid patterns are ignored
+                validity.pattern_mismatch = !value.is_empty()
+                    && regex.as_ref().is_some_and(|regex| !regex.is_match(&value));
+            }
+        }
+
+        validity
+    }
+}
+
+/// Whether changes to attributes or descendants of an element with this tag name may
+/// affect its form control state (or that of its descendants)
+pub(crate) fn is_form_related(tag: &LocalName) -> bool {
+    matches!(
+        *tag,
+        local_name!("form")
+            | local_name!("fieldset")
+            | local_name!("legend")
+            | local_name!("button")
+            | local_name!("input")
+            | local_name!("select")
+            | local_name!("textarea")
+            | local_name!("optgroup")
+            | local_name!("option")
+            | local_name!("datalist")
+            | local_name!("progress")
+            | local_name!("meter")
+    )
+}
+
+/// Whether an element's form control state needs to be computed
+fn has_form_control_state(node: &Node) -> bool {
+    node.element_data().is_some_and(|element| {
+        is_form_related(&element.name.local)
+            || element.has_attr(local_name!("contenteditable"))
+            || node.element_state.intersects(FORM_CONTROL_STATES)
+    })
+}
+
+/// The form owner and name of the group of a radio button (if it has a name)
+///
+/// <https://html.spec.whatwg.org/multipage/input.html#radio-button-group>
+fn radio_group_key(doc: &BaseDocument, node_id: usize) -> Option<(Option<usize>, String)> {
+    let node = &doc.nodes[node_id];
+    let element = node.element_data()?;
+    if !node.flags.is_in_document()
+        || element.name.local != local_name!("input")
+        || input_type(element) != "radio"
+    {
+        return None;
+    }
+    let name = element
+        .attr(local_name!("name"))
+        .filter(|name| !name.is_empty())?;
+    let form_owner = doc.controls_to_form.get(&node_id).copied();
+    Some((form_owner, name.to_string()))
+}
+
+fn input_type(element: &ElementData) -> &str {
+    element.attr(local_name!("type")).unwrap_or("text")
+}
+
+/// The current value of an `<input>` or `<textarea>`
+fn control_value(element: &ElementData) -> String {
+    match element.text_input_data() {
+        Some(input_data) => input_data.editor.raw_text().to_string(),
+        None => element.attr(local_name!("value")).unwrap_or("").to_string(),
+    }
+}
+
+fn is_content_editable(node: &Node) -> bool {
+    node.attr(local_name!("contenteditable"))
+        .is_some_and(|value| matches!(value, "" | "true" | "plaintext-only"))
+}
+
+fn check_length(element: &ElementData, value: &str, validity: &mut ValidityState) {
+    // Length constraints only apply to values edited by the user
+    if !element.user_validity || value.is_empty() {
+        return;
+    }
+    let length = value.encode_utf16().count();
+    let min_length: Option<usize> = element.attr_parsed(local_name!("minlength"));
+    let max_length: Option<usize> = element.attr_parsed(local_name!("maxlength"));
+    validity.too_short = min_length.is_some_and(|min| length < min);
+    validity.too_long = max_length.is_some_and(|max| length > max);
+}
+
+/// <https://html.spec.whatwg.org/multipage/input.html#valid-e-mail-address>
+fn is_valid_email(value: &str) -> bool {
+    let Some((local, domain)) = value.split_once('@') else {
+        return false;
+    };
+
+    let is_valid_local_char =
+        |c: char| c.is_ascii_alphanumeric() || ".!#$%&'*+/=?^_`{|}~-".contains(c);
+    let is_valid_label = |label: &str| {
+        !label.is_empty()
+            && label.len() <= 63
+            && !label.starts_with('-')
+            && !label.ends_with('-')
+            && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+    };
+
+    !local.is_empty()
+        && local.chars().all(is_valid_local_char)
+        && domain.split('.').all(is_valid_label)
+}
+
+/// Compile a `pattern` attribute, which must match the entire value
+///
+/// <https://html.spec.whatwg.org/multipage/input.html#compiled-pattern-regular-expression>
+fn compile_pattern(pattern: &str) -> Option<regex::Regex> {
+    regex::Regex::new(&format!("^(?:{pattern})$")).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_valid_email() {
+        assert!(is_valid_email("user@example.com"));
+        assert!(is_valid_email("first.last+tag@localhost"));
+        assert!(!is_valid_email("user"));
+        assert!(!is_valid_email("@example.com"));
+        assert!(!is_valid_email("user@-example.com"));
+        assert!(!is_valid_email("us er@example.com"));
+    }
+}
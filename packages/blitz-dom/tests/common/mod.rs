@@ -0,0 +1,126 @@
+//! Fixtures shared by the integration tests
+#![allow(dead_code)]
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use blitz_dom::node::TextLayout;
+use blitz_dom::{BaseDocument, DocumentConfig, DocumentMutator, EventDriver, EventHandler};
+use blitz_html::HtmlDocument;
+use blitz_traits::events::{
+    BlitzKeyEvent, BlitzMouseButtonEvent, DomEvent, EventState, KeyState, MouseEventButton,
+    MouseEventButtons, UiEvent,
+};
+use blitz_traits::shell::{ColorScheme, Viewport};
+use keyboard_types::{Code, Key, Location, Modifiers};
+
+/// Parse and lay out `html` in an 800x600 viewport
+pub fn layout_html(html: &str) -> HtmlDocument {
+    let config = DocumentConfig {
+        viewport: Some(Viewport::new(800, 600, 1.0, ColorScheme::Light)),
+        ..Default::default()
+    };
+    let mut doc = HtmlDocument::from_html(html, config);
+    doc.resolve(0.0);
+    doc
+}
+
+/// The document-relative border box (x, y, width, height) of the first element matching `selector`
+pub fn border_box(doc: &BaseDocument, selector: &str) -> (f32, f32, f32, f32) {
+    let node_id = doc.query_selector(selector).unwrap().unwrap();
+    let node = doc.get_node(node_id).unwrap();
+    let size = node.final_layout.size;
+
+    // Boxes within vertical flows are rotated, so map both corners
+    let start = node.absolute_position(0.0, 0.0);
+    let end = node.absolute_position(size.width, size.height);
+    (
+        start.x.min(end.x),
+        start.y.min(end.y),
+        (end.x - start.x).abs(),
+        (end.y - start.y).abs(),
+    )
+}
+
+/// The inline layout of the element matching `selector`
+pub fn text_layout<'a>(doc: &'a BaseDocument, selector: &str) -> &'a TextLayout {
+    let node_id = doc.query_selector(selector).unwrap().unwrap();
+    doc.get_node(node_id)
+        .unwrap()
+        .element_data()
+        .and_then(|el| el.inline_layout_data.as_deref())
+        .unwrap()
+}
+
+/// Records the target and name of each event handled by an [`EventDriver`]
+struct EventRecorder(Rc<RefCell<Vec<(usize, &'static str)>>>);
+
+impl EventHandler for EventRecorder {
+    fn handle_event(
+        &mut self,
+        _chain: &[usize],
+        event: &mut DomEvent,
+        _mutr: &mut DocumentMutator<'_>,
+        _event_state: &mut EventState,
+    ) {
+        self.0.borrow_mut().push((event.target, event.name()));
+    }
+}
+
+/// Dispatch `event` to the document, returning the target and name of each resulting DOM event
+pub fn dispatch(doc: &mut BaseDocument, event: UiEvent) -> Vec<(usize, &'static str)> {
+    let events = Rc::new(RefCell::new(Vec::new()));
+    let mut driver = EventDriver::new(doc.mutate(), EventRecorder(events.clone()));
+    driver.handle_ui_event(event);
+    drop(driver);
+    events.take()
+}
+
+/// A main mouse button event at (`x`, `y`) in the viewport
+pub fn mouse_event(x: f32, y: f32, buttons: MouseEventButtons) -> BlitzMouseButtonEvent {
+    BlitzMouseButtonEvent {
+        x,
+        y,
+        button: MouseEventButton::Main,
+        buttons,
+        mods: Modifiers::empty(),
+    }
+}
+
+/// Click the main mouse button at (`x`, `y`) in the viewport while holding `mods`
+pub fn click(
+    doc: &mut BaseDocument,
+    x: f32,
+    y: f32,
+    mods: Modifiers,
+) -> Vec<(usize, &'static str)> {
+    let event = |buttons| BlitzMouseButtonEvent {
+        mods,
+        ..mouse_event(x, y, buttons)
+    };
+    let mut events = dispatch(doc, UiEvent::MouseMove(event(MouseEventButtons::None)));
+    events.extend(dispatch(
+        doc,
+        UiEvent::MouseDown(event(MouseEventButtons::Primary)),
+    ));
+    events.extend(dispatch(
+        doc,
+        UiEvent::MouseUp(event(MouseEventButtons::None)),
+    ));
+    events
+}
+
+/// Press `key` (without modifiers)
+pub fn key_down(doc: &mut BaseDocument, key: Key) -> Vec<(usize, &'static str)> {
+    let event = BlitzKeyEvent {
+        key,
+        code: Code::Unidentified,
+        modifiers: Modifiers::empty(),
+        location: Location::Standard,
+        is_auto_repeating: false,
+        is_composing: false,
+        state: KeyState::Pressed,
+        text: None,
+    };
+    dispatch(doc, UiEvent::KeyDown(event))
+}
@@ -0,0 +1,202 @@
+mod common;
+
+use blitz_dom::BaseDocument;
+use blitz_traits::events::{MouseEventButtons, UiEvent};
+use common::{border_box, click, dispatch, key_down, layout_html, mouse_event};
+use keyboard_types::{Key, Modifiers};
+
+#[test]
+fn test_form_control_state_updates() {
+    use blitz_dom::{QualName, local_name, ns};
+
+    let mut doc = layout_html(
+        r#"<form id="form">
+            <input id="a" type="radio" name="choice" required>
+            <input id="b" type="radio" name="choice">
+            <input id="text" pattern="[0-9]+" value="abc">
+            <fieldset id="fieldset"><input id="nested"></fieldset>
+        </form>"#,
+    );
+    let matches =
+        |doc: &BaseDocument, selector: &str| doc.query_selector(selector).unwrap().is_some();
+    let id = |doc: &BaseDocument, selector: &str| doc.query_selector(selector).unwrap().unwrap();
+
+    // Both buttons are invalid as their group is required but has no checked button
+    assert!(matches(&doc, "#a:invalid"));
+    assert!(matches(&doc, "#b:invalid"));
+    assert!(matches(&doc, "#text:invalid"));
+    assert!(matches(&doc, "#form:invalid"));
+    assert!(matches(&doc, "#nested:enabled"));
+
+    let (b, text, fieldset) = (id(&doc, "#b"), id(&doc, "#text"), id(&doc, "#fieldset"));
+    let attr = |name| QualName::new(None, ns!(), name);
+    let mut mutator = doc.mutate();
+    mutator.set_attribute(b, attr(local_name!("checked")), "");
+    mutator.set_attribute(text, attr(local_name!("pattern")), "[a-z]+");
+    mutator.set_attribute(fieldset, attr(local_name!("disabled")), "");
+    drop(mutator);
+
+    // Checking one button updates the whole group, and the changed pattern is recompiled
+    assert!(matches(&doc, "#a:valid"));
+    assert!(matches(&doc, "#b:valid"));
+    assert!(matches(&doc, "#text:valid"));
+    assert!(matches(&doc, "#form:valid"));
+    assert!(matches(&doc, "#nested:disabled"));
+}
+
+#[test]
+fn test_radio_groups() {
+    let mut doc = layout_html(
+        r#"<form><input id="a" type="radio" name="choice" checked></form>
+        <form><input id="b" type="radio" name="choice" checked></form>
+        <form><input id="c" type="radio" name="choice"></form>"#,
+    );
+    let id = |doc: &BaseDocument, selector: &str| doc.query_selector(selector).unwrap().unwrap();
+    let checked = |doc: &BaseDocument, selector: &str| {
+        doc.query_selector(&format!("{selector}:checked"))
+            .unwrap()
+            .is_some()
+    };
+
+    // Buttons with the same name in other forms are in other groups
+    let c = id(&doc, "#c");
+    doc.toggle_radio(c);
+    assert!(checked(&doc, "#a"));
+    assert!(checked(&doc, "#b"));
+    assert!(checked(&doc, "#c"));
+}
+
+#[test]
+fn test_select_popup_placement() {
+    let options = "<option>Option</option>".repeat(30);
+    let mut doc = layout_html(&format!(
+        r#"<body style="margin: 0; height: 2000px">
+            <select id="top">{options}</select>
+            <select id="bottom" style="position: absolute; top: 580px; left: 790px">{options}</select>
+        </body>"#
+    ));
+    let top = doc.query_selector("#top").unwrap().unwrap();
+    let bottom = doc.query_selector("#bottom").unwrap().unwrap();
+
+    // The popup opens below the select, showing at most 20 rows with the selected option in view
+    doc.set_select_option_selected(top, 25, true);
+    doc.open_select_popup(top);
+    let popup = doc.select_popup().unwrap();
+    let (_, y, _, height) = border_box(&doc, "#top");
+    assert_eq!(popup.y, y + height);
+    assert_eq!(popup.row_count, 20);
+    assert_eq!(popup.first_option, 6);
+    assert_eq!(popup.option_at(popup.x + 1.0, popup.y + 1.0), Some(6));
+
+    // Scrolling the popup scrolls by whole options
+    doc.scroll_node_by(top, 0.0, 1000.0);
+    assert_eq!(doc.select_popup().unwrap().first_option, 0);
+
+    // Near the bottom right of the viewport it opens above the select, within the viewport
+    doc.open_select_popup(bottom);
+    let popup = doc.select_popup().unwrap();
+    let (_, y, _, _) = border_box(&doc, "#bottom");
+    assert!((popup.y + popup.height() - y).abs() < 0.01);
+    assert_eq!(popup.row_count, 20);
+    assert!(popup.x + popup.width <= 800.0);
+}
+
+#[test]
+fn test_list_box_selection() {
+    let mut doc = layout_html(
+        r#"<body style="margin: 0">
+            <select id="list" multiple size="4">
+                <option>A</option><option>B</option><option>C</option><option>D</option>
+            </select>
+        </body>"#,
+    );
+    let list = doc.query_selector("#list").unwrap().unwrap();
+    let (x, y, _, _) = border_box(&doc, "#list");
+    let selected = |doc: &BaseDocument| -> Vec<usize> {
+        let element = doc.get_node(list).unwrap().element_data().unwrap();
+        element.select_data().unwrap().selected_indexes().collect()
+    };
+    let row_y = |doc: &BaseDocument, row: usize| {
+        let node = doc.get_node(list).unwrap();
+        let layout = &node.final_layout;
+        let row_height = node
+            .element_data()
+            .unwrap()
+            .select_data()
+            .unwrap()
+            .row_height;
+        y + layout.border.top + layout.padding.top + (row as f32 + 0.5) * row_height
+    };
+
+    // Ctrl+click toggles options, adding to the selection
+    click(&mut doc, x + 5.0, row_y(&doc, 0), Modifiers::empty());
+    let row = row_y(&doc, 2);
+    click(&mut doc, x + 5.0, row, Modifiers::CONTROL);
+    assert_eq!(selected(&doc), [0, 2]);
+
+    // A plain click selects only the clicked option
+    let row = row_y(&doc, 1);
+    click(&mut doc, x + 5.0, row, Modifiers::empty());
+    assert_eq!(selected(&doc), [1]);
+}
+
+#[test]
+fn test_range_input() {
+    let mut doc = layout_html(
+        r#"<body style="margin: 0">
+            <input id="range" type="range" min="0" max="10" step="2" value="5"
+                style="width: 100px; margin: 0; padding: 0; border: 0">
+        </body>"#,
+    );
+    let range = doc.query_selector("#range").unwrap().unwrap();
+    let (x, y, _, height) = border_box(&doc, "#range");
+    let y = y + height / 2.0;
+    let value = |doc: &BaseDocument| doc.range_input_value(range).unwrap();
+    let names = |events: Vec<(usize, &'static str)>| -> Vec<&'static str> {
+        events
+            .into_iter()
+            .filter(|(target, _)| *target == range)
+            .map(|(_, name)| name)
+            .filter(|name| matches!(*name, "input" | "change"))
+            .collect()
+    };
+
+    // The value is snapped to the step (ties round up)
+    assert_eq!(value(&doc), 6.0);
+
+    // Dragging the thumb sets the value from the position along the slider, even outside of it,
+    // and fires a change event when released
+    dispatch(
+        &mut doc,
+        UiEvent::MouseMove(mouse_event(x + 31.0, y, MouseEventButtons::None)),
+    );
+    let held = MouseEventButtons::Primary;
+    let events = dispatch(&mut doc, UiEvent::MouseDown(mouse_event(x + 31.0, y, held)));
+    assert_eq!(value(&doc), 4.0);
+    assert_eq!(names(events), ["input"]);
+    dispatch(&mut doc, UiEvent::MouseMove(mouse_event(x + 79.0, y, held)));
+    assert_eq!(value(&doc), 8.0);
+    dispatch(
+        &mut doc,
+        UiEvent::MouseMove(mouse_event(x + 200.0, y, held)),
+    );
+    assert_eq!(value(&doc), 10.0);
+    let events = dispatch(
+        &mut doc,
+        UiEvent::MouseUp(mouse_event(x + 200.0, y, MouseEventButtons::None)),
+    );
+    assert!(names(events).contains(&"change"));
+
+    // The focussed input is stepped with the keyboard, and clamped to its bounds
+    assert_eq!(
+        names(key_down(&mut doc, Key::ArrowLeft)),
+        ["input", "change"]
+    );
+    assert_eq!(value(&doc), 8.0);
+    key_down(&mut doc, Key::Home);
+    assert_eq!(value(&doc), 0.0);
+    assert!(names(key_down(&mut doc, Key::ArrowDown)).is_empty());
+    assert_eq!(value(&doc), 0.0);
+    key_down(&mut doc, Key::PageUp);
+    assert_eq!(value(&doc), 2.0);
+}
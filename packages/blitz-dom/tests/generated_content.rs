@@ -0,0 +1,92 @@
+mod common;
+
+use blitz_dom::BaseDocument;
+use blitz_dom::node::Marker;
+use common::layout_html;
+
+/// The text of the `::before` pseudo element of the first element matching `selector`
+fn before_text(doc: &BaseDocument, selector: &str) -> String {
+    let node_id = doc.query_selector(selector).unwrap().unwrap();
+    let before_id = doc.get_node(node_id).unwrap().before.unwrap();
+    doc.get_node(before_id).unwrap().text_content()
+}
+
+/// The marker of the first list item matching `selector`
+fn marker(doc: &BaseDocument, selector: &str) -> Marker {
+    let node_id = doc.query_selector(selector).unwrap().unwrap();
+    let element = doc.get_node(node_id).unwrap().element_data().unwrap();
+    element.list_item_data.as_ref().unwrap().marker.clone()
+}
+
+#[test]
+fn test_counters_quotes_and_attr() {
+    let doc = layout_html(
+        r#"<style>
+            body { counter-reset: section }
+            h2::before { counter-increment: section; content: counter(section, upper-roman) ". " }
+            ol { counter-reset: item; list-style: none }
+            li { counter-increment: item }
+            li::before { content: counters(item, ".") " " }
+            q { quotes: "«" "»" "‹" "›" }
+            q::before { content: open-quote }
+            abbr::before { content: attr(title) ": " }
+        </style>
+        <h2 id="first"></h2><h2 id="second"></h2>
+        <ol>
+            <li id="a">A</li>
+            <li id="b">B<ol><li id="b1">B1</li><li id="b2">B2</li></ol></li>
+        </ol>
+        <p><q id="outer">Outer <q id="inner">inner</q></q></p>
+        <abbr id="abbr" title="HyperText Markup Language">HTML</abbr>"#,
+    );
+
+    assert_eq!(before_text(&doc, "#first"), "I. ");
+    assert_eq!(before_text(&doc, "#second"), "II. ");
+
+    // Nested counters are scoped to their list, and joined with the separator
+    assert_eq!(before_text(&doc, "#a"), "1 ");
+    assert_eq!(before_text(&doc, "#b"), "2 ");
+    assert_eq!(before_text(&doc, "#b1"), "2.1 ");
+    assert_eq!(before_text(&doc, "#b2"), "2.2 ");
+
+    // Nested quotes use the next pair of quotation marks
+    assert_eq!(before_text(&doc, "#outer"), "«");
+    assert_eq!(before_text(&doc, "#inner"), "‹");
+
+    assert_eq!(before_text(&doc, "#abbr"), "HyperText Markup Language: ");
+}
+
+#[test]
+fn test_counter_style_rules() {
+    let doc = layout_html(
+        r#"<style>
+            @counter-style binary { system: numeric; symbols: "0" "1"; suffix: ") " }
+            @counter-style dashed { system: extends decimal; prefix: "-"; suffix: " " }
+            @counter-style stars { system: cyclic; symbols: "*" "+"; suffix: " " }
+            body { counter-reset: section }
+            h2 { counter-increment: section }
+            h2::before { content: counter(section, binary) }
+        </style>
+        <ol style="list-style-type: binary"><li id="b1"></li><li id="b2"></li><li id="b3"></li></ol>
+        <ol style="list-style-type: dashed" start="9"><li id="d9"></li><li id="d10"></li></ol>
+        <ul style="list-style-type: stars"><li id="s1"></li><li id="s2"></li><li id="s3"></li></ul>
+        <h2></h2><h2 id="section"></h2>"#,
+    );
+    let string = |text: &str| Marker::String(text.to_string());
+
+    assert_eq!(marker(&doc, "#b1"), string("1) "));
+    assert_eq!(marker(&doc, "#b2"), string("10) "));
+    assert_eq!(marker(&doc, "#b3"), string("11) "));
+
+    // Extended styles inherit the algorithm of the style they extend
+    assert_eq!(marker(&doc, "#d9"), string("-9 "));
+    assert_eq!(marker(&doc, "#d10"), string("-10 "));
+
+    // Cyclic styles repeat their symbols
+    assert_eq!(marker(&doc, "#s1"), string("* "));
+    assert_eq!(marker(&doc, "#s2"), string("+ "));
+    assert_eq!(marker(&doc, "#s3"), string("* "));
+
+    // Counter styles also apply to counters in generated content (without the suffix)
+    assert_eq!(before_text(&doc, "#section"), "10");
+}
@@ -0,0 +1,296 @@
+mod common;
+
+use blitz_dom::BaseDocument;
+use common::{border_box, layout_html, text_layout};
+
+#[test]
+fn test_float_placement_and_clearance() {
+    let doc = layout_html(
+        r#"<body style="margin: 0">
+            <div id="left1" style="float: left; width: 100px; height: 50px"></div>
+            <div id="left2" style="float: left; width: 100px; height: 30px"></div>
+            <div id="right" style="float: right; width: 200px; height: 80px"></div>
+            <div id="clear-left" style="clear: left; height: 10px"></div>
+            <div id="clear-both" style="clear: both; height: 10px"></div>
+            <div id="after" style="float: left; width: 50px; height: 50px"></div>
+        </body>"#,
+    );
+
+    // Floats stack horizontally against their side of the containing block
+    assert_eq!(border_box(&doc, "#left1"), (0.0, 0.0, 100.0, 50.0));
+    assert_eq!(border_box(&doc, "#left2"), (100.0, 0.0, 100.0, 30.0));
+    assert_eq!(border_box(&doc, "#right"), (600.0, 0.0, 200.0, 80.0));
+
+    // Cleared boxes are moved below the relevant floats
+    assert_eq!(border_box(&doc, "#clear-left"), (0.0, 50.0, 800.0, 10.0));
+    assert_eq!(border_box(&doc, "#clear-both"), (0.0, 80.0, 800.0, 10.0));
+
+    // Floats can't be placed above earlier content
+    assert_eq!(border_box(&doc, "#after"), (0.0, 90.0, 50.0, 50.0));
+}
+
+#[test]
+fn test_inline_content_beside_float() {
+    let doc = layout_html(
+        r#"<body style="margin: 0">
+            <div style="width: 300px; font-size: 16px; line-height: 20px">
+                <div style="float: left; width: 100px; height: 50px"></div>
+                <p id="text" style="margin: 0">
+                    Some text beside a float which is long enough to wrap onto several lines, so
+                    that the lines below the float are given the full width of the paragraph
+                </p>
+            </div>
+        </body>"#,
+    );
+
+    // The paragraph box spans the containing block
+    let node_id = doc.query_selector("#text").unwrap().unwrap();
+    let node = doc.get_node(node_id).unwrap();
+    assert_eq!(node.final_layout.size.width, 300.0);
+
+    // Each line box level with the float is moved beside it and shortened, while the lines below
+    // the float span the paragraph
+    let text_layout = text_layout(&doc, "#text");
+    let lines: Vec<_> = text_layout.layout.lines().collect();
+    assert!(lines.len() >= 4);
+    for (line_index, line) in lines.iter().enumerate() {
+        let metrics = line.metrics();
+        let offset = text_layout.line_offset(line_index);
+        assert_eq!(offset.y, 0.0);
+        if metrics.min_coord < 50.0 {
+            assert_eq!(offset.x, 100.0);
+            assert!(metrics.advance - metrics.trailing_whitespace <= 200.0);
+        } else {
+            assert_eq!(offset.x, 0.0);
+        }
+    }
+    let last = lines.last().unwrap().metrics();
+    assert!(last.max_coord > 50.0);
+    assert_eq!(node.final_layout.size.height, last.max_coord);
+}
+
+#[test]
+fn test_inline_content_below_float() {
+    let doc = layout_html(
+        r#"<body style="margin: 0">
+            <div style="width: 300px; font-size: 16px; line-height: 20px">
+                <div style="float: right; width: 280px; height: 50px"></div>
+                <p id="text" style="margin: 0">Unbreakable</p>
+            </div>
+        </body>"#,
+    );
+
+    // A line which doesn't fit beside the float is moved below it
+    let text_layout = text_layout(&doc, "#text");
+    assert_eq!(text_layout.layout.lines().count(), 1);
+    let offset = text_layout.line_offset(0);
+    assert_eq!((offset.x, offset.y), (0.0, 50.0));
+    assert_eq!(border_box(&doc, "#text").3, 70.0);
+}
+
+#[test]
+fn test_rtl_layout() {
+    let doc = layout_html(
+        r#"<body style="margin: 0">
+            <div style="direction: rtl; width: 400px; padding-right: 10px">
+                <div id="block" style="width: 100px; height: 10px; margin-right: 20px"></div>
+                <div style="display: flex; height: 10px">
+                    <div id="first" style="width: 50px"></div>
+                    <div id="second" style="width: 50px"></div>
+                </div>
+                <div style="display: grid; grid-template-columns: 100px 100px; height: 10px">
+                    <div id="column-1"></div>
+                    <div id="column-2"></div>
+                </div>
+            </div>
+        </body>"#,
+    );
+
+    // Blocks are placed against the right edge of the content box, and physical margins still
+    // apply to the physical side
+    assert_eq!(border_box(&doc, "#block"), (280.0, 0.0, 100.0, 10.0));
+
+    // Flex items and grid columns are placed from right to left
+    assert_eq!(border_box(&doc, "#first"), (350.0, 10.0, 50.0, 10.0));
+    assert_eq!(border_box(&doc, "#second"), (300.0, 10.0, 50.0, 10.0));
+    assert_eq!(border_box(&doc, "#column-1"), (300.0, 20.0, 100.0, 10.0));
+    assert_eq!(border_box(&doc, "#column-2"), (200.0, 20.0, 100.0, 10.0));
+}
+
+/// The text of the inline layout of the element matching `selector`, and whether its base direction
+/// is right-to-left
+fn paragraph_text(doc: &BaseDocument, selector: &str) -> (String, bool) {
+    let text_layout = text_layout(doc, selector);
+    (text_layout.text.clone(), text_layout.layout.is_rtl())
+}
+
+#[test]
+fn test_bidi_paragraph_direction() {
+    let doc = layout_html(
+        r#"<body style="margin: 0">
+            <p id="rtl-latin" style="direction: rtl">abc</p>
+            <p id="ltr-hebrew" style="direction: ltr">שלום</p>
+            <p id="rtl-hebrew" style="direction: rtl">שלום</p>
+            <p id="plaintext" style="direction: rtl; unicode-bidi: plaintext">abc</p>
+            <p id="override" style="direction: rtl; unicode-bidi: bidi-override">abc</p>
+            <p id="empty" style="direction: rtl"> </p>
+        </body>"#,
+    );
+    let hebrew = "\u{5e9}\u{5dc}\u{5d5}\u{5dd}";
+
+    // A mark is only inserted when the first strong character doesn't match `direction`
+    assert_eq!(
+        paragraph_text(&doc, "#rtl-latin"),
+        ("\u{200F}abc".to_string(), true)
+    );
+    assert_eq!(
+        paragraph_text(&doc, "#ltr-hebrew"),
+        (format!("\u{200E}{hebrew}"), false)
+    );
+    assert_eq!(
+        paragraph_text(&doc, "#rtl-hebrew"),
+        (hebrew.to_string(), true)
+    );
+
+    // Plaintext paragraphs take their direction from their content, and overridden paragraphs
+    // start with an override
+    assert_eq!(
+        paragraph_text(&doc, "#plaintext"),
+        ("abc".to_string(), false)
+    );
+    assert_eq!(
+        paragraph_text(&doc, "#override"),
+        ("\u{200F}\u{202E}abc".to_string(), true)
+    );
+
+    // Empty paragraphs stay empty
+    assert_eq!(paragraph_text(&doc, "#empty"), (String::new(), false));
+}
+
+#[test]
+fn test_unicode_bidi_controls() {
+    let doc = layout_html(
+        r#"<body style="margin: 0">
+            <p id="p">a <span style="direction: rtl; unicode-bidi: embed"> b </span><bdi>c</bdi><span style="direction: rtl; unicode-bidi: isolate-override">de</span><span style="unicode-bidi: plaintext">f</span></p>
+        </body>"#,
+    );
+
+    // Inline boxes are wrapped in the controls for their `unicode-bidi` and `direction`, and their
+    // whitespace is still collapsed
+    let (text, is_rtl) = paragraph_text(&doc, "#p");
+    assert_eq!(
+        text,
+        "a \u{202B}b\u{202C}\u{2066}c\u{2069}\u{2067}\u{202E}de\u{202C}\u{2069}\u{2068}f\u{2069}"
+    );
+    assert!(!is_rtl);
+
+    // The overridden text is laid out right-to-left
+    let node_id = doc.query_selector("#p").unwrap().unwrap();
+    let layout = &doc
+        .get_node(node_id)
+        .unwrap()
+        .element_data()
+        .and_then(|el| el.inline_layout_data.as_ref())
+        .unwrap()
+        .layout;
+    let de_start = text.find("de").unwrap();
+    let de_run_is_rtl = layout
+        .lines()
+        .flat_map(|line| line.items().collect::<Vec<_>>())
+        .find_map(|item| match item {
+            parley::PositionedLayoutItem::GlyphRun(glyph_run)
+                if glyph_run.run().text_range().contains(&de_start) =>
+            {
+                Some(glyph_run.run().is_rtl())
+            }
+            _ => None,
+        });
+    assert_eq!(de_run_is_rtl, Some(true));
+}
+
+#[test]
+fn test_vertical_writing_mode_layout() {
+    let doc = layout_html(
+        r#"<body style="margin: 0">
+            <div id="rl" style="writing-mode: vertical-rl; width: 300px; height: 200px">
+                <div id="rl-a" style="width: 30px"></div>
+                <div id="rl-b" style="width: 50px; margin-top: 10px"></div>
+            </div>
+            <div id="lr" style="writing-mode: vertical-lr; width: 300px; height: 200px">
+                <div id="lr-a" style="width: 30px"></div>
+                <div id="lr-b" style="width: 50px"></div>
+            </div>
+        </body>"#,
+    );
+
+    assert_eq!(border_box(&doc, "#rl"), (0.0, 0.0, 300.0, 200.0));
+    assert_eq!(border_box(&doc, "#lr"), (0.0, 200.0, 300.0, 200.0));
+
+    // Blocks are stacked horizontally (from the right for vertical-rl) and fill the height of the
+    // flow, less any margins in the inline (vertical) axis
+    assert_eq!(border_box(&doc, "#rl-a"), (270.0, 0.0, 30.0, 200.0));
+    assert_eq!(border_box(&doc, "#rl-b"), (220.0, 10.0, 50.0, 190.0));
+    assert_eq!(border_box(&doc, "#lr-a"), (0.0, 200.0, 30.0, 200.0));
+    assert_eq!(border_box(&doc, "#lr-b"), (30.0, 200.0, 50.0, 200.0));
+}
+
+#[test]
+fn test_text_wrap_mode() {
+    let doc = layout_html(
+        r#"<body style="margin: 0; width: 40px">
+            <p id="wrap" style="margin: 0">wrapping text <span id="nowrap" style="white-space: nowrap">not wrapping text</span></p>
+        </body>"#,
+    );
+
+    // The unwrappable span is kept on a single line (overflowing the paragraph), but the preceding
+    // text still wraps
+    let node_id = doc.query_selector("#wrap").unwrap().unwrap();
+    let text_layout = doc
+        .get_node(node_id)
+        .unwrap()
+        .element_data()
+        .and_then(|el| el.inline_layout_data.as_ref())
+        .unwrap();
+    let widths: Vec<f32> = text_layout
+        .layout
+        .lines()
+        .map(|line| line.metrics().advance)
+        .collect();
+    assert_eq!(widths.len(), 3);
+    assert!(widths[2] > 40.0);
+    assert!(widths[2] > widths[0] && widths[2] > widths[1]);
+    assert_eq!(text_layout.layout.full_width(), widths[2]);
+}
+
+#[test]
+fn test_absolute_position_in_inline_containing_block() {
+    let doc = layout_html(
+        r#"<body style="margin: 0">
+            <div style="position: relative; margin-top: 100px"><span style="display: inline-block; width: 100px; height: 20px"></span><span style="position: relative"><span id="inner" style="display: inline-block; width: 50px; height: 20px"></span><b id="absolute" style="position: absolute; left: 5px; top: 5px; width: 10px; height: 10px"></b></span></div>
+        </body>"#,
+    );
+
+    // The positioned span (rather than the positioned div) is the containing block
+    let inner = border_box(&doc, "#inner");
+    assert_eq!(inner.0, 100.0);
+    assert_eq!(
+        border_box(&doc, "#absolute"),
+        (inner.0 + 5.0, inner.1 + 5.0, 10.0, 10.0)
+    );
+}
+
+#[test]
+fn test_fixed_position_in_inline_context() {
+    use blitz_dom::Point;
+
+    let mut doc = layout_html(
+        r#"<body style="margin: 0">
+            <div style="position: relative; margin-top: 100px; height: 2000px"><span><b id="fixed" style="position: fixed; left: 20px; top: 10px; width: 10px; height: 10px"></b></span></div>
+        </body>"#,
+    );
+
+    // Fixed boxes are positioned against the viewport, and stay in place when it is scrolled
+    assert_eq!(border_box(&doc, "#fixed"), (20.0, 10.0, 10.0, 10.0));
+    doc.set_viewport_scroll(Point { x: 0.0, y: 50.0 });
+    assert_eq!(border_box(&doc, "#fixed"), (20.0, 60.0, 10.0, 10.0));
+}
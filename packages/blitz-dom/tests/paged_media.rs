@@ -0,0 +1,82 @@
+mod common;
+
+use blitz_dom::PageOptions;
+use common::{border_box, layout_html, text_layout};
+
+#[test]
+fn test_paginate() {
+    let mut doc = layout_html(
+        r#"<style>
+            @page { size: 400px 300px; margin: 0 }
+            @page :left { margin-top: 100px }
+            @media print { #a { height: 50px !important } }
+            .break { break-before: page }
+            #c { break-before: page !important }
+        </style>
+        <body style="margin: 0">
+            <div id="a" style="height: 100px"></div>
+            <div id="b" class="break" style="height: 100px; break-before: auto"></div>
+            <div id="c" style="height: 100px; break-before: avoid"></div>
+            <div id="d" style="height: 150px"></div>
+        </body>"#,
+    );
+
+    let pages = doc.paginate(&PageOptions::default());
+
+    // The `style` attribute overrides the normal declaration for #b, but not the important one for
+    // #c. Print styles apply while paginating.
+    let fragments: Vec<(f32, f32)> = pages
+        .iter()
+        .map(|page| (page.offset, page.fragment_height))
+        .collect();
+    assert_eq!(fragments, [(0.0, 300.0), (300.0, 200.0), (500.0, 150.0)]);
+
+    // Boxes after a break are pushed to the start of the next page, and the document stays laid
+    // out for the pages until pagination ends
+    assert!(doc.is_paginated());
+    assert_eq!(doc.root_element().final_layout.size.width, 400.0);
+    assert_eq!(border_box(&doc, "#c"), (0.0, 300.0, 400.0, 100.0));
+    assert_eq!(border_box(&doc, "#d"), (0.0, 500.0, 400.0, 150.0));
+
+    // The media type and viewport are restored afterwards
+    doc.end_pagination();
+    assert!(!doc.is_paginated());
+    assert_eq!(doc.root_element().final_layout.size.width, 800.0);
+    assert_eq!(border_box(&doc, "#a"), (0.0, 0.0, 800.0, 100.0));
+    assert_eq!(border_box(&doc, "#c"), (0.0, 200.0, 800.0, 100.0));
+}
+
+#[test]
+fn test_paginate_lines() {
+    let html = |extra_css: &str| {
+        format!(
+            r#"<style>
+                @page {{ size: 400px 100px; margin: 0 }}
+                body {{ margin: 0; line-height: 30px }}
+                p {{ margin: 0 }}
+                {extra_css}
+            </style>
+            <body>
+                <div style="height: 50px"></div>
+                <p>{}</p>
+            </body>"#,
+            "Lorem ipsum dolor sit amet, consectetur adipiscing elit. ".repeat(8)
+        )
+    };
+
+    // The first line fits on the first page, but a single line would be left behind there, so the
+    // whole paragraph is pushed onto the next page
+    let mut doc = layout_html(&html(""));
+    doc.paginate(&PageOptions::default());
+    assert_eq!(border_box(&doc, "p").1, 100.0);
+    assert_eq!(text_layout(&doc, "p").line_offset(1).y, 0.0);
+
+    // Otherwise only the line which would be split by the end of the page is pushed
+    let mut doc = layout_html(&html("p { orphans: 1 }"));
+    let pages = doc.paginate(&PageOptions::default());
+    assert_eq!(border_box(&doc, "p").1, 50.0);
+    assert_eq!(text_layout(&doc, "p").line_offset(0).y, 0.0);
+    assert_eq!(text_layout(&doc, "p").line_offset(1).y, 20.0);
+    assert_eq!(pages[0].fragment_height, 100.0);
+    assert_eq!(pages[1].offset, 100.0);
+}
@@ -0,0 +1,48 @@
+mod common;
+
+use blitz_dom::{BaseDocument, QualName, local_name, ns};
+use common::layout_html;
+
+fn matches(doc: &BaseDocument, selector: &str) -> bool {
+    doc.query_selector(selector).unwrap().is_some()
+}
+
+#[test]
+fn test_lang() {
+    let doc =
+        layout_html(r#"<div lang="fr-CA"><p id="inherited"></p><p id="own" lang="en"></p></div>"#);
+
+    // The language is inherited from the nearest ancestor with a `lang` attribute
+    assert!(matches(&doc, "#inherited:lang(fr)"));
+    assert!(!matches(&doc, "#inherited:lang(en)"));
+    assert!(matches(&doc, "#own:lang(en)"));
+    assert!(!matches(&doc, "#own:lang(fr)"));
+}
+
+#[test]
+fn test_target() {
+    let mut doc =
+        layout_html(r#"<div id="a"></div><div id="b"></div><a id="c" name="anchor"></a>"#);
+    let id = |doc: &BaseDocument, selector: &str| doc.query_selector(selector).unwrap().unwrap();
+    let attr = |name| QualName::new(None, ns!(), name);
+
+    doc.set_base_url("https://example.com/#a");
+    assert!(matches(&doc, "#a:target"));
+
+    // The target is updated when ids change
+    let (a, b) = (id(&doc, "#a"), id(&doc, "#b"));
+    let mut mutator = doc.mutate();
+    mutator.clear_attribute(a, attr(local_name!("id")));
+    mutator.set_attribute(b, attr(local_name!("id")), "a");
+    drop(mutator);
+    assert!(matches(&doc, "#a:target"));
+    assert!(!matches(&doc, "div:not(#a):target"));
+
+    // `<a>` elements can also be targeted by name
+    doc.set_base_url("https://example.com/#anchor");
+    assert!(matches(&doc, "#c:target"));
+    let c = id(&doc, "#c");
+    doc.mutate()
+        .set_attribute(c, attr(local_name!("name")), "other");
+    assert!(!matches(&doc, ":target"));
+}
@@ -0,0 +1,76 @@
+mod common;
+
+use blitz_dom::BaseDocument;
+use common::{border_box, layout_html};
+
+#[test]
+fn test_shadow_dom_slots_and_scoped_styles() {
+    use blitz_dom::{Attribute, DocumentMutator, LocalName, QualName, ShadowRootMode, ns};
+
+    fn element(mutator: &mut DocumentMutator, tag: &str, attrs: &[(&str, &str)]) -> usize {
+        let attrs = attrs
+            .iter()
+            .map(|(name, value)| Attribute {
+                name: QualName::new(None, ns!(), LocalName::from(*name)),
+                value: value.to_string(),
+            })
+            .collect();
+        mutator.create_element(QualName::new(None, ns!(html), LocalName::from(tag)), attrs)
+    }
+    fn assigned_nodes(doc: &BaseDocument, slot_id: usize) -> Vec<usize> {
+        let slot = doc.get_node(slot_id).unwrap();
+        slot.element_data().unwrap().assigned_nodes.clone()
+    }
+
+    let mut doc = layout_html(
+        r#"<body style="margin: 0">
+            <div id="host"><span id="named" slot="title">Title</span><b id="unslotted" slot="missing">Hidden</b><i id="default">Body</i></div>
+            <p id="outside" class="inner" style="margin: 0">Outside</p>
+        </body>"#,
+    );
+    let id = |doc: &BaseDocument, selector| doc.query_selector(selector).unwrap().unwrap();
+    let (host, named) = (id(&doc, "#host"), id(&doc, "#named"));
+    let (unslotted, default) = (id(&doc, "#unslotted"), id(&doc, "#default"));
+
+    // <style>..</style><slot name="title"></slot><div class="inner"><slot></slot></div>
+    let mut mutator = doc.mutate();
+    let shadow_root = mutator.attach_shadow(host, ShadowRootMode::Open).unwrap();
+    let style = element(&mut mutator, "style", &[]);
+    let css = ":host { display: block; width: 200px } .inner { height: 30px }";
+    let css = mutator.create_text_node(css);
+    mutator.append_children(style, &[css]);
+    let title_slot = element(&mut mutator, "slot", &[("name", "title")]);
+    let inner = element(&mut mutator, "div", &[("class", "inner")]);
+    let default_slot = element(&mut mutator, "slot", &[]);
+    mutator.append_children(inner, &[default_slot]);
+    mutator.append_children(shadow_root, &[style, title_slot, inner]);
+    drop(mutator);
+    doc.resolve(0.0);
+
+    // Children are assigned to the slot with a matching name, or the default slot
+    assert_eq!(assigned_nodes(&doc, title_slot), [named]);
+    assert_eq!(assigned_nodes(&doc, default_slot), [default]);
+    assert_eq!(doc.get_node(named).unwrap().assigned_slot, Some(title_slot));
+    assert_eq!(doc.get_node(unslotted).unwrap().assigned_slot, None);
+
+    // Styles in the shadow tree apply to the host and the shadow tree, but not the rest of the document
+    assert_eq!(border_box(&doc, "#host").2, 200.0);
+    assert_eq!(doc.get_node(inner).unwrap().final_layout.size.height, 30.0);
+    assert_ne!(border_box(&doc, "#outside").3, 30.0);
+
+    // Slots are re-assigned when the host's children change
+    let mut mutator = doc.mutate();
+    let slot_attr = QualName::new(None, ns!(), LocalName::from("slot"));
+    mutator.set_attribute(default, slot_attr, "title");
+    mutator.remove_node(named);
+    drop(mutator);
+    doc.resolve(0.0);
+
+    assert_eq!(assigned_nodes(&doc, title_slot), [default]);
+    assert!(assigned_nodes(&doc, default_slot).is_empty());
+    assert_eq!(
+        doc.get_node(default).unwrap().assigned_slot,
+        Some(title_slot)
+    );
+    assert_eq!(doc.get_node(named).unwrap().assigned_slot, None);
+}
@@ -0,0 +1,72 @@
+mod common;
+
+use blitz_dom::BaseDocument;
+use common::layout_html;
+
+fn id(doc: &BaseDocument, selector: &str) -> usize {
+    doc.query_selector(selector).unwrap().unwrap()
+}
+
+fn hit(doc: &BaseDocument, x: f32, y: f32) -> usize {
+    doc.hit(x, y).unwrap().node_id
+}
+
+#[test]
+fn test_z_index_paint_and_hit_order() {
+    let doc = layout_html(
+        r#"<body style="margin: 0">
+            <style>div { position: absolute; width: 100px; height: 100px }</style>
+            <div id="top" style="left: 0; top: 0; z-index: 2"></div>
+            <div id="middle" style="left: 50px; top: 50px; z-index: 1"></div>
+            <div id="auto" style="left: 300px; top: 0"></div>
+            <div id="bottom" style="left: 0; top: 0; width: 200px; height: 200px; z-index: -1"></div>
+        </body>"#,
+    );
+    let (top, middle, auto, bottom) = (
+        id(&doc, "#top"),
+        id(&doc, "#middle"),
+        id(&doc, "#auto"),
+        id(&doc, "#bottom"),
+    );
+
+    // Positioned boxes are painted by the root stacking context in z-index order, with boxes with
+    // the same z-index in tree order
+    let root = doc.root_element();
+    let stacking_context = root.stacking_context.as_deref().unwrap();
+    assert_eq!(stacking_context.negative_z_descendants, [bottom]);
+    assert_eq!(stacking_context.positive_z_descendants, [auto, middle, top]);
+
+    // The box painted on top is hit where boxes overlap
+    assert_eq!(hit(&doc, 75.0, 75.0), top);
+    assert_eq!(hit(&doc, 125.0, 125.0), middle);
+    assert_eq!(hit(&doc, 175.0, 175.0), bottom);
+    assert_eq!(hit(&doc, 350.0, 50.0), auto);
+}
+
+#[test]
+fn test_nested_stacking_contexts() {
+    let doc = layout_html(
+        r#"<body style="margin: 0">
+            <style>div { position: absolute; left: 0; top: 0; width: 100px; height: 100px }</style>
+            <div id="parent" style="z-index: 1">
+                <div id="child" style="z-index: 100"></div>
+            </div>
+            <div id="sibling" style="z-index: 2"></div>
+        </body>"#,
+    );
+    let (parent, child, sibling) = (
+        id(&doc, "#parent"),
+        id(&doc, "#child"),
+        id(&doc, "#sibling"),
+    );
+
+    // The child's z-index only orders it within its parent's stacking context, so it is painted
+    // (and hit) below the parent's sibling
+    let root = doc.root_element();
+    let stacking_context = root.stacking_context.as_deref().unwrap();
+    assert_eq!(stacking_context.positive_z_descendants, [parent, sibling]);
+    let parent_node = doc.get_node(parent).unwrap();
+    let parent_context = parent_node.stacking_context.as_deref().unwrap();
+    assert_eq!(parent_context.positive_z_descendants, [child]);
+    assert_eq!(hit(&doc, 50.0, 50.0), sibling);
+}
@@ -0,0 +1,35 @@
+mod common;
+
+use common::layout_html;
+
+#[test]
+fn test_sticky_offsets_after_scrolling() {
+    let mut doc = layout_html(
+        r#"<body style="margin: 0">
+            <div id="scroller" style="height: 100px; overflow: auto">
+                <div style="height: 150px">
+                    <div style="height: 50px"></div>
+                    <div id="sticky" style="position: sticky; top: 0; height: 20px"></div>
+                </div>
+                <div style="height: 500px"></div>
+            </div>
+        </body>"#,
+    );
+    let scroller = doc.query_selector("#scroller").unwrap().unwrap();
+    let sticky = doc.query_selector("#sticky").unwrap().unwrap();
+    let offset = |doc: &blitz_dom::BaseDocument| doc.get_node(sticky).unwrap().sticky_offset.y;
+
+    assert_eq!(offset(&doc), 0.0);
+
+    // Once scrolled past, the box sticks to the top of the scrollport, and is hit there
+    doc.scroll_node_by(scroller, 0.0, -120.0);
+    assert_eq!(offset(&doc), 70.0);
+    assert_eq!(doc.hit(5.0, 5.0).unwrap().node_id, sticky);
+
+    // It stays within its containing block
+    doc.scroll_node_by(scroller, 0.0, -80.0);
+    assert_eq!(offset(&doc), 80.0);
+
+    doc.scroll_node_by(scroller, 0.0, 200.0);
+    assert_eq!(offset(&doc), 0.0);
+}
@@ -0,0 +1,69 @@
+mod common;
+
+use common::{border_box, layout_html};
+
+#[test]
+fn test_table_spans() {
+    let doc = layout_html(
+        r#"<body style="margin: 0">
+            <style>td { padding: 0 }</style>
+            <table style="border-spacing: 0">
+                <tr>
+                    <td id="a" rowspan="2" style="width: 50px; height: 40px"></td>
+                    <td id="b" style="width: 30px; height: 20px"></td>
+                    <td id="c" style="width: 30px; height: 20px"></td>
+                </tr>
+                <tr><td id="d" colspan="2" style="height: 20px"></td></tr>
+            </table>
+        </body>"#,
+    );
+
+    assert_eq!(border_box(&doc, "#a"), (0.0, 0.0, 50.0, 40.0));
+    assert_eq!(border_box(&doc, "#b"), (50.0, 0.0, 30.0, 20.0));
+    assert_eq!(border_box(&doc, "#c"), (80.0, 0.0, 30.0, 20.0));
+    assert_eq!(border_box(&doc, "#d"), (50.0, 20.0, 60.0, 20.0));
+}
+
+#[test]
+fn test_table_fixed_and_auto_layout() {
+    let doc = layout_html(
+        r#"<body style="margin: 0">
+            <style>
+                table { width: 300px; border-spacing: 0 }
+                td { padding: 0; height: 10px }
+            </style>
+            <table style="table-layout: fixed">
+                <tr><td id="fixed-a" style="width: 100px"><div style="width: 250px"></div></td><td id="fixed-b"></td></tr>
+            </table>
+            <table>
+                <tr><td id="auto-a" style="width: 100px"><div style="width: 250px"></div></td><td id="auto-b"></td></tr>
+            </table>
+        </body>"#,
+    );
+
+    // Fixed layout uses the specified widths, ignoring the content of the cells
+    assert_eq!(border_box(&doc, "#fixed-a").2, 100.0);
+    assert_eq!(border_box(&doc, "#fixed-b").2, 200.0);
+
+    // Auto layout widens columns to fit their content
+    assert_eq!(border_box(&doc, "#auto-a").2, 250.0);
+    assert_eq!(border_box(&doc, "#auto-b").2, 50.0);
+}
+
+#[test]
+fn test_table_captions() {
+    let doc = layout_html(
+        r#"<body style="margin: 0">
+            <table style="border-spacing: 0">
+                <caption id="top" style="height: 10px"></caption>
+                <caption id="bottom" style="caption-side: bottom; height: 15px"></caption>
+                <tr><td id="cell" style="padding: 0; width: 50px; height: 20px"></td></tr>
+            </table>
+        </body>"#,
+    );
+
+    // Captions are placed above and below the rows, and span all of the columns
+    assert_eq!(border_box(&doc, "#top"), (0.0, 0.0, 50.0, 10.0));
+    assert_eq!(border_box(&doc, "#cell"), (0.0, 10.0, 50.0, 20.0));
+    assert_eq!(border_box(&doc, "#bottom"), (0.0, 30.0, 50.0, 15.0));
+}
@@ -0,0 +1,27 @@
+mod common;
+
+use common::layout_html;
+
+#[test]
+fn test_hit_testing_nested_transforms() {
+    let doc = layout_html(
+        r#"<body style="margin: 0">
+            <div id="outer" style="position: absolute; left: 100px; top: 100px; width: 100px; height: 100px; transform: rotate(90deg)">
+                <div id="inner" style="width: 50px; height: 100px; transform: translateX(50px)"></div>
+            </div>
+        </body>"#,
+    );
+    let outer = doc.query_selector("#outer").unwrap().unwrap();
+    let inner = doc.query_selector("#inner").unwrap().unwrap();
+    let hit = |x, y| doc.hit(x, y).map(|hit| hit.node_id);
+
+    // The inner box is moved to the right half of the outer box, which is then rotated about its
+    // center so that the inner box covers its bottom half
+    assert_eq!(hit(150.0, 175.0), Some(inner));
+    assert_eq!(hit(160.0, 130.0), Some(outer));
+
+    // The result is in the hit node's untransformed coordinate space
+    let result = doc.hit(150.0, 175.0).unwrap();
+    assert!((result.x - 25.0).abs() < 0.01);
+    assert!((result.y - 50.0).abs() < 0.01);
+}
@@ -1,6 +1,6 @@
 use anyrender::PaintScene;
 use blitz_dom::BaseDocument;
-use kurbo::{Affine, Rect, Vec2};
+use kurbo::{Affine, Point, Rect, Vec2};
 
 use crate::color::Color;
 
@@ -10,9 +10,9 @@ pub(crate) fn render_debug_overlay(
     scene: &mut impl PaintScene,
     dom: &BaseDocument,
     node_id: usize,
+    origin: Point,
     scale: f64,
 ) {
-    let viewport_scroll = dom.as_ref().viewport_scroll();
     let mut node = &dom.as_ref().tree()[node_id];
 
     let taffy::Layout {
@@ -44,8 +44,8 @@ pub(crate) fn render_debug_overlay(
         abs_y += y;
     }
 
-    abs_x -= viewport_scroll.x as f32;
-    abs_y -= viewport_scroll.y as f32;
+    abs_x -= origin.x as f32;
+    abs_y -= origin.y as f32;
 
     // Hack: scale factor
     let abs_x = f64::from(abs_x) * scale;
@@ -0,0 +1,1151 @@
+//! A retained display list, which caches the drawing commands of each box so that only the boxes
+//! which have changed since the previous frame need to be re-recorded and repainted
+
+use std::any::Any;
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+use anyrender::{Glyph, NormalizedCoord, Paint, PaintRef, PaintScene};
+use blitz_dom::node::SpecialElementData;
+use blitz_dom::{BaseDocument, Node};
+use blitz_traits::devtools::DevtoolSettings;
+use kurbo::{Affine, BezPath, Point, Rect, Shape, Stroke, Vec2};
+use peniko::{BlendMode, Fill, FontData, Gradient, ImageBrush, ImageBrushRef, StyleRef};
+use style::properties::{
+    ComputedValues,
+    generated::longhands::background_attachment::single_value::computed_value::T as StyloBackgroundAttachment,
+};
+use style::servo_arc::Arc as ServoArc;
+use taffy::Layout;
+
+use crate::color::Color;
+use crate::layers::{maybe_with_layer, reset_layer_stats};
+use crate::render::BlitzDomPainter;
+
+const PATH_TOLERANCE: f64 = 0.1;
+
+/// Damaged rects are merged into their bounding rect if there are more than this many
+const MAX_DAMAGE_RECTS: usize = 16;
+
+/// A retained display list for a [`BaseDocument`].
+///
+/// The drawing commands of each box are recorded into an entry keyed by the box's node id, in
+/// which the boxes it paints (its children and stacking context descendants) are referenced rather
+/// than recorded. Entries are recorded in the coordinate space of their box, so moving a box (or
+/// scrolling the viewport) doesn't invalidate it. An entry is only re-recorded when its node is
+/// damaged (see [`DisplayList::invalidate`]) or its style, layout or scroll offset changes.
+///
+/// Each frame, [`DisplayList::update`] walks the entries which are out of date (and their ancestors)
+/// and computes the rects of the scene which have changed since the previous frame. Subtrees which
+/// are neither damaged nor moved are skipped. [`DisplayList::paint`] then replays the entries into a
+/// scene (or [`DisplayList::paint_damaged`] replays just the damaged rects, for renderers which draw
+/// over the previous frame).
+pub struct DisplayList {
+    entries: HashMap<usize, Entry>,
+    root_id: Option<usize>,
+    /// Nodes whose entries are re-recorded every frame (see [`is_volatile`])
+    volatile: HashSet<usize>,
+    scale: f64,
+    width: u32,
+    height: u32,
+    origin: Point,
+    devtools: DevtoolSettings,
+    /// The color and size of the canvas background
+    background: Option<(Color, (f64, f64))>,
+    /// Whether a select popup or debug overlay was painted over the document
+    has_overlay: bool,
+    /// Whether the whole scene needs to be repainted
+    repaint_all: bool,
+    frame: u64,
+    /// Entries which stopped being painted by their parent in this update
+    orphans: Vec<usize>,
+    damage: Vec<Rect>,
+}
+
+impl Default for DisplayList {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DisplayList {
+    pub fn new() -> Self {
+        Self {
+            entries: HashMap::new(),
+            root_id: None,
+            volatile: HashSet::new(),
+            scale: 1.0,
+            width: 0,
+            height: 0,
+            origin: Point::ZERO,
+            devtools: DevtoolSettings::default(),
+            background: None,
+            has_overlay: false,
+            repaint_all: true,
+            frame: 0,
+            orphans: Vec::new(),
+            damage: Vec::new(),
+        }
+    }
+
+    /// Mark the entries of damaged nodes (see [`BaseDocument::take_paint_damage`]) as needing to
+    /// be re-recorded
+    pub fn invalidate(&mut self, node_ids: impl IntoIterator<Item = usize>) {
+        for node_id in node_ids {
+            self.mark_dirty(node_id);
+        }
+    }
+
+    /// Mark the entry of a node as dirty, and its ancestors as having dirty descendants
+    fn mark_dirty(&mut self, node_id: usize) {
+        let Some(entry) = self.entries.get_mut(&node_id) else {
+            return;
+        };
+        entry.dirty = true;
+        let mut parent_id = entry.parent;
+        while let Some(entry) = parent_id.and_then(|id| self.entries.get_mut(&id)) {
+            if entry.dirty_descendants {
+                break;
+            }
+            entry.dirty_descendants = true;
+            parent_id = entry.parent;
+        }
+    }
+
+    /// Discard all entries, so that the whole document is re-recorded and repainted
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.volatile.clear();
+        self.root_id = None;
+        self.repaint_all = true;
+    }
+
+    /// The rects of the scene (in device pixels) which changed in the last update
+    pub fn damage(&self) -> &[Rect] {
+        &self.damage
+    }
+
+    /// Bring the display list up to date with the document, re-recording the entries which are out
+    /// of date, and return the rects of the scene (in device pixels) which have changed since the
+    /// previous update.
+    ///
+    /// This assumes that the styles and layout in the [`BaseDocument`] are already resolved, and that
+    /// damaged nodes have been passed to [`DisplayList::invalidate`].
+    pub fn update(&mut self, dom: &BaseDocument, scale: f64, width: u32, height: u32) -> &[Rect] {
+        let devtools = *dom.devtools();
+        if scale != self.scale
+            || width != self.width
+            || height != self.height
+            || devtools.show_layout != self.devtools.show_layout
+        {
+            self.clear();
+        }
+        let viewport_scroll = dom.viewport_scroll();
+        let origin = Point::new(viewport_scroll.x, viewport_scroll.y);
+        if origin != self.origin {
+            // Entries are positioned relative to the document, so scrolling the viewport moves the
+            // whole scene without invalidating any of them
+            self.repaint_all = true;
+        }
+        self.scale = scale;
+        self.width = width;
+        self.height = height;
+        self.origin = origin;
+        self.devtools = devtools;
+        self.frame += 1;
+        self.damage.clear();
+
+        reset_layer_stats();
+        let recording = RefCell::new(Vec::new());
+        let painter = BlitzDomPainter {
+            recording: Some(&recording),
+            ..self.painter(dom)
+        };
+
+        // The canvas background and overlays are painted outside of the entries
+        let background = painter
+            .background_color()
+            .map(|color| (color, painter.background_size()));
+        let has_overlay = dom.select_popup().is_some() || painter.debug_overlay_node().is_some();
+        if background != self.background || has_overlay || self.has_overlay {
+            self.repaint_all = true;
+        }
+        self.background = background;
+        self.has_overlay = has_overlay;
+
+        let root_id = dom.root_element().id;
+        if self.root_id != Some(root_id) {
+            self.clear();
+            self.root_id = Some(root_id);
+        }
+        for node_id in self.volatile.iter().copied().collect::<Vec<_>>() {
+            self.mark_dirty(node_id);
+        }
+        let transform = box_transform(dom, root_id, Affine::IDENTITY, scale);
+        self.visit(&painter, &recording, root_id, None, transform, false);
+
+        // Remove the entries of nodes which are no longer painted (unless they were moved to
+        // another parent in this update)
+        while let Some(node_id) = self.orphans.pop() {
+            if self
+                .entries
+                .get(&node_id)
+                .is_some_and(|entry| entry.frame != self.frame)
+            {
+                let entry = self.entries.remove(&node_id).unwrap();
+                self.volatile.remove(&node_id);
+                self.damage.extend(entry.document_bounds());
+                self.orphans.extend(entry.children());
+            }
+        }
+
+        let viewport = Rect::new(0.0, 0.0, width as f64, height as f64);
+        if std::mem::take(&mut self.repaint_all) {
+            self.damage = vec![viewport];
+        } else {
+            let viewport_offset = -self.origin.to_vec2() * scale;
+            self.damage = self
+                .damage
+                .iter()
+                .map(|rect| {
+                    (*rect + viewport_offset)
+                        .inflate(1.0, 1.0)
+                        .expand()
+                        .intersect(viewport)
+                })
+                .filter(|rect| !rect.is_zero_area())
+                .collect();
+            if self.damage.len() > MAX_DAMAGE_RECTS {
+                self.damage = self
+                    .damage
+                    .iter()
+                    .copied()
+                    .reduce(Rect::union)
+                    .into_iter()
+                    .collect();
+            }
+        }
+
+        &self.damage
+    }
+
+    /// Paint the display list (as of the last [`DisplayList::update`]) by pushing drawing commands
+    /// into an impl [`PaintScene`]
+    pub fn paint(&self, scene: &mut impl PaintScene, dom: &BaseDocument) {
+        reset_layer_stats();
+        let viewport = Rect::new(0.0, 0.0, self.width as f64, self.height as f64);
+        scene.reset();
+        self.painter(dom).paint_scene_with(scene, |scene| {
+            if let Some(root_id) = self.root_id {
+                self.replay(
+                    scene,
+                    root_id,
+                    viewport + self.origin.to_vec2() * self.scale,
+                );
+            }
+        });
+    }
+
+    /// Paint only the rects of the scene which were damaged in the last [`DisplayList::update`]
+    /// (clipped to those rects). This is for renderers which draw the scene over the previous frame
+    /// rather than clearing it.
+    pub fn paint_damaged(&self, scene: &mut impl PaintScene, dom: &BaseDocument) {
+        reset_layer_stats();
+        scene.reset();
+        let Some(bounds) = self.damage.iter().copied().reduce(Rect::union) else {
+            return;
+        };
+        let mut clip = BezPath::new();
+        for rect in &self.damage {
+            clip.extend(rect.path_elements(PATH_TOLERANCE));
+        }
+        let painter = self.painter(dom);
+        maybe_with_layer(scene, true, 1.0, Affine::IDENTITY, &clip, |scene| {
+            painter.paint_scene_with(scene, |scene| {
+                if let Some(root_id) = self.root_id {
+                    self.replay(scene, root_id, bounds + self.origin.to_vec2() * self.scale);
+                }
+            });
+        });
+    }
+
+    fn painter<'dom>(&self, dom: &'dom BaseDocument) -> BlitzDomPainter<'dom> {
+        BlitzDomPainter {
+            dom,
+            scale: self.scale,
+            width: self.width,
+            height: self.height,
+            origin: self.origin,
+            clip: None,
+            devtools: self.devtools,
+            recording: None,
+        }
+    }
+
+    /// The transform which maps the document to the scene
+    fn viewport_transform(&self) -> Affine {
+        Affine::translate(-self.origin.to_vec2() * self.scale)
+    }
+
+    /// Bring the entry of a node (and the entries of the boxes it paints) up to date, returning the
+    /// bounds of everything it paints in the document. `transform` maps the coordinate space of the
+    /// node's box to the document (in device pixels).
+    ///
+    /// Entries which are up to date, haven't moved and have no dirty descendants are skipped
+    /// without visiting their descendants.
+    fn visit(
+        &mut self,
+        painter: &BlitzDomPainter,
+        recording: &RefCell<Vec<Item>>,
+        node_id: usize,
+        parent_id: Option<usize>,
+        transform: Affine,
+        force_record: bool,
+    ) -> Option<Rect> {
+        let node = &painter.dom.tree()[node_id];
+        let old = self.entries.remove(&node_id);
+        let is_current = !force_record
+            && old
+                .as_ref()
+                .is_some_and(|entry| !entry.dirty && entry.key.matches(node));
+
+        // Skip the subtree of an entry which is up to date and hasn't moved
+        let old = match old {
+            Some(mut entry)
+                if is_current && !entry.dirty_descendants && entry.transform == transform =>
+            {
+                entry.parent = parent_id;
+                entry.frame = self.frame;
+                let subtree_bounds = entry.subtree_bounds;
+                self.entries.insert(node_id, entry);
+                return subtree_bounds;
+            }
+            old => old,
+        };
+
+        let old_bounds = old.as_ref().and_then(Entry::document_bounds);
+        let (mut entry, changed) = match old {
+            Some(entry) if is_current => {
+                let moved = entry.transform != transform;
+                (entry, moved)
+            }
+            old => {
+                let items = record(
+                    painter,
+                    recording,
+                    node_id,
+                    self.viewport_transform() * transform,
+                );
+                let changed = old
+                    .as_ref()
+                    .is_none_or(|old| old.transform != transform || old.items != items);
+                let entry = Entry::new(node, items);
+                if let Some(old) = &old {
+                    let children: HashSet<usize> = entry.children().collect();
+                    self.orphans
+                        .extend(old.children().filter(|id| !children.contains(id)));
+                }
+                if is_volatile(node) {
+                    self.volatile.insert(node_id);
+                } else {
+                    self.volatile.remove(&node_id);
+                }
+                (entry, changed)
+            }
+        };
+        entry.transform = transform;
+        entry.parent = parent_id;
+        entry.frame = self.frame;
+        entry.dirty = false;
+        entry.dirty_descendants = false;
+        if changed {
+            self.damage.extend(old_bounds);
+            self.damage.extend(entry.document_bounds());
+        }
+
+        let mut subtree_bounds = entry.document_bounds();
+        for item in &entry.items {
+            let Item::Node {
+                node_id: child_id,
+                transform: child_transform,
+            } = item
+            else {
+                continue;
+            };
+            let child_transform = box_transform(
+                painter.dom,
+                *child_id,
+                transform * *child_transform,
+                self.scale,
+            );
+            // Anonymous blocks aren't damaged themselves when the inline content they wrap changes
+            let force_record = !is_current && painter.dom.tree()[*child_id].is_anonymous();
+            let child_bounds = self.visit(
+                painter,
+                recording,
+                *child_id,
+                Some(node_id),
+                child_transform,
+                force_record,
+            );
+            subtree_bounds = union(subtree_bounds, child_bounds);
+        }
+        entry.subtree_bounds = subtree_bounds;
+
+        self.entries.insert(node_id, entry);
+        subtree_bounds
+    }
+
+    /// Replay the entry of a node (and the entries of the boxes it paints), skipping entries which
+    /// don't paint anything within `clip` (in device pixels, relative to the document)
+    fn replay(&self, scene: &mut impl PaintScene, node_id: usize, clip: Rect) {
+        let Some(entry) = self.entries.get(&node_id) else {
+            return;
+        };
+        if !entry
+            .subtree_bounds
+            .is_some_and(|bounds| overlaps(bounds, clip))
+        {
+            return;
+        }
+        let transform = self.viewport_transform() * entry.transform;
+        for item in &entry.items {
+            match item {
+                Item::Command(command) => command.draw(scene, transform),
+                Item::Node { node_id, .. } => self.replay(scene, *node_id, clip),
+            }
+        }
+    }
+}
+
+/// The transform which maps the coordinate space of a box to the document, given the transform of
+/// the coordinate space which its layout location is relative to
+fn box_transform(dom: &BaseDocument, node_id: usize, transform: Affine, scale: f64) -> Affine {
+    let node = &dom.tree()[node_id];
+    let location = node.unrounded_layout.location;
+    let offset = Vec2::new(
+        location.x as f64 + node.sticky_offset.x,
+        location.y as f64 + node.sticky_offset.y,
+    );
+    transform * Affine::translate(offset * scale)
+}
+
+/// Record the entry of a node, with `transform` mapping the coordinate space of its box to the scene
+fn record(
+    painter: &BlitzDomPainter,
+    recording: &RefCell<Vec<Item>>,
+    node_id: usize,
+    transform: Affine,
+) -> Vec<Item> {
+    // Nothing painted into a degenerate coordinate space is visible (and it can't be inverted)
+    if transform.determinant().abs() < 1e-12 {
+        return Vec::new();
+    }
+
+    let (_, position) = painter.node_position(node_id, Point::ZERO);
+    let parent_transform = transform * Affine::translate(-position.to_vec2() * painter.scale);
+    let mut recorder = Recorder { items: recording };
+    painter.render_element(&mut recorder, node_id, Point::ZERO, parent_transform);
+
+    // Commands are recorded in scene coordinates, so map them into the box's coordinate space
+    let inverse = transform.inverse();
+    let mut items = recording.take();
+    for item in &mut items {
+        match item {
+            Item::Command(command) => command.pre_transform(inverse),
+            Item::Node { transform, .. } => *transform = inverse * *transform,
+        }
+    }
+    items
+}
+
+/// Whether a node paints state which changes without it being damaged (the state of form controls,
+/// the content of canvases, or backgrounds fixed to the viewport), so that its entry needs to be
+/// re-recorded every frame
+fn is_volatile(node: &Node) -> bool {
+    let Some(element) = node.element_data() else {
+        return false;
+    };
+    let has_state = !matches!(
+        element.special_data,
+        SpecialElementData::None
+            | SpecialElementData::Image(_)
+            | SpecialElementData::TableRoot(_)
+            | SpecialElementData::Stylesheet(_)
+    );
+    has_state
+        || node.primary_styles().is_some_and(|style| {
+            style
+                .get_background()
+                .background_attachment
+                .0
+                .iter()
+                .any(|attachment| *attachment == StyloBackgroundAttachment::Fixed)
+        })
+}
+
+fn union(a: Option<Rect>, b: Option<Rect>) -> Option<Rect> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(a.union(b)),
+        (a, b) => a.or(b),
+    }
+}
+
+fn overlaps(a: Rect, b: Rect) -> bool {
+    a.x0 <= b.x1 && b.x0 <= a.x1 && a.y0 <= b.y1 && b.y0 <= a.y1
+}
+
+/// The recorded drawing commands of a box
+struct Entry {
+    items: Vec<Item>,
+    /// The state of the node which the entry was recorded with
+    key: EntryKey,
+    /// The entry which paints this entry
+    parent: Option<usize>,
+    /// Whether the node has been damaged since the entry was recorded
+    dirty: bool,
+    /// Whether any of the entries painted (directly or indirectly) by this entry are dirty
+    dirty_descendants: bool,
+    /// The transform which maps the entry's coordinate space to the document
+    transform: Affine,
+    /// The bounds of the entry's commands (in the entry's coordinate space)
+    bounds: Option<Rect>,
+    /// The bounds of the entry's commands and the entries it paints (in the document)
+    subtree_bounds: Option<Rect>,
+    /// The last frame in which the entry was painted
+    frame: u64,
+}
+
+impl Entry {
+    fn new(node: &Node, items: Vec<Item>) -> Self {
+        let bounds = items
+            .iter()
+            .filter_map(|item| match item {
+                Item::Command(command) => command.bounds(),
+                Item::Node { .. } => None,
+            })
+            .reduce(Rect::union);
+        Self {
+            items,
+            key: EntryKey::new(node),
+            parent: None,
+            dirty: false,
+            dirty_descendants: false,
+            transform: Affine::IDENTITY,
+            bounds,
+            subtree_bounds: None,
+            frame: 0,
+        }
+    }
+
+    fn document_bounds(&self) -> Option<Rect> {
+        self.bounds
+            .map(|bounds| self.transform.transform_rect_bbox(bounds))
+    }
+
+    /// The nodes whose entries are painted by this entry
+    fn children(&self) -> impl Iterator<Item = usize> + '_ {
+        self.items.iter().filter_map(|item| match item {
+            Item::Node { node_id, .. } => Some(*node_id),
+            Item::Command(_) => None,
+        })
+    }
+}
+
+/// The state of a node which its entry depends on (which isn't necessarily reported as damage
+/// when it changes). Changes to the paint order of a node are reported as damage by the document.
+struct EntryKey {
+    style: Option<ServoArc<ComputedValues>>,
+    layout: Layout,
+    final_layout: Layout,
+    scroll_offset: blitz_dom::Point<f64>,
+}
+
+impl EntryKey {
+    fn new(node: &Node) -> Self {
+        Self {
+            style: primary_style(node),
+            layout: node.unrounded_layout,
+            final_layout: node.final_layout,
+            scroll_offset: node.scroll_offset,
+        }
+    }
+
+    fn matches(&self, node: &Node) -> bool {
+        let style_matches = match (&self.style, &*node.stylo_element_data.borrow()) {
+            (Some(a), Some(data)) => ServoArc::ptr_eq(a, data.styles.primary()),
+            (None, None) => true,
+            _ => false,
+        };
+        style_matches
+            && self.layout == node.unrounded_layout
+            && self.final_layout == node.final_layout
+            && self.scroll_offset == node.scroll_offset
+    }
+}
+
+fn primary_style(node: &Node) -> Option<ServoArc<ComputedValues>> {
+    node.stylo_element_data
+        .borrow()
+        .as_ref()
+        .map(|element_data| element_data.styles.primary().clone())
+}
+
+/// An item of a display list entry
+#[derive(PartialEq)]
+pub(crate) enum Item {
+    Command(Command),
+    /// A box which is painted (from its own entry) at this point. The transform maps the coordinate
+    /// space which the box's layout location is relative to into the entry's coordinate space.
+    Node {
+        node_id: usize,
+        transform: Affine,
+    },
+}
+
+/// A recorded drawing command
+#[derive(PartialEq)]
+pub(crate) enum Command {
+    PushLayer {
+        blend: BlendMode,
+        alpha: f32,
+        transform: Affine,
+        clip: BezPath,
+    },
+    PopLayer,
+    Fill {
+        fill: Fill,
+        transform: Affine,
+        brush: Brush,
+        brush_transform: Option<Affine>,
+        path: BezPath,
+    },
+    Stroke {
+        stroke: Stroke,
+        transform: Affine,
+        brush: Brush,
+        brush_transform: Option<Affine>,
+        path: BezPath,
+    },
+    Glyphs(Box<GlyphRun>),
+    BoxShadow {
+        transform: Affine,
+        rect: Rect,
+        color: Color,
+        radius: f64,
+        std_dev: f64,
+    },
+    Image {
+        image: Image,
+        transform: Affine,
+    },
+}
+
+impl Command {
+    fn pre_transform(&mut self, transform: Affine) {
+        match self {
+            Command::PushLayer { transform: t, .. }
+            | Command::Fill { transform: t, .. }
+            | Command::Stroke { transform: t, .. }
+            | Command::BoxShadow { transform: t, .. }
+            | Command::Image { transform: t, .. } => *t = transform * *t,
+            Command::Glyphs(run) => run.transform = transform * run.transform,
+            Command::PopLayer => {}
+        }
+    }
+
+    /// Conservative bounds of the area painted by the command
+    fn bounds(&self) -> Option<Rect> {
+        match self {
+            Command::PushLayer { .. } | Command::PopLayer => None,
+            Command::Fill {
+                transform, path, ..
+            } => Some(transform.transform_rect_bbox(path.bounding_box())),
+            Command::Stroke {
+                stroke,
+                transform,
+                path,
+                ..
+            } => {
+                // Miter joins can extend beyond half the stroke width
+                let extent = stroke.width * stroke.miter_limit.max(1.0) / 2.0;
+                let bounds = path.bounding_box().inflate(extent, extent);
+                Some(transform.transform_rect_bbox(bounds))
+            }
+            Command::Glyphs(run) => run.bounds(),
+            Command::BoxShadow {
+                transform,
+                rect,
+                std_dev,
+                ..
+            } => {
+                let extent = std_dev * 3.0;
+                Some(transform.transform_rect_bbox(rect.inflate(extent, extent)))
+            }
+            Command::Image { image, transform } => {
+                let size = (image.0.image.width as f64, image.0.image.height as f64);
+                Some(transform.transform_rect_bbox(Rect::from_origin_size(Point::ZERO, size)))
+            }
+        }
+    }
+
+    fn draw(&self, scene: &mut impl PaintScene, transform: Affine) {
+        match self {
+            Command::PushLayer {
+                blend,
+                alpha,
+                transform: t,
+                clip,
+            } => scene.push_layer(*blend, *alpha, transform * *t, clip),
+            Command::PopLayer => scene.pop_layer(),
+            Command::Fill {
+                fill,
+                transform: t,
+                brush,
+                brush_transform,
+                path,
+            } => scene.fill(
+                *fill,
+                transform * *t,
+                brush.as_paint(),
+                *brush_transform,
+                path,
+            ),
+            Command::Stroke {
+                stroke,
+                transform: t,
+                brush,
+                brush_transform,
+                path,
+            } => scene.stroke(
+                stroke,
+                transform * *t,
+                brush.as_paint(),
+                *brush_transform,
+                path,
+            ),
+            Command::Glyphs(run) => scene.draw_glyphs(
+                &run.font,
+                run.font_size,
+                run.hint,
+                &run.normalized_coords,
+                run.style.as_style(),
+                run.brush.as_paint(),
+                run.brush_alpha,
+                transform * run.transform,
+                run.glyph_transform,
+                run.glyphs.iter().copied(),
+            ),
+            Command::BoxShadow {
+                transform: t,
+                rect,
+                color,
+                radius,
+                std_dev,
+            } => scene.draw_box_shadow(transform * *t, *rect, *color, *radius, *std_dev),
+            Command::Image {
+                image,
+                transform: t,
+            } => scene.draw_image(image.0.as_ref(), transform * *t),
+        }
+    }
+}
+
+/// A recorded paint
+pub(crate) enum Brush {
+    Solid(Color),
+    Gradient(Gradient),
+    Image(Image),
+    Custom(Arc<dyn Any + Send + Sync>),
+}
+
+impl Brush {
+    fn from_paint(paint: PaintRef<'_>) -> Self {
+        match paint {
+            Paint::Solid(color) => Brush::Solid(color),
+            Paint::Gradient(gradient) => Brush::Gradient(gradient.clone()),
+            Paint::Image(image) => Brush::Image(Image::new(image)),
+            Paint::Custom(custom) => Brush::Custom(custom),
+        }
+    }
+
+    fn as_paint(&self) -> PaintRef<'_> {
+        match self {
+            Brush::Solid(color) => Paint::Solid(*color),
+            Brush::Gradient(gradient) => Paint::Gradient(gradient),
+            Brush::Image(image) => Paint::Image(image.0.as_ref()),
+            Brush::Custom(custom) => Paint::Custom(custom.clone()),
+        }
+    }
+}
+
+impl PartialEq for Brush {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Brush::Solid(a), Brush::Solid(b)) => a == b,
+            (Brush::Gradient(a), Brush::Gradient(b)) => a == b,
+            (Brush::Image(a), Brush::Image(b)) => a == b,
+            // The content of custom paints (such as canvases) can change at any time
+            _ => false,
+        }
+    }
+}
+
+/// A recorded image (which is compared by the identity of its data)
+pub(crate) struct Image(ImageBrush);
+
+impl Image {
+    fn new(image: ImageBrushRef<'_>) -> Self {
+        Self(ImageBrush {
+            image: image.image.clone(),
+            sampler: image.sampler,
+        })
+    }
+}
+
+impl PartialEq for Image {
+    fn eq(&self, other: &Self) -> bool {
+        let (a, b) = (&self.0, &other.0);
+        a.image.data.id() == b.image.data.id()
+            && a.image.format == b.image.format
+            && a.image.alpha_type == b.image.alpha_type
+            && a.image.width == b.image.width
+            && a.image.height == b.image.height
+            && a.sampler.x_extend == b.sampler.x_extend
+            && a.sampler.y_extend == b.sampler.y_extend
+            && a.sampler.quality == b.sampler.quality
+            && a.sampler.alpha == b.sampler.alpha
+    }
+}
+
+/// Whether glyphs are filled or stroked
+#[derive(PartialEq)]
+pub(crate) enum GlyphStyle {
+    Fill(Fill),
+    Stroke(Stroke),
+}
+
+impl GlyphStyle {
+    fn as_style(&self) -> StyleRef<'_> {
+        match self {
+            GlyphStyle::Fill(fill) => StyleRef::Fill(*fill),
+            GlyphStyle::Stroke(stroke) => StyleRef::Stroke(stroke),
+        }
+    }
+}
+
+/// A recorded run of glyphs
+pub(crate) struct GlyphRun {
+    font: FontData,
+    font_size: f32,
+    hint: bool,
+    normalized_coords: Vec<NormalizedCoord>,
+    style: GlyphStyle,
+    brush: Brush,
+    brush_alpha: f32,
+    transform: Affine,
+    glyph_transform: Option<Affine>,
+    glyphs: Vec<Glyph>,
+}
+
+impl GlyphRun {
+    fn bounds(&self) -> Option<Rect> {
+        let mut glyphs = self.glyphs.iter();
+        let first = glyphs.next()?;
+        let origins = glyphs.fold(
+            Rect::from_points(
+                (first.x as f64, first.y as f64),
+                (first.x as f64, first.y as f64),
+            ),
+            |rect, glyph| rect.union_pt((glyph.x as f64, glyph.y as f64)),
+        );
+        // Glyphs extend (roughly) up to the font size from their origin, which the glyph transform
+        // (used for synthetic italics) may skew
+        let extent = self.font_size as f64 * 2.0;
+        Some(
+            self.transform
+                .transform_rect_bbox(origins.inflate(extent, extent)),
+        )
+    }
+}
+
+impl PartialEq for GlyphRun {
+    fn eq(&self, other: &Self) -> bool {
+        self.font.data.id() == other.font.data.id()
+            && self.font.index == other.font.index
+            && self.font_size == other.font_size
+            && self.hint == other.hint
+            && self.normalized_coords == other.normalized_coords
+            && self.style == other.style
+            && self.brush == other.brush
+            && self.brush_alpha == other.brush_alpha
+            && self.transform == other.transform
+            && self.glyph_transform == other.glyph_transform
+            && self.glyphs.len() == other.glyphs.len()
+            && self
+                .glyphs
+                .iter()
+                .zip(&other.glyphs)
+                .all(|(a, b)| a.id == b.id && a.x == b.x && a.y == b.y)
+    }
+}
+
+/// A [`PaintScene`] which records the commands pushed into it into a display list entry
+pub(crate) struct Recorder<'a> {
+    pub(crate) items: &'a RefCell<Vec<Item>>,
+}
+
+impl Recorder<'_> {
+    fn push(&mut self, command: Command) {
+        self.items.borrow_mut().push(Item::Command(command));
+    }
+}
+
+impl PaintScene for Recorder<'_> {
+    fn reset(&mut self) {
+        self.items.borrow_mut().clear();
+    }
+
+    fn push_layer(
+        &mut self,
+        blend: impl Into<BlendMode>,
+        alpha: f32,
+        transform: Affine,
+        clip: &impl Shape,
+    ) {
+        self.push(Command::PushLayer {
+            blend: blend.into(),
+            alpha,
+            transform,
+            clip: clip.to_path(PATH_TOLERANCE),
+        });
+    }
+
+    fn pop_layer(&mut self) {
+        self.push(Command::PopLayer);
+    }
+
+    fn stroke<'a>(
+        &mut self,
+        style: &Stroke,
+        transform: Affine,
+        brush: impl Into<PaintRef<'a>>,
+        brush_transform: Option<Affine>,
+        shape: &impl Shape,
+    ) {
+        self.push(Command::Stroke {
+            stroke: style.clone(),
+            transform,
+            brush: Brush::from_paint(brush.into()),
+            brush_transform,
+            path: shape.to_path(PATH_TOLERANCE),
+        });
+    }
+
+    fn fill<'a>(
+        &mut self,
+        style: Fill,
+        transform: Affine,
+        brush: impl Into<PaintRef<'a>>,
+        brush_transform: Option<Affine>,
+        shape: &impl Shape,
+    ) {
+        self.push(Command::Fill {
+            fill: style,
+            transform,
+            brush: Brush::from_paint(brush.into()),
+            brush_transform,
+            path: shape.to_path(PATH_TOLERANCE),
+        });
+    }
+
+    fn draw_glyphs<'a, 's: 'a>(
+        &'s mut self,
+        font: &'a FontData,
+        font_size: f32,
+        hint: bool,
+        normalized_coords: &'a [NormalizedCoord],
+        style: impl Into<StyleRef<'a>>,
+        brush: impl Into<PaintRef<'a>>,
+        brush_alpha: f32,
+        transform: Affine,
+        glyph_transform: Option<Affine>,
+        glyphs: impl Iterator<Item = Glyph>,
+    ) {
+        let style = match style.into() {
+            StyleRef::Fill(fill) => GlyphStyle::Fill(fill),
+            StyleRef::Stroke(stroke) => GlyphStyle::Stroke(stroke.clone()),
+        };
+        self.push(Command::Glyphs(Box::new(GlyphRun {
+            font: font.clone(),
+            font_size,
+            hint,
+            normalized_coords: normalized_coords.to_vec(),
+            style,
+            brush: Brush::from_paint(brush.into()),
+            brush_alpha,
+            transform,
+            glyph_transform,
+            glyphs: glyphs.collect(),
+        })));
+    }
+
+    fn draw_box_shadow(
+        &mut self,
+        transform: Affine,
+        rect: Rect,
+        brush: Color,
+        radius: f64,
+        std_dev: f64,
+    ) {
+        self.push(Command::BoxShadow {
+            transform,
+            rect,
+            color: brush,
+            radius,
+            std_dev,
+        });
+    }
+
+    fn draw_image(&mut self, image: ImageBrushRef, transform: Affine) {
+        self.push(Command::Image {
+            image: Image::new(image),
+            transform,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::layout_html;
+
+    #[test]
+    fn test_recorded_commands() {
+        let items = RefCell::new(Vec::new());
+        let mut recorder = Recorder { items: &items };
+        let rect = Rect::new(0.0, 0.0, 10.0, 20.0);
+        let transform = Affine::translate((5.0, 5.0));
+        recorder.fill(Fill::NonZero, transform, Color::BLACK, None, &rect);
+        recorder.stroke(&Stroke::new(2.0), transform, Color::BLACK, None, &rect);
+        let mut items = items.take();
+
+        let bounds: Vec<_> = items
+            .iter()
+            .map(|item| match item {
+                Item::Command(command) => command.bounds().unwrap(),
+                Item::Node { .. } => unreachable!(),
+            })
+            .collect();
+        assert_eq!(bounds[0], Rect::new(5.0, 5.0, 15.0, 25.0));
+        // Strokes are inflated by half their width (scaled by the miter limit)
+        assert_eq!(bounds[1], Rect::new(1.0, 1.0, 19.0, 29.0));
+
+        // Commands recorded in scene coordinates are mapped into the entry's coordinate space
+        for item in &mut items {
+            if let Item::Command(command) = item {
+                command.pre_transform(transform.inverse());
+            }
+        }
+        let Item::Command(command) = &items[0] else {
+            unreachable!();
+        };
+        assert_eq!(command.bounds(), Some(rect));
+    }
+
+    /// Whether every damaged rect lies within `bounds`
+    fn damage_within(damage: &[Rect], bounds: Rect) -> bool {
+        !damage.is_empty() && damage.iter().all(|rect| bounds.union(*rect) == bounds)
+    }
+
+    #[test]
+    fn test_update_damage() {
+        let mut doc = layout_html(
+            r#"<body style="margin: 0">
+                <div id="a" style="height: 50px; background: red">aaa</div>
+                <div id="b" style="height: 50px; background: blue"></div>
+            </body>"#,
+        );
+        let mut display_list = DisplayList::new();
+
+        // The first update repaints the whole viewport, and then nothing has changed
+        let viewport = Rect::new(0.0, 0.0, 200.0, 200.0);
+        assert_eq!(display_list.update(&doc, 1.0, 200, 200), [viewport]);
+        assert_eq!(display_list.update(&doc, 1.0, 200, 200), []);
+
+        // Restyling a box damages just that box (with a pixel of antialiasing either side)
+        let b = doc.query_selector("#b").unwrap().unwrap();
+        doc.set_style_property(b, "background-color", "green");
+        doc.resolve(0.0);
+        display_list.invalidate(doc.take_paint_damage());
+        let damage = display_list.update(&doc, 1.0, 200, 200);
+        assert!(damage_within(damage, Rect::new(0.0, 49.0, 200.0, 101.0)));
+
+        // Changing text repaints the inline root which paints it
+        let a = doc.query_selector("#a").unwrap().unwrap();
+        let text = doc.get_node(a).unwrap().children[0];
+        doc.mutate().set_node_text(text, "bbb");
+        doc.resolve(0.0);
+        display_list.invalidate(doc.take_paint_damage());
+        let damage = display_list.update(&doc, 1.0, 200, 200);
+        assert!(damage_within(damage, Rect::new(0.0, 0.0, 200.0, 51.0)));
+
+        // Explicitly invalidating a box which hasn't changed re-records it without damaging it
+        doc.invalidate_paint(b);
+        display_list.invalidate(doc.take_paint_damage());
+        assert_eq!(display_list.update(&doc, 1.0, 200, 200), []);
+
+        // Resizing the viewport repaints everything
+        let viewport = Rect::new(0.0, 0.0, 100.0, 200.0);
+        assert_eq!(display_list.update(&doc, 1.0, 100, 200), [viewport]);
+    }
+
+    #[test]
+    fn test_paint_damaged() {
+        let mut doc = layout_html(
+            r#"<body style="margin: 0">
+                <div style="height: 50px; margin-bottom: 10px; background: red"></div>
+                <div id="b" style="height: 50px; background: blue"></div>
+            </body>"#,
+        );
+        let mut display_list = DisplayList::new();
+        display_list.update(&doc, 1.0, 200, 200);
+
+        let b = doc.query_selector("#b").unwrap().unwrap();
+        doc.set_style_property(b, "background-color", "green");
+        doc.resolve(0.0);
+        display_list.invalidate(doc.take_paint_damage());
+        display_list.update(&doc, 1.0, 200, 200);
+
+        // Only the damaged box is painted, within a clip to the damaged rects
+        let items = RefCell::new(Vec::new());
+        display_list.paint_damaged(&mut Recorder { items: &items }, &doc);
+        let commands: Vec<Command> = items
+            .take()
+            .into_iter()
+            .filter_map(|item| match item {
+                Item::Command(command) => Some(command),
+                Item::Node { .. } => None,
+            })
+            .collect();
+        let Some(Command::PushLayer { clip, .. }) = commands.first() else {
+            panic!("damaged rects were not clipped");
+        };
+        assert!(damage_within(
+            &[clip.bounding_box()],
+            Rect::new(0.0, 59.0, 200.0, 111.0)
+        ));
+        let colors: Vec<_> = commands
+            .iter()
+            .filter_map(|command| match command {
+                Command::Fill {
+                    brush: Brush::Solid(color),
+                    ..
+                } if color.components[3] > 0.0 => Some(*color),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(colors, [Color::from_rgb8(0, 128, 0)]);
+    }
+}
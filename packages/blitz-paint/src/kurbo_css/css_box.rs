@@ -153,6 +153,64 @@ impl CssBox {
         path
     }
 
+    /// Construct a bezpath covering a band of the border area which runs parallel to the border
+    /// edge. `start` and `end` are fractions of the border width, measured inwards from the outer
+    /// edge of the border. The path must be filled using the even-odd fill rule.
+    ///
+    /// Used to draw double, groove and ridge borders.
+    pub fn border_band(&self, start: f64, end: f64) -> BezPath {
+        let mut path = self.with_border_fraction(start).padding_box_path();
+        path.extend(self.with_border_fraction(end).padding_box_path());
+        path
+    }
+
+    /// Construct a bezpath running along the middle of the border area.
+    ///
+    /// Used to draw dashed and dotted borders by stroking the path.
+    pub fn border_centerline(&self) -> BezPath {
+        self.with_border_fraction(0.5).padding_box_path()
+    }
+
+    /// A box whose border area is the outline area of this box, such that outlines can be drawn
+    /// using the same methods as borders.
+    pub fn outline_frame(&self) -> CssBox {
+        let width = self.outline_width;
+        let expand = |radii: Vec2| match radii.x == 0.0 || radii.y == 0.0 {
+            true => radii,
+            false => radii + Vec2::new(width, width),
+        };
+        let radii = NonUniformRoundedRectRadii {
+            top_left: expand(self.border_radii.top_left),
+            top_right: expand(self.border_radii.top_right),
+            bottom_right: expand(self.border_radii.bottom_right),
+            bottom_left: expand(self.border_radii.bottom_left),
+        };
+        CssBox::new(
+            self.outline_box,
+            Insets::uniform(width),
+            Insets::ZERO,
+            0.0,
+            radii,
+        )
+    }
+
+    /// A box with the same border box and radii as this one, but with the border widths scaled
+    fn with_border_fraction(&self, fraction: f64) -> CssBox {
+        let border = Insets {
+            x0: self.border_width.x0 * fraction,
+            y0: self.border_width.y0 * fraction,
+            x1: self.border_width.x1 * fraction,
+            y1: self.border_width.y1 * fraction,
+        };
+        CssBox::new(
+            self.border_box,
+            border,
+            Insets::ZERO,
+            0.0,
+            self.border_radii,
+        )
+    }
+
     /// Construct a bezpath drawing the frame border
     pub fn border_box_path(&self) -> BezPath {
         let mut path = BezPath::new();
@@ -191,6 +249,20 @@ impl CssBox {
         }
     }
 
+    /// The radii of the corners of the padding box
+    pub fn padding_box_radii(&self) -> NonUniformRoundedRectRadii {
+        let inner = |radii: Vec2, corner: Corner| {
+            let radii = radii - get_corner_insets(self.border_width, corner);
+            Vec2::new(radii.x.max(0.0), radii.y.max(0.0))
+        };
+        NonUniformRoundedRectRadii {
+            top_left: inner(self.border_radii.top_left, Corner::TopLeft),
+            top_right: inner(self.border_radii.top_right, Corner::TopRight),
+            bottom_right: inner(self.border_radii.bottom_right, Corner::BottomRight),
+            bottom_left: inner(self.border_radii.bottom_left, Corner::BottomLeft),
+        }
+    }
+
     /// Construct a bezpath drawing the frame
     pub fn shadow_clip(&self, shadow_rect: Rect) -> BezPath {
         let mut path = BezPath::new();
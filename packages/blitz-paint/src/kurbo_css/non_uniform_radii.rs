@@ -37,6 +37,47 @@ impl NonUniformRoundedRectRadii {
     }
 }
 
+impl NonUniformRoundedRectRadii {
+    /// Whether every corner has the same circular radius
+    pub fn is_uniform(&self) -> bool {
+        const EPSILON: f64 = 1e-3;
+        let radius = self.top_left.x;
+        [
+            self.top_left,
+            self.top_right,
+            self.bottom_right,
+            self.bottom_left,
+        ]
+        .iter()
+        .all(|r| (r.x - radius).abs() < EPSILON && (r.y - radius).abs() < EPSILON)
+    }
+
+    /// The radii of a shadow shape which is `spread` larger (or smaller, if negative) than the box
+    /// with these radii.
+    ///
+    /// See: <https://drafts.csswg.org/css-backgrounds/#shadow-shape>
+    pub fn spread(&self, spread: f64) -> Self {
+        let adjust = |radius: f64| {
+            if spread < 0.0 {
+                (radius + spread).max(0.0)
+            } else if radius >= spread {
+                radius + spread
+            } else {
+                // Radii smaller than the spread grow less, so that sharp corners stay sharp
+                let ratio = radius / spread;
+                radius + spread * (1.0 + (ratio - 1.0).powi(3))
+            }
+        };
+        let adjust_corner = |radii: Vec2| Vec2::new(adjust(radii.x), adjust(radii.y));
+        Self {
+            top_left: adjust_corner(self.top_left),
+            top_right: adjust_corner(self.top_right),
+            bottom_right: adjust_corner(self.bottom_right),
+            bottom_left: adjust_corner(self.bottom_left),
+        }
+    }
+}
+
 impl Mul<f64> for NonUniformRoundedRectRadii {
     type Output = Self;
 
@@ -58,3 +99,28 @@ impl MulAssign<f64> for NonUniformRoundedRectRadii {
         self.bottom_right *= rhs;
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_spread_radii() {
+        let radii = NonUniformRoundedRectRadii {
+            top_left: Vec2::new(10.0, 10.0),
+            top_right: Vec2::new(0.0, 0.0),
+            bottom_right: Vec2::new(2.0, 4.0),
+            bottom_left: Vec2::new(10.0, 10.0),
+        };
+        assert!(!radii.is_uniform());
+
+        let spread = radii.spread(4.0);
+        assert_eq!(spread.top_left, Vec2::new(14.0, 14.0));
+        assert_eq!(spread.top_right, Vec2::new(0.0, 0.0));
+        assert_eq!(spread.bottom_right, Vec2::new(5.5, 8.0));
+
+        let shrunk = radii.spread(-5.0);
+        assert_eq!(shrunk.top_left, Vec2::new(5.0, 5.0));
+        assert_eq!(shrunk.bottom_right, Vec2::new(0.0, 0.0));
+    }
+}
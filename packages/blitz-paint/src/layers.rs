@@ -1,6 +1,6 @@
 use anyrender::PaintScene;
 use kurbo::{Affine, Shape};
-use peniko::Mix;
+use peniko::{BlendMode, Mix};
 use std::sync::atomic::{AtomicUsize, Ordering};
 
 const LAYER_LIMIT: usize = 1024;
@@ -36,6 +36,25 @@ pub(crate) fn maybe_push_layer(
     opacity: f32,
     transform: Affine,
     shape: &impl Shape,
+) -> bool {
+    let blend_mode = if opacity == 1.0 {
+        #[allow(deprecated)]
+        Mix::Clip
+    } else {
+        Mix::Normal
+    };
+    maybe_push_blend_layer(scene, condition, blend_mode, opacity, transform, shape)
+}
+
+/// Push a layer which is composited with the specified blend mode (unlike clip layers, this
+/// always isolates the layer's content)
+pub(crate) fn maybe_push_blend_layer(
+    scene: &mut impl PaintScene,
+    condition: bool,
+    blend_mode: impl Into<BlendMode>,
+    opacity: f32,
+    transform: Affine,
+    shape: &impl Shape,
 ) -> bool {
     if !condition {
         return false;
@@ -47,14 +66,8 @@ pub(crate) fn maybe_push_layer(
     if !layers_available {
         return false;
     }
-    let blend_mode = if opacity == 1.0 {
-        #[allow(deprecated)]
-        Mix::Clip
-    } else {
-        Mix::Normal
-    };
 
-    // Actually push the clip layer
+    // Actually push the layer
     scene.push_layer(blend_mode, opacity, transform, shape);
 
     // Update accounting
@@ -3,15 +3,19 @@
 
 mod color;
 mod debug_overlay;
+mod display_list;
 mod gradient;
 mod kurbo_css;
 mod layers;
 mod render;
 mod sizing;
+#[cfg(test)]
+mod test_util;
 mod text;
 
 use anyrender::PaintScene;
-use blitz_dom::BaseDocument;
+use blitz_dom::{BaseDocument, Page};
+pub use display_list::DisplayList;
 use layers::reset_layer_stats;
 use render::BlitzDomPainter;
 
@@ -30,6 +34,35 @@ pub fn paint_scene(
     scale: f64,
     width: u32,
     height: u32,
+) {
+    let viewport_scroll = dom.viewport_scroll();
+    paint_scene_region(
+        scene,
+        dom,
+        scale,
+        viewport_scroll.x,
+        viewport_scroll.y,
+        width,
+        height,
+    );
+}
+
+/// Paint a region of a [`blitz_dom::BaseDocument`] (rather than the region currently scrolled into
+/// the viewport) by pushing drawing commands into an impl [`anyrender::PaintScene`].
+///
+/// The point `(x, y)` (in CSS pixels, relative to the top-left of the document) is painted at the
+/// origin of the scene, and `width` and `height` (in device pixels) are the size of the region.
+/// This can be used to paint the full height of a scrollable document, or a single element.
+///
+/// As with [`paint_scene`], the styles and layout in the [`BaseDocument`] must already be resolved.
+pub fn paint_scene_region(
+    scene: &mut impl PaintScene,
+    dom: &BaseDocument,
+    scale: f64,
+    x: f64,
+    y: f64,
+    width: u32,
+    height: u32,
 ) {
     reset_layer_stats();
 
@@ -39,7 +72,10 @@ pub fn paint_scene(
         scale,
         width,
         height,
+        origin: kurbo::Point::new(x, y),
+        clip: None,
         devtools,
+        recording: None,
     };
     generator.paint_scene(scene);
 
@@ -50,3 +86,35 @@ pub fn paint_scene(
     //     CLIPS_WANTED.load(atomic::Ordering::SeqCst)
     // );
 }
+
+/// Paint a page of a paginated [`blitz_dom::BaseDocument`] by pushing drawing commands into an impl
+/// [`anyrender::PaintScene`]. This must be called after [`BaseDocument::paginate`] returns the
+/// page, while the document is still paginated (before [`BaseDocument::end_pagination`]).
+///
+/// The page box is painted at the origin of the scene at `scale` device pixels per CSS pixel, with
+/// the fragment of the document displayed on the page clipped to the page's content area.
+pub fn paint_page(scene: &mut impl PaintScene, dom: &BaseDocument, page: &Page, scale: f64) {
+    reset_layer_stats();
+
+    let margin_left = page.margin.left as f64;
+    let margin_top = page.margin.top as f64;
+    let clip = kurbo::Rect::new(
+        margin_left,
+        margin_top,
+        margin_left + page.content_width() as f64,
+        margin_top + page.fragment_height as f64,
+    );
+
+    let devtools = *dom.devtools();
+    let generator = BlitzDomPainter {
+        dom,
+        scale,
+        width: (page.size.width as f64 * scale).ceil() as u32,
+        height: (page.size.height as f64 * scale).ceil() as u32,
+        origin: kurbo::Point::new(-margin_left, page.offset as f64 - margin_top),
+        clip: Some(clip.scale_from_origin(scale)),
+        devtools,
+        recording: None,
+    };
+    generator.paint_scene(scene);
+}
@@ -1,12 +1,15 @@
 mod background;
+mod border;
 mod box_shadow;
 mod form_controls;
 
+use std::cell::RefCell;
 use std::sync::Arc;
 
-use super::kurbo_css::{CssBox, Edge};
+use super::kurbo_css::CssBox;
 use crate::color::{Color, ToColorColor};
 use crate::debug_overlay::render_debug_overlay;
+use crate::display_list::Item;
 use crate::kurbo_css::NonUniformRoundedRectRadii;
 use crate::layers::maybe_with_layer;
 use crate::sizing::compute_object_fit;
@@ -15,10 +18,9 @@ use blitz_dom::node::{
     ListItemLayout, ListItemLayoutPosition, Marker, NodeData, RasterImageData, TextInputData,
     TextNodeData,
 };
-use blitz_dom::{BaseDocument, ElementData, Node, local_name};
+use blitz_dom::{BaseDocument, ElementData, Node, VerticalFlow, local_name};
 use blitz_traits::devtools::DevtoolSettings;
 
-use euclid::Transform3D;
 use style::values::computed::BorderCornerRadius;
 use style::{
     dom::TElement,
@@ -28,12 +30,13 @@ use style::{
     },
     values::{
         computed::{CSSPixelLength, Overflow},
-        specified::{BorderStyle, OutlineStyle, image::ImageRendering},
+        specified::{image::ImageRendering, text::TextOverflowSide},
     },
 };
 
 use kurbo::{self, Affine, Insets, Point, Rect, Stroke, Vec2};
 use peniko::{self, Fill, ImageData, ImageSampler};
+use style::properties::generated::longhands::object_fit::computed_value::T as ObjectFit;
 use style::values::generics::color::GenericColor;
 use taffy::Layout;
 
@@ -45,13 +48,25 @@ pub struct BlitzDomPainter<'dom> {
     pub(crate) scale: f64,
     pub(crate) width: u32,
     pub(crate) height: u32,
+    /// The position in the document (in CSS pixels) which is painted at the origin of the scene
+    pub(crate) origin: Point,
+    /// The rectangle of the scene (in device pixels) that the document is clipped to
+    pub(crate) clip: Option<Rect>,
     pub(crate) devtools: DevtoolSettings,
+    /// When recording a display list entry, the items of the entry. Boxes painted by the element
+    /// being recorded are recorded as placeholders (rather than being painted) and nothing is culled.
+    pub(crate) recording: Option<&'dom RefCell<Vec<Item>>>,
 }
 
 impl BlitzDomPainter<'_> {
-    fn node_position(&self, node: usize, location: Point) -> (Layout, Point) {
+    pub(crate) fn node_position(&self, node: usize, location: Point) -> (Layout, Point) {
         let layout = self.layout(node);
-        let pos = location + Vec2::new(layout.location.x as f64, layout.location.y as f64);
+        let sticky_offset = self.dom.as_ref().tree()[node].sticky_offset;
+        let pos = location
+            + Vec2::new(
+                layout.location.x as f64 + sticky_offset.x,
+                layout.location.y as f64 + sticky_offset.y,
+            );
         (layout, pos)
     }
 
@@ -67,14 +82,63 @@ impl BlitzDomPainter<'_> {
     /// Make sure you do those before trying to render
     pub fn paint_scene(&self, scene: &mut impl PaintScene) {
         // Simply render the document (the root element (note that this is not the same as the root node)))
+        let root_id = self.dom.as_ref().root_element().id;
         scene.reset();
-        let viewport_scroll = self.dom.as_ref().viewport_scroll();
+        self.paint_scene_with(scene, |scene| {
+            self.render_element(
+                scene,
+                root_id,
+                Point {
+                    x: -self.origin.x,
+                    y: -self.origin.y,
+                },
+                Affine::IDENTITY,
+            )
+        });
+    }
 
-        let root_element = self.dom.as_ref().root_element();
-        let root_id = root_element.id;
-        let bg_width = (self.width as f32).max(root_element.final_layout.size.width);
-        let bg_height = (self.height as f32).max(root_element.final_layout.size.height);
+    /// Draw the canvas background and the overlays drawn on top of the document, with the
+    /// document itself drawn by `paint_root`
+    pub(crate) fn paint_scene_with<S: PaintScene>(
+        &self,
+        scene: &mut S,
+        paint_root: impl FnOnce(&mut S),
+    ) {
+        let (bg_width, bg_height) = self.background_size();
+        if let Some(bg_color) = self.background_color() {
+            let rect = Rect::from_origin_size((0.0, 0.0), (bg_width, bg_height));
+            scene.fill(Fill::NonZero, Affine::IDENTITY, bg_color, None, &rect);
+        }
+
+        let clip = self.clip.unwrap_or_default();
+        maybe_with_layer(
+            scene,
+            self.clip.is_some(),
+            1.0,
+            Affine::IDENTITY,
+            &clip,
+            paint_root,
+        );
 
+        self.draw_select_popup(scene);
+
+        // Render debug overlay
+        if let Some(node_id) = self.debug_overlay_node() {
+            render_debug_overlay(scene, self.dom, node_id, self.origin, self.scale);
+        }
+    }
+
+    /// The size of the canvas background
+    pub(crate) fn background_size(&self) -> (f64, f64) {
+        let root_layout = self.dom.as_ref().root_element().final_layout;
+        let bg_width = (self.width as f32).max(root_layout.size.width);
+        let bg_height = (self.height as f32).max(root_layout.size.height);
+        (bg_width as f64, bg_height as f64)
+    }
+
+    /// The color of the canvas background (which is propagated from the root element or body)
+    pub(crate) fn background_color(&self) -> Option<Color> {
+        let root_element = self.dom.as_ref().root_element();
         let background_color = {
             let html_color = root_element
                 .primary_styles()
@@ -103,27 +167,15 @@ impl BlitzDomPainter<'_> {
             }
         };
 
-        if let Some(bg_color) = background_color {
-            let bg_color = bg_color.as_srgb_color();
-            let rect = Rect::from_origin_size((0.0, 0.0), (bg_width as f64, bg_height as f64));
-            scene.fill(Fill::NonZero, Affine::IDENTITY, bg_color, None, &rect);
-        }
-
-        self.render_element(
-            scene,
-            root_id,
-            Point {
-                x: -viewport_scroll.x,
-                y: -viewport_scroll.y,
-            },
-        );
+        background_color.map(|color| color.as_srgb_color())
+    }
 
-        // Render debug overlay
-        if self.devtools.highlight_hover {
-            if let Some(node_id) = self.dom.as_ref().get_hover_node_id() {
-                render_debug_overlay(scene, self.dom, node_id, self.scale);
-            }
+    /// The node which the debug overlay is drawn for (if any)
+    pub(crate) fn debug_overlay_node(&self) -> Option<usize> {
+        if !self.devtools.highlight_hover {
+            return None;
         }
+        self.dom.as_ref().get_hover_node_id()
     }
 
     /// Renders a node, but is guaranteed that the node is an element
@@ -135,7 +187,16 @@ impl BlitzDomPainter<'_> {
     ///
     /// Approaching rendering this way guarantees we have all the styles we need when rendering text with not having
     /// to traverse back to the parent for its styles, or needing to pass down styles
-    fn render_element(&self, scene: &mut impl PaintScene, node_id: usize, location: Point) {
+    ///
+    /// `parent_transform` is the combined CSS transform of the element's ancestors (in the same coordinate
+    /// space as `location`), which the element's own transform is composed with.
+    pub(crate) fn render_element(
+        &self,
+        scene: &mut impl PaintScene,
+        node_id: usize,
+        location: Point,
+        parent_transform: Affine,
+    ) {
         let node = &self.dom.as_ref().tree()[node_id];
 
         // Early return if the element is hidden
@@ -203,10 +264,15 @@ impl BlitzDomPainter<'_> {
             height: (size.height as f64 - scaled_pb.top - scaled_pb.bottom) * self.scale,
         };
 
-        // Don't render things that are out of view
+        // Don't render things that are out of view (transformed elements may be moved into view so
+        // we can't cull those based on their untransformed position)
         let scaled_y = box_position.y * self.scale;
         let scaled_content_height = content_size.height.max(size.height) as f64 * self.scale;
-        if scaled_y > self.height as f64 || scaled_y + scaled_content_height < 0.0 {
+        let is_transformed = parent_transform != Affine::IDENTITY || node.css_transform().is_some();
+        if !is_transformed
+            && self.recording.is_none()
+            && (scaled_y > self.height as f64 || scaled_y + scaled_content_height < 0.0)
+        {
             return;
         }
 
@@ -216,7 +282,7 @@ impl BlitzDomPainter<'_> {
             return;
         }
 
-        let mut cx = self.element_cx(node, layout, box_position);
+        let mut cx = self.element_cx(node, layout, box_position, parent_transform);
         cx.draw_outline(scene);
         cx.draw_outset_box_shadow(scene);
         cx.draw_background(scene);
@@ -243,32 +309,82 @@ impl BlitzDomPainter<'_> {
                 x: -node.scroll_offset.x,
                 y: -node.scroll_offset.y,
             });
+
+            // The content of the root of a vertical flow is laid out in the flow's coordinate space
+            let content_position = match node.vertical_flow_root() {
+                Some(flow) => {
+                    let t = node.content_transform().unwrap();
+                    cx.transform *= Affine::new([
+                        t.m11 as f64,
+                        t.m12 as f64,
+                        t.m21 as f64,
+                        t.m22 as f64,
+                        t.m31 as f64 * cx.scale,
+                        t.m32 as f64 * cx.scale,
+                    ]);
+                    let flow_pb = flow.transpose_rect(padding + border).map(f64::from);
+                    Point {
+                        x: cx.pos.x + flow_pb.left,
+                        y: cx.pos.y + flow_pb.top,
+                    }
+                }
+                None => content_position,
+            };
+            if let Some(stacking_context) = &node.stacking_context {
+                cx.draw_stacking_context_descendants(
+                    scene,
+                    &stacking_context.negative_z_descendants,
+                );
+            }
             cx.draw_image(scene);
             #[cfg(feature = "svg")]
             cx.draw_svg(scene);
             cx.draw_canvas(scene);
             cx.draw_input(scene);
 
+            cx.draw_range_input(scene);
+            cx.draw_progress_or_meter(scene);
+            cx.draw_select(scene, content_position);
             cx.draw_text_input_text(scene, content_position);
             cx.draw_inline_layout(scene, content_position);
             cx.draw_marker(scene, content_position);
             cx.draw_children(scene);
+            if let Some(stacking_context) = &node.stacking_context {
+                cx.draw_stacking_context_descendants(
+                    scene,
+                    &stacking_context.positive_z_descendants,
+                );
+            }
         });
     }
 
-    fn render_node(&self, scene: &mut impl PaintScene, node_id: usize, location: Point) {
+    fn render_node(
+        &self,
+        scene: &mut impl PaintScene,
+        node_id: usize,
+        location: Point,
+        parent_transform: Affine,
+    ) {
         let node = &self.dom.as_ref().tree()[node_id];
 
         match &node.data {
-            NodeData::Element(_) | NodeData::AnonymousBlock(_) => {
-                self.render_element(scene, node_id, location)
-            }
+            NodeData::Element(_) | NodeData::AnonymousBlock(_) => match self.recording {
+                // The node is painted from its own display list entry
+                Some(recording) => recording.borrow_mut().push(Item::Node {
+                    node_id,
+                    transform: parent_transform
+                        * Affine::translate(location.to_vec2() * self.scale),
+                }),
+                None => self.render_element(scene, node_id, location, parent_transform),
+            },
             NodeData::Text(TextNodeData { .. }) => {
                 // Text nodes should never be rendered directly
                 // (they should always be rendered as part of an inline layout)
                 // unreachable!()
             }
             NodeData::Document => {}
+            // Shadow roots are not part of the flat tree
+            NodeData::ShadowRoot(_) => {}
             // NodeData::Doctype => {}
             NodeData::Comment => {} // NodeData::ProcessingInstruction { .. } => {}
         }
@@ -279,6 +395,7 @@ impl BlitzDomPainter<'_> {
         node: &'w Node,
         layout: Layout,
         box_position: Point,
+        parent_transform: Affine,
     ) -> ElementCx<'w> {
         let style = node
             .stylo_element_data
@@ -298,42 +415,19 @@ impl BlitzDomPainter<'_> {
 
         // the bezpaths for every element are (potentially) cached (not yet, tbd)
         // By performing the transform, we prevent the cache from becoming invalid when the page shifts around
-        let mut transform = Affine::translate(box_position.to_vec2() * scale);
-
-        // Apply CSS transform property (where transforms are 2d)
-        //
-        // TODO: Handle hit testing correctly for transformed nodes
-        // TODO: Implement nested transforms
-        let (t, has_3d) = &style
-            .get_box()
-            .transform
-            .to_transform_3d_matrix(None)
-            .unwrap_or((Transform3D::default(), false));
-        if !has_3d {
-            // See: https://drafts.csswg.org/css-transforms-2/#two-dimensional-subset
-            // And https://docs.rs/kurbo/latest/kurbo/struct.Affine.html#method.new
-            let kurbo_transform =
-                Affine::new([t.m11, t.m12, t.m21, t.m22, t.m41, t.m42].map(|v| v as f64));
-
-            // Apply the transform origin by:
-            //   - Translating by the origin offset
-            //   - Applying our transform
-            //   - Translating by the inverse of the origin offset
-            let transform_origin = &style.get_box().transform_origin;
-            let origin_translation = Affine::translate(Vec2 {
-                x: transform_origin
-                    .horizontal
-                    .resolve(CSSPixelLength::new(frame.border_box.width() as f32))
-                    .px() as f64,
-                y: transform_origin
-                    .vertical
-                    .resolve(CSSPixelLength::new(frame.border_box.width() as f32))
-                    .px() as f64,
-            });
-            let kurbo_transform =
-                origin_translation * kurbo_transform * origin_translation.inverse();
-
-            transform *= kurbo_transform;
+        let mut transform = parent_transform * Affine::translate(box_position.to_vec2() * scale);
+
+        // Apply the CSS transform (which is in CSS pixels relative to the border box, so only
+        // the translation needs scaling)
+        if let Some(t) = node.css_transform() {
+            transform *= Affine::new([
+                t.m11 as f64,
+                t.m12 as f64,
+                t.m21 as f64,
+                t.m22 as f64,
+                t.m31 as f64 * scale,
+                t.m32 as f64 * scale,
+            ]);
         }
 
         let element = node.element_data().unwrap();
@@ -415,13 +509,31 @@ impl ElementCx<'_> {
                     panic!("Tried to render node marked as inline root that does not have an inline layout: {:?}", self.node);
                 });
 
+            // Lines which overflow the content box are truncated with the `text-overflow` marker if
+            // the overflow is clipped
+            let text_overflow = if self.style.get_box().overflow_x != Overflow::Visible {
+                match &self.style.get_text().text_overflow.second {
+                    TextOverflowSide::Clip => None,
+                    TextOverflowSide::Ellipsis => Some("\u{2026}"),
+                    TextOverflowSide::String(marker) => Some(&**marker),
+                }
+            } else {
+                None
+            };
+            let content_width = match self.node.vertical_flow_root() {
+                Some(_) => self.frame.content_box.height() as f32,
+                None => self.frame.content_box.width() as f32,
+            };
+
             // Render text
-            crate::text::stroke_text(
+            crate::text::stroke_inline_layout(
                 self.scale,
                 scene,
-                text_layout.layout.lines(),
+                text_layout,
                 self.context.dom,
-                pos,
+                self.transform_at(pos),
+                text_overflow.map(|marker| (content_width, marker)),
+                self.node.vertical_flow == Some(VerticalFlow::Lr),
             );
         }
     }
@@ -429,7 +541,7 @@ impl ElementCx<'_> {
     fn draw_text_input_text(&self, scene: &mut impl PaintScene, pos: Point) {
         // Render the text in text inputs
         if let Some(input_data) = self.text_input {
-            let transform = Affine::translate((pos.x * self.scale, pos.y * self.scale));
+            let transform = self.transform_at(pos);
 
             if self.node.is_focussed() {
                 // Render selection/caret
@@ -459,7 +571,7 @@ impl ElementCx<'_> {
                 scene,
                 input_data.editor.try_layout().unwrap().lines(),
                 self.context.dom,
-                pos,
+                transform,
             );
         }
     }
@@ -470,6 +582,11 @@ impl ElementCx<'_> {
             position: ListItemLayoutPosition::Outside(layout),
         }) = self.list_item
         {
+            // A loaded list-style-image replaces the marker
+            if self.draw_list_style_image(scene, pos, layout.scale()) {
+                return;
+            }
+
             // Right align and pad the bullet when rendering outside
             let x_padding = match marker {
                 Marker::Char(_) => 8.0,
@@ -496,133 +613,238 @@ impl ElementCx<'_> {
                 y: pos.y + y_offset as f64,
             };
 
-            crate::text::stroke_text(self.scale, scene, layout.lines(), self.context.dom, pos);
+            crate::text::stroke_text(
+                self.scale,
+                scene,
+                layout.lines(),
+                self.context.dom,
+                self.transform_at(pos),
+            );
         }
     }
 
+    /// Draw the list-style-image of an (outside) list item marker, returning false if the list item
+    /// has no loaded image
+    fn draw_list_style_image(&self, scene: &mut impl PaintScene, pos: Point, scale: f32) -> bool {
+        let Some(image) = self.element.list_style_image.as_ref() else {
+            return false;
+        };
+        let blitz_dom::node::ImageData::Raster(image) = &image.image else {
+            return false;
+        };
+
+        // Right align and pad the image, and align its bottom with the baseline of the first line
+        // of text in the list item
+        let width = image.width as f64;
+        let height = image.height as f64;
+        let baseline = self
+            .element
+            .inline_layout_data
+            .as_ref()
+            .and_then(|text_layout| text_layout.layout.lines().next())
+            .map(|line| (line.metrics().baseline / scale) as f64)
+            .unwrap_or(height);
+        let pos = Point {
+            x: pos.x - width - 8.0,
+            y: pos.y + baseline - height,
+        };
+
+        let quality = to_image_quality(self.style.clone_image_rendering());
+        let transform = self.transform_at(pos).pre_scale(self.scale);
+        scene.draw_image(to_peniko_image(image, quality).as_ref(), transform);
+        true
+    }
+
+    /// The transform for drawing content positioned at `pos` (which is in the same coordinate space as
+    /// `self.pos`), taking into account the CSS transforms of this element and its ancestors
+    fn transform_at(&self, pos: Point) -> Affine {
+        self.transform.pre_translate((pos - self.pos) * self.scale)
+    }
+
     fn draw_children(&self, scene: &mut impl PaintScene) {
+        // Children are positioned relative to `self.pos`, so undo that translation to get the
+        // combined transform of this element and its ancestors
+        let transform = self.transform * Affine::translate(-self.pos.to_vec2() * self.scale);
         if let Some(children) = &*self.node.paint_children.borrow() {
             for child_id in children {
-                self.render_node(scene, *child_id, self.pos);
+                self.render_node(scene, *child_id, self.pos, transform);
             }
         }
     }
 
-    #[cfg(feature = "svg")]
-    fn draw_svg(&self, scene: &mut impl PaintScene) {
-        use style::properties::generated::longhands::object_fit::computed_value::T as ObjectFit;
-
-        let Some(svg) = self.svg else {
-            return;
-        };
-
-        let width = self.frame.content_box.width() as u32;
-        let height = self.frame.content_box.height() as u32;
-        let svg_size = svg.size();
-
-        let x = self.frame.content_box.origin().x;
-        let y = self.frame.content_box.origin().y;
-
-        // let object_fit = self.style.clone_object_fit();
-        let object_position = self.style.clone_object_position();
+    /// Draw descendants which are painted by this element's stacking context. Each descendant is
+    /// positioned relative to its layout parent, which may be any (non-hoisted) descendant of this element.
+    fn draw_stacking_context_descendants(
+        &self,
+        scene: &mut impl PaintScene,
+        descendants: &[usize],
+    ) {
+        let transform = self.transform * Affine::translate(-self.pos.to_vec2() * self.scale);
+        for &descendant_id in descendants {
+            let offset = self
+                .node
+                .with(descendant_id)
+                .layout_parent
+                .get()
+                .map(|parent_id| self.node.with(parent_id).offset_from_ancestor(self.node.id))
+                .unwrap_or(taffy::Point::ZERO);
+            let parent_pos = Point {
+                x: self.pos.x + offset.x as f64,
+                y: self.pos.y + offset.y as f64,
+            };
+            self.render_node(scene, descendant_id, parent_pos, transform);
+        }
+    }
 
-        // Apply object-fit algorithm
+    /// Compute the rect (in the same coordinates as `self.frame`) which replaced content with the
+    /// specified natural size is painted into, according to `object-fit` and `object-position`
+    fn object_rect(&self, object_size: taffy::Size<f32>, object_fit: ObjectFit) -> Rect {
+        let content_box = self.frame.content_box;
         let container_size = taffy::Size {
-            width: width as f32,
-            height: height as f32,
+            width: content_box.width() as f32,
+            height: content_box.height() as f32,
         };
-        let object_size = taffy::Size {
-            width: svg_size.width(),
-            height: svg_size.height(),
-        };
-        let paint_size = compute_object_fit(container_size, Some(object_size), ObjectFit::Contain);
+        let object_size =
+            (object_size.width > 0.0 && object_size.height > 0.0).then_some(object_size);
+        let paint_size = compute_object_fit(container_size, object_size, object_fit);
 
         // Compute object-position
+        let object_position = self.style.clone_object_position();
         let x_offset = object_position.horizontal.resolve(
             CSSPixelLength::new(container_size.width - paint_size.width) / self.scale as f32,
         ) * self.scale as f32;
         let y_offset = object_position.vertical.resolve(
             CSSPixelLength::new(container_size.height - paint_size.height) / self.scale as f32,
         ) * self.scale as f32;
-        let x = x + x_offset.px() as f64;
-        let y = y + y_offset.px() as f64;
 
-        let x_scale = paint_size.width as f64 / object_size.width as f64;
-        let y_scale = paint_size.height as f64 / object_size.height as f64;
+        Rect::from_origin_size(
+            (
+                content_box.x0 + x_offset.px() as f64,
+                content_box.y0 + y_offset.px() as f64,
+            ),
+            (paint_size.width as f64, paint_size.height as f64),
+        )
+    }
+
+    /// Paint replaced content into `object_rect`, clipping it to the content box if it overflows
+    fn with_object_clip<S: PaintScene>(
+        &self,
+        scene: &mut S,
+        object_rect: Rect,
+        paint: impl FnOnce(&mut S),
+    ) {
+        const EPSILON: f64 = 0.01;
+        let content_box = self.frame.content_box;
+        let overflows = object_rect.x0 < content_box.x0 - EPSILON
+            || object_rect.y0 < content_box.y0 - EPSILON
+            || object_rect.x1 > content_box.x1 + EPSILON
+            || object_rect.y1 > content_box.y1 + EPSILON;
+        let clip = self.frame.content_box_path();
+        maybe_with_layer(scene, overflows, 1.0, self.transform, &clip, paint);
+    }
+
+    #[cfg(feature = "svg")]
+    fn draw_svg(&self, scene: &mut impl PaintScene) {
+        let Some(svg) = self.svg else {
+            return;
+        };
+
+        let svg_size = svg.size();
+        let object_size = taffy::Size {
+            width: svg_size.width(),
+            height: svg_size.height(),
+        };
+
+        // SVGs scale their viewBox into their viewport preserving their aspect ratio, so the
+        // default `object-fit: fill` behaves like `contain`
+        let object_fit = match self.style.clone_object_fit() {
+            ObjectFit::Fill => ObjectFit::Contain,
+            object_fit => object_fit,
+        };
+        let object_rect = self.object_rect(object_size, object_fit);
+        if object_rect.is_zero_area() {
+            return;
+        }
 
-        let transform =
-            Affine::translate((self.pos.x * self.scale + x, self.pos.y * self.scale + y))
-                .pre_scale_non_uniform(x_scale, y_scale);
+        let transform = self
+            .transform
+            .pre_translate(object_rect.origin().to_vec2())
+            .pre_scale_non_uniform(
+                object_rect.width() / object_size.width as f64,
+                object_rect.height() / object_size.height as f64,
+            );
 
-        anyrender_svg::render_svg_tree(scene, svg, transform);
+        self.with_object_clip(scene, object_rect, |scene| {
+            anyrender_svg::render_svg_tree(scene, svg, transform);
+        });
     }
 
     fn draw_image(&self, scene: &mut impl PaintScene) {
         if let Some(image) = self.element.raster_image_data() {
-            let width = self.frame.content_box.width() as u32;
-            let height = self.frame.content_box.height() as u32;
-            let x = self.frame.content_box.origin().x;
-            let y = self.frame.content_box.origin().y;
-
-            let object_fit = self.style.clone_object_fit();
-            let object_position = self.style.clone_object_position();
             let image_rendering = self.style.clone_image_rendering();
             let quality = to_image_quality(image_rendering);
 
-            // Apply object-fit algorithm
-            let container_size = taffy::Size {
-                width: width as f32,
-                height: height as f32,
-            };
             let object_size = taffy::Size {
                 width: image.width as f32,
                 height: image.height as f32,
             };
-            let paint_size = compute_object_fit(container_size, Some(object_size), object_fit);
-
-            // Compute object-position
-            let x_offset = object_position.horizontal.resolve(
-                CSSPixelLength::new(container_size.width - paint_size.width) / self.scale as f32,
-            ) * self.scale as f32;
-            let y_offset = object_position.vertical.resolve(
-                CSSPixelLength::new(container_size.height - paint_size.height) / self.scale as f32,
-            ) * self.scale as f32;
-            let x = x + x_offset.px() as f64;
-            let y = y + y_offset.px() as f64;
-
-            let x_scale = paint_size.width as f64 / object_size.width as f64;
-            let y_scale = paint_size.height as f64 / object_size.height as f64;
+            let object_rect = self.object_rect(object_size, self.style.clone_object_fit());
+            if object_rect.is_zero_area() {
+                return;
+            }
+
             let transform = self
                 .transform
-                .pre_scale_non_uniform(x_scale, y_scale)
-                .then_translate(Vec2 { x, y });
-
-            scene.draw_image(to_peniko_image(image, quality).as_ref(), transform);
+                .pre_translate(object_rect.origin().to_vec2())
+                .pre_scale_non_uniform(
+                    object_rect.width() / object_size.width as f64,
+                    object_rect.height() / object_size.height as f64,
+                );
+
+            self.with_object_clip(scene, object_rect, |scene| {
+                scene.draw_image(to_peniko_image(image, quality).as_ref(), transform);
+            });
         }
     }
 
     fn draw_canvas(&self, scene: &mut impl PaintScene) {
         if let Some(custom_paint_source) = self.element.canvas_data() {
-            let width = self.frame.content_box.width() as u32;
-            let height = self.frame.content_box.height() as u32;
-            let x = self.frame.content_box.origin().x;
-            let y = self.frame.content_box.origin().y;
-
-            let transform = self.transform.then_translate(Vec2 { x, y });
+            // The natural size of a canvas is set by its width and height attributes
+            let attr_size = |name, default| {
+                self.element
+                    .attr(name)
+                    .and_then(|val| val.trim().parse::<u32>().ok())
+                    .unwrap_or(default) as f32
+                    * self.scale as f32
+            };
+            let object_size = taffy::Size {
+                width: attr_size(local_name!("width"), 300),
+                height: attr_size(local_name!("height"), 150),
+            };
+            let object_rect = self.object_rect(object_size, self.style.clone_object_fit());
+            let width = object_rect.width().round() as u32;
+            let height = object_rect.height().round() as u32;
+            if width == 0 || height == 0 {
+                return;
+            }
 
-            scene.fill(
-                Fill::NonZero,
-                transform,
-                // TODO: replace `Arc<dyn Any>` with `CustomPaint` in API?
-                Paint::Custom(Arc::new(CustomPaint {
-                    source_id: custom_paint_source.custom_paint_source_id,
-                    width,
-                    height,
-                    scale: self.scale,
-                })),
-                None,
-                &Rect::from_origin_size((0.0, 0.0), (width as f64, height as f64)),
-            );
+            let transform = self.transform.pre_translate(object_rect.origin().to_vec2());
+
+            self.with_object_clip(scene, object_rect, |scene| {
+                scene.fill(
+                    Fill::NonZero,
+                    transform,
+                    // TODO: replace `Arc<dyn Any>` with `CustomPaint` in API?
+                    Paint::Custom(Arc::new(CustomPaint {
+                        source_id: custom_paint_source.custom_paint_source_id,
+                        width,
+                        height,
+                        scale: self.scale,
+                    })),
+                    None,
+                    &Rect::from_origin_size((0.0, 0.0), (width as f64, height as f64)),
+                );
+            });
         }
     }
 
@@ -641,118 +863,6 @@ impl ElementCx<'_> {
             scene.stroke(&stroke, self.transform, stroke_color, None, &shape);
         }
     }
-
-    /// Stroke a border
-    ///
-    /// The border-style property specifies what kind of border to display.
-    ///
-    /// The following values are allowed:
-    /// ❌ dotted - Defines a dotted border
-    /// ❌ dashed - Defines a dashed border
-    /// ✅ solid - Defines a solid border
-    /// ❌ double - Defines a double border
-    /// ❌ groove - Defines a 3D grooved border.
-    /// ❌ ridge - Defines a 3D ridged border.
-    /// ❌ inset - Defines a 3D inset border.
-    /// ❌ outset - Defines a 3D outset border.
-    /// ✅ none - Defines no border
-    /// ✅ hidden - Defines a hidden border
-    ///
-    /// The border-style property can have from one to four values (for the top border, right border, bottom border, and the left border).
-    fn draw_border(&self, sb: &mut impl PaintScene) {
-        for edge in [Edge::Top, Edge::Right, Edge::Bottom, Edge::Left] {
-            self.draw_border_edge(sb, edge);
-        }
-    }
-
-    /// The border-style property specifies what kind of border to display.
-    ///
-    /// [Border](https://www.w3schools.com/css/css_border.asp)
-    ///
-    /// The following values are allowed:
-    /// - ❌ dotted: Defines a dotted border
-    /// - ❌ dashed: Defines a dashed border
-    /// - ✅ solid: Defines a solid border
-    /// - ❌ double: Defines a double border
-    /// - ❌ groove: Defines a 3D grooved border*
-    /// - ❌ ridge: Defines a 3D ridged border*
-    /// - ❌ inset: Defines a 3D inset border*
-    /// - ❌ outset: Defines a 3D outset border*
-    /// - ✅ none: Defines no border
-    /// - ✅ hidden: Defines a hidden border
-    ///
-    /// [*] The effect depends on the border-color value
-    fn draw_border_edge(&self, sb: &mut impl PaintScene, edge: Edge) {
-        let style = &*self.style;
-        let border = style.get_border();
-        let path = self.frame.border_edge_shape(edge);
-
-        let current_color = style.clone_color();
-        let color = match edge {
-            Edge::Top => border
-                .border_top_color
-                .resolve_to_absolute(&current_color)
-                .as_srgb_color(),
-            Edge::Right => border
-                .border_right_color
-                .resolve_to_absolute(&current_color)
-                .as_srgb_color(),
-            Edge::Bottom => border
-                .border_bottom_color
-                .resolve_to_absolute(&current_color)
-                .as_srgb_color(),
-            Edge::Left => border
-                .border_left_color
-                .resolve_to_absolute(&current_color)
-                .as_srgb_color(),
-        };
-
-        let alpha = color.components[3];
-        if alpha != 0.0 {
-            sb.fill(Fill::NonZero, self.transform, color, None, &path);
-        }
-    }
-
-    /// ❌ dotted - Defines a dotted border
-    /// ❌ dashed - Defines a dashed border
-    /// ✅ solid - Defines a solid border
-    /// ❌ double - Defines a double border
-    /// ❌ groove - Defines a 3D grooved border. The effect depends on the border-color value
-    /// ❌ ridge - Defines a 3D ridged border. The effect depends on the border-color value
-    /// ❌ inset - Defines a 3D inset border. The effect depends on the border-color value
-    /// ❌ outset - Defines a 3D outset border. The effect depends on the border-color value
-    /// ✅ none - Defines no border
-    /// ✅ hidden - Defines a hidden border
-    fn draw_outline(&self, scene: &mut impl PaintScene) {
-        let outline = self.style.get_outline();
-
-        let current_color = self.style.clone_color();
-        let color = outline
-            .outline_color
-            .resolve_to_absolute(&current_color)
-            .as_srgb_color();
-
-        let style = match outline.outline_style {
-            OutlineStyle::Auto => return,
-            OutlineStyle::BorderStyle(style) => style,
-        };
-
-        let path = match style {
-            BorderStyle::None | BorderStyle::Hidden => return,
-            BorderStyle::Solid => self.frame.outline(),
-
-            // TODO: Implement other border styles
-            BorderStyle::Inset
-            | BorderStyle::Groove
-            | BorderStyle::Outset
-            | BorderStyle::Ridge
-            | BorderStyle::Dotted
-            | BorderStyle::Dashed
-            | BorderStyle::Double => self.frame.outline(),
-        };
-
-        scene.fill(Fill::NonZero, self.transform, color, None, &path);
-    }
 }
 impl<'a> std::ops::Deref for ElementCx<'a> {
     type Target = BlitzDomPainter<'a>;
@@ -800,3 +910,94 @@ fn create_css_rect(style: &ComputedValues, layout: &Layout, scale: f64) -> CssBo
 
     CssBox::new(border_box, border, padding, outline_width, border_radii)
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use blitz_dom::net::Resource;
+    use blitz_dom::util::ImageType;
+    use kurbo::Point;
+
+    use crate::display_list::Command;
+    use crate::test_util::{layout_html, paint_commands};
+
+    /// Lay out a 100x50 `<img>` with the given style, load a 20x20 raster into it and return the
+    /// commands which paint the document
+    fn paint_image(style: &str) -> Vec<Command> {
+        let mut doc = layout_html(&format!(
+            r#"<body style="margin: 0"><img id="img" style="display: block; width: 100px; height: 50px; {style}"></body>"#
+        ));
+        let node_id = doc.query_selector("#img").unwrap().unwrap();
+        doc.load_resource(Resource::Image(
+            node_id,
+            ImageType::Image,
+            20,
+            20,
+            Arc::new(vec![255; 20 * 20 * 4]),
+        ));
+        doc.resolve(0.0);
+        paint_commands(&doc)
+    }
+
+    /// The position of the image command and the transform which maps the image into the page
+    fn image_transform(commands: &[Command]) -> (usize, kurbo::Affine) {
+        commands
+            .iter()
+            .enumerate()
+            .find_map(|(idx, command)| match command {
+                Command::Image { transform, .. } => Some((idx, *transform)),
+                _ => None,
+            })
+            .expect("image was not painted")
+    }
+
+    fn assert_maps(transform: kurbo::Affine, from: (f64, f64), to: (f64, f64)) {
+        let mapped = transform * Point::new(from.0, from.1);
+        assert!(
+            (mapped - Point::new(to.0, to.1)).hypot() < 0.01,
+            "{from:?} mapped to {mapped:?}, expected {to:?}"
+        );
+    }
+
+    #[test]
+    fn test_object_fit_contain() {
+        // The image is scaled to 50x50 and positioned against the right edge
+        let commands = paint_image("object-fit: contain; object-position: right top");
+        let (idx, transform) = image_transform(&commands);
+        assert_maps(transform, (0.0, 0.0), (50.0, 0.0));
+        assert_maps(transform, (20.0, 20.0), (100.0, 50.0));
+
+        // The image fits inside the content box so it isn't clipped
+        assert!(!matches!(
+            commands[..idx].last(),
+            Some(Command::PushLayer { .. })
+        ));
+    }
+
+    #[test]
+    fn test_object_fit_cover() {
+        // The image is scaled to 100x100 and 75% of the overflow is above the content box
+        let commands = paint_image("object-fit: cover; object-position: 25% 75%");
+        let (idx, transform) = image_transform(&commands);
+        assert_maps(transform, (0.0, 0.0), (0.0, -37.5));
+        assert_maps(transform, (20.0, 20.0), (100.0, 62.5));
+
+        // The overflowing image is clipped to the content box
+        let Some(Command::PushLayer { clip, .. }) = commands[..idx].last() else {
+            panic!("image was not clipped");
+        };
+        let bounds = kurbo::Shape::bounding_box(clip);
+        assert_eq!(bounds, kurbo::Rect::new(0.0, 0.0, 100.0, 50.0));
+        assert!(matches!(commands.get(idx + 1), Some(Command::PopLayer)));
+    }
+
+    #[test]
+    fn test_object_fit_none() {
+        // The image keeps its natural size and is centered by default
+        let commands = paint_image("object-fit: none");
+        let (_, transform) = image_transform(&commands);
+        assert_maps(transform, (0.0, 0.0), (40.0, 15.0));
+        assert_maps(transform, (20.0, 20.0), (60.0, 35.0));
+    }
+}
@@ -3,12 +3,14 @@ use crate::color::{Color, ToColorColor};
 use crate::gradient::to_peniko_gradient;
 use crate::layers::maybe_with_layer;
 use anyrender::PaintScene;
-use blitz_dom::node::ImageData;
-use kurbo::{self, BezPath, Point, Rect, Shape, Size, Vec2};
+use blitz_dom::VerticalFlow;
+use blitz_dom::node::{ImageData, RasterImageData};
+use kurbo::{self, Affine, BezPath, Rect, Size, Vec2};
 use peniko::{self, Fill};
 use style::{
     properties::{
         generated::longhands::{
+            background_attachment::single_value::computed_value::T as StyloBackgroundAttachment,
             background_clip::single_value::computed_value::T as StyloBackgroundClip,
             background_origin::single_value::computed_value::T as StyloBackgroundOrigin,
         },
@@ -24,73 +26,143 @@ use style::{
 #[cfg(feature = "tracing")]
 use tracing::warn;
 
+/// The image of a background layer
+enum BackgroundSource<'a> {
+    Raster(&'a RasterImageData),
+    #[cfg(feature = "svg")]
+    Svg(&'a usvg::Tree),
+    Gradient(&'a StyloGradient),
+}
+
+impl BackgroundSource<'_> {
+    /// The natural size of the image in CSS pixels (gradients have no natural size)
+    fn natural_size(&self) -> Option<Size> {
+        match self {
+            BackgroundSource::Raster(image) => {
+                Some(Size::new(image.width as f64, image.height as f64))
+            }
+            #[cfg(feature = "svg")]
+            BackgroundSource::Svg(svg) => {
+                let size = svg.size();
+                Some(Size::new(size.width() as f64, size.height() as f64))
+            }
+            BackgroundSource::Gradient(_) => None,
+        }
+        .filter(|size| size.width > 0.0 && size.height > 0.0)
+    }
+}
+
+/// The background painting area of a layer (`background-clip`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BackgroundClip {
+    BorderBox,
+    PaddingBox,
+    ContentBox,
+    /// The glyph outlines of the element's inline content
+    #[cfg_attr(
+        not(test),
+        allow(
+            dead_code,
+            reason = "Stylo's servo build doesn't parse `background-clip: text`"
+        )
+    )]
+    Text,
+}
+
+impl From<StyloBackgroundClip> for BackgroundClip {
+    fn from(clip: StyloBackgroundClip) -> Self {
+        match clip {
+            StyloBackgroundClip::BorderBox => BackgroundClip::BorderBox,
+            StyloBackgroundClip::PaddingBox => BackgroundClip::PaddingBox,
+            StyloBackgroundClip::ContentBox => BackgroundClip::ContentBox,
+        }
+    }
+}
+
+/// The tiles of a background layer along one axis
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct TileRun {
+    /// The position of the first tile
+    start: f64,
+    /// The distance between the starts of consecutive tiles
+    step: f64,
+    count: usize,
+}
+
+impl TileRun {
+    /// The end of the last tile
+    fn end(&self, tile_length: f64) -> f64 {
+        match self.count {
+            0 => self.start,
+            count => self.start + (count - 1) as f64 * self.step + tile_length,
+        }
+    }
+}
+
 impl ElementCx<'_> {
     pub(super) fn draw_background(&self, scene: &mut impl PaintScene) {
-        use GenericImage::*;
-        use StyloBackgroundClip::*;
-
         let bg_styles = &self.style.get_background();
+        let layer_count = bg_styles.background_image.0.len();
 
-        let background_clip = get_cyclic(
-            &bg_styles.background_clip.0,
-            bg_styles.background_image.0.len() - 1,
-        );
-        let background_clip_path = match background_clip {
-            BorderBox => self.frame.border_box_path(),
-            PaddingBox => self.frame.padding_box_path(),
-            ContentBox => self.frame.content_box_path(),
+        // The background color is clipped to the background painting area of the bottom layer
+        let background_clip = *get_cyclic(&bg_styles.background_clip.0, layer_count - 1);
+        self.draw_solid_bg(scene, &self.background_clip_path(background_clip.into()));
+
+        // Layers are painted from the bottom (last) layer to the top (first) layer
+        for idx in (0..layer_count).rev() {
+            self.draw_background_layer(scene, idx);
+        }
+    }
+
+    fn background_clip_path(&self, background_clip: BackgroundClip) -> BezPath {
+        match background_clip {
+            BackgroundClip::BorderBox => self.frame.border_box_path(),
+            BackgroundClip::PaddingBox => self.frame.padding_box_path(),
+            BackgroundClip::ContentBox => self.frame.content_box_path(),
+            BackgroundClip::Text => self.text_clip_path(),
+        }
+    }
+
+    /// The glyph outlines of the element's inline layout, positioned as they are painted (see
+    /// `render_element` and `draw_inline_layout`). Elements without inline content clip their
+    /// background away entirely.
+    fn text_clip_path(&self) -> BezPath {
+        let text_layout = match self.node.flags.is_inline_root() {
+            true => self.element.inline_layout_data.as_deref(),
+            false => None,
+        };
+        let Some(text_layout) = text_layout else {
+            return BezPath::new();
         };
 
-        // Draw background color (if any)
-        self.draw_solid_bg(scene, &background_clip_path);
+        // The content of the root of a vertical flow is laid out in the flow's coordinate space
+        let layout = &self.node.final_layout;
+        let (content_transform, pb) = match self.node.vertical_flow_root() {
+            Some(flow) => {
+                let t = self.node.content_transform().unwrap();
+                let content_transform = Affine::new([
+                    t.m11 as f64,
+                    t.m12 as f64,
+                    t.m21 as f64,
+                    t.m22 as f64,
+                    t.m31 as f64 * self.scale,
+                    t.m32 as f64 * self.scale,
+                ]);
+                let pb = flow.transpose_rect(layout.padding + layout.border);
+                (content_transform, pb)
+            }
+            None => (Affine::IDENTITY, layout.padding + layout.border),
+        };
 
-        for (idx, segment) in bg_styles.background_image.0.iter().enumerate().rev() {
-            let background_clip = get_cyclic(&bg_styles.background_clip.0, idx);
-            let background_clip_path = match background_clip {
-                BorderBox => self.frame.border_box_path(),
-                PaddingBox => self.frame.padding_box_path(),
-                ContentBox => self.frame.content_box_path(),
-            };
+        let origin = Vec2::new(pb.left as f64, pb.top as f64);
+        let scroll_offset = Vec2::new(-self.node.scroll_offset.x, -self.node.scroll_offset.y);
 
-            maybe_with_layer(
-                scene,
-                true,
-                1.0,
-                self.transform,
-                &background_clip_path,
-                |scene| {
-                    match segment {
-                        None => {
-                            // Do nothing
-                        }
-                        Gradient(gradient) => {
-                            self.draw_gradient_bg(scene, gradient, idx, *background_clip)
-                        }
-                        Url(_) => {
-                            self.draw_raster_bg_image(scene, idx);
-                            #[cfg(feature = "svg")]
-                            self.draw_svg_bg_image(scene, idx);
-                        }
-                        LightDark(_) => {
-                            #[cfg(feature = "tracing")]
-                            warn!("Implement background drawing for ImageLightDark")
-                        }
-                        PaintWorklet(_) => {
-                            #[cfg(feature = "tracing")]
-                            warn!("Implement background drawing for Image::PaintWorklet")
-                        }
-                        CrossFade(_) => {
-                            #[cfg(feature = "tracing")]
-                            warn!("Implement background drawing for Image::CrossFade")
-                        }
-                        ImageSet(_) => {
-                            #[cfg(feature = "tracing")]
-                            warn!("Implement background drawing for Image::ImageSet")
-                        }
-                    }
-                },
-            );
-        }
+        let flip_lines = self.node.vertical_flow == Some(VerticalFlow::Lr);
+        let outline = crate::text::inline_layout_outline(text_layout, self.scale, flip_lines);
+        Affine::translate(scroll_offset)
+            * content_transform
+            * Affine::translate(origin * self.scale)
+            * outline
     }
 
     fn draw_solid_bg(&self, scene: &mut impl PaintScene, shape: &BezPath) {
@@ -106,550 +178,302 @@ impl ElementCx<'_> {
         }
     }
 
-    #[cfg(feature = "svg")]
-    fn draw_svg_bg_image(&self, scene: &mut impl PaintScene, idx: usize) {
-        let bg_image = self.element.background_images.get(idx);
+    /// The image of a background layer (if it is loaded)
+    fn background_source(&self, idx: usize) -> Option<BackgroundSource<'_>> {
+        use GenericImage::*;
 
-        let Some(Some(bg_image)) = bg_image.as_ref() else {
-            return;
-        };
-        let ImageData::Svg(svg) = &bg_image.image else {
-            return;
-        };
+        match &self.style.get_background().background_image.0[idx] {
+            None => Option::None,
+            Gradient(gradient) => Some(BackgroundSource::Gradient(gradient)),
+            // The image selected from an `image-set()` is loaded like a `url()` image
+            Url(_) | ImageSet(_) => {
+                let bg_image = self.element.background_images.get(idx)?.as_ref()?;
+                match &bg_image.image {
+                    ImageData::Raster(image) => Some(BackgroundSource::Raster(image)),
+                    #[cfg(feature = "svg")]
+                    ImageData::Svg(svg) => Some(BackgroundSource::Svg(svg)),
+                    ImageData::None => Option::None,
+                }
+            }
+            LightDark(_) => {
+                #[cfg(feature = "tracing")]
+                warn!("Implement background drawing for ImageLightDark");
+                Option::None
+            }
+            PaintWorklet(_) => {
+                #[cfg(feature = "tracing")]
+                warn!("Implement background drawing for Image::PaintWorklet");
+                Option::None
+            }
+            CrossFade(_) => {
+                #[cfg(feature = "tracing")]
+                warn!("Implement background drawing for Image::CrossFade");
+                Option::None
+            }
+        }
+    }
 
+    /// The background positioning area of a layer
+    fn background_positioning_area(&self, idx: usize) -> Rect {
         let bg_styles = &self.style.get_background();
 
-        let frame_w = self.frame.padding_box.width() as f32;
-        let frame_h = self.frame.padding_box.height() as f32;
-
-        let svg_size = svg.size();
-        let bg_size = compute_background_size(
-            bg_styles,
-            frame_w,
-            frame_h,
-            idx,
-            BackgroundSizeComputeMode::Size(
-                svg_size.width() / self.scale as f32,
-                svg_size.height() / self.scale as f32,
-            ),
-            self.scale as f32,
-        );
-        let bg_size = bg_size * self.scale;
-
-        let x_ratio = bg_size.width as f64 / svg_size.width() as f64;
-        let y_ratio = bg_size.height as f64 / svg_size.height() as f64;
-
-        let bg_pos = compute_background_position(
-            bg_styles,
-            idx,
-            frame_w - bg_size.width as f32,
-            frame_h - bg_size.height as f32,
-        );
-
-        let transform = kurbo::Affine::translate((
-            (self.pos.x * self.scale) + bg_pos.x,
-            (self.pos.y * self.scale) + bg_pos.y,
-        ))
-        .pre_scale_non_uniform(x_ratio, y_ratio);
+        // Fixed backgrounds are positioned relative to the viewport
+        let attachment = *get_cyclic(&bg_styles.background_attachment.0, idx);
+        if attachment == StyloBackgroundAttachment::Fixed {
+            let viewport = Rect::new(
+                0.0,
+                0.0,
+                self.context.width as f64,
+                self.context.height as f64,
+            );
+            return self.transform.inverse().transform_rect_bbox(viewport);
+        }
 
-        anyrender_svg::render_svg_tree(scene, svg, transform);
+        match get_cyclic(&bg_styles.background_origin.0, idx) {
+            StyloBackgroundOrigin::BorderBox => self.frame.border_box,
+            StyloBackgroundOrigin::PaddingBox => self.frame.padding_box,
+            StyloBackgroundOrigin::ContentBox => self.frame.content_box,
+        }
     }
 
-    fn draw_raster_bg_image(&self, scene: &mut impl PaintScene, idx: usize) {
+    fn draw_background_layer(&self, scene: &mut impl PaintScene, idx: usize) {
         use BackgroundRepeatKeyword::*;
 
-        let bg_image = self.element.background_images.get(idx);
-
-        let Some(Some(bg_image)) = bg_image.as_ref() else {
-            return;
-        };
-        let ImageData::Raster(image_data) = &bg_image.image else {
+        let Some(source) = self.background_source(idx) else {
             return;
         };
 
-        let image_rendering = self.style.clone_image_rendering();
-        let quality = to_image_quality(image_rendering);
-
         let bg_styles = &self.style.get_background();
-
-        let background_origin = get_cyclic(&bg_styles.background_origin.0, idx);
-        let origin_rect = match background_origin {
-            StyloBackgroundOrigin::BorderBox => self.frame.border_box,
-            StyloBackgroundOrigin::PaddingBox => self.frame.padding_box,
-            StyloBackgroundOrigin::ContentBox => self.frame.content_box,
+        let background_clip = BackgroundClip::from(*get_cyclic(&bg_styles.background_clip.0, idx));
+        let painting_area = match background_clip {
+            BackgroundClip::BorderBox | BackgroundClip::Text => self.frame.border_box,
+            BackgroundClip::PaddingBox => self.frame.padding_box,
+            BackgroundClip::ContentBox => self.frame.content_box,
         };
-
-        let image_width = image_data.width as f64;
-        let image_height = image_data.height as f64;
-
-        let (bg_pos, bg_size) = compute_background_position_and_background_size(
-            bg_styles,
-            origin_rect.width() / self.scale,
-            origin_rect.height() / self.scale,
-            idx,
-            BackgroundSizeComputeMode::Size(image_width as f32, image_height as f32),
-        );
-
-        let bg_pos_x = bg_pos.x * self.scale;
-        let bg_pos_y = bg_pos.y * self.scale;
-        let bg_size = bg_size * self.scale;
-
-        let x_ratio = bg_size.width / image_width;
-        let y_ratio = bg_size.height / image_height;
-
+        let positioning_area = self.background_positioning_area(idx);
         let BackgroundRepeat(repeat_x, repeat_y) = get_cyclic(&bg_styles.background_repeat.0, idx);
+        let (repeat_x, repeat_y) = (*repeat_x, *repeat_y);
 
-        let transform = self.transform.pre_scale_non_uniform(x_ratio, y_ratio);
-        let (origin_rect, transform) = match repeat_x {
-            Repeat | Round => {
-                let extend_width = extend(bg_pos_x, bg_size.width);
-
-                let transform = transform.then_translate(Vec2 {
-                    x: origin_rect.x0 - extend_width,
-                    y: 0.0,
-                });
-
-                let origin_rect = origin_rect.with_size(Size::new(
-                    (origin_rect.width() + extend_width) / x_ratio,
-                    origin_rect.height(),
-                ));
-
-                (origin_rect, transform)
-            }
-            Space => (origin_rect, transform),
-            NoRepeat => {
-                let transform = transform.then_translate(Vec2 {
-                    x: origin_rect.x0 + bg_pos_x,
-                    y: 0.0,
-                });
-
-                let origin_rect =
-                    origin_rect.with_size(Size::new(image_width, origin_rect.height()));
-
-                (origin_rect, transform)
-            }
-        };
-        let (origin_rect, transform) = match repeat_y {
-            Repeat | Round => {
-                let extend_height = extend(bg_pos_y, bg_size.height);
-
-                let transform = transform.then_translate(Vec2 {
-                    x: 0.0,
-                    y: origin_rect.y0 - extend_height,
-                });
-
-                let origin_rect = origin_rect.with_size(Size::new(
-                    origin_rect.width(),
-                    (origin_rect.height() + extend_height) / y_ratio,
-                ));
+        // Compute the tile size (in CSS pixels)
+        let scale = self.scale;
+        let area_size = Size::new(
+            positioning_area.width() / scale,
+            positioning_area.height() / scale,
+        );
+        let natural_size = source.natural_size();
+        let mut tile_size = compute_background_size(bg_styles, idx, area_size, natural_size);
+        if tile_size.width <= 0.0 || tile_size.height <= 0.0 {
+            return;
+        }
 
-                (origin_rect, transform)
-            }
-            Space => (origin_rect, transform),
-            NoRepeat => {
-                let transform = transform.then_translate(Vec2 {
-                    x: 0.0,
-                    y: origin_rect.y0 + bg_pos_y,
-                });
-
-                let origin_rect =
-                    origin_rect.with_size(Size::new(origin_rect.width(), image_height));
-                (origin_rect, transform)
+        // `round` scales the tiles so that a whole number of them fit the positioning area. If
+        // only one axis is rounded and the other has an auto size, the aspect ratio is preserved.
+        let (auto_width, auto_height) = background_size_is_auto(bg_styles, idx);
+        let rounded_width = round_tile_length(area_size.width, tile_size.width);
+        let rounded_height = round_tile_length(area_size.height, tile_size.height);
+        match (repeat_x, repeat_y) {
+            (Round, Round) => tile_size = Size::new(rounded_width, rounded_height),
+            (Round, _) => {
+                if auto_height {
+                    tile_size.height *= rounded_width / tile_size.width;
+                }
+                tile_size.width = rounded_width;
             }
-        };
-
-        if matches!(repeat_x, Space) || matches!(repeat_y, Space) {
-            let (origin_rect, transform, width_count, width_gap) = if matches!(repeat_x, Space) {
-                let (count, gap) = compute_space_count_and_gap(origin_rect.width(), bg_size.width);
-
-                let transform = if count == 1 {
-                    transform.then_translate(Vec2 {
-                        x: bg_pos_x,
-                        y: 0.0,
-                    })
-                } else {
-                    transform
-                };
-
-                let origin_rect =
-                    origin_rect.with_size(Size::new(image_width, origin_rect.height()));
-
-                (origin_rect, transform, count, gap)
-            } else {
-                (origin_rect, transform, 1, 0.0)
-            };
-
-            let (origin_rect, transform, height_count, height_gap) = if matches!(repeat_y, Space) {
-                let (count, gap) =
-                    compute_space_count_and_gap(origin_rect.height(), bg_size.height);
-
-                let transform = if count == 1 {
-                    transform.then_translate(Vec2 {
-                        x: 0.0,
-                        y: bg_pos_y,
-                    })
-                } else {
-                    transform
-                };
-
-                let origin_rect =
-                    origin_rect.with_size(Size::new(origin_rect.width(), image_height));
-
-                (origin_rect, transform, count, gap)
-            } else {
-                (origin_rect, transform, 1, 0.0)
-            };
-
-            for hc in 0..height_count {
-                for wc in 0..width_count {
-                    let width_gap = if matches!(repeat_x, Space) {
-                        origin_rect.x0 + wc as f64 * width_gap
-                    } else {
-                        0.0
-                    };
-
-                    let height_gap = if matches!(repeat_y, Space) {
-                        origin_rect.y0 + hc as f64 * height_gap
-                    } else {
-                        0.0
-                    };
-
-                    let transform = transform.then_translate(Vec2 {
-                        x: width_gap,
-                        y: height_gap,
-                    });
-
-                    scene.fill(
-                        peniko::Fill::NonZero,
-                        transform,
-                        to_peniko_image(image_data, quality).as_ref(),
-                        None,
-                        &Rect::new(0.0, 0.0, origin_rect.width(), origin_rect.height()),
-                    );
+            (_, Round) => {
+                if auto_width {
+                    tile_size.width *= rounded_height / tile_size.height;
                 }
+                tile_size.height = rounded_height;
             }
-        } else {
-            scene.fill(
-                peniko::Fill::NonZero,
-                transform,
-                to_peniko_image(image_data, quality).as_ref(),
-                None,
-                &Rect::new(0.0, 0.0, origin_rect.width(), origin_rect.height()),
-            );
+            _ => {}
         }
-    }
-
-    fn draw_gradient_bg(
-        &self,
-        scene: &mut impl PaintScene,
-        gradient: &StyloGradient,
-        idx: usize,
-        background_clip: StyloBackgroundClip,
-    ) {
-        use BackgroundRepeatKeyword::*;
-
-        let bg_styles = &self.style.get_background();
-
-        let background_origin = *get_cyclic(&bg_styles.background_origin.0, idx);
-        let origin_rect = match background_origin {
-            StyloBackgroundOrigin::BorderBox => self.frame.border_box,
-            StyloBackgroundOrigin::PaddingBox => self.frame.padding_box,
-            StyloBackgroundOrigin::ContentBox => self.frame.content_box,
-        };
 
-        let (bg_pos, bg_size) = compute_background_position_and_background_size(
+        let bg_pos = compute_background_position(
             bg_styles,
-            origin_rect.width() / self.scale,
-            origin_rect.height() / self.scale,
             idx,
-            BackgroundSizeComputeMode::Auto,
+            (area_size.width - tile_size.width) as f32,
+            (area_size.height - tile_size.height) as f32,
+        );
+        let tile_size = tile_size * scale;
+
+        let x_tiles = compute_tile_run(
+            repeat_x,
+            positioning_area.x0,
+            positioning_area.width(),
+            painting_area.x0..painting_area.x1,
+            tile_size.width,
+            bg_pos.x * scale,
+        );
+        let y_tiles = compute_tile_run(
+            repeat_y,
+            positioning_area.y0,
+            positioning_area.height(),
+            painting_area.y0..painting_area.y1,
+            tile_size.height,
+            bg_pos.y * scale,
         );
 
-        let bg_pos_x = bg_pos.x * self.scale;
-        let bg_pos_y = bg_pos.y * self.scale;
-        let bg_size = bg_size * self.scale;
-
-        let BackgroundRepeat(repeat_x, repeat_y) = get_cyclic(&bg_styles.background_repeat.0, idx);
-
-        let transform = self.transform;
-        let (origin_rect, transform, width_count, width_gap) = match repeat_x {
-            Repeat | Round => {
-                let (origin_rect, extend_width, count) = if (background_clip, background_origin)
-                    == (
-                        StyloBackgroundClip::BorderBox,
-                        StyloBackgroundOrigin::PaddingBox,
-                    ) {
-                    let extend_width = extend(self.frame.border_width.x0 + bg_pos_x, bg_size.width);
-
-                    let width = self.frame.border_box.width() + extend_width;
-                    let count = (width / bg_size.width).ceil() as u32;
-
-                    let origin_rect = Rect::from_origin_size(
-                        Point::new(self.frame.border_box.x0, origin_rect.y0),
-                        Size::new(bg_size.width, origin_rect.height()),
-                    );
-
-                    (origin_rect, extend_width, count)
-                } else if (background_clip, background_origin)
-                    == (
-                        StyloBackgroundClip::BorderBox,
-                        StyloBackgroundOrigin::ContentBox,
-                    )
-                {
-                    let extend_width = extend(
-                        self.frame.border_width.x0 + self.frame.padding_width.x0 + bg_pos_x,
-                        bg_size.width,
-                    );
-                    let width = self.frame.border_box.width() + extend_width;
-                    let count = (width / bg_size.width).ceil() as u32;
-
-                    let origin_rect = Rect::from_origin_size(
-                        Point::new(self.frame.border_box.x0, origin_rect.y0),
-                        Size::new(bg_size.width, origin_rect.height()),
+        let clip_path = self.background_clip_path(background_clip);
+        maybe_with_layer(scene, true, 1.0, self.transform, &clip_path, |scene| {
+            // Raster images are tiled by the renderer with a repeating image brush. Only spaced
+            // tiles (which have gaps between them) and vector sources are drawn tile by tile.
+            if let BackgroundSource::Raster(image) = &source {
+                if repeat_x != Space && repeat_y != Space {
+                    let tiled_area = Rect::new(
+                        x_tiles.start,
+                        y_tiles.start,
+                        x_tiles.end(tile_size.width),
+                        y_tiles.end(tile_size.height),
                     );
-
-                    (origin_rect, extend_width, count)
-                } else if (background_clip, background_origin)
-                    == (
-                        StyloBackgroundClip::PaddingBox,
-                        StyloBackgroundOrigin::ContentBox,
-                    )
-                {
-                    let extend_width =
-                        extend(self.frame.padding_width.x0 + bg_pos_x, bg_size.width);
-                    let width = self.frame.padding_box.width() + extend_width;
-                    let count = (width / bg_size.width).ceil() as u32;
-
-                    let origin_rect = Rect::from_origin_size(
-                        Point::new(self.frame.padding_box.x0, origin_rect.y0),
-                        Size::new(bg_size.width, origin_rect.height()),
+                    let origin = Vec2::new(x_tiles.start, y_tiles.start);
+                    self.draw_raster_bg(
+                        scene,
+                        image,
+                        origin,
+                        tile_size,
+                        tiled_area.intersect(painting_area),
                     );
-
-                    (origin_rect, extend_width, count)
-                } else {
-                    let extend_width = extend(bg_pos_x, bg_size.width);
-                    let width = origin_rect.width() + extend_width;
-                    let count = (width / bg_size.width).ceil() as u32;
-                    let origin_rect =
-                        origin_rect.with_size(Size::new(bg_size.width, origin_rect.height()));
-
-                    (origin_rect, extend_width, count)
-                };
-
-                let transform = transform.then_translate(Vec2 {
-                    x: origin_rect.x0 - extend_width,
-                    y: 0.0,
-                });
-
-                (origin_rect, transform, count, bg_size.width)
-            }
-            Space => {
-                let (count, gap) = compute_space_count_and_gap(origin_rect.width(), bg_size.width);
-
-                let transform = transform.then_translate(Vec2 {
-                    x: origin_rect.x0 + if count == 1 { bg_pos_x } else { 0.0 },
-                    y: 0.0,
-                });
-
-                let origin_rect =
-                    origin_rect.with_size(Size::new(bg_size.width, origin_rect.height()));
-
-                (origin_rect, transform, count, gap)
-            }
-            NoRepeat => {
-                let transform = transform.then_translate(Vec2 {
-                    x: origin_rect.x0 + bg_pos_x,
-                    y: 0.0,
-                });
-
-                let origin_rect =
-                    origin_rect.with_size(Size::new(bg_size.width, origin_rect.height()));
-
-                (origin_rect, transform, 1, 0.0)
+                    return;
+                }
             }
-        };
-        let (origin_rect, transform, height_count, height_gap) = match repeat_y {
-            Repeat | Round => {
-                let (origin_rect, extend_height, count) = if (background_clip, background_origin)
-                    == (
-                        StyloBackgroundClip::BorderBox,
-                        StyloBackgroundOrigin::PaddingBox,
-                    ) {
-                    let extend_height =
-                        extend(self.frame.border_width.y0 + bg_pos_y, bg_size.height);
-                    let height = self.frame.border_box.height() + extend_height;
-                    let count = (height / bg_size.height).ceil() as u32;
-
-                    let origin_rect = Rect::from_origin_size(
-                        Point::new(origin_rect.x0, self.frame.border_box.y0),
-                        Size::new(origin_rect.width(), bg_size.height),
-                    );
 
-                    (origin_rect, extend_height, count)
-                } else if (background_clip, background_origin)
-                    == (
-                        StyloBackgroundClip::BorderBox,
-                        StyloBackgroundOrigin::ContentBox,
-                    )
-                {
-                    let extend_height = extend(
-                        self.frame.border_width.y0 + self.frame.padding_width.y0 + bg_pos_x,
-                        bg_size.height,
-                    );
-                    let height = self.frame.border_box.height() + extend_height;
-                    let count = (height / bg_size.height).ceil() as u32;
-
-                    let origin_rect = Rect::from_origin_size(
-                        Point::new(origin_rect.x0, self.frame.border_box.y0),
-                        Size::new(origin_rect.width(), bg_size.height),
-                    );
+            let tile = Rect::from_origin_size((0.0, 0.0), tile_size);
+            let gradient = match &source {
+                BackgroundSource::Gradient(gradient) => {
+                    let current_color = self.style.clone_color();
+                    Some(to_peniko_gradient(
+                        gradient,
+                        tile,
+                        tile,
+                        scale,
+                        &current_color,
+                    ))
+                }
+                _ => None,
+            };
 
-                    (origin_rect, extend_height, count)
-                } else if (background_clip, background_origin)
-                    == (
-                        StyloBackgroundClip::PaddingBox,
-                        StyloBackgroundOrigin::ContentBox,
-                    )
-                {
-                    let extend_height =
-                        extend(self.frame.padding_width.y0 + bg_pos_x, bg_size.height);
-                    let height = self.frame.padding_box.height() + extend_height;
-                    let count = (height / bg_size.height).ceil() as u32;
-
-                    let origin_rect = Rect::from_origin_size(
-                        Point::new(origin_rect.x0, self.frame.padding_box.y0),
-                        Size::new(origin_rect.width(), bg_size.height),
+            for y in 0..y_tiles.count {
+                for x in 0..x_tiles.count {
+                    let offset = Vec2::new(
+                        x_tiles.start + x as f64 * x_tiles.step,
+                        y_tiles.start + y as f64 * y_tiles.step,
                     );
-
-                    (origin_rect, extend_height, count)
-                } else {
-                    let extend_height = extend(bg_pos_x, bg_size.height);
-                    let height = origin_rect.height() + extend_height;
-                    let count = (height / bg_size.height).ceil() as u32;
-                    let origin_rect =
-                        origin_rect.with_size(Size::new(origin_rect.width(), bg_size.height));
-
-                    (origin_rect, extend_height, count)
-                };
-
-                let transform = transform.then_translate(Vec2 {
-                    x: 0.0,
-                    y: origin_rect.y0 - extend_height,
-                });
-
-                (origin_rect, transform, count, bg_size.height)
-            }
-            Space => {
-                let (count, gap) =
-                    compute_space_count_and_gap(origin_rect.height(), bg_size.height);
-
-                let transform = transform.then_translate(Vec2 {
-                    x: 0.0,
-                    y: origin_rect.y0 + if count == 1 { bg_pos_y } else { 0.0 },
-                });
-
-                let origin_rect =
-                    origin_rect.with_size(Size::new(origin_rect.width(), bg_size.height));
-
-                (origin_rect, transform, count, gap)
-            }
-            NoRepeat => {
-                let transform = transform.then_translate(Vec2 {
-                    x: 0.0,
-                    y: origin_rect.y0 + bg_pos_y,
-                });
-
-                let origin_rect =
-                    origin_rect.with_size(Size::new(origin_rect.width(), bg_size.height));
-                (origin_rect, transform, 1, 0.0)
+                    let transform = self.transform.pre_translate(offset);
+                    match &source {
+                        BackgroundSource::Raster(image) => {
+                            let tile = Rect::from_origin_size(offset.to_point(), tile_size);
+                            self.draw_raster_bg(scene, image, offset, tile_size, tile)
+                        }
+                        #[cfg(feature = "svg")]
+                        BackgroundSource::Svg(svg) => {
+                            let svg_size = svg.size();
+                            let transform = transform.pre_scale_non_uniform(
+                                tile_size.width / svg_size.width() as f64,
+                                tile_size.height / svg_size.height() as f64,
+                            );
+                            anyrender_svg::render_svg_tree(scene, svg, transform);
+                        }
+                        BackgroundSource::Gradient(_) => {
+                            let (gradient, gradient_transform) = gradient.as_ref().unwrap();
+                            scene.fill(
+                                Fill::NonZero,
+                                transform,
+                                anyrender::Paint::Gradient(gradient),
+                                *gradient_transform,
+                                &tile,
+                            );
+                        }
+                    }
+                }
             }
-        };
+        });
+    }
 
-        // FIXME: https://wpt.live/css/css-backgrounds/background-size/background-size-near-zero-gradient.html
-        if width_count * height_count > 500 {
+    /// Fill `area` with a raster image repeated in both directions from a tile at `origin`
+    fn draw_raster_bg(
+        &self,
+        scene: &mut impl PaintScene,
+        image: &RasterImageData,
+        origin: Vec2,
+        tile_size: Size,
+        area: Rect,
+    ) {
+        if area.is_zero_area() {
             return;
         }
 
-        let origin_rect = Rect::new(0.0, 0.0, origin_rect.width(), origin_rect.height());
-        let bounding_box = self.frame.border_box.bounding_box();
-        let current_color = self.style.clone_color();
-
-        let (gradient, gradient_transform) = to_peniko_gradient(
-            gradient,
-            origin_rect,
-            bounding_box,
-            self.scale,
-            &current_color,
+        let quality = to_image_quality(self.style.clone_image_rendering());
+        let x_ratio = tile_size.width / image.width as f64;
+        let y_ratio = tile_size.height / image.height as f64;
+        let brush_transform = Affine::translate(origin).pre_scale_non_uniform(x_ratio, y_ratio);
+
+        scene.fill(
+            peniko::Fill::NonZero,
+            self.transform,
+            to_peniko_image(image, quality).as_ref(),
+            Some(brush_transform),
+            &area,
         );
-        let brush = anyrender::Paint::Gradient(&gradient);
-
-        for hc in 0..height_count {
-            for wc in 0..width_count {
-                let transform = transform.then_translate(Vec2 {
-                    x: wc as f64 * width_gap,
-                    y: hc as f64 * height_gap,
-                });
-
-                scene.fill(
-                    peniko::Fill::NonZero,
-                    transform,
-                    brush.clone(),
-                    gradient_transform,
-                    &origin_rect,
-                );
-            }
-        }
     }
 }
 
-fn compute_background_position_and_background_size(
-    background: &Background,
-    container_w: f64,
-    container_h: f64,
-    bg_idx: usize,
-    size_mode: BackgroundSizeComputeMode,
-) -> (Point, Size) {
+/// Compute the position and spacing of the tiles of a background layer along one axis.
+///
+/// `area_start` and `area_length` are the background positioning area, `painting_area` the
+/// range covered by the background painting area, and `offset` the position of the tile
+/// (as resolved from `background-position`) within the positioning area.
+fn compute_tile_run(
+    repeat: BackgroundRepeatKeyword,
+    area_start: f64,
+    area_length: f64,
+    painting_area: std::ops::Range<f64>,
+    tile_length: f64,
+    offset: f64,
+) -> TileRun {
     use BackgroundRepeatKeyword::*;
 
-    let bg_size = compute_background_size(
-        background,
-        container_w as f32,
-        container_h as f32,
-        bg_idx,
-        size_mode,
-        1.0,
-    );
-
-    let bg_pos = compute_background_position(
-        background,
-        bg_idx,
-        (container_w - bg_size.width) as f32,
-        (container_h - bg_size.height) as f32,
-    );
-
-    let BackgroundRepeat(repeat_x, repeat_y) = get_cyclic(&background.background_repeat.0, bg_idx);
-
-    let bg_size = if matches!(repeat_x, Round) && matches!(repeat_y, Round) {
-        let count = (container_w / bg_size.width).round();
-        let width = container_w / count;
-
-        let count = (container_h / bg_size.height).round();
-        let height = container_h / count;
-
-        Size::new(width, height)
-    } else if matches!(repeat_x, Round) {
-        let count = (container_w / bg_size.width).round();
-        let width = container_w / count;
-        Size::new(width, bg_size.height)
-    } else if matches!(repeat_y, Round) {
-        let count = (container_h / bg_size.height).round();
-        let height = container_h / count;
-        Size::new(bg_size.width, height)
-    } else {
-        bg_size
+    // Repeat a tile at `start` with the specified step until it covers the painting area
+    let cover = |start: f64, step: f64| {
+        let before = ((start - painting_area.start) / step).ceil().max(0.0);
+        let first = start - before * step;
+        let count = ((painting_area.end - first) / step).ceil().max(0.0) as usize;
+        TileRun {
+            start: first,
+            step,
+            count,
+        }
     };
 
-    (bg_pos, bg_size)
+    match repeat {
+        NoRepeat => TileRun {
+            start: area_start + offset,
+            step: tile_length,
+            count: 1,
+        },
+        Repeat | Round => cover(area_start + offset, tile_length),
+        Space => {
+            // As many whole tiles as fit in the positioning area are spaced evenly across it
+            let count = (area_length / tile_length).floor();
+            if count < 2.0 {
+                return TileRun {
+                    start: area_start + offset,
+                    step: tile_length,
+                    count: 1,
+                };
+            }
+            let gap = (area_length - count * tile_length) / (count - 1.0);
+            cover(area_start, tile_length + gap)
+        }
+    }
+}
+
+/// The tile length which fits a whole number of tiles in the background positioning area
+fn round_tile_length(area_length: f64, tile_length: f64) -> f64 {
+    if area_length <= 0.0 {
+        return tile_length;
+    }
+    let count = (area_length / tile_length).round().max(1.0);
+    area_length / count
 }
 
 #[inline]
@@ -658,7 +482,7 @@ fn compute_background_position(
     bg_idx: usize,
     width: f32,
     height: f32,
-) -> Point {
+) -> kurbo::Point {
     use style::values::computed::Length;
 
     let bg_pos_x = get_cyclic(&background.background_position_x.0, bg_idx)
@@ -668,123 +492,159 @@ fn compute_background_position(
         .resolve(Length::new(height))
         .px() as f64;
 
-    Point::new(bg_pos_x, bg_pos_y)
+    kurbo::Point::new(bg_pos_x, bg_pos_y)
 }
 
+/// Whether each dimension of a layer's `background-size` is `auto`
+fn background_size_is_auto(background: &Background, bg_idx: usize) -> (bool, bool) {
+    use style::values::computed::BackgroundSize;
+    use style::values::generics::length::GenericLengthPercentageOrAuto as Lpa;
+
+    match get_cyclic(&background.background_size.0, bg_idx) {
+        BackgroundSize::ExplicitSize { width, height } => {
+            (matches!(width, Lpa::Auto), matches!(height, Lpa::Auto))
+        }
+        BackgroundSize::Cover | BackgroundSize::Contain => (false, false),
+    }
+}
+
+/// Compute the size of a background image (in CSS pixels) given the size of the background
+/// positioning area and the natural size of the image (if it has one)
 fn compute_background_size(
     background: &Background,
-    container_w: f32,
-    container_h: f32,
     bg_idx: usize,
-    mode: BackgroundSizeComputeMode,
-    scale: f32,
-) -> kurbo::Size {
-    use style::values::computed::{BackgroundSize, Length};
+    area: Size,
+    natural_size: Option<Size>,
+) -> Size {
+    use style::values::computed::{BackgroundSize, Length, NonNegativeLengthPercentage};
     use style::values::generics::length::GenericLengthPercentageOrAuto as Lpa;
 
     let bg_size = get_cyclic(&background.background_size.0, bg_idx);
 
-    let (width, height): (f32, f32) = match bg_size {
+    match bg_size {
         BackgroundSize::ExplicitSize { width, height } => {
-            let width = width.map(|w| w.0.resolve(Length::new(container_w)));
-            let height = height.map(|h| h.0.resolve(Length::new(container_h)));
-
-            match (width, height) {
-                (Lpa::LengthPercentage(width), Lpa::LengthPercentage(height)) => {
-                    let width = width.px();
-                    let height = height.px();
-                    match mode {
-                        BackgroundSizeComputeMode::Auto => (width, height),
-                        BackgroundSizeComputeMode::Size(_, _) => (width, height),
-                    }
+            let resolve = |lpa: &Lpa<NonNegativeLengthPercentage>, basis: f64| match lpa {
+                Lpa::LengthPercentage(lp) => {
+                    Some(lp.0.resolve(Length::new(basis as f32)).px() as f64)
                 }
-                (Lpa::LengthPercentage(width), Lpa::Auto) => {
-                    let width = width.px();
-                    let height = match mode {
-                        BackgroundSizeComputeMode::Auto => container_h,
-                        BackgroundSizeComputeMode::Size(bg_w, bg_h) => bg_h / bg_w * width,
-                    };
-                    (width, height)
+                Lpa::Auto => None,
+            };
+            let width = resolve(width, area.width);
+            let height = resolve(height, area.height);
+
+            match (width, height, natural_size) {
+                (Some(width), Some(height), _) => Size::new(width, height),
+                (Some(width), None, Some(natural)) => {
+                    Size::new(width, width * natural.height / natural.width)
                 }
-                (Lpa::Auto, Lpa::LengthPercentage(height)) => {
-                    let height = height.px();
-                    let width = match mode {
-                        BackgroundSizeComputeMode::Auto => container_w,
-                        BackgroundSizeComputeMode::Size(bg_w, bg_h) => bg_w / bg_h * height,
-                    };
-                    (width, height)
+                (None, Some(height), Some(natural)) => {
+                    Size::new(height * natural.width / natural.height, height)
                 }
-                (Lpa::Auto, Lpa::Auto) => match mode {
-                    BackgroundSizeComputeMode::Auto => (container_w, container_h),
-                    BackgroundSizeComputeMode::Size(bg_w, bg_h) => (bg_w * scale, bg_h * scale),
-                },
+                (Some(width), None, None) => Size::new(width, area.height),
+                (None, Some(height), None) => Size::new(area.width, height),
+                (None, None, Some(natural)) => natural,
+                (None, None, None) => area,
             }
         }
-        BackgroundSize::Cover => match mode {
-            BackgroundSizeComputeMode::Auto => (container_w, container_h),
-            BackgroundSizeComputeMode::Size(bg_w, bg_h) => {
-                let x_ratio = container_w / bg_w;
-                let y_ratio = container_h / bg_h;
-
-                let ratio = if x_ratio < 1.0 || y_ratio < 1.0 {
-                    x_ratio.min(y_ratio)
-                } else {
-                    x_ratio.max(y_ratio)
-                };
-
-                (bg_w * ratio, bg_h * ratio)
-            }
-        },
-        BackgroundSize::Contain => match mode {
-            BackgroundSizeComputeMode::Auto => (container_w, container_h),
-            BackgroundSizeComputeMode::Size(bg_w, bg_h) => {
-                let x_ratio = container_w / bg_w;
-                let y_ratio = container_h / bg_h;
-
-                let ratio = if x_ratio < 1.0 || y_ratio < 1.0 {
-                    x_ratio.max(y_ratio)
-                } else {
-                    x_ratio.min(y_ratio)
-                };
-
-                (bg_w * ratio, bg_h * ratio)
-            }
-        },
-    };
-
-    kurbo::Size {
-        width: width as f64,
-        height: height as f64,
+        BackgroundSize::Cover | BackgroundSize::Contain => {
+            let Some(natural) = natural_size else {
+                return area;
+            };
+            let x_ratio = area.width / natural.width;
+            let y_ratio = area.height / natural.height;
+            let ratio = match bg_size {
+                BackgroundSize::Cover => x_ratio.max(y_ratio),
+                _ => x_ratio.min(y_ratio),
+            };
+            natural * ratio
+        }
     }
 }
 
-enum BackgroundSizeComputeMode {
-    Auto,
-    Size(f32, f32),
-}
-
-fn compute_space_count_and_gap(bg_size: f64, size: f64) -> (u32, f64) {
-    let modulo = bg_size % size;
-    let count = (((bg_size - modulo) / size) as u32).max(1);
-    let gap = if count > 1 {
-        modulo / (count - 1) as f64
-    } else {
-        0.0
-    } + size;
-
-    (count, gap)
-}
-
 #[inline]
 fn get_cyclic<T>(values: &[T], layer_index: usize) -> &T {
     &values[layer_index % values.len()]
 }
 
-fn extend(offset: f64, length: f64) -> f64 {
-    let extend_length = offset % length;
-    if extend_length > 0.0 {
-        length - extend_length
-    } else {
-        -extend_length
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::render::BlitzDomPainter;
+    use crate::test_util::{VIEWPORT_SIZE, layout_html};
+    use blitz_traits::devtools::DevtoolSettings;
+    use kurbo::{Point, Shape as _};
+
+    /// The background painting area of the element with id `target` (relative to its border box)
+    fn clip_path(html: &str, clip: BackgroundClip) -> BezPath {
+        let doc = layout_html(html);
+        let painter = BlitzDomPainter {
+            dom: &doc,
+            scale: 1.0,
+            width: VIEWPORT_SIZE,
+            height: VIEWPORT_SIZE,
+            origin: Point::ZERO,
+            clip: None,
+            devtools: DevtoolSettings::default(),
+            recording: None,
+        };
+        let node_id = doc.query_selector("#target").unwrap().unwrap();
+        let node = doc.get_node(node_id).unwrap();
+        let (layout, position) = painter.node_position(node_id, Point::ZERO);
+        painter
+            .element_cx(node, layout, position, Affine::IDENTITY)
+            .background_clip_path(clip)
+    }
+
+    #[test]
+    fn test_tile_runs() {
+        use BackgroundRepeatKeyword::*;
+
+        // A single tile at its position
+        let run = compute_tile_run(NoRepeat, 10.0, 100.0, 0.0..120.0, 30.0, 20.0);
+        assert_eq!(run.start, 30.0);
+        assert_eq!(run.count, 1);
+
+        // Repeated tiles extend backwards and forwards to cover the painting area
+        let run = compute_tile_run(Repeat, 10.0, 100.0, 0.0..120.0, 30.0, 20.0);
+        assert_eq!(run.start, 0.0);
+        assert_eq!(run.step, 30.0);
+        assert_eq!(run.count, 4);
+        assert_eq!(run.end(30.0), 120.0);
+
+        // Spaced tiles fill the positioning area exactly
+        let run = compute_tile_run(Space, 0.0, 100.0, 0.0..100.0, 30.0, 0.0);
+        assert_eq!(run.start, 0.0);
+        assert_eq!(run.step, 35.0);
+        assert_eq!(run.count, 3);
+
+        assert_eq!(round_tile_length(100.0, 30.0), 100.0 / 3.0);
+        assert_eq!(round_tile_length(100.0, 80.0), 100.0);
+    }
+
+    #[test]
+    fn test_background_clip_text() {
+        let html = |padding: &str, text: &str| {
+            format!(
+                r#"<body style="margin: 0"><p id="target" style="margin: 0; padding: {padding}; font-size: 20px">{text}</p></body>"#
+            )
+        };
+
+        // The clip is the union of the glyph outlines, which lie within the line box
+        let text_clip = clip_path(&html("0", "Hi"), BackgroundClip::Text);
+        let bounds = text_clip.bounding_box();
+        assert!(bounds.width() > 0.0 && bounds.height() > 0.0);
+        let border_box = clip_path(&html("0", "Hi"), BackgroundClip::BorderBox).bounding_box();
+        assert!(bounds.x0 >= border_box.x0 && bounds.x1 < border_box.x1);
+        assert!(bounds.y0 >= border_box.y0 && bounds.y1 <= border_box.y1);
+        assert!(bounds.width() < 50.0);
+
+        // The glyphs are inside the border box: the clip is offset by the padding
+        let padded = clip_path(&html("10px", "Hi"), BackgroundClip::Text).bounding_box();
+        assert!((padded.x0 - bounds.x0 - 10.0).abs() < 1e-6);
+        assert!((padded.y0 - bounds.y0 - 10.0).abs() < 1e-6);
+
+        // Elements without text clip their background away entirely
+        let empty = clip_path(&html("10px", ""), BackgroundClip::Text);
+        assert!(empty.elements().is_empty());
     }
 }
@@ -0,0 +1,390 @@
+use super::ElementCx;
+use crate::{
+    color::{Color, ToColorColor as _},
+    kurbo_css::{CssBox, Edge, NonUniformRoundedRectRadii},
+    layers::maybe_with_layer,
+};
+use anyrender::PaintScene;
+use blitz_dom::node::CollapsedBorder;
+use kurbo::{Affine, Cap, Insets, Stroke};
+use peniko::Fill;
+use style::computed_values::border_collapse::T as BorderCollapse;
+use style::values::specified::{BorderStyle, OutlineStyle};
+
+impl ElementCx<'_> {
+    /// Stroke a border
+    ///
+    /// The border-style property specifies what kind of border to display.
+    ///
+    /// [Border](https://www.w3schools.com/css/css_border.asp)
+    ///
+    /// The following values are allowed:
+    /// ✅ dotted - Defines a dotted border
+    /// ✅ dashed - Defines a dashed border
+    /// ✅ solid - Defines a solid border
+    /// ✅ double - Defines a double border
+    /// ✅ groove - Defines a 3D grooved border.
+    /// ✅ ridge - Defines a 3D ridged border.
+    /// ✅ inset - Defines a 3D inset border.
+    /// ✅ outset - Defines a 3D outset border.
+    /// ✅ none - Defines no border
+    /// ✅ hidden - Defines a hidden border
+    ///
+    /// The border-style property can have from one to four values (for the top border, right border, bottom border, and the left border).
+    pub(super) fn draw_border(&self, scene: &mut impl PaintScene) {
+        // In the collapsing border model, table borders are painted by the table's cells
+        if let Some(borders) = &self.element.collapsed_borders {
+            self.draw_collapsed_border(scene, borders);
+            return;
+        }
+        if self.node.flags.is_table_root()
+            && self.style.clone_border_collapse() == BorderCollapse::Collapse
+        {
+            return;
+        }
+
+        for edge in [Edge::Top, Edge::Right, Edge::Bottom, Edge::Left] {
+            self.draw_border_edge(scene, edge);
+        }
+    }
+
+    /// Draw the collapsed borders of a table cell. Collapsed borders are centered on the edges
+    /// of the cell's grid slot, so half of each border lies outside of the cell's border box.
+    fn draw_collapsed_border(
+        &self,
+        scene: &mut impl PaintScene,
+        borders: &taffy::Rect<Option<CollapsedBorder>>,
+    ) {
+        let width = |border: &Option<CollapsedBorder>| {
+            border
+                .as_ref()
+                .map_or(0.0, |border| border.width as f64 * self.scale)
+        };
+        let border_width = Insets {
+            x0: width(&borders.left),
+            y0: width(&borders.top),
+            x1: width(&borders.right),
+            y1: width(&borders.bottom),
+        };
+        let half_width = Insets {
+            x0: border_width.x0 / 2.0,
+            y0: border_width.y0 / 2.0,
+            x1: border_width.x1 / 2.0,
+            y1: border_width.y1 / 2.0,
+        };
+        let frame = CssBox::new(
+            self.frame.border_box + half_width,
+            border_width,
+            Insets::ZERO,
+            0.0,
+            NonUniformRoundedRectRadii::default(),
+        );
+
+        for (edge, border) in [
+            (Edge::Top, &borders.top),
+            (Edge::Right, &borders.right),
+            (Edge::Bottom, &borders.bottom),
+            (Edge::Left, &borders.left),
+        ] {
+            if let Some(border) = border {
+                let color = border.color.as_srgb_color();
+                draw_edge(scene, self.transform, &frame, edge, border.style, color);
+            }
+        }
+    }
+
+    /// Draw a single edge of the border. Each edge has its own style, color and width.
+    fn draw_border_edge(&self, scene: &mut impl PaintScene, edge: Edge) {
+        let border = self.style.get_border();
+        let current_color = self.style.clone_color();
+        let (style, color) = match edge {
+            Edge::Top => (border.border_top_style, &border.border_top_color),
+            Edge::Right => (border.border_right_style, &border.border_right_color),
+            Edge::Bottom => (border.border_bottom_style, &border.border_bottom_color),
+            Edge::Left => (border.border_left_style, &border.border_left_color),
+        };
+        let color = color.resolve_to_absolute(&current_color).as_srgb_color();
+
+        draw_edge(scene, self.transform, &self.frame, edge, style, color);
+    }
+
+    /// Draw the outline. Outlines support the same styles as borders, but have the same style,
+    /// color and width on every side. `outline-style: auto` is drawn as a solid outline.
+    pub(super) fn draw_outline(&self, scene: &mut impl PaintScene) {
+        let outline = self.style.get_outline();
+
+        let current_color = self.style.clone_color();
+        let color = outline
+            .outline_color
+            .resolve_to_absolute(&current_color)
+            .as_srgb_color();
+
+        let style = match outline.outline_style {
+            OutlineStyle::Auto => BorderStyle::Solid,
+            OutlineStyle::BorderStyle(style) => style,
+        };
+
+        match style {
+            BorderStyle::None | BorderStyle::Hidden => {}
+            // Solid outlines are drawn as one shape to avoid seams at the corners
+            BorderStyle::Solid => {
+                let path = self.frame.outline();
+                scene.fill(Fill::NonZero, self.transform, color, None, &path);
+            }
+            _ => {
+                let frame = self.frame.outline_frame();
+                for edge in [Edge::Top, Edge::Right, Edge::Bottom, Edge::Left] {
+                    draw_edge(scene, self.transform, &frame, edge, style, color);
+                }
+            }
+        }
+    }
+}
+
+/// Draw one edge of the border area of `frame` in the specified style
+fn draw_edge(
+    scene: &mut impl PaintScene,
+    transform: Affine,
+    frame: &CssBox,
+    edge: Edge,
+    style: BorderStyle,
+    color: Color,
+) {
+    let width = match edge {
+        Edge::Top => frame.border_width.y0,
+        Edge::Right => frame.border_width.x1,
+        Edge::Bottom => frame.border_width.y1,
+        Edge::Left => frame.border_width.x0,
+    };
+    if width <= 0.0 || color.components[3] == 0.0 {
+        return;
+    }
+
+    // Whether this edge is one of the "light source" edges for 3D styles
+    let is_top_left = matches!(edge, Edge::Top | Edge::Left);
+
+    let edge_shape = frame.border_edge_shape(edge);
+    match style {
+        BorderStyle::None | BorderStyle::Hidden => {}
+        BorderStyle::Solid => {
+            scene.fill(Fill::NonZero, transform, color, None, &edge_shape);
+        }
+        BorderStyle::Inset | BorderStyle::Outset => {
+            let is_dark = is_top_left == (style == BorderStyle::Inset);
+            let color = shade(color, is_dark);
+            scene.fill(Fill::NonZero, transform, color, None, &edge_shape);
+        }
+        BorderStyle::Double | BorderStyle::Groove | BorderStyle::Ridge => {
+            // Double borders are two solid lines with a gap between them, each a third of the width.
+            // Groove and ridge borders are split in half, with the halves shaded in opposite ways.
+            let (outer, inner) = match style {
+                BorderStyle::Double => ((color, 0.0, 1.0 / 3.0), (color, 2.0 / 3.0, 1.0)),
+                _ => {
+                    let is_dark = is_top_left == (style == BorderStyle::Groove);
+                    (
+                        (shade(color, is_dark), 0.0, 0.5),
+                        (shade(color, !is_dark), 0.5, 1.0),
+                    )
+                }
+            };
+            maybe_with_layer(scene, true, 1.0, transform, &edge_shape, |scene| {
+                for (color, start, end) in [outer, inner] {
+                    let band = frame.border_band(start, end);
+                    scene.fill(Fill::EvenOdd, transform, color, None, &band);
+                }
+            });
+        }
+        BorderStyle::Dashed | BorderStyle::Dotted => {
+            let stroke = match style {
+                BorderStyle::Dashed => Stroke::new(width).with_dashes(0.0, [width * 3.0; 2]),
+                _ => Stroke::new(width)
+                    .with_caps(Cap::Round)
+                    .with_dashes(0.0, [0.0, width * 2.0]),
+            };
+            let centerline = frame.border_centerline();
+            maybe_with_layer(scene, true, 1.0, transform, &edge_shape, |scene| {
+                scene.stroke(&stroke, transform, color, None, &centerline);
+            });
+        }
+    }
+}
+
+/// Darken or lighten a color for the shaded sides of 3D border styles
+fn shade(color: Color, dark: bool) -> Color {
+    let [r, g, b, a] = color.components;
+    let adjust = |c: f32| match dark {
+        true => c * (2.0 / 3.0),
+        false => c + (1.0 - c) / 3.0,
+    };
+    Color::new([adjust(r), adjust(g), adjust(b), a])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::display_list::{Brush, Command};
+    use crate::test_util::{layout_html, paint_commands};
+    use kurbo::{Rect, Shape as _};
+
+    /// Paint a 50x20 div at the origin with the given style
+    fn paint_box(style: &str) -> Vec<Command> {
+        let doc = layout_html(&format!(
+            r#"<body style="margin: 0"><div style="width: 50px; height: 20px; {style}"></div></body>"#
+        ));
+        paint_commands(&doc)
+    }
+
+    /// The color and bounds of each visible solid fill with the specified fill rule (which
+    /// excludes the transparent canvas background)
+    fn fills(commands: &[Command], rule: Fill) -> Vec<(Color, Rect)> {
+        commands
+            .iter()
+            .filter_map(|command| match command {
+                Command::Fill {
+                    fill,
+                    brush: Brush::Solid(color),
+                    path,
+                    ..
+                } if *fill == rule && color.components[3] > 0.0 => {
+                    Some((*color, path.bounding_box()))
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn strokes(commands: &[Command]) -> Vec<&Stroke> {
+        commands
+            .iter()
+            .filter_map(|command| match command {
+                Command::Stroke { stroke, .. } => Some(stroke),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn layer_count(commands: &[Command]) -> usize {
+        commands
+            .iter()
+            .filter(|command| matches!(command, Command::PushLayer { .. }))
+            .count()
+    }
+
+    #[test]
+    fn test_solid_border_per_side_colors() {
+        let commands = paint_box(
+            "border: 4px solid; border-color: rgb(255, 0, 0) rgb(0, 255, 0) rgb(0, 0, 255) rgb(255, 255, 0)",
+        );
+        let fills = fills(&commands, Fill::NonZero);
+        let bounds_of = |color| {
+            fills
+                .iter()
+                .find(|(c, _)| *c == color)
+                .map(|(_, bounds)| *bounds)
+                .unwrap()
+        };
+
+        // Each edge spans the border box along its side, and is as deep as the border width
+        let top = bounds_of(Color::from_rgb8(255, 0, 0));
+        assert_eq!((top.y0, top.y1, top.width()), (0.0, 4.0, 58.0));
+        let right = bounds_of(Color::from_rgb8(0, 255, 0));
+        assert_eq!((right.x0, right.x1, right.height()), (54.0, 58.0, 28.0));
+        let bottom = bounds_of(Color::from_rgb8(0, 0, 255));
+        assert_eq!((bottom.y0, bottom.y1, bottom.width()), (24.0, 28.0, 58.0));
+        let left = bounds_of(Color::from_rgb8(255, 255, 0));
+        assert_eq!((left.x0, left.x1, left.height()), (0.0, 4.0, 28.0));
+        assert_eq!(layer_count(&commands), 0);
+    }
+
+    #[test]
+    fn test_dashed_and_dotted_borders() {
+        // Each dashed edge strokes the border centerline with dashes three times the border width,
+        // clipped to the edge
+        let commands = paint_box("border: 4px dashed red");
+        let strokes = strokes(&commands);
+        assert_eq!(strokes.len(), 4);
+        for stroke in &strokes {
+            assert_eq!(stroke.width, 4.0);
+            assert_eq!(&stroke.dash_pattern[..], &[12.0, 12.0]);
+        }
+        assert_eq!(layer_count(&commands), 4);
+
+        // Dotted edges are round-capped zero length dashes
+        let commands = paint_box("border: 4px dotted red");
+        let strokes = strokes(&commands);
+        assert_eq!(strokes.len(), 4);
+        for stroke in &strokes {
+            assert_eq!(stroke.start_cap, Cap::Round);
+            assert_eq!(&stroke.dash_pattern[..], &[0.0, 8.0]);
+        }
+    }
+
+    #[test]
+    fn test_double_border() {
+        // Each edge is two even-odd bands in its own clip layer
+        let commands = paint_box("border: 6px double red");
+        let bands = fills(&commands, Fill::EvenOdd);
+        assert_eq!(bands.len(), 8);
+        assert!(
+            bands
+                .iter()
+                .all(|(color, _)| *color == Color::from_rgb8(255, 0, 0))
+        );
+        assert_eq!(layer_count(&commands), 4);
+    }
+
+    #[test]
+    fn test_3d_borders() {
+        let gray = Color::from_rgb8(150, 150, 150);
+        let edge_colors = |style: &str| {
+            let commands = paint_box(&format!("border: 4px {style} rgb(150, 150, 150)"));
+            let fills = fills(&commands, Fill::NonZero);
+            let color_at = |x: f64, y: f64| {
+                fills
+                    .iter()
+                    .find(|(_, bounds)| bounds.contains((x, y).into()))
+                    .map(|(color, _)| *color)
+                    .unwrap()
+            };
+            (color_at(29.0, 1.0), color_at(29.0, 27.0))
+        };
+
+        // Inset borders are dark at the top-left, outset borders are dark at the bottom-right
+        assert_eq!(
+            edge_colors("inset"),
+            (shade(gray, true), shade(gray, false))
+        );
+        assert_eq!(
+            edge_colors("outset"),
+            (shade(gray, false), shade(gray, true))
+        );
+
+        // Groove and ridge borders shade the two halves of each edge in opposite ways
+        let commands = paint_box("border: 4px groove rgb(150, 150, 150)");
+        let bands = fills(&commands, Fill::EvenOdd);
+        assert_eq!(bands.len(), 8);
+        assert_eq!(bands[0].0, shade(gray, true));
+        assert_eq!(bands[1].0, shade(gray, false));
+        let commands = paint_box("border: 4px ridge rgb(150, 150, 150)");
+        let bands = fills(&commands, Fill::EvenOdd);
+        assert_eq!(bands[0].0, shade(gray, false));
+        assert_eq!(bands[1].0, shade(gray, true));
+    }
+
+    #[test]
+    fn test_outline_styles() {
+        // Non-solid outlines are drawn edge by edge outside of the border box
+        let commands = paint_box("outline: 2px dashed red; outline-offset: 3px");
+        let strokes = strokes(&commands);
+        assert_eq!(strokes.len(), 4);
+        assert!(strokes.iter().all(|stroke| stroke.width == 2.0));
+
+        // Solid outlines are a single shape around the border box
+        let commands = paint_box("outline: 2px solid red; outline-offset: 3px");
+        let outline = fills(&commands, Fill::NonZero)
+            .into_iter()
+            .find(|(color, _)| *color == Color::from_rgb8(255, 0, 0))
+            .unwrap();
+        assert_eq!(outline.1, Rect::new(-5.0, -5.0, 55.0, 25.0));
+    }
+}
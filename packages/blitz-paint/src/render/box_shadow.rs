@@ -1,16 +1,18 @@
 use super::ElementCx;
 use crate::{
     color::{Color, ToColorColor as _},
-    layers::maybe_with_layer,
+    kurbo_css::{CssBox, NonUniformRoundedRectRadii},
+    layers::{maybe_pop_layer, maybe_push_blend_layer, maybe_with_layer},
 };
 use anyrender::PaintScene;
-use kurbo::{Rect, Vec2};
+use kurbo::{Affine, BezPath, Insets, Rect, Vec2};
+use peniko::{Compose, Fill, Mix};
+use style::properties::generated::longhands::background_clip::single_value::computed_value::T as StyloBackgroundClip;
 
 impl ElementCx<'_> {
     pub(super) fn draw_outset_box_shadow(&self, scene: &mut impl PaintScene) {
         let box_shadow = &self.style.get_effects().box_shadow.0;
 
-        // TODO: Only apply clip if element has transparency
         let has_outset_shadow = box_shadow.iter().any(|s| !s.inset);
         if !has_outset_shadow {
             return;
@@ -20,18 +22,22 @@ impl ElementCx<'_> {
         let max_shadow_rect = box_shadow.iter().fold(Rect::ZERO, |prev, shadow| {
             let x = shadow.base.horizontal.px() as f64 * self.scale;
             let y = shadow.base.vertical.px() as f64 * self.scale;
-            let blur = shadow.base.blur.px() as f64 * self.scale;
+            let std_dev = shadow.base.blur.px() as f64 * self.scale / 2.0;
             let spread = shadow.spread.px() as f64 * self.scale;
-            let offset = spread + blur * 2.5;
+            let offset = spread + std_dev * 3.0;
 
             let rect = self.frame.border_box.inflate(offset, offset) + Vec2::new(x, y);
 
             prev.union(rect)
         });
 
+        // Outset shadows are only painted outside of the border box. An opaque background which
+        // covers the border box hides the shadow beneath the box anyway, so no clip is needed.
+        let needs_clip = !self.has_opaque_border_box_background();
+
         maybe_with_layer(
             scene,
-            has_outset_shadow,
+            needs_clip,
             1.0,
             self.transform,
             &self.frame.shadow_clip(max_shadow_rect),
@@ -42,29 +48,25 @@ impl ElementCx<'_> {
                         .color
                         .resolve_to_absolute(&current_color)
                         .as_srgb_color();
+                    if shadow_color.components[3] == 0.0 {
+                        continue;
+                    }
 
-                    let alpha = shadow_color.components[3];
-                    if alpha != 0.0 {
-                        let transform = self.transform.then_translate(Vec2 {
-                            x: shadow.base.horizontal.px() as f64 * self.scale,
-                            y: shadow.base.vertical.px() as f64 * self.scale,
-                        });
-
-                        // TODO draw shadows with matching individual radii instead of averaging
-                        let radius = self.frame.border_radii.average();
-
-                        let spread = shadow.spread.px() as f64 * self.scale;
-                        let rect = self.frame.border_box.inflate(spread, spread);
-
-                        // Fill the color
-                        scene.draw_box_shadow(
-                            transform,
-                            rect,
-                            shadow_color,
-                            radius,
-                            shadow.base.blur.px() as f64,
-                        );
+                    let transform = self.transform.pre_translate(Vec2 {
+                        x: shadow.base.horizontal.px() as f64 * self.scale,
+                        y: shadow.base.vertical.px() as f64 * self.scale,
+                    });
+
+                    // The shadow shape is the border box grown by the spread
+                    let spread = shadow.spread.px() as f64 * self.scale;
+                    let rect = self.frame.border_box.inflate(spread, spread);
+                    if rect.width() <= 0.0 || rect.height() <= 0.0 {
+                        continue;
                     }
+                    let radii = self.frame.border_radii.spread(spread);
+                    let std_dev = shadow.base.blur.px() as f64 * self.scale / 2.0;
+
+                    draw_blurred_rounded_rect(scene, transform, rect, radii, shadow_color, std_dev);
                 }
             },
         )
@@ -78,39 +80,304 @@ impl ElementCx<'_> {
             return;
         }
 
-        maybe_with_layer(
-            scene,
-            has_inset_shadow,
-            1.0,
-            self.transform,
-            &self.frame.padding_box_path(),
-            |scene| {
-                for shadow in box_shadow.iter().filter(|s| s.inset) {
-                    let shadow_color = shadow
-                        .base
-                        .color
-                        .resolve_to_absolute(&current_color)
-                        .as_srgb_color();
-                    if shadow_color != Color::TRANSPARENT {
-                        let transform = self.transform.then_translate(Vec2 {
-                            x: shadow.base.horizontal.px() as f64,
-                            y: shadow.base.vertical.px() as f64,
-                        });
-
-                        //TODO draw shadows with matching individual radii instead of averaging
-                        let radius = self.frame.border_radii.average();
-
-                        // Fill the color
-                        scene.draw_box_shadow(
-                            transform,
-                            self.frame.border_box,
-                            shadow_color,
-                            radius,
-                            shadow.base.blur.px() as f64 * self.scale,
-                        );
-                    }
+        let padding_box = self.frame.padding_box;
+        let padding_radii = self.frame.padding_box_radii();
+        let padding_box_path = self.frame.padding_box_path();
+
+        for shadow in box_shadow.iter().filter(|s| s.inset).rev() {
+            let shadow_color = shadow
+                .base
+                .color
+                .resolve_to_absolute(&current_color)
+                .as_srgb_color();
+            if shadow_color.components[3] == 0.0 {
+                continue;
+            }
+
+            // An inset shadow is painted everywhere in the padding box outside of the "hole": the
+            // padding box shrunk by the spread and moved by the offset
+            let offset = Vec2 {
+                x: shadow.base.horizontal.px() as f64 * self.scale,
+                y: shadow.base.vertical.px() as f64 * self.scale,
+            };
+            let spread = shadow.spread.px() as f64 * self.scale;
+            let std_dev = shadow.base.blur.px() as f64 * self.scale / 2.0;
+            let hole = padding_box.inflate(-spread, -spread) + offset;
+            let hole_radii = padding_radii.spread(-spread);
+
+            if hole.width() <= 0.0 || hole.height() <= 0.0 {
+                scene.fill(
+                    Fill::NonZero,
+                    self.transform,
+                    shadow_color,
+                    None,
+                    &padding_box_path,
+                );
+                continue;
+            }
+
+            // A sharp hole within the padding box can be cut out of the padding box directly
+            let hole_is_contained = padding_box.union(hole) == padding_box
+                && (offset == Vec2::ZERO || padding_radii == NonUniformRoundedRectRadii::default());
+            if std_dev == 0.0 && hole_is_contained {
+                let mut path = padding_box_path.clone();
+                path.extend(
+                    rounded_rect_path(hole, hole_radii)
+                        .elements()
+                        .iter()
+                        .copied(),
+                );
+                scene.fill(Fill::EvenOdd, self.transform, shadow_color, None, &path);
+                continue;
+            }
+
+            // Otherwise the padding box is filled and the (blurred) hole is then erased from it
+            let group_pushed = maybe_push_blend_layer(
+                scene,
+                true,
+                Mix::Normal,
+                1.0,
+                self.transform,
+                &padding_box_path,
+            );
+            if !group_pushed {
+                continue;
+            }
+            scene.fill(
+                Fill::NonZero,
+                self.transform,
+                shadow_color,
+                None,
+                &padding_box_path,
+            );
+            let knockout_pushed = maybe_push_blend_layer(
+                scene,
+                true,
+                Compose::DestOut,
+                1.0,
+                self.transform,
+                &padding_box_path,
+            );
+            if knockout_pushed {
+                draw_blurred_rounded_rect(
+                    scene,
+                    self.transform,
+                    hole,
+                    hole_radii,
+                    Color::BLACK,
+                    std_dev,
+                );
+            }
+            maybe_pop_layer(scene, knockout_pushed);
+            maybe_pop_layer(scene, group_pushed);
+        }
+    }
+
+    /// Whether the element's background color is opaque and painted beneath its whole border box
+    fn has_opaque_border_box_background(&self) -> bool {
+        let background = self.style.get_background();
+        let layer_count = background.background_image.0.len();
+        let clip = &background.background_clip.0;
+        let color = background
+            .background_color
+            .resolve_to_absolute(&self.style.clone_color())
+            .as_srgb_color();
+
+        color.components[3] == 1.0
+            && clip[(layer_count - 1) % clip.len()] == StyloBackgroundClip::BorderBox
+    }
+}
+
+/// A rectangle with (possibly elliptical) rounded corners
+fn rounded_rect_path(rect: Rect, radii: NonUniformRoundedRectRadii) -> BezPath {
+    CssBox::new(rect, Insets::ZERO, Insets::ZERO, 0.0, radii).border_box_path()
+}
+
+/// Draw a rounded rect blurred by a gaussian blur with the specified standard deviation.
+///
+/// Blurred shapes are drawn by the renderer with a single circular radius. Rects whose corners
+/// have different (or elliptical) radii are drawn one quadrant at a time, with each quadrant using
+/// the radius of its corner (elliptical corners are drawn as circles in a scaled space).
+fn draw_blurred_rounded_rect(
+    scene: &mut impl PaintScene,
+    transform: Affine,
+    rect: Rect,
+    radii: NonUniformRoundedRectRadii,
+    color: Color,
+    std_dev: f64,
+) {
+    if std_dev <= 0.0 {
+        let path = rounded_rect_path(rect, radii);
+        scene.fill(Fill::NonZero, transform, color, None, &path);
+        return;
+    }
+
+    // Correct overlapping radii in the same way as the unblurred shape
+    let radii = CssBox::new(rect, Insets::ZERO, Insets::ZERO, 0.0, radii).border_radii;
+    if radii.is_uniform() {
+        scene.draw_box_shadow(transform, rect, color, radii.top_left.x, std_dev);
+        return;
+    }
+
+    let extent = std_dev * 3.0;
+    let center = rect.center();
+    let quadrants = [
+        (
+            radii.top_left,
+            Rect::new(rect.x0 - extent, rect.y0 - extent, center.x, center.y),
+        ),
+        (
+            radii.top_right,
+            Rect::new(center.x, rect.y0 - extent, rect.x1 + extent, center.y),
+        ),
+        (
+            radii.bottom_right,
+            Rect::new(center.x, center.y, rect.x1 + extent, rect.y1 + extent),
+        ),
+        (
+            radii.bottom_left,
+            Rect::new(rect.x0 - extent, center.y, center.x, rect.y1 + extent),
+        ),
+    ];
+    for (radius, quadrant) in quadrants {
+        maybe_with_layer(scene, true, 1.0, transform, &quadrant, |scene| {
+            if radius.x <= 0.0 || radius.y <= 0.0 {
+                scene.draw_box_shadow(transform, rect, color, 0.0, std_dev);
+                return;
+            }
+
+            // Scale the y axis so that the elliptical corner becomes a circle
+            let y_scale = radius.y / radius.x;
+            let scaled_transform = transform.pre_scale_non_uniform(1.0, y_scale);
+            let scaled_rect = Rect::new(rect.x0, rect.y0 / y_scale, rect.x1, rect.y1 / y_scale);
+            scene.draw_box_shadow(scaled_transform, scaled_rect, color, radius.x, std_dev);
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::display_list::Command;
+    use crate::test_util::{layout_html, paint_commands};
+    use kurbo::{Point, Shape as _};
+
+    /// Paint a 50x20 div at the origin with the given style
+    fn paint_box(style: &str) -> Vec<Command> {
+        let doc = layout_html(&format!(
+            r#"<body style="margin: 0"><div style="width: 50px; height: 20px; {style}"></div></body>"#
+        ));
+        paint_commands(&doc)
+    }
+
+    /// The transform, rect, radius and standard deviation of each blurred shadow
+    fn box_shadows(commands: &[Command]) -> Vec<(Affine, Rect, f64, f64)> {
+        commands
+            .iter()
+            .filter_map(|command| match command {
+                Command::BoxShadow {
+                    transform,
+                    rect,
+                    radius,
+                    std_dev,
+                    ..
+                } => Some((*transform, *rect, *radius, *std_dev)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn layer_count(commands: &[Command]) -> usize {
+        commands
+            .iter()
+            .filter(|command| matches!(command, Command::PushLayer { .. }))
+            .count()
+    }
+
+    #[test]
+    fn test_uniform_radius_outset_shadow() {
+        // The spread grows the border box and its radius, and the blur radius is twice the
+        // standard deviation
+        let commands = paint_box("border-radius: 10px; box-shadow: 5px 5px 8px 2px red");
+        let shadows = box_shadows(&commands);
+        assert_eq!(
+            shadows,
+            [(
+                Affine::translate((5.0, 5.0)),
+                Rect::new(-2.0, -2.0, 52.0, 22.0),
+                12.0,
+                4.0
+            )]
+        );
+
+        // The shadow is clipped to outside of the border box
+        assert_eq!(layer_count(&commands), 1);
+
+        // Unless an opaque background hides the shadow beneath the box
+        let commands =
+            paint_box("background: white; border-radius: 10px; box-shadow: 5px 5px 8px 2px red");
+        assert_eq!(box_shadows(&commands).len(), 1);
+        assert_eq!(layer_count(&commands), 0);
+    }
+
+    #[test]
+    fn test_non_uniform_radii_outset_shadow() {
+        // Each quadrant is drawn with the radius of its own corner, clipped to the quadrant
+        let commands =
+            paint_box("background: white; border-radius: 10px 0; box-shadow: 0 0 8px red");
+        let shadows = box_shadows(&commands);
+        let radii: Vec<_> = shadows.iter().map(|shadow| shadow.2).collect();
+        assert_eq!(radii, [10.0, 0.0, 10.0, 0.0]);
+        assert!(
+            shadows
+                .iter()
+                .all(|shadow| shadow.1 == Rect::new(0.0, 0.0, 50.0, 20.0))
+        );
+        assert_eq!(layer_count(&commands), 4);
+
+        // Elliptical corners are drawn as circles in a space scaled along the y axis
+        let commands =
+            paint_box("background: white; border-radius: 20px / 10px; box-shadow: 0 0 8px red");
+        let shadows = box_shadows(&commands);
+        assert_eq!(shadows.len(), 4);
+        for (transform, rect, radius, _) in shadows {
+            assert_eq!(radius, 20.0);
+            assert_eq!(transform, Affine::scale_non_uniform(1.0, 0.5));
+            assert_eq!(rect, Rect::new(0.0, 0.0, 50.0, 40.0));
+        }
+    }
+
+    #[test]
+    fn test_inset_shadow_spread() {
+        // A sharp inset shadow is the padding box with the hole (shrunk by the spread) cut out
+        let commands = paint_box("box-shadow: inset 0 0 0 5px red");
+        let Some(Command::Fill { fill, path, .. }) = commands.iter().find(|command| {
+            matches!(
+                command,
+                Command::Fill {
+                    fill: Fill::EvenOdd,
+                    ..
                 }
-            },
+            )
+        }) else {
+            panic!("inset shadow was not painted");
+        };
+        assert_eq!(*fill, Fill::EvenOdd);
+        assert_eq!(path.bounding_box(), Rect::new(0.0, 0.0, 50.0, 20.0));
+        let is_filled = |x, y| path.winding(Point::new(x, y)) % 2 != 0;
+        assert!(is_filled(2.0, 10.0));
+        assert!(is_filled(25.0, 2.0));
+        assert!(!is_filled(25.0, 10.0));
+        assert!(!is_filled(6.0, 6.0));
+        assert_eq!(layer_count(&commands), 0);
+
+        // A blurred hole is erased from the filled padding box
+        let commands = paint_box("box-shadow: inset 2px 0 6px 5px red");
+        let shadows = box_shadows(&commands);
+        assert_eq!(
+            shadows,
+            [(Affine::IDENTITY, Rect::new(7.0, 5.0, 47.0, 15.0), 0.0, 3.0)]
         );
+        assert_eq!(layer_count(&commands), 2);
     }
 }
@@ -1,8 +1,9 @@
-use super::ElementCx;
+use super::{BlitzDomPainter, ElementCx};
 use crate::color::{Color, ToColorColor as _};
 use anyrender::PaintScene;
-use blitz_dom::local_name;
-use kurbo::{Affine, BezPath, Cap, Circle, Join, Point, RoundedRect, Stroke, Vec2};
+use blitz_dom::node::{SelectData, SelectOption};
+use blitz_dom::{MeterRegion, MeterValues, ProgressValues, RangeBounds, local_name};
+use kurbo::{Affine, BezPath, Cap, Circle, Join, Point, Rect, RoundedRect, Stroke, Vec2};
 use peniko::Fill;
 use style::dom::TElement as _;
 
@@ -45,6 +46,257 @@ impl ElementCx<'_> {
     }
 }
 
+// TODO: this should be coming from css accent-color
+const ACCENT_COLOR: Color = Color::from_rgba8(0, 117, 255, 255);
+const DISABLED_COLOR: Color = Color::from_rgba8(209, 209, 209, 255);
+const TRACK_COLOR: Color = Color::from_rgba8(224, 224, 224, 255);
+
+impl ElementCx<'_> {
+    pub(super) fn draw_range_input(&self, scene: &mut impl PaintScene) {
+        let Some(range_data) = self.element.range_input_data() else {
+            return;
+        };
+        let bounds = RangeBounds::from_element(self.element);
+        let position = bounds.position(range_data.value);
+        let disabled = self.node.attr(local_name!("disabled")).is_some();
+        let accent_color = match disabled {
+            true => DISABLED_COLOR,
+            false => ACCENT_COLOR,
+        };
+
+        let content_box = self.frame.content_box;
+        let thumb_radius = (8.0 * self.scale).min(content_box.height() / 2.0);
+        let track_start = content_box.x0 + thumb_radius;
+        let track_end = (content_box.x1 - thumb_radius).max(track_start);
+        let thumb_x = track_start + position * (track_end - track_start);
+        let center_y = content_box.center().y;
+        let track_height = 4.0 * self.scale;
+
+        let track = |x0: f64, x1: f64| {
+            Rect::new(
+                x0,
+                center_y - track_height / 2.0,
+                x1,
+                center_y + track_height / 2.0,
+            )
+            .to_rounded_rect(track_height / 2.0)
+        };
+        let filled = track(content_box.x0, thumb_x);
+        let unfilled = track(thumb_x, content_box.x1);
+        scene.fill(Fill::NonZero, self.transform, TRACK_COLOR, None, &unfilled);
+        scene.fill(Fill::NonZero, self.transform, accent_color, None, &filled);
+
+        let thumb = Circle::new((thumb_x, center_y), thumb_radius);
+        scene.fill(Fill::NonZero, self.transform, accent_color, None, &thumb);
+    }
+
+    pub(super) fn draw_progress_or_meter(&self, scene: &mut impl PaintScene) {
+        let (position, color) = match self.element.name.local.as_ref() {
+            "progress" => {
+                // Indeterminate progress bars are drawn as an empty track
+                let position = ProgressValues::from_element(self.element).position();
+                (position.unwrap_or(0.0), ACCENT_COLOR)
+            }
+            "meter" => {
+                let meter = MeterValues::from_element(self.element);
+                let color = match meter.region() {
+                    MeterRegion::Optimum => Color::from_rgba8(16, 124, 16, 255),
+                    MeterRegion::SubOptimum => Color::from_rgba8(255, 185, 0, 255),
+                    MeterRegion::SubSubOptimum => Color::from_rgba8(216, 59, 1, 255),
+                };
+                (meter.position(), color)
+            }
+            _ => return,
+        };
+
+        let border_box = self.frame.border_box;
+        let radius = (4.0 * self.scale).min(border_box.height() / 2.0);
+        let track = border_box.to_rounded_rect(radius);
+        scene.fill(Fill::NonZero, self.transform, TRACK_COLOR, None, &track);
+
+        if position > 0.0 {
+            let bar = Rect::new(
+                border_box.x0,
+                border_box.y0,
+                border_box.x0 + position * border_box.width(),
+                border_box.y1,
+            )
+            .to_rounded_rect(radius);
+            scene.fill(Fill::NonZero, self.transform, color, None, &bar);
+        }
+    }
+
+    pub(super) fn draw_select(&self, scene: &mut impl PaintScene, pos: Point) {
+        let Some(data) = self.element.select_data() else {
+            return;
+        };
+        let content_box = self.frame.content_box;
+        let text_color = self.style.clone_color().as_srgb_color();
+
+        if data.is_drop_down() {
+            // The selected option
+            if let Some(option) = data.selected_index().map(|idx| &data.options[idx]) {
+                let text_height = option.layout.height() as f64 / self.scale;
+                let y_offset = (content_box.height() / self.scale - text_height) / 2.0;
+                let pos = Point {
+                    x: pos.x,
+                    y: pos.y + y_offset,
+                };
+                crate::text::stroke_text(
+                    self.scale,
+                    scene,
+                    option.layout.lines(),
+                    self.context.dom,
+                    self.transform_at(pos),
+                );
+            }
+
+            // The drop-down arrow
+            let arrow_width = SelectData::ARROW_WIDTH as f64 * self.scale;
+            let center = Point {
+                x: content_box.x1 - arrow_width / 2.0,
+                y: content_box.center().y,
+            };
+            draw_chevron(scene, center, self.transform, text_color, self.scale);
+            return;
+        }
+
+        // A list box shows `display_size` rows, starting at the scroll position
+        let row_height = data.row_height as f64;
+        let highlight_color = match self.node.is_focussed() {
+            true => color::palette::css::LIGHT_STEEL_BLUE,
+            false => color::palette::css::LIGHT_GRAY,
+        };
+        let rows = data
+            .options
+            .iter()
+            .skip(data.scroll_index)
+            .take(data.display_size);
+        for (row, option) in rows.enumerate() {
+            let row_y = row as f64 * row_height;
+            if option.selected {
+                let rect = Rect::new(
+                    content_box.x0,
+                    content_box.y0 + row_y * self.scale,
+                    content_box.x1,
+                    content_box.y0 + (row_y + row_height) * self.scale,
+                );
+                scene.fill(Fill::NonZero, self.transform, highlight_color, None, &rect);
+            }
+            let transform = self.transform_at(pos);
+            draw_option_text(self.context, scene, option, row_height, transform, row_y);
+        }
+    }
+}
+
+impl BlitzDomPainter<'_> {
+    /// Draw the popup of an open drop-down \<select\> above all other content
+    pub(super) fn draw_select_popup(&self, scene: &mut impl PaintScene) {
+        let Some(popup) = self.dom.select_popup() else {
+            return;
+        };
+        let Some(data) = self
+            .dom
+            .get_node(popup.select_id)
+            .and_then(|node| node.element_data())
+            .and_then(|el| el.select_data())
+        else {
+            return;
+        };
+
+        let mut pos = Point {
+            x: popup.x as f64 - self.origin.x,
+            y: popup.y as f64 - self.origin.y,
+        };
+        let transform = Affine::translate((pos.x * self.scale, pos.y * self.scale));
+        let rect = Rect::new(
+            0.0,
+            0.0,
+            popup.width as f64 * self.scale,
+            popup.height() as f64 * self.scale,
+        );
+        scene.fill(Fill::NonZero, transform, Color::WHITE, None, &rect);
+
+        // Inset option labels slightly from the edge of the popup
+        pos.x += 2.0;
+        let text_transform = Affine::translate((pos.x * self.scale, pos.y * self.scale));
+        let row_height = popup.row_height as f64;
+        let rows = data
+            .options
+            .iter()
+            .enumerate()
+            .skip(popup.first_option)
+            .take(popup.row_count);
+        for (row, (idx, option)) in rows.enumerate() {
+            let row_y = row as f64 * row_height;
+            if data.highlighted == Some(idx) {
+                let row_rect = Rect::new(
+                    0.0,
+                    row_y * self.scale,
+                    rect.x1,
+                    (row_y + row_height) * self.scale,
+                );
+                let highlight_color = color::palette::css::LIGHT_STEEL_BLUE;
+                scene.fill(Fill::NonZero, transform, highlight_color, None, &row_rect);
+            }
+            draw_option_text(self, scene, option, row_height, text_transform, row_y);
+        }
+
+        const GRAY: Color = color::palette::css::GRAY;
+        scene.stroke(&Stroke::new(self.scale), transform, GRAY, None, &rect);
+    }
+}
+
+/// Draw the label of an option within a row of a list box or popup. `transform` is the transform
+/// of the top-left of the list of options.
+fn draw_option_text(
+    painter: &BlitzDomPainter,
+    scene: &mut impl PaintScene,
+    option: &SelectOption,
+    row_height: f64,
+    transform: Affine,
+    row_y: f64,
+) {
+    let indent = match option.in_optgroup {
+        true => SelectOption::OPTGROUP_INDENT as f64,
+        false => 0.0,
+    };
+    let text_height = option.layout.height() as f64 / painter.scale;
+    let offset = Vec2 {
+        x: indent * painter.scale,
+        y: (row_y + (row_height - text_height) / 2.0) * painter.scale,
+    };
+    crate::text::stroke_text(
+        painter.scale,
+        scene,
+        option.layout.lines(),
+        painter.dom,
+        transform.pre_translate(offset),
+    );
+}
+
+fn draw_chevron(
+    scene: &mut impl PaintScene,
+    center: Point,
+    transform: Affine,
+    color: Color,
+    scale: f64,
+) {
+    let mut path = BezPath::new();
+    path.move_to((center.x - 4.0 * scale, center.y - 2.0 * scale));
+    path.line_to((center.x, center.y + 2.0 * scale));
+    path.line_to((center.x + 4.0 * scale, center.y - 2.0 * scale));
+
+    let style = Stroke {
+        width: 1.5 * scale,
+        join: Join::Round,
+        start_cap: Cap::Round,
+        end_cap: Cap::Round,
+        ..Default::default()
+    };
+    scene.stroke(&style, transform, color, None, &path);
+}
+
 fn draw_checkbox(
     scene: &mut impl PaintScene,
     checked: bool,
@@ -61,3 +61,28 @@ fn compute_object_fit_cover(
 
     object_size.map(|dim| dim * ratio)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_object_fit() {
+        let size = |width, height| taffy::Size { width, height };
+        let container = size(200.0, 100.0);
+        let object = Some(size(50.0, 50.0));
+
+        let fit = |object_fit| compute_object_fit(container, object, object_fit);
+        assert_eq!(fit(ObjectFit::Fill), size(200.0, 100.0));
+        assert_eq!(fit(ObjectFit::Contain), size(100.0, 100.0));
+        assert_eq!(fit(ObjectFit::Cover), size(200.0, 200.0));
+        assert_eq!(fit(ObjectFit::None), size(50.0, 50.0));
+        assert_eq!(fit(ObjectFit::ScaleDown), size(50.0, 50.0));
+
+        let large = Some(size(400.0, 400.0));
+        assert_eq!(
+            compute_object_fit(container, large, ObjectFit::ScaleDown),
+            size(100.0, 100.0)
+        );
+    }
+}
@@ -0,0 +1,43 @@
+//! Fixtures shared by the unit tests
+
+use std::cell::RefCell;
+
+use blitz_dom::{BaseDocument, DocumentConfig};
+use blitz_html::HtmlDocument;
+use blitz_traits::shell::{ColorScheme, Viewport};
+
+use crate::display_list::{Command, Item, Recorder};
+
+/// The width and height of the viewport (in CSS pixels)
+pub(crate) const VIEWPORT_SIZE: u32 = 200;
+
+/// Parse and lay out `html` in the viewport
+pub(crate) fn layout_html(html: &str) -> HtmlDocument {
+    let config = DocumentConfig {
+        viewport: Some(Viewport::new(
+            VIEWPORT_SIZE,
+            VIEWPORT_SIZE,
+            1.0,
+            ColorScheme::Light,
+        )),
+        ..Default::default()
+    };
+    let mut doc = HtmlDocument::from_html(html, config);
+    doc.resolve(0.0);
+    doc
+}
+
+/// The drawing commands which paint the document (at a scale of 1)
+pub(crate) fn paint_commands(doc: &BaseDocument) -> Vec<Command> {
+    let items = RefCell::new(Vec::new());
+    let mut recorder = Recorder { items: &items };
+    crate::paint_scene(&mut recorder, doc, 1.0, VIEWPORT_SIZE, VIEWPORT_SIZE);
+    items
+        .take()
+        .into_iter()
+        .filter_map(|item| match item {
+            Item::Command(command) => Some(command),
+            Item::Node { .. } => None,
+        })
+        .collect()
+}
@@ -1,99 +1,781 @@
+use crate::layers::{maybe_pop_layer, maybe_push_blend_layer};
 use anyrender::PaintScene;
-use blitz_dom::{BaseDocument, node::TextBrush, util::ToColorColor};
-use kurbo::{Affine, Point, Stroke};
-use parley::{Line, PositionedLayoutItem};
-use peniko::Fill;
-use style::values::computed::TextDecorationLine;
+use blitz_dom::BaseDocument;
+use blitz_dom::node::{TextBrush, TextLayout};
+use blitz_dom::util::{Color, ToColorColor};
+use kurbo::{Affine, BezPath, Cap, Rect, Stroke, Vec2};
+use parley::{GlyphRun, Line, PositionedLayoutItem};
+use peniko::{Compose, Fill, Mix};
+use skrifa::MetadataProvider as _;
+use skrifa::instance::{LocationRef, Size};
+use skrifa::outline::{DrawSettings, OutlinePen};
+use skrifa::raw::types::F2Dot14;
+use style::properties::ComputedValues;
+use style::properties::generated::longhands::text_decoration_style::computed_value::T as TextDecorationStyle;
+use style::values::computed::{
+    CSSPixelLength, LengthPercentageOrAuto, TextDecorationLength, TextDecorationLine,
+    TextDecorationSkipInk,
+};
 
 pub(crate) fn stroke_text<'a>(
     scale: f64,
     scene: &mut impl PaintScene,
     lines: impl Iterator<Item = Line<'a, TextBrush>>,
     doc: &BaseDocument,
-    pos: Point,
+    transform: Affine,
 ) {
-    let transform = Affine::translate((pos.x * scale, pos.y * scale));
     for line in lines {
-        for item in line.items() {
-            if let PositionedLayoutItem::GlyphRun(glyph_run) = item {
-                let mut x = glyph_run.offset();
-                let y = glyph_run.baseline();
-
-                let run = glyph_run.run();
-                let font = run.font();
-                let font_size = run.font_size();
-                let metrics = run.metrics();
-                let style = glyph_run.style();
-                let synthesis = run.synthesis();
-                let glyph_xform = synthesis
-                    .skew()
-                    .map(|angle| Affine::skew(angle.to_radians().tan() as f64, 0.0));
-
-                // Styles
-                let styles = doc
-                    .get_node(style.brush.id)
-                    .unwrap()
-                    .primary_styles()
-                    .unwrap();
-                let itext_styles = styles.get_inherited_text();
-                let text_styles = styles.get_text();
-                let text_color = itext_styles.color.as_color_color();
-                let text_decoration_color = text_styles
-                    .text_decoration_color
-                    .as_absolute()
-                    .map(ToColorColor::as_color_color)
-                    .unwrap_or(text_color);
-                let text_decoration_brush = peniko::Brush::from(text_decoration_color);
-                let text_decoration_line = text_styles.text_decoration_line;
-                let has_underline = text_decoration_line.contains(TextDecorationLine::UNDERLINE);
-                let has_strikethrough =
-                    text_decoration_line.contains(TextDecorationLine::LINE_THROUGH);
-
-                scene.draw_glyphs(
-                    font,
-                    font_size,
-                    true, // hint
-                    run.normalized_coords(),
-                    Fill::NonZero,
-                    &peniko::Brush::from(text_color),
-                    1.0, // alpha
-                    transform,
-                    glyph_xform,
-                    glyph_run.glyphs().map(|glyph| {
-                        let gx = x + glyph.x;
-                        let gy = y - glyph.y;
-                        x += glyph.advance;
-
-                        anyrender::Glyph {
-                            id: glyph.id as _,
-                            x: gx,
-                            y: gy,
-                        }
-                    }),
-                );
+        stroke_line(scale, scene, &line, doc, transform, f32::INFINITY);
+    }
+}
 
-                let mut draw_decoration_line = |offset: f32, size: f32, brush: &peniko::Brush| {
-                    let x = glyph_run.offset() as f64;
-                    let w = glyph_run.advance() as f64;
-                    let y = (glyph_run.baseline() - offset + size / 2.0) as f64;
-                    let line = kurbo::Line::new((x, y), (x + w, y));
-                    scene.stroke(&Stroke::new(size as f64), transform, brush, None, &line)
-                };
+/// Draw the text of an inline formatting context. In addition to what `stroke_text` draws, this draws
+/// a hyphen at the end of lines which were broken at a soft hyphen and, if `text_overflow` is set
+/// to a width and a marker string, truncates lines which overflow that width with the marker.
+///
+/// If `flip_lines` is set then each line is flipped vertically within its line box. This is used to
+/// paint `vertical-lr` text, as the transform from the flow's coordinate space is a reflection.
+///
+/// Lines are drawn at their line offsets (see `TextLayout::line_offset`), and the `text_overflow`
+/// width is relative to the content box.
+pub(crate) fn stroke_inline_layout(
+    scale: f64,
+    scene: &mut impl PaintScene,
+    text_layout: &TextLayout,
+    doc: &BaseDocument,
+    transform: Affine,
+    text_overflow: Option<(f32, &str)>,
+    flip_lines: bool,
+) {
+    for (line_index, line) in text_layout.layout.lines().enumerate() {
+        let metrics = line.metrics();
+        let transform =
+            transform * line_transform(text_layout, line_index, &line, scale, flip_lines);
+        let line_x = text_layout.line_offset(line_index).x * scale as f32;
+        let overflow = text_overflow
+            .map(|(width, marker)| (width - line_x, marker))
+            .filter(|(width, _)| metrics.offset + metrics.advance > *width);
 
-                if has_underline {
-                    let offset = metrics.underline_offset;
-                    let size = metrics.underline_size;
+        if let Some((width, marker)) = overflow {
+            // The marker is drawn in the font of the last glyph run which starts within the line box
+            let marker_run = line
+                .items()
+                .filter_map(|item| match item {
+                    PositionedLayoutItem::GlyphRun(glyph_run) => Some(glyph_run),
+                    PositionedLayoutItem::InlineBox(_) => None,
+                })
+                .take_while(|glyph_run| glyph_run.offset() < width)
+                .last();
+            let Some(marker_run) = marker_run else {
+                continue;
+            };
+            let marker_glyphs = marker_glyphs(&marker_run, marker);
+            let marker_width: f32 = marker_glyphs.iter().map(|(_, advance)| advance).sum();
 
-                    // TODO: intercept line when crossing an descending character like "gqy"
-                    draw_decoration_line(offset, size, &text_decoration_brush);
+            let end = stroke_line(scale, scene, &line, doc, transform, width - marker_width)
+                .map(|(_, end)| end)
+                .unwrap_or(metrics.offset);
+            draw_marker_glyphs(scene, doc, &marker_run, &marker_glyphs, end, transform);
+        } else {
+            let last_run = stroke_line(scale, scene, &line, doc, transform, f32::INFINITY);
+            let text_end = line.text_range().end;
+            let ends_with_soft_hyphen = text_layout.text[..text_end]
+                .trim_end_matches(char::is_whitespace)
+                .ends_with(SOFT_HYPHEN);
+            if let Some((glyph_run, end)) = last_run.filter(|_| ends_with_soft_hyphen) {
+                // Prefer U+2010 HYPHEN, falling back to U+002D HYPHEN-MINUS
+                let mut hyphen = marker_glyphs(&glyph_run, "\u{2010}");
+                if hyphen.is_empty() {
+                    hyphen = marker_glyphs(&glyph_run, "-");
                 }
-                if has_strikethrough {
-                    let offset = metrics.strikethrough_offset;
-                    let size = metrics.strikethrough_size;
+                draw_marker_glyphs(scene, doc, &glyph_run, &hyphen, end, transform);
+            }
+        }
+    }
+}
+
+const SOFT_HYPHEN: char = '\u{00AD}';
+
+/// The transform from the coordinate space of a line to the coordinate space of the inline layout,
+/// which moves the line to its line offset and (if `flip_lines` is set) flips it vertically within
+/// its line box
+fn line_transform(
+    text_layout: &TextLayout,
+    line_index: usize,
+    line: &Line<'_, TextBrush>,
+    scale: f64,
+    flip_lines: bool,
+) -> Affine {
+    let offset = text_layout.line_offset(line_index);
+    let translate = Affine::translate((offset.x as f64 * scale, offset.y as f64 * scale));
+    match flip_lines {
+        true => {
+            let metrics = line.metrics();
+            let flip_y = (metrics.min_coord + metrics.max_coord) as f64;
+            translate * Affine::new([1.0, 0.0, 0.0, -1.0, 0.0, flip_y])
+        }
+        false => translate,
+    }
+}
 
-                    draw_decoration_line(offset, size, &text_decoration_brush);
+/// Draw the glyph runs of a line (and their shadows and decorations), omitting glyphs which would
+/// extend past `max_x`. Returns the last glyph run which was drawn and the position of the end of its
+/// last drawn glyph.
+fn stroke_line<'a>(
+    scale: f64,
+    scene: &mut impl PaintScene,
+    line: &Line<'a, TextBrush>,
+    doc: &BaseDocument,
+    transform: Affine,
+    max_x: f32,
+) -> Option<(GlyphRun<'a, TextBrush>, f32)> {
+    let mut last_run = None;
+    for item in line.items() {
+        if let PositionedLayoutItem::GlyphRun(glyph_run) = item {
+            if glyph_run.offset() >= max_x {
+                break;
+            }
+
+            // Styles
+            let style = glyph_run.style();
+            let styles = doc
+                .get_node(style.brush.id)
+                .unwrap()
+                .primary_styles()
+                .unwrap();
+            let itext_styles = styles.get_inherited_text();
+            let text_color = itext_styles.color.as_color_color();
+            let decoration = TextDecoration::from_styles(&styles, &glyph_run, text_color, scale);
+
+            // Shadows are painted beneath the text, with the first shadow on top
+            for shadow in itext_styles.text_shadow.0.iter().rev() {
+                let shadow_color = shadow
+                    .color
+                    .resolve_to_absolute(&itext_styles.color)
+                    .as_color_color();
+                if shadow_color.components[3] == 0.0 {
+                    continue;
                 }
+
+                let offset = Vec2 {
+                    x: shadow.horizontal.px() as f64 * scale,
+                    y: shadow.vertical.px() as f64 * scale,
+                };
+                let blur = shadow.blur.px() as f64 * scale;
+                draw_shadow_glyph_run(
+                    scene,
+                    &glyph_run,
+                    &decoration,
+                    shadow_color,
+                    transform.pre_translate(offset),
+                    blur,
+                    max_x,
+                );
+            }
+
+            let end = draw_glyph_run(
+                scene,
+                &glyph_run,
+                &decoration,
+                text_color,
+                decoration.color,
+                transform,
+                max_x,
+            );
+            last_run = Some((glyph_run, end));
+        }
+    }
+    last_run
+}
+
+/// Draw the shadow of a glyph run (and its decorations), blurred by a gaussian blur with the
+/// specified radius.
+///
+/// The scene API can only blur rounded rects, so the blur is approximated by summing copies of the
+/// glyphs sampled from the gaussian. Each copy is drawn in its own additive layer (weighted by its
+/// sample) within an isolated group, so the copies add up to the shadow's color where they overlap.
+fn draw_shadow_glyph_run(
+    scene: &mut impl PaintScene,
+    glyph_run: &GlyphRun<'_, TextBrush>,
+    decoration: &TextDecoration,
+    color: Color,
+    transform: Affine,
+    blur: f64,
+    max_x: f32,
+) {
+    let samples = blur_samples(blur);
+    if samples.len() == 1 {
+        draw_glyph_run(scene, glyph_run, decoration, color, color, transform, max_x);
+        return;
+    }
+
+    // The layers are clipped to the line box of the run, grown by the extent of the blur and the
+    // font size (to include glyphs and decorations which overflow the line box)
+    let metrics = glyph_run.run().metrics();
+    let baseline = glyph_run.baseline() as f64;
+    let overflow = blur + glyph_run.run().font_size() as f64;
+    let bounds = Rect::new(
+        glyph_run.offset() as f64,
+        baseline - metrics.ascent as f64,
+        (glyph_run.offset() + glyph_run.advance()) as f64,
+        baseline + metrics.descent as f64,
+    )
+    .inflate(overflow, overflow);
+
+    let group = maybe_push_blend_layer(scene, true, Mix::Normal, 1.0, transform, &bounds);
+    for (sample_offset, weight) in samples {
+        let pushed =
+            maybe_push_blend_layer(scene, group, Compose::Plus, weight, transform, &bounds);
+        // Without a layer the copies are composited over each other, which lightens the shadow
+        let color = match pushed {
+            true => color,
+            false => color.multiply_alpha(weight),
+        };
+        let transform = transform.pre_translate(sample_offset);
+        draw_glyph_run(scene, glyph_run, decoration, color, color, transform, max_x);
+        maybe_pop_layer(scene, pushed);
+    }
+    maybe_pop_layer(scene, group);
+}
+
+/// Offsets and weights of the copies of a shadow which are summed to approximate a gaussian blur
+/// with the specified radius (which is twice the standard deviation). The weights sum to 1.
+fn blur_samples(blur: f64) -> Vec<(Vec2, f32)> {
+    /// The maximum number of samples along each axis
+    const MAX_TAPS: usize = 5;
+
+    let sigma = blur / 2.0;
+    if sigma < 0.25 {
+        return vec![(Vec2::ZERO, 1.0)];
+    }
+
+    // Sample the gaussian out to two standard deviations, with the samples about a pixel apart
+    // (up to `MAX_TAPS`)
+    let taps = ((4.0 * sigma).ceil() as usize + 1).clamp(3, MAX_TAPS) | 1;
+    let step = 4.0 * sigma / (taps - 1) as f64;
+    let half = (taps / 2) as f64;
+    let mut samples = Vec::with_capacity(taps * taps);
+    for i in 0..taps {
+        for j in 0..taps {
+            let offset = Vec2::new((i as f64 - half) * step, (j as f64 - half) * step);
+            let weight = (-offset.hypot2() / (2.0 * sigma * sigma)).exp();
+            samples.push((offset, weight));
+        }
+    }
+    let total: f64 = samples.iter().map(|(_, weight)| weight).sum();
+    samples
+        .into_iter()
+        .map(|(offset, weight)| (offset, (weight / total) as f32))
+        .collect()
+}
+
+/// Draw a glyph run and its decorations in the order specified by CSS: underlines and overlines
+/// beneath the text, and line-throughs over it. Glyphs which would extend past `max_x` are omitted.
+/// Returns the position of the end of the last drawn glyph.
+fn draw_glyph_run(
+    scene: &mut impl PaintScene,
+    glyph_run: &GlyphRun<'_, TextBrush>,
+    decoration: &TextDecoration,
+    text_color: Color,
+    decoration_color: Color,
+    transform: Affine,
+    max_x: f32,
+) -> f32 {
+    let run = glyph_run.run();
+    let synthesis = run.synthesis();
+    let glyph_xform = synthesis
+        .skew()
+        .map(|angle| Affine::skew(angle.to_radians().tan() as f64, 0.0));
+    let end = positioned_glyphs(glyph_run, max_x)
+        .last()
+        .map(|glyph| glyph.end)
+        .unwrap_or(glyph_run.offset());
+
+    let decoration_brush = peniko::Brush::from(decoration_color);
+    for line in [TextDecorationLine::UNDERLINE, TextDecorationLine::OVERLINE] {
+        decoration.draw(scene, glyph_run, line, end, &decoration_brush, transform);
+    }
+
+    scene.draw_glyphs(
+        run.font(),
+        run.font_size(),
+        true, // hint
+        run.normalized_coords(),
+        Fill::NonZero,
+        &peniko::Brush::from(text_color),
+        1.0, // alpha
+        transform,
+        glyph_xform,
+        positioned_glyphs(glyph_run, max_x).map(|glyph| anyrender::Glyph {
+            id: glyph.id,
+            x: glyph.x,
+            y: glyph.y,
+        }),
+    );
+
+    decoration.draw(
+        scene,
+        glyph_run,
+        TextDecorationLine::LINE_THROUGH,
+        end,
+        &decoration_brush,
+        transform,
+    );
+
+    end
+}
+
+struct PositionedGlyph {
+    id: u32,
+    x: f32,
+    y: f32,
+    /// The position of the end of the glyph's advance
+    end: f32,
+}
+
+/// The glyphs of a run with their absolute positions, up to the first glyph which would extend past
+/// `max_x`
+fn positioned_glyphs<'a>(
+    glyph_run: &'a GlyphRun<'_, TextBrush>,
+    max_x: f32,
+) -> impl Iterator<Item = PositionedGlyph> + 'a {
+    let mut x = glyph_run.offset();
+    let y = glyph_run.baseline();
+    glyph_run
+        .glyphs()
+        .map(move |glyph| {
+            let positioned = PositionedGlyph {
+                id: glyph.id as _,
+                x: x + glyph.x,
+                y: y - glyph.y,
+                end: x + glyph.advance,
+            };
+            x += glyph.advance;
+            positioned
+        })
+        .take_while(move |glyph| glyph.end <= max_x)
+}
+
+/// The outlines of the glyphs of an inline formatting context as a single path, in the coordinate
+/// space the layout is drawn in. Lines are placed as in `stroke_inline_layout`.
+pub(crate) fn inline_layout_outline(
+    text_layout: &TextLayout,
+    scale: f64,
+    flip_lines: bool,
+) -> BezPath {
+    let mut path = BezPath::new();
+    for (line_index, line) in text_layout.layout.lines().enumerate() {
+        let line_transform = line_transform(text_layout, line_index, &line, scale, flip_lines);
+
+        for item in line.items() {
+            let PositionedLayoutItem::GlyphRun(glyph_run) = item else {
+                continue;
+            };
+            let run = glyph_run.run();
+            let font = run.font();
+            let Ok(font_ref) = skrifa::FontRef::from_index(font.data.as_ref(), font.index) else {
+                continue;
+            };
+            let outlines = font_ref.outline_glyphs();
+            let coords = normalized_coords(run);
+            let skew = run
+                .synthesis()
+                .skew()
+                .map(|angle| Affine::skew(angle.to_radians().tan() as f64, 0.0))
+                .unwrap_or(Affine::IDENTITY);
+
+            for glyph in positioned_glyphs(&glyph_run, f32::INFINITY) {
+                let Some(outline) = outlines.get(skrifa::GlyphId::new(glyph.id)) else {
+                    continue;
+                };
+                let settings =
+                    DrawSettings::unhinted(Size::new(run.font_size()), LocationRef::new(&coords));
+                let mut pen = BezPathPen(BezPath::new());
+                if outline.draw(settings, &mut pen).is_err() {
+                    continue;
+                }
+
+                // Outlines have y pointing up from the glyph origin
+                let glyph_transform = line_transform
+                    * Affine::translate((glyph.x as f64, glyph.y as f64))
+                    * skew
+                    * Affine::FLIP_Y;
+                path.extend(glyph_transform * pen.0);
+            }
+        }
+    }
+    path
+}
+
+/// Converts skrifa outlines into kurbo paths
+struct BezPathPen(BezPath);
+
+impl OutlinePen for BezPathPen {
+    fn move_to(&mut self, x: f32, y: f32) {
+        self.0.move_to((x as f64, y as f64));
+    }
+
+    fn line_to(&mut self, x: f32, y: f32) {
+        self.0.line_to((x as f64, y as f64));
+    }
+
+    fn quad_to(&mut self, cx0: f32, cy0: f32, x: f32, y: f32) {
+        self.0
+            .quad_to((cx0 as f64, cy0 as f64), (x as f64, y as f64));
+    }
+
+    fn curve_to(&mut self, cx0: f32, cy0: f32, cx1: f32, cy1: f32, x: f32, y: f32) {
+        self.0.curve_to(
+            (cx0 as f64, cy0 as f64),
+            (cx1 as f64, cy1 as f64),
+            (x as f64, y as f64),
+        );
+    }
+
+    fn close(&mut self) {
+        self.0.close_path();
+    }
+}
+
+/// Map `text` to glyphs (and their advances) in the font of a glyph run. The text isn't shaped:
+/// characters are mapped to glyphs individually and characters missing from the font are skipped.
+fn marker_glyphs(glyph_run: &GlyphRun<'_, TextBrush>, text: &str) -> Vec<(u32, f32)> {
+    let run = glyph_run.run();
+    let font = run.font();
+    let Ok(font_ref) = skrifa::FontRef::from_index(font.data.as_ref(), font.index) else {
+        return Vec::new();
+    };
+    let coords = normalized_coords(run);
+    let glyph_metrics =
+        font_ref.glyph_metrics(Size::new(run.font_size()), LocationRef::new(&coords));
+    let charmap = font_ref.charmap();
+    text.chars()
+        .filter_map(|c| {
+            let glyph_id = charmap.map(c)?;
+            let advance = glyph_metrics.advance_width(glyph_id).unwrap_or(0.0);
+            Some((glyph_id.to_u32(), advance))
+        })
+        .collect()
+}
+
+/// Draw glyphs obtained from `marker_glyphs` starting at `x`, on the baseline and in the font and
+/// color of a glyph run
+fn draw_marker_glyphs(
+    scene: &mut impl PaintScene,
+    doc: &BaseDocument,
+    glyph_run: &GlyphRun<'_, TextBrush>,
+    glyphs: &[(u32, f32)],
+    x: f32,
+    transform: Affine,
+) {
+    let run = glyph_run.run();
+    let Some(styles) = doc
+        .get_node(glyph_run.style().brush.id)
+        .and_then(|node| node.primary_styles())
+    else {
+        return;
+    };
+    let text_color = styles.get_inherited_text().color.as_color_color();
+
+    let y = glyph_run.baseline();
+    let mut x = x;
+    scene.draw_glyphs(
+        run.font(),
+        run.font_size(),
+        true, // hint
+        run.normalized_coords(),
+        Fill::NonZero,
+        &peniko::Brush::from(text_color),
+        1.0, // alpha
+        transform,
+        None,
+        glyphs.iter().map(|&(id, advance)| {
+            let glyph = anyrender::Glyph { id, x, y };
+            x += advance;
+            glyph
+        }),
+    );
+}
+
+fn normalized_coords(run: &parley::Run<'_, TextBrush>) -> Vec<F2Dot14> {
+    run.normalized_coords()
+        .iter()
+        .map(|coord| F2Dot14::from_bits(*coord))
+        .collect()
+}
+
+/// The resolved `text-decoration-*` styles of a glyph run. Lengths are in scaled pixels.
+struct TextDecoration {
+    line: TextDecorationLine,
+    style: TextDecorationStyle,
+    color: Color,
+    /// The thickness of the decoration lines, or `None` to use the font's thickness for each line
+    thickness: Option<f32>,
+    /// The offset of the underline below the baseline, or `None` to use the font's offset
+    underline_offset: Option<f32>,
+    skip_ink: bool,
+}
+
+impl TextDecoration {
+    fn from_styles(
+        styles: &ComputedValues,
+        glyph_run: &GlyphRun<'_, TextBrush>,
+        text_color: Color,
+        scale: f64,
+    ) -> Self {
+        let text_styles = styles.get_text();
+        let itext_styles = styles.get_inherited_text();
+
+        // Percentages are relative to the font size
+        let font_size = CSSPixelLength::new(glyph_run.run().font_size() / scale as f32);
+
+        let thickness = match &text_styles.text_decoration_thickness {
+            TextDecorationLength::LengthPercentage(lp) => {
+                Some(lp.resolve(font_size).px() * scale as f32)
+            }
+            TextDecorationLength::Auto | TextDecorationLength::FromFont => None,
+        };
+        let underline_offset = match &itext_styles.text_underline_offset {
+            LengthPercentageOrAuto::LengthPercentage(lp) => {
+                Some(lp.resolve(font_size).px() * scale as f32)
             }
+            LengthPercentageOrAuto::Auto => None,
+        };
+
+        Self {
+            line: text_styles.text_decoration_line,
+            style: text_styles.text_decoration_style,
+            color: text_styles
+                .text_decoration_color
+                .as_absolute()
+                .map(ToColorColor::as_color_color)
+                .unwrap_or(text_color),
+            thickness,
+            underline_offset,
+            skip_ink: itext_styles.text_decoration_skip_ink != TextDecorationSkipInk::None,
         }
     }
+
+    /// Draw one of the decoration lines (if the glyph run has it)
+    fn draw(
+        &self,
+        scene: &mut impl PaintScene,
+        glyph_run: &GlyphRun<'_, TextBrush>,
+        line: TextDecorationLine,
+        end: f32,
+        brush: &peniko::Brush,
+        transform: Affine,
+    ) {
+        if !self.line.contains(line) || self.style == TextDecorationStyle::MozNone {
+            return;
+        }
+
+        let metrics = glyph_run.run().metrics();
+        let baseline = glyph_run.baseline();
+        let (top, font_thickness) = if line == TextDecorationLine::UNDERLINE {
+            let offset = self.underline_offset.unwrap_or(-metrics.underline_offset);
+            (baseline + offset, metrics.underline_size)
+        } else if line == TextDecorationLine::OVERLINE {
+            (baseline - metrics.ascent, metrics.underline_size)
+        } else {
+            (
+                baseline - metrics.strikethrough_offset,
+                metrics.strikethrough_size,
+            )
+        };
+        let thickness = self.thickness.unwrap_or(font_thickness).max(1.0) as f64;
+        let y = top as f64 + thickness / 2.0;
+
+        let start = glyph_run.offset() as f64;
+        let end = end as f64;
+
+        // Line-throughs are drawn over the text so never skip ink
+        let segments = if self.skip_ink && line != TextDecorationLine::LINE_THROUGH {
+            let clearance = thickness;
+            let band = (
+                y - thickness / 2.0 - clearance,
+                y + thickness / 2.0 + clearance,
+            );
+            let gaps = ink_gaps(glyph_run, band, clearance);
+            subtract_gaps(start, end, &gaps)
+        } else {
+            vec![(start, end)]
+        };
+
+        for (x0, x1) in segments {
+            draw_decoration_segment(scene, self.style, x0, x1, y, thickness, brush, transform);
+        }
+    }
+}
+
+/// Draw a segment of a decoration line centred on `y`
+#[allow(clippy::too_many_arguments)]
+fn draw_decoration_segment(
+    scene: &mut impl PaintScene,
+    style: TextDecorationStyle,
+    x0: f64,
+    x1: f64,
+    y: f64,
+    thickness: f64,
+    brush: &peniko::Brush,
+    transform: Affine,
+) {
+    let line = |y: f64| kurbo::Line::new((x0, y), (x1, y));
+    match style {
+        TextDecorationStyle::Double => {
+            let stroke = Stroke::new(thickness);
+            for y in [y - thickness, y + thickness] {
+                scene.stroke(&stroke, transform, brush, None, &line(y));
+            }
+        }
+        TextDecorationStyle::Dotted => {
+            let stroke = Stroke::new(thickness)
+                .with_caps(Cap::Round)
+                .with_dashes(0.0, [0.0, thickness * 2.0]);
+            scene.stroke(&stroke, transform, brush, None, &line(y));
+        }
+        TextDecorationStyle::Dashed => {
+            let stroke =
+                Stroke::new(thickness).with_dashes(0.0, [thickness * 3.0, thickness * 2.0]);
+            scene.stroke(&stroke, transform, brush, None, &line(y));
+        }
+        TextDecorationStyle::Wavy => {
+            // A sequence of quadratic curves alternating above and below the centre line, with peaks
+            // at a distance of `thickness` from it
+            let half_wavelength = thickness * 3.0;
+            let mut path = BezPath::new();
+            path.move_to((x0, y));
+            let mut x = x0;
+            let mut direction = -1.0;
+            while x < x1 {
+                let next_x = (x + half_wavelength).min(x1);
+                let control = ((x + next_x) / 2.0, y + direction * thickness * 2.0);
+                path.quad_to(control, (next_x, y));
+                x = next_x;
+                direction = -direction;
+            }
+            scene.stroke(&Stroke::new(thickness), transform, brush, None, &path);
+        }
+        TextDecorationStyle::Solid | TextDecorationStyle::MozNone => {
+            scene.stroke(&Stroke::new(thickness), transform, brush, None, &line(y));
+        }
+    }
+}
+
+/// The horizontal ranges in which glyphs of the run intersect the vertical `band` (expanded by
+/// `clearance` on each side). Glyph bounding boxes are used rather than outlines.
+fn ink_gaps(
+    glyph_run: &GlyphRun<'_, TextBrush>,
+    band: (f64, f64),
+    clearance: f64,
+) -> Vec<(f64, f64)> {
+    let run = glyph_run.run();
+    let font = run.font();
+    let Ok(font_ref) = skrifa::FontRef::from_index(font.data.as_ref(), font.index) else {
+        return Vec::new();
+    };
+    let coords = normalized_coords(run);
+    let glyph_metrics =
+        font_ref.glyph_metrics(Size::new(run.font_size()), LocationRef::new(&coords));
+
+    positioned_glyphs(glyph_run, f32::INFINITY)
+        .filter_map(|PositionedGlyph { id, x, y, .. }| {
+            // Glyph bounds are relative to the glyph origin with y pointing up
+            let bounds = glyph_metrics.bounds(skrifa::GlyphId::new(id))?;
+            let top = (y - bounds.y_max) as f64;
+            let bottom = (y - bounds.y_min) as f64;
+            (bottom > band.0 && top < band.1).then(|| {
+                (
+                    (x + bounds.x_min) as f64 - clearance,
+                    (x + bounds.x_max) as f64 + clearance,
+                )
+            })
+        })
+        .collect()
+}
+
+/// Subtract the (possibly overlapping, unsorted) `gaps` from the range `start..end`
+fn subtract_gaps(start: f64, end: f64, gaps: &[(f64, f64)]) -> Vec<(f64, f64)> {
+    let mut gaps = gaps.to_vec();
+    gaps.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+    let mut segments = Vec::new();
+    let mut x = start;
+    for (gap_start, gap_end) in gaps {
+        if gap_start > x {
+            segments.push((x, gap_start.min(end)));
+        }
+        x = x.max(gap_end);
+        if x >= end {
+            break;
+        }
+    }
+    if x < end {
+        segments.push((x, end));
+    }
+    segments.retain(|(x0, x1)| x1 > x0);
+    segments
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::display_list::Command;
+    use crate::test_util::{layout_html, paint_commands};
+
+    #[test]
+    fn test_subtract_gaps() {
+        assert_eq!(subtract_gaps(0.0, 10.0, &[]), vec![(0.0, 10.0)]);
+        assert_eq!(
+            subtract_gaps(0.0, 10.0, &[(6.0, 7.0), (2.0, 3.0), (2.5, 4.0)]),
+            vec![(0.0, 2.0), (4.0, 6.0), (7.0, 10.0)]
+        );
+        assert_eq!(subtract_gaps(0.0, 10.0, &[(-1.0, 11.0)]), vec![]);
+    }
+
+    #[test]
+    fn test_blur_samples() {
+        // Sharp shadows are drawn once
+        assert_eq!(blur_samples(0.0), vec![(Vec2::ZERO, 1.0)]);
+
+        // Blurred shadows are sampled symmetrically out to two standard deviations, with the
+        // heaviest sample in the center
+        let samples = blur_samples(4.0);
+        assert_eq!(samples.len(), 25);
+        let total: f32 = samples.iter().map(|(_, weight)| weight).sum();
+        assert!((total - 1.0).abs() < 1e-5);
+        assert_eq!(samples[0].0, Vec2::new(-4.0, -4.0));
+        assert_eq!(samples[24].0, Vec2::new(4.0, 4.0));
+        assert_eq!(samples[0].1, samples[24].1);
+        let center = samples[12];
+        assert_eq!(center.0, Vec2::ZERO);
+        assert!(samples.iter().all(|(_, weight)| *weight <= center.1));
+    }
+
+    #[test]
+    fn test_text_shadow_blur() {
+        let glyph_runs = |style: &str| {
+            let doc = layout_html(&format!(
+                r#"<body style="margin: 0"><p style="{style}">x</p></body>"#
+            ));
+            let commands = paint_commands(&doc);
+            let glyphs = commands
+                .iter()
+                .filter(|command| matches!(command, Command::Glyphs(_)))
+                .count();
+            let additive_layers = commands
+                .iter()
+                .filter(|command| {
+                    matches!(command, Command::PushLayer { blend, .. } if blend.compose == Compose::Plus)
+                })
+                .count();
+            (glyphs, additive_layers)
+        };
+
+        // A sharp shadow is a single copy of the text
+        assert_eq!(glyph_runs("text-shadow: 2px 2px red"), (2, 0));
+
+        // A blurred shadow is summed from copies in additive layers
+        assert_eq!(glyph_runs("text-shadow: 2px 2px 4px red"), (26, 25));
+    }
 }
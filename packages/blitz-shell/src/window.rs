@@ -6,7 +6,7 @@ use crate::convert_events::{
 use crate::event::{BlitzShellEvent, create_waker};
 use anyrender::WindowRenderer;
 use blitz_dom::Document;
-use blitz_paint::paint_scene;
+use blitz_paint::DisplayList;
 use blitz_traits::events::{BlitzMouseButtonEvent, MouseEventButton, MouseEventButtons, UiEvent};
 use blitz_traits::shell::Viewport;
 use winit::keyboard::PhysicalKey;
@@ -26,6 +26,7 @@ pub struct WindowConfig<Rend: WindowRenderer> {
     doc: Box<dyn Document>,
     attributes: WindowAttributes,
     renderer: Rend,
+    preserves_frame: bool,
 }
 
 impl<Rend: WindowRenderer> WindowConfig<Rend> {
@@ -42,8 +43,16 @@ impl<Rend: WindowRenderer> WindowConfig<Rend> {
             doc,
             attributes,
             renderer,
+            preserves_frame: false,
         }
     }
+
+    /// Set whether the renderer draws each frame over the previous one (rather than clearing the
+    /// surface), in which case only the damaged rects of the document are painted each frame
+    pub fn with_preserved_frame(mut self, preserves_frame: bool) -> Self {
+        self.preserves_frame = preserves_frame;
+        self
+    }
 }
 
 pub struct View<Rend: WindowRenderer> {
@@ -51,6 +60,10 @@ pub struct View<Rend: WindowRenderer> {
 
     pub renderer: Rend,
     pub waker: Option<Waker>,
+    /// Retained drawing commands of the document, so that only damaged boxes are re-recorded
+    display_list: DisplayList,
+    /// Whether the renderer keeps the previous frame, so that only damaged rects need to be painted
+    preserves_frame: bool,
 
     pub event_loop_proxy: EventLoopProxy<BlitzShellEvent>,
     pub window: Arc<Window>,
@@ -104,6 +117,8 @@ impl<Rend: WindowRenderer> View<Rend> {
         Self {
             renderer: config.renderer,
             waker: None,
+            display_list: DisplayList::new(),
+            preserves_frame: config.preserves_frame,
             animation_timer: None,
             keyboard_modifiers: Default::default(),
             event_loop_proxy: proxy.clone(),
@@ -123,6 +138,7 @@ impl<Rend: WindowRenderer> View<Rend> {
         let shell_provider = self.doc.shell_provider.clone();
 
         self.doc = new_doc;
+        self.display_list.clear();
         self.doc.set_viewport(viewport);
         self.doc.set_shell_provider(shell_provider);
         self.poll();
@@ -177,8 +193,8 @@ impl<Rend: WindowRenderer> View<Rend> {
         };
 
         // Render
-        self.renderer
-            .render(|scene| paint_scene(scene, &self.doc, scale, width, height));
+        self.display_list.clear();
+        self.paint(scale, width, height);
 
         // Set waker
         self.waker = Some(create_waker(&self.event_loop_proxy, self.window_id()));
@@ -219,14 +235,29 @@ impl<Rend: WindowRenderer> View<Rend> {
         self.doc.resolve(animation_time);
         let (width, height) = self.doc.viewport().window_size;
         let scale = self.doc.viewport().scale_f64();
-        self.renderer
-            .render(|scene| paint_scene(scene, &self.doc, scale, width, height));
+        self.paint(scale, width, height);
 
         if self.doc.is_animating() {
             self.request_redraw();
         }
     }
 
+    /// Re-record the damaged parts of the display list and render it.
+    ///
+    /// Renderers which keep the previous frame only paint the rects which were damaged since the
+    /// previous frame. Other renderers clear the surface, so the whole scene is painted.
+    fn paint(&mut self, scale: f64, width: u32, height: u32) {
+        self.display_list.invalidate(self.doc.take_paint_damage());
+        self.display_list.update(&self.doc, scale, width, height);
+        if self.preserves_frame {
+            self.renderer
+                .render(|scene| self.display_list.paint_damaged(scene, &self.doc));
+        } else {
+            self.renderer
+                .render(|scene| self.display_list.paint(scene, &self.doc));
+        }
+    }
+
     pub fn window_id(&self) -> WindowId {
         self.window.id()
     }
@@ -104,6 +104,7 @@ pub enum DomEventKind {
     KeyDown,
     KeyUp,
     Input,
+    Change,
     Ime,
 }
 impl DomEventKind {
@@ -123,6 +124,7 @@ impl FromStr for DomEventKind {
             "keydown" => Ok(Self::KeyDown),
             "keyup" => Ok(Self::KeyUp),
             "input" => Ok(Self::Input),
+            "change" => Ok(Self::Change),
             "composition" => Ok(Self::Ime),
             _ => Err(()),
         }
@@ -140,6 +142,7 @@ pub enum DomEventData {
     KeyDown(BlitzKeyEvent),
     KeyUp(BlitzKeyEvent),
     Input(BlitzInputEvent),
+    Change(BlitzInputEvent),
     Ime(BlitzImeEvent),
 }
 impl DomEventData {
@@ -163,6 +166,7 @@ impl DomEventData {
             Self::KeyDown { .. } => "keydown",
             Self::KeyUp { .. } => "keyup",
             Self::Input { .. } => "input",
+            Self::Change { .. } => "change",
             Self::Ime { .. } => "composition",
         }
     }
@@ -177,6 +181,7 @@ impl DomEventData {
             Self::KeyDown { .. } => DomEventKind::KeyDown,
             Self::KeyUp { .. } => DomEventKind::KeyUp,
             Self::Input { .. } => DomEventKind::Input,
+            Self::Change { .. } => DomEventKind::Change,
             Self::Ime { .. } => DomEventKind::Ime,
         }
     }
@@ -192,6 +197,7 @@ impl DomEventData {
             Self::KeyPress { .. } => true,
             Self::Ime { .. } => true,
             Self::Input { .. } => false,
+            Self::Change { .. } => false,
         }
     }
 
@@ -206,6 +212,7 @@ impl DomEventData {
             Self::KeyPress { .. } => true,
             Self::Ime { .. } => true,
             Self::Input { .. } => true,
+            Self::Change { .. } => true,
         }
     }
 }
@@ -0,0 +1,166 @@
+//! Glyph outlines, font metadata (using skrifa) and font subsetting (using subsetter)
+
+use std::collections::BTreeMap;
+
+use anyrender::NormalizedCoord;
+use kurbo::BezPath;
+use peniko::FontData;
+use skrifa::MetadataProvider as _;
+use skrifa::instance::{LocationRef, Size};
+use skrifa::outline::{DrawSettings, OutlinePen};
+use skrifa::raw::types::F2Dot14;
+use skrifa::{FontRef, GlyphId, string::StringId};
+
+/// Converts skrifa outlines into kurbo paths
+struct BezPathPen(BezPath);
+
+impl OutlinePen for BezPathPen {
+    fn move_to(&mut self, x: f32, y: f32) {
+        self.0.move_to((x as f64, y as f64));
+    }
+
+    fn line_to(&mut self, x: f32, y: f32) {
+        self.0.line_to((x as f64, y as f64));
+    }
+
+    fn quad_to(&mut self, cx0: f32, cy0: f32, x: f32, y: f32) {
+        self.0
+            .quad_to((cx0 as f64, cy0 as f64), (x as f64, y as f64));
+    }
+
+    fn curve_to(&mut self, cx0: f32, cy0: f32, cx1: f32, cy1: f32, x: f32, y: f32) {
+        self.0.curve_to(
+            (cx0 as f64, cy0 as f64),
+            (cx1 as f64, cy1 as f64),
+            (x as f64, y as f64),
+        );
+    }
+
+    fn close(&mut self) {
+        self.0.close_path();
+    }
+}
+
+fn to_f2dot14(coords: &[NormalizedCoord]) -> Vec<F2Dot14> {
+    coords.iter().map(|c| F2Dot14::from_bits(*c)).collect()
+}
+
+/// The outline of a glyph at the specified font size (with y pointing up)
+pub(crate) fn glyph_outline(
+    font: &FontData,
+    normalized_coords: &[NormalizedCoord],
+    glyph_id: u32,
+    font_size: f32,
+) -> Option<BezPath> {
+    let font_ref = FontRef::from_index(font.data.as_ref(), font.index).ok()?;
+    let outline = font_ref.outline_glyphs().get(GlyphId::new(glyph_id))?;
+    let coords = to_f2dot14(normalized_coords);
+    let settings = DrawSettings::unhinted(Size::new(font_size), LocationRef::new(&coords));
+
+    let mut pen = BezPathPen(BezPath::new());
+    outline.draw(settings, &mut pen).ok()?;
+    Some(pen.0)
+}
+
+/// The format of the outlines in a font
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum OutlineFormat {
+    TrueType,
+    Cff,
+}
+
+/// Metadata required to embed a font in a PDF. All metrics are in units of 1/1000 em.
+#[derive(Debug, Clone)]
+pub(crate) struct EmbeddableFont {
+    pub format: OutlineFormat,
+    pub postscript_name: String,
+    pub ascent: f32,
+    pub descent: f32,
+    pub cap_height: f32,
+    pub italic_angle: f32,
+    pub bbox: [f32; 4],
+}
+
+impl EmbeddableFont {
+    /// Read the metadata of a font. Returns `None` if the font can't be embedded as-is (font
+    /// collections must be split and variable font instances must be flattened, so glyphs from
+    /// those fonts are drawn as outlines instead).
+    pub fn new(font: &FontData, normalized_coords: &[NormalizedCoord]) -> Option<Self> {
+        let data = font.data.as_ref();
+        if data.starts_with(b"ttcf") || normalized_coords.iter().any(|c| *c != 0) {
+            return None;
+        }
+        let format = match data.get(0..4)? {
+            b"OTTO" => OutlineFormat::Cff,
+            [0, 1, 0, 0] | b"true" => OutlineFormat::TrueType,
+            _ => return None,
+        };
+
+        let font_ref = FontRef::new(data).ok()?;
+        let metrics = font_ref.metrics(Size::new(1000.0), LocationRef::default());
+        let postscript_name = font_ref
+            .localized_strings(StringId::POSTSCRIPT_NAME)
+            .english_or_first()
+            .map(|name| name.chars().filter(char::is_ascii_alphanumeric).collect())
+            .filter(|name: &String| !name.is_empty())
+            .unwrap_or_else(|| String::from("BlitzFont"));
+        let bbox = metrics
+            .bounds
+            .map(|b| [b.x_min, b.y_min, b.x_max, b.y_max])
+            .unwrap_or([0.0, metrics.descent, 1000.0, metrics.ascent]);
+
+        Some(Self {
+            format,
+            postscript_name,
+            ascent: metrics.ascent,
+            descent: metrics.descent,
+            cap_height: metrics.cap_height.unwrap_or(metrics.ascent),
+            italic_angle: metrics.italic_angle,
+            bbox,
+        })
+    }
+}
+
+/// Subset a font to the outlines of the specified glyphs, for embedding in a PDF. Glyph ids are
+/// preserved (the outlines of other glyphs are removed rather than renumbering the glyphs).
+pub(crate) fn subset(font: &FontData, glyph_ids: impl Iterator<Item = u32>) -> Option<Vec<u8>> {
+    // The `.notdef` glyph is always retained
+    let glyph_ids: Vec<u16> = std::iter::once(0)
+        .chain(glyph_ids.filter_map(|id| u16::try_from(id).ok()))
+        .collect();
+    let profile = subsetter::Profile::pdf(&glyph_ids);
+    subsetter::subset(font.data.as_ref(), font.index, profile).ok()
+}
+
+/// The characters which map to each of the specified glyphs in the font's character map (the
+/// lowest code point is used if several characters map to a glyph). Glyphs which are not in the
+/// character map (such as ligatures and contextual alternates) are omitted.
+pub(crate) fn glyph_chars(
+    font: &FontData,
+    glyph_ids: impl Iterator<Item = u32>,
+) -> BTreeMap<u32, char> {
+    let mut chars: BTreeMap<u32, Option<char>> = glyph_ids.map(|id| (id, None)).collect();
+    if let Ok(font_ref) = FontRef::from_index(font.data.as_ref(), font.index) {
+        for (codepoint, glyph_id) in font_ref.charmap().mappings() {
+            if let Some(char @ None) = chars.get_mut(&glyph_id.to_u32()) {
+                *char = char::from_u32(codepoint);
+            }
+        }
+    }
+    chars
+        .into_iter()
+        .filter_map(|(id, char)| Some((id, char?)))
+        .collect()
+}
+
+/// The advance width of a glyph in units of 1/1000 em
+pub(crate) fn advance_width(font: &FontData, glyph_id: u32) -> f32 {
+    FontRef::from_index(font.data.as_ref(), font.index)
+        .ok()
+        .and_then(|font_ref| {
+            font_ref
+                .glyph_metrics(Size::new(1000.0), LocationRef::default())
+                .advance_width(GlyphId::new(glyph_id))
+        })
+        .unwrap_or(0.0)
+}
@@ -0,0 +1,50 @@
+//! Conversion of images to the formats embedded in SVG and PDF
+
+use base64::Engine as _;
+use peniko::{ImageAlphaType, ImageData, ImageFormat};
+
+/// The pixels of an image as (non-premultiplied) RGBA8
+pub(crate) fn to_rgba8(image: &ImageData) -> Option<Vec<u8>> {
+    let len = image.width as usize * image.height as usize * 4;
+    let mut data = image.data.as_ref().get(..len)?.to_vec();
+
+    match image.format {
+        ImageFormat::Rgba8 => {}
+        ImageFormat::Bgra8 => {
+            for pixel in data.chunks_exact_mut(4) {
+                pixel.swap(0, 2);
+            }
+        }
+        #[allow(unreachable_patterns)]
+        _ => return None,
+    }
+
+    if image.alpha_type == ImageAlphaType::AlphaPremultiplied {
+        for pixel in data.chunks_exact_mut(4) {
+            let alpha = pixel[3] as u16;
+            for component in &mut pixel[..3] {
+                if let Some(value) = (*component as u16 * 255 + alpha / 2).checked_div(alpha) {
+                    *component = value.min(255) as u8;
+                }
+            }
+        }
+    }
+
+    Some(data)
+}
+
+/// Encode an image as a PNG `data:` URI
+pub(crate) fn png_data_uri(image: &ImageData) -> Option<String> {
+    let data = to_rgba8(image)?;
+
+    let mut png = Vec::new();
+    let mut encoder = png::Encoder::new(&mut png, image.width, image.height);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header().ok()?;
+    writer.write_image_data(&data).ok()?;
+    writer.finish().ok()?;
+
+    let base64 = base64::engine::general_purpose::STANDARD.encode(&png);
+    Some(format!("data:image/png;base64,{base64}"))
+}
@@ -0,0 +1,191 @@
+//! Export a [`blitz_dom::BaseDocument`] (or anything else painted using [`anyrender`]) to vector
+//! formats (SVG and PDF).
+//!
+//! [`VectorScene`] is an implementation of [`anyrender::PaintScene`] which records the drawing
+//! commands pushed into it. A recorded scene can be serialised to SVG using [`VectorScene::to_svg`]
+//! or added as a page of a [`PdfDocument`].
+//!
+//! ```no_run
+//! # fn export(document: &blitz_dom::BaseDocument) {
+//! let svg = blitz_vector::document_to_svg(document);
+//! let pdf = blitz_vector::document_to_pdf(document, &blitz_vector::PdfOptions::A4);
+//! # }
+//! ```
+
+mod font;
+mod image;
+mod pdf;
+mod scene;
+mod svg;
+
+use anyrender::PaintScene as _;
+use blitz_dom::{BaseDocument, PageOptions};
+use blitz_paint::{paint_page, paint_scene_region};
+use kurbo::{Affine, Rect};
+use peniko::Mix;
+
+pub use pdf::PdfDocument;
+pub use scene::{Brush, Command, GlyphRun, GlyphStyle, VectorScene};
+
+/// The tolerance used when converting shapes to paths
+pub(crate) const PATH_TOLERANCE: f64 = 0.1;
+
+/// The number of points (the unit of PDF documents) per CSS pixel
+const POINTS_PER_PX: f64 = 0.75;
+
+/// Paint a region of a document into a [`VectorScene`]. The region is specified in CSS pixels
+/// relative to the top-left of the document, and is painted at `scale` scene units per CSS pixel.
+///
+/// The styles and layout of the document must already be resolved.
+pub fn paint_document(
+    dom: &BaseDocument,
+    x: f64,
+    y: f64,
+    width: f64,
+    height: f64,
+    scale: f64,
+) -> VectorScene {
+    let mut scene = VectorScene::new(width * scale, height * scale);
+    paint_scene_region(
+        &mut scene,
+        dom,
+        scale,
+        x,
+        y,
+        (width * scale).ceil() as u32,
+        (height * scale).ceil() as u32,
+    );
+    scene
+}
+
+/// Render the viewport of a document to SVG
+pub fn document_to_svg(dom: &BaseDocument) -> String {
+    let viewport = dom.viewport();
+    let scale = viewport.scale_f64();
+    let scroll = dom.viewport_scroll();
+    let width = viewport.window_size.0 as f64 / scale;
+    let height = viewport.window_size.1 as f64 / scale;
+    paint_document(dom, scroll.x, scroll.y, width, height, scale).to_svg()
+}
+
+/// The page size and margins of an exported PDF (in points)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PdfOptions {
+    pub page_width: f64,
+    pub page_height: f64,
+    pub margin: f64,
+}
+
+impl PdfOptions {
+    /// A4 pages with 1cm margins
+    pub const A4: Self = Self {
+        page_width: 595.28,
+        page_height: 841.89,
+        margin: 28.35,
+    };
+
+    /// US Letter pages with 0.4in margins
+    pub const LETTER: Self = Self {
+        page_width: 612.0,
+        page_height: 792.0,
+        margin: 28.8,
+    };
+
+    /// The width of the content area of a page in CSS pixels. Documents should be laid out with
+    /// a viewport of this width.
+    pub fn content_width(&self) -> f64 {
+        (self.page_width - self.margin * 2.0).max(0.0) / POINTS_PER_PX
+    }
+
+    /// The height of the content area of a page in CSS pixels
+    pub fn content_height(&self) -> f64 {
+        (self.page_height - self.margin * 2.0).max(0.0) / POINTS_PER_PX
+    }
+}
+
+impl Default for PdfOptions {
+    fn default() -> Self {
+        Self::A4
+    }
+}
+
+/// Render a document to a PDF. The full height of the document is split across as many pages as
+/// are required to fit it.
+///
+/// This slices the document into equal height pages. Use [`paginated_document_to_pdf`] to export
+/// a document paginated using `@page` rules and the `break-*` properties.
+pub fn document_to_pdf(dom: &BaseDocument, options: &PdfOptions) -> Vec<u8> {
+    let content_width = options.content_width();
+    let content_height = options.content_height();
+    let document_height = dom.root_element().final_layout.size.height as f64;
+    let page_count = if content_height > 0.0 {
+        (document_height / content_height).ceil().max(1.0) as usize
+    } else {
+        1
+    };
+
+    let margin = options.margin;
+    let content_area = Rect::new(
+        margin,
+        margin,
+        options.page_width - margin,
+        options.page_height - margin,
+    );
+
+    let mut pdf = PdfDocument::new();
+    for page in 0..page_count {
+        let content = paint_document(
+            dom,
+            0.0,
+            page as f64 * content_height,
+            content_width,
+            content_height,
+            POINTS_PER_PX,
+        );
+
+        let mut scene = VectorScene::new(options.page_width, options.page_height);
+        scene.push_layer(Mix::Normal, 1.0, Affine::IDENTITY, &content_area);
+        scene.append(&content, Affine::translate((margin, margin)));
+        scene.pop_layer();
+        pdf.add_page(&scene);
+    }
+    pdf.finish()
+}
+
+/// Paginate a document (see [`BaseDocument::paginate`]) and render its pages to a PDF
+pub fn paginated_document_to_pdf(dom: &mut BaseDocument, options: &PageOptions) -> Vec<u8> {
+    let mut pdf = PdfDocument::new();
+    for page in dom.paginate(options) {
+        let mut scene = VectorScene::new(
+            page.size.width as f64 * POINTS_PER_PX,
+            page.size.height as f64 * POINTS_PER_PX,
+        );
+        paint_page(&mut scene, dom, &page, POINTS_PER_PX);
+        pdf.add_page(&scene);
+    }
+    dom.end_pagination();
+    pdf.finish()
+}
+
+/// Format a number compactly (with at most 4 decimal places)
+pub(crate) fn num(value: f64) -> String {
+    let mut s = format!("{value:.4}");
+    if s.contains('.') {
+        let trimmed = s.trim_end_matches('0').trim_end_matches('.').len();
+        s.truncate(trimmed);
+    }
+    if s == "-0" { String::from("0") } else { s }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_num() {
+        assert_eq!(num(1.0), "1");
+        assert_eq!(num(0.5), "0.5");
+        assert_eq!(num(-0.00001), "0");
+        assert_eq!(num(12.34567), "12.3457");
+    }
+}
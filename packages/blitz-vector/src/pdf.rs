@@ -0,0 +1,1150 @@
+//! Serialisation of [`VectorScene`]s to a (paginated) PDF
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write as _;
+use std::io::Write as _;
+
+use flate2::Compression;
+use flate2::write::ZlibEncoder;
+use kurbo::{Affine, BezPath, PathEl, Point, Rect, Shape, StrokeOpts};
+use peniko::color::Srgb;
+use peniko::{BlendMode, Color, Compose, Extend, Fill, FontData, Gradient, GradientKind, Mix};
+
+use crate::font::{
+    EmbeddableFont, OutlineFormat, advance_width, glyph_chars, glyph_outline, subset,
+};
+use crate::image::to_rgba8;
+use crate::num;
+use crate::scene::{Brush, Command, GlyphRun, GlyphStyle, VectorScene};
+
+/// The number of steps used to approximate a blurred box shadow (PDF has no blur filter)
+const BOX_SHADOW_STEPS: usize = 8;
+/// The maximum number of repetitions of a repeating gradient
+const MAX_GRADIENT_REPEATS: f64 = 256.0;
+
+type ObjectId = usize;
+
+struct PdfFont {
+    id: ObjectId,
+    name: String,
+    font: FontData,
+    info: EmbeddableFont,
+    /// The advance widths of the glyphs which have been used
+    widths: BTreeMap<u32, f32>,
+}
+
+/// A PDF document which is built one page at a time from [`VectorScene`]s.
+///
+/// Each scene becomes a page the size of the scene (in points). Fonts are subset to the glyphs
+/// which are used and embedded (glyphs from font collections and variable font instances are drawn
+/// as outlines). Text can be extracted using the font's character map, so glyphs which aren't in
+/// it (such as ligatures) can't be extracted. Some features are
+/// approximated: box shadow blurs are drawn as a series of translucent steps, conic gradients
+/// are drawn with their average color, and repeating radial gradients with offset centers are
+/// not repeated.
+pub struct PdfDocument {
+    /// Serialised objects (object `n` is at index `n - 1`)
+    objects: Vec<Vec<u8>>,
+    pages: Vec<ObjectId>,
+    pages_id: ObjectId,
+    /// A resource dictionary which is shared by every page and form
+    resources_id: ObjectId,
+    fonts: HashMap<(u64, u32), Option<usize>>,
+    font_list: Vec<PdfFont>,
+    images: HashMap<u64, Option<String>>,
+    ext_gstates: HashMap<String, String>,
+    resources: BTreeMap<&'static str, BTreeMap<String, ObjectId>>,
+    next_name: usize,
+}
+
+impl Default for PdfDocument {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PdfDocument {
+    pub fn new() -> Self {
+        let mut doc = Self {
+            objects: Vec::new(),
+            pages: Vec::new(),
+            pages_id: 0,
+            resources_id: 0,
+            fonts: HashMap::new(),
+            font_list: Vec::new(),
+            images: HashMap::new(),
+            ext_gstates: HashMap::new(),
+            resources: BTreeMap::new(),
+            next_name: 0,
+        };
+        doc.pages_id = doc.reserve();
+        doc.resources_id = doc.reserve();
+        doc
+    }
+
+    /// The number of pages which have been added
+    pub fn page_count(&self) -> usize {
+        self.pages.len()
+    }
+
+    /// Add a page containing a scene. The page is the size of the scene (in points).
+    pub fn add_page(&mut self, scene: &VectorScene) {
+        let mut page = PageWriter::new(self, scene.width, scene.height);
+        for command in &scene.commands {
+            page.write_command(command);
+        }
+        let content = page.finish();
+
+        // Flip the y axis so that the content can use the same coordinates as the scene
+        let content = format!("1 0 0 -1 0 {} cm\n{content}", num(scene.height));
+        let content_id = self.add_stream("", content.as_bytes());
+        let page_id = self.add(format!(
+            "<< /Type /Page /Parent {} 0 R /MediaBox [0 0 {} {}] /Resources {} 0 R /Contents {content_id} 0 R >>",
+            self.pages_id,
+            num(scene.width),
+            num(scene.height),
+            self.resources_id,
+        ));
+        self.pages.push(page_id);
+    }
+
+    /// Serialise the document
+    pub fn finish(mut self) -> Vec<u8> {
+        self.write_fonts();
+
+        let mut resources = String::from("<< /ProcSet [/PDF /Text /ImageB /ImageC /ImageI]");
+        for (kind, entries) in &self.resources {
+            let _ = write!(resources, " /{kind} <<");
+            for (name, id) in entries {
+                let _ = write!(resources, " /{name} {id} 0 R");
+            }
+            resources.push_str(" >>");
+        }
+        resources.push_str(" >>");
+        self.set(self.resources_id, resources);
+
+        let kids: Vec<String> = self.pages.iter().map(|id| format!("{id} 0 R")).collect();
+        self.set(
+            self.pages_id,
+            format!(
+                "<< /Type /Pages /Kids [{}] /Count {} >>",
+                kids.join(" "),
+                self.pages.len()
+            ),
+        );
+        let catalog_id = self.add(format!("<< /Type /Catalog /Pages {} 0 R >>", self.pages_id));
+        let info_id = self.add(String::from("<< /Producer (Blitz) >>"));
+
+        let mut out = Vec::new();
+        out.extend_from_slice(b"%PDF-1.7\n%\xE2\xE3\xCF\xD3\n");
+        let mut offsets = Vec::with_capacity(self.objects.len());
+        for (idx, object) in self.objects.iter().enumerate() {
+            offsets.push(out.len());
+            let _ = writeln!(out, "{} 0 obj", idx + 1);
+            out.extend_from_slice(object);
+            out.extend_from_slice(b"\nendobj\n");
+        }
+
+        let xref_offset = out.len();
+        let _ = writeln!(
+            out,
+            "xref\n0 {}\n0000000000 65535 f ",
+            self.objects.len() + 1
+        );
+        for offset in offsets {
+            let _ = writeln!(out, "{offset:010} 00000 n ");
+        }
+        let _ = writeln!(
+            out,
+            "trailer\n<< /Size {} /Root {catalog_id} 0 R /Info {info_id} 0 R >>\nstartxref\n{xref_offset}\n%%EOF",
+            self.objects.len() + 1,
+        );
+        out
+    }
+
+    fn reserve(&mut self) -> ObjectId {
+        self.objects.push(Vec::new());
+        self.objects.len()
+    }
+
+    fn set(&mut self, id: ObjectId, object: impl Into<Vec<u8>>) {
+        self.objects[id - 1] = object.into();
+    }
+
+    fn add(&mut self, object: impl Into<Vec<u8>>) -> ObjectId {
+        self.objects.push(object.into());
+        self.objects.len()
+    }
+
+    /// Add a (compressed) stream object. `dict` contains any extra entries of the stream dictionary.
+    fn add_stream(&mut self, dict: &str, data: &[u8]) -> ObjectId {
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        let _ = encoder.write_all(data);
+        let data = encoder.finish().unwrap_or_default();
+
+        let mut object = format!(
+            "<< {dict} /Filter /FlateDecode /Length {} >>\nstream\n",
+            data.len()
+        )
+        .into_bytes();
+        object.extend_from_slice(&data);
+        object.extend_from_slice(b"\nendstream");
+        self.add(object)
+    }
+
+    /// Register a resource and return its name
+    fn add_resource(&mut self, kind: &'static str, prefix: &str, id: ObjectId) -> String {
+        self.next_name += 1;
+        let name = format!("{prefix}{}", self.next_name);
+        self.resources
+            .entry(kind)
+            .or_default()
+            .insert(name.clone(), id);
+        name
+    }
+
+    /// A graphics state with the specified fill/stroke alpha and blend mode
+    fn ext_gstate(&mut self, alpha: f32, blend: Option<&str>) -> String {
+        let mut dict = format!("<< /ca {a} /CA {a}", a = num(alpha.clamp(0.0, 1.0) as f64));
+        if let Some(blend) = blend {
+            let _ = write!(dict, " /BM /{blend}");
+        }
+        dict.push_str(" >>");
+
+        if let Some(name) = self.ext_gstates.get(&dict) {
+            return name.clone();
+        }
+        let id = self.add(dict.clone());
+        let name = self.add_resource("ExtGState", "GS", id);
+        self.ext_gstates.insert(dict, name.clone());
+        name
+    }
+
+    /// A graphics state which applies a luminosity soft mask (drawn by a form XObject)
+    fn soft_mask_gstate(&mut self, mask_form: ObjectId) -> String {
+        let id = self.add(format!(
+            "<< /SMask << /Type /Mask /S /Luminosity /G {mask_form} 0 R >> >>"
+        ));
+        self.add_resource("ExtGState", "GS", id)
+    }
+
+    /// Add a transparency group form XObject which covers a page
+    fn add_form(&mut self, width: f64, height: f64, content: &str, luminosity: bool) -> ObjectId {
+        let color_space = if luminosity { " /CS /DeviceRGB" } else { "" };
+        let dict = format!(
+            "/Type /XObject /Subtype /Form /BBox [0 0 {} {}] /Group << /S /Transparency /I true /K false{color_space} >> /Resources {} 0 R",
+            num(width),
+            num(height),
+            self.resources_id,
+        );
+        self.add_stream(&dict, content.as_bytes())
+    }
+
+    fn image(&mut self, image: &peniko::ImageData) -> Option<String> {
+        if let Some(name) = self.images.get(&image.data.id()) {
+            return name.clone();
+        }
+
+        let name = to_rgba8(image).map(|rgba| {
+            let mut rgb = Vec::with_capacity(rgba.len() / 4 * 3);
+            let mut alpha = Vec::with_capacity(rgba.len() / 4);
+            for pixel in rgba.chunks_exact(4) {
+                rgb.extend_from_slice(&pixel[..3]);
+                alpha.push(pixel[3]);
+            }
+
+            let size = format!("/Width {} /Height {}", image.width, image.height);
+            let smask = if alpha.iter().any(|a| *a != 255) {
+                let dict = format!(
+                    "/Type /XObject /Subtype /Image {size} /ColorSpace /DeviceGray /BitsPerComponent 8"
+                );
+                let id = self.add_stream(&dict, &alpha);
+                format!(" /SMask {id} 0 R")
+            } else {
+                String::new()
+            };
+            let dict = format!(
+                "/Type /XObject /Subtype /Image {size} /ColorSpace /DeviceRGB /BitsPerComponent 8{smask}"
+            );
+            let id = self.add_stream(&dict, &rgb);
+            self.add_resource("XObject", "Im", id)
+        });
+        self.images.insert(image.data.id(), name.clone());
+        name
+    }
+
+    /// The index of an embedded font (if the font can be embedded)
+    fn font(&mut self, font: &FontData, normalized_coords: &[i16]) -> Option<usize> {
+        if normalized_coords.iter().any(|c| *c != 0) {
+            return None;
+        }
+        let key = (font.data.id(), font.index);
+        if let Some(idx) = self.fonts.get(&key) {
+            return *idx;
+        }
+
+        let idx = EmbeddableFont::new(font, normalized_coords).map(|info| {
+            let id = self.reserve();
+            let name = self.add_resource("Font", "F", id);
+            self.font_list.push(PdfFont {
+                id,
+                name,
+                font: font.clone(),
+                info,
+                widths: BTreeMap::new(),
+            });
+            self.font_list.len() - 1
+        });
+        self.fonts.insert(key, idx);
+        idx
+    }
+
+    fn write_fonts(&mut self) {
+        for (index, font) in std::mem::take(&mut self.font_list).into_iter().enumerate() {
+            let info = &font.info;
+            let data = subset(&font.font, font.widths.keys().copied())
+                .unwrap_or_else(|| font.font.data.as_ref().to_vec());
+            let (font_file, subtype, file_key, cid_to_gid) = match info.format {
+                OutlineFormat::TrueType => {
+                    let dict = format!("/Length1 {}", data.len());
+                    (
+                        self.add_stream(&dict, &data),
+                        "CIDFontType2",
+                        "FontFile2",
+                        " /CIDToGIDMap /Identity",
+                    )
+                }
+                OutlineFormat::Cff => (
+                    self.add_stream("/Subtype /OpenType", &data),
+                    "CIDFontType0",
+                    "FontFile3",
+                    "",
+                ),
+            };
+            let base_font = format!("{}+{}", subset_tag(index), info.postscript_name);
+
+            let [x0, y0, x1, y1] = info.bbox.map(|v| num(v as f64));
+            let descriptor = self.add(format!(
+                "<< /Type /FontDescriptor /FontName /{base_font} /Flags 4 /FontBBox [{x0} {y0} {x1} {y1}] /ItalicAngle {} /Ascent {} /Descent {} /CapHeight {} /StemV 80 /{file_key} {font_file} 0 R >>",
+                num(info.italic_angle as f64),
+                num(info.ascent as f64),
+                num(info.descent as f64),
+                num(info.cap_height as f64),
+            ));
+
+            let mut widths = String::new();
+            for (glyph_id, width) in &font.widths {
+                let _ = write!(widths, "{glyph_id} [{}] ", num(*width as f64));
+            }
+            let cid_font = self.add(format!(
+                "<< /Type /Font /Subtype /{subtype} /BaseFont /{base_font} /CIDSystemInfo << /Registry (Adobe) /Ordering (Identity) /Supplement 0 >> /FontDescriptor {descriptor} 0 R /W [{widths}]{cid_to_gid} >>",
+            ));
+
+            let chars = glyph_chars(&font.font, font.widths.keys().copied());
+            let to_unicode = self.add_stream("", to_unicode_cmap(&chars).as_bytes());
+            self.set(
+                font.id,
+                format!(
+                    "<< /Type /Font /Subtype /Type0 /BaseFont /{base_font} /Encoding /Identity-H /DescendantFonts [{cid_font} 0 R] /ToUnicode {to_unicode} 0 R >>",
+                ),
+            );
+        }
+    }
+}
+
+/// The tag which prefixes the name of a subset font (six upper case letters which are unique
+/// within the document)
+fn subset_tag(index: usize) -> String {
+    let mut tag = [b'A'; 6];
+    let mut n = index;
+    for letter in tag.iter_mut().rev() {
+        *letter = b'A' + (n % 26) as u8;
+        n /= 26;
+    }
+    String::from_utf8_lossy(&tag).into_owned()
+}
+
+/// A CMap which maps the glyph ids used as character codes (with the `Identity-H` encoding) to
+/// Unicode, so that text can be extracted
+fn to_unicode_cmap(chars: &BTreeMap<u32, char>) -> String {
+    let mut cmap = String::from(
+        "/CIDInit /ProcSet findresource begin\n\
+         12 dict begin\n\
+         begincmap\n\
+         /CIDSystemInfo << /Registry (Adobe) /Ordering (UCS) /Supplement 0 >> def\n\
+         /CMapName /Adobe-Identity-UCS def\n\
+         /CMapType 2 def\n\
+         1 begincodespacerange\n<0000> <FFFF>\nendcodespacerange\n",
+    );
+    let chars: Vec<(u32, char)> = chars
+        .iter()
+        .filter(|(glyph_id, _)| **glyph_id <= u16::MAX as u32)
+        .map(|(glyph_id, char)| (*glyph_id, *char))
+        .collect();
+    // A `bfchar` block can contain at most 100 mappings
+    for block in chars.chunks(100) {
+        let _ = writeln!(cmap, "{} beginbfchar", block.len());
+        for (glyph_id, char) in block {
+            let mut utf16 = [0; 2];
+            let utf16: String = char
+                .encode_utf16(&mut utf16)
+                .iter()
+                .map(|unit| format!("{unit:04X}"))
+                .collect();
+            let _ = writeln!(cmap, "<{glyph_id:04X}> <{utf16}>");
+        }
+        cmap.push_str("endbfchar\n");
+    }
+    cmap.push_str(
+        "endcmap\n\
+         CMapName currentdict /CMap defineresource pop\n\
+         end\n\
+         end",
+    );
+    cmap
+}
+
+/// The kind of content group a layer is drawn into
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum GroupKind {
+    /// Root content of the page
+    Root,
+    /// A layer which only clips
+    Clip,
+    /// A layer which is composited as a transparency group (with opacity or a blend mode)
+    Transparency,
+    /// A layer which erases its content from its parent (`Compose::DestOut`)
+    Knockout,
+}
+
+struct PdfGroup {
+    kind: GroupKind,
+    content: String,
+    alpha: f32,
+    blend: Option<&'static str>,
+    /// The clip path of the layer (in page coordinates)
+    clip: Option<BezPath>,
+}
+
+struct PageWriter<'a> {
+    doc: &'a mut PdfDocument,
+    width: f64,
+    height: f64,
+    groups: Vec<PdfGroup>,
+}
+
+impl<'a> PageWriter<'a> {
+    fn new(doc: &'a mut PdfDocument, width: f64, height: f64) -> Self {
+        Self {
+            doc,
+            width,
+            height,
+            groups: vec![PdfGroup {
+                kind: GroupKind::Root,
+                content: String::new(),
+                alpha: 1.0,
+                blend: None,
+                clip: None,
+            }],
+        }
+    }
+
+    fn finish(mut self) -> String {
+        while self.groups.len() > 1 {
+            self.pop_layer();
+        }
+        self.groups.pop().unwrap().content
+    }
+
+    fn content(&mut self) -> &mut String {
+        &mut self.groups.last_mut().unwrap().content
+    }
+
+    fn in_knockout(&self) -> bool {
+        self.groups.iter().any(|g| g.kind == GroupKind::Knockout)
+    }
+
+    fn write_command(&mut self, command: &Command) {
+        match command {
+            Command::Fill {
+                fill,
+                transform,
+                brush,
+                brush_transform,
+                path,
+            } => self.fill(*fill, *transform, brush, *brush_transform, path),
+            Command::Stroke {
+                stroke,
+                transform,
+                brush,
+                brush_transform,
+                path,
+            } => {
+                if matches!(brush, Brush::Solid(_)) || self.in_knockout() {
+                    let color = self.knockout_color(brush.average_color());
+                    let mut ops = String::from("q ");
+                    self.push_alpha(&mut ops, color.components[3]);
+                    let [r, g, b] = rgb(color);
+                    let _ = write!(ops, "{r} {g} {b} RG {}", stroke_params(stroke));
+                    let _ = writeln!(ops, "{} cm {}S Q", matrix(*transform), path_ops(path));
+                    self.content().push_str(&ops);
+                } else {
+                    // Strokes with other brushes are converted to fills
+                    let outline = kurbo::stroke(
+                        path.iter(),
+                        stroke,
+                        &StrokeOpts::default(),
+                        crate::PATH_TOLERANCE,
+                    );
+                    self.fill(Fill::NonZero, *transform, brush, *brush_transform, &outline);
+                }
+            }
+            Command::PushLayer {
+                blend,
+                alpha,
+                transform,
+                clip,
+            } => self.push_layer(*blend, *alpha, *transform, clip),
+            Command::PopLayer => self.pop_layer(),
+            Command::Glyphs(run) => self.glyphs(run),
+            Command::BoxShadow {
+                transform,
+                rect,
+                color,
+                radius,
+                std_dev,
+            } => self.box_shadow(*transform, *rect, *color, *radius, *std_dev),
+        }
+    }
+
+    fn push_layer(&mut self, blend: BlendMode, alpha: f32, transform: Affine, clip: &BezPath) {
+        let blend_name = blend_mode(blend.mix);
+        let kind = if blend.compose == Compose::DestOut {
+            GroupKind::Knockout
+        } else if alpha < 1.0 || blend_name.is_some() {
+            GroupKind::Transparency
+        } else {
+            GroupKind::Clip
+        };
+        self.groups.push(PdfGroup {
+            kind,
+            content: String::new(),
+            alpha,
+            blend: blend_name,
+            clip: Some(transform * clip.clone()),
+        });
+    }
+
+    fn pop_layer(&mut self) {
+        if self.groups.len() <= 1 {
+            return;
+        }
+        let group = self.groups.pop().unwrap();
+        let clip = group
+            .clip
+            .as_ref()
+            .map(|clip| format!("{}W n\n", path_ops(clip)))
+            .unwrap_or_default();
+
+        match group.kind {
+            GroupKind::Root | GroupKind::Clip => {
+                let _ = writeln!(self.content(), "q {clip}{}Q", group.content);
+            }
+            GroupKind::Transparency => {
+                let form = self
+                    .doc
+                    .add_form(self.width, self.height, &group.content, false);
+                let form = self.doc.add_resource("XObject", "X", form);
+                let gs = self.doc.ext_gstate(group.alpha, group.blend);
+                let _ = writeln!(self.content(), "q {clip}/{gs} gs /{form} Do Q");
+            }
+            GroupKind::Knockout => {
+                // The content of the layer (drawn in black) over a white background is used as a
+                // luminosity mask for everything drawn before it in the parent
+                let layer = self
+                    .doc
+                    .add_form(self.width, self.height, &group.content, false);
+                let layer = self.doc.add_resource("XObject", "X", layer);
+                let gs = self.doc.ext_gstate(group.alpha, None);
+                let mask_content = format!(
+                    "1 1 1 rg 0 0 {} {} re f\nq {clip}/{gs} gs /{layer} Do Q\n",
+                    num(self.width),
+                    num(self.height),
+                );
+                let mask = self
+                    .doc
+                    .add_form(self.width, self.height, &mask_content, true);
+                let mask_gs = self.doc.soft_mask_gstate(mask);
+
+                let parent = std::mem::take(self.content());
+                let parent = self.doc.add_form(self.width, self.height, &parent, false);
+                let parent = self.doc.add_resource("XObject", "X", parent);
+                let _ = writeln!(self.content(), "q /{mask_gs} gs /{parent} Do Q");
+            }
+        }
+    }
+
+    fn knockout_color(&self, color: Color) -> Color {
+        if self.in_knockout() {
+            Color::new([0.0, 0.0, 0.0, color.components[3]])
+        } else {
+            color
+        }
+    }
+
+    /// Set the fill/stroke alpha (if it isn't opaque)
+    fn push_alpha(&mut self, ops: &mut String, alpha: f32) {
+        if alpha < 1.0 {
+            let gs = self.doc.ext_gstate(alpha, None);
+            let _ = write!(ops, "/{gs} gs ");
+        }
+    }
+
+    fn fill(
+        &mut self,
+        fill: Fill,
+        transform: Affine,
+        brush: &Brush,
+        brush_transform: Option<Affine>,
+        path: &BezPath,
+    ) {
+        let fill_op = match fill {
+            Fill::NonZero => "f",
+            Fill::EvenOdd => "f*",
+        };
+        let clip_op = match fill {
+            Fill::NonZero => "W n",
+            Fill::EvenOdd => "W* n",
+        };
+        let brush_transform = brush_transform.unwrap_or(Affine::IDENTITY);
+
+        let brush = match brush {
+            Brush::Gradient(gradient) if !self.in_knockout() => {
+                self.fill_gradient(clip_op, transform, gradient, brush_transform, path);
+                return;
+            }
+            Brush::Image(image) if !self.in_knockout() => {
+                let Some(name) = self.doc.image(&image.image) else {
+                    return;
+                };
+                let data = &image.image;
+                let mut ops = String::from("q ");
+                self.push_alpha(&mut ops, image.sampler.alpha);
+                let _ = writeln!(
+                    ops,
+                    "{} cm {}{clip_op}\n{} cm {w} 0 0 -{h} 0 {h} cm /{name} Do Q",
+                    matrix(transform),
+                    path_ops(path),
+                    matrix(brush_transform),
+                    w = data.width,
+                    h = data.height,
+                );
+                self.content().push_str(&ops);
+                return;
+            }
+            Brush::Image(image) => Brush::Solid(Color::new([0.0, 0.0, 0.0, image.sampler.alpha])),
+            brush => brush.clone(),
+        };
+
+        let color = self.knockout_color(brush.average_color());
+        let mut ops = String::from("q ");
+        self.push_alpha(&mut ops, color.components[3]);
+        let [r, g, b] = rgb(color);
+        let _ = writeln!(
+            ops,
+            "{r} {g} {b} rg {} cm {}{fill_op} Q",
+            matrix(transform),
+            path_ops(path)
+        );
+        self.content().push_str(&ops);
+    }
+
+    fn fill_gradient(
+        &mut self,
+        clip_op: &str,
+        transform: Affine,
+        gradient: &Gradient,
+        brush_transform: Affine,
+        path: &BezPath,
+    ) {
+        // The range of the gradient parameter covered by the shape (used for repeating gradients)
+        let bounds = (brush_transform.inverse() * path.clone()).bounding_box();
+        let Some(shading) = shading(gradient, bounds) else {
+            // Conic gradients can't be represented
+            let color = Brush::Gradient(gradient.clone()).average_color();
+            self.fill(Fill::NonZero, transform, &Brush::Solid(color), None, path);
+            return;
+        };
+        let shading_id = self.doc.add(shading);
+        let shading = self.doc.add_resource("Shading", "Sh", shading_id);
+
+        let geometry = format!(
+            "{} cm {}{clip_op}\n{} cm",
+            matrix(transform),
+            path_ops(path),
+            matrix(brush_transform),
+        );
+
+        // Alpha can't be part of a shading, so it is applied with a soft mask (or a constant alpha)
+        let alphas: Vec<f32> = gradient
+            .stops
+            .iter()
+            .map(|stop| stop.color.to_alpha_color::<Srgb>().components[3])
+            .collect();
+        let mut ops = String::from("q ");
+        if alphas.iter().any(|a| *a != alphas[0]) {
+            if let Some(mask_shading) = shading_for_alpha(gradient, bounds) {
+                let mask_shading = self.doc.add(mask_shading);
+                let mask_shading = self.doc.add_resource("Shading", "Sh", mask_shading);
+                let mask_content = format!("q {geometry} /{mask_shading} sh Q\n");
+                let mask = self
+                    .doc
+                    .add_form(self.width, self.height, &mask_content, true);
+                let gs = self.doc.soft_mask_gstate(mask);
+                let _ = write!(ops, "/{gs} gs ");
+            }
+        } else if let Some(alpha) = alphas.first() {
+            self.push_alpha(&mut ops, *alpha);
+        }
+        let _ = writeln!(ops, "{geometry} /{shading} sh Q");
+        self.content().push_str(&ops);
+    }
+
+    fn glyphs(&mut self, run: &GlyphRun) {
+        let color = self.knockout_color(run.brush.average_color());
+        let alpha = color.components[3] * run.brush_alpha;
+        let [r, g, b] = rgb(color);
+
+        let font_idx = self.doc.font(&run.font, &run.normalized_coords);
+        let Some(font_idx) = font_idx else {
+            // Draw the glyphs as outlines
+            let mut path = BezPath::new();
+            for glyph in &run.glyphs {
+                if let Some(outline) =
+                    glyph_outline(&run.font, &run.normalized_coords, glyph.id, run.font_size)
+                {
+                    path.extend((run.glyph_outline_transform(glyph) * outline).iter());
+                }
+            }
+            let mut ops = String::from("q ");
+            self.push_alpha(&mut ops, alpha);
+            let _ = write!(ops, "{} cm ", matrix(run.transform));
+            match &run.style {
+                GlyphStyle::Fill(fill) => {
+                    let fill_op = if *fill == Fill::EvenOdd { "f*" } else { "f" };
+                    let _ = writeln!(ops, "{r} {g} {b} rg {}{fill_op} Q", path_ops(&path));
+                }
+                GlyphStyle::Stroke(stroke) => {
+                    let _ = writeln!(
+                        ops,
+                        "{r} {g} {b} RG {}{}S Q",
+                        stroke_params(stroke),
+                        path_ops(&path)
+                    );
+                }
+            }
+            self.content().push_str(&ops);
+            return;
+        };
+
+        let font = &mut self.doc.font_list[font_idx];
+        let name = font.name.clone();
+        let mut text = String::new();
+        for glyph in &run.glyphs {
+            font.widths
+                .entry(glyph.id)
+                .or_insert_with(|| advance_width(&font.font, glyph.id));
+            let text_matrix = run.transform
+                * run.glyph_outline_transform(glyph)
+                * Affine::scale(run.font_size as f64);
+            let _ = writeln!(
+                text,
+                "{} Tm <{:04X}> Tj",
+                matrix(text_matrix),
+                glyph.id.min(u16::MAX as u32)
+            );
+        }
+
+        let mut ops = String::from("q ");
+        self.push_alpha(&mut ops, alpha);
+        match &run.style {
+            GlyphStyle::Fill(_) => {
+                let _ = writeln!(ops, "{r} {g} {b} rg BT /{name} 1 Tf 0 Tr");
+            }
+            GlyphStyle::Stroke(stroke) => {
+                // The stroke width is in text space (which is scaled by the font size)
+                let mut stroke = stroke.clone();
+                stroke.width /= run.font_size.max(f32::EPSILON) as f64;
+                let _ = writeln!(
+                    ops,
+                    "{r} {g} {b} RG {}BT /{name} 1 Tf 1 Tr",
+                    stroke_params(&stroke)
+                );
+            }
+        }
+        let _ = writeln!(ops, "{text}ET Q");
+        self.content().push_str(&ops);
+    }
+
+    fn box_shadow(
+        &mut self,
+        transform: Affine,
+        rect: Rect,
+        color: Color,
+        radius: f64,
+        std_dev: f64,
+    ) {
+        let color = self.knockout_color(color);
+        let [r, g, b] = rgb(color);
+        let alpha = color.components[3];
+
+        // A blur is approximated by a series of translucent rounded rects which step from 2
+        // standard deviations outside of the rect to 2 standard deviations inside of it
+        let steps = if std_dev > 0.0 { BOX_SHADOW_STEPS } else { 1 };
+        let step_alpha = 1.0 - (1.0 - alpha).powf(1.0 / steps as f32);
+        let mut ops = String::from("q ");
+        self.push_alpha(&mut ops, step_alpha);
+        let _ = writeln!(ops, "{r} {g} {b} rg {} cm", matrix(transform));
+        for step in 0..steps {
+            let offset = if steps == 1 {
+                0.0
+            } else {
+                std_dev * (2.0 - 4.0 * (step as f64 + 0.5) / steps as f64)
+            };
+            let rect = rect.inflate(offset, offset);
+            if rect.width() <= 0.0 || rect.height() <= 0.0 {
+                continue;
+            }
+            let shape = rect.to_rounded_rect((radius + offset).max(0.0));
+            let _ = writeln!(ops, "{}f", path_ops(&shape.to_path(crate::PATH_TOLERANCE)));
+        }
+        ops.push_str("Q\n");
+        self.content().push_str(&ops);
+    }
+}
+
+/// A shading dictionary for a gradient. Returns `None` for conic gradients.
+fn shading(gradient: &Gradient, bounds: Rect) -> Option<String> {
+    let stops: Vec<(f32, [f32; 3])> = gradient
+        .stops
+        .iter()
+        .map(|stop| {
+            let [r, g, b, _] = stop.color.to_alpha_color::<Srgb>().components;
+            (stop.offset, [r, g, b])
+        })
+        .collect();
+    shading_dict(gradient, bounds, "DeviceRGB", &stops)
+}
+
+/// A (grayscale) shading dictionary for the alpha of a gradient
+fn shading_for_alpha(gradient: &Gradient, bounds: Rect) -> Option<String> {
+    let stops: Vec<(f32, [f32; 1])> = gradient
+        .stops
+        .iter()
+        .map(|stop| {
+            let alpha = stop.color.to_alpha_color::<Srgb>().components[3];
+            (stop.offset, [alpha])
+        })
+        .collect();
+    shading_dict(gradient, bounds, "DeviceGray", &stops)
+}
+
+fn shading_dict<const N: usize>(
+    gradient: &Gradient,
+    bounds: Rect,
+    color_space: &str,
+    stops: &[(f32, [f32; N])],
+) -> Option<String> {
+    if stops.is_empty() {
+        return None;
+    }
+    let function = stops_function(stops);
+    let corners = [
+        Point::new(bounds.x0, bounds.y0),
+        Point::new(bounds.x1, bounds.y0),
+        Point::new(bounds.x1, bounds.y1),
+        Point::new(bounds.x0, bounds.y1),
+    ];
+
+    match &gradient.kind {
+        GradientKind::Linear(pos) => {
+            let axis = pos.end - pos.start;
+            let len_sq = axis.hypot2();
+            let (t0, t1) = if gradient.extend == Extend::Pad || len_sq == 0.0 {
+                (0.0, 1.0)
+            } else {
+                let ts = corners.map(|p| (p - pos.start).dot(axis) / len_sq);
+                let t0 = ts.iter().copied().fold(f64::INFINITY, f64::min).floor();
+                let t1 = ts.iter().copied().fold(f64::NEG_INFINITY, f64::max).ceil();
+                (t0.max(-MAX_GRADIENT_REPEATS), t1.min(MAX_GRADIENT_REPEATS))
+            };
+            let p0 = pos.start + axis * t0;
+            let p1 = pos.start + axis * t1;
+            Some(format!(
+                "<< /ShadingType 2 /ColorSpace /{color_space} /Coords [{} {} {} {}] /Domain [{} {}] /Function {} /Extend [true true] >>",
+                num(p0.x),
+                num(p0.y),
+                num(p1.x),
+                num(p1.y),
+                num(t0),
+                num(t1),
+                repeat_function(&function, t0, t1, gradient.extend),
+            ))
+        }
+        GradientKind::Radial(pos) => {
+            let r0 = pos.start_radius as f64;
+            let r1 = pos.end_radius as f64;
+            // Only concentric gradients are repeated (as the radius can't become negative)
+            let t1 = if gradient.extend != Extend::Pad
+                && pos.start_center == pos.end_center
+                && r1 > r0
+            {
+                let max_dist = corners
+                    .iter()
+                    .map(|p| (*p - pos.end_center).hypot())
+                    .fold(0.0, f64::max);
+                ((max_dist - r0) / (r1 - r0))
+                    .ceil()
+                    .clamp(1.0, MAX_GRADIENT_REPEATS)
+            } else {
+                1.0
+            };
+            let end_radius = r0 + (r1 - r0) * t1;
+            let end_center = pos.start_center + (pos.end_center - pos.start_center) * t1;
+            Some(format!(
+                "<< /ShadingType 3 /ColorSpace /{color_space} /Coords [{} {} {} {} {} {}] /Domain [0 {}] /Function {} /Extend [true true] >>",
+                num(pos.start_center.x),
+                num(pos.start_center.y),
+                num(r0),
+                num(end_center.x),
+                num(end_center.y),
+                num(end_radius),
+                num(t1),
+                repeat_function(&function, 0.0, t1, gradient.extend),
+            ))
+        }
+        GradientKind::Sweep(_) => None,
+    }
+}
+
+/// A function which maps `[0, 1]` to the colors of the stops
+fn stops_function<const N: usize>(stops: &[(f32, [f32; N])]) -> String {
+    let color = |c: &[f32; N]| {
+        c.iter()
+            .map(|v| num(*v as f64))
+            .collect::<Vec<_>>()
+            .join(" ")
+    };
+    let exponential = |c0: &[f32; N], c1: &[f32; N]| {
+        format!(
+            "<< /FunctionType 2 /Domain [0 1] /C0 [{}] /C1 [{}] /N 1 >>",
+            color(c0),
+            color(c1)
+        )
+    };
+
+    // Extend the stops to cover the whole range
+    let mut points: Vec<(f32, [f32; N])> = Vec::with_capacity(stops.len() + 2);
+    let first = stops[0];
+    let last = stops[stops.len() - 1];
+    if first.0 > 0.0 {
+        points.push((0.0, first.1));
+    }
+    points.extend(stops.iter().map(|(offset, c)| (offset.clamp(0.0, 1.0), *c)));
+    if last.0 < 1.0 {
+        points.push((1.0, last.1));
+    }
+    if points.len() == 1 {
+        return exponential(&points[0].1, &points[0].1);
+    }
+    if points.len() == 2 {
+        return exponential(&points[0].1, &points[1].1);
+    }
+
+    let functions: Vec<String> = points
+        .windows(2)
+        .map(|pair| exponential(&pair[0].1, &pair[1].1))
+        .collect();
+    let bounds: Vec<String> = points[1..points.len() - 1]
+        .iter()
+        .map(|(offset, _)| num(*offset as f64))
+        .collect();
+    let encode = vec!["0 1"; functions.len()].join(" ");
+    format!(
+        "<< /FunctionType 3 /Domain [0 1] /Functions [{}] /Bounds [{}] /Encode [{encode}] >>",
+        functions.join(" "),
+        bounds.join(" "),
+    )
+}
+
+/// Repeat a function over the domain `[t0, t1]` (which is a whole number of repetitions)
+fn repeat_function(function: &str, t0: f64, t1: f64, extend: Extend) -> String {
+    let count = (t1 - t0).round() as i64;
+    if extend == Extend::Pad || count <= 1 && t0 == 0.0 {
+        return function.to_string();
+    }
+
+    let functions = vec![function; count as usize].join(" ");
+    let bounds: Vec<String> = (1..count).map(|i| num(t0 + i as f64)).collect();
+    let encode: Vec<&str> = (0..count)
+        .map(|i| {
+            let reversed = extend == Extend::Reflect && (t0 as i64 + i).rem_euclid(2) == 1;
+            if reversed { "1 0" } else { "0 1" }
+        })
+        .collect();
+    format!(
+        "<< /FunctionType 3 /Domain [{} {}] /Functions [{functions}] /Bounds [{}] /Encode [{}] >>",
+        num(t0),
+        num(t1),
+        bounds.join(" "),
+        encode.join(" "),
+    )
+}
+
+fn stroke_params(stroke: &kurbo::Stroke) -> String {
+    let cap = match stroke.start_cap {
+        kurbo::Cap::Butt => 0,
+        kurbo::Cap::Round => 1,
+        kurbo::Cap::Square => 2,
+    };
+    let join = match stroke.join {
+        kurbo::Join::Miter => 0,
+        kurbo::Join::Round => 1,
+        kurbo::Join::Bevel => 2,
+    };
+    let dashes: Vec<String> = stroke.dash_pattern.iter().map(|d| num(*d)).collect();
+    format!(
+        "{} w {cap} J {join} j {} M [{}] {} d ",
+        num(stroke.width),
+        num(stroke.miter_limit),
+        dashes.join(" "),
+        num(stroke.dash_offset),
+    )
+}
+
+#[allow(deprecated)]
+fn blend_mode(mix: Mix) -> Option<&'static str> {
+    Some(match mix {
+        Mix::Multiply => "Multiply",
+        Mix::Screen => "Screen",
+        Mix::Overlay => "Overlay",
+        Mix::Darken => "Darken",
+        Mix::Lighten => "Lighten",
+        Mix::ColorDodge => "ColorDodge",
+        Mix::ColorBurn => "ColorBurn",
+        Mix::HardLight => "HardLight",
+        Mix::SoftLight => "SoftLight",
+        Mix::Difference => "Difference",
+        Mix::Exclusion => "Exclusion",
+        Mix::Hue => "Hue",
+        Mix::Saturation => "Saturation",
+        Mix::Color => "Color",
+        Mix::Luminosity => "Luminosity",
+        _ => return None,
+    })
+}
+
+fn rgb(color: Color) -> [String; 3] {
+    let [r, g, b, _] = color.components;
+    [r, g, b].map(|c| num(c.clamp(0.0, 1.0) as f64))
+}
+
+fn matrix(transform: Affine) -> String {
+    let [a, b, c, d, e, f] = transform.as_coeffs().map(num);
+    format!("{a} {b} {c} {d} {e} {f}")
+}
+
+/// The path construction operators for a path
+fn path_ops(path: &BezPath) -> String {
+    let mut ops = String::new();
+    let mut current = Point::ZERO;
+    for el in path.elements() {
+        match *el {
+            PathEl::MoveTo(p) => {
+                let _ = write!(ops, "{} {} m ", num(p.x), num(p.y));
+                current = p;
+            }
+            PathEl::LineTo(p) => {
+                let _ = write!(ops, "{} {} l ", num(p.x), num(p.y));
+                current = p;
+            }
+            PathEl::QuadTo(p1, p2) => {
+                // Elevate the quadratic curve to a cubic
+                let c1 = current + (p1 - current) * (2.0 / 3.0);
+                let c2 = p2 + (p1 - p2) * (2.0 / 3.0);
+                let _ = write!(
+                    ops,
+                    "{} {} {} {} {} {} c ",
+                    num(c1.x),
+                    num(c1.y),
+                    num(c2.x),
+                    num(c2.y),
+                    num(p2.x),
+                    num(p2.y)
+                );
+                current = p2;
+            }
+            PathEl::CurveTo(p1, p2, p3) => {
+                let _ = write!(
+                    ops,
+                    "{} {} {} {} {} {} c ",
+                    num(p1.x),
+                    num(p1.y),
+                    num(p2.x),
+                    num(p2.y),
+                    num(p3.x),
+                    num(p3.y)
+                );
+                current = p3;
+            }
+            PathEl::ClosePath => ops.push_str("h "),
+        }
+    }
+    ops
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stops_function() {
+        let stops = [(0.0, [0.0]), (1.0, [1.0])];
+        assert_eq!(
+            stops_function(&stops),
+            "<< /FunctionType 2 /Domain [0 1] /C0 [0] /C1 [1] /N 1 >>"
+        );
+
+        let stops = [(0.25, [0.0]), (0.5, [1.0])];
+        assert_eq!(
+            stops_function(&stops),
+            "<< /FunctionType 3 /Domain [0 1] /Functions [\
+         << /FunctionType 2 /Domain [0 1] /C0 [0] /C1 [0] /N 1 >> \
+         << /FunctionType 2 /Domain [0 1] /C0 [0] /C1 [1] /N 1 >> \
+         << /FunctionType 2 /Domain [0 1] /C0 [1] /C1 [1] /N 1 >>] \
+         /Bounds [0.25 0.5] /Encode [0 1 0 1 0 1] >>"
+        );
+    }
+
+    #[test]
+    fn test_scene_to_pdf() {
+        let scene = crate::scene::test_scene();
+        let mut doc = PdfDocument::new();
+        let mut page = PageWriter::new(&mut doc, scene.width, scene.height);
+        for command in &scene.commands {
+            page.write_command(command);
+        }
+        let content = page.finish();
+        assert_eq!(content, include_str!("../snapshots/scene.pdf.txt"));
+
+        // The font is subset and can be mapped back to text
+        let font = &doc.font_list[0];
+        let subset_font = subset(&font.font, font.widths.keys().copied()).unwrap();
+        assert!(subset_font.len() < font.font.data.len());
+        let cmap = to_unicode_cmap(&glyph_chars(&font.font, font.widths.keys().copied()));
+        assert!(cmap.contains("2 beginbfchar\n<0004> <2022>\n<0009> <25E6>\nendbfchar\n"));
+        let pdf = String::from_utf8_lossy(&doc.finish()).into_owned();
+        assert!(pdf.contains("/BaseFont /AAAAAA+"));
+        assert!(pdf.contains("/ToUnicode"));
+    }
+}
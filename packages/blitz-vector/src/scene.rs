@@ -0,0 +1,371 @@
+//! A [`PaintScene`] which records drawing commands so that they can be serialised
+
+use anyrender::{Glyph, NormalizedCoord, Paint, PaintRef, PaintScene};
+use kurbo::{Affine, BezPath, Rect, Shape, Stroke};
+use peniko::color::Srgb;
+use peniko::{BlendMode, Color, Fill, FontData, Gradient, ImageBrush, ImageBrushRef, StyleRef};
+
+use crate::PATH_TOLERANCE;
+use crate::svg::SvgWriter;
+
+/// A paint which can be serialised to a vector format
+#[derive(Clone, Debug)]
+pub enum Brush {
+    Solid(Color),
+    Gradient(Gradient),
+    Image(ImageBrush),
+}
+
+impl Brush {
+    /// Convert a paint to a brush. Returns `None` for custom paints (which can't be serialised).
+    fn from_paint(paint: PaintRef<'_>) -> Option<Self> {
+        match paint {
+            Paint::Solid(color) => Some(Brush::Solid(color)),
+            Paint::Gradient(gradient) => Some(Brush::Gradient(gradient.clone())),
+            Paint::Image(image) => Some(Brush::Image(to_owned_image(image))),
+            Paint::Custom(_) => None,
+        }
+    }
+
+    /// A single color which approximates the brush (for formats which can't represent it)
+    pub(crate) fn average_color(&self) -> Color {
+        match self {
+            Brush::Solid(color) => *color,
+            Brush::Gradient(gradient) => {
+                let stops = &gradient.stops;
+                if stops.is_empty() {
+                    return Color::TRANSPARENT;
+                }
+                let mut sum = [0.0; 4];
+                for stop in stops.iter() {
+                    let color = stop.color.to_alpha_color::<Srgb>();
+                    for (sum, component) in sum.iter_mut().zip(color.components) {
+                        *sum += component;
+                    }
+                }
+                Color::new(sum.map(|c| c / stops.len() as f32))
+            }
+            Brush::Image(_) => Color::TRANSPARENT,
+        }
+    }
+}
+
+fn to_owned_image(image: ImageBrushRef<'_>) -> ImageBrush {
+    ImageBrush {
+        image: image.image.clone(),
+        sampler: image.sampler,
+    }
+}
+
+/// Whether glyphs are filled or stroked
+#[derive(Clone, Debug)]
+pub enum GlyphStyle {
+    Fill(Fill),
+    Stroke(Stroke),
+}
+
+/// A run of glyphs which share a font and brush
+#[derive(Clone, Debug)]
+pub struct GlyphRun {
+    pub font: FontData,
+    pub font_size: f32,
+    pub normalized_coords: Vec<NormalizedCoord>,
+    pub style: GlyphStyle,
+    pub brush: Brush,
+    pub brush_alpha: f32,
+    pub transform: Affine,
+    pub glyph_transform: Option<Affine>,
+    pub glyphs: Vec<Glyph>,
+}
+
+impl GlyphRun {
+    /// The transform from the outline of a glyph (in font units scaled to the font size, with y
+    /// pointing up) to the coordinate space of the run
+    pub(crate) fn glyph_outline_transform(&self, glyph: &Glyph) -> Affine {
+        Affine::translate((glyph.x as f64, glyph.y as f64))
+            * self.glyph_transform.unwrap_or(Affine::IDENTITY)
+            * Affine::FLIP_Y
+    }
+}
+
+/// A recorded drawing command
+#[derive(Clone, Debug)]
+pub enum Command {
+    Fill {
+        fill: Fill,
+        transform: Affine,
+        brush: Brush,
+        brush_transform: Option<Affine>,
+        path: BezPath,
+    },
+    Stroke {
+        stroke: Stroke,
+        transform: Affine,
+        brush: Brush,
+        brush_transform: Option<Affine>,
+        path: BezPath,
+    },
+    PushLayer {
+        blend: BlendMode,
+        alpha: f32,
+        transform: Affine,
+        clip: BezPath,
+    },
+    PopLayer,
+    Glyphs(Box<GlyphRun>),
+    BoxShadow {
+        transform: Affine,
+        rect: Rect,
+        color: Color,
+        radius: f64,
+        std_dev: f64,
+    },
+}
+
+/// A [`PaintScene`] which records the commands pushed into it so that they can be serialised to
+/// a vector format (see [`VectorScene::to_svg`] and [`PdfDocument`](crate::PdfDocument)).
+///
+/// Custom paints (such as `<canvas>` content) can't be serialised and are skipped.
+#[derive(Clone, Debug)]
+pub struct VectorScene {
+    pub(crate) width: f64,
+    pub(crate) height: f64,
+    pub(crate) commands: Vec<Command>,
+}
+
+impl VectorScene {
+    /// Create an empty scene. The width and height are the size of the resulting image (in
+    /// device pixels for SVG and in points for PDF).
+    pub fn new(width: f64, height: f64) -> Self {
+        Self {
+            width,
+            height,
+            commands: Vec::new(),
+        }
+    }
+
+    pub fn width(&self) -> f64 {
+        self.width
+    }
+
+    pub fn height(&self) -> f64 {
+        self.height
+    }
+
+    /// The commands which have been recorded
+    pub fn commands(&self) -> &[Command] {
+        &self.commands
+    }
+
+    /// Append the commands of another scene (drawn with an additional transform)
+    pub fn append(&mut self, other: &VectorScene, transform: Affine) {
+        self.commands.extend(other.commands.iter().map(|command| {
+            let mut command = command.clone();
+            match &mut command {
+                Command::Fill { transform: t, .. }
+                | Command::Stroke { transform: t, .. }
+                | Command::PushLayer { transform: t, .. }
+                | Command::BoxShadow { transform: t, .. } => *t = transform * *t,
+                Command::Glyphs(run) => run.transform = transform * run.transform,
+                Command::PopLayer => {}
+            }
+            command
+        }));
+    }
+
+    /// Serialise the scene to an SVG document
+    pub fn to_svg(&self) -> String {
+        SvgWriter::new(self.width, self.height).write_scene(self)
+    }
+}
+
+impl PaintScene for VectorScene {
+    fn reset(&mut self) {
+        self.commands.clear();
+    }
+
+    fn push_layer(
+        &mut self,
+        blend: impl Into<BlendMode>,
+        alpha: f32,
+        transform: Affine,
+        clip: &impl Shape,
+    ) {
+        self.commands.push(Command::PushLayer {
+            blend: blend.into(),
+            alpha,
+            transform,
+            clip: clip.to_path(PATH_TOLERANCE),
+        });
+    }
+
+    fn pop_layer(&mut self) {
+        self.commands.push(Command::PopLayer);
+    }
+
+    fn stroke<'a>(
+        &mut self,
+        style: &Stroke,
+        transform: Affine,
+        brush: impl Into<PaintRef<'a>>,
+        brush_transform: Option<Affine>,
+        shape: &impl Shape,
+    ) {
+        let Some(brush) = Brush::from_paint(brush.into()) else {
+            return;
+        };
+        self.commands.push(Command::Stroke {
+            stroke: style.clone(),
+            transform,
+            brush,
+            brush_transform,
+            path: shape.to_path(PATH_TOLERANCE),
+        });
+    }
+
+    fn fill<'a>(
+        &mut self,
+        style: Fill,
+        transform: Affine,
+        brush: impl Into<PaintRef<'a>>,
+        brush_transform: Option<Affine>,
+        shape: &impl Shape,
+    ) {
+        let Some(brush) = Brush::from_paint(brush.into()) else {
+            return;
+        };
+        self.commands.push(Command::Fill {
+            fill: style,
+            transform,
+            brush,
+            brush_transform,
+            path: shape.to_path(PATH_TOLERANCE),
+        });
+    }
+
+    fn draw_glyphs<'a, 's: 'a>(
+        &'s mut self,
+        font: &'a FontData,
+        font_size: f32,
+        _hint: bool,
+        normalized_coords: &'a [NormalizedCoord],
+        style: impl Into<StyleRef<'a>>,
+        brush: impl Into<PaintRef<'a>>,
+        brush_alpha: f32,
+        transform: Affine,
+        glyph_transform: Option<Affine>,
+        glyphs: impl Iterator<Item = Glyph>,
+    ) {
+        let Some(brush) = Brush::from_paint(brush.into()) else {
+            return;
+        };
+        let style = match style.into() {
+            StyleRef::Fill(fill) => GlyphStyle::Fill(fill),
+            StyleRef::Stroke(stroke) => GlyphStyle::Stroke(stroke.clone()),
+        };
+        self.commands.push(Command::Glyphs(Box::new(GlyphRun {
+            font: font.clone(),
+            font_size,
+            normalized_coords: normalized_coords.to_vec(),
+            style,
+            brush,
+            brush_alpha,
+            transform,
+            glyph_transform,
+            glyphs: glyphs.collect(),
+        })));
+    }
+
+    fn draw_box_shadow(
+        &mut self,
+        transform: Affine,
+        rect: Rect,
+        brush: Color,
+        radius: f64,
+        std_dev: f64,
+    ) {
+        self.commands.push(Command::BoxShadow {
+            transform,
+            rect,
+            color: brush,
+            radius,
+            std_dev,
+        });
+    }
+
+    fn draw_image(&mut self, image: ImageBrushRef, transform: Affine) {
+        let rect = Rect::new(
+            0.0,
+            0.0,
+            image.image.width as f64,
+            image.image.height as f64,
+        );
+        self.commands.push(Command::Fill {
+            fill: Fill::NonZero,
+            transform,
+            brush: Brush::Image(to_owned_image(image)),
+            brush_transform: None,
+            path: rect.to_path(PATH_TOLERANCE),
+        });
+    }
+}
+
+/// A scene containing a solid fill, a gradient within a translucent clipped layer, an image and a
+/// run of glyphs (two bullets from the font used for list markers)
+#[cfg(test)]
+pub(crate) fn test_scene() -> VectorScene {
+    use kurbo::Circle;
+    use peniko::{Blob, ImageAlphaType, ImageData, ImageFormat, Mix};
+
+    let mut scene = VectorScene::new(100.0, 50.0);
+    let red = Color::from_rgb8(255, 0, 0);
+    scene.fill(
+        Fill::NonZero,
+        Affine::IDENTITY,
+        red,
+        None,
+        &Rect::new(0.0, 0.0, 50.0, 50.0),
+    );
+
+    let gradient =
+        Gradient::new_linear((50.0, 0.0), (100.0, 0.0)).with_stops([Color::BLACK, Color::WHITE]);
+    let clip = Circle::new((75.0, 25.0), 25.0);
+    scene.push_layer(Mix::Multiply, 0.5, Affine::IDENTITY, &clip);
+    scene.fill(
+        Fill::NonZero,
+        Affine::IDENTITY,
+        &gradient,
+        None,
+        &Rect::new(50.0, 0.0, 100.0, 50.0),
+    );
+    scene.pop_layer();
+
+    let image = ImageData {
+        data: Blob::from(vec![0, 0, 255, 255, 0, 255, 0, 128]),
+        format: ImageFormat::Rgba8,
+        alpha_type: ImageAlphaType::Alpha,
+        width: 2,
+        height: 1,
+    };
+    let image = ImageBrush::new(image);
+    scene.draw_image(image.as_ref(), Affine::scale(10.0));
+
+    let font = FontData::new(
+        Blob::from(include_bytes!("../../blitz-dom/assets/moz-bullet-font.otf").to_vec()),
+        0,
+    );
+    let glyphs = [(4, 10.0), (9, 30.0)].map(|(id, x)| Glyph { id, x, y: 40.0 });
+    scene.draw_glyphs(
+        &font,
+        20.0,
+        false,
+        &[],
+        Fill::NonZero,
+        Color::BLACK,
+        1.0,
+        Affine::IDENTITY,
+        None,
+        glyphs.into_iter(),
+    );
+    scene
+}
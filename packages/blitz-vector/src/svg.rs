@@ -0,0 +1,568 @@
+//! Serialisation of a [`VectorScene`] to SVG
+
+use std::collections::HashMap;
+use std::fmt::Write as _;
+
+use kurbo::{Affine, BezPath, Rect, Shape as _};
+use peniko::color::Srgb;
+use peniko::{BlendMode, Color, Compose, Extend, Fill, Gradient, GradientKind, ImageBrush, Mix};
+
+use crate::font::glyph_outline;
+use crate::image::png_data_uri;
+use crate::num;
+use crate::scene::{Brush, Command, GlyphRun, GlyphStyle, VectorScene};
+
+/// Glyphs are defined at this size and scaled to the size of each run
+const GLYPH_DEF_SIZE: f32 = 1000.0;
+
+/// A group of content (the root of the document or a layer)
+struct Group {
+    content: String,
+    blend: BlendMode,
+    alpha: f32,
+    clip_id: Option<String>,
+}
+
+impl Group {
+    fn is_knockout(&self) -> bool {
+        self.blend.compose == Compose::DestOut
+    }
+}
+
+#[derive(Hash, PartialEq, Eq)]
+struct GlyphKey {
+    font_id: u64,
+    font_index: u32,
+    normalized_coords: Vec<i16>,
+    glyph_id: u32,
+}
+
+pub(crate) struct SvgWriter {
+    width: f64,
+    height: f64,
+    defs: String,
+    groups: Vec<Group>,
+    next_id: usize,
+    glyphs: HashMap<GlyphKey, Option<String>>,
+    images: HashMap<u64, Option<String>>,
+}
+
+impl SvgWriter {
+    pub(crate) fn new(width: f64, height: f64) -> Self {
+        Self {
+            width,
+            height,
+            defs: String::new(),
+            groups: vec![Group {
+                content: String::new(),
+                blend: BlendMode::default(),
+                alpha: 1.0,
+                clip_id: None,
+            }],
+            next_id: 0,
+            glyphs: HashMap::new(),
+            images: HashMap::new(),
+        }
+    }
+
+    pub(crate) fn write_scene(mut self, scene: &VectorScene) -> String {
+        for command in &scene.commands {
+            self.write_command(command);
+        }
+        // Close any layers which were not popped
+        while self.groups.len() > 1 {
+            self.pop_layer();
+        }
+
+        let mut svg = String::new();
+        let _ = write!(
+            svg,
+            r#"<svg xmlns="http://www.w3.org/2000/svg" xmlns:xlink="http://www.w3.org/1999/xlink" width="{w}" height="{h}" viewBox="0 0 {w} {h}">"#,
+            w = num(self.width),
+            h = num(self.height),
+        );
+        if !self.defs.is_empty() {
+            let _ = write!(svg, "<defs>{}</defs>", self.defs);
+        }
+        svg.push_str(&self.groups[0].content);
+        svg.push_str("</svg>");
+        svg
+    }
+
+    fn next_id(&mut self, prefix: &str) -> String {
+        self.next_id += 1;
+        format!("{prefix}{}", self.next_id)
+    }
+
+    fn content(&mut self) -> &mut String {
+        &mut self.groups.last_mut().unwrap().content
+    }
+
+    /// Content drawn within a knockout (`Compose::DestOut`) layer is only used for its alpha, so
+    /// it is drawn in black (which makes it usable as a luminance mask)
+    fn in_knockout(&self) -> bool {
+        self.groups.iter().any(Group::is_knockout)
+    }
+
+    fn write_command(&mut self, command: &Command) {
+        match command {
+            Command::Fill {
+                fill,
+                transform,
+                brush,
+                brush_transform,
+                path,
+            } => self.fill(*fill, *transform, brush, *brush_transform, path),
+            Command::Stroke {
+                stroke,
+                transform,
+                brush,
+                brush_transform,
+                path,
+            } => {
+                let brush = self.knockout_brush(brush);
+                let paint = self.paint("stroke", &brush, *brush_transform, 1.0);
+                let attrs = stroke_attrs(stroke);
+                let _ = write!(
+                    self.content(),
+                    r#"<path d="{}" transform="{}" fill="none"{paint}{attrs}/>"#,
+                    path.to_svg(),
+                    matrix(*transform),
+                );
+            }
+            Command::PushLayer {
+                blend,
+                alpha,
+                transform,
+                clip,
+            } => {
+                let clip_id = self.clip_path(*transform, clip);
+                self.groups.push(Group {
+                    content: String::new(),
+                    blend: *blend,
+                    alpha: *alpha,
+                    clip_id: Some(clip_id),
+                });
+            }
+            Command::PopLayer => self.pop_layer(),
+            Command::Glyphs(run) => self.glyphs(run),
+            Command::BoxShadow {
+                transform,
+                rect,
+                color,
+                radius,
+                std_dev,
+            } => self.box_shadow(*transform, *rect, *color, *radius, *std_dev),
+        }
+    }
+
+    fn pop_layer(&mut self) {
+        if self.groups.len() <= 1 {
+            return;
+        }
+        let group = self.groups.pop().unwrap();
+        let clip = group
+            .clip_id
+            .as_ref()
+            .map(|id| format!(r#" clip-path="url(#{id})""#))
+            .unwrap_or_default();
+        let opacity = opacity_attr("opacity", group.alpha);
+
+        if group.is_knockout() {
+            // Erase the content of the layer from everything drawn before it in the parent
+            let mask_id = self.next_id("m");
+            let _ = write!(
+                self.defs,
+                r#"<mask id="{mask_id}" maskUnits="userSpaceOnUse" x="0" y="0" width="{w}" height="{h}"><rect width="{w}" height="{h}" fill="white"/><g{clip}{opacity}>{content}</g></mask>"#,
+                w = num(self.width),
+                h = num(self.height),
+                content = group.content,
+            );
+            let parent = self.content();
+            *parent = format!(r#"<g mask="url(#{mask_id})">{parent}</g>"#);
+            return;
+        }
+
+        let blend = match mix_blend_mode(group.blend.mix) {
+            Some(mode) => format!(r#" style="mix-blend-mode:{mode}""#),
+            None => String::new(),
+        };
+        let _ = write!(
+            self.content(),
+            "<g{clip}{opacity}{blend}>{}</g>",
+            group.content
+        );
+    }
+
+    fn clip_path(&mut self, transform: Affine, path: &BezPath) -> String {
+        let id = self.next_id("c");
+        let _ = write!(
+            self.defs,
+            r#"<clipPath id="{id}" clipPathUnits="userSpaceOnUse"><path d="{}" transform="{}"/></clipPath>"#,
+            path.to_svg(),
+            matrix(transform),
+        );
+        id
+    }
+
+    fn knockout_brush(&self, brush: &Brush) -> Brush {
+        if !self.in_knockout() {
+            return brush.clone();
+        }
+        let alpha = match brush {
+            Brush::Image(image) => image.sampler.alpha,
+            _ => brush.average_color().components[3],
+        };
+        Brush::Solid(Color::new([0.0, 0.0, 0.0, alpha]))
+    }
+
+    /// The attributes which set the fill or stroke of an element to a (non-image) brush
+    fn paint(
+        &mut self,
+        attr: &str,
+        brush: &Brush,
+        brush_transform: Option<Affine>,
+        alpha: f32,
+    ) -> String {
+        if let Brush::Gradient(gradient) = brush {
+            if let Some(id) = self.gradient(gradient, brush_transform) {
+                return format!(
+                    r#" {attr}="url(#{id})"{}"#,
+                    opacity_attr(&format!("{attr}-opacity"), alpha)
+                );
+            }
+        }
+
+        let color = brush.average_color();
+        let alpha = color.components[3] * alpha;
+        format!(
+            r#" {attr}="{}"{}"#,
+            rgb(color),
+            opacity_attr(&format!("{attr}-opacity"), alpha)
+        )
+    }
+
+    fn gradient(&mut self, gradient: &Gradient, brush_transform: Option<Affine>) -> Option<String> {
+        let id = self.next_id("g");
+        let transform = brush_transform
+            .map(|t| format!(r#" gradientTransform="{}""#, matrix(t)))
+            .unwrap_or_default();
+        let spread = match gradient.extend {
+            Extend::Pad => "pad",
+            Extend::Repeat => "repeat",
+            Extend::Reflect => "reflect",
+        };
+        let common = format!(
+            r#"id="{id}" gradientUnits="userSpaceOnUse" spreadMethod="{spread}"{transform}"#
+        );
+
+        let mut stops = String::new();
+        for stop in gradient.stops.iter() {
+            let color = stop.color.to_alpha_color::<Srgb>();
+            let _ = write!(
+                stops,
+                r#"<stop offset="{}" stop-color="{}"{}/>"#,
+                num(stop.offset as f64),
+                rgb(color),
+                opacity_attr("stop-opacity", color.components[3]),
+            );
+        }
+
+        match &gradient.kind {
+            GradientKind::Linear(pos) => {
+                let _ = write!(
+                    self.defs,
+                    r#"<linearGradient {common} x1="{}" y1="{}" x2="{}" y2="{}">{stops}</linearGradient>"#,
+                    num(pos.start.x),
+                    num(pos.start.y),
+                    num(pos.end.x),
+                    num(pos.end.y),
+                );
+            }
+            GradientKind::Radial(pos) => {
+                let _ = write!(
+                    self.defs,
+                    r#"<radialGradient {common} cx="{}" cy="{}" r="{}" fx="{}" fy="{}" fr="{}">{stops}</radialGradient>"#,
+                    num(pos.end_center.x),
+                    num(pos.end_center.y),
+                    num(pos.end_radius as f64),
+                    num(pos.start_center.x),
+                    num(pos.start_center.y),
+                    num(pos.start_radius as f64),
+                );
+            }
+            // SVG has no conic gradients
+            GradientKind::Sweep(_) => return None,
+        }
+        Some(id)
+    }
+
+    fn fill(
+        &mut self,
+        fill: Fill,
+        transform: Affine,
+        brush: &Brush,
+        brush_transform: Option<Affine>,
+        path: &BezPath,
+    ) {
+        let fill_rule = match fill {
+            Fill::NonZero => "",
+            Fill::EvenOdd => r#" fill-rule="evenodd""#,
+        };
+
+        let brush = self.knockout_brush(brush);
+        if let Brush::Image(image) = &brush {
+            self.fill_image(fill_rule, transform, image, brush_transform, path);
+            return;
+        }
+
+        let paint = self.paint("fill", &brush, brush_transform, 1.0);
+        let _ = write!(
+            self.content(),
+            r#"<path d="{}" transform="{}"{paint}{fill_rule}/>"#,
+            path.to_svg(),
+            matrix(transform),
+        );
+    }
+
+    fn fill_image(
+        &mut self,
+        fill_rule: &str,
+        transform: Affine,
+        image: &ImageBrush,
+        brush_transform: Option<Affine>,
+        path: &BezPath,
+    ) {
+        let data = &image.image;
+        let href = self
+            .images
+            .entry(data.data.id())
+            .or_insert_with(|| png_data_uri(data))
+            .clone();
+        let Some(href) = href else {
+            return;
+        };
+
+        let image_rect = Rect::new(0.0, 0.0, data.width as f64, data.height as f64);
+        let needs_clip = brush_transform.is_some() || path.bounding_box() != image_rect;
+        let clip = if needs_clip {
+            let id = self.next_id("c");
+            let _ = write!(
+                self.defs,
+                r#"<clipPath id="{id}" clipPathUnits="userSpaceOnUse"><path d="{}" transform="{}"{}/></clipPath>"#,
+                path.to_svg(),
+                matrix(transform),
+                fill_rule.replace("fill-rule", "clip-rule"),
+            );
+            format!(r#" clip-path="url(#{id})""#)
+        } else {
+            String::new()
+        };
+
+        let rendering = match image.sampler.quality {
+            peniko::ImageQuality::Low => r#" style="image-rendering:pixelated""#,
+            _ => "",
+        };
+        let _ = write!(
+            self.content(),
+            r#"<g{clip}><image width="{}" height="{}" preserveAspectRatio="none" transform="{}" href="{href}"{}{rendering}/></g>"#,
+            data.width,
+            data.height,
+            matrix(transform * brush_transform.unwrap_or(Affine::IDENTITY)),
+            opacity_attr("opacity", image.sampler.alpha),
+        );
+    }
+
+    fn glyphs(&mut self, run: &GlyphRun) {
+        let brush = self.knockout_brush(&run.brush);
+
+        // Stroked glyphs are drawn as paths (as a stroke would be scaled with a glyph definition)
+        if let GlyphStyle::Stroke(stroke) = &run.style {
+            let paint = self.paint("stroke", &brush, None, run.brush_alpha);
+            let attrs = stroke_attrs(stroke);
+            let mut paths = String::new();
+            for glyph in &run.glyphs {
+                let Some(outline) =
+                    glyph_outline(&run.font, &run.normalized_coords, glyph.id, run.font_size)
+                else {
+                    continue;
+                };
+                let outline = run.glyph_outline_transform(glyph) * outline;
+                let _ = write!(paths, r#"<path d="{}"/>"#, outline.to_svg());
+            }
+            let _ = write!(
+                self.content(),
+                r#"<g transform="{}" fill="none"{paint}{attrs}>{paths}</g>"#,
+                matrix(run.transform),
+            );
+            return;
+        }
+
+        let paint = self.paint("fill", &brush, None, run.brush_alpha);
+        let scale = (run.font_size / GLYPH_DEF_SIZE) as f64;
+        let mut uses = String::new();
+        for glyph in &run.glyphs {
+            let Some(id) = self.glyph_def(run, glyph.id) else {
+                continue;
+            };
+            let transform = run.glyph_outline_transform(glyph) * Affine::scale(scale);
+            let _ = write!(
+                uses,
+                r##"<use href="#{id}" xlink:href="#{id}" transform="{}"/>"##,
+                matrix(transform),
+            );
+        }
+        let _ = write!(
+            self.content(),
+            r#"<g transform="{}"{paint}>{uses}</g>"#,
+            matrix(run.transform),
+        );
+    }
+
+    /// The id of the definition of a glyph outline
+    fn glyph_def(&mut self, run: &GlyphRun, glyph_id: u32) -> Option<String> {
+        let key = GlyphKey {
+            font_id: run.font.data.id(),
+            font_index: run.font.index,
+            normalized_coords: run.normalized_coords.clone(),
+            glyph_id,
+        };
+        if let Some(id) = self.glyphs.get(&key) {
+            return id.clone();
+        }
+
+        let outline = glyph_outline(&run.font, &run.normalized_coords, glyph_id, GLYPH_DEF_SIZE);
+        let id = outline.map(|outline| {
+            let id = self.next_id("t");
+            let _ = write!(self.defs, r#"<path id="{id}" d="{}"/>"#, outline.to_svg());
+            id
+        });
+        self.glyphs.insert(key, id.clone());
+        id
+    }
+
+    fn box_shadow(
+        &mut self,
+        transform: Affine,
+        rect: Rect,
+        color: Color,
+        radius: f64,
+        std_dev: f64,
+    ) {
+        let color = if self.in_knockout() {
+            Color::new([0.0, 0.0, 0.0, color.components[3]])
+        } else {
+            color
+        };
+
+        let filter = if std_dev > 0.0 {
+            let id = self.next_id("f");
+            let region = rect.inflate(std_dev * 3.0, std_dev * 3.0);
+            let _ = write!(
+                self.defs,
+                r#"<filter id="{id}" filterUnits="userSpaceOnUse" x="{}" y="{}" width="{}" height="{}"><feGaussianBlur stdDeviation="{}"/></filter>"#,
+                num(region.x0),
+                num(region.y0),
+                num(region.width()),
+                num(region.height()),
+                num(std_dev),
+            );
+            format!(r#" filter="url(#{id})""#)
+        } else {
+            String::new()
+        };
+
+        let _ = write!(
+            self.content(),
+            r#"<rect x="{}" y="{}" width="{}" height="{}" rx="{r}" ry="{r}" transform="{}" fill="{}"{}{filter}/>"#,
+            num(rect.x0),
+            num(rect.y0),
+            num(rect.width()),
+            num(rect.height()),
+            matrix(transform),
+            rgb(color),
+            opacity_attr("fill-opacity", color.components[3]),
+            r = num(radius),
+        );
+    }
+}
+
+fn stroke_attrs(stroke: &kurbo::Stroke) -> String {
+    let mut attrs = format!(r#" stroke-width="{}""#, num(stroke.width));
+    let join = match stroke.join {
+        kurbo::Join::Bevel => "bevel",
+        kurbo::Join::Miter => "miter",
+        kurbo::Join::Round => "round",
+    };
+    let cap = match stroke.start_cap {
+        kurbo::Cap::Butt => "butt",
+        kurbo::Cap::Square => "square",
+        kurbo::Cap::Round => "round",
+    };
+    let _ = write!(
+        attrs,
+        r#" stroke-linejoin="{join}" stroke-linecap="{cap}" stroke-miterlimit="{}""#,
+        num(stroke.miter_limit)
+    );
+    if !stroke.dash_pattern.is_empty() {
+        let dashes: Vec<String> = stroke.dash_pattern.iter().map(|d| num(*d)).collect();
+        let _ = write!(
+            attrs,
+            r#" stroke-dasharray="{}" stroke-dashoffset="{}""#,
+            dashes.join(" "),
+            num(stroke.dash_offset)
+        );
+    }
+    attrs
+}
+
+#[allow(deprecated)]
+fn mix_blend_mode(mix: Mix) -> Option<&'static str> {
+    Some(match mix {
+        Mix::Multiply => "multiply",
+        Mix::Screen => "screen",
+        Mix::Overlay => "overlay",
+        Mix::Darken => "darken",
+        Mix::Lighten => "lighten",
+        Mix::ColorDodge => "color-dodge",
+        Mix::ColorBurn => "color-burn",
+        Mix::HardLight => "hard-light",
+        Mix::SoftLight => "soft-light",
+        Mix::Difference => "difference",
+        Mix::Exclusion => "exclusion",
+        Mix::Hue => "hue",
+        Mix::Saturation => "saturation",
+        Mix::Color => "color",
+        Mix::Luminosity => "luminosity",
+        _ => return None,
+    })
+}
+
+fn opacity_attr(name: &str, alpha: f32) -> String {
+    if alpha >= 1.0 {
+        String::new()
+    } else {
+        format!(r#" {name}="{}""#, num(alpha.max(0.0) as f64))
+    }
+}
+
+fn rgb(color: Color) -> String {
+    let [r, g, b, _] = color.to_rgba8().to_u8_array();
+    format!("#{r:02x}{g:02x}{b:02x}")
+}
+
+fn matrix(transform: Affine) -> String {
+    let [a, b, c, d, e, f] = transform.as_coeffs().map(num);
+    format!("matrix({a} {b} {c} {d} {e} {f})")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scene_to_svg() {
+        let svg = crate::scene::test_scene().to_svg();
+        assert_eq!(svg, include_str!("../snapshots/scene.svg"));
+    }
+}
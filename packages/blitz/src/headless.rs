@@ -0,0 +1,663 @@
+//! Render documents without a window
+//!
+//! A [`HeadlessDocument`] is built from HTML (or a URL), waits until the resources it depends on
+//! (stylesheets, images, fonts) have loaded, and then renders to an RGBA buffer or a PNG image
+//! using a CPU renderer. This makes it possible to snapshot documents in environments without a
+//! GPU or display (such as CI).
+//!
+//! ```no_run
+//! # async fn snapshot() {
+//! use blitz::headless::{ElementBox, HeadlessConfig, HeadlessDocument};
+//!
+//! let config = HeadlessConfig::default();
+//! let mut document = HeadlessDocument::from_html("<h1>Hello</h1>", None, config).await;
+//! document.render().save_png("hello.png").unwrap();
+//!
+//! // The full height of the document, or a single element, can also be captured
+//! document.render_full_page().save_png("page.png").unwrap();
+//! document
+//!     .render_selector("h1", ElementBox::Border)
+//!     .unwrap()
+//!     .save_png("heading.png")
+//!     .unwrap();
+//! # }
+//! ```
+
+use std::io::Write;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyrender::render_to_buffer;
+use anyrender_vello_cpu::VelloCpuImageRenderer;
+use blitz_dom::net::Resource;
+use blitz_dom::{BaseDocument, DEFAULT_CSS, DocumentConfig};
+use blitz_html::HtmlDocument;
+use blitz_paint::{paint_scene, paint_scene_region};
+use blitz_traits::shell::{ColorScheme, Viewport};
+
+#[cfg(feature = "net")]
+use blitz_traits::net::{DummyNetCallback, Request};
+#[cfg(feature = "net")]
+use tokio::sync::mpsc::UnboundedReceiver;
+
+/// Configuration for headless rendering
+#[derive(Debug, Clone)]
+pub struct HeadlessConfig {
+    /// The width of the viewport in CSS pixels
+    pub width: u32,
+    /// The height of the viewport in CSS pixels
+    pub height: u32,
+    /// The device pixel ratio. The rendered image is `width * scale` by `height * scale` pixels.
+    pub scale: f64,
+    /// The color scheme used for `prefers-color-scheme` media queries
+    pub color_scheme: ColorScheme,
+    /// Additional user agent stylesheets
+    pub stylesheets: Vec<String>,
+    /// The maximum time to wait for resources to load. Resources which have not loaded by then
+    /// are ignored.
+    pub resource_timeout: Duration,
+}
+
+impl Default for HeadlessConfig {
+    fn default() -> Self {
+        Self {
+            width: 800,
+            height: 600,
+            scale: 1.0,
+            color_scheme: ColorScheme::Light,
+            stylesheets: Vec::new(),
+            resource_timeout: Duration::from_secs(30),
+        }
+    }
+}
+
+/// An error which occurred while loading a headless document
+#[derive(Debug)]
+pub enum HeadlessError {
+    /// The URL could not be parsed
+    InvalidUrl(String),
+    /// The document could not be fetched
+    Fetch(String),
+    /// The document is not valid UTF-8
+    InvalidUtf8,
+    /// The selector could not be parsed
+    InvalidSelector(String),
+    /// No element matches the selector
+    NoMatchingElement(String),
+}
+
+impl std::fmt::Display for HeadlessError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            HeadlessError::InvalidUrl(url) => write!(f, "Invalid url: {url}"),
+            HeadlessError::Fetch(err) => write!(f, "Error fetching document: {err}"),
+            HeadlessError::InvalidUtf8 => write!(f, "Document is not valid UTF-8"),
+            HeadlessError::InvalidSelector(selector) => write!(f, "Invalid selector: {selector}"),
+            HeadlessError::NoMatchingElement(selector) => {
+                write!(f, "No element matches selector: {selector}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for HeadlessError {}
+
+/// An RGBA8 image rendered from a document
+#[derive(Debug, Clone)]
+pub struct RenderedImage {
+    /// The width of the image in pixels
+    pub width: u32,
+    /// The height of the image in pixels
+    pub height: u32,
+    /// The scale the image was rendered at
+    pub scale: f64,
+    /// The pixels of the image (in row-major order, 4 bytes per pixel)
+    pub data: Vec<u8>,
+}
+
+impl RenderedImage {
+    /// Encode the image as a PNG
+    pub fn write_png<W: Write>(&self, writer: W) -> Result<(), png::EncodingError> {
+        // Pixels-per-meter at 96 DPI (scaled by the device pixel ratio)
+        let ppm = (96.0 * self.scale * 39.3701) as u32;
+
+        let mut encoder = png::Encoder::new(writer, self.width, self.height);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder.set_pixel_dims(Some(png::PixelDimensions {
+            xppu: ppm,
+            yppu: ppm,
+            unit: png::Unit::Meter,
+        }));
+
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(&self.data)?;
+        writer.finish()
+    }
+
+    /// Encode the image as a PNG and write it to a file
+    pub fn save_png(&self, path: impl AsRef<Path>) -> Result<(), png::EncodingError> {
+        let file = std::fs::File::create(path)?;
+        self.write_png(std::io::BufWriter::new(file))
+    }
+}
+
+/// The box of an element which is captured when rendering a single element. These match the
+/// boxes of the box model shown by browser devtools.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ElementBox {
+    /// The content box
+    Content,
+    /// The content box and padding
+    Padding,
+    /// The content box, padding and border
+    #[default]
+    Border,
+    /// The content box, padding, border and margin
+    Margin,
+}
+
+/// The maximum width or height (in pixels) of a rendered image. Larger regions are truncated.
+pub const MAX_IMAGE_SIZE: u32 = 16384;
+
+#[cfg(feature = "net")]
+type HeadlessNetProvider = blitz_net::Provider<Resource>;
+
+/// A document which is rendered without a window
+pub struct HeadlessDocument {
+    document: HtmlDocument,
+    config: HeadlessConfig,
+    #[cfg(feature = "net")]
+    net_provider: Arc<HeadlessNetProvider>,
+    #[cfg(feature = "net")]
+    resources: UnboundedReceiver<(usize, Resource)>,
+}
+
+impl HeadlessDocument {
+    /// Parse a document from HTML and wait for its resources to load. Relative URLs are resolved
+    /// against `base_url`.
+    ///
+    /// Resources are fetched using the current tokio runtime.
+    pub async fn from_html(html: &str, base_url: Option<String>, config: HeadlessConfig) -> Self {
+        let mut document = Self::new(html, base_url, config);
+        document.settle().await;
+        document
+    }
+
+    /// Fetch a document from a URL and wait for its resources to load
+    ///
+    /// Resources are fetched using the current tokio runtime.
+    #[cfg(feature = "net")]
+    pub async fn from_url(url: &str, config: HeadlessConfig) -> Result<Self, HeadlessError> {
+        let url = url::Url::parse(url).map_err(|_| HeadlessError::InvalidUrl(url.to_string()))?;
+        let net_provider = blitz_net::Provider::<Resource>::new(Arc::new(DummyNetCallback));
+        let (url, bytes) = net_provider
+            .fetch_async(Request::get(url))
+            .await
+            .map_err(|err| HeadlessError::Fetch(format!("{err:?}")))?;
+        let html = std::str::from_utf8(&bytes).map_err(|_| HeadlessError::InvalidUtf8)?;
+
+        Ok(Self::from_html(html, Some(url), config).await)
+    }
+
+    fn new(html: &str, base_url: Option<String>, config: HeadlessConfig) -> Self {
+        #[cfg(feature = "net")]
+        let (resources, net_provider) = {
+            let (receiver, callback) = blitz_net::MpscCallback::new();
+            let net_provider = Arc::new(blitz_net::Provider::new(Arc::new(callback)));
+            (receiver, net_provider)
+        };
+        #[cfg(not(feature = "net"))]
+        let net_provider = Arc::new(blitz_traits::net::DummyNetProvider::default());
+
+        let document = HtmlDocument::from_html(
+            html,
+            DocumentConfig {
+                viewport: Some(viewport(&config)),
+                base_url,
+                ua_stylesheets: Some(config.stylesheets.clone()),
+                net_provider: Some(Arc::clone(&net_provider) as _),
+                ..Default::default()
+            },
+        );
+
+        Self {
+            document,
+            config,
+            #[cfg(feature = "net")]
+            net_provider,
+            #[cfg(feature = "net")]
+            resources,
+        }
+    }
+
+    /// Resolve the document and wait until all of the resources it has requested have loaded
+    /// (or until the resource timeout has elapsed).
+    ///
+    /// Resolving the document can request further resources (such as background images and web
+    /// fonts), so the document is resolved again after each batch of resources has loaded.
+    pub async fn settle(&mut self) {
+        self.document.as_mut().resolve(0.0);
+
+        #[cfg(feature = "net")]
+        {
+            let deadline = tokio::time::Instant::now() + self.config.resource_timeout;
+            while self.load_pending_resources(deadline).await {
+                self.document.as_mut().resolve(0.0);
+            }
+        }
+    }
+
+    /// Load the resources which are currently in flight. Returns whether any were loaded.
+    #[cfg(feature = "net")]
+    async fn load_pending_resources(&mut self, deadline: tokio::time::Instant) -> bool {
+        let mut loaded_any = false;
+        while !self.net_provider.is_empty() {
+            match tokio::time::timeout_at(deadline, self.resources.recv()).await {
+                Ok(Some((_, resource))) => {
+                    self.document.as_mut().load_resource(resource);
+                    loaded_any = true;
+                }
+                // The timeout elapsed (or the provider was dropped)
+                Ok(None) | Err(_) => break,
+            }
+        }
+
+        // Resources which were delivered just before the provider became empty
+        while let Ok((_, resource)) = self.resources.try_recv() {
+            self.document.as_mut().load_resource(resource);
+            loaded_any = true;
+        }
+
+        loaded_any
+    }
+
+    /// Change the viewport size, scale, color scheme or stylesheets of the document. Call
+    /// [`settle`](Self::settle) afterwards to load any resources the new stylesheets depend on.
+    pub fn set_config(&mut self, config: HeadlessConfig) {
+        let document = self.document.as_mut();
+
+        // The default stylesheet is always applied by the HTML document
+        let old_stylesheets = &self.config.stylesheets;
+        for css in old_stylesheets {
+            if !config.stylesheets.contains(css) && css != DEFAULT_CSS {
+                document.remove_user_agent_stylesheet(css);
+            }
+        }
+        for css in &config.stylesheets {
+            if !old_stylesheets.contains(css) && css != DEFAULT_CSS {
+                document.add_user_agent_stylesheet(css);
+            }
+        }
+
+        document.set_viewport(viewport(&config));
+        self.config = config;
+        self.document.as_mut().resolve(0.0);
+    }
+
+    /// The configuration of the document
+    pub fn config(&self) -> &HeadlessConfig {
+        &self.config
+    }
+
+    /// The underlying document
+    pub fn document(&self) -> &BaseDocument {
+        self.document.as_ref()
+    }
+
+    /// The underlying document (for example, to modify it before rendering). Call
+    /// [`settle`](Self::settle) after modifying it to load any new resources.
+    pub fn document_mut(&mut self) -> &mut BaseDocument {
+        self.document.as_mut()
+    }
+
+    /// Render the viewport of the document to an RGBA8 buffer
+    pub fn render(&mut self) -> RenderedImage {
+        self.document.as_mut().resolve(0.0);
+
+        let scale = self.config.scale;
+        let width = (self.config.width as f64 * scale).round() as u32;
+        let height = (self.config.height as f64 * scale).round() as u32;
+        let document = self.document.as_ref();
+        let data = render_to_buffer::<VelloCpuImageRenderer, _>(
+            |scene| paint_scene(scene, document, scale, width, height),
+            width,
+            height,
+        );
+
+        RenderedImage {
+            width,
+            height,
+            scale,
+            data,
+        }
+    }
+
+    /// Render the full scrollable area of the document (rather than just the viewport) to an
+    /// RGBA8 buffer
+    pub fn render_full_page(&mut self) -> RenderedImage {
+        self.document.as_mut().resolve(0.0);
+
+        // The scrollable overflow of the root element includes descendants which overflow it
+        let layout = &self.document.as_ref().root_element().final_layout;
+        let width = layout.location.x + layout.size.width.max(layout.content_size.width);
+        let height = layout.location.y + layout.size.height.max(layout.content_size.height);
+        let width = (width as f64).max(self.config.width as f64);
+        let height = (height as f64).max(self.config.height as f64);
+        self.render_region(0.0, 0.0, width, height)
+    }
+
+    /// Render the box of a single node to an RGBA8 buffer. Returns `None` if the node does not
+    /// exist.
+    ///
+    /// Anything painted within the box (including overlapping elements) is included.
+    pub fn render_node(
+        &mut self,
+        node_id: usize,
+        element_box: ElementBox,
+    ) -> Option<RenderedImage> {
+        self.document.as_mut().resolve(0.0);
+
+        let (x0, y0, x1, y1) = self.node_bounds(node_id, element_box)?;
+        Some(self.render_region(x0, y0, x1 - x0, y1 - y0))
+    }
+
+    /// Render the box of the first element matching a CSS selector to an RGBA8 buffer
+    pub fn render_selector(
+        &mut self,
+        selector: &str,
+        element_box: ElementBox,
+    ) -> Result<RenderedImage, HeadlessError> {
+        self.document.as_mut().resolve(0.0);
+
+        let node_id = self
+            .document
+            .as_ref()
+            .query_selector(selector)
+            .map_err(|_| HeadlessError::InvalidSelector(selector.to_string()))?
+            .ok_or_else(|| HeadlessError::NoMatchingElement(selector.to_string()))?;
+        self.render_node(node_id, element_box)
+            .ok_or_else(|| HeadlessError::NoMatchingElement(selector.to_string()))
+    }
+
+    /// Render a region of the document to an RGBA8 buffer. The region is specified in CSS pixels
+    /// relative to the top-left of the document (ignoring the scroll position of the viewport).
+    pub fn render_region(&mut self, x: f64, y: f64, width: f64, height: f64) -> RenderedImage {
+        self.document.as_mut().resolve(0.0);
+
+        let scale = self.config.scale;
+        let x = (x * scale).floor() / scale;
+        let y = (y * scale).floor() / scale;
+        let width = ((width * scale).ceil() as u32).clamp(1, MAX_IMAGE_SIZE);
+        let height = ((height * scale).ceil() as u32).clamp(1, MAX_IMAGE_SIZE);
+        let document = self.document.as_ref();
+        let data = render_to_buffer::<VelloCpuImageRenderer, _>(
+            |scene| paint_scene_region(scene, document, scale, x, y, width, height),
+            width,
+            height,
+        );
+
+        RenderedImage {
+            width,
+            height,
+            scale,
+            data,
+        }
+    }
+
+    /// The bounding box (`x0, y0, x1, y1`) of a box of a node in document coordinates (taking
+    /// the transforms of the node and its ancestors into account)
+    fn node_bounds(&self, node_id: usize, element_box: ElementBox) -> Option<(f64, f64, f64, f64)> {
+        let node = self.document.as_ref().get_node(node_id)?;
+        let layout = &node.final_layout;
+
+        let (mut x0, mut y0, mut x1, mut y1) = (0.0, 0.0, layout.size.width, layout.size.height);
+        let insets = match element_box {
+            ElementBox::Content => Some(layout.border + layout.padding),
+            ElementBox::Padding => Some(layout.border),
+            ElementBox::Border => None,
+            ElementBox::Margin => Some(layout.margin.map(|m| -m)),
+        };
+        if let Some(insets) = insets {
+            x0 += insets.left;
+            y0 += insets.top;
+            x1 -= insets.right;
+            y1 -= insets.bottom;
+        }
+
+        // `absolute_position` maps points in the (scrolled) content of the node
+        let (scroll_x, scroll_y) = (node.scroll_offset.x as f32, node.scroll_offset.y as f32);
+        let corners = [(x0, y0), (x1, y0), (x1, y1), (x0, y1)]
+            .map(|(x, y)| node.absolute_position(x + scroll_x, y + scroll_y));
+
+        let bounds = corners.iter().fold(
+            (
+                f64::INFINITY,
+                f64::INFINITY,
+                f64::NEG_INFINITY,
+                f64::NEG_INFINITY,
+            ),
+            |(x0, y0, x1, y1), point| {
+                let (x, y) = (point.x as f64, point.y as f64);
+                (x0.min(x), y0.min(y), x1.max(x), y1.max(y))
+            },
+        );
+        Some(bounds)
+    }
+}
+
+fn viewport(config: &HeadlessConfig) -> Viewport {
+    Viewport::new(
+        (config.width as f64 * config.scale).round() as u32,
+        (config.height as f64 * config.scale).round() as u32,
+        config.scale as f32,
+        config.color_scheme,
+    )
+}
+
+/// Render HTML to an image, blocking until its resources have loaded.
+///
+/// This creates its own tokio runtime, so it must not be called from within an async context
+/// (use [`HeadlessDocument::from_html`] there instead).
+pub fn render_html(html: &str, base_url: Option<String>, config: HeadlessConfig) -> RenderedImage {
+    block_on(async move {
+        let mut document = HeadlessDocument::from_html(html, base_url, config).await;
+        document.render()
+    })
+}
+
+/// Fetch a document from a URL and render it to an image, blocking until its resources have
+/// loaded.
+///
+/// This creates its own tokio runtime, so it must not be called from within an async context
+/// (use [`HeadlessDocument::from_url`] there instead).
+#[cfg(feature = "net")]
+pub fn render_url(url: &str, config: HeadlessConfig) -> Result<RenderedImage, HeadlessError> {
+    block_on(async move {
+        let mut document = HeadlessDocument::from_url(url, config).await?;
+        Ok(document.render())
+    })
+}
+
+#[cfg(feature = "net")]
+fn block_on<T>(future: impl Future<Output = T>) -> T {
+    let rt = tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+    rt.block_on(future)
+}
+
+#[cfg(not(feature = "net"))]
+fn block_on<T>(future: impl Future<Output = T>) -> T {
+    // Without networking the futures never wait on anything, so they complete on the first poll
+    let waker = std::task::Waker::noop();
+    let mut cx = std::task::Context::from_waker(waker);
+    let mut future = std::pin::pin!(future);
+    match future.as_mut().poll(&mut cx) {
+        std::task::Poll::Ready(value) => value,
+        std::task::Poll::Pending => {
+            unreachable!("Headless rendering without networking never waits")
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The RGBA8 color of a pixel of an image
+    fn pixel(image: &RenderedImage, x: u32, y: u32) -> [u8; 4] {
+        let offset = ((y * image.width + x) * 4) as usize;
+        image.data[offset..offset + 4].try_into().unwrap()
+    }
+
+    #[test]
+    fn test_render_html() {
+        let config = HeadlessConfig {
+            width: 20,
+            height: 10,
+            ..Default::default()
+        };
+        let image = render_html(
+            r#"<body style="margin: 0; background: rgb(255, 0, 0)"></body>"#,
+            None,
+            config,
+        );
+
+        assert_eq!((image.width, image.height), (20, 10));
+        assert_eq!(image.data.len(), 20 * 10 * 4);
+        assert_eq!(pixel(&image, 0, 0), [255, 0, 0, 255]);
+        assert_eq!(pixel(&image, 19, 9), [255, 0, 0, 255]);
+    }
+
+    #[test]
+    fn test_render_scale() {
+        let config = HeadlessConfig {
+            width: 20,
+            height: 10,
+            scale: 2.0,
+            ..Default::default()
+        };
+        let image = render_html(
+            r#"<body style="margin: 0; background: rgb(255, 0, 0)">
+            <div style="margin-left: 10px; width: 10px; height: 10px; background: rgb(0, 0, 255)"></div>
+        </body>"#,
+            None,
+            config,
+        );
+
+        // The image is scaled, but the layout is in CSS pixels
+        assert_eq!((image.width, image.height), (40, 20));
+        assert_eq!(image.scale, 2.0);
+        assert_eq!(pixel(&image, 19, 10), [255, 0, 0, 255]);
+        assert_eq!(pixel(&image, 20, 10), [0, 0, 255, 255]);
+        assert_eq!(pixel(&image, 39, 19), [0, 0, 255, 255]);
+    }
+
+    #[test]
+    fn test_set_config() {
+        const GREEN_ROOT: &str = "html { background: rgb(0, 255, 0) }";
+
+        block_on(async {
+            let config = HeadlessConfig {
+                width: 20,
+                height: 10,
+                ..Default::default()
+            };
+            let html = r#"<style>
+            body { margin: 0; background: rgb(255, 0, 0) }
+            @media (min-width: 30px) { body { background: rgb(0, 0, 255) } }
+        </style>"#;
+            let mut document = HeadlessDocument::from_html(html, None, config.clone()).await;
+            assert_eq!(pixel(&document.render(), 0, 0), [255, 0, 0, 255]);
+
+            // Media queries are evaluated against the new viewport (in CSS pixels)
+            let config = HeadlessConfig {
+                width: 40,
+                scale: 2.0,
+                ..config
+            };
+            document.set_config(config.clone());
+            let image = document.render();
+            assert_eq!((image.width, image.height), (80, 20));
+            assert_eq!(pixel(&image, 0, 0), [0, 0, 255, 255]);
+
+            // Stylesheets are added and removed
+            document.set_config(HeadlessConfig {
+                stylesheets: vec![GREEN_ROOT.to_string()],
+                ..config.clone()
+            });
+            assert_eq!(pixel(&document.render(), 0, 0), [0, 255, 0, 255]);
+            document.set_config(config);
+            assert_eq!(pixel(&document.render(), 0, 0), [0, 0, 255, 255]);
+        });
+    }
+
+    #[test]
+    fn test_render_full_page() {
+        block_on(async {
+            let html = r#"<body style="margin: 0; height: 100px">
+            <div style="width: 1200px; height: 1000px"></div>
+        </body>"#;
+            let mut document =
+                HeadlessDocument::from_html(html, None, HeadlessConfig::default()).await;
+
+            // The page includes content which overflows the root element
+            let image = document.render_full_page();
+            assert_eq!((image.width, image.height), (1200, 1000));
+        });
+    }
+
+    #[test]
+    fn test_element_boxes() {
+        block_on(async {
+            let html = r#"<body style="margin: 0">
+            <div id="box" style="margin: 5px; border: 3px solid; padding: 2px; width: 10px; height: 10px"></div>
+            <div id="rotated" style="margin-left: 100px; width: 20px; height: 10px; transform: rotate(90deg)"></div>
+        </body>"#;
+            let mut document =
+                HeadlessDocument::from_html(html, None, HeadlessConfig::default()).await;
+            let id = |document: &HeadlessDocument, selector| {
+                document
+                    .document()
+                    .query_selector(selector)
+                    .unwrap()
+                    .unwrap()
+            };
+            let bounds = |document: &HeadlessDocument, node_id, element_box| {
+                let (x0, y0, x1, y1) = document.node_bounds(node_id, element_box).unwrap();
+                [x0, y0, x1, y1].map(|value| value.round())
+            };
+
+            let box_id = id(&document, "#box");
+            assert_eq!(
+                bounds(&document, box_id, ElementBox::Content),
+                [10.0, 10.0, 20.0, 20.0]
+            );
+            assert_eq!(
+                bounds(&document, box_id, ElementBox::Padding),
+                [8.0, 8.0, 22.0, 22.0]
+            );
+            assert_eq!(
+                bounds(&document, box_id, ElementBox::Border),
+                [5.0, 5.0, 25.0, 25.0]
+            );
+            assert_eq!(
+                bounds(&document, box_id, ElementBox::Margin),
+                [0.0, 0.0, 30.0, 30.0]
+            );
+
+            let image = document.render_node(box_id, ElementBox::Margin).unwrap();
+            assert_eq!((image.width, image.height), (30, 30));
+
+            // The bounds of transformed elements enclose the transformed box
+            let rotated_id = id(&document, "#rotated");
+            assert_eq!(
+                bounds(&document, rotated_id, ElementBox::Border),
+                [105.0, 25.0, 115.0, 45.0]
+            );
+        });
+    }
+}
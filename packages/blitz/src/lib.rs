@@ -23,6 +23,9 @@ use blitz_shell::{
 };
 use blitz_traits::net::{NetProvider, Request};
 
+#[cfg(feature = "headless")]
+pub mod headless;
+
 #[doc(inline)]
 /// Re-export of [`blitz_dom`].
 pub use blitz_dom as dom;
@@ -41,6 +44,10 @@ pub use blitz_shell as shell;
 #[doc(inline)]
 /// Re-export of [`blitz_traits`](https://docs.rs/blitz-traits). Base types and traits for interoperability between modules
 pub use blitz_traits as traits;
+#[cfg(feature = "vector")]
+#[doc(inline)]
+/// Re-export of [`blitz_vector`]. SVG and PDF export
+pub use blitz_vector as vector;
 
 #[cfg(feature = "net")]
 pub fn launch_url(url: &str) {
@@ -175,10 +175,12 @@ impl EventHandler for DioxusEventHandler<'_> {
                 Some(wrap_event_data(BlitzKeyboardData(kevent.clone())))
             }
 
-            DomEventData::Input(data) => Some(wrap_event_data(NativeFormData {
-                value: data.value.clone(),
-                values: HashMap::new(),
-            })),
+            DomEventData::Input(data) | DomEventData::Change(data) => {
+                Some(wrap_event_data(NativeFormData {
+                    value: data.value.clone(),
+                    values: HashMap::new(),
+                }))
+            }
 
             // TODO: Implement IME handling
             DomEventData::Ime(_) => None,
@@ -3,10 +3,12 @@
 /// Private module of type aliases so we can refer to stylo types with nicer names
 pub(crate) mod stylo {
     pub(crate) use style::Atom;
+    pub(crate) use style::computed_values::direction::T as Direction;
     pub(crate) use style::properties::ComputedValues;
     pub(crate) use style::properties::generated::longhands::box_sizing::computed_value::T as BoxSizing;
     pub(crate) use style::properties::longhands::aspect_ratio::computed_value::T as AspectRatio;
     pub(crate) use style::properties::longhands::position::computed_value::T as Position;
+    pub(crate) use style::properties::style_structs::Position as PositionStruct;
     pub(crate) use style::values::computed::length_percentage::CalcLengthPercentage;
     pub(crate) use style::values::computed::length_percentage::Unpacked as UnpackedLengthPercentage;
     pub(crate) use style::values::computed::{LengthPercentage, Percentage};
@@ -39,6 +41,8 @@ pub(crate) mod stylo {
 
     #[cfg(feature = "block")]
     pub(crate) use style::values::computed::text::TextAlign;
+    #[cfg(feature = "float")]
+    pub(crate) use style::values::computed::{Clear, Float};
     #[cfg(feature = "grid")]
     pub(crate) use style::{
         computed_values::grid_auto_flow::T as GridAutoFlow,
@@ -203,6 +207,30 @@ pub fn box_sizing(input: stylo::BoxSizing) -> taffy::BoxSizing {
     }
 }
 
+#[inline]
+pub fn insets(
+    position: stylo::Position,
+    position_styles: &stylo::PositionStruct,
+) -> taffy::Rect<taffy::LengthPercentageAuto> {
+    match position {
+        stylo::Position::Sticky => taffy::Rect::auto(),
+        _ => taffy::Rect {
+            left: self::inset(&position_styles.left),
+            right: self::inset(&position_styles.right),
+            top: self::inset(&position_styles.top),
+            bottom: self::inset(&position_styles.bottom),
+        },
+    }
+}
+
+#[inline]
+pub fn direction(input: stylo::Direction) -> taffy::Direction {
+    match input {
+        stylo::Direction::Ltr => taffy::Direction::Ltr,
+        stylo::Direction::Rtl => taffy::Direction::Rtl,
+    }
+}
+
 #[inline]
 pub fn position(input: stylo::Position) -> taffy::Position {
     match input {
@@ -210,13 +238,46 @@ pub fn position(input: stylo::Position) -> taffy::Position {
         stylo::Position::Relative => taffy::Position::Relative,
         stylo::Position::Static => taffy::Position::Relative,
 
-        // TODO: support position:fixed and sticky
+        // TODO: support position:fixed
+        //
+        // Sticky boxes are laid out as relatively positioned boxes with no offset. The sticky offset
+        // depends on scroll position, and so is computed and applied by the embedder after layout
+        // (see `insets`).
         stylo::Position::Absolute => taffy::Position::Absolute,
         stylo::Position::Fixed => taffy::Position::Absolute,
         stylo::Position::Sticky => taffy::Position::Relative,
     }
 }
 
+#[inline]
+#[cfg(feature = "float")]
+pub fn float(input: stylo::Float) -> taffy::Float {
+    match input {
+        stylo::Float::Left => taffy::Float::Left,
+        stylo::Float::Right => taffy::Float::Right,
+        stylo::Float::None => taffy::Float::None,
+
+        // Logical values are resolved to physical ones at computed value time
+        stylo::Float::InlineStart => taffy::Float::Left,
+        stylo::Float::InlineEnd => taffy::Float::Right,
+    }
+}
+
+#[inline]
+#[cfg(feature = "float")]
+pub fn clear(input: stylo::Clear) -> taffy::Clear {
+    match input {
+        stylo::Clear::Left => taffy::Clear::Left,
+        stylo::Clear::Right => taffy::Clear::Right,
+        stylo::Clear::Both => taffy::Clear::Both,
+        stylo::Clear::None => taffy::Clear::None,
+
+        // Logical values are resolved to physical ones at computed value time
+        stylo::Clear::InlineStart => taffy::Clear::Left,
+        stylo::Clear::InlineEnd => taffy::Clear::Right,
+    }
+}
+
 #[inline]
 pub fn overflow(input: stylo::Overflow) -> taffy::Overflow {
     match input {
@@ -549,6 +610,7 @@ pub fn to_taffy_style(style: &stylo::ComputedValues) -> taffy::Style<Atom> {
         item_is_table: display.inside() == stylo::DisplayInside::Table,
         item_is_replaced: false,
         position: self::position(style.clone_position()),
+        direction: self::direction(style.clone_direction()),
         overflow: taffy::Point {
             x: self::overflow(style.clone_overflow_x()),
             y: self::overflow(style.clone_overflow_y()),
@@ -569,12 +631,7 @@ pub fn to_taffy_style(style: &stylo::ComputedValues) -> taffy::Style<Atom> {
         },
         aspect_ratio: self::aspect_ratio(pos.aspect_ratio),
 
-        inset: taffy::Rect {
-            left: self::inset(&pos.left),
-            right: self::inset(&pos.right),
-            top: self::inset(&pos.top),
-            bottom: self::inset(&pos.bottom),
-        },
+        inset: self::insets(style.clone_position(), pos),
         margin: taffy::Rect {
             left: self::margin(&margin.margin_left),
             right: self::margin(&margin.margin_right),
@@ -616,6 +673,10 @@ pub fn to_taffy_style(style: &stylo::ComputedValues) -> taffy::Style<Atom> {
         justify_self: self::item_alignment((pos.justify_self.0).0),
         #[cfg(feature = "block")]
         text_align: self::text_align(style.clone_text_align()),
+        #[cfg(feature = "float")]
+        float: self::float(style.clone_float()),
+        #[cfg(feature = "float")]
+        clear: self::clear(style.clone_clear()),
 
         // Flexbox
         #[cfg(feature = "flexbox")]
@@ -669,3 +730,44 @@ pub fn to_taffy_style(style: &stylo::ComputedValues) -> taffy::Style<Atom> {
         },
     }
 }
+
+/// Convert the physical properties of a box in a vertical writing mode into the coordinate space in
+/// which vertical flows are laid out: the inline axis (top-to-bottom) becomes the horizontal axis and
+/// the block axis becomes the vertical axis. Block-start is the right side for `vertical-rl` and the
+/// left side for `vertical-lr`.
+///
+/// Properties which are already relative to the flow (such as flex direction, grid tracks and gaps)
+/// are unaffected.
+pub fn transpose_for_vertical_writing_mode(style: &mut taffy::Style<Atom>, is_vertical_lr: bool) {
+    fn transpose_rect<T: Copy>(rect: taffy::Rect<T>, is_vertical_lr: bool) -> taffy::Rect<T> {
+        let (block_start, block_end) = match is_vertical_lr {
+            true => (rect.left, rect.right),
+            false => (rect.right, rect.left),
+        };
+        taffy::Rect {
+            left: rect.top,
+            right: rect.bottom,
+            top: block_start,
+            bottom: block_end,
+        }
+    }
+    fn transpose_size<T>(size: taffy::Size<T>) -> taffy::Size<T> {
+        taffy::Size {
+            width: size.height,
+            height: size.width,
+        }
+    }
+
+    style.size = transpose_size(style.size);
+    style.min_size = transpose_size(style.min_size);
+    style.max_size = transpose_size(style.max_size);
+    style.aspect_ratio = style.aspect_ratio.map(|ratio| 1.0 / ratio);
+    style.overflow = taffy::Point {
+        x: style.overflow.y,
+        y: style.overflow.x,
+    };
+    style.inset = transpose_rect(style.inset, is_vertical_lr);
+    style.margin = transpose_rect(style.margin, is_vertical_lr);
+    style.padding = transpose_rect(style.padding, is_vertical_lr);
+    style.border = transpose_rect(style.border, is_vertical_lr);
+}
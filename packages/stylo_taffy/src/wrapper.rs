@@ -50,6 +50,11 @@ impl<T: Deref<Target = ComputedValues>> taffy::CoreStyle for TaffyStyloStyle<T>
         convert::box_sizing(self.0.get_position().box_sizing)
     }
 
+    #[inline]
+    fn direction(&self) -> taffy::Direction {
+        convert::direction(self.0.clone_direction())
+    }
+
     #[inline]
     fn overflow(&self) -> taffy::Point<taffy::Overflow> {
         let box_styles = self.0.get_box();
@@ -71,13 +76,7 @@ impl<T: Deref<Target = ComputedValues>> taffy::CoreStyle for TaffyStyloStyle<T>
 
     #[inline]
     fn inset(&self) -> taffy::Rect<taffy::LengthPercentageAuto> {
-        let position_styles = self.0.get_position();
-        taffy::Rect {
-            left: convert::inset(&position_styles.left),
-            right: convert::inset(&position_styles.right),
-            top: convert::inset(&position_styles.top),
-            bottom: convert::inset(&position_styles.bottom),
-        }
+        convert::insets(self.0.get_box().position, self.0.get_position())
     }
 
     #[inline]
@@ -166,6 +165,18 @@ impl<T: Deref<Target = ComputedValues>> taffy::BlockItemStyle for TaffyStyloStyl
     fn is_table(&self) -> bool {
         convert::is_table(self.0.clone_display())
     }
+
+    #[cfg(feature = "float")]
+    #[inline]
+    fn float(&self) -> taffy::Float {
+        convert::float(self.0.clone_float())
+    }
+
+    #[cfg(feature = "float")]
+    #[inline]
+    fn clear(&self) -> taffy::Clear {
+        convert::clear(self.0.clone_clear())
+    }
 }
 
 // FlexboxContainerStyle impl